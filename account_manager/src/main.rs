@@ -1,9 +1,14 @@
-use bls::Keypair;
-use clap::{App, Arg, SubCommand};
-use slog::{crit, debug, info, o, Drain};
+use bip39::{Language, Mnemonic, MnemonicType, Seed};
+use bls::{Keypair, PublicKey};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use eth2_key_derivation::{signing_key_path, DerivedKey};
+use slog::{crit, debug, info, o, warn, Drain};
 use std::fs;
-use std::path::PathBuf;
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
 use types::test_utils::generate_deterministic_keypair;
+use validator_client::datadir;
+use validator_client::keystore::{Kdf, Keystore};
 use validator_client::Config as ValidatorClientConfig;
 
 pub const DEFAULT_DATA_DIR: &str = ".lighthouse-validator";
@@ -36,6 +41,22 @@ fn main() {
                 .help("Data directory for keys and databases.")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("eth2-spec")
+                .long("eth2-spec")
+                .short("e")
+                .value_name("TOML_FILE")
+                .help("Path to the Ethereum 2.0 specifications file of the validator client.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("default-spec")
+                .long("default-spec")
+                .value_name("TITLE")
+                .help("The default eth2 spec of the validator client, if it is started with --default-spec.")
+                .takes_value(true)
+                .possible_values(&["mainnet", "minimal", "interop"]),
+        )
         .subcommand(
             SubCommand::with_name("generate")
                 .about("Generates a new validator private key")
@@ -66,6 +87,27 @@ fn main() {
                         .default_value("1"),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("new_mnemonic")
+                .about("Generates a new mnemonic and the encrypted keystores of validator keys derived from it")
+                .version("0.0.1")
+                .author("Sigma Prime <contact@sigmaprime.io>")
+                .args(&derivation_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("recover")
+                .about("Regenerates the encrypted keystores of validator keys derived from an existing mnemonic")
+                .version("0.0.1")
+                .author("Sigma Prime <contact@sigmaprime.io>")
+                .args(&derivation_args())
+                .arg(
+                    Arg::with_name("mnemonic file")
+                        .long("mnemonic-file")
+                        .value_name("FILE")
+                        .help("A file containing the mnemonic. If not supplied, the mnemonic is read from stdin.")
+                        .takes_value(true),
+                ),
+        )
         .get_matches();
 
     let data_dir = match matches
@@ -106,6 +148,27 @@ fn main() {
         return;
     };
 
+    // Keys are saved in the directory of the network run by the validator client, selected as
+    // the validator client selects it.
+    let eth2_config = match datadir::select_eth2_config(
+        &data_dir,
+        matches.value_of("eth2-spec").map(Path::new),
+        matches.value_of("default-spec"),
+    ) {
+        Ok(selected) => selected.eth2_config,
+        Err(e) => {
+            crit!(log, "Failed to read the Eth2Config from file"; "error" => e);
+            return;
+        }
+    };
+    match datadir::open_network_dir(&data_dir, &eth2_config) {
+        Ok(network_dir) => client_config.data_dir = network_dir.path,
+        Err(e) => {
+            crit!(log, "Unable to open the data directory of the network"; "error" => e.to_string());
+            return;
+        }
+    }
+
     // Log configuration
     info!(log, "";
           "data_dir" => &client_config.data_dir.to_str());
@@ -125,12 +188,143 @@ fn main() {
                 }
             }
         }
+        ("new_mnemonic", Some(m)) => {
+            let mnemonic = Mnemonic::new(MnemonicType::Words24, Language::English);
+
+            warn!(
+                log,
+                "Write down the following mnemonic and store it securely. It is the only means of recovering the generated keys."
+            );
+            println!("\n{}\n", mnemonic.phrase());
+
+            if let Err(e) = derive_keystores(&mnemonic, m, &client_config, &log) {
+                crit!(log, "Failed to generate keystores"; "error" => e);
+            }
+        }
+        ("recover", Some(m)) => {
+            let recovered = read_mnemonic(m)
+                .and_then(|mnemonic| derive_keystores(&mnemonic, m, &client_config, &log));
+
+            if let Err(e) = recovered {
+                crit!(log, "Failed to recover keystores"; "error" => e);
+            }
+        }
         _ => panic!(
             "The account manager must be run with a subcommand. See help for more information."
         ),
     }
 }
 
+/// The arguments of the subcommands which derive validator keys from a mnemonic.
+fn derivation_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("first index")
+            .long("first-index")
+            .short("i")
+            .value_name("index")
+            .help("The index of the first validator, for which a key is derived")
+            .takes_value(true)
+            .default_value("0"),
+        Arg::with_name("validator count")
+            .long("validator_count")
+            .short("n")
+            .value_name("validator_count")
+            .help("The number of validators, for which keys are derived")
+            .takes_value(true)
+            .default_value("1"),
+        Arg::with_name("password file")
+            .long("password-file")
+            .value_name("FILE")
+            .help("A file containing the password with which the keystores are encrypted.")
+            .takes_value(true)
+            .required(true),
+    ]
+}
+
+/// Reads the mnemonic from the `--mnemonic-file`, or from stdin if it is not supplied.
+fn read_mnemonic(matches: &ArgMatches) -> Result<Mnemonic, String> {
+    let phrase = match matches.value_of("mnemonic file") {
+        Some(path) => fs::read_to_string(path)
+            .map_err(|e| format!("Unable to read mnemonic file {}: {}", path, e))?,
+        None => {
+            eprintln!("Enter the mnemonic:");
+            let mut phrase = String::new();
+            io::stdin()
+                .lock()
+                .read_line(&mut phrase)
+                .map_err(|e| format!("Unable to read mnemonic: {}", e))?;
+            phrase
+        }
+    };
+
+    Mnemonic::from_phrase(phrase.trim(), Language::English)
+        .map_err(|e| format!("Invalid mnemonic: {}", e))
+}
+
+/// Derives the signing keys of validators `i..i + n` from `mnemonic`, at their EIP-2334 paths,
+/// and saves them as keystores encrypted with the password in the `--password-file`.
+fn derive_keystores(
+    mnemonic: &Mnemonic,
+    matches: &ArgMatches,
+    config: &ValidatorClientConfig,
+    log: &slog::Logger,
+) -> Result<(), String> {
+    let parse = |name| -> Result<u32, String> {
+        matches
+            .value_of(name)
+            .ok_or_else(|| format!("{} is required", name))?
+            .parse()
+            .map_err(|_| format!("Invalid {}", name))
+    };
+    let first_index = parse("first index")?;
+    let count = parse("validator count")?;
+    let last_index = first_index
+        .checked_add(count)
+        .ok_or_else(|| "Validator indices overflow".to_string())?;
+
+    let password = ValidatorClientConfig {
+        keystore_password_file: matches.value_of("password file").map(PathBuf::from),
+        ..config.clone()
+    }
+    .keystore_password()
+    .map_err(|e| format!("Unable to read password file: {}", e))?
+    .ok_or_else(|| "--password-file is required".to_string())?;
+
+    let seed = Seed::new(mnemonic, "");
+
+    for index in first_index..last_index {
+        let path = signing_key_path(index);
+        let sk = DerivedKey::from_path(seed.as_bytes(), &path)
+            .map_err(|e| format!("Unable to derive key: {:?}", e))?
+            .secret_key();
+        let keypair = Keypair {
+            pk: PublicKey::from_secret_key(&sk),
+            sk,
+        };
+
+        let keystore = Keystore::encrypt(&keypair, &password, &path, Kdf::default())
+            .map_err(|e| format!("Unable to encrypt keystore: {:?}", e))?;
+        let keystore_path = config
+            .save_keystore(&keystore)
+            .map_err(|e| format!("Unable to save keystore: {}", e))?;
+
+        info!(
+            log,
+            "Keystore generated";
+            "path" => keystore_path.to_string_lossy().into_owned(),
+            "derivation_path" => path,
+            "validator" => keypair.identifier(),
+        );
+    }
+
+    info!(
+        log,
+        "Run the validator client with --keystore-password-file to load the keystores"
+    );
+
+    Ok(())
+}
+
 fn generate_random(config: &ValidatorClientConfig, log: &slog::Logger) {
     save_key(&Keypair::random(), config, log)
 }
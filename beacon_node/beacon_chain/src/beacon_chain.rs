@@ -1,28 +1,43 @@
 use crate::checkpoint::CheckPoint;
 use crate::errors::{BeaconChainError as Error, BlockProductionError};
 use crate::fork_choice::{Error as ForkChoiceError, ForkChoice};
+use crate::head_events::{HeadEvent, HeadSubscribers};
 use crate::iter::{ReverseBlockRootIterator, ReverseStateRootIterator};
 use crate::metrics::Metrics;
+use crate::operational_state::{
+    epochs_since_finality, OperationalState, LONG_NON_FINALITY_MAX_ATTESTATIONS_PER_SLOT,
+    LONG_NON_FINALITY_MAX_ATTESTATION_SLOTS,
+};
 use crate::persisted_beacon_chain::{PersistedBeaconChain, BEACON_CHAIN_DB_KEY};
+use crate::state_root_verification::StateRootVerification;
+use futures::sync::mpsc::UnboundedReceiver;
 use lmd_ghost::LmdGhost;
 use log::trace;
 use operation_pool::DepositInsertStatus;
-use operation_pool::{OperationPool, PersistedOperationPool};
+use operation_pool::{
+    OperationPool, PersistedOperationPool, ShardInsertOutcome, DEFAULT_MAX_ATTESTATIONS_PER_SLOT,
+    DEFAULT_MAX_SLOTS,
+};
 use parking_lot::{RwLock, RwLockReadGuard};
 use slog::{error, info, warn, Logger};
 use slot_clock::SlotClock;
+use ssz::Encode;
 use state_processing::per_block_processing::errors::{
     AttestationValidationError, AttesterSlashingValidationError, DepositValidationError,
     ExitValidationError, ProposerSlashingValidationError, TransferValidationError,
 };
+use state_processing::per_block_processing::{
+    verify_block_signature_with_proposers, EpochProposers,
+};
 use state_processing::{
-    per_block_processing, per_block_processing_without_verifying_block_signature,
-    per_slot_processing, BlockProcessingError,
+    per_block_processing_with_cache, per_block_processing_without_verifying_block_signature,
+    per_slot_processing, BlockProcessingError, PubkeyAggregateCache,
 };
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use store::iter::{BlockRootsIterator, StateRootsIterator};
 use store::{Error as DBError, Store};
-use tree_hash::TreeHash;
+use tree_hash::{SignedRoot, TreeHash};
 use types::*;
 
 // Text included in blocks.
@@ -83,6 +98,22 @@ pub struct BeaconChain<T: BeaconChainTypes> {
     /// A state-machine that is updated with information from the network and chooses a canonical
     /// head block.
     pub fork_choice: ForkChoice<T>,
+    /// The mode of operation most recently applied to `self`, see `OperationalState`.
+    operational_state: RwLock<OperationalState>,
+    /// Subscribers to changes of the canonical head.
+    head_subscribers: HeadSubscribers,
+    /// The block proposers of an epoch, keyed by the root of the block from whose state they were
+    /// built. Used to verify gossip block signatures without building committees.
+    proposer_cache: RwLock<Option<(Hash256, EpochProposers)>>,
+    /// The aggregate public keys of committees and the decompressed deposit public keys, shared
+    /// by the signature verification of every imported block.
+    pubkey_aggregate_cache: PubkeyAggregateCache,
+    /// Which imported blocks have their state root verified, see `StateRootVerification`.
+    state_root_verification: StateRootVerification,
+    /// The number of blocks imported since startup, used to sample state root verification.
+    blocks_imported: AtomicU64,
+    /// Follows the deposit contract, for the eth1 data votes and deposits of produced blocks.
+    eth1: Option<Arc<eth1::Service>>,
     /// Stores metrics about this `BeaconChain`.
     pub metrics: Metrics,
     /// Logging to CLI, etc.
@@ -134,6 +165,13 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
             canonical_head,
             genesis_block_root,
             fork_choice: ForkChoice::new(store.clone(), &genesis_block, genesis_block_root),
+            operational_state: RwLock::new(OperationalState::default()),
+            head_subscribers: HeadSubscribers::default(),
+            proposer_cache: RwLock::new(None),
+            pubkey_aggregate_cache: PubkeyAggregateCache::new(),
+            state_root_verification: StateRootVerification::default(),
+            blocks_imported: AtomicU64::new(0),
+            eth1: None,
             metrics: Metrics::new()?,
             store,
             log,
@@ -162,9 +200,20 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
         let last_finalized_root = p.canonical_head.beacon_state.finalized_checkpoint.root;
         let last_finalized_block = &p.canonical_head.beacon_block;
 
-        let op_pool = p.op_pool.into_operation_pool(&p.state, &spec);
+        let op_pool = match p.op_pool() {
+            Ok(op_pool) => op_pool.into_operation_pool(&p.state, &spec),
+            Err(e) => {
+                warn!(
+                    log,
+                    "Discarding the persisted operation pool";
+                    "reason" => "unable to decode, it may have been persisted by an earlier release",
+                    "error" => format!("{:?}", e),
+                );
+                OperationPool::new()
+            }
+        };
 
-        Ok(Some(BeaconChain {
+        let chain = BeaconChain {
             spec,
             slot_clock,
             fork_choice: ForkChoice::new(store.clone(), last_finalized_block, last_finalized_root),
@@ -172,17 +221,51 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
             canonical_head: RwLock::new(p.canonical_head),
             state: RwLock::new(p.state),
             genesis_block_root: p.genesis_block_root,
+            operational_state: RwLock::new(OperationalState::default()),
+            head_subscribers: HeadSubscribers::default(),
+            proposer_cache: RwLock::new(None),
+            pubkey_aggregate_cache: PubkeyAggregateCache::new(),
+            state_root_verification: StateRootVerification::default(),
+            blocks_imported: AtomicU64::new(0),
+            eth1: None,
             metrics: Metrics::new()?,
             store,
             log,
-        }))
+        };
+
+        // The persisted chain may have been stored during a long period of non-finality.
+        chain.update_operational_state();
+
+        Ok(Some(chain))
+    }
+
+    /// Sets which imported blocks have their state root verified.
+    ///
+    /// Warns loudly if the chain will no longer verify every block.
+    pub fn set_state_root_verification(&mut self, state_root_verification: StateRootVerification) {
+        if state_root_verification.is_sampled() {
+            warn!(
+                self.log,
+                "State roots are only verified on sampled blocks, this node is NOT VALIDATING";
+                "verification" => format!("{:?}", state_root_verification),
+            );
+        }
+        self.state_root_verification = state_root_verification;
+    }
+
+    /// Sets the eth1 service from which produced blocks take their eth1 data votes and deposits.
+    ///
+    /// Without one, blocks vote for placeholder eth1 data and take deposits from the op pool.
+    pub fn set_eth1_service(&mut self, eth1: Arc<eth1::Service>) {
+        self.eth1 = Some(eth1);
     }
 
     /// Attempt to save this instance to `self.store`.
     pub fn persist(&self) -> Result<(), Error> {
         let p: PersistedBeaconChain<T> = PersistedBeaconChain {
             canonical_head: self.canonical_head.read().clone(),
-            op_pool: PersistedOperationPool::from_operation_pool(&self.op_pool),
+            op_pool_bytes: PersistedOperationPool::from_operation_pool(&self.op_pool)
+                .as_ssz_bytes(),
             genesis_block_root: self.genesis_block_root,
             state: self.state.read().clone(),
         };
@@ -272,6 +355,22 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
         ReverseStateRootIterator::new((state_root, state_slot), iter)
     }
 
+    /// Returns the root of the canonical block at the start of `epoch` (or of its closest prior
+    /// block, if the slot was skipped), as in a `Checkpoint` of `epoch`.
+    ///
+    /// Returns `None` if `epoch` starts later than the head or earlier than the state history.
+    pub fn checkpoint_root(&self, epoch: Epoch) -> Option<Hash256> {
+        let slot = epoch.start_slot(T::EthSpec::slots_per_epoch());
+        let head_slot = self.head().beacon_block.slot;
+        if slot > head_slot {
+            return None;
+        }
+
+        self.rev_iter_block_roots(head_slot)
+            .find(|(_root, root_slot)| *root_slot == slot)
+            .map(|(root, _slot)| root)
+    }
+
     /// Returns the block at the given root, if any.
     ///
     /// ## Errors
@@ -328,9 +427,98 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
             state.build_all_caches(spec)?;
         }
 
+        self.update_operational_state();
+
         Ok(())
     }
 
+    /// Returns the number of epochs since the last finalized epoch, as seen by the present state.
+    pub fn epochs_since_finality(&self) -> u64 {
+        let state = self.state.read();
+        epochs_since_finality(state.current_epoch(), state.finalized_checkpoint.epoch)
+    }
+
+    /// Returns a stream of `HeadEvent`s, one for each future change of the canonical head.
+    pub fn subscribe_head(&self) -> UnboundedReceiver<HeadEvent> {
+        self.head_subscribers.subscribe()
+    }
+
+    /// Finds the latest common ancestor of the blocks with `old_root` and `new_root`.
+    ///
+    /// Returns the slot of the common ancestor and the number of blocks from it to `old_root`,
+    /// which is the depth of a re-org from `old_root` to `new_root`.
+    pub fn find_common_ancestor(
+        &self,
+        mut old_root: Hash256,
+        mut new_root: Hash256,
+    ) -> Result<(Slot, u64), Error> {
+        let get_block = |root: Hash256| -> Result<BeaconBlock<T::EthSpec>, Error> {
+            self.store
+                .get(&root)?
+                .ok_or_else(|| Error::MissingBeaconBlock(root))
+        };
+
+        let mut old_block = get_block(old_root)?;
+        let mut new_block = get_block(new_root)?;
+        let mut depth = 0;
+
+        // Step back along whichever chain is higher, until both reach the same block. Both chains
+        // end at the genesis block, which is also stored as its own parent (`Hash256::zero()`).
+        while old_root != new_root {
+            if old_block.slot >= new_block.slot {
+                old_root = old_block.parent_root;
+                old_block = get_block(old_root)?;
+                depth += 1;
+            } else {
+                new_root = new_block.parent_root;
+                new_block = get_block(new_root)?;
+            }
+        }
+
+        Ok((old_block.slot, depth))
+    }
+
+    /// Returns the mode in which `self` is presently operating.
+    pub fn operational_state(&self) -> OperationalState {
+        *self.operational_state.read()
+    }
+
+    /// Determine the operational state from the present state and, if it has changed, apply the
+    /// corresponding operation pool limits.
+    fn update_operational_state(&self) {
+        let new_state = OperationalState::from_state(&*self.state.read());
+
+        if new_state == *self.operational_state.read() {
+            return;
+        }
+
+        *self.operational_state.write() = new_state;
+
+        match new_state {
+            OperationalState::LongNonFinality => {
+                warn!(
+                    self.log,
+                    "Entering long non-finality mode";
+                    "epochs_since_finality" => self.epochs_since_finality(),
+                    "fork_choice_nodes" => self.fork_choice.node_count(),
+                );
+                self.op_pool.set_attestation_limits(
+                    LONG_NON_FINALITY_MAX_ATTESTATIONS_PER_SLOT,
+                    LONG_NON_FINALITY_MAX_ATTESTATION_SLOTS,
+                );
+            }
+            OperationalState::Healthy => {
+                info!(
+                    self.log,
+                    "Leaving long non-finality mode";
+                    "epochs_since_finality" => self.epochs_since_finality(),
+                );
+                self.op_pool
+                    .set_attestation_limits(DEFAULT_MAX_ATTESTATIONS_PER_SLOT, DEFAULT_MAX_SLOTS);
+            }
+        }
+    }
+
     /// Build all of the caches on the current state.
     ///
     /// Ideally this shouldn't be required, however we leave it here for testing.
@@ -409,6 +597,41 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
         Ok(index)
     }
 
+    /// Verifies the proposer signature of `block` without processing it, for use when deciding
+    /// whether to propagate a gossip block.
+    ///
+    /// Returns `None` if the proposers of the block's epoch cannot be determined cheaply (i.e.,
+    /// the parent of `block` is not the block of the present state, or is in a different epoch).
+    pub fn verify_block_signature_fast(&self, block: &BeaconBlock<T::EthSpec>) -> Option<bool> {
+        let epoch = block.slot.epoch(T::EthSpec::slots_per_epoch());
+
+        if let Some((parent_root, proposers)) = self.proposer_cache.read().as_ref() {
+            if *parent_root == block.parent_root && proposers.epoch() == epoch {
+                return Some(verify_block_signature_with_proposers(block, proposers).is_ok());
+            }
+        }
+
+        let proposers = {
+            let state = self.state.read();
+
+            // The present state is only a valid source of proposers for children of its latest
+            // block, in the same epoch.
+            if Hash256::from_slice(&state.latest_block_header.signed_root()) != block.parent_root
+                || state.current_epoch() != epoch
+                || state.slot < block.slot
+            {
+                return None;
+            }
+
+            EpochProposers::new(&state, &self.spec).ok()?
+        };
+
+        let is_valid = verify_block_signature_with_proposers(block, &proposers).is_ok();
+        *self.proposer_cache.write() = Some((block.parent_root, proposers));
+
+        Some(is_valid)
+    }
+
     /// Returns the attestation slot and shard for a given validator index.
     ///
     /// Information is read from the current state, so only information from the present and prior
@@ -530,10 +753,25 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
 
         timer.observe_duration();
 
-        if result.is_ok() {
-            self.metrics.attestation_processing_successes.inc();
+        match result {
+            Ok(ShardInsertOutcome::ShardFull) | Ok(ShardInsertOutcome::TooOld) => {
+                self.metrics.attestation_processing_dropped.inc()
+            }
+            Ok(_) => self.metrics.attestation_processing_successes.inc(),
+            Err(_) => {}
         }
 
+        let shard_metrics = self.op_pool.attestation_shard_metrics();
+        self.metrics
+            .op_pool_attestation_shards
+            .set(shard_metrics.len() as i64);
+        self.metrics.op_pool_attestations.set(
+            shard_metrics
+                .iter()
+                .map(|(_, metrics)| metrics.num_attestations as i64)
+                .sum(),
+        );
+
         // TODO: process attestation. Please consider:
         //
         //  - Because a block was not added to the op pool does not mean it's invalid (it might
@@ -545,7 +783,7 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
         //  - You can avoid verifying some attestations by first checking if they're a latest
         //  message. This would involve expanding the `LmdGhost` API.
 
-        result
+        result.map(|_| ())
     }
 
     /// Accept some deposit and queue it for inclusion in an appropriate block.
@@ -652,6 +890,9 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
             .get(&parent_state_root)?
             .ok_or_else(|| Error::DBInconsistent(format!("Missing state {}", parent_state_root)))?;
 
+        let crosses_epoch =
+            parent_state.current_epoch() != block.slot.epoch(T::EthSpec::slots_per_epoch());
+
         // Transition the parent state to the block slot.
         let mut state: BeaconState<T::EthSpec> = parent_state;
         for _ in state.slot.as_u64()..block.slot.as_u64() {
@@ -662,7 +903,12 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
 
         // Apply the received block to its parent state (which has been transitioned into this
         // slot).
-        match per_block_processing(&mut state, &block, &self.spec) {
+        match per_block_processing_with_cache(
+            &mut state,
+            &block,
+            &self.pubkey_aggregate_cache,
+            &self.spec,
+        ) {
             Err(BlockProcessingError::BeaconStateError(e)) => {
                 return Err(Error::BeaconStateError(e))
             }
@@ -670,11 +916,24 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
             _ => {}
         }
 
-        let state_root = state.canonical_root();
+        let import_index = self.blocks_imported.fetch_add(1, Ordering::Relaxed);
+        let state_root = if self
+            .state_root_verification
+            .must_verify(import_index, crosses_epoch)
+        {
+            let state_root = state.canonical_root();
 
-        if block.state_root != state_root {
-            return Ok(BlockProcessingOutcome::StateRootMismatch);
-        }
+            if block.state_root != state_root {
+                return Ok(BlockProcessingOutcome::StateRootMismatch);
+            }
+
+            state_root
+        } else {
+            // The state is stored under the root claimed by the block, so that its children may
+            // find it.
+            self.metrics.state_root_verifications_skipped.inc();
+            block.state_root
+        };
 
         // Store the block and state.
         self.store.put(&block_root, &block)?;
@@ -765,6 +1024,7 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
 
         let (proposer_slashings, attester_slashings) =
             self.op_pool.get_slashings(&state, &self.spec);
+        let (eth1_data, deposits) = self.eth1_data_and_deposits(&state)?;
 
         let mut block = BeaconBlock {
             slot: state.slot,
@@ -773,17 +1033,12 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
             signature: Signature::empty_signature(), // To be completed by a validator.
             body: BeaconBlockBody {
                 randao_reveal,
-                // TODO: replace with real data.
-                eth1_data: Eth1Data {
-                    deposit_count: state.eth1_data.deposit_count,
-                    deposit_root: Hash256::zero(),
-                    block_hash: Hash256::zero(),
-                },
+                eth1_data,
                 graffiti,
                 proposer_slashings: proposer_slashings.into(),
                 attester_slashings: attester_slashings.into(),
                 attestations: self.op_pool.get_attestations(&state, &self.spec).into(),
-                deposits: self.op_pool.get_deposits(&state).into(),
+                deposits: deposits.into(),
                 voluntary_exits: self.op_pool.get_voluntary_exits(&state, &self.spec).into(),
                 transfers: self.op_pool.get_transfers(&state, &self.spec).into(),
             },
@@ -801,6 +1056,48 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
         Ok((block, state))
     }
 
+    /// Returns the eth1 data vote and the deposits of a block produced on `state`.
+    ///
+    /// Returns an error if the eth1 cache is inconsistent with the `state`, since the block would
+    /// be invalid.
+    fn eth1_data_and_deposits(
+        &self,
+        state: &BeaconState<T::EthSpec>,
+    ) -> Result<(Eth1Data, Vec<Deposit>), BlockProductionError> {
+        let eth1 = match &self.eth1 {
+            Some(eth1) => eth1,
+            None => {
+                // TODO: replace with real data.
+                let eth1_data = Eth1Data {
+                    deposit_count: state.eth1_data.deposit_count,
+                    deposit_root: Hash256::zero(),
+                    block_hash: Hash256::zero(),
+                };
+                return Ok((eth1_data, self.op_pool.get_deposits(state)));
+            }
+        };
+
+        let cache = eth1.cache();
+        let eth1_data = cache.eth1_data_vote(state).unwrap_or_else(|| {
+            warn!(
+                self.log,
+                "No eth1 block to vote for, voting for the current eth1 data";
+                "eth1_head" => cache.head_number(),
+            );
+            state.eth1_data.clone()
+        });
+        let deposits = cache.deposits_for_block(state, &eth1_data).map_err(|e| {
+            error!(
+                self.log,
+                "The eth1 cache is inconsistent with the beacon chain, not producing a block";
+                "error" => format!("{:?}", e),
+            );
+            BlockProductionError::Eth1Error(e)
+        })?;
+
+        Ok((eth1_data, deposits))
+    }
+
     /// Execute the fork choice algorithm and enthrone the result as the canonical head.
     pub fn fork_choice(&self) -> Result<(), Error> {
         self.metrics.fork_choice_requests.inc();
@@ -830,16 +1127,25 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
                 .ok_or_else(|| Error::MissingBeaconState(beacon_state_root))?;
 
             let previous_slot = self.head().beacon_block.slot;
+            let previous_block_root = self.head().beacon_block_root;
             let new_slot = beacon_block.slot;
+            let reorg = previous_block_root != beacon_block.parent_root;
+            let (common_ancestor_slot, reorg_depth) = if reorg {
+                self.find_common_ancestor(previous_block_root, beacon_block_root)?
+            } else {
+                (previous_slot, 0)
+            };
 
             // If we switched to a new chain (instead of building atop the present chain).
-            if self.head().beacon_block_root != beacon_block.parent_root {
+            if reorg {
                 self.metrics.fork_choice_reorg_count.inc();
                 warn!(
                     self.log,
                     "Beacon chain re-org";
                     "previous_slot" => previous_slot,
-                    "new_slot" => new_slot
+                    "new_slot" => new_slot,
+                    "common_ancestor_slot" => common_ancestor_slot,
+                    "depth" => reorg_depth,
                 );
             } else {
                 info!(
@@ -870,6 +1176,16 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
                     beacon_state_root,
                 })?;
 
+                self.head_subscribers.notify(&HeadEvent {
+                    slot: new_slot,
+                    block_root: beacon_block_root,
+                    state_root: beacon_state_root,
+                    previous_block_root,
+                    reorg,
+                    reorg_depth,
+                    common_ancestor_slot,
+                });
+
                 if new_finalized_epoch != old_finalized_epoch {
                     self.after_finalization(old_finalized_epoch, finalized_root)?;
                 }
@@ -903,9 +1219,22 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
 
             state.build_all_caches(&self.spec)?;
 
+            // Blocks built upon the head can only include attestations of its previous epoch
+            // onwards.
+            self.pubkey_aggregate_cache.prune(state.previous_epoch());
+
             state
         };
 
+        self.update_operational_state();
+
+        // The head state is always in the store and `self.state` (which retains its caches) is
+        // derived from it above, so there is no need to keep the head state caches in memory when
+        // memory is scarce.
+        if self.operational_state().is_long_non_finality() {
+            self.canonical_head.write().beacon_state.drop_all_caches();
+        }
+
         // Save `self` to `self.store`.
         self.persist()?;
 
@@ -30,10 +30,18 @@ pub enum BeaconChainError {
     MissingBeaconBlock(Hash256),
     MissingBeaconState(Hash256),
     SlotProcessingError(SlotProcessingError),
+    BlockProcessingError(BlockProcessingError),
+    /// A replayed block produced a state root which does not match the block.
+    StateRootMismatch {
+        block_root: Hash256,
+        expected: Hash256,
+        found: Hash256,
+    },
     MetricsError(String),
 }
 
 easy_from_to!(SlotProcessingError, BeaconChainError);
+easy_from_to!(BlockProcessingError, BeaconChainError);
 
 impl From<MetricsError> for BeaconChainError {
     fn from(e: MetricsError) -> BeaconChainError {
@@ -48,6 +56,8 @@ pub enum BlockProductionError {
     SlotProcessingError(SlotProcessingError),
     BlockProcessingError(BlockProcessingError),
     BeaconStateError(BeaconStateError),
+    /// The deposits known to the eth1 cache do not match the eth1 data of the state.
+    Eth1Error(eth1::Error),
 }
 
 easy_from_to!(BlockProcessingError, BlockProductionError);
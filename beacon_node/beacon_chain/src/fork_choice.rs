@@ -1,3 +1,4 @@
+use crate::operational_state::{OperationalState, LONG_NON_FINALITY_MAX_FORK_CHOICE_NODES};
 use crate::{BeaconChain, BeaconChainTypes};
 use lmd_ghost::LmdGhost;
 use state_processing::common::get_attesting_indices;
@@ -95,9 +96,20 @@ impl<T: BeaconChainTypes> ForkChoice<T> {
                 .map(|v| v.effective_balance)
         };
 
-        self.backend
-            .find_head(start_block_slot, start_block_root, weight)
-            .map_err(Into::into)
+        let head = self
+            .backend
+            .find_head(start_block_slot, start_block_root, weight)?;
+
+        // Without finality the backend is never pruned by `process_finalization`, so instead the
+        // branches which can no longer become the head are removed.
+        if OperationalState::from_state(&chain.head().beacon_state).is_long_non_finality()
+            && self.backend.node_count() >= LONG_NON_FINALITY_MAX_FORK_CHOICE_NODES
+        {
+            self.backend
+                .prune_non_viable(start_block_slot, start_block_root, head)?;
+        }
+
+        Ok(head)
     }
 
     /// Process all attestations in the given `block`.
@@ -167,6 +179,11 @@ impl<T: BeaconChainTypes> ForkChoice<T> {
         Ok(())
     }
 
+    /// Returns the number of nodes stored by the fork choice backend.
+    pub fn node_count(&self) -> usize {
+        self.backend.node_count()
+    }
+
     /// Inform the fork choice that the given block (and corresponding root) have been finalized so
     /// it may prune it's storage.
     ///
@@ -0,0 +1,94 @@
+//! Notifications of changes to the canonical head, for consumers (e.g., validator clients) which
+//! would otherwise have to poll the head.
+use futures::sync::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use parking_lot::Mutex;
+use types::{Hash256, Slot};
+
+/// A change of the canonical head.
+#[derive(Debug, PartialEq, Clone)]
+pub struct HeadEvent {
+    /// The slot of the new head block.
+    pub slot: Slot,
+    /// The root of the new head block.
+    pub block_root: Hash256,
+    /// The state root of the new head block.
+    pub state_root: Hash256,
+    /// The root of the previous head block.
+    pub previous_block_root: Hash256,
+    /// `true` if the new head is not a child of the previous head.
+    pub reorg: bool,
+    /// The number of blocks of the previous canonical chain which are not ancestors of the new
+    /// head, zero unless `reorg`.
+    pub reorg_depth: u64,
+    /// The slot of the latest block which is an ancestor of both the previous and the new head.
+    pub common_ancestor_slot: Slot,
+}
+
+/// The set of subscribers to `HeadEvent`s.
+///
+/// Subscribers are removed once their receiver is dropped.
+#[derive(Default)]
+pub struct HeadSubscribers {
+    senders: Mutex<Vec<UnboundedSender<HeadEvent>>>,
+}
+
+impl HeadSubscribers {
+    /// Returns a stream of all future `HeadEvent`s.
+    pub fn subscribe(&self) -> UnboundedReceiver<HeadEvent> {
+        let (sender, receiver) = unbounded();
+        self.senders.lock().push(sender);
+        receiver
+    }
+
+    /// Sends `event` to all subscribers, removing any which have disconnected.
+    pub fn notify(&self, event: &HeadEvent) {
+        self.senders
+            .lock()
+            .retain(|sender| sender.unbounded_send(event.clone()).is_ok());
+    }
+
+    /// Returns the number of subscribers.
+    pub fn len(&self) -> usize {
+        self.senders.lock().len()
+    }
+
+    /// Returns `true` if there are no subscribers.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::Stream;
+
+    fn event(slot: u64) -> HeadEvent {
+        HeadEvent {
+            slot: Slot::new(slot),
+            block_root: Hash256::from_low_u64_be(slot),
+            state_root: Hash256::zero(),
+            previous_block_root: Hash256::zero(),
+            reorg: false,
+            reorg_depth: 0,
+            common_ancestor_slot: Slot::new(slot.saturating_sub(1)),
+        }
+    }
+
+    #[test]
+    fn notifies_subscribers_until_dropped() {
+        let subscribers = HeadSubscribers::default();
+        let first = subscribers.subscribe();
+        let second = subscribers.subscribe();
+
+        subscribers.notify(&event(1));
+        drop(second);
+        subscribers.notify(&event(2));
+
+        assert_eq!(subscribers.len(), 1);
+        assert_eq!(
+            first.wait().take(2).collect::<Result<Vec<_>, _>>(),
+            Ok(vec![event(1), event(2)])
+        );
+    }
+}
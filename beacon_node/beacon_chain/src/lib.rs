@@ -2,14 +2,21 @@ mod beacon_chain;
 mod checkpoint;
 mod errors;
 mod fork_choice;
+mod head_events;
 mod iter;
 mod metrics;
+mod operational_state;
 mod persisted_beacon_chain;
+pub mod state_regen;
+mod state_root_verification;
 pub mod test_utils;
 
 pub use self::beacon_chain::{BeaconChain, BeaconChainTypes, BlockProcessingOutcome};
 pub use self::checkpoint::CheckPoint;
 pub use self::errors::{BeaconChainError, BlockProductionError};
+pub use self::head_events::HeadEvent;
+pub use self::operational_state::{OperationalState, LONG_NON_FINALITY_EPOCHS};
+pub use self::state_root_verification::StateRootVerification;
 pub use lmd_ghost;
 pub use parking_lot;
 pub use slot_clock;
@@ -1,5 +1,5 @@
 pub use prometheus::Error;
-use prometheus::{Histogram, HistogramOpts, IntCounter, Opts, Registry};
+use prometheus::{Histogram, HistogramOpts, IntCounter, IntGauge, Opts, Registry};
 
 pub struct Metrics {
     pub block_processing_requests: IntCounter,
@@ -14,11 +14,17 @@ pub struct Metrics {
     pub attestation_processing_requests: IntCounter,
     pub attestation_processing_successes: IntCounter,
     pub attestation_processing_times: Histogram,
+    pub attestation_processing_dropped: IntCounter,
+    pub op_pool_attestation_shards: IntGauge,
+    pub op_pool_attestations: IntGauge,
     pub fork_choice_requests: IntCounter,
     pub fork_choice_changed_head: IntCounter,
     pub fork_choice_reorg_count: IntCounter,
     pub fork_choice_times: Histogram,
     pub operations_per_block_attestation: Histogram,
+    pub conflicting_blocks_refused: IntCounter,
+    pub conflicting_attestations_refused: IntCounter,
+    pub state_root_verifications_skipped: IntCounter,
 }
 
 impl Metrics {
@@ -90,6 +96,24 @@ impl Metrics {
                 );
                 Histogram::with_opts(opts)?
             },
+            attestation_processing_dropped: {
+                let opts = Opts::new(
+                    "attestation_processing_dropped",
+                    "total_attestations_dropped_by_full_op_pool_shards",
+                );
+                IntCounter::with_opts(opts)?
+            },
+            op_pool_attestation_shards: {
+                let opts = Opts::new(
+                    "op_pool_attestation_shards",
+                    "number_of_slots_with_attestations_in_op_pool",
+                );
+                IntGauge::with_opts(opts)?
+            },
+            op_pool_attestations: {
+                let opts = Opts::new("op_pool_attestations", "number_of_attestations_in_op_pool");
+                IntGauge::with_opts(opts)?
+            },
             fork_choice_requests: {
                 let opts = Opts::new("fork_choice_requests", "total_times_fork_choice_called");
                 IntCounter::with_opts(opts)?
@@ -116,6 +140,27 @@ impl Metrics {
                 );
                 Histogram::with_opts(opts)?
             },
+            conflicting_blocks_refused: {
+                let opts = Opts::new(
+                    "conflicting_blocks_refused",
+                    "total_blocks_from_validators_refused_as_conflicting",
+                );
+                IntCounter::with_opts(opts)?
+            },
+            conflicting_attestations_refused: {
+                let opts = Opts::new(
+                    "conflicting_attestations_refused",
+                    "total_attestations_from_validators_refused_as_conflicting",
+                );
+                IntCounter::with_opts(opts)?
+            },
+            state_root_verifications_skipped: {
+                let opts = Opts::new(
+                    "state_root_verifications_skipped",
+                    "total_blocks_imported_without_verifying_their_state_root",
+                );
+                IntCounter::with_opts(opts)?
+            },
         })
     }
 
@@ -132,11 +177,17 @@ impl Metrics {
         registry.register(Box::new(self.attestation_processing_requests.clone()))?;
         registry.register(Box::new(self.attestation_processing_successes.clone()))?;
         registry.register(Box::new(self.attestation_processing_times.clone()))?;
+        registry.register(Box::new(self.attestation_processing_dropped.clone()))?;
+        registry.register(Box::new(self.op_pool_attestation_shards.clone()))?;
+        registry.register(Box::new(self.op_pool_attestations.clone()))?;
         registry.register(Box::new(self.fork_choice_requests.clone()))?;
         registry.register(Box::new(self.fork_choice_changed_head.clone()))?;
         registry.register(Box::new(self.fork_choice_reorg_count.clone()))?;
         registry.register(Box::new(self.fork_choice_times.clone()))?;
         registry.register(Box::new(self.operations_per_block_attestation.clone()))?;
+        registry.register(Box::new(self.conflicting_blocks_refused.clone()))?;
+        registry.register(Box::new(self.conflicting_attestations_refused.clone()))?;
+        registry.register(Box::new(self.state_root_verifications_skipped.clone()))?;
 
         Ok(())
     }
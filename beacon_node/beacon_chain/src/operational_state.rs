@@ -0,0 +1,91 @@
+use serde_derive::Serialize;
+use types::{BeaconState, Epoch, EthSpec};
+
+/// The number of epochs without finality after which the chain enters `LongNonFinality`.
+pub const LONG_NON_FINALITY_EPOCHS: u64 = 16;
+
+/// Whilst in `LongNonFinality`, the branches of fork choice which cannot become the head are
+/// pruned once it holds this many nodes.
+pub const LONG_NON_FINALITY_MAX_FORK_CHOICE_NODES: usize = 1_024;
+
+/// Whilst in `LongNonFinality`, the maximum number of attestations stored per slot in the
+/// operation pool.
+pub const LONG_NON_FINALITY_MAX_ATTESTATIONS_PER_SLOT: usize = 512;
+
+/// Whilst in `LongNonFinality`, the maximum number of slots for which attestations are stored in
+/// the operation pool.
+pub const LONG_NON_FINALITY_MAX_ATTESTATION_SLOTS: usize = 64;
+
+/// Describes the mode in which the `BeaconChain` is operating.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationalState {
+    /// The chain is finalizing (or has only recently stopped finalizing).
+    Healthy,
+    /// The chain has not finalized for at least `LONG_NON_FINALITY_EPOCHS`.
+    ///
+    /// Memory usage is restricted by pruning fork choice, capping the operation pool size and
+    /// dropping the caches of the head state. States are not otherwise moved out of memory.
+    LongNonFinality,
+}
+
+impl Default for OperationalState {
+    fn default() -> Self {
+        OperationalState::Healthy
+    }
+}
+
+impl OperationalState {
+    /// Determine the operational state from the current and the last finalized epoch.
+    pub fn from_epochs(current_epoch: Epoch, finalized_epoch: Epoch) -> Self {
+        if epochs_since_finality(current_epoch, finalized_epoch) >= LONG_NON_FINALITY_EPOCHS {
+            OperationalState::LongNonFinality
+        } else {
+            OperationalState::Healthy
+        }
+    }
+
+    /// Determine the operational state from the given `state`.
+    pub fn from_state<E: EthSpec>(state: &BeaconState<E>) -> Self {
+        Self::from_epochs(state.current_epoch(), state.finalized_checkpoint.epoch)
+    }
+
+    /// Returns `true` if memory-saving restrictions should be applied.
+    pub fn is_long_non_finality(self) -> bool {
+        self == OperationalState::LongNonFinality
+    }
+}
+
+/// Returns the number of epochs between `finalized_epoch` and `current_epoch`, saturating at zero.
+pub fn epochs_since_finality(current_epoch: Epoch, finalized_epoch: Epoch) -> u64 {
+    current_epoch
+        .as_u64()
+        .saturating_sub(finalized_epoch.as_u64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_epochs() {
+        let state = |current: u64, finalized: u64| {
+            OperationalState::from_epochs(Epoch::new(current), Epoch::new(finalized))
+        };
+
+        assert_eq!(state(0, 0), OperationalState::Healthy);
+        assert_eq!(state(2, 5), OperationalState::Healthy);
+        assert_eq!(
+            state(LONG_NON_FINALITY_EPOCHS - 1, 0),
+            OperationalState::Healthy
+        );
+        assert_eq!(
+            state(LONG_NON_FINALITY_EPOCHS, 0),
+            OperationalState::LongNonFinality
+        );
+        assert_eq!(
+            state(LONG_NON_FINALITY_EPOCHS + 10, 10),
+            OperationalState::LongNonFinality
+        );
+    }
+}
@@ -1,6 +1,6 @@
 use crate::{BeaconChainTypes, CheckPoint};
 use operation_pool::PersistedOperationPool;
-use ssz::{Decode, Encode};
+use ssz::{Decode, DecodeError, Encode};
 use ssz_derive::{Decode, Encode};
 use store::{DBColumn, Error as StoreError, StoreItem};
 use types::{BeaconState, Hash256};
@@ -11,11 +11,23 @@ pub const BEACON_CHAIN_DB_KEY: &str = "PERSISTEDBEACONCHAINPERSISTEDBEA";
 #[derive(Encode, Decode)]
 pub struct PersistedBeaconChain<T: BeaconChainTypes> {
     pub canonical_head: CheckPoint<T::EthSpec>,
-    pub op_pool: PersistedOperationPool<T::EthSpec>,
+    /// The SSZ encoding of a `PersistedOperationPool`.
+    ///
+    /// It is decoded separately (see `Self::op_pool`) so that a pool persisted in the layout of an
+    /// earlier release can be discarded rather than prevent the chain from resuming. It has the
+    /// same SSZ encoding as a `PersistedOperationPool` field.
+    pub op_pool_bytes: Vec<u8>,
     pub genesis_block_root: Hash256,
     pub state: BeaconState<T::EthSpec>,
 }
 
+impl<T: BeaconChainTypes> PersistedBeaconChain<T> {
+    /// Decode the persisted operation pool.
+    pub fn op_pool(&self) -> Result<PersistedOperationPool<T::EthSpec>, DecodeError> {
+        PersistedOperationPool::from_ssz_bytes(&self.op_pool_bytes)
+    }
+}
+
 impl<T: BeaconChainTypes> StoreItem for PersistedBeaconChain<T> {
     fn db_column() -> DBColumn {
         DBColumn::BeaconChain
@@ -0,0 +1,104 @@
+//! Regeneration of the post-state of an arbitrary block in the store.
+//!
+//! Starting at the requested block, blocks are read from the store (via `parent_root`) until one
+//! is found whose post-state is also in the store. That state is then transitioned forward by
+//! re-applying the blocks, in order.
+use crate::errors::BeaconChainError as Error;
+use state_processing::{
+    per_block_processing_without_verifying_block_signature, per_slot_processing,
+};
+use std::time::{Duration, Instant};
+use store::Store;
+use types::{BeaconBlock, BeaconState, ChainSpec, EthSpec, Hash256, RelativeEpoch, Slot};
+
+/// Statistics about a call to `state_at_block_root`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ReplayStatistics {
+    /// The slot of the stored state from which the replay started.
+    pub base_state_slot: Slot,
+    /// The number of blocks which were re-applied to the base state.
+    pub blocks_replayed: usize,
+    /// The number of calls to `per_slot_processing` (including skip slots).
+    pub slots_processed: u64,
+    /// The time taken to find the base state in the store.
+    pub load_time: Duration,
+    /// The time taken to re-apply the blocks to the base state.
+    pub replay_time: Duration,
+}
+
+/// Returns the post-state of the block with `block_root`, reconstructing it from the nearest
+/// ancestor state in `store` if it is not stored itself.
+///
+/// Returns an error if any replayed block produces a state root which does not match the root
+/// committed to in that block.
+pub fn state_at_block_root<E: EthSpec, S: Store>(
+    store: &S,
+    block_root: Hash256,
+    spec: &ChainSpec,
+) -> Result<(BeaconState<E>, ReplayStatistics), Error> {
+    let load_timer = Instant::now();
+
+    let mut block: BeaconBlock<E> = store
+        .get(&block_root)?
+        .ok_or_else(|| Error::MissingBeaconBlock(block_root))?;
+    let mut block_root = block_root;
+
+    // Blocks that must be applied to the base state, in reverse order.
+    let mut blocks = vec![];
+
+    let mut state = loop {
+        if let Some(state) = store.get::<BeaconState<E>>(&block.state_root)? {
+            break state;
+        }
+
+        let parent_root = block.parent_root;
+        if block.slot == spec.genesis_slot {
+            return Err(Error::MissingBeaconState(block.state_root));
+        }
+
+        blocks.push((block_root, block));
+        block = store
+            .get(&parent_root)?
+            .ok_or_else(|| Error::MissingBeaconBlock(parent_root))?;
+        block_root = parent_root;
+    };
+
+    let load_time = load_timer.elapsed();
+    let replay_timer = Instant::now();
+
+    let base_state_slot = state.slot;
+    let blocks_replayed = blocks.len();
+    let mut slots_processed = 0;
+
+    for (block_root, block) in blocks.into_iter().rev() {
+        while state.slot < block.slot {
+            // Ensure the next epoch state caches are built in case of an epoch transition.
+            state.build_committee_cache(RelativeEpoch::Next, spec)?;
+
+            per_slot_processing(&mut state, spec)?;
+            slots_processed += 1;
+        }
+
+        state.build_all_caches(spec)?;
+        per_block_processing_without_verifying_block_signature(&mut state, &block, spec)?;
+
+        if state.canonical_root() != block.state_root {
+            return Err(Error::StateRootMismatch {
+                block_root,
+                expected: block.state_root,
+                found: state.canonical_root(),
+            });
+        }
+    }
+
+    Ok((
+        state,
+        ReplayStatistics {
+            base_state_slot,
+            blocks_replayed,
+            slots_processed,
+            load_time,
+            replay_time: replay_timer.elapsed(),
+        },
+    ))
+}
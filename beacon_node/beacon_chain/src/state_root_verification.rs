@@ -0,0 +1,71 @@
+use serde_derive::{Deserialize, Serialize};
+
+/// Describes which imported blocks have their state root verified.
+///
+/// Computing the state root is a large part of the cost of importing a block. Resource-constrained
+/// followers (e.g., archive nodes which only serve the chain) may choose to only verify state roots
+/// on a sample of blocks. Signatures and all other block processing are always verified.
+///
+/// A node which samples is **not validating**: it may import (and serve) a block whose state root
+/// is invalid. It must never be used to produce blocks or attestations.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum StateRootVerification {
+    /// Verify the state root of every block.
+    Full,
+    /// Verify the state root of one in every `one_in` imported blocks, as well as every block which
+    /// is the first of its epoch on its chain.
+    Sampled { one_in: u64 },
+}
+
+impl Default for StateRootVerification {
+    fn default() -> Self {
+        StateRootVerification::Full
+    }
+}
+
+impl StateRootVerification {
+    /// Returns `true` if state roots are not verified on every block.
+    pub fn is_sampled(self) -> bool {
+        match self {
+            StateRootVerification::Full => false,
+            StateRootVerification::Sampled { one_in } => one_in > 1,
+        }
+    }
+
+    /// Returns `true` if the state root of a block must be verified.
+    ///
+    /// `import_index` counts the blocks imported before this one and `crosses_epoch` is `true` if
+    /// the block is in a later epoch than its parent.
+    pub fn must_verify(self, import_index: u64, crosses_epoch: bool) -> bool {
+        match self {
+            StateRootVerification::Full => true,
+            StateRootVerification::Sampled { one_in } => {
+                crosses_epoch || one_in <= 1 || import_index % one_in == 0
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_blocks_and_epoch_boundaries() {
+        let full = StateRootVerification::Full;
+        assert!(!full.is_sampled());
+        assert!((0..8).all(|i| full.must_verify(i, false)));
+
+        let sampled = StateRootVerification::Sampled { one_in: 4 };
+        assert!(sampled.is_sampled());
+        let verified = (0..8)
+            .filter(|i| sampled.must_verify(*i, false))
+            .collect::<Vec<_>>();
+        assert_eq!(verified, vec![0, 4]);
+        assert!((0..8).all(|i| sampled.must_verify(i, true)));
+
+        // Sampling one in one block is full verification.
+        assert!(!StateRootVerification::Sampled { one_in: 1 }.is_sampled());
+    }
+}
@@ -1,14 +1,17 @@
 #![cfg(not(debug_assertions))]
 
+use beacon_chain::state_regen::state_at_block_root;
 use beacon_chain::test_utils::{
     AttestationStrategy, BeaconChainHarness, BlockStrategy, CommonTypes, PersistedBeaconChain,
     BEACON_CHAIN_DB_KEY,
 };
+use beacon_chain::BeaconChain;
+use futures::Stream;
 use lmd_ghost::ThreadSafeReducedTree;
 use rand::Rng;
 use store::{MemoryStore, Store};
 use types::test_utils::{SeedableRng, TestRandom, XorShiftRng};
-use types::{Deposit, EthSpec, Hash256, MinimalEthSpec, Slot};
+use types::{BeaconBlock, BeaconState, Deposit, EthSpec, Hash256, MinimalEthSpec, Slot};
 
 // Should ideally be divisible by 3.
 pub const VALIDATOR_COUNT: usize = 24;
@@ -247,7 +250,163 @@ fn roundtrip_operation_pool() {
     let p: PersistedBeaconChain<CommonTypes<TestForkChoice, MinimalEthSpec>> =
         harness.chain.store.get(&key).unwrap().unwrap();
 
-    let restored_op_pool = p.op_pool.into_operation_pool(&p.state, &harness.spec);
+    let restored_op_pool = p
+        .op_pool()
+        .expect("should decode the op pool")
+        .into_operation_pool(&p.state, &harness.spec);
 
     assert_eq!(harness.chain.op_pool, restored_op_pool);
 }
+
+#[test]
+fn discards_undecodable_op_pool() {
+    let harness = get_harness(VALIDATOR_COUNT);
+    harness.extend_chain(
+        2,
+        BlockStrategy::OnCanonicalHead,
+        AttestationStrategy::AllValidators,
+    );
+    assert!(harness.chain.op_pool.num_attestations() > 0);
+    harness.chain.persist().unwrap();
+
+    // Mimic an op pool persisted in a layout which can no longer be decoded.
+    let key = Hash256::from_slice(&BEACON_CHAIN_DB_KEY.as_bytes());
+    let mut p: PersistedBeaconChain<CommonTypes<TestForkChoice, MinimalEthSpec>> =
+        harness.chain.store.get(&key).unwrap().unwrap();
+    p.op_pool_bytes = vec![1, 2, 3];
+    harness.chain.store.put(&key, &p).unwrap();
+
+    let log = slog::Logger::root(slog::Discard, slog::o!());
+    let chain = BeaconChain::<CommonTypes<TestForkChoice, MinimalEthSpec>>::from_store(
+        harness.chain.store.clone(),
+        harness.spec.clone(),
+        log,
+    )
+    .expect("should resume the chain")
+    .expect("the chain should be persisted");
+
+    assert_eq!(chain.op_pool.num_attestations(), 0);
+    assert_eq!(
+        chain.head().beacon_block_root,
+        harness.chain.head().beacon_block_root
+    );
+}
+
+#[test]
+fn regenerates_state_from_ancestor() {
+    let num_blocks_produced = MinimalEthSpec::slots_per_epoch() * 2;
+    let num_deleted_states = MinimalEthSpec::slots_per_epoch() as usize + 1;
+
+    let harness = get_harness(VALIDATOR_COUNT);
+
+    harness.extend_chain(
+        num_blocks_produced as usize,
+        BlockStrategy::OnCanonicalHead,
+        AttestationStrategy::AllValidators,
+    );
+
+    let store = harness.chain.store.clone();
+    let head_root = harness.chain.head().beacon_block_root;
+    let head_state_root = harness.chain.head().beacon_state_root;
+
+    // Remove the states of the most recent blocks, so they must be replayed.
+    let mut block_root = head_root;
+    for _ in 0..num_deleted_states {
+        let block: BeaconBlock<MinimalEthSpec> = store.get(&block_root).unwrap().unwrap();
+        store
+            .delete::<BeaconState<MinimalEthSpec>>(&block.state_root)
+            .unwrap();
+        block_root = block.parent_root;
+    }
+
+    let (state, stats) =
+        state_at_block_root::<MinimalEthSpec, _>(&*store, head_root, &harness.spec).unwrap();
+
+    assert_eq!(state.canonical_root(), head_state_root);
+    assert_eq!(stats.blocks_replayed, num_deleted_states);
+    assert_eq!(
+        stats.slots_processed, num_deleted_states as u64,
+        "one slot is processed per block"
+    );
+}
+
+#[test]
+fn notifies_head_subscribers() {
+    let harness = get_harness(VALIDATOR_COUNT);
+    let previous_block_root = harness.chain.head().beacon_block_root;
+    let events = harness.chain.subscribe_head();
+
+    harness.extend_chain(
+        2,
+        BlockStrategy::OnCanonicalHead,
+        AttestationStrategy::AllValidators,
+    );
+
+    let events = events
+        .wait()
+        .take(2)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let head = harness.chain.head();
+
+    assert_eq!(events[0].previous_block_root, previous_block_root);
+    assert_eq!(events[1].previous_block_root, events[0].block_root);
+    assert_eq!(events[1].block_root, head.beacon_block_root);
+    assert_eq!(events[1].state_root, head.beacon_state_root);
+    assert_eq!(events[1].slot, head.beacon_block.slot);
+    assert!(events.iter().all(|event| !event.reorg));
+    assert!(events.iter().all(|event| event.reorg_depth == 0));
+    assert_eq!(
+        events[1].common_ancestor_slot, events[0].slot,
+        "the common ancestor of a child is its parent"
+    );
+}
+
+#[test]
+fn finds_common_ancestor_of_forks() {
+    let harness = get_harness(VALIDATOR_COUNT);
+
+    let two_thirds = (VALIDATOR_COUNT / 3) * 2;
+    let honest_validators: Vec<usize> = (0..two_thirds).collect();
+    let faulty_validators: Vec<usize> = (two_thirds..VALIDATOR_COUNT).collect();
+
+    harness.extend_chain(
+        2,
+        BlockStrategy::OnCanonicalHead,
+        AttestationStrategy::AllValidators,
+    );
+    let fork_slot = harness.chain.head().beacon_block.slot;
+
+    let (honest_head, faulty_head) = harness.generate_two_forks_by_skipping_a_block(
+        &honest_validators,
+        &faulty_validators,
+        3,
+        2,
+    );
+
+    assert_eq!(
+        harness
+            .chain
+            .find_common_ancestor(honest_head, faulty_head)
+            .unwrap(),
+        (fork_slot, 3),
+        "re-orging away from the honest head removes all of its fork blocks"
+    );
+    assert_eq!(
+        harness
+            .chain
+            .find_common_ancestor(faulty_head, honest_head)
+            .unwrap(),
+        (fork_slot, 2)
+    );
+    assert_eq!(
+        harness
+            .chain
+            .find_common_ancestor(honest_head, honest_head)
+            .unwrap(),
+        (
+            harness.chain.get_block(&honest_head).unwrap().unwrap().slot,
+            0
+        )
+    );
+}
@@ -1,10 +1,13 @@
 use crate::Eth2Config;
+use beacon_chain::StateRootVerification;
 use clap::ArgMatches;
 use http_server::HttpServerConfig;
 use network::NetworkConfig;
 use serde_derive::{Deserialize, Serialize};
 use slog::{info, o, Drain};
+use std::fmt;
 use std::fs::{self, OpenOptions};
+use std::net::IpAddr;
 use std::path::PathBuf;
 use std::sync::Mutex;
 
@@ -27,6 +30,12 @@ pub struct Config {
     pub rpc: rpc::RPCConfig,
     pub http: HttpServerConfig,
     pub rest_api: rest_api::APIConfig,
+    #[serde(default)]
+    pub eth1: eth1::Config,
+    /// Which imported blocks have their state root verified. Anything but `Full` makes the node
+    /// non-validating.
+    #[serde(default)]
+    pub state_root_verification: StateRootVerification,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -50,6 +59,37 @@ pub enum GenesisState {
     Yaml { file: PathBuf },
 }
 
+/// A problem found when validating a `Config`, with a suggestion on how to fix it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ConfigProblem {
+    /// The setting (or settings) at fault.
+    pub setting: String,
+    /// A description of the problem.
+    pub problem: String,
+    /// A suggested fix.
+    pub suggestion: String,
+}
+
+impl ConfigProblem {
+    fn new(setting: &str, problem: String, suggestion: String) -> Self {
+        Self {
+            setting: setting.to_string(),
+            problem,
+            suggestion,
+        }
+    }
+}
+
+impl fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} ({})",
+            self.setting, self.problem, self.suggestion
+        )
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
@@ -61,10 +101,12 @@ impl Default for Config {
             rpc: rpc::RPCConfig::default(),
             http: HttpServerConfig::default(),
             rest_api: rest_api::APIConfig::default(),
+            eth1: eth1::Config::default(),
             spec_constants: TESTNET_SPEC_CONSTANTS.into(),
             genesis_state: GenesisState::RecentGenesis {
                 validator_count: TESTNET_VALIDATOR_COUNT,
             },
+            state_root_verification: StateRootVerification::default(),
         }
     }
 }
@@ -83,6 +125,150 @@ impl Config {
         Some(path)
     }
 
+    /// Cross-check all settings against each other and against `eth2_config`, returning every
+    /// problem found.
+    ///
+    /// Intended to be called once all config files and CLI args have been applied, so that all
+    /// problems may be reported at once rather than individually by each subsystem on startup.
+    pub fn validate(&self, eth2_config: &Eth2Config) -> Vec<ConfigProblem> {
+        let mut problems = vec![];
+
+        if self.spec_constants != eth2_config.spec_constants {
+            problems.push(ConfigProblem::new(
+                "spec_constants",
+                format!(
+                    "client config uses {:?} but the eth2 config uses {:?}",
+                    self.spec_constants, eth2_config.spec_constants
+                ),
+                "set both to the same value, or use --default-spec".to_string(),
+            ));
+        }
+
+        if eth2_config.spec.seconds_per_slot == 0 {
+            problems.push(ConfigProblem::new(
+                "seconds_per_slot",
+                "slot duration is zero".to_string(),
+                "set seconds_per_slot to a positive value in the eth2 config".to_string(),
+            ));
+        }
+
+        if self.db_type != "disk" && self.db_type != "memory" {
+            problems.push(ConfigProblem::new(
+                "db_type",
+                format!("unknown database type {:?}", self.db_type),
+                "use --db disk or --db memory".to_string(),
+            ));
+        }
+
+        match self.data_dir() {
+            Some(path) => {
+                let probe = path.join(".write_test");
+                if fs::write(&probe, b"")
+                    .and_then(|_| fs::remove_file(&probe))
+                    .is_err()
+                {
+                    problems.push(ConfigProblem::new(
+                        "data_dir",
+                        format!("{:?} is not writable", path),
+                        "fix the directory permissions or choose another --datadir".to_string(),
+                    ));
+                }
+            }
+            None => problems.push(ConfigProblem::new(
+                "data_dir",
+                format!("unable to create {:?}", self.data_dir),
+                "choose another --datadir".to_string(),
+            )),
+        }
+
+        if let GenesisState::Yaml { file } = &self.genesis_state {
+            if !file.is_file() {
+                problems.push(ConfigProblem::new(
+                    "genesis_state",
+                    format!("genesis state file {:?} does not exist", file),
+                    "check the path to the YAML genesis state".to_string(),
+                ));
+            }
+        }
+
+        if self.state_root_verification.is_sampled() && self.rpc.enabled {
+            problems.push(ConfigProblem::new(
+                "state_root_verification",
+                "a node which samples state roots must not serve validators".to_string(),
+                "remove --state-root-sample-rate or --rpc".to_string(),
+            ));
+        }
+
+        if self.eth1.enabled && !self.eth1.is_valid_deposit_contract_address() {
+            problems.push(ConfigProblem::new(
+                "eth1.deposit_contract_address",
+                format!(
+                    "{:?} is not a deposit contract address",
+                    self.eth1.deposit_contract_address
+                ),
+                "set --deposit-contract to the 0x-prefixed address of the contract".to_string(),
+            ));
+        }
+
+        problems.append(&mut self.port_problems());
+
+        problems
+    }
+
+    /// Returns a problem for each pair of enabled TCP servers which would bind to the same port.
+    fn port_problems(&self) -> Vec<ConfigProblem> {
+        let mut problems = vec![];
+
+        let mut servers: Vec<(&str, IpAddr, u16)> = vec![(
+            "network.libp2p_port",
+            self.network.listen_address,
+            self.network.libp2p_port,
+        )];
+        if self.rpc.enabled {
+            servers.push(("rpc.port", self.rpc.listen_address.into(), self.rpc.port));
+        }
+        if self.rest_api.enabled {
+            servers.push((
+                "rest_api.port",
+                self.rest_api.listen_address.into(),
+                self.rest_api.port,
+            ));
+        }
+        if self.http.enabled {
+            match (
+                self.http.listen_address.parse::<IpAddr>(),
+                self.http.listen_port.parse::<u16>(),
+            ) {
+                (Ok(address), Ok(port)) => servers.push(("http.listen_port", address, port)),
+                _ => problems.push(ConfigProblem::new(
+                    "http",
+                    format!(
+                        "invalid metrics server address {}:{}",
+                        self.http.listen_address, self.http.listen_port
+                    ),
+                    "set --http-address to an IP address and --http-port to a u16".to_string(),
+                )),
+            }
+        }
+
+        for (i, (name_a, address_a, port_a)) in servers.iter().enumerate() {
+            for (name_b, address_b, port_b) in &servers[i + 1..] {
+                let overlapping = address_a == address_b
+                    || address_a.is_unspecified()
+                    || address_b.is_unspecified();
+                if port_a == port_b && overlapping {
+                    problems.push(ConfigProblem::new(
+                        &format!("{}, {}", name_a, name_b),
+                        format!("both servers listen on port {}", port_a),
+                        format!("change {} or {} to a free port", name_a, name_b),
+                    ));
+                }
+            }
+        }
+
+        problems
+    }
+
     // Update the logger to output in JSON to specified file
     fn update_logger(&mut self, log: &mut slog::Logger) -> Result<(), &'static str> {
         let file = OpenOptions::new()
@@ -141,10 +327,20 @@ impl Config {
             self.db_type = dir.to_string();
         };
 
+        if let Some(rate) = args.value_of("state-root-sample-rate") {
+            let one_in = rate
+                .parse::<u64>()
+                .ok()
+                .filter(|one_in| *one_in > 0)
+                .ok_or_else(|| format!("Invalid state root sample rate: {}", rate))?;
+            self.state_root_verification = StateRootVerification::Sampled { one_in };
+        }
+
         self.network.apply_cli_args(args)?;
         self.rpc.apply_cli_args(args)?;
         self.http.apply_cli_args(args)?;
         self.rest_api.apply_cli_args(args)?;
+        self.eth1.apply_cli_args(args)?;
 
         if let Some(log_file) = args.value_of("logfile") {
             self.log_file = PathBuf::from(log_file);
@@ -154,3 +350,40 @@ impl Config {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_all_port_conflicts() {
+        let mut config = Config::default();
+        config.rpc.enabled = true;
+        config.rpc.port = config.rest_api.port;
+        config.http.enabled = true;
+        config.http.listen_port = config.network.libp2p_port.to_string();
+
+        let problems = config.port_problems();
+        let settings = problems
+            .iter()
+            .map(|problem| problem.setting.as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            settings,
+            vec![
+                "network.libp2p_port, http.listen_port",
+                "rpc.port, rest_api.port"
+            ]
+        );
+    }
+
+    #[test]
+    fn default_ports_do_not_conflict() {
+        let mut config = Config::default();
+        config.rpc.enabled = true;
+        config.http.enabled = true;
+
+        assert_eq!(config.port_problems(), vec![]);
+    }
+}
@@ -1,9 +1,29 @@
-use network;
+//! Errors which prevent the client from starting.
+use thiserror::Error;
 
-use error_chain::error_chain;
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("unable to start the network service: {0}")]
+    Network(#[from] network::error::Error),
+    #[error("unable to start the eth1 service: {0}")]
+    Eth1(String),
+    #[error("unable to start the RPC server: {0}")]
+    Rpc(String),
+    /// Any other failure to start, described by its message.
+    #[error("{0}")]
+    Message(String),
+}
+
+impl From<String> for Error {
+    fn from(message: String) -> Error {
+        Error::Message(message)
+    }
+}
 
-error_chain! {
-   links  {
-       Network(network::error::Error, network::error::ErrorKind);
-   }
+impl From<&str> for Error {
+    fn from(message: &str) -> Error {
+        Error::Message(message.to_string())
+    }
 }
+
+pub type Result<T> = std::result::Result<T, Error>;
@@ -5,6 +5,7 @@ mod config;
 
 pub mod error;
 pub mod notifier;
+pub mod readiness;
 
 use beacon_chain::BeaconChain;
 use exit_future::Signal;
@@ -22,7 +23,7 @@ use tokio::timer::Interval;
 pub use beacon_chain::BeaconChainTypes;
 pub use beacon_chain_types::ClientType;
 pub use beacon_chain_types::InitialiseBeaconChain;
-pub use config::Config as ClientConfig;
+pub use config::{Config as ClientConfig, ConfigProblem};
 pub use eth2_config::Eth2Config;
 
 /// Main beacon node client service. This provides the connection and initialisation of the clients
@@ -42,6 +43,8 @@ pub struct Client<T: BeaconChainTypes> {
     pub slot_timer_exit_signal: Option<Signal>,
     /// Signal to terminate the API
     pub api_exit_signal: Option<Signal>,
+    /// Signal to stop following the deposit contract.
+    pub eth1_exit_signal: Option<Signal>,
     /// The clients logger.
     log: slog::Logger,
     /// Marker to pin the beacon chain generics.
@@ -65,12 +68,24 @@ where
         let seconds_per_slot = eth2_config.spec.seconds_per_slot;
 
         // Load a `BeaconChain` from the store, or create a new one if it does not exist.
-        let beacon_chain = Arc::new(T::initialise_beacon_chain(
+        let mut beacon_chain = T::initialise_beacon_chain(
             store,
             &client_config,
             eth2_config.spec.clone(),
             log.clone(),
-        )?);
+        )?;
+        beacon_chain.set_state_root_verification(client_config.state_root_verification);
+        let eth1 = if client_config.eth1.enabled {
+            let eth1 =
+                eth1::Service::new(client_config.eth1.clone(), log.new(o!("Service" => "Eth1")))
+                    .map_err(error::Error::Eth1)?;
+            let eth1 = Arc::new(eth1);
+            beacon_chain.set_eth1_service(eth1.clone());
+            Some(eth1)
+        } else {
+            None
+        };
+        let beacon_chain = Arc::new(beacon_chain);
         // Registry all beacon chain metrics with the global registry.
         beacon_chain
             .metrics
@@ -113,13 +128,16 @@ where
 
         // spawn the RPC server
         let rpc_exit_signal = if client_config.rpc.enabled {
-            Some(rpc::start_server(
-                &client_config.rpc,
-                executor,
-                network_send.clone(),
-                beacon_chain.clone(),
-                &log,
-            ))
+            Some(
+                rpc::start_server(
+                    &client_config.rpc,
+                    executor,
+                    network_send.clone(),
+                    beacon_chain.clone(),
+                    &log,
+                )
+                .map_err(error::Error::Rpc)?,
+            )
         } else {
             None
         };
@@ -159,6 +177,13 @@ where
             None
         };
 
+        // Start following the deposit contract.
+        let eth1_exit_signal = eth1.map(|eth1| {
+            let (signal, exit) = exit_future::signal();
+            eth1::Service::start(eth1, executor, exit);
+            signal
+        });
+
         let (slot_timer_exit_signal, exit) = exit_future::signal();
         if let Ok(Some(duration_to_next_slot)) = beacon_chain.slot_clock.duration_to_next_slot() {
             // set up the validator work interval - start at next slot and proceed every slot
@@ -192,6 +217,7 @@ where
             rpc_exit_signal,
             slot_timer_exit_signal: Some(slot_timer_exit_signal),
             api_exit_signal,
+            eth1_exit_signal,
             log,
             network,
             phantom: PhantomData,
@@ -0,0 +1,56 @@
+use crate::Client;
+use beacon_chain::{BeaconChain, BeaconChainTypes};
+use exit_future::Exit;
+use futures::Future;
+use slog::{info, o, warn};
+use system_service::{Probe, ServiceManager};
+use tokio::runtime::TaskExecutor;
+use types::EthSpec;
+
+/// Reports readiness to the service manager which started the node (if any) once it is synced,
+/// and feeds its watchdog whilst the beacon chain is responsive.
+pub fn run<T: BeaconChainTypes + Send + Sync + 'static>(
+    client: &Client<T>,
+    executor: TaskExecutor,
+    exit: Exit,
+) {
+    let log = client.log.new(o!("Service" => "Readiness"));
+
+    let manager = match ServiceManager::detect() {
+        Ok(Some(manager)) => manager,
+        Ok(None) => return,
+        Err(e) => {
+            warn!(log, "Unable to contact the service manager"; "error" => e.to_string());
+            return;
+        }
+    };
+    info!(log, "Reporting readiness to the service manager");
+
+    let chain = client.beacon_chain.clone();
+    let monitor = system_service::monitor(manager, move || probe(&chain), log);
+
+    executor.spawn(exit.until(monitor).map(|_| ()));
+}
+
+/// The node is ready once its head is within an epoch of the present slot.
+///
+/// Reading the head blocks if the beacon chain is deadlocked, which stops the watchdog being fed.
+fn probe<T: BeaconChainTypes>(chain: &BeaconChain<T>) -> Probe {
+    let head_slot = chain.head().beacon_block.slot;
+
+    match chain.read_slot_clock() {
+        Some(present_slot) => {
+            let distance = present_slot.as_u64().saturating_sub(head_slot.as_u64());
+            Probe {
+                live: true,
+                ready: distance <= T::EthSpec::slots_per_epoch(),
+                status: format!("head slot {}, {} slots behind", head_slot, distance),
+            }
+        }
+        None => Probe {
+            live: false,
+            ready: false,
+            status: "unable to read the slot clock".to_string(),
+        },
+    }
+}
@@ -0,0 +1,329 @@
+//! The eth1 blocks and deposits from which blocks take their eth1 data votes and deposits.
+use crate::deposit_cache::{DepositCache, DepositLog, Error};
+use std::cmp;
+use std::collections::VecDeque;
+use types::{BeaconState, Deposit, Eth1Data, EthSpec, Hash256, Unsigned};
+
+/// An eth1 block, with the deposits made up to and including it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Eth1Block {
+    pub hash: Hash256,
+    pub number: u64,
+    pub deposit_root: Hash256,
+    pub deposit_count: u64,
+}
+
+impl Eth1Block {
+    /// Returns the eth1 data which votes for this block.
+    pub fn eth1_data(&self) -> Eth1Data {
+        Eth1Data {
+            deposit_root: self.deposit_root,
+            deposit_count: self.deposit_count,
+            block_hash: self.hash,
+        }
+    }
+}
+
+/// The deposits of the deposit contract and the most recent blocks of the eth1 chain, up to the
+/// follow distance from its head.
+#[derive(Debug, Clone)]
+pub struct Eth1Cache {
+    deposits: DepositCache,
+    /// The blocks with consecutive numbers, oldest first.
+    blocks: VecDeque<Eth1Block>,
+    /// The number of the head of the eth1 chain when the cache was last updated.
+    head_number: u64,
+    /// The number of the last block whose deposits are in `deposits`.
+    last_log_block: Option<u64>,
+    /// The distance from the head of blocks to be voted for.
+    follow_distance: u64,
+    /// The maximum number of blocks kept.
+    max_blocks: usize,
+}
+
+impl Eth1Cache {
+    /// Creates an empty cache which keeps at most `max_blocks` blocks.
+    pub fn new(follow_distance: u64, max_blocks: usize) -> Self {
+        Self {
+            deposits: DepositCache::default(),
+            blocks: VecDeque::new(),
+            head_number: 0,
+            last_log_block: None,
+            follow_distance,
+            max_blocks,
+        }
+    }
+
+    pub fn deposits(&self) -> &DepositCache {
+        &self.deposits
+    }
+
+    /// Returns the most recent block, if any.
+    pub fn latest_block(&self) -> Option<&Eth1Block> {
+        self.blocks.back()
+    }
+
+    pub fn head_number(&self) -> u64 {
+        self.head_number
+    }
+
+    pub fn set_head_number(&mut self, head_number: u64) {
+        self.head_number = head_number;
+    }
+
+    /// Returns the number of the last block whose deposits are known.
+    pub fn last_log_block(&self) -> Option<u64> {
+        self.last_log_block
+    }
+
+    /// Adds the deposits made in blocks up to and including `to_block`.
+    pub fn insert_logs(&mut self, logs: Vec<DepositLog>, to_block: u64) -> Result<(), Error> {
+        for log in logs {
+            self.deposits.insert_log(log)?;
+        }
+        self.last_log_block = Some(to_block);
+        Ok(())
+    }
+
+    /// Adds the block with `hash` and `number`, which must be the next block or replace a block
+    /// known (in a re-org).
+    ///
+    /// The deposits made up to the block must be known.
+    pub fn insert_block(&mut self, hash: Hash256, number: u64) -> Result<(), Error> {
+        if self.last_log_block.map_or(true, |last| last < number) {
+            return Err(Error::DepositsUnknown {
+                deposit_count: self.deposits.len() as u64 + 1,
+                known_deposits: self.deposits.len() as u64,
+            });
+        }
+
+        let deposit_count = self.deposits.deposit_count_at_block(number);
+        let block = Eth1Block {
+            hash,
+            number,
+            deposit_root: self.deposits.deposit_root(deposit_count)?,
+            deposit_count,
+        };
+
+        match self.blocks.front().map(|first| first.number) {
+            // Remove the blocks replaced by a re-org, or all blocks after a gap.
+            Some(first) if number >= first && number <= first + self.blocks.len() as u64 => {
+                self.blocks.truncate((number - first) as usize);
+            }
+            _ => self.blocks.clear(),
+        }
+        self.blocks.push_back(block);
+        while self.blocks.len() > self.max_blocks {
+            self.blocks.pop_front();
+        }
+        Ok(())
+    }
+
+    /// Returns the block at `distance` from the head.
+    fn block_at_distance(&self, distance: u64) -> Option<&Eth1Block> {
+        let number = self.head_number.checked_sub(distance)?;
+        let first = self.blocks.front()?.number;
+        self.blocks.get(number.checked_sub(first)? as usize)
+    }
+
+    /// Returns the eth1 data that a block on `state` should vote for, or `None` if no block is
+    /// known at the follow distance.
+    ///
+    /// Spec v0.8.1 `get_eth1_vote`.
+    pub fn eth1_data_vote<E: EthSpec>(&self, state: &BeaconState<E>) -> Option<Eth1Data> {
+        let follow_distance = self.follow_distance;
+        let latest = self.block_at_distance(follow_distance)?;
+
+        // If the block of the current eth1 data is unknown, all known blocks are candidates.
+        let previous_distance = self
+            .blocks
+            .iter()
+            .find(|block| block.hash == state.eth1_data.block_hash)
+            .map_or(u64::max_value(), |block| {
+                self.head_number.saturating_sub(block.number)
+            });
+
+        // The candidates, the most recent first.
+        let candidates = |to_distance: u64| {
+            self.blocks
+                .iter()
+                .rev()
+                .skip_while(move |block| block.number > latest.number)
+                .take_while(move |block| self.head_number - block.number < to_distance)
+                .map(Eth1Block::eth1_data)
+                .collect::<Vec<_>>()
+        };
+        let new_eth1_data = candidates(2 * follow_distance);
+        let all_eth1_data = candidates(previous_distance);
+
+        let period = E::SlotsPerEth1VotingPeriod::to_u64();
+        let valid_votes: Vec<&Eth1Data> = state
+            .eth1_data_votes
+            .iter()
+            .enumerate()
+            .filter(|(slot, vote)| {
+                let period_tail = *slot as u64 % period >= integer_squareroot(period);
+                new_eth1_data.contains(vote) || (period_tail && all_eth1_data.contains(vote))
+            })
+            .map(|(_, vote)| vote)
+            .collect();
+
+        // The most common vote, and of those the most recent.
+        valid_votes
+            .iter()
+            .max_by_key(|vote| {
+                let count = valid_votes.iter().filter(|other| other == vote).count();
+                let age = all_eth1_data
+                    .iter()
+                    .position(|eth1_data| eth1_data == **vote)
+                    .unwrap_or(usize::max_value());
+                (count, cmp::Reverse(age))
+            })
+            .map(|vote| (**vote).clone())
+            .or_else(|| Some(latest.eth1_data()))
+    }
+
+    /// Returns the deposits that a block on `state` voting for `eth1_data_vote` must include.
+    ///
+    /// Returns an error if the deposits known do not match the eth1 data of the state, before or
+    /// after the vote, so a block would be invalid.
+    pub fn deposits_for_block<E: EthSpec>(
+        &self,
+        state: &BeaconState<E>,
+        eth1_data_vote: &Eth1Data,
+    ) -> Result<Vec<Deposit>, Error> {
+        self.deposits.check_eth1_data(&state.eth1_data)?;
+
+        // The vote is counted before the deposits are processed, and may change the eth1 data.
+        let votes = state
+            .eth1_data_votes
+            .iter()
+            .filter(|vote| *vote == eth1_data_vote)
+            .count()
+            + 1;
+        let eth1_data = if votes * 2 > E::SlotsPerEth1VotingPeriod::to_usize() {
+            self.deposits.check_eth1_data(eth1_data_vote)?;
+            eth1_data_vote
+        } else {
+            &state.eth1_data
+        };
+
+        let start = state.eth1_deposit_index;
+        let end = cmp::min(
+            eth1_data.deposit_count,
+            start.saturating_add(E::MaxDeposits::to_u64()),
+        );
+        if end <= start {
+            return Ok(vec![]);
+        }
+        self.deposits
+            .get_deposits(start..end, eth1_data.deposit_count)
+    }
+}
+
+/// Spec v0.8.1 `integer_squareroot`.
+fn integer_squareroot(n: u64) -> u64 {
+    let mut x = n;
+    let mut y = (x + 1) / 2;
+    while y < x {
+        x = y;
+        y = (x + n / x) / 2;
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bls::{PublicKeyBytes, SignatureBytes};
+    use types::{DepositData, EthSpec, MinimalEthSpec};
+
+    fn log(index: u64, block_number: u64) -> DepositLog {
+        DepositLog {
+            deposit_data: DepositData {
+                pubkey: PublicKeyBytes::from_bytes(&[index as u8; 48]).unwrap(),
+                withdrawal_credentials: Hash256::zero(),
+                amount: 32_000_000_000,
+                signature: SignatureBytes::from_bytes(&[0; 96]).unwrap(),
+            },
+            block_number,
+            index,
+        }
+    }
+
+    fn state() -> BeaconState<MinimalEthSpec> {
+        BeaconState::new(0, Eth1Data::default(), &MinimalEthSpec::default_spec())
+    }
+
+    /// A cache with a deposit in each of blocks 1 to 20, following 4 blocks behind block 24.
+    fn cache() -> Eth1Cache {
+        let mut cache = Eth1Cache::new(4, 16);
+        let logs = (0..20).map(|i| log(i, i + 1)).collect();
+        cache.insert_logs(logs, 20).unwrap();
+        for number in 0..=20 {
+            cache
+                .insert_block(Hash256::from_low_u64_be(number), number)
+                .unwrap();
+        }
+        cache.set_head_number(24);
+        cache
+    }
+
+    #[test]
+    fn keeps_consecutive_blocks() {
+        let mut cache = cache();
+        assert_eq!(cache.blocks.len(), 16);
+        assert_eq!(cache.blocks.front().unwrap().number, 5);
+
+        // A re-org replaces the later blocks.
+        cache.insert_block(Hash256::repeat_byte(1), 18).unwrap();
+        assert_eq!(cache.latest_block().unwrap().number, 18);
+        assert_eq!(cache.blocks.len(), 14);
+
+        assert!(cache.insert_block(Hash256::zero(), 21).is_err());
+    }
+
+    #[test]
+    fn votes_for_the_most_common_valid_vote() {
+        let cache = cache();
+        let mut state = state();
+
+        // Without votes, the block at the follow distance.
+        let latest = cache.block_at_distance(4).unwrap().eth1_data();
+        assert_eq!(latest.deposit_count, 20);
+        assert_eq!(cache.eth1_data_vote(&state), Some(latest.clone()));
+
+        let older = cache.block_at_distance(6).unwrap().eth1_data();
+        let too_old = cache.block_at_distance(12).unwrap().eth1_data();
+        // `too_old` is only valid in the tail of the voting period, so `older` is the most common.
+        for vote in &[
+            older.clone(),
+            too_old.clone(),
+            too_old,
+            older.clone(),
+            latest,
+        ] {
+            state.eth1_data_votes.push(vote.clone()).unwrap();
+        }
+        assert_eq!(cache.eth1_data_vote(&state), Some(older));
+    }
+
+    #[test]
+    fn includes_deposits_up_to_the_eth1_data() {
+        let cache = cache();
+        let mut state = state();
+        state.eth1_data = cache.blocks[0].eth1_data();
+        state.eth1_deposit_index = 1;
+
+        let vote = cache.block_at_distance(4).unwrap().eth1_data();
+        let deposits = cache.deposits_for_block(&state, &vote).unwrap();
+        assert_eq!(deposits.len(), 4);
+        assert_eq!(deposits[0].data, log(1, 2).deposit_data);
+
+        state.eth1_data.deposit_root = Hash256::repeat_byte(1);
+        assert!(match cache.deposits_for_block(&state, &vote) {
+            Err(Error::DepositRootMismatch { .. }) => true,
+            _ => false,
+        });
+    }
+}
@@ -0,0 +1,71 @@
+use clap::ArgMatches;
+use serde_derive::{Deserialize, Serialize};
+
+/// Eth1 endpoint configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    /// Follow the deposit contract, so that produced blocks vote for eth1 data and include
+    /// deposits.
+    pub enabled: bool,
+    /// The HTTP JSON-RPC endpoint of an eth1 node.
+    pub endpoint: String,
+    /// The address of the deposit contract, as a `0x`-prefixed hex string.
+    pub deposit_contract_address: String,
+    /// The eth1 block in which the deposit contract was deployed, from which deposits are read.
+    pub deposit_contract_deploy_block: u64,
+    /// The distance from the eth1 head of the blocks voted for (`ETH1_FOLLOW_DISTANCE`).
+    pub follow_distance: u64,
+    /// The number of eth1 blocks kept as candidates for a vote.
+    pub block_cache_len: usize,
+    /// The most blocks whose deposit logs are requested at once.
+    pub log_chunk_size: u64,
+    /// The seconds between each update from the eth1 node.
+    pub update_interval_secs: u64,
+    /// The seconds to wait for each request to the eth1 node.
+    pub request_timeout_secs: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            enabled: false,
+            endpoint: "http://localhost:8545".to_string(),
+            deposit_contract_address: String::new(),
+            deposit_contract_deploy_block: 0,
+            follow_distance: 1024,
+            block_cache_len: 4096,
+            log_chunk_size: 1000,
+            update_interval_secs: 7,
+            request_timeout_secs: 10,
+        }
+    }
+}
+
+impl Config {
+    pub fn apply_cli_args(&mut self, args: &ArgMatches) -> Result<(), &'static str> {
+        if let Some(endpoint) = args.value_of("eth1-endpoint") {
+            self.enabled = true;
+            self.endpoint = endpoint.to_string();
+        }
+
+        if let Some(address) = args.value_of("deposit-contract") {
+            self.deposit_contract_address = address.to_string();
+        }
+
+        if let Some(block) = args.value_of("deposit-contract-deploy-block") {
+            self.deposit_contract_deploy_block = block
+                .parse::<u64>()
+                .map_err(|_| "deposit-contract-deploy-block is not a valid u64.")?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns `true` if `deposit_contract_address` is a `0x`-prefixed, 20 byte hex string.
+    pub fn is_valid_deposit_contract_address(&self) -> bool {
+        let address = &self.deposit_contract_address;
+        address.len() == 42
+            && address.starts_with("0x")
+            && address[2..].chars().all(|c| c.is_ascii_hexdigit())
+    }
+}
@@ -0,0 +1,310 @@
+//! The deposits made to the deposit contract, and the tree of their roots.
+use crate::deposit_tree::DepositTree;
+use bls::{PublicKeyBytes, SignatureBytes};
+use std::ops::Range;
+use tree_hash::TreeHash;
+use types::{Deposit, DepositData, Eth1Data, FixedVector, Hash256};
+
+/// The number of fields in a `DepositEvent`.
+const DEPOSIT_EVENT_FIELDS: usize = 5;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Error {
+    /// A log was not the next deposit, so some deposits are missing.
+    NonConsecutiveLog { log_index: u64, expected: u64 },
+    /// A log has the index of a known deposit, but other data.
+    ConflictingLog(u64),
+    /// A log could not be decoded as a `DepositEvent`.
+    InvalidLog(String),
+    /// Eth1 data (or a block) requires more deposits than are known.
+    DepositsUnknown {
+        deposit_count: u64,
+        known_deposits: u64,
+    },
+    /// Eth1 data has another deposit root to the deposits known, so the beacon chain follows
+    /// another deposit contract (or eth1 chain) to the cache.
+    DepositRootMismatch {
+        deposit_count: u64,
+        state_root: Hash256,
+        cache_root: Hash256,
+    },
+}
+
+/// A `DepositEvent` of the deposit contract.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DepositLog {
+    pub deposit_data: DepositData,
+    /// The number of the eth1 block which included the deposit.
+    pub block_number: u64,
+    /// The index of the deposit, counting all deposits to the contract.
+    pub index: u64,
+}
+
+impl DepositLog {
+    /// Decodes the (ABI-encoded) data of a `DepositEvent` log, included in the eth1 block
+    /// `block_number`.
+    ///
+    /// The event is `DepositEvent(bytes pubkey, bytes withdrawal_credentials, bytes amount, bytes
+    /// signature, bytes index)`, where `amount` and `index` are little-endian.
+    pub fn from_log_data(data: &[u8], block_number: u64) -> Result<Self, Error> {
+        let fields = (0..DEPOSIT_EVENT_FIELDS)
+            .map(|i| abi_bytes(data, i))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| Error::InvalidLog("malformed event data".to_string()))?;
+
+        let u64_field = |bytes: &[u8], name: &str| {
+            if bytes.len() == 8 {
+                let mut array = [0; 8];
+                array.copy_from_slice(bytes);
+                Ok(u64::from_le_bytes(array))
+            } else {
+                Err(Error::InvalidLog(format!("{} is not 8 bytes", name)))
+            }
+        };
+        if fields[1].len() != 32 {
+            return Err(Error::InvalidLog(
+                "withdrawal credentials are not 32 bytes".to_string(),
+            ));
+        }
+
+        let deposit_data = DepositData {
+            pubkey: PublicKeyBytes::from_bytes(fields[0])
+                .map_err(|e| Error::InvalidLog(format!("invalid pubkey: {:?}", e)))?,
+            withdrawal_credentials: Hash256::from_slice(fields[1]),
+            amount: u64_field(fields[2], "amount")?,
+            signature: SignatureBytes::from_bytes(fields[3])
+                .map_err(|e| Error::InvalidLog(format!("invalid signature: {:?}", e)))?,
+        };
+
+        Ok(Self {
+            deposit_data,
+            block_number,
+            index: u64_field(fields[4], "index")?,
+        })
+    }
+}
+
+/// Returns the `i`th `bytes` value of ABI-encoded `data` made only of `bytes` values.
+fn abi_bytes(data: &[u8], i: usize) -> Option<&[u8]> {
+    let word = |at: usize| -> Option<usize> {
+        let word = data.get(at..at + 32)?;
+        // Offsets and lengths beyond 8 bytes cannot be within `data`.
+        if word[..24].iter().any(|byte| *byte != 0) {
+            return None;
+        }
+        let mut array = [0; 8];
+        array.copy_from_slice(&word[24..]);
+        Some(u64::from_be_bytes(array) as usize)
+    };
+
+    let offset = word(i * 32)?;
+    let len = word(offset)?;
+    data.get(offset + 32..offset.checked_add(32 + len)?)
+}
+
+/// All deposits made to the deposit contract, in order, up to some eth1 block.
+#[derive(Debug, Default, Clone)]
+pub struct DepositCache {
+    logs: Vec<DepositLog>,
+    tree: DepositTree,
+}
+
+impl DepositCache {
+    /// Returns the number of deposits known.
+    pub fn len(&self) -> usize {
+        self.logs.len()
+    }
+
+    /// Returns `true` if no deposits are known.
+    pub fn is_empty(&self) -> bool {
+        self.logs.is_empty()
+    }
+
+    /// Adds the next deposit, ignoring a deposit which is already known.
+    pub fn insert_log(&mut self, log: DepositLog) -> Result<(), Error> {
+        let expected = self.logs.len() as u64;
+        if log.index == expected {
+            self.tree
+                .push(Hash256::from_slice(&log.deposit_data.tree_hash_root()));
+            self.logs.push(log);
+            Ok(())
+        } else if log.index < expected {
+            if self.logs[log.index as usize] == log {
+                Ok(())
+            } else {
+                Err(Error::ConflictingLog(log.index))
+            }
+        } else {
+            Err(Error::NonConsecutiveLog {
+                log_index: log.index,
+                expected,
+            })
+        }
+    }
+
+    /// Returns the number of deposits included in eth1 blocks up to `block_number`.
+    pub fn deposit_count_at_block(&self, block_number: u64) -> u64 {
+        // The logs are ordered by block number, so no log compares equal.
+        match self.logs.binary_search_by(|log| {
+            if log.block_number <= block_number {
+                std::cmp::Ordering::Less
+            } else {
+                std::cmp::Ordering::Greater
+            }
+        }) {
+            Ok(count) | Err(count) => count as u64,
+        }
+    }
+
+    /// Returns the deposit root of the first `deposit_count` deposits.
+    pub fn deposit_root(&self, deposit_count: u64) -> Result<Hash256, Error> {
+        self.tree
+            .root(deposit_count as usize)
+            .ok_or_else(|| self.unknown(deposit_count))
+    }
+
+    /// Checks that `eth1_data` (of a `BeaconState`) has the deposit root of the deposits known.
+    pub fn check_eth1_data(&self, eth1_data: &Eth1Data) -> Result<(), Error> {
+        let cache_root = self.deposit_root(eth1_data.deposit_count)?;
+        if cache_root == eth1_data.deposit_root {
+            Ok(())
+        } else {
+            Err(Error::DepositRootMismatch {
+                deposit_count: eth1_data.deposit_count,
+                state_root: eth1_data.deposit_root,
+                cache_root,
+            })
+        }
+    }
+
+    /// Returns the deposits in `indices`, with proofs against the deposit root of the first
+    /// `deposit_count` deposits.
+    pub fn get_deposits(
+        &self,
+        indices: Range<u64>,
+        deposit_count: u64,
+    ) -> Result<Vec<Deposit>, Error> {
+        let proofs = self
+            .tree
+            .proofs(
+                indices.start as usize..indices.end as usize,
+                deposit_count as usize,
+            )
+            .ok_or_else(|| self.unknown(std::cmp::max(indices.end, deposit_count)))?;
+
+        Ok(self.logs[indices.start as usize..indices.end as usize]
+            .iter()
+            .zip(proofs)
+            .map(|(log, proof)| Deposit {
+                proof: FixedVector::from(proof),
+                data: log.deposit_data.clone(),
+            })
+            .collect())
+    }
+
+    fn unknown(&self, deposit_count: u64) -> Error {
+        Error::DepositsUnknown {
+            deposit_count,
+            known_deposits: self.logs.len() as u64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the ABI encoding of a `DepositEvent` with the given fields.
+    fn encode_event(fields: &[&[u8]]) -> Vec<u8> {
+        let word = |value: usize| {
+            let mut word = vec![0; 32];
+            word[24..].copy_from_slice(&(value as u64).to_be_bytes());
+            word
+        };
+
+        let mut head = vec![];
+        let mut tail = vec![];
+        for field in fields {
+            head.extend(word(fields.len() * 32 + tail.len()));
+            tail.extend(word(field.len()));
+            tail.extend_from_slice(field);
+            tail.resize((tail.len() + 31) / 32 * 32, 0);
+        }
+        head.extend(tail);
+        head
+    }
+
+    fn log(index: u64, block_number: u64) -> DepositLog {
+        DepositLog {
+            deposit_data: DepositData {
+                pubkey: PublicKeyBytes::from_bytes(&[index as u8; 48]).unwrap(),
+                withdrawal_credentials: Hash256::from_low_u64_be(index),
+                amount: 32_000_000_000,
+                signature: SignatureBytes::from_bytes(&[7; 96]).unwrap(),
+            },
+            block_number,
+            index,
+        }
+    }
+
+    #[test]
+    fn decodes_deposit_events() {
+        let expected = log(3, 100);
+        let data = encode_event(&[
+            &[3; 48],
+            expected.deposit_data.withdrawal_credentials.as_bytes(),
+            &32_000_000_000u64.to_le_bytes(),
+            &[7; 96],
+            &3u64.to_le_bytes(),
+        ]);
+
+        assert_eq!(DepositLog::from_log_data(&data, 100), Ok(expected));
+        assert!(DepositLog::from_log_data(&data[..data.len() - 32], 100).is_err());
+    }
+
+    #[test]
+    fn checks_logs_and_eth1_data() {
+        let mut cache = DepositCache::default();
+        cache.insert_log(log(0, 10)).unwrap();
+        cache.insert_log(log(1, 10)).unwrap();
+        cache.insert_log(log(2, 12)).unwrap();
+        assert_eq!(cache.insert_log(log(1, 10)), Ok(()));
+        assert_eq!(cache.insert_log(log(1, 11)), Err(Error::ConflictingLog(1)));
+        assert_eq!(
+            cache.insert_log(log(4, 13)),
+            Err(Error::NonConsecutiveLog {
+                log_index: 4,
+                expected: 3
+            })
+        );
+
+        assert_eq!(cache.deposit_count_at_block(9), 0);
+        assert_eq!(cache.deposit_count_at_block(11), 2);
+        assert_eq!(cache.deposit_count_at_block(12), 3);
+
+        let mut eth1_data = Eth1Data {
+            deposit_root: cache.deposit_root(2).unwrap(),
+            deposit_count: 2,
+            block_hash: Hash256::zero(),
+        };
+        assert_eq!(cache.check_eth1_data(&eth1_data), Ok(()));
+        eth1_data.deposit_count = 3;
+        assert!(match cache.check_eth1_data(&eth1_data) {
+            Err(Error::DepositRootMismatch { .. }) => true,
+            _ => false,
+        });
+        eth1_data.deposit_count = 4;
+        assert_eq!(
+            cache.check_eth1_data(&eth1_data),
+            Err(Error::DepositsUnknown {
+                deposit_count: 4,
+                known_deposits: 3
+            })
+        );
+
+        let deposits = cache.get_deposits(1..3, 3).unwrap();
+        assert_eq!(deposits.len(), 2);
+        assert_eq!(deposits[0].data, log(1, 10).deposit_data);
+        assert!(cache.get_deposits(1..4, 4).is_err());
+    }
+}
@@ -0,0 +1,180 @@
+//! The Merkle tree of deposit data roots which the deposit contract maintains.
+//!
+//! The root after each deposit is kept, so that the root may be found for any deposit count. Proofs
+//! are generated from the leaves, against the tree of any number of deposits.
+use eth2_hashing::hash;
+use types::Hash256;
+
+/// The depth of the deposit tree, not counting the mix-in of its length.
+///
+/// Spec v0.8.1 `DEPOSIT_CONTRACT_TREE_DEPTH`.
+pub const DEPOSIT_TREE_DEPTH: usize = 32;
+
+/// An append-only tree of deposit data roots.
+#[derive(Debug, Clone)]
+pub struct DepositTree {
+    leaves: Vec<Hash256>,
+    /// `roots[n]` is the root of the tree of the first `n` leaves, with its length mixed in.
+    roots: Vec<Hash256>,
+    /// The rightmost non-empty node at each height, as in the deposit contract.
+    branch: [Hash256; DEPOSIT_TREE_DEPTH],
+    /// `zero_hashes[h]` is the root of an empty tree of height `h`.
+    zero_hashes: [Hash256; DEPOSIT_TREE_DEPTH + 1],
+}
+
+impl Default for DepositTree {
+    fn default() -> Self {
+        let mut zero_hashes = [Hash256::zero(); DEPOSIT_TREE_DEPTH + 1];
+        for height in 1..=DEPOSIT_TREE_DEPTH {
+            zero_hashes[height] = hash_pair(zero_hashes[height - 1], zero_hashes[height - 1]);
+        }
+
+        let mut tree = Self {
+            leaves: vec![],
+            roots: vec![],
+            branch: [Hash256::zero(); DEPOSIT_TREE_DEPTH],
+            zero_hashes,
+        };
+        tree.roots.push(tree.current_root());
+        tree
+    }
+}
+
+impl DepositTree {
+    /// Returns the number of leaves in the tree.
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    /// Returns `true` if the tree has no leaves.
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    /// Appends a leaf (a deposit data root) to the tree.
+    pub fn push(&mut self, leaf: Hash256) {
+        self.leaves.push(leaf);
+
+        let mut size = self.leaves.len();
+        let mut node = leaf;
+        for height in 0..DEPOSIT_TREE_DEPTH {
+            if size & 1 == 1 {
+                self.branch[height] = node;
+                break;
+            }
+            node = hash_pair(self.branch[height], node);
+            size /= 2;
+        }
+
+        let root = self.current_root();
+        self.roots.push(root);
+    }
+
+    /// Returns the root of the tree of the first `count` leaves, or `None` if there are fewer
+    /// leaves.
+    pub fn root(&self, count: usize) -> Option<Hash256> {
+        self.roots.get(count).copied()
+    }
+
+    /// Returns a proof of each leaf in `indices` against the tree of the first `count` leaves, or
+    /// `None` if there are fewer leaves or an index is not below `count`.
+    ///
+    /// Each proof has `DEPOSIT_TREE_DEPTH + 1` nodes, the last being the mixed-in `count`, as
+    /// expected by `process_deposit`.
+    pub fn proofs(
+        &self,
+        indices: std::ops::Range<usize>,
+        count: usize,
+    ) -> Option<Vec<Vec<Hash256>>> {
+        if count > self.len() || indices.start > indices.end || indices.end > count {
+            return None;
+        }
+
+        // Build each layer of the tree, leaving out the empty nodes to the right.
+        let mut layers = vec![self.leaves[..count].to_vec()];
+        for height in 0..DEPOSIT_TREE_DEPTH {
+            let layer = layers[height]
+                .chunks(2)
+                .map(|pair| hash_pair(pair[0], *pair.get(1).unwrap_or(&self.zero_hashes[height])))
+                .collect();
+            layers.push(layer);
+        }
+
+        Some(
+            indices
+                .map(|index| {
+                    let mut proof: Vec<Hash256> = (0..DEPOSIT_TREE_DEPTH)
+                        .map(|height| {
+                            let sibling = (index >> height) ^ 1;
+                            *layers[height]
+                                .get(sibling)
+                                .unwrap_or(&self.zero_hashes[height])
+                        })
+                        .collect();
+                    proof.push(length_node(count));
+                    proof
+                })
+                .collect(),
+        )
+    }
+
+    /// Returns the root of the tree of all its leaves, as the deposit contract computes it.
+    fn current_root(&self) -> Hash256 {
+        let mut size = self.leaves.len();
+        let mut node = Hash256::zero();
+        for height in 0..DEPOSIT_TREE_DEPTH {
+            node = if size & 1 == 1 {
+                hash_pair(self.branch[height], node)
+            } else {
+                hash_pair(node, self.zero_hashes[height])
+            };
+            size /= 2;
+        }
+        hash_pair(node, length_node(self.leaves.len()))
+    }
+}
+
+/// Returns the node mixing in the length of the tree, a little-endian `count`.
+fn length_node(count: usize) -> Hash256 {
+    let mut node = Hash256::zero();
+    node.as_bytes_mut()[0..8].copy_from_slice(&(count as u64).to_le_bytes());
+    node
+}
+
+fn hash_pair(left: Hash256, right: Hash256) -> Hash256 {
+    let mut input = left.as_bytes().to_vec();
+    input.extend_from_slice(right.as_bytes());
+    Hash256::from_slice(&hash(&input))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use merkle_proof::verify_merkle_proof;
+
+    #[test]
+    fn proofs_verify_against_the_root_of_each_count() {
+        let mut tree = DepositTree::default();
+        for i in 0..9 {
+            tree.push(Hash256::from_low_u64_be(i + 1));
+        }
+
+        for count in 0..=tree.len() {
+            let root = tree.root(count).expect("should have root");
+            let proofs = tree.proofs(0..count, count).expect("should have proofs");
+            for (index, proof) in proofs.iter().enumerate() {
+                assert!(verify_merkle_proof(
+                    Hash256::from_low_u64_be(index as u64 + 1),
+                    proof,
+                    DEPOSIT_TREE_DEPTH + 1,
+                    index,
+                    root
+                ));
+            }
+        }
+
+        assert_eq!(tree.root(10), None);
+        assert_eq!(tree.proofs(0..3, 10), None);
+        assert_eq!(tree.proofs(0..4, 3), None);
+    }
+}
@@ -0,0 +1,132 @@
+//! The JSON-RPC methods of an eth1 node used to follow the deposit contract.
+//!
+//! Requests are made with the asynchronous `reqwest` client, so they must be polled on a runtime.
+use crate::deposit_cache::DepositLog;
+use futures::{future, Future};
+use reqwest::r#async::Client;
+use serde_json::{json, Value};
+use std::time::Duration;
+use types::Hash256;
+
+/// `keccak256("DepositEvent(bytes,bytes,bytes,bytes,bytes)")`, the topic of deposit logs.
+pub const DEPOSIT_EVENT_TOPIC: &str =
+    "0x649bbc62d0e31342afea4e5cd82d4049e7e1ee912fc0889aa790803be39038c5";
+
+/// A future resolving to the result of a JSON-RPC request, or a description of its failure.
+pub type RpcFuture<T> = Box<dyn Future<Item = T, Error = String> + Send>;
+
+/// The eth1 node of an endpoint.
+#[derive(Clone)]
+pub struct Eth1Node {
+    client: Client,
+    endpoint: String,
+}
+
+impl Eth1Node {
+    pub fn new(endpoint: String, timeout: Duration) -> Result<Self, String> {
+        let client = Client::builder()
+            .timeout(timeout)
+            .build()
+            .map_err(|e| format!("unable to build HTTP client: {:?}", e))?;
+        Ok(Self { client, endpoint })
+    }
+
+    /// Returns the number of the head block.
+    pub fn block_number(&self) -> RpcFuture<u64> {
+        Box::new(
+            self.request("eth_blockNumber", json!([]))
+                .and_then(|result| hex_u64(&result)),
+        )
+    }
+
+    /// Returns the hash of the block with `number`.
+    pub fn block_hash(&self, number: u64) -> RpcFuture<Hash256> {
+        Box::new(
+            self.request("eth_getBlockByNumber", json!([to_hex(number), false]))
+                .and_then(|result| hex_bytes(&result["hash"]))
+                .and_then(|hash| {
+                    if hash.len() == 32 {
+                        Ok(Hash256::from_slice(&hash))
+                    } else {
+                        Err(format!("block hash is {} bytes", hash.len()))
+                    }
+                }),
+        )
+    }
+
+    /// Returns the deposits made to `deposit_contract` in blocks `from_block` to `to_block`
+    /// (inclusive).
+    pub fn deposit_logs(
+        &self,
+        deposit_contract: &str,
+        from_block: u64,
+        to_block: u64,
+    ) -> RpcFuture<Vec<DepositLog>> {
+        let filter = json!([{
+            "address": deposit_contract,
+            "topics": [DEPOSIT_EVENT_TOPIC],
+            "fromBlock": to_hex(from_block),
+            "toBlock": to_hex(to_block),
+        }]);
+
+        Box::new(self.request("eth_getLogs", filter).and_then(|result| {
+            result
+                .as_array()
+                .ok_or_else(|| "logs are not an array".to_string())?
+                .iter()
+                .map(|log| {
+                    let data = hex_bytes(&log["data"])?;
+                    let block_number = hex_u64(&log["blockNumber"])?;
+                    DepositLog::from_log_data(&data, block_number)
+                        .map_err(|e| format!("invalid deposit log: {:?}", e))
+                })
+                .collect()
+        }))
+    }
+
+    fn request(&self, method: &str, params: Value) -> RpcFuture<Value> {
+        let body = json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": params,
+            "id": 1,
+        });
+        let method = method.to_string();
+
+        Box::new(
+            self.client
+                .post(&self.endpoint)
+                .json(&body)
+                .send()
+                .and_then(|response| response.error_for_status())
+                .and_then(|mut response| response.json::<Value>())
+                .map_err(|e| format!("{:?}", e))
+                .and_then(move |mut response| match response.get("error") {
+                    Some(error) => future::err(format!("{} failed: {}", method, error)),
+                    None => future::ok(response["result"].take()),
+                }),
+        )
+    }
+}
+
+fn to_hex(n: u64) -> String {
+    format!("0x{:x}", n)
+}
+
+/// Decodes a JSON-RPC quantity, such as `"0x1b4"`.
+fn hex_u64(value: &Value) -> Result<u64, String> {
+    value
+        .as_str()
+        .and_then(|s| s.get(2..))
+        .and_then(|s| u64::from_str_radix(s, 16).ok())
+        .ok_or_else(|| format!("invalid quantity: {}", value))
+}
+
+/// Decodes JSON-RPC data, such as `"0x0123"`.
+fn hex_bytes(value: &Value) -> Result<Vec<u8>, String> {
+    value
+        .as_str()
+        .and_then(|s| s.get(2..))
+        .and_then(|s| hex::decode(s).ok())
+        .ok_or_else(|| format!("invalid data: {}", value))
+}
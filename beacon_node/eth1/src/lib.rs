@@ -0,0 +1,24 @@
+//! Follows the deposit contract on the eth1 chain, so that produced blocks may vote for eth1 data
+//! and include deposits.
+//!
+//! The `Service` reads the deposit logs and blocks from an eth1 node over HTTP JSON-RPC, up to the
+//! follow distance from its head, into an `Eth1Cache`. The cache holds the Merkle tree of the
+//! deposits, from which the deposit root at each block and the proof of each deposit are found.
+//!
+//! Before deposits are included in a block, the deposit roots of the cache are checked against the
+//! eth1 data of the state. A cache which does not match the state (which follows another deposit
+//! contract or eth1 chain, or has not yet read enough deposits) returns an error, so that no
+//! invalid block is produced for signing.
+mod cache;
+mod config;
+mod deposit_cache;
+mod deposit_tree;
+mod http;
+mod service;
+
+pub use cache::{Eth1Block, Eth1Cache};
+pub use config::Config;
+pub use deposit_cache::{DepositCache, DepositLog, Error};
+pub use deposit_tree::{DepositTree, DEPOSIT_TREE_DEPTH};
+pub use http::{Eth1Node, DEPOSIT_EVENT_TOPIC};
+pub use service::Service;
@@ -0,0 +1,150 @@
+use crate::cache::Eth1Cache;
+use crate::config::Config;
+use crate::http::Eth1Node;
+use exit_future::Exit;
+use futures::{stream, Future, Stream};
+use parking_lot::{RwLock, RwLockReadGuard};
+use slog::{debug, info, warn, Logger};
+use std::cmp;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::runtime::TaskExecutor;
+use tokio::timer::Interval;
+
+/// Keeps an `Eth1Cache` up to date with an eth1 node.
+pub struct Service {
+    config: Config,
+    node: Eth1Node,
+    cache: RwLock<Eth1Cache>,
+    log: Logger,
+}
+
+impl Service {
+    pub fn new(config: Config, log: Logger) -> Result<Self, String> {
+        let node = Eth1Node::new(
+            config.endpoint.clone(),
+            Duration::from_secs(config.request_timeout_secs),
+        )?;
+        let cache = Eth1Cache::new(config.follow_distance, config.block_cache_len);
+
+        Ok(Self {
+            config,
+            node,
+            cache: RwLock::new(cache),
+            log,
+        })
+    }
+
+    /// Returns the deposits and blocks read from the eth1 node so far.
+    pub fn cache(&self) -> RwLockReadGuard<Eth1Cache> {
+        self.cache.read()
+    }
+
+    /// Updates the cache every `update_interval_secs` until `exit`.
+    pub fn start(service: Arc<Self>, executor: &TaskExecutor, exit: Exit) {
+        info!(
+            service.log,
+            "Following the deposit contract";
+            "endpoint" => &service.config.endpoint,
+            "deposit_contract" => &service.config.deposit_contract_address,
+        );
+
+        let interval = Interval::new(
+            Instant::now(),
+            Duration::from_secs(service.config.update_interval_secs),
+        );
+        executor.spawn(
+            exit.until(interval.map_err(|_| ()).for_each(move |_| {
+                let log = service.log.clone();
+                Self::update(service.clone()).then(move |result| {
+                    if let Err(e) = result {
+                        warn!(log, "Unable to update the eth1 cache"; "error" => e);
+                    }
+                    Ok(())
+                })
+            }))
+            .map(|_| ()),
+        );
+    }
+
+    /// Reads the deposits and blocks up to the follow distance from the head of the eth1 chain.
+    pub fn update(service: Arc<Self>) -> impl Future<Item = (), Error = String> {
+        service.node.block_number().and_then(move |head| {
+            let target = head.saturating_sub(service.config.follow_distance);
+            let blocks_service = service.clone();
+
+            Self::update_deposits(service.clone(), target)
+                .and_then(move |()| Self::update_blocks(blocks_service, target))
+                .map(move |()| {
+                    let mut cache = service.cache.write();
+                    cache.set_head_number(head);
+                    debug!(
+                        service.log,
+                        "Updated the eth1 cache";
+                        "head" => head,
+                        "deposits" => cache.deposits().len(),
+                        "latest_block" => cache.latest_block().map(|block| block.number),
+                    );
+                })
+        })
+    }
+
+    /// Reads the deposits made in blocks up to `target`.
+    fn update_deposits(service: Arc<Self>, target: u64) -> impl Future<Item = (), Error = String> {
+        let from = service
+            .cache()
+            .last_log_block()
+            .map_or(service.config.deposit_contract_deploy_block, |last| {
+                last + 1
+            });
+        let chunk_size = cmp::max(service.config.log_chunk_size, 1);
+        let chunks: Vec<(u64, u64)> = (from..=target)
+            .step_by(chunk_size as usize)
+            .map(|start| (start, cmp::min(start + chunk_size - 1, target)))
+            .collect();
+
+        stream::iter_ok(chunks).for_each(move |(from, to)| {
+            let service = service.clone();
+            service
+                .node
+                .deposit_logs(&service.config.deposit_contract_address, from, to)
+                .and_then(move |logs| {
+                    service
+                        .cache
+                        .write()
+                        .insert_logs(logs, to)
+                        .map_err(|e| format!("inconsistent deposit logs: {:?}", e))
+                })
+        })
+    }
+
+    /// Reads the blocks up to `target` (or the last block whose deposits are known).
+    fn update_blocks(service: Arc<Self>, target: u64) -> impl Future<Item = (), Error = String> {
+        let (next, last) = {
+            let cache = service.cache();
+            (
+                cache.latest_block().map_or(0, |block| block.number + 1),
+                cache.last_log_block(),
+            )
+        };
+        let first = cmp::max(
+            next,
+            (target + 1).saturating_sub(service.config.block_cache_len as u64),
+        );
+        let numbers = match last {
+            Some(last) => first..cmp::min(target, last) + 1,
+            None => 0..0,
+        };
+
+        stream::iter_ok(numbers).for_each(move |number| {
+            let service = service.clone();
+            service.node.block_hash(number).and_then(move |hash| {
+                service
+                    .cache
+                    .write()
+                    .insert_block(hash, number)
+                    .map_err(|e| format!("unable to add eth1 block {}: {:?}", number, e))
+            })
+        })
+    }
+}
@@ -1,8 +1,12 @@
 use crate::discovery::Discovery;
-use crate::rpc::{RPCEvent, RPCMessage, RPC};
+use crate::peer_manager::subnet_id;
+use crate::rpc::methods::GoodbyeReason;
+use crate::rpc::{RPCEvent, RPCMessage, RPCRequest, RPC};
 use crate::{error, NetworkConfig};
 use crate::{Topic, TopicHash};
-use crate::{BEACON_ATTESTATION_TOPIC, BEACON_BLOCK_TOPIC};
+use crate::{
+    ATTESTER_SLASHING_TOPIC, BEACON_ATTESTATION_TOPIC, BEACON_BLOCK_TOPIC, PROPOSER_SLASHING_TOPIC,
+};
 use futures::prelude::*;
 use libp2p::{
     core::identity::Keypair,
@@ -14,12 +18,19 @@ use libp2p::{
     tokio_io::{AsyncRead, AsyncWrite},
     NetworkBehaviour, PeerId,
 };
-use slog::{debug, o, trace};
+use slog::{debug, o, trace, warn};
 use ssz::{ssz_encode, Encode};
 use std::num::NonZeroU32;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use tokio_timer::{Delay, DelayQueue};
 
 const MAX_IDENTIFY_ADDRESSES: usize = 20;
+/// Seconds between checks for peers in excess of the target.
+const PRUNE_INTERVAL: u64 = 30;
+/// Seconds a pruned peer is given to receive its `Goodbye`, before it is disconnected.
+const GOODBYE_TIMEOUT: u64 = 2;
+/// The score penalty of a peer for an RPC error.
+const RPC_ERROR_PENALTY: i32 = -10;
 
 /// Builds the network behaviour that manages the core protocols of eth2.
 /// This core behaviour is managed by `Behaviour` which adds peer management to all core
@@ -42,6 +53,12 @@ pub struct Behaviour<TSubstream: AsyncRead + AsyncWrite> {
     #[behaviour(ignore)]
     /// The events generated by this behaviour to be consumed in the swarm poll.
     events: Vec<BehaviourEvent>,
+    /// The delay until peers in excess of the target are next pruned.
+    #[behaviour(ignore)]
+    prune_delay: Delay,
+    /// The pruned peers which are disconnected once they have been sent a `Goodbye`.
+    #[behaviour(ignore)]
+    goodbyes: DelayQueue<PeerId>,
     /// Logger for behaviour actions.
     #[behaviour(ignore)]
     log: slog::Logger,
@@ -75,6 +92,8 @@ impl<TSubstream: AsyncRead + AsyncWrite> Behaviour<TSubstream> {
             ping: Ping::new(ping_config),
             identify,
             events: Vec::new(),
+            prune_delay: Delay::new(Instant::now() + Duration::from_secs(PRUNE_INTERVAL)),
+            goodbyes: DelayQueue::new(),
             log: behaviour_log,
         })
     }
@@ -97,8 +116,20 @@ impl<TSubstream: AsyncRead + AsyncWrite> NetworkBehaviourEventProcess<GossipsubE
                     message: msg,
                 });
             }
-            GossipsubEvent::Subscribed { .. } => {}
-            GossipsubEvent::Unsubscribed { .. } => {}
+            GossipsubEvent::Subscribed { peer_id, topic } => {
+                if let Some(subnet) = subnet_id(topic.as_str()) {
+                    self.discovery
+                        .peer_manager_mut()
+                        .subscribe(&peer_id, subnet);
+                }
+            }
+            GossipsubEvent::Unsubscribed { peer_id, topic } => {
+                if let Some(subnet) = subnet_id(topic.as_str()) {
+                    self.discovery
+                        .peer_manager_mut()
+                        .unsubscribe(&peer_id, subnet);
+                }
+            }
         }
     }
 }
@@ -115,6 +146,11 @@ impl<TSubstream: AsyncRead + AsyncWrite> NetworkBehaviourEventProcess<RPCMessage
                 self.events.push(BehaviourEvent::PeerDisconnected(peer_id))
             }
             RPCMessage::RPC(peer_id, rpc_event) => {
+                if let RPCEvent::Error(..) = rpc_event {
+                    self.discovery
+                        .peer_manager_mut()
+                        .adjust_score(&peer_id, RPC_ERROR_PENALTY);
+                }
                 self.events.push(BehaviourEvent::RPC(peer_id, rpc_event))
             }
         }
@@ -134,6 +170,36 @@ impl<TSubstream: AsyncRead + AsyncWrite> Behaviour<TSubstream> {
     fn poll<TBehaviourIn>(
         &mut self,
     ) -> Async<NetworkBehaviourAction<TBehaviourIn, BehaviourEvent>> {
+        // prune peers in excess of the target if it is time
+        loop {
+            match self.prune_delay.poll() {
+                Ok(Async::Ready(_)) => {
+                    self.prune_peers();
+                    self.prune_delay
+                        .reset(Instant::now() + Duration::from_secs(PRUNE_INTERVAL));
+                }
+                Ok(Async::NotReady) => break,
+                Err(e) => {
+                    warn!(self.log, "Peer pruning timer failed: {:?}", e);
+                    break;
+                }
+            }
+        }
+
+        // disconnect pruned peers once they have been sent a goodbye
+        loop {
+            match self.goodbyes.poll() {
+                Ok(Async::Ready(Some(expired))) => self
+                    .events
+                    .push(BehaviourEvent::Disconnect(expired.into_inner())),
+                Ok(Async::Ready(None)) | Ok(Async::NotReady) => break,
+                Err(e) => {
+                    warn!(self.log, "Peer disconnection timer failed: {:?}", e);
+                    break;
+                }
+            }
+        }
+
         if !self.events.is_empty() {
             return Async::Ready(NetworkBehaviourAction::GenerateEvent(self.events.remove(0)));
         }
@@ -183,10 +249,23 @@ impl<TSubstream: AsyncRead + AsyncWrite> Behaviour<TSubstream> {
     /* Pubsub behaviour functions */
 
     /// Subscribes to a gossipsub topic.
+    ///
+    /// Peers covering subscribed shard subnets are protected from pruning.
     pub fn subscribe(&mut self, topic: Topic) -> bool {
+        if let Some(subnet) = subnet_id(topic.no_hash().as_str()) {
+            self.discovery.peer_manager_mut().add_subnet(subnet);
+        }
         self.gossipsub.subscribe(topic)
     }
 
+    /// Unsubscribes from a gossipsub topic.
+    pub fn unsubscribe(&mut self, topic: Topic) -> bool {
+        if let Some(subnet) = subnet_id(topic.no_hash().as_str()) {
+            self.discovery.peer_manager_mut().remove_subnet(subnet);
+        }
+        self.gossipsub.unsubscribe(topic)
+    }
+
     /// Publishes a message on the pubsub (gossipsub) behaviour.
     pub fn publish(&mut self, topics: Vec<Topic>, message: PubsubMessage) {
         let message_bytes = ssz_encode(&message);
@@ -206,6 +285,20 @@ impl<TSubstream: AsyncRead + AsyncWrite> Behaviour<TSubstream> {
     pub fn connected_peers(&self) -> usize {
         self.discovery.connected_peers()
     }
+
+    /// Sends a `Goodbye` to peers in excess of the target number of peers, and disconnects them
+    /// after `GOODBYE_TIMEOUT`.
+    fn prune_peers(&mut self) {
+        for peer_id in self.discovery.peer_manager_mut().prune() {
+            debug!(self.log, "Pruning excess peer"; "peer" => format!("{:?}", peer_id));
+            self.eth2_rpc.send_rpc(
+                peer_id.clone(),
+                RPCEvent::Request(0, RPCRequest::Goodbye(GoodbyeReason::TooManyPeers)),
+            );
+            self.goodbyes
+                .insert(peer_id, Duration::from_secs(GOODBYE_TIMEOUT));
+        }
+    }
 }
 
 /// The types of events than can be obtained from polling the behaviour.
@@ -213,6 +306,8 @@ pub enum BehaviourEvent {
     RPC(PeerId, RPCEvent),
     PeerDialed(PeerId),
     PeerDisconnected(PeerId),
+    /// The peer should be disconnected, which the swarm does.
+    Disconnect(PeerId),
     GossipMessage {
         source: PeerId,
         topics: Vec<TopicHash>,
@@ -227,6 +322,10 @@ pub enum PubsubMessage {
     Block(Vec<u8>),
     /// Gossipsub message providing notification of a new attestation.
     Attestation(Vec<u8>),
+    /// Gossipsub message providing notification of a new proposer slashing.
+    ProposerSlashing(Vec<u8>),
+    /// Gossipsub message providing notification of a new attester slashing.
+    AttesterSlashing(Vec<u8>),
     /// Gossipsub message from an unknown topic.
     Unknown(Vec<u8>),
 }
@@ -243,6 +342,10 @@ impl PubsubMessage {
             match topic.as_str() {
                 BEACON_BLOCK_TOPIC => return PubsubMessage::Block(data),
                 BEACON_ATTESTATION_TOPIC => return PubsubMessage::Attestation(data),
+                PROPOSER_SLASHING_TOPIC => return PubsubMessage::ProposerSlashing(data),
+                ATTESTER_SLASHING_TOPIC => return PubsubMessage::AttesterSlashing(data),
+                // Attestations are published to the subnet of their shard too.
+                topic if subnet_id(topic).is_some() => return PubsubMessage::Attestation(data),
                 _ => {}
             }
         }
@@ -259,6 +362,8 @@ impl Encode for PubsubMessage {
         match self {
             PubsubMessage::Block(inner)
             | PubsubMessage::Attestation(inner)
+            | PubsubMessage::ProposerSlashing(inner)
+            | PubsubMessage::AttesterSlashing(inner)
             | PubsubMessage::Unknown(inner) => {
                 // Encode the gossip as a Vec<u8>;
                 buf.append(&mut inner.as_ssz_bytes());
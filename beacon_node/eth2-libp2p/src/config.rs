@@ -1,3 +1,4 @@
+use crate::trusted_peers::parse_trusted_peers;
 use clap::ArgMatches;
 use enr::Enr;
 use libp2p::gossipsub::{GossipsubConfig, GossipsubConfigBuilder};
@@ -10,6 +11,8 @@ use std::time::Duration;
 pub const BEACON_BLOCK_TOPIC: &str = "beacon_block";
 pub const BEACON_ATTESTATION_TOPIC: &str = "beacon_attestation";
 pub const SHARD_TOPIC_PREFIX: &str = "shard";
+pub const PROPOSER_SLASHING_TOPIC: &str = "proposer_slashing";
+pub const ATTESTER_SLASHING_TOPIC: &str = "attester_slashing";
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(default)]
@@ -30,8 +33,9 @@ pub struct Config {
     /// UDP port that discovery listens on.
     pub discovery_port: u16,
 
-    /// Target number of connected peers.
-    pub max_peers: usize,
+    /// Target number of connected peers, excluding trusted peers. Excess peers are pruned.
+    #[serde(alias = "max_peers")]
+    pub target_peers: usize,
 
     /// Gossipsub configuration parameters.
     #[serde(skip)]
@@ -43,6 +47,12 @@ pub struct Config {
     /// List of libp2p nodes to initially connect to.
     pub libp2p_nodes: Vec<Multiaddr>,
 
+    /// Multiaddrs (ending in `/p2p/<peer id>`) of peers that are always connected to.
+    pub trusted_peers: Vec<Multiaddr>,
+
+    /// ENRs of peers that are always connected to.
+    pub trusted_enrs: Vec<Enr>,
+
     /// Client version
     pub client_version: String,
 
@@ -62,7 +72,7 @@ impl Default for Config {
             libp2p_port: 9000,
             discovery_address: "127.0.0.1".parse().expect("valid ip address"),
             discovery_port: 9000,
-            max_peers: 10,
+            target_peers: 10,
             //TODO: Set realistic values for production
             // Note: This defaults topics to plain strings. Not hashes
             gs_config: GossipsubConfigBuilder::new()
@@ -71,6 +81,8 @@ impl Default for Config {
                 .build(),
             boot_nodes: vec![],
             libp2p_nodes: vec![],
+            trusted_peers: vec![],
+            trusted_enrs: vec![],
             client_version: version::version(),
             topics: Vec::new(),
         }
@@ -102,10 +114,10 @@ impl Config {
             self.discovery_address = listen_address;
         }
 
-        if let Some(max_peers_str) = args.value_of("maxpeers") {
-            self.max_peers = max_peers_str
+        if let Some(target_peers_str) = args.value_of("target-peers") {
+            self.target_peers = target_peers_str
                 .parse::<usize>()
-                .map_err(|_| format!("Invalid number of max peers: {}", max_peers_str))?;
+                .map_err(|_| format!("Invalid number of target peers: {}", target_peers_str))?;
         }
 
         if let Some(port_str) = args.value_of("port") {
@@ -134,6 +146,12 @@ impl Config {
                 .collect::<Result<Vec<Multiaddr>, _>>()?;
         }
 
+        if let Some(trusted_peers_str) = args.value_of("trusted-peers") {
+            let (trusted_peers, trusted_enrs) = parse_trusted_peers(trusted_peers_str)?;
+            self.trusted_peers = trusted_peers;
+            self.trusted_enrs = trusted_enrs;
+        }
+
         if let Some(topics_str) = args.value_of("topics") {
             self.topics = topics_str.split(',').map(|s| s.into()).collect();
         }
@@ -1,4 +1,5 @@
-use crate::{error, NetworkConfig};
+use crate::peer_manager::PeerManager;
+use crate::{error, NetworkConfig, TrustedPeers};
 /// This manages the discovery and management of peers.
 ///
 /// Currently using discv5 for peer discovery.
@@ -10,7 +11,7 @@ use libp2p::enr::{Enr, EnrBuilder, NodeId};
 use libp2p::multiaddr::Protocol;
 use libp2p::swarm::{NetworkBehaviour, NetworkBehaviourAction, PollParameters, ProtocolsHandler};
 use slog::{debug, info, o, warn};
-use std::collections::HashSet;
+use std::collections::VecDeque;
 use std::fs::File;
 use std::io::prelude::*;
 use std::path::Path;
@@ -23,17 +24,26 @@ use tokio_timer::Delay;
 const MAX_TIME_BETWEEN_PEER_SEARCHES: u64 = 60;
 /// Initial delay between peer searches.
 const INITIAL_SEARCH_DELAY: u64 = 5;
+/// Seconds between attempts to reconnect to disconnected trusted peers.
+const TRUSTED_PEER_REDIAL_INTERVAL: u64 = 30;
 /// Local ENR storage filename.
 const ENR_FILENAME: &str = "enr.dat";
 
 /// Lighthouse discovery behaviour. This provides peer management and discovery using the Discv5
 /// libp2p protocol.
 pub struct Discovery<TSubstream> {
-    /// The peers currently connected to libp2p streams.
-    connected_peers: HashSet<PeerId>,
+    /// The peers currently connected to libp2p streams. Peers are only dialed whilst there are
+    /// fewer than the target number of peers, and the excess is pruned.
+    peer_manager: PeerManager,
 
-    /// The target number of connected peers on the libp2p interface.
-    max_peers: usize,
+    /// Peers which are always dialed, regardless of the target number of peers.
+    trusted_peers: TrustedPeers,
+
+    /// Trusted peers waiting to be dialed.
+    trusted_dial_queue: VecDeque<PeerId>,
+
+    /// The delay until disconnected trusted peers are next redialed.
+    trusted_redial_delay: Delay,
 
     /// directory to save ENR to
     enr_dir: String,
@@ -66,7 +76,7 @@ impl<TSubstream> Discovery<TSubstream> {
         let log = log.new(o!("Service" => "Libp2p-Discovery"));
 
         // checks if current ENR matches that found on disk
-        let local_enr = load_enr(local_key, config, &log)?;
+        let local_enr = load_enr(local_key, config, &log).map_err(error::Error::Enr)?;
 
         let enr_dir = match config.network_dir.to_str() {
             Some(path) => String::from(path),
@@ -78,7 +88,7 @@ impl<TSubstream> Discovery<TSubstream> {
         debug!(log, "Local ENR seq: {}", local_enr.seq());
 
         let mut discovery = Discv5::new(local_enr, local_key.clone(), config.listen_address)
-            .map_err(|e| format!("Discv5 service failed: {:?}", e))?;
+            .map_err(|e| error::Error::Discovery(format!("{:?}", e)))?;
 
         // Add bootnodes to routing table
         for bootnode_enr in config.boot_nodes.clone() {
@@ -90,9 +100,21 @@ impl<TSubstream> Discovery<TSubstream> {
             discovery.add_enr(bootnode_enr);
         }
 
+        let trusted_peers = TrustedPeers::from_config(config)?;
+        for trusted_enr in config.trusted_enrs.clone() {
+            discovery.add_enr(trusted_enr);
+        }
+        if !trusted_peers.is_empty() {
+            info!(log, "Trusted peers: {}", trusted_peers.len());
+        }
+
         Ok(Self {
-            connected_peers: HashSet::new(),
-            max_peers: config.max_peers,
+            peer_manager: PeerManager::new(config.target_peers, trusted_peers.clone()),
+            trusted_dial_queue: trusted_peers.peer_ids().cloned().collect(),
+            trusted_peers,
+            trusted_redial_delay: Delay::new(
+                Instant::now() + Duration::from_secs(TRUSTED_PEER_REDIAL_INTERVAL),
+            ),
             peer_discovery_delay: Delay::new(Instant::now()),
             past_discovery_delay: INITIAL_SEARCH_DELAY,
             tcp_port: config.libp2p_port,
@@ -116,7 +138,23 @@ impl<TSubstream> Discovery<TSubstream> {
 
     /// The current number of connected libp2p peers.
     pub fn connected_peers(&self) -> usize {
-        self.connected_peers.len()
+        self.peer_manager.connected_peers()
+    }
+
+    /// The connected libp2p peers.
+    pub fn peer_manager_mut(&mut self) -> &mut PeerManager {
+        &mut self.peer_manager
+    }
+
+    /// Queue all disconnected trusted peers to be dialed.
+    fn queue_trusted_peers(&mut self) {
+        for peer_id in self.trusted_peers.peer_ids() {
+            if !self.peer_manager.is_connected(peer_id)
+                && !self.trusted_dial_queue.contains(peer_id)
+            {
+                self.trusted_dial_queue.push_back(peer_id.clone());
+            }
+        }
     }
 
     /// Search for new peers using the underlying discovery mechanism.
@@ -154,15 +192,24 @@ where
 
     fn addresses_of_peer(&mut self, peer_id: &PeerId) -> Vec<Multiaddr> {
         // Let discovery track possible known peers.
-        self.discovery.addresses_of_peer(peer_id)
+        let mut addresses = self.trusted_peers.addresses(peer_id);
+        addresses.extend(self.discovery.addresses_of_peer(peer_id));
+        addresses
     }
 
-    fn inject_connected(&mut self, peer_id: PeerId, _endpoint: ConnectedPoint) {
-        self.connected_peers.insert(peer_id);
+    fn inject_connected(&mut self, peer_id: PeerId, endpoint: ConnectedPoint) {
+        let outbound = match endpoint {
+            ConnectedPoint::Dialer { .. } => true,
+            ConnectedPoint::Listener { .. } => false,
+        };
+        self.peer_manager.connect(peer_id, outbound);
     }
 
     fn inject_disconnected(&mut self, peer_id: &PeerId, _endpoint: ConnectedPoint) {
-        self.connected_peers.remove(peer_id);
+        self.peer_manager.disconnect(peer_id);
+        if self.trusted_peers.contains(peer_id) {
+            info!(self.log, "Trusted peer disconnected"; "Peer" => format!("{:?}", peer_id));
+        }
     }
 
     fn inject_replaced(
@@ -191,11 +238,34 @@ where
             Self::OutEvent,
         >,
     > {
+        // queue disconnected trusted peers if it is time
+        loop {
+            match self.trusted_redial_delay.poll() {
+                Ok(Async::Ready(_)) => {
+                    self.queue_trusted_peers();
+                    self.trusted_redial_delay
+                        .reset(Instant::now() + Duration::from_secs(TRUSTED_PEER_REDIAL_INTERVAL));
+                }
+                Ok(Async::NotReady) => break,
+                Err(e) => {
+                    warn!(self.log, "Trusted peer redial timer failed: {:?}", e);
+                }
+            }
+        }
+
+        // dial trusted peers, regardless of the number of connected peers
+        while let Some(peer_id) = self.trusted_dial_queue.pop_front() {
+            if !self.peer_manager.is_connected(&peer_id) {
+                debug!(self.log, "Dialing trusted peer"; "Peer" => format!("{:?}", peer_id));
+                return Async::Ready(NetworkBehaviourAction::DialPeer { peer_id });
+            }
+        }
+
         // search for peers if it is time
         loop {
             match self.peer_discovery_delay.poll() {
                 Ok(Async::Ready(_)) => {
-                    if self.connected_peers.len() < self.max_peers {
+                    if self.peer_manager.needs_peers() {
                         self.find_peers();
                     }
                 }
@@ -233,8 +303,8 @@ where
                             }
                             for peer_id in closer_peers {
                                 // if we need more peers, attempt a connection
-                                if self.connected_peers.len() < self.max_peers
-                                    && self.connected_peers.get(&peer_id).is_none()
+                                if self.peer_manager.needs_peers()
+                                    && !self.peer_manager.is_connected(&peer_id)
                                 {
                                     debug!(self.log, "Discv5: Peer discovered"; "Peer"=> format!("{:?}", peer_id));
                                     return Async::Ready(NetworkBehaviourAction::DialPeer {
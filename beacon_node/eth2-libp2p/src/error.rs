@@ -1,5 +1,18 @@
-// generates error types
+//! Errors which prevent the libp2p service from starting.
+use crate::multiaddr::Multiaddr;
+use thiserror::Error;
 
-use error_chain::error_chain;
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("unable to build or load the local ENR: {0}")]
+    Enr(String),
+    #[error("discv5 service failed: {0}")]
+    Discovery(String),
+    #[error("invalid trusted peer {multiaddr}: {reason}")]
+    TrustedPeer {
+        multiaddr: Multiaddr,
+        reason: &'static str,
+    },
+}
 
-error_chain! {}
+pub type Result<T> = std::result::Result<T, Error>;
@@ -6,12 +6,15 @@ pub mod behaviour;
 mod config;
 mod discovery;
 pub mod error;
+mod peer_manager;
 pub mod rpc;
 mod service;
+mod trusted_peers;
 
 pub use behaviour::PubsubMessage;
 pub use config::{
-    Config as NetworkConfig, BEACON_ATTESTATION_TOPIC, BEACON_BLOCK_TOPIC, SHARD_TOPIC_PREFIX,
+    Config as NetworkConfig, ATTESTER_SLASHING_TOPIC, BEACON_ATTESTATION_TOPIC, BEACON_BLOCK_TOPIC,
+    PROPOSER_SLASHING_TOPIC, SHARD_TOPIC_PREFIX,
 };
 pub use libp2p::gossipsub::{Topic, TopicHash};
 pub use libp2p::multiaddr;
@@ -23,3 +26,4 @@ pub use libp2p::{
 pub use rpc::RPCEvent;
 pub use service::Libp2pEvent;
 pub use service::Service;
+pub use trusted_peers::TrustedPeers;
@@ -0,0 +1,259 @@
+//! Keeps the number of connected peers near `--target-peers`.
+//!
+//! Once more than the target number of untrusted peers are connected, the excess is pruned in
+//! order of lowest score, preferring peers whose shard subnets are well covered by other peers.
+//! Trusted peers and peers which this node dialed are never pruned, nor are peers whose removal
+//! would leave a subnet this node subscribes to with fewer than `MIN_SUBNET_PEERS` peers.
+use crate::{TrustedPeers, SHARD_TOPIC_PREFIX};
+use libp2p::PeerId;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+
+/// The number of peers of each subscribed subnet which are protected from pruning.
+pub const MIN_SUBNET_PEERS: usize = 2;
+/// The lowest score of a peer.
+const MIN_SCORE: i32 = -100;
+/// The highest score of a peer.
+const MAX_SCORE: i32 = 100;
+
+/// The state of a connected peer.
+#[derive(Debug, Clone, Default)]
+struct PeerInfo {
+    /// `true` if this node dialed the peer.
+    outbound: bool,
+    /// The score of the peer, from `MIN_SCORE` to `MAX_SCORE`. Peers start at zero.
+    score: i32,
+    /// The shard subnets to which the peer subscribes.
+    subnets: HashSet<u64>,
+    /// `true` if the peer has been asked to disconnect.
+    pruned: bool,
+}
+
+/// Tracks connected peers and selects those to prune.
+#[derive(Debug, Clone)]
+pub struct PeerManager {
+    /// The target number of connected untrusted peers.
+    target_peers: usize,
+    /// Peers which are never pruned, and do not count towards `target_peers`.
+    trusted_peers: TrustedPeers,
+    /// All connected peers.
+    peers: HashMap<PeerId, PeerInfo>,
+    /// The shard subnets to which this node subscribes.
+    subnets: HashSet<u64>,
+}
+
+impl PeerManager {
+    pub fn new(target_peers: usize, trusted_peers: TrustedPeers) -> Self {
+        Self {
+            target_peers,
+            trusted_peers,
+            peers: HashMap::new(),
+            subnets: HashSet::new(),
+        }
+    }
+
+    /// Records a new connection to `peer_id`, which was dialed by this node if `outbound`.
+    pub fn connect(&mut self, peer_id: PeerId, outbound: bool) {
+        self.peers.insert(
+            peer_id,
+            PeerInfo {
+                outbound,
+                ..PeerInfo::default()
+            },
+        );
+    }
+
+    /// Forgets `peer_id`, which has disconnected.
+    pub fn disconnect(&mut self, peer_id: &PeerId) {
+        self.peers.remove(peer_id);
+    }
+
+    /// Returns `true` if `peer_id` is connected.
+    pub fn is_connected(&self, peer_id: &PeerId) -> bool {
+        self.peers.contains_key(peer_id)
+    }
+
+    /// The number of connected peers.
+    pub fn connected_peers(&self) -> usize {
+        self.peers.len()
+    }
+
+    /// Returns `true` if fewer than the target number of untrusted peers are connected.
+    pub fn needs_peers(&self) -> bool {
+        self.untrusted_peers() < self.target_peers
+    }
+
+    /// Records that this node subscribes to `subnet`.
+    pub fn add_subnet(&mut self, subnet: u64) {
+        self.subnets.insert(subnet);
+    }
+
+    /// Records that this node no longer subscribes to `subnet`.
+    pub fn remove_subnet(&mut self, subnet: u64) {
+        self.subnets.remove(&subnet);
+    }
+
+    /// Records that `peer_id` subscribes to `subnet`.
+    pub fn subscribe(&mut self, peer_id: &PeerId, subnet: u64) {
+        if let Some(info) = self.peers.get_mut(peer_id) {
+            info.subnets.insert(subnet);
+        }
+    }
+
+    /// Records that `peer_id` no longer subscribes to `subnet`.
+    pub fn unsubscribe(&mut self, peer_id: &PeerId, subnet: u64) {
+        if let Some(info) = self.peers.get_mut(peer_id) {
+            info.subnets.remove(&subnet);
+        }
+    }
+
+    /// Adds `delta` to the score of `peer_id`.
+    pub fn adjust_score(&mut self, peer_id: &PeerId, delta: i32) {
+        if let Some(info) = self.peers.get_mut(peer_id) {
+            info.score = info
+                .score
+                .saturating_add(delta)
+                .max(MIN_SCORE)
+                .min(MAX_SCORE);
+        }
+    }
+
+    /// Returns the peers which should be pruned to return to the target number of peers, marking
+    /// them so they are not returned again.
+    pub fn prune(&mut self) -> Vec<PeerId> {
+        let pruned = self.peers_to_prune();
+        for peer_id in &pruned {
+            if let Some(info) = self.peers.get_mut(peer_id) {
+                info.pruned = true;
+            }
+        }
+        pruned
+    }
+
+    /// Returns the peers which should be pruned to return to the target number of peers.
+    ///
+    /// Fewer peers are returned if there are not enough unprotected peers.
+    pub fn peers_to_prune(&self) -> Vec<PeerId> {
+        let mut excess = self.untrusted_peers().saturating_sub(self.target_peers);
+        if excess == 0 {
+            return vec![];
+        }
+
+        let mut subnet_peers: HashMap<u64, usize> = HashMap::new();
+        for info in self.peers.values().filter(|info| !info.pruned) {
+            for subnet in info.subnets.intersection(&self.subnets) {
+                *subnet_peers.entry(*subnet).or_insert(0) += 1;
+            }
+        }
+
+        let mut candidates: Vec<(&PeerId, &PeerInfo)> = self
+            .peers
+            .iter()
+            .filter(|(peer_id, info)| {
+                !info.pruned && !info.outbound && !self.trusted_peers.contains(peer_id)
+            })
+            .collect();
+
+        let mut pruned = vec![];
+        while excess > 0 {
+            let next = candidates
+                .iter()
+                .enumerate()
+                .filter(|(_, (_, info))| !self.is_protected(info, &subnet_peers))
+                .min_by_key(|(_, (_, info))| {
+                    (info.score, Reverse(self.coverage(info, &subnet_peers)))
+                })
+                .map(|(index, _)| index);
+
+            let (peer_id, info) = match next {
+                Some(index) => candidates.swap_remove(index),
+                None => break,
+            };
+
+            for subnet in info.subnets.intersection(&self.subnets) {
+                if let Some(count) = subnet_peers.get_mut(subnet) {
+                    *count -= 1;
+                }
+            }
+            pruned.push(peer_id.clone());
+            excess -= 1;
+        }
+
+        pruned
+    }
+
+    /// The number of connected peers which are neither trusted nor being pruned.
+    fn untrusted_peers(&self) -> usize {
+        self.peers
+            .iter()
+            .filter(|(peer_id, info)| !info.pruned && !self.trusted_peers.contains(peer_id))
+            .count()
+    }
+
+    /// Returns `true` if pruning the peer would leave one of our subnets with fewer than
+    /// `MIN_SUBNET_PEERS` peers.
+    fn is_protected(&self, info: &PeerInfo, subnet_peers: &HashMap<u64, usize>) -> bool {
+        info.subnets
+            .intersection(&self.subnets)
+            .any(|subnet| subnet_peers.get(subnet).cloned().unwrap_or(0) <= MIN_SUBNET_PEERS)
+    }
+
+    /// The fewest peers covering any of our subnets to which the peer subscribes, or
+    /// `usize::max_value()` if it subscribes to none.
+    fn coverage(&self, info: &PeerInfo, subnet_peers: &HashMap<u64, usize>) -> usize {
+        info.subnets
+            .intersection(&self.subnets)
+            .map(|subnet| subnet_peers.get(subnet).cloned().unwrap_or(0))
+            .min()
+            .unwrap_or_else(usize::max_value)
+    }
+}
+
+/// Returns the shard subnet of `topic`, if it is a shard topic (e.g., `shard3`).
+pub fn subnet_id(topic: &str) -> Option<u64> {
+    if topic.starts_with(SHARD_TOPIC_PREFIX) {
+        topic[SHARD_TOPIC_PREFIX.len()..].parse().ok()
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prunes_low_score_and_redundant_peers() {
+        let mut manager = PeerManager::new(2, TrustedPeers::default());
+        manager.add_subnet(1);
+
+        let peers: Vec<PeerId> = (0..6).map(|_| PeerId::random()).collect();
+        for (index, peer_id) in peers.iter().enumerate() {
+            // The first peer was dialed by this node.
+            manager.connect(peer_id.clone(), index == 0);
+        }
+        // Three inbound peers cover the subnet, so only one of them may be pruned.
+        for peer_id in &peers[1..4] {
+            manager.subscribe(peer_id, 1);
+        }
+        manager.adjust_score(&peers[3], -10);
+        manager.adjust_score(&peers[4], 5);
+
+        // Four of the six peers are in excess. The outbound peer, and all but one of the subnet
+        // peers, are protected.
+        let pruned: HashSet<PeerId> = manager.prune().into_iter().collect();
+        let expected: HashSet<PeerId> = peers[3..].iter().cloned().collect();
+        assert_eq!(pruned, expected);
+
+        // Pruned peers are not counted, or returned again.
+        assert!(manager.prune().is_empty());
+        assert!(!manager.needs_peers());
+    }
+
+    #[test]
+    fn parses_subnet_topics() {
+        assert_eq!(subnet_id("shard3"), Some(3));
+        assert_eq!(subnet_id("shard"), None);
+        assert_eq!(subnet_id("beacon_block"), None);
+    }
+}
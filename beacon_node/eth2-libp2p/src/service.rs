@@ -4,7 +4,9 @@ use crate::multiaddr::Protocol;
 use crate::rpc::RPCEvent;
 use crate::NetworkConfig;
 use crate::{Topic, TopicHash};
-use crate::{BEACON_ATTESTATION_TOPIC, BEACON_BLOCK_TOPIC};
+use crate::{
+    ATTESTER_SLASHING_TOPIC, BEACON_ATTESTATION_TOPIC, BEACON_BLOCK_TOPIC, PROPOSER_SLASHING_TOPIC,
+};
 use futures::prelude::*;
 use futures::Stream;
 use libp2p::core::{
@@ -92,6 +94,8 @@ impl Service {
         // attestations
         topics.push(Topic::new(BEACON_ATTESTATION_TOPIC.into()));
         topics.push(Topic::new(BEACON_BLOCK_TOPIC.into()));
+        topics.push(Topic::new(PROPOSER_SLASHING_TOPIC.into()));
+        topics.push(Topic::new(ATTESTER_SLASHING_TOPIC.into()));
         topics.append(
             &mut config
                 .topics
@@ -151,6 +155,13 @@ impl Stream for Service {
                     BehaviourEvent::PeerDisconnected(peer_id) => {
                         return Ok(Async::Ready(Some(Libp2pEvent::PeerDisconnected(peer_id))));
                     }
+                    BehaviourEvent::Disconnect(peer_id) => {
+                        // Banning a peer closes its connection, and it is unbanned at once so that
+                        // it may connect again later.
+                        debug!(self.log, "Disconnecting peer"; "peer" => format!("{:?}", peer_id));
+                        Swarm::ban_peer_id(&mut self.swarm, peer_id.clone());
+                        Swarm::unban_peer_id(&mut self.swarm, peer_id);
+                    }
                 },
                 Ok(Async::Ready(None)) => unreachable!("Swarm stream shouldn't end"),
                 Ok(Async::NotReady) => break,
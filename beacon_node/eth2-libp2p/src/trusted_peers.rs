@@ -0,0 +1,111 @@
+//! Peers which are always dialed and never disconnected for faults.
+//!
+//! Trusted peers allow private fleets to form a deterministic topology regardless of what public
+//! discovery returns. They may be given as multiaddrs, which must end in `/p2p/<peer id>`, or as
+//! ENRs.
+use crate::error::{self, Error};
+use crate::multiaddr::Protocol;
+use crate::NetworkConfig;
+use enr::Enr;
+use libp2p::{Multiaddr, PeerId};
+use std::collections::HashMap;
+
+/// The set of trusted peers and their known addresses.
+#[derive(Debug, Clone, Default)]
+pub struct TrustedPeers {
+    /// The known TCP addresses of each trusted peer.
+    peers: HashMap<PeerId, Vec<Multiaddr>>,
+}
+
+impl TrustedPeers {
+    /// Collects the trusted multiaddrs and ENRs of `config`.
+    pub fn from_config(config: &NetworkConfig) -> error::Result<Self> {
+        let mut peers: HashMap<PeerId, Vec<Multiaddr>> = HashMap::new();
+
+        for multiaddr in &config.trusted_peers {
+            let (peer_id, address) = split_peer_id(multiaddr)?;
+            peers.entry(peer_id).or_default().push(address);
+        }
+
+        for enr in &config.trusted_enrs {
+            let addresses = peers.entry(enr.peer_id()).or_default();
+            addresses.extend(enr.multiaddr().into_iter().filter(is_tcp));
+        }
+
+        Ok(Self { peers })
+    }
+
+    /// Returns `true` if `peer_id` is a trusted peer.
+    pub fn contains(&self, peer_id: &PeerId) -> bool {
+        self.peers.contains_key(peer_id)
+    }
+
+    /// The known addresses of `peer_id`, if it is a trusted peer.
+    pub fn addresses(&self, peer_id: &PeerId) -> Vec<Multiaddr> {
+        self.peers.get(peer_id).cloned().unwrap_or_default()
+    }
+
+    /// Iterates over the `PeerId` of all trusted peers.
+    pub fn peer_ids(&self) -> impl Iterator<Item = &PeerId> {
+        self.peers.keys()
+    }
+
+    /// The number of trusted peers.
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+
+    /// Returns `true` if there are no trusted peers.
+    pub fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+}
+
+/// Splits a multiaddr of the form `<address>/p2p/<peer id>` into its `PeerId` and address.
+pub fn split_peer_id(multiaddr: &Multiaddr) -> error::Result<(PeerId, Multiaddr)> {
+    let invalid = |reason| Error::TrustedPeer {
+        multiaddr: multiaddr.clone(),
+        reason,
+    };
+    let mut address = multiaddr.clone();
+    match address.pop() {
+        Some(Protocol::P2p(hash)) => PeerId::from_multihash(hash)
+            .map(|peer_id| (peer_id, address))
+            .map_err(|_| invalid("invalid peer id")),
+        _ => Err(invalid("the multiaddr must end in /p2p/<peer id>")),
+    }
+}
+
+/// Returns `true` if libp2p can dial `address` (i.e., it is a TCP address).
+fn is_tcp(address: &Multiaddr) -> bool {
+    address.iter().any(|protocol| match protocol {
+        Protocol::Tcp(_) => true,
+        _ => false,
+    })
+}
+
+/// Parses a comma-separated list of trusted peers, as given on the command line.
+///
+/// Entries beginning with `/` are parsed as multiaddrs, all others are parsed as ENRs.
+pub fn parse_trusted_peers(list: &str) -> Result<(Vec<Multiaddr>, Vec<Enr>), String> {
+    let mut multiaddrs = vec![];
+    let mut enrs = vec![];
+
+    for entry in list.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        if entry.starts_with('/') {
+            let multiaddr: Multiaddr = entry
+                .parse()
+                .map_err(|_| format!("Invalid Multiaddr: {}", entry))?;
+            split_peer_id(&multiaddr).map_err(|e| e.to_string())?;
+            multiaddrs.push(multiaddr);
+        } else {
+            enrs.push(
+                entry
+                    .parse()
+                    .map_err(|_| format!("Invalid ENR: {}", entry))?,
+            );
+        }
+    }
+
+    Ok((multiaddrs, enrs))
+}
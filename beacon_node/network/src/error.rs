@@ -1,10 +1,19 @@
-// generates error types
-use eth2_libp2p;
+//! Errors which prevent the network service from starting, or stop it whilst running.
+//!
+//! Errors concerning a message from a peer carry the `PeerId` of that peer.
+use eth2_libp2p::PeerId;
+use thiserror::Error;
 
-use error_chain::error_chain;
-
-error_chain! {
-   links  {
-       Libp2p(eth2_libp2p::error::Error, eth2_libp2p::error::ErrorKind);
-   }
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("libp2p error: {0}")]
+    Libp2p(#[from] eth2_libp2p::error::Error),
+    #[error("the network channel closed")]
+    ChannelClosed,
+    #[error("the network channel failed")]
+    ChannelFailed,
+    #[error("unable to pass the {message} of peer {peer} to the message handler")]
+    HandlerUnavailable { peer: PeerId, message: &'static str },
 }
+
+pub type Result<T> = std::result::Result<T, Error>;
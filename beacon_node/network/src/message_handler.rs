@@ -6,7 +6,7 @@ use eth2_libp2p::rpc::methods::*;
 use eth2_libp2p::{
     behaviour::PubsubMessage,
     rpc::{RPCError, RPCErrorResponse, RPCRequest, RPCResponse, RequestId},
-    PeerId, RPCEvent,
+    PeerId, RPCEvent, TrustedPeers,
 };
 use futures::future::Future;
 use futures::stream::Stream;
@@ -14,12 +14,12 @@ use slog::{debug, warn};
 use ssz::{Decode, DecodeError};
 use std::sync::Arc;
 use tokio::sync::mpsc;
-use types::{Attestation, BeaconBlock, BeaconBlockHeader};
+use types::{Attestation, AttesterSlashing, BeaconBlock, BeaconBlockHeader, ProposerSlashing};
 
 /// Handles messages received from the network and client and organises syncing.
 pub struct MessageHandler<T: BeaconChainTypes> {
     /// Currently loaded and initialised beacon chain.
-    _chain: Arc<BeaconChain<T>>,
+    chain: Arc<BeaconChain<T>>,
     /// The syncing framework.
     sync: SimpleSync<T>,
     /// The context required to send messages to, and process messages from peers.
@@ -46,6 +46,7 @@ impl<T: BeaconChainTypes + 'static> MessageHandler<T> {
     pub fn spawn(
         beacon_chain: Arc<BeaconChain<T>>,
         network_send: mpsc::UnboundedSender<NetworkMessage>,
+        trusted_peers: TrustedPeers,
         executor: &tokio::runtime::TaskExecutor,
         log: slog::Logger,
     ) -> error::Result<mpsc::UnboundedSender<HandlerMessage>> {
@@ -58,9 +59,9 @@ impl<T: BeaconChainTypes + 'static> MessageHandler<T> {
         let sync = SimpleSync::new(beacon_chain.clone(), &log);
 
         let mut handler = MessageHandler {
-            _chain: beacon_chain.clone(),
+            chain: beacon_chain.clone(),
             sync,
-            network_context: NetworkContext::new(network_send, log.clone()),
+            network_context: NetworkContext::new(network_send, trusted_peers, log.clone()),
             log: log.clone(),
         };
 
@@ -246,6 +247,31 @@ impl<T: BeaconChainTypes + 'static> MessageHandler<T> {
                         .on_attestation_gossip(peer_id, attestation, &mut self.network_context)
                 }
             },
+            // Valid slashings are queued for inclusion in a block produced by this node.
+            PubsubMessage::ProposerSlashing(message) => {
+                match ProposerSlashing::from_ssz_bytes(&message) {
+                    Err(e) => {
+                        debug!(self.log, "Invalid Gossiped Proposer Slashing"; "Peer" => format!("{}", peer_id), "Error" => format!("{:?}", e));
+                    }
+                    Ok(slashing) => {
+                        if let Err(e) = self.chain.process_proposer_slashing(slashing) {
+                            debug!(self.log, "Rejected Gossiped Proposer Slashing"; "Peer" => format!("{}", peer_id), "Error" => format!("{:?}", e));
+                        }
+                    }
+                }
+            }
+            PubsubMessage::AttesterSlashing(message) => {
+                match AttesterSlashing::from_ssz_bytes(&message) {
+                    Err(e) => {
+                        debug!(self.log, "Invalid Gossiped Attester Slashing"; "Peer" => format!("{}", peer_id), "Error" => format!("{:?}", e));
+                    }
+                    Ok(slashing) => {
+                        if let Err(e) = self.chain.process_attester_slashing(slashing) {
+                            debug!(self.log, "Rejected Gossiped Attester Slashing"; "Peer" => format!("{}", peer_id), "Error" => format!("{:?}", e));
+                        }
+                    }
+                }
+            }
             PubsubMessage::Unknown(message) => {
                 // Received a message from an unknown topic. Ignore for now
                 debug!(self.log, "Unknown Gossip Message"; "Peer" => format!("{}", peer_id), "Message" => format!("{:?}", message));
@@ -303,16 +329,41 @@ impl<T: BeaconChainTypes + 'static> MessageHandler<T> {
 pub struct NetworkContext {
     /// The network channel to relay messages to the Network service.
     network_send: mpsc::UnboundedSender<NetworkMessage>,
+    /// Peers which are never disconnected for faults.
+    trusted_peers: TrustedPeers,
     /// The `MessageHandler` logger.
     log: slog::Logger,
 }
 
 impl NetworkContext {
-    pub fn new(network_send: mpsc::UnboundedSender<NetworkMessage>, log: slog::Logger) -> Self {
-        Self { network_send, log }
+    pub fn new(
+        network_send: mpsc::UnboundedSender<NetworkMessage>,
+        trusted_peers: TrustedPeers,
+        log: slog::Logger,
+    ) -> Self {
+        Self {
+            network_send,
+            trusted_peers,
+            log,
+        }
     }
 
+    /// Sends a `Goodbye` to `peer_id`.
+    ///
+    /// Trusted peers are not disconnected for faults, since they are expected to remain part of
+    /// the topology regardless of their behaviour.
     pub fn disconnect(&mut self, peer_id: PeerId, reason: GoodbyeReason) {
+        if let GoodbyeReason::Fault = reason {
+            if self.trusted_peers.contains(&peer_id) {
+                warn!(
+                    self.log,
+                    "Not disconnecting faulty trusted peer";
+                    "peer" => format!("{:?}", peer_id)
+                );
+                return;
+            }
+        }
+
         self.send_rpc_request(peer_id, RPCRequest::Goodbye(reason))
         // TODO: disconnect peers.
     }
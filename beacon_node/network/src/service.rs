@@ -5,7 +5,7 @@ use beacon_chain::{BeaconChain, BeaconChainTypes};
 use core::marker::PhantomData;
 use eth2_libp2p::Service as LibP2PService;
 use eth2_libp2p::Topic;
-use eth2_libp2p::{Libp2pEvent, PeerId};
+use eth2_libp2p::{Libp2pEvent, PeerId, TrustedPeers};
 use eth2_libp2p::{PubsubMessage, RPCEvent};
 use futures::prelude::*;
 use futures::Stream;
@@ -38,6 +38,7 @@ impl<T: BeaconChainTypes + 'static> Service<T> {
         let message_handler_send = MessageHandler::spawn(
             beacon_chain,
             network_send.clone(),
+            TrustedPeers::from_config(config)?,
             executor,
             message_handler_log,
         )?;
@@ -103,8 +104,8 @@ fn network_service(
     mut network_recv: mpsc::UnboundedReceiver<NetworkMessage>,
     mut message_handler_send: mpsc::UnboundedSender<HandlerMessage>,
     log: slog::Logger,
-) -> impl futures::Future<Item = (), Error = eth2_libp2p::error::Error> {
-    futures::future::poll_fn(move || -> Result<_, eth2_libp2p::error::Error> {
+) -> impl futures::Future<Item = (), Error = error::Error> {
+    futures::future::poll_fn(move || -> Result<_, error::Error> {
         // if the network channel is not ready, try the swarm
         loop {
             // poll the network channel
@@ -120,13 +121,27 @@ fn network_service(
                         debug!(log, "Sending pubsub message"; "topics" => format!("{:?}",topics));
                         libp2p_service.lock().swarm.publish(topics, message);
                     }
+                    NetworkMessage::Subscribe(topics) => {
+                        for topic in topics {
+                            if libp2p_service.lock().swarm.subscribe(topic.clone()) {
+                                debug!(log, "Subscribed to topic"; "topic" => format!("{:?}", topic));
+                            }
+                        }
+                    }
+                    NetworkMessage::Unsubscribe(topics) => {
+                        for topic in topics {
+                            if libp2p_service.lock().swarm.unsubscribe(topic.clone()) {
+                                debug!(log, "Unsubscribed from topic"; "topic" => format!("{:?}", topic));
+                            }
+                        }
+                    }
                 },
                 Ok(Async::NotReady) => break,
                 Ok(Async::Ready(None)) => {
-                    return Err(eth2_libp2p::error::Error::from("Network channel closed"));
+                    return Err(error::Error::ChannelClosed);
                 }
                 Err(_) => {
-                    return Err(eth2_libp2p::error::Error::from("Network channel error"));
+                    return Err(error::Error::ChannelFailed);
                 }
             }
         }
@@ -138,20 +153,29 @@ fn network_service(
                     Libp2pEvent::RPC(peer_id, rpc_event) => {
                         trace!(log, "RPC Event: RPC message received: {:?}", rpc_event);
                         message_handler_send
-                            .try_send(HandlerMessage::RPC(peer_id, rpc_event))
-                            .map_err(|_| "Failed to send RPC to handler")?;
+                            .try_send(HandlerMessage::RPC(peer_id.clone(), rpc_event))
+                            .map_err(|_| error::Error::HandlerUnavailable {
+                                peer: peer_id,
+                                message: "RPC event",
+                            })?;
                     }
                     Libp2pEvent::PeerDialed(peer_id) => {
                         debug!(log, "Peer Dialed: {:?}", peer_id);
                         message_handler_send
-                            .try_send(HandlerMessage::PeerDialed(peer_id))
-                            .map_err(|_| "Failed to send PeerDialed to handler")?;
+                            .try_send(HandlerMessage::PeerDialed(peer_id.clone()))
+                            .map_err(|_| error::Error::HandlerUnavailable {
+                                peer: peer_id,
+                                message: "dial",
+                            })?;
                     }
                     Libp2pEvent::PeerDisconnected(peer_id) => {
                         debug!(log, "Peer Disconnected: {:?}", peer_id);
                         message_handler_send
-                            .try_send(HandlerMessage::PeerDisconnected(peer_id))
-                            .map_err(|_| "Failed to send PeerDisconnected to handler")?;
+                            .try_send(HandlerMessage::PeerDisconnected(peer_id.clone()))
+                            .map_err(|_| error::Error::HandlerUnavailable {
+                                peer: peer_id,
+                                message: "disconnection",
+                            })?;
                     }
                     Libp2pEvent::PubsubMessage {
                         source, message, ..
@@ -159,8 +183,11 @@ fn network_service(
                         //TODO: Decide if we need to propagate the topic upwards. (Potentially for
                         //attestations)
                         message_handler_send
-                            .try_send(HandlerMessage::PubsubMessage(source, message))
-                            .map_err(|_| " failed to send pubsub message to handler")?;
+                            .try_send(HandlerMessage::PubsubMessage(source.clone(), message))
+                            .map_err(|_| error::Error::HandlerUnavailable {
+                                peer: source,
+                                message: "gossip message",
+                            })?;
                     }
                 },
                 Ok(Async::Ready(None)) => unreachable!("Stream never ends"),
@@ -184,6 +211,10 @@ pub enum NetworkMessage {
         topics: Vec<Topic>,
         message: PubsubMessage,
     },
+    /// Subscribe to gossipsub topics (e.g., the attestation subnets of aggregators).
+    Subscribe(Vec<Topic>),
+    /// Unsubscribe from gossipsub topics.
+    Unsubscribe(Vec<Topic>),
 }
 
 /// Type of outgoing messages that can be sent through the network service.
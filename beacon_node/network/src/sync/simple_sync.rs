@@ -560,6 +560,17 @@ impl<T: BeaconChainTypes> SimpleSync<T> {
         block: BeaconBlock<T::EthSpec>,
         network: &mut NetworkContext,
     ) -> bool {
+        // Reject blocks with invalid proposer signatures before the (expensive) full block
+        // processing, where the proposers are known cheaply.
+        if let Some(false) = self.chain.verify_block_signature_fast(&block) {
+            warn!(
+                self.log, "InvalidGossipBlockSignature";
+                "slot" => block.slot,
+                "peer" => format!("{:?}", peer_id),
+            );
+            return SHOULD_NOT_FORWARD_GOSSIP_BLOCK;
+        }
+
         if let Some(outcome) =
             self.process_block(peer_id.clone(), block.clone(), network, &"gossip")
         {
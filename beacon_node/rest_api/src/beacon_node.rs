@@ -1,10 +1,11 @@
-use beacon_chain::{BeaconChain, BeaconChainTypes};
+use beacon_chain::types::{Epoch, Hash256};
+use beacon_chain::{BeaconChain, BeaconChainTypes, OperationalState};
 use serde::Serialize;
 use slog::info;
 use std::sync::Arc;
 use version;
 
-use super::{path_from_request, success_response, APIResult, APIService};
+use super::{path_from_request, query_param, success_response, APIError, APIResult, APIService};
 
 use hyper::{Body, Request, Response};
 use hyper_router::{Route, RouterBuilder};
@@ -32,11 +33,35 @@ impl From<u64> for GenesisTime {
     }
 }
 
+/// The root of the canonical block at the start of an epoch, and the finalized epoch of the node.
+///
+/// `root` is `None` if the node has no canonical block at the epoch.
+#[derive(Serialize)]
+pub struct CheckpointRoot {
+    root: Option<Hash256>,
+    finalized_epoch: Epoch,
+}
+
+/// The operational health of the beacon node.
+///
+/// `state` is `long_non_finality` if the chain has not finalized for at least
+/// `beacon_chain::LONG_NON_FINALITY_EPOCHS`, in which case the node is restricting its memory usage.
+#[derive(Serialize)]
+pub struct Health {
+    state: OperationalState,
+    epochs_since_finality: u64,
+}
+
 impl<T: BeaconChainTypes + 'static> APIService for BeaconNodeServiceInstance<T> {
     fn add_routes(&mut self, router_builder: RouterBuilder) -> Result<RouterBuilder, hyper::Error> {
         let router_builder = router_builder
             .add(Route::get("/version").using(result_to_response!(get_version)))
-            .add(Route::get("/genesis_time").using(result_to_response!(get_genesis_time::<T>)));
+            .add(Route::get("/genesis_time").using(result_to_response!(get_genesis_time::<T>)))
+            .add(Route::get("/genesis_root").using(result_to_response!(get_genesis_root::<T>)))
+            .add(
+                Route::get("/checkpoint_root").using(result_to_response!(get_checkpoint_root::<T>)),
+            )
+            .add(Route::get("/health").using(result_to_response!(get_health::<T>)));
         Ok(router_builder)
     }
 }
@@ -63,3 +88,47 @@ fn get_genesis_time<T: BeaconChainTypes + 'static>(req: Request<Body>) -> APIRes
     );
     Ok(success_response(body))
 }
+
+/// Read the root of the genesis block, which identifies the chain.
+fn get_genesis_root<T: BeaconChainTypes + 'static>(req: Request<Body>) -> APIResult {
+    let beacon_chain = req.extensions().get::<Arc<BeaconChain<T>>>().unwrap();
+    let body = Body::from(
+        serde_json::to_string(&beacon_chain.genesis_block_root)
+            .expect("Genesis root should always have a valid JSON serialization."),
+    );
+    Ok(success_response(body))
+}
+
+/// Read the root of the canonical block at the start of `?epoch=`, to be checked against a weak
+/// subjectivity checkpoint.
+fn get_checkpoint_root<T: BeaconChainTypes + 'static>(req: Request<Body>) -> APIResult {
+    let epoch = query_param(&req, "epoch")
+        .and_then(|epoch| epoch.parse::<u64>().ok())
+        .map(Epoch::new)
+        .ok_or_else(|| APIError::InvalidQueryParams {
+            desc: "the epoch query parameter must be a u64".to_string(),
+        })?;
+    let beacon_chain = req.extensions().get::<Arc<BeaconChain<T>>>().unwrap();
+    let checkpoint_root = CheckpointRoot {
+        root: beacon_chain.checkpoint_root(epoch),
+        finalized_epoch: beacon_chain.head().beacon_state.finalized_checkpoint.epoch,
+    };
+    let body = Body::from(
+        serde_json::to_string(&checkpoint_root)
+            .expect("Checkpoint root should always be serializable as JSON."),
+    );
+    Ok(success_response(body))
+}
+
+/// Read the operational state of the beacon chain.
+fn get_health<T: BeaconChainTypes + 'static>(req: Request<Body>) -> APIResult {
+    let beacon_chain = req.extensions().get::<Arc<BeaconChain<T>>>().unwrap();
+    let health = Health {
+        state: beacon_chain.operational_state(),
+        epochs_since_finality: beacon_chain.epochs_since_finality(),
+    };
+    let body = Body::from(
+        serde_json::to_string(&health).expect("Health should always be serializable as JSON."),
+    );
+    Ok(success_response(body))
+}
@@ -22,6 +22,7 @@ pub enum APIError {
     MethodNotAllowed { desc: String },
     ServerError { desc: String },
     NotImplemented { desc: String },
+    InvalidQueryParams { desc: String },
 }
 
 pub type APIResult = Result<Response<Body>, APIError>;
@@ -32,6 +33,7 @@ impl Into<Response<Body>> for APIError {
             APIError::MethodNotAllowed { desc } => (StatusCode::METHOD_NOT_ALLOWED, desc),
             APIError::ServerError { desc } => (StatusCode::INTERNAL_SERVER_ERROR, desc),
             APIError::NotImplemented { desc } => (StatusCode::NOT_IMPLEMENTED, desc),
+            APIError::InvalidQueryParams { desc } => (StatusCode::BAD_REQUEST, desc),
         };
         Response::builder()
             .status(status_code.0)
@@ -124,6 +126,18 @@ fn path_from_request(req: &Request<Body>) -> String {
         .unwrap_or(String::new())
 }
 
+/// Returns the value of the query parameter `name`, if present.
+fn query_param(req: &Request<Body>, name: &str) -> Option<String> {
+    req.uri().query()?.split('&').find_map(|pair| {
+        let mut pair = pair.splitn(2, '=');
+        if pair.next() == Some(name) {
+            pair.next().map(String::from)
+        } else {
+            None
+        }
+    })
+}
+
 fn success_response(body: Body) -> Response<Body> {
     Response::builder()
         .status(StatusCode::OK)
@@ -1,28 +1,115 @@
+use crate::conflict_guard::{Conflict, ConflictGuard};
 use beacon_chain::{BeaconChain, BeaconChainTypes};
 use eth2_libp2p::PubsubMessage;
 use eth2_libp2p::Topic;
-use eth2_libp2p::BEACON_ATTESTATION_TOPIC;
+use eth2_libp2p::{BEACON_ATTESTATION_TOPIC, SHARD_TOPIC_PREFIX};
 use futures::Future;
 use grpcio::{RpcContext, RpcStatus, RpcStatusCode, UnarySink};
 use network::NetworkMessage;
+use protos::convert::{FromProto, ToProto};
 use protos::services::{
-    AttestationData as AttestationDataProto, ProduceAttestationDataRequest,
-    ProduceAttestationDataResponse, PublishAttestationRequest, PublishAttestationResponse,
+    AttestationKnownRequest, AttestationKnownResponse, BlockAttestationsRequest,
+    BlockAttestationsResponse, ProduceAttestationDataRequest, ProduceAttestationDataResponse,
+    PublishAttestationRequest, PublishAttestationResponse,
 };
 use protos::services_grpc::AttestationService;
 use slog::{error, info, trace, warn};
-use ssz::{ssz_encode, Decode, Encode};
+use ssz::Encode;
+use state_processing::common::get_attesting_indices;
 use std::sync::Arc;
 use tokio::sync::mpsc;
-use types::Attestation;
+use types::{Attestation, BeaconBlock, Hash256, Slot};
 
 #[derive(Clone)]
 pub struct AttestationServiceInstance<T: BeaconChainTypes> {
     pub chain: Arc<BeaconChain<T>>,
     pub network_chan: mpsc::UnboundedSender<NetworkMessage>,
+    pub conflict_guard: Arc<ConflictGuard>,
+    /// The token which every call must carry, if any.
+    pub auth_token: Option<String>,
     pub log: slog::Logger,
 }
 
+impl<T: BeaconChainTypes> AttestationServiceInstance<T> {
+    /// Returns the indices of the validators attesting in `attestation`, as checked against the
+    /// conflict guard.
+    ///
+    /// The attesters are determined from the head state. If they cannot be (e.g., the attestation
+    /// is too old), `None` is returned and the attestation is not checked.
+    fn attesters(&self, attestation: &Attestation<T::EthSpec>) -> Option<Vec<usize>> {
+        let head = self.chain.head();
+        match get_attesting_indices(
+            &head.beacon_state,
+            &attestation.data,
+            &attestation.aggregation_bits,
+        ) {
+            Ok(indices) => Some(indices.into_iter().collect()),
+            Err(e) => {
+                trace!(
+                    self.log,
+                    "Unable to check attestation for conflicts";
+                    "error" => format!("{:?}", e)
+                );
+                None
+            }
+        }
+    }
+
+    /// Fails `resp` for an attestation which conflicts with one already submitted by a connected
+    /// validator.
+    fn refuse_conflicting_attestation(
+        &self,
+        resp: &mut PublishAttestationResponse,
+        conflict: Conflict,
+    ) {
+        warn!(
+            self.log,
+            "PublishAttestation";
+            "type" => "conflicting_attestation",
+            "conflict" => format!("{:?}", conflict),
+        );
+        self.chain.metrics.conflicting_attestations_refused.inc();
+
+        resp.set_success(false);
+        resp.set_msg(
+            format!("ConflictingAttestation: {:?}", conflict)
+                .as_bytes()
+                .to_vec(),
+        );
+    }
+
+    /// Returns the root of the canonical block at `slot` (or of its closest prior block, if the
+    /// slot was skipped) and the block itself, if it was produced at `slot`.
+    ///
+    /// Returns `None` if `slot` is later than the head or earlier than the state history.
+    fn canonical_block_at_slot(
+        &self,
+        slot: Slot,
+    ) -> Result<Option<(Hash256, Option<BeaconBlock<T::EthSpec>>)>, String> {
+        let head_slot = self.chain.head().beacon_block.slot;
+        if slot > head_slot {
+            return Ok(None);
+        }
+
+        let block_root = match self
+            .chain
+            .rev_iter_block_roots(head_slot)
+            .find(|(_root, root_slot)| *root_slot == slot)
+        {
+            Some((root, _slot)) => root,
+            None => return Ok(None),
+        };
+
+        let block = self
+            .chain
+            .get_block(&block_root)
+            .map_err(|e| format!("{:?}", e))?
+            .filter(|block| block.slot == slot);
+
+        Ok(Some((block_root, block)))
+    }
+}
+
 impl<T: BeaconChainTypes> AttestationService for AttestationServiceInstance<T> {
     /// Produce the `AttestationData` for signing by a validator.
     fn produce_attestation_data(
@@ -31,6 +118,8 @@ impl<T: BeaconChainTypes> AttestationService for AttestationServiceInstance<T> {
         req: ProduceAttestationDataRequest,
         sink: UnarySink<ProduceAttestationDataResponse>,
     ) {
+        authorize!(self, ctx, sink);
+
         trace!(
             &self.log,
             "Attempting to produce attestation at slot {}",
@@ -94,11 +183,8 @@ impl<T: BeaconChainTypes> AttestationService for AttestationServiceInstance<T> {
             }
         };
 
-        let mut attestation_data_proto = AttestationDataProto::new();
-        attestation_data_proto.set_ssz(ssz_encode(&attestation_data));
-
         let mut resp = ProduceAttestationDataResponse::new();
-        resp.set_attestation_data(attestation_data_proto);
+        resp.set_attestation_data(attestation_data.to_proto());
 
         let error_log = self.log.clone();
         let f = sink
@@ -115,12 +201,12 @@ impl<T: BeaconChainTypes> AttestationService for AttestationServiceInstance<T> {
         req: PublishAttestationRequest,
         sink: UnarySink<PublishAttestationResponse>,
     ) {
+        authorize!(self, ctx, sink);
+
         trace!(self.log, "Publishing attestation");
 
         let mut resp = PublishAttestationResponse::new();
-        let ssz_serialized_attestation = req.get_attestation().get_ssz();
-
-        let attestation = match Attestation::from_ssz_bytes(ssz_serialized_attestation) {
+        let attestation = match Attestation::from_proto(req.get_attestation()) {
             Ok(v) => v,
             Err(_) => {
                 let log_clone = self.log.clone();
@@ -134,6 +220,27 @@ impl<T: BeaconChainTypes> AttestationService for AttestationServiceInstance<T> {
             }
         };
 
+        // The attestation is recorded before it is processed, so that a conflicting attestation
+        // submitted concurrently is refused rather than also imported.
+        let attesters = self.attesters(&attestation);
+        let recorded = attesters.as_ref().map_or(Ok(vec![]), |indices| {
+            self.conflict_guard
+                .check_and_insert_attestation(indices, &attestation.data)
+        });
+        let recorded = match recorded {
+            Ok(recorded) => recorded,
+            Err(conflict) => {
+                // A connected validator has already submitted a conflicting attestation.
+                self.refuse_conflicting_attestation(&mut resp, conflict);
+
+                let error_log = self.log.clone();
+                let f = sink
+                    .success(resp)
+                    .map_err(move |e| error!(error_log, "failed to reply {:?}: {:?}", req, e));
+                return ctx.spawn(f);
+            }
+        };
+
         match self.chain.process_attestation(attestation.clone()) {
             Ok(_) => {
                 // Attestation was successfully processed.
@@ -143,13 +250,20 @@ impl<T: BeaconChainTypes> AttestationService for AttestationServiceInstance<T> {
                     "type" => "valid_attestation",
                 );
 
-                // valid attestation, propagate to the network
-                let topic = Topic::new(BEACON_ATTESTATION_TOPIC.into());
+                // valid attestation, propagate to the network and to the subnet of its shard, to
+                // which the aggregators of its committee subscribe
+                let topics = vec![
+                    Topic::new(BEACON_ATTESTATION_TOPIC.into()),
+                    Topic::new(format!(
+                        "{}{}",
+                        SHARD_TOPIC_PREFIX, attestation.data.crosslink.shard
+                    )),
+                ];
                 let message = PubsubMessage::Attestation(attestation.as_ssz_bytes());
 
                 self.network_chan
                     .try_send(NetworkMessage::Publish {
-                        topics: vec![topic],
+                        topics,
                         message: message,
                     })
                     .unwrap_or_else(|e| {
@@ -164,7 +278,10 @@ impl<T: BeaconChainTypes> AttestationService for AttestationServiceInstance<T> {
                 resp.set_success(true);
             }
             Err(e) => {
-                // Attestation was invalid
+                // Attestation was invalid, and must not prevent a valid one being published for
+                // the same attesters.
+                self.conflict_guard
+                    .remove_attestation(&recorded, &attestation.data);
                 warn!(
                     self.log,
                     "PublishAttestation";
@@ -182,4 +299,86 @@ impl<T: BeaconChainTypes> AttestationService for AttestationServiceInstance<T> {
             .map_err(move |e| error!(error_log, "failed to reply {:?}: {:?}", req, e));
         ctx.spawn(f)
     }
+
+    /// Respond whether the operation pool holds an attestation signed by the given committee
+    /// member, allowing its validator to skip signing a redundant attestation.
+    fn attestation_known(
+        &mut self,
+        ctx: RpcContext,
+        req: AttestationKnownRequest,
+        sink: UnarySink<AttestationKnownResponse>,
+    ) {
+        authorize!(self, ctx, sink);
+
+        let known = self.chain.op_pool.contains_attester(
+            Slot::new(req.get_slot()),
+            req.get_shard(),
+            req.get_committee_index() as usize,
+        );
+        trace!(
+            self.log,
+            "AttestationKnown";
+            "slot" => req.get_slot(),
+            "shard" => req.get_shard(),
+            "known" => known,
+        );
+
+        let mut resp = AttestationKnownResponse::new();
+        resp.set_known(known);
+
+        let error_log = self.log.clone();
+        let f = sink
+            .success(resp)
+            .map_err(move |e| error!(error_log, "failed to reply {:?}: {:?}", req, e));
+        ctx.spawn(f)
+    }
+
+    /// Returns the attestations included in the canonical block at a slot.
+    fn block_attestations(
+        &mut self,
+        ctx: RpcContext,
+        req: BlockAttestationsRequest,
+        sink: UnarySink<BlockAttestationsResponse>,
+    ) {
+        authorize!(self, ctx, sink);
+
+        let slot = Slot::new(req.get_slot());
+        trace!(self.log, "BlockAttestations"; "slot" => slot.as_u64());
+
+        let mut resp = BlockAttestationsResponse::new();
+        match self.canonical_block_at_slot(slot) {
+            Ok(Some((block_root, block))) => {
+                resp.set_known(true);
+                resp.set_block_root(block_root.as_bytes().to_vec());
+                resp.set_skipped(block.is_none());
+                if let Some(block) = block {
+                    resp.set_attestations(
+                        block
+                            .body
+                            .attestations
+                            .iter()
+                            .map(ToProto::to_proto)
+                            .collect(),
+                    );
+                }
+            }
+            Ok(None) => resp.set_known(false),
+            Err(e) => {
+                let log_clone = self.log.clone();
+                let f = sink
+                    .fail(RpcStatus::new(
+                        RpcStatusCode::Internal,
+                        Some(format!("Unable to read the block at slot {}: {}", slot, e)),
+                    ))
+                    .map_err(move |e| error!(log_clone, "failed to reply {:?}: {:?}", req, e));
+                return ctx.spawn(f);
+            }
+        }
+
+        let error_log = self.log.clone();
+        let f = sink
+            .success(resp)
+            .map_err(move |e| error!(error_log, "failed to reply {:?}: {:?}", req, e));
+        ctx.spawn(f)
+    }
 }
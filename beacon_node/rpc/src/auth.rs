@@ -0,0 +1,34 @@
+//! Rejects calls which do not carry the token configured with `--rpc-auth-token`.
+use slog::warn;
+
+/// Fails the call with `Unauthenticated` and returns from the handler, unless the call carries
+/// the token of the service instance.
+///
+/// The instance must have `auth_token: Option<String>` and `log` fields.
+macro_rules! authorize {
+    ($instance: ident, $ctx: ident, $sink: ident) => {
+        if let Err(status) =
+            protos::auth::check_token(&$ctx, $instance.auth_token.as_ref().map(String::as_str))
+        {
+            let log = $instance.log.clone();
+            crate::auth::log_rejection(&log, &$ctx);
+            let f = $sink
+                .fail(status)
+                .map_err(move |e| crate::auth::log_reply_failure(&log, e));
+            return $ctx.spawn(f);
+        }
+    };
+}
+
+pub fn log_rejection(log: &slog::Logger, ctx: &grpcio::RpcContext) {
+    warn!(
+        log,
+        "Unauthenticated RPC call rejected";
+        "method" => String::from_utf8_lossy(ctx.method()).into_owned(),
+        "peer" => ctx.peer(),
+    );
+}
+
+pub fn log_reply_failure(log: &slog::Logger, error: grpcio::Error) {
+    warn!(log, "Failed to reply to unauthenticated call"; "error" => format!("{:?}", error));
+}
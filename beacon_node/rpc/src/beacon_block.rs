@@ -1,41 +1,114 @@
+use crate::conflict_guard::{Conflict, ConflictGuard};
+use crate::FeeRecipients;
 use beacon_chain::{BeaconChain, BeaconChainTypes, BlockProcessingOutcome};
 use eth2_libp2p::BEACON_BLOCK_TOPIC;
 use eth2_libp2p::{PubsubMessage, Topic};
 use futures::Future;
 use grpcio::{RpcContext, RpcStatus, RpcStatusCode, UnarySink};
 use network::NetworkMessage;
+use protos::convert::{self, ToProto};
 use protos::services::{
-    BeaconBlock as BeaconBlockProto, ProduceBeaconBlockRequest, ProduceBeaconBlockResponse,
-    PublishBeaconBlockRequest, PublishBeaconBlockResponse,
+    ProduceBeaconBlockRequest, ProduceBeaconBlockResponse, PublishBeaconBlockRequest,
+    PublishBeaconBlockResponse,
 };
 use protos::services_grpc::BeaconBlockService;
 use slog::Logger;
 use slog::{error, info, trace, warn};
-use ssz::{ssz_encode, Decode, Encode};
+use ssz::{Decode, Encode};
 use std::sync::Arc;
 use tokio::sync::mpsc;
-use types::{BeaconBlock, Signature, Slot};
+use types::{Address, BeaconBlock, BeaconBlockAny, EthSpec, RelativeEpoch, Signature, Slot};
 
 #[derive(Clone)]
 pub struct BeaconBlockServiceInstance<T: BeaconChainTypes> {
     pub chain: Arc<BeaconChain<T>>,
     pub network_chan: mpsc::UnboundedSender<NetworkMessage>,
+    pub fee_recipients: FeeRecipients,
+    pub conflict_guard: Arc<ConflictGuard>,
+    /// The token which every call must carry, if any.
+    pub auth_token: Option<String>,
     pub log: Logger,
 }
 
+impl<T: BeaconChainTypes> BeaconBlockServiceInstance<T> {
+    /// Returns the fee recipient registered for the proposer at `slot`, if any.
+    fn registered_fee_recipient(&self, slot: Slot) -> Option<Address> {
+        let head = self.chain.head();
+        let state = &head.beacon_state;
+        if slot.epoch(T::EthSpec::slots_per_epoch()) != state.current_epoch() {
+            return None;
+        }
+        let proposer_index = state
+            .get_beacon_proposer_index(slot, RelativeEpoch::Current, &self.chain.spec)
+            .ok()?;
+        let proposer = state.validators.get(proposer_index)?;
+
+        self.fee_recipients
+            .read()
+            .ok()?
+            .get(&proposer.pubkey)
+            .cloned()
+    }
+
+    /// Fails `resp` for a block which conflicts with one already submitted by a connected
+    /// validator.
+    fn refuse_conflicting_block(&self, resp: &mut PublishBeaconBlockResponse, conflict: Conflict) {
+        warn!(
+            self.log,
+            "Refused conflicting block from RPC";
+            "conflict" => format!("{:?}", conflict)
+        );
+        self.chain.metrics.conflicting_blocks_refused.inc();
+
+        resp.set_success(false);
+        resp.set_msg(
+            format!("ConflictingBlock: {:?}", conflict)
+                .as_bytes()
+                .to_vec(),
+        );
+    }
+
+    /// Publishes `block` to the p2p network via gossipsub, returning `true` if successful.
+    fn gossip_block(&self, block: &BeaconBlock<T::EthSpec>) -> bool {
+        // get the network topic to send on
+        let topic = Topic::new(BEACON_BLOCK_TOPIC.into());
+        let message = PubsubMessage::Block(block.as_ssz_bytes());
+
+        self.network_chan
+            .try_send(NetworkMessage::Publish {
+                topics: vec![topic],
+                message,
+            })
+            .map_err(|e| {
+                error!(
+                    self.log,
+                    "PublishBeaconBlock";
+                    "type" => "failed to publish to gossipsub",
+                    "error" => format!("{:?}", e)
+                );
+            })
+            .is_ok()
+    }
+}
+
 impl<T: BeaconChainTypes> BeaconBlockService for BeaconBlockServiceInstance<T> {
     /// Produce a `BeaconBlock` for signing by a validator.
+    ///
+    /// The request's fee recipient is validated, but blocks do not yet contain an execution
+    /// payload, so it is otherwise unused.
     fn produce_beacon_block(
         &mut self,
         ctx: RpcContext,
         req: ProduceBeaconBlockRequest,
         sink: UnarySink<ProduceBeaconBlockResponse>,
     ) {
+        authorize!(self, ctx, sink);
+
         trace!(self.log, "Generating a beacon block"; "req" => format!("{:?}", req));
 
         // decode the request
-        // TODO: requested slot currently unused, see: https://github.com/sigp/lighthouse/issues/336
-        let _requested_slot = Slot::from(req.get_slot());
+        // TODO: requested slot is only used to find the fee recipient, see: https://github.com/sigp/lighthouse/issues/336
+        let requested_slot = Slot::from(req.get_slot());
         let randao_reveal = match Signature::from_ssz_bytes(req.get_randao_reveal()) {
             Ok(reveal) => reveal,
             Err(_) => {
@@ -51,6 +124,24 @@ impl<T: BeaconChainTypes> BeaconBlockService for BeaconBlockServiceInstance<T> {
             }
         };
 
+        // An explicit fee recipient in the request takes precedence over a registered one.
+        let fee_recipient = match req.get_fee_recipient() {
+            bytes if bytes.is_empty() => self.registered_fee_recipient(requested_slot),
+            bytes if bytes.len() == 20 => Some(Address::from_slice(bytes)),
+            _ => {
+                let log_clone = self.log.clone();
+                let f = sink
+                    .fail(RpcStatus::new(
+                        RpcStatusCode::InvalidArgument,
+                        Some("Invalid fee recipient".to_string()),
+                    ))
+                    .map_err(move |e| warn!(log_clone, "failed to reply {:?}: {:?}", req, e));
+                return ctx.spawn(f);
+            }
+        };
+        // TODO: blocks do not yet contain an execution payload, so the fee recipient is unused.
+        trace!(self.log, "Block fee recipient"; "fee_recipient" => format!("{:?}", fee_recipient));
+
         let produced_block = match self.chain.produce_block(randao_reveal) {
             Ok((block, _state)) => block,
             Err(e) => {
@@ -67,11 +158,8 @@ impl<T: BeaconChainTypes> BeaconBlockService for BeaconBlockServiceInstance<T> {
             }
         };
 
-        let mut block = BeaconBlockProto::new();
-        block.set_ssz(ssz_encode(&produced_block));
-
         let mut resp = ProduceBeaconBlockResponse::new();
-        resp.set_block(block);
+        resp.set_block(BeaconBlockAny::from(produced_block).to_proto());
 
         let f = sink
             .success(resp)
@@ -86,53 +174,85 @@ impl<T: BeaconChainTypes> BeaconBlockService for BeaconBlockServiceInstance<T> {
         req: PublishBeaconBlockRequest,
         sink: UnarySink<PublishBeaconBlockResponse>,
     ) {
+        authorize!(self, ctx, sink);
+
         trace!(&self.log, "Attempting to publish a block");
 
         let mut resp = PublishBeaconBlockResponse::new();
 
-        let ssz_serialized_block = req.get_block().get_ssz();
+        // blocks are refused unless they are of the block variant of the fork at their slot
+        let spec = &self.chain.spec;
+        let block = convert::block_from_proto::<T::EthSpec>(req.get_block(), |slot| {
+            spec.block_variant(slot.epoch(T::EthSpec::slots_per_epoch()))
+        })
+        .map(|block| match block {
+            BeaconBlockAny::Base(block) => block,
+        });
 
-        match BeaconBlock::from_ssz_bytes(ssz_serialized_block) {
-            Ok(block) => {
-                match self.chain.process_block(block.clone()) {
+        match block {
+            // The block is recorded before it is processed, so that a different block at the same
+            // slot submitted concurrently is refused rather than also imported.
+            Ok(block) => match self
+                .conflict_guard
+                .check_and_insert_block(block.slot, block.canonical_root())
+            {
+                // A connected validator has already submitted a different block at this slot.
+                Err(conflict) => self.refuse_conflicting_block(&mut resp, conflict),
+                Ok(recorded) => match self.chain.process_block(block.clone()) {
                     Ok(outcome) => {
-                        if let BlockProcessingOutcome::Processed { block_root } = outcome {
-                            // Block was successfully processed.
-                            info!(
-                                self.log,
-                                "Valid block from RPC";
-                                "block_slot" => block.slot,
-                                "block_root" => format!("{}", block_root),
-                            );
+                        let imported = match outcome {
+                            BlockProcessingOutcome::Processed { block_root } => {
+                                // Block was successfully processed.
+                                info!(
+                                    self.log,
+                                    "Valid block from RPC";
+                                    "block_slot" => block.slot,
+                                    "block_root" => format!("{}", block_root),
+                                );
+                                true
+                            }
+                            // The block may have been imported by an earlier attempt to publish
+                            // it, which failed to broadcast it.
+                            BlockProcessingOutcome::BlockIsAlreadyKnown => true,
+                            BlockProcessingOutcome::ParentUnknown { .. }
+                            | BlockProcessingOutcome::FutureSlot { .. } => {
+                                // The block may become valid (e.g., once its parent arrives).
+                                warn!(
+                                    self.log,
+                                    "Unable to import block from RPC";
+                                    "outcome" => format!("{:?}", outcome)
+                                );
 
-                            // get the network topic to send on
-                            let topic = Topic::new(BEACON_BLOCK_TOPIC.into());
-                            let message = PubsubMessage::Block(block.as_ssz_bytes());
-
-                            // Publish the block to the p2p network via gossipsub.
-                            self.network_chan
-                                .try_send(NetworkMessage::Publish {
-                                    topics: vec![topic],
-                                    message: message,
-                                })
-                                .unwrap_or_else(|e| {
-                                    error!(
-                                        self.log,
-                                        "PublishBeaconBlock";
-                                        "type" => "failed to publish to gossipsub",
-                                        "error" => format!("{:?}", e)
-                                    );
-                                });
-
-                            resp.set_success(true);
-                        } else {
-                            // Block was not successfully processed.
-                            warn!(
-                                self.log,
-                                "Invalid block from RPC";
-                                "outcome" => format!("{:?}", outcome)
-                            );
+                                resp.set_retryable(true);
+                                false
+                            }
+                            _ => {
+                                // Block was not successfully processed.
+                                warn!(
+                                    self.log,
+                                    "Invalid block from RPC";
+                                    "outcome" => format!("{:?}", outcome)
+                                );
+                                false
+                            }
+                        };
 
+                        if imported {
+                            let gossiped = self.gossip_block(&block);
+                            resp.set_imported(true);
+                            resp.set_gossiped(gossiped);
+                            resp.set_success(gossiped);
+                            if !gossiped {
+                                resp.set_retryable(true);
+                                resp.set_msg(b"failed to publish to gossipsub".to_vec());
+                            }
+                        } else {
+                            // An invalid block must not prevent a valid one being published at
+                            // the same slot.
+                            if recorded {
+                                self.conflict_guard
+                                    .remove_block(block.slot, block.canonical_root());
+                            }
                             resp.set_success(false);
                             resp.set_msg(
                                 format!("InvalidBlock: {:?}", outcome).as_bytes().to_vec(),
@@ -141,6 +261,10 @@ impl<T: BeaconChainTypes> BeaconBlockService for BeaconBlockServiceInstance<T> {
                     }
                     Err(e) => {
                         // Some failure during processing.
+                        if recorded {
+                            self.conflict_guard
+                                .remove_block(block.slot, block.canonical_root());
+                        }
                         error!(
                             self.log,
                             "PublishBeaconBlock";
@@ -149,15 +273,14 @@ impl<T: BeaconChainTypes> BeaconBlockService for BeaconBlockServiceInstance<T> {
                         );
 
                         resp.set_success(false);
+                        resp.set_retryable(true);
                         resp.set_msg(format!("failed_to_process: {:?}", e).as_bytes().to_vec());
                     }
-                }
-
-                resp.set_success(true);
-            }
-            Err(_) => {
+                },
+            },
+            Err(e) => {
                 resp.set_success(false);
-                resp.set_msg(b"Invalid SSZ".to_vec());
+                resp.set_msg(format!("Invalid block: {:?}", e).as_bytes().to_vec());
             }
         };
 
@@ -1,20 +1,29 @@
 use beacon_chain::{BeaconChain, BeaconChainTypes};
-use futures::Future;
-use grpcio::{RpcContext, UnarySink};
-use protos::services::{Empty, Fork, NodeInfoResponse};
+use futures::{Future, Sink, Stream};
+use grpcio::{RpcContext, ServerStreamingSink, UnarySink, WriteFlags};
+use protos::convert::ToProto;
+use protos::services::{
+    ChainHeadResponse, CheckpointRootRequest, CheckpointRootResponse, Empty, HeadEvent,
+    NodeInfoResponse,
+};
 use protos::services_grpc::BeaconNodeService;
-use slog::{trace, warn};
+use slog::{debug, trace, warn};
 use std::sync::Arc;
+use types::Epoch;
 
 #[derive(Clone)]
 pub struct BeaconNodeServiceInstance<T: BeaconChainTypes> {
     pub chain: Arc<BeaconChain<T>>,
+    /// The token which every call must carry, if any.
+    pub auth_token: Option<String>,
     pub log: slog::Logger,
 }
 
 impl<T: BeaconChainTypes> BeaconNodeService for BeaconNodeServiceInstance<T> {
     /// Provides basic node information.
     fn info(&mut self, ctx: RpcContext, _req: Empty, sink: UnarySink<NodeInfoResponse>) {
+        authorize!(self, ctx, sink);
+
         trace!(self.log, "Node info requested via RPC");
 
         // build the response
@@ -26,18 +35,13 @@ impl<T: BeaconChainTypes> BeaconNodeService for BeaconNodeServiceInstance<T> {
         let state_fork = state.fork.clone();
         let genesis_time = state.genesis_time;
 
-        // build the rpc fork struct
-        let mut fork = Fork::new();
-        fork.set_previous_version(state_fork.previous_version.to_vec());
-        fork.set_current_version(state_fork.current_version.to_vec());
-        fork.set_epoch(state_fork.epoch.into());
-
         let spec = &self.chain.spec;
 
-        node_info.set_fork(fork);
+        node_info.set_fork(state_fork.to_proto());
         node_info.set_genesis_time(genesis_time);
         node_info.set_genesis_slot(spec.genesis_slot.as_u64());
         node_info.set_network_id(u32::from(spec.network_id));
+        node_info.set_genesis_root(self.chain.genesis_block_root.as_bytes().to_vec());
 
         // send the node_info the requester
         let error_log = self.log.clone();
@@ -46,4 +50,96 @@ impl<T: BeaconChainTypes> BeaconNodeService for BeaconNodeServiceInstance<T> {
             .map_err(move |e| warn!(error_log, "failed to reply {:?}", e));
         ctx.spawn(f)
     }
+
+    /// Provides the canonical head and finalized checkpoint, so that validator clients may
+    /// cross-check beacon nodes.
+    fn chain_head(&mut self, ctx: RpcContext, _req: Empty, sink: UnarySink<ChainHeadResponse>) {
+        authorize!(self, ctx, sink);
+
+        trace!(self.log, "Chain head requested via RPC");
+
+        let mut chain_head = ChainHeadResponse::new();
+        {
+            let head = self.chain.head();
+            let finalized_checkpoint = &head.beacon_state.finalized_checkpoint;
+
+            chain_head.set_slot(head.beacon_block.slot.as_u64());
+            chain_head.set_block_root(head.beacon_block_root.as_bytes().to_vec());
+            chain_head.set_finalized_epoch(finalized_checkpoint.epoch.as_u64());
+            chain_head.set_finalized_root(finalized_checkpoint.root.as_bytes().to_vec());
+        }
+
+        let error_log = self.log.clone();
+        let f = sink
+            .success(chain_head)
+            .map_err(move |e| warn!(error_log, "failed to reply {:?}", e));
+        ctx.spawn(f)
+    }
+
+    /// Provides the root of the canonical block at the start of an epoch, so that validator
+    /// clients may check the node against a weak subjectivity checkpoint.
+    fn checkpoint_root(
+        &mut self,
+        ctx: RpcContext,
+        req: CheckpointRootRequest,
+        sink: UnarySink<CheckpointRootResponse>,
+    ) {
+        authorize!(self, ctx, sink);
+
+        let epoch = Epoch::new(req.get_epoch());
+        trace!(self.log, "Checkpoint root requested via RPC"; "epoch" => epoch.as_u64());
+
+        let mut response = CheckpointRootResponse::new();
+        if let Some(root) = self.chain.checkpoint_root(epoch) {
+            response.set_root(root.as_bytes().to_vec());
+        }
+        response.set_finalized_epoch(
+            self.chain
+                .head()
+                .beacon_state
+                .finalized_checkpoint
+                .epoch
+                .as_u64(),
+        );
+
+        let error_log = self.log.clone();
+        let f = sink
+            .success(response)
+            .map_err(move |e| warn!(error_log, "failed to reply {:?}", e));
+        ctx.spawn(f)
+    }
+
+    /// Streams a `HeadEvent` for each change of the canonical head, until the client disconnects.
+    fn subscribe_head(
+        &mut self,
+        ctx: RpcContext,
+        _req: Empty,
+        sink: ServerStreamingSink<HeadEvent>,
+    ) {
+        authorize!(self, ctx, sink);
+
+        trace!(self.log, "Head subscription requested via RPC");
+
+        let events = self
+            .chain
+            .subscribe_head()
+            .map(|event| {
+                let mut head_event = HeadEvent::new();
+                head_event.set_slot(event.slot.as_u64());
+                head_event.set_block_root(event.block_root.as_bytes().to_vec());
+                head_event.set_state_root(event.state_root.as_bytes().to_vec());
+                head_event.set_previous_block_root(event.previous_block_root.as_bytes().to_vec());
+                head_event.set_reorg(event.reorg);
+                head_event.set_reorg_depth(event.reorg_depth);
+                head_event.set_common_ancestor_slot(event.common_ancestor_slot.as_u64());
+                (head_event, WriteFlags::default())
+            })
+            .map_err(|_| grpcio::Error::RemoteStopped);
+
+        let log = self.log.clone();
+        let f = sink.send_all(events).map(|_| ()).map_err(
+            move |e| debug!(log, "Head subscription ended"; "reason" => format!("{:?}", e)),
+        );
+        ctx.spawn(f)
+    }
 }
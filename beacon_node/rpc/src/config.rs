@@ -1,6 +1,8 @@
 use clap::ArgMatches;
+use protos::auth::{read_token, TlsCredentials};
 use serde_derive::{Deserialize, Serialize};
 use std::net::Ipv4Addr;
+use std::path::PathBuf;
 
 /// RPC Configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -11,6 +13,15 @@ pub struct Config {
     pub listen_address: Ipv4Addr,
     /// The port the RPC will listen on.
     pub port: u16,
+    /// The PEM encoded certificate authority which signs the certificates of clients, if the RPC
+    /// is served over mutual TLS.
+    pub tls_ca: Option<PathBuf>,
+    /// The PEM encoded certificate of the RPC, if served over mutual TLS.
+    pub tls_cert: Option<PathBuf>,
+    /// The PEM encoded private key of `tls_cert`.
+    pub tls_key: Option<PathBuf>,
+    /// A file containing the token which every call must carry, if any.
+    pub auth_token_file: Option<PathBuf>,
 }
 
 impl Default for Config {
@@ -19,6 +30,10 @@ impl Default for Config {
             enabled: false, // rpc disabled by default
             listen_address: Ipv4Addr::new(127, 0, 0, 1),
             port: 5051,
+            tls_ca: None,
+            tls_cert: None,
+            tls_key: None,
+            auth_token_file: None,
         }
     }
 }
@@ -39,6 +54,47 @@ impl Config {
             self.port = rpc_port.parse::<u16>().map_err(|_| "rpc-port is not u16")?;
         }
 
+        if let Some(tls_ca) = args.value_of("rpc-tls-ca") {
+            self.tls_ca = Some(PathBuf::from(tls_ca));
+        }
+
+        if let Some(tls_cert) = args.value_of("rpc-tls-cert") {
+            self.tls_cert = Some(PathBuf::from(tls_cert));
+        }
+
+        if let Some(tls_key) = args.value_of("rpc-tls-key") {
+            self.tls_key = Some(PathBuf::from(tls_key));
+        }
+
+        match (&self.tls_ca, &self.tls_cert, &self.tls_key) {
+            (None, None, None) | (Some(_), Some(_), Some(_)) => {}
+            _ => return Err("rpc-tls-ca, rpc-tls-cert and rpc-tls-key must be used together"),
+        }
+
+        if let Some(auth_token_file) = args.value_of("rpc-auth-token") {
+            self.auth_token_file = Some(PathBuf::from(auth_token_file));
+        }
+
         Ok(())
     }
+
+    /// Loads the TLS credentials of the RPC, if it is served over mutual TLS.
+    pub fn tls_credentials(&self) -> Result<Option<TlsCredentials>, String> {
+        match (&self.tls_ca, &self.tls_cert, &self.tls_key) {
+            (Some(ca), Some(cert), Some(key)) => TlsCredentials::load(ca, cert, key)
+                .map(Some)
+                .map_err(|e| format!("Unable to load RPC TLS credentials: {}", e)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Loads the token which every call must carry, if any.
+    pub fn auth_token(&self) -> Result<Option<String>, String> {
+        match &self.auth_token_file {
+            Some(path) => read_token(path)
+                .map(Some)
+                .map_err(|e| format!("Unable to read RPC auth token {:?}: {}", path, e)),
+            None => Ok(None),
+        }
+    }
 }
@@ -0,0 +1,342 @@
+//! A second line of defence against the validator clients connected to this node signing
+//! slashable messages (e.g., if two validator clients are misconfigured with the same keys).
+//!
+//! The `ConflictGuard` remembers every block and attestation submitted via the RPC, and refuses any
+//! later submission which conflicts with one of them. A refused message is neither imported (so
+//! it cannot be included in a block produced by this node) nor published to the network.
+//!
+//! A submission is recorded before it is processed, so that of two conflicting submissions
+//! processed concurrently, only the first to be recorded may be imported. The record is removed
+//! if the submission fails to process, so that an invalid submission does not cause a later valid
+//! one to be refused.
+use std::collections::{BTreeMap, HashMap};
+use std::sync::RwLock;
+use types::{AttestationData, Epoch, Hash256, Slot};
+
+/// The number of epochs of history retained, relative to the most recent message.
+///
+/// Conflicts with messages older than this are not detected.
+pub const HISTORY_EPOCHS: u64 = 64;
+
+/// A submitted message which conflicts with one submitted previously.
+#[derive(Debug, PartialEq)]
+pub enum Conflict {
+    /// A different block was submitted at the same slot.
+    DoubleProposal { slot: Slot, existing_root: Hash256 },
+    /// The validator has attested to different data with the same target epoch.
+    DoubleVote {
+        validator_index: usize,
+        target_epoch: Epoch,
+    },
+    /// The new attestation surrounds, or is surrounded by, an earlier attestation of the
+    /// validator.
+    SurroundVote {
+        validator_index: usize,
+        existing_source_epoch: Epoch,
+        existing_target_epoch: Epoch,
+    },
+}
+
+/// The attestations of a validator, keyed by target epoch.
+type Votes = BTreeMap<Epoch, AttestationData>;
+
+/// Records the blocks and attestations submitted by connected validators.
+pub struct ConflictGuard {
+    /// The canonical root of the block submitted at each slot.
+    blocks: RwLock<BTreeMap<Slot, Hash256>>,
+    /// The attestations of each validator, by validator index.
+    attestations: RwLock<HashMap<usize, Votes>>,
+    slots_per_epoch: u64,
+}
+
+impl ConflictGuard {
+    pub fn new(slots_per_epoch: u64) -> Self {
+        Self {
+            blocks: RwLock::new(BTreeMap::new()),
+            attestations: RwLock::new(HashMap::new()),
+            slots_per_epoch,
+        }
+    }
+
+    /// Records a block at `slot` with `canonical_root`, unless a different block has already
+    /// been recorded at `slot`.
+    ///
+    /// Submitting the same block more than once (e.g., to retry publishing it) is not a conflict.
+    /// Returns `true` if the block was not already recorded, in which case it should be removed
+    /// with `remove_block` if it fails to process.
+    pub fn check_and_insert_block(
+        &self,
+        slot: Slot,
+        canonical_root: Hash256,
+    ) -> Result<bool, Conflict> {
+        let mut blocks = self
+            .blocks
+            .write()
+            .expect("conflict guard lock should not be poisoned");
+
+        block_conflict(&blocks, slot, canonical_root)?;
+        let inserted = blocks.insert(slot, canonical_root).is_none();
+
+        // Prune blocks which are too old to be checked.
+        let history_slots = HISTORY_EPOCHS * self.slots_per_epoch;
+        if let Some(latest) = blocks.keys().next_back().cloned() {
+            if latest.as_u64() > history_slots {
+                *blocks = blocks.split_off(&(latest - history_slots));
+            }
+        }
+
+        Ok(inserted)
+    }
+
+    /// Removes the record of the block at `slot` with `canonical_root`, if any.
+    pub fn remove_block(&self, slot: Slot, canonical_root: Hash256) {
+        let mut blocks = self
+            .blocks
+            .write()
+            .expect("conflict guard lock should not be poisoned");
+
+        if blocks.get(&slot) == Some(&canonical_root) {
+            blocks.remove(&slot);
+        }
+    }
+
+    /// Records an attestation to `data` by each of `validator_indices`, unless it would be a
+    /// double or surround vote for any of them, in which case nothing is recorded.
+    ///
+    /// Submitting the same attestation more than once is not a conflict. Returns the validators
+    /// for which the attestation was not already recorded, for which it should be removed with
+    /// `remove_attestation` if it fails to process.
+    pub fn check_and_insert_attestation(
+        &self,
+        validator_indices: &[usize],
+        data: &AttestationData,
+    ) -> Result<Vec<usize>, Conflict> {
+        let target = data.target.epoch;
+
+        let mut attestations = self
+            .attestations
+            .write()
+            .expect("conflict guard lock should not be poisoned");
+
+        attestation_conflict(&attestations, validator_indices, data)?;
+
+        let mut inserted = vec![];
+        for &validator_index in validator_indices {
+            let votes = attestations.entry(validator_index).or_default();
+            if votes.insert(target, data.clone()).is_none() {
+                inserted.push(validator_index);
+            }
+
+            // Prune attestations which are too old to be checked.
+            if let Some(latest) = votes.keys().next_back().cloned() {
+                if latest.as_u64() > HISTORY_EPOCHS {
+                    *votes = votes.split_off(&(latest - HISTORY_EPOCHS));
+                }
+            }
+        }
+
+        Ok(inserted)
+    }
+
+    /// Removes the record of an attestation to `data` by each of `validator_indices`, if any.
+    pub fn remove_attestation(&self, validator_indices: &[usize], data: &AttestationData) {
+        let target = data.target.epoch;
+
+        let mut attestations = self
+            .attestations
+            .write()
+            .expect("conflict guard lock should not be poisoned");
+
+        for validator_index in validator_indices {
+            if let Some(votes) = attestations.get_mut(validator_index) {
+                if votes.get(&target) == Some(data) {
+                    votes.remove(&target);
+                }
+            }
+        }
+    }
+}
+
+/// Returns the conflict of a block at `slot` with `canonical_root` with the recorded `blocks`, if
+/// any.
+fn block_conflict(
+    blocks: &BTreeMap<Slot, Hash256>,
+    slot: Slot,
+    canonical_root: Hash256,
+) -> Result<(), Conflict> {
+    match blocks.get(&slot) {
+        Some(existing_root) if *existing_root != canonical_root => Err(Conflict::DoubleProposal {
+            slot,
+            existing_root: *existing_root,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Returns the first conflict of an attestation to `data` by `validator_indices` with the recorded
+/// `attestations`, if any.
+fn attestation_conflict(
+    attestations: &HashMap<usize, Votes>,
+    validator_indices: &[usize],
+    data: &AttestationData,
+) -> Result<(), Conflict> {
+    let source = data.source.epoch;
+    let target = data.target.epoch;
+
+    for &validator_index in validator_indices {
+        let votes = match attestations.get(&validator_index) {
+            Some(votes) => votes,
+            None => continue,
+        };
+
+        for (&existing_target, existing) in votes {
+            let existing_source = existing.source.epoch;
+            if existing_target == target && existing != data {
+                return Err(Conflict::DoubleVote {
+                    validator_index,
+                    target_epoch: target,
+                });
+            }
+
+            let surrounds = source < existing_source && existing_target < target;
+            let surrounded = existing_source < source && target < existing_target;
+            if surrounds || surrounded {
+                return Err(Conflict::SurroundVote {
+                    validator_index,
+                    existing_source_epoch: existing_source,
+                    existing_target_epoch: existing_target,
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use types::{Checkpoint, Crosslink};
+
+    fn data(source: u64, target: u64, root: u8) -> AttestationData {
+        AttestationData {
+            beacon_block_root: Hash256::repeat_byte(root),
+            source: Checkpoint {
+                epoch: Epoch::new(source),
+                root: Hash256::zero(),
+            },
+            target: Checkpoint {
+                epoch: Epoch::new(target),
+                root: Hash256::repeat_byte(root),
+            },
+            crosslink: Crosslink::default(),
+        }
+    }
+
+    fn insert(
+        guard: &ConflictGuard,
+        indices: &[usize],
+        data: AttestationData,
+    ) -> Result<(), Conflict> {
+        guard
+            .check_and_insert_attestation(indices, &data)
+            .map(|_| ())
+    }
+
+    #[test]
+    fn refuses_double_proposals() {
+        let guard = ConflictGuard::new(8);
+        let slot = Slot::new(10);
+
+        assert_eq!(
+            guard.check_and_insert_block(slot, Hash256::repeat_byte(1)),
+            Ok(true)
+        );
+        assert_eq!(
+            guard.check_and_insert_block(slot, Hash256::repeat_byte(1)),
+            Ok(false)
+        );
+        assert_eq!(
+            guard.check_and_insert_block(slot, Hash256::repeat_byte(2)),
+            Err(Conflict::DoubleProposal {
+                slot,
+                existing_root: Hash256::repeat_byte(1)
+            })
+        );
+        assert_eq!(
+            guard.check_and_insert_block(slot + 1, Hash256::repeat_byte(2)),
+            Ok(true)
+        );
+    }
+
+    #[test]
+    fn refuses_double_and_surround_votes() {
+        let guard = ConflictGuard::new(8);
+
+        assert_eq!(insert(&guard, &[1, 2], data(2, 3, 1)), Ok(()));
+        assert_eq!(insert(&guard, &[1, 2], data(2, 3, 1)), Ok(()));
+        assert_eq!(
+            insert(&guard, &[3, 2], data(2, 3, 2)),
+            Err(Conflict::DoubleVote {
+                validator_index: 2,
+                target_epoch: Epoch::new(3)
+            })
+        );
+        assert_eq!(
+            insert(&guard, &[1], data(1, 4, 1)),
+            Err(Conflict::SurroundVote {
+                validator_index: 1,
+                existing_source_epoch: Epoch::new(2),
+                existing_target_epoch: Epoch::new(3),
+            })
+        );
+
+        // Nothing is recorded for a refused attestation.
+        assert_eq!(insert(&guard, &[3], data(2, 3, 3)), Ok(()));
+        assert_eq!(insert(&guard, &[1, 2, 3], data(3, 4, 1)), Ok(()));
+    }
+
+    #[test]
+    fn removed_messages_are_not_recorded() {
+        let guard = ConflictGuard::new(8);
+        let slot = Slot::new(10);
+
+        // An invalid block is recorded whilst it is processed, refusing any other at its slot.
+        assert_eq!(
+            guard.check_and_insert_block(slot, Hash256::repeat_byte(1)),
+            Ok(true)
+        );
+        assert!(guard
+            .check_and_insert_block(slot, Hash256::repeat_byte(2))
+            .is_err());
+        // Once it fails to process it is removed, so the valid block which follows is accepted.
+        guard.remove_block(slot, Hash256::repeat_byte(1));
+        assert_eq!(
+            guard.check_and_insert_block(slot, Hash256::repeat_byte(2)),
+            Ok(true)
+        );
+        // Removing a block which is not recorded leaves the recorded block in place.
+        guard.remove_block(slot, Hash256::repeat_byte(1));
+        assert!(guard
+            .check_and_insert_block(slot, Hash256::repeat_byte(1))
+            .is_err());
+
+        // Likewise for an invalid attestation followed by a valid one.
+        assert_eq!(
+            guard.check_and_insert_attestation(&[1, 2], &data(2, 3, 1)),
+            Ok(vec![1, 2])
+        );
+        assert!(insert(&guard, &[1], data(2, 3, 2)).is_err());
+        guard.remove_attestation(&[1, 2], &data(2, 3, 1));
+        assert_eq!(
+            guard.check_and_insert_attestation(&[1], &data(2, 3, 2)),
+            Ok(vec![1])
+        );
+        // A resubmission is not recorded again, so it is not removed if it fails.
+        assert_eq!(
+            guard.check_and_insert_attestation(&[1, 2], &data(2, 3, 2)),
+            Ok(vec![2])
+        );
+        assert!(insert(&guard, &[1], data(2, 3, 1)).is_err());
+    }
+}
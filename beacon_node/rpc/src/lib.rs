@@ -1,12 +1,20 @@
+#[macro_use]
+mod auth;
 mod attestation;
 mod beacon_block;
 mod beacon_node;
 pub mod config;
+mod conflict_guard;
+mod slashing;
+mod subnet_subscriptions;
 mod validator;
 
 use self::attestation::AttestationServiceInstance;
 use self::beacon_block::BeaconBlockServiceInstance;
 use self::beacon_node::BeaconNodeServiceInstance;
+use self::conflict_guard::ConflictGuard;
+use self::slashing::SlashingServiceInstance;
+use self::subnet_subscriptions::SubnetSubscriptions;
 use self::validator::ValidatorServiceInstance;
 use beacon_chain::{BeaconChain, BeaconChainTypes};
 pub use config::Config as RPCConfig;
@@ -15,12 +23,18 @@ use grpcio::{Environment, ServerBuilder};
 use network::NetworkMessage;
 use protos::services_grpc::{
     create_attestation_service, create_beacon_block_service, create_beacon_node_service,
-    create_validator_service,
+    create_slashing_service, create_validator_service,
 };
 use slog::{info, o, warn};
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
 use tokio::runtime::TaskExecutor;
 use tokio::sync::mpsc;
+use types::{Address, EthSpec, PublicKey};
+
+/// The fee recipients registered by validator clients via `PrepareBeaconProposer`, shared between
+/// the validator and block production services.
+pub type FeeRecipients = Arc<RwLock<HashMap<PublicKey, Address>>>;
 
 pub fn start_server<T: BeaconChainTypes + Clone + 'static>(
     config: &RPCConfig,
@@ -28,17 +42,31 @@ pub fn start_server<T: BeaconChainTypes + Clone + 'static>(
     network_chan: mpsc::UnboundedSender<NetworkMessage>,
     beacon_chain: Arc<BeaconChain<T>>,
     log: &slog::Logger,
-) -> exit_future::Signal {
+) -> Result<exit_future::Signal, String> {
     let log = log.new(o!("Service"=>"RPC"));
     let env = Arc::new(Environment::new(1));
 
+    let tls = config.tls_credentials()?;
+    let auth_token = config.auth_token()?;
+    if auth_token.is_some() && tls.is_none() {
+        warn!(
+            log,
+            "RPC auth token is sent unencrypted";
+            "advice" => "use --rpc-tls-ca, --rpc-tls-cert and --rpc-tls-key to serve the RPC over TLS"
+        );
+    }
+
     // build a channel to kill the rpc server
     let (rpc_exit_signal, rpc_exit) = exit_future::signal();
 
+    let fee_recipients = FeeRecipients::default();
+    let conflict_guard = Arc::new(ConflictGuard::new(T::EthSpec::slots_per_epoch()));
+
     // build the individual rpc services
     let beacon_node_service = {
         let instance = BeaconNodeServiceInstance {
             chain: beacon_chain.clone(),
+            auth_token: auth_token.clone(),
             log: log.clone(),
         };
         create_beacon_node_service(instance)
@@ -48,6 +76,9 @@ pub fn start_server<T: BeaconChainTypes + Clone + 'static>(
         let instance = BeaconBlockServiceInstance {
             chain: beacon_chain.clone(),
             network_chan: network_chan.clone(),
+            fee_recipients: fee_recipients.clone(),
+            conflict_guard: conflict_guard.clone(),
+            auth_token: auth_token.clone(),
             log: log.clone(),
         };
         create_beacon_block_service(instance)
@@ -55,32 +86,57 @@ pub fn start_server<T: BeaconChainTypes + Clone + 'static>(
     let validator_service = {
         let instance = ValidatorServiceInstance {
             chain: beacon_chain.clone(),
+            network_chan: network_chan.clone(),
+            fee_recipients,
+            subnet_subscriptions: Arc::new(SubnetSubscriptions::default()),
+            auth_token: auth_token.clone(),
             log: log.clone(),
         };
         create_validator_service(instance)
     };
     let attestation_service = {
         let instance = AttestationServiceInstance {
-            network_chan,
+            network_chan: network_chan.clone(),
             chain: beacon_chain.clone(),
+            conflict_guard,
+            auth_token: auth_token.clone(),
             log: log.clone(),
         };
         create_attestation_service(instance)
     };
+    let slashing_service = {
+        let instance = SlashingServiceInstance {
+            chain: beacon_chain.clone(),
+            network_chan,
+            auth_token: auth_token.clone(),
+            log: log.clone(),
+        };
+        create_slashing_service(instance)
+    };
 
-    let mut server = ServerBuilder::new(env)
+    let builder = ServerBuilder::new(env)
         .register_service(beacon_block_service)
         .register_service(validator_service)
         .register_service(beacon_node_service)
         .register_service(attestation_service)
-        .bind(config.listen_address.to_string(), config.port)
-        .build()
-        .unwrap();
+        .register_service(slashing_service);
+    let builder = match &tls {
+        Some(tls) => {
+            builder.bind_secure(config.listen_address.to_string(), config.port, tls.server())
+        }
+        None => builder.bind(config.listen_address.to_string(), config.port),
+    };
+    let mut server = builder.build().unwrap();
 
     let spawn_rpc = {
         server.start();
         for &(ref host, port) in server.bind_addrs() {
-            info!(log, "gRPC listening on {}:{}", host, port);
+            info!(
+                log,
+                "gRPC listening on {}:{}", host, port;
+                "tls" => tls.is_some(),
+                "auth_token" => auth_token.is_some(),
+            );
         }
         rpc_exit.and_then(move |_| {
             info!(log, "RPC Server shutting down");
@@ -93,5 +149,5 @@ pub fn start_server<T: BeaconChainTypes + Clone + 'static>(
         })
     };
     executor.spawn(spawn_rpc);
-    rpc_exit_signal
+    Ok(rpc_exit_signal)
 }
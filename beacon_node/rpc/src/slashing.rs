@@ -0,0 +1,155 @@
+use beacon_chain::{BeaconChain, BeaconChainTypes};
+use eth2_libp2p::{PubsubMessage, Topic};
+use eth2_libp2p::{ATTESTER_SLASHING_TOPIC, PROPOSER_SLASHING_TOPIC};
+use futures::Future;
+use grpcio::{RpcContext, RpcStatus, RpcStatusCode, UnarySink};
+use network::NetworkMessage;
+use protos::services::{
+    PublishAttesterSlashingRequest, PublishProposerSlashingRequest, PublishSlashingResponse,
+};
+use protos::services_grpc::SlashingService;
+use slog::{error, info, trace, warn};
+use ssz::{Decode, Encode};
+use std::fmt::Debug;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use types::{AttesterSlashing, ProposerSlashing};
+
+#[derive(Clone)]
+pub struct SlashingServiceInstance<T: BeaconChainTypes> {
+    pub chain: Arc<BeaconChain<T>>,
+    pub network_chan: mpsc::UnboundedSender<NetworkMessage>,
+    /// The token which every call must carry, if any.
+    pub auth_token: Option<String>,
+    pub log: slog::Logger,
+}
+
+impl<T: BeaconChainTypes> SlashingServiceInstance<T> {
+    /// Decodes the evidence of `ssz` and has `process` validate it, replying to `sink` with
+    /// whether it was accepted.
+    ///
+    /// Accepted evidence is queued in the operation pool, for inclusion in a block produced by
+    /// this node, and published on `topic` (as the message made by `gossip`) so that other nodes
+    /// may include it too.
+    fn publish<S, E>(
+        &mut self,
+        ctx: RpcContext,
+        sink: UnarySink<PublishSlashingResponse>,
+        kind: &'static str,
+        topic: &'static str,
+        gossip: fn(Vec<u8>) -> PubsubMessage,
+        ssz: &[u8],
+        process: impl FnOnce(S) -> Result<(), E>,
+    ) where
+        S: Decode + Encode,
+        E: Debug,
+    {
+        trace!(self.log, "Publishing slashing"; "kind" => kind);
+
+        let slashing = match S::from_ssz_bytes(ssz) {
+            Ok(slashing) => slashing,
+            Err(_) => {
+                let log_clone = self.log.clone();
+                let f = sink
+                    .fail(RpcStatus::new(
+                        RpcStatusCode::InvalidArgument,
+                        Some(format!("Invalid {}", kind)),
+                    ))
+                    .map_err(move |e| warn!(log_clone, "failed to reply {:?}", e));
+                return ctx.spawn(f);
+            }
+        };
+
+        let mut resp = PublishSlashingResponse::new();
+        let message = gossip(slashing.as_ssz_bytes());
+        match process(slashing) {
+            Ok(()) => {
+                info!(
+                    self.log,
+                    "PublishSlashing";
+                    "type" => "valid_slashing",
+                    "kind" => kind,
+                );
+                resp.set_success(true);
+
+                // The slashing is in the operation pool regardless, so failing to gossip it is
+                // not reported to the caller.
+                self.network_chan
+                    .try_send(NetworkMessage::Publish {
+                        topics: vec![Topic::new(topic.into())],
+                        message,
+                    })
+                    .unwrap_or_else(|e| {
+                        error!(
+                            self.log,
+                            "PublishSlashing";
+                            "type" => "failed to publish to gossipsub",
+                            "kind" => kind,
+                            "error" => format!("{:?}", e),
+                        )
+                    });
+            }
+            Err(e) => {
+                warn!(
+                    self.log,
+                    "PublishSlashing";
+                    "type" => "invalid_slashing",
+                    "kind" => kind,
+                    "error" => format!("{:?}", e),
+                );
+                resp.set_success(false);
+                resp.set_msg(format!("InvalidSlashing: {:?}", e).as_bytes().to_vec());
+            }
+        }
+
+        let error_log = self.log.clone();
+        let f = sink
+            .success(resp)
+            .map_err(move |e| error!(error_log, "failed to reply: {:?}", e));
+        ctx.spawn(f)
+    }
+}
+
+impl<T: BeaconChainTypes> SlashingService for SlashingServiceInstance<T> {
+    /// Validates a proposer slashing, queues it for inclusion in a block and gossips it.
+    fn publish_proposer_slashing(
+        &mut self,
+        ctx: RpcContext,
+        req: PublishProposerSlashingRequest,
+        sink: UnarySink<PublishSlashingResponse>,
+    ) {
+        authorize!(self, ctx, sink);
+
+        let chain = self.chain.clone();
+        self.publish(
+            ctx,
+            sink,
+            "proposer_slashing",
+            PROPOSER_SLASHING_TOPIC,
+            PubsubMessage::ProposerSlashing,
+            req.get_proposer_slashing().get_ssz(),
+            |slashing: ProposerSlashing| chain.process_proposer_slashing(slashing),
+        )
+    }
+
+    /// Validates an attester slashing, queues it for inclusion in a block and gossips it.
+    fn publish_attester_slashing(
+        &mut self,
+        ctx: RpcContext,
+        req: PublishAttesterSlashingRequest,
+        sink: UnarySink<PublishSlashingResponse>,
+    ) {
+        authorize!(self, ctx, sink);
+
+        let chain = self.chain.clone();
+        self.publish(
+            ctx,
+            sink,
+            "attester_slashing",
+            ATTESTER_SLASHING_TOPIC,
+            PubsubMessage::AttesterSlashing,
+            req.get_attester_slashing().get_ssz(),
+            |slashing: AttesterSlashing<T::EthSpec>| chain.process_attester_slashing(slashing),
+        )
+    }
+}
@@ -0,0 +1,104 @@
+//! The attestation subnets needed by the validator clients connected to this node.
+//!
+//! Validator clients send their upcoming attestation duties via `PrepareAttestationSubnets`, ahead
+//! of the duties' slots. A validator which aggregates must receive every attestation of its
+//! committee, so this node subscribes to the subnet of its shard until the slot of its duty has
+//! passed. A validator which only attests publishes to the subnet without subscribing to it.
+use std::collections::HashMap;
+use std::sync::RwLock;
+use types::Slot;
+
+/// The subnets to which this node must subscribe or unsubscribe, by shard.
+#[derive(Debug, PartialEq, Default)]
+pub struct SubnetChanges {
+    pub subscribe: Vec<u64>,
+    pub unsubscribe: Vec<u64>,
+}
+
+/// Records the subnets to which this node subscribes on behalf of aggregators.
+#[derive(Default)]
+pub struct SubnetSubscriptions {
+    /// The last slot at which an aggregator needs each subscribed subnet, by shard.
+    expiries: RwLock<HashMap<u64, Slot>>,
+}
+
+impl SubnetSubscriptions {
+    /// Records that an aggregator needs the subnet of each `(shard, slot)` of `aggregations` until
+    /// `slot`, and drops the subnets which are no longer needed at `current_slot`.
+    ///
+    /// Returns the subnets which were not subscribed before, and those which are no longer needed.
+    pub fn update(
+        &self,
+        current_slot: Slot,
+        aggregations: impl IntoIterator<Item = (u64, Slot)>,
+    ) -> SubnetChanges {
+        let mut expiries = self
+            .expiries
+            .write()
+            .expect("subnet subscriptions lock should not be poisoned");
+        let mut changes = SubnetChanges::default();
+
+        for (shard, slot) in aggregations {
+            if slot < current_slot {
+                continue;
+            }
+            let expiry = expiries.entry(shard).or_insert_with(|| {
+                changes.subscribe.push(shard);
+                slot
+            });
+            *expiry = (*expiry).max(slot);
+        }
+
+        expiries.retain(|shard, expiry| {
+            if *expiry < current_slot {
+                changes.unsubscribe.push(*shard);
+                false
+            } else {
+                true
+            }
+        });
+
+        changes.subscribe.sort();
+        changes.unsubscribe.sort();
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subscribes_until_the_last_aggregation() {
+        let subscriptions = SubnetSubscriptions::default();
+        let slot = Slot::new;
+
+        assert_eq!(
+            subscriptions.update(slot(10), vec![(1, slot(12)), (2, slot(11)), (3, slot(9))]),
+            SubnetChanges {
+                subscribe: vec![1, 2],
+                unsubscribe: vec![],
+            }
+        );
+
+        // A later aggregation extends the subscription, without subscribing again.
+        assert_eq!(
+            subscriptions.update(slot(11), vec![(2, slot(14))]),
+            SubnetChanges::default()
+        );
+        assert_eq!(
+            subscriptions.update(slot(13), vec![]),
+            SubnetChanges {
+                subscribe: vec![],
+                unsubscribe: vec![1],
+            }
+        );
+        assert_eq!(
+            subscriptions.update(slot(15), vec![(1, slot(15))]),
+            SubnetChanges {
+                subscribe: vec![1],
+                unsubscribe: vec![2],
+            }
+        );
+    }
+}
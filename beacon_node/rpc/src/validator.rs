@@ -1,20 +1,181 @@
+use crate::subnet_subscriptions::SubnetSubscriptions;
+use crate::FeeRecipients;
+use beacon_chain::state_regen::state_at_block_root;
 use beacon_chain::{BeaconChain, BeaconChainTypes};
 use bls::PublicKey;
+use eth2_libp2p::{Topic, SHARD_TOPIC_PREFIX};
 use futures::Future;
 use grpcio::{RpcContext, RpcStatus, RpcStatusCode, UnarySink};
-use protos::services::{ActiveValidator, GetDutiesRequest, GetDutiesResponse, ValidatorDuty};
+use network::NetworkMessage;
+use protos::services::{
+    ActiveValidator, Empty, GetDutiesRequest, GetDutiesResponse, GetValidatorHistoryRequest,
+    GetValidatorHistoryResponse, PrepareAttestationSubnetsRequest, PrepareBeaconProposerRequest,
+    ValidatorDuty, ValidatorHistory,
+};
 use protos::services_grpc::ValidatorService;
-use slog::{trace, warn};
+use slog::{debug, error, info, trace, warn};
 use ssz::Decode;
+use state_processing::per_slot_processing;
+use std::collections::HashMap;
 use std::sync::Arc;
-use types::{Epoch, EthSpec, RelativeEpoch};
+use tokio::sync::mpsc;
+use types::{Address, BeaconState, BeaconStateError, Epoch, EthSpec, Hash256, RelativeEpoch, Slot};
+
+/// The number of epochs before the head for which validator history is served. The state of each
+/// requested epoch is replayed from the block roots before the head, so older epochs are refused.
+pub const MAX_HISTORY_EPOCHS: u64 = 128;
 
 #[derive(Clone)]
 pub struct ValidatorServiceInstance<T: BeaconChainTypes> {
     pub chain: Arc<BeaconChain<T>>,
+    pub network_chan: mpsc::UnboundedSender<NetworkMessage>,
+    pub fee_recipients: FeeRecipients,
+    pub subnet_subscriptions: Arc<SubnetSubscriptions>,
+    /// The token which every call must carry, if any.
+    pub auth_token: Option<String>,
     pub log: slog::Logger,
 }
 
+impl<T: BeaconChainTypes> ValidatorServiceInstance<T> {
+    /// Returns the canonical state at the start of `epoch`, with the committee cache of its
+    /// current epoch built.
+    ///
+    /// Returns `None` if `epoch` starts later than the head or earlier than the state history.
+    fn state_at_epoch_start(
+        &self,
+        epoch: Epoch,
+    ) -> Result<Option<BeaconState<T::EthSpec>>, String> {
+        let spec = &self.chain.spec;
+        let start_slot = epoch.start_slot(T::EthSpec::slots_per_epoch());
+        let head_slot = self.chain.head().beacon_block.slot;
+        if start_slot > head_slot {
+            return Ok(None);
+        }
+
+        // The root of the block at the start slot, or of the closest prior block if it was skipped.
+        let block_root = match self
+            .chain
+            .rev_iter_block_roots(head_slot)
+            .find(|(_root, root_slot)| *root_slot == start_slot)
+        {
+            Some((root, _slot)) => root,
+            None => return Ok(None),
+        };
+
+        let (mut state, _) = state_at_block_root(&*self.chain.store, block_root, spec)
+            .map_err(|e| format!("{:?}", e))?;
+        while state.slot < start_slot {
+            state
+                .build_committee_cache(RelativeEpoch::Next, spec)
+                .map_err(|e| format!("{:?}", e))?;
+            per_slot_processing(&mut state, spec).map_err(|e| format!("{:?}", e))?;
+        }
+        state
+            .build_committee_cache(RelativeEpoch::Current, spec)
+            .map_err(|e| format!("{:?}", e))?;
+        state
+            .update_pubkey_cache()
+            .map_err(|e| format!("{:?}", e))?;
+
+        Ok(Some(state))
+    }
+
+    /// Returns the balances and duties at `epoch` of the validators with the SSZ-encoded
+    /// `public_keys`, or the status with which the request fails.
+    fn validator_history(
+        &self,
+        epoch: Epoch,
+        public_keys: &[Vec<u8>],
+    ) -> Result<GetValidatorHistoryResponse, (RpcStatusCode, String)> {
+        let public_keys = public_keys
+            .iter()
+            .map(|bytes| PublicKey::from_ssz_bytes(bytes))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| {
+                (
+                    RpcStatusCode::InvalidArgument,
+                    "Invalid public_key".to_string(),
+                )
+            })?;
+
+        let head_epoch = self
+            .chain
+            .head()
+            .beacon_block
+            .slot
+            .epoch(T::EthSpec::slots_per_epoch());
+        if epoch + MAX_HISTORY_EPOCHS < head_epoch {
+            return Err((
+                RpcStatusCode::OutOfRange,
+                format!(
+                    "Epoch {} is more than {} epochs before the head epoch {}",
+                    epoch, MAX_HISTORY_EPOCHS, head_epoch
+                ),
+            ));
+        }
+
+        let mut resp = GetValidatorHistoryResponse::new();
+        let state = match self.state_at_epoch_start(epoch) {
+            Ok(Some(state)) => state,
+            Ok(None) => {
+                resp.set_known(false);
+                return Ok(resp);
+            }
+            Err(e) => {
+                return Err((
+                    RpcStatusCode::Internal,
+                    format!("Unable to load the state at epoch {}: {}", epoch, e),
+                ))
+            }
+        };
+        resp.set_known(true);
+
+        let state_error = |e| {
+            (
+                RpcStatusCode::Internal,
+                format!("Beacon state error {:?}", e),
+            )
+        };
+        let spec = &self.chain.spec;
+        let start_slot = epoch.start_slot(T::EthSpec::slots_per_epoch());
+        let proposers = epoch
+            .slot_iter(T::EthSpec::slots_per_epoch())
+            .map(|slot| state.get_beacon_proposer_index(slot, RelativeEpoch::Current, spec))
+            .collect::<Result<Vec<usize>, _>>()
+            .map_err(state_error)?;
+
+        for public_key in &public_keys {
+            // validators not yet in the registry have no history
+            let index = match state.get_validator_index(public_key).map_err(state_error)? {
+                Some(index) => index,
+                None => continue,
+            };
+
+            let mut duties = ActiveValidator::new();
+            match validator_duty(
+                &state,
+                index,
+                RelativeEpoch::Current,
+                &proposers,
+                start_slot,
+            )
+            .map_err(state_error)?
+            {
+                Some(duty) => duties.set_duty(duty),
+                None => duties.set_none(false),
+            }
+
+            let mut history = ValidatorHistory::new();
+            history.set_balance(state.balances[index]);
+            history.set_duties(duties);
+            resp.mut_validators()
+                .insert(public_key.as_hex_string(), history);
+        }
+
+        Ok(resp)
+    }
+}
+
 impl<T: BeaconChainTypes> ValidatorService for ValidatorServiceInstance<T> {
     /// For a list of validator public keys, this function returns the slot at which each
     /// validator must propose a block, attest to a shard, their shard committee and the shard they
@@ -25,6 +186,8 @@ impl<T: BeaconChainTypes> ValidatorService for ValidatorServiceInstance<T> {
         req: GetDutiesRequest,
         sink: UnarySink<GetDutiesResponse>,
     ) {
+        authorize!(self, ctx, sink);
+
         let validators = req.get_validators();
         trace!(self.log, "RPC request"; "endpoint" => "GetValidatorDuties", "epoch" => req.get_epoch());
 
@@ -36,7 +199,6 @@ impl<T: BeaconChainTypes> ValidatorService for ValidatorServiceInstance<T> {
             .expect("This is legacy code and should be removed");
         let epoch = Epoch::from(req.get_epoch());
         let mut resp = GetDutiesResponse::new();
-        let resp_validators = resp.mut_active_validators();
 
         let relative_epoch =
             match RelativeEpoch::from_epoch(state.slot.epoch(T::EthSpec::slots_per_epoch()), epoch)
@@ -55,6 +217,23 @@ impl<T: BeaconChainTypes> ValidatorService for ValidatorServiceInstance<T> {
                 }
             };
 
+        let dependent_root = match dependent_root(state, epoch, self.chain.head().beacon_block_root)
+        {
+            Ok(root) => root,
+            Err(e) => {
+                let log_clone = self.log.clone();
+                let f = sink
+                    .fail(RpcStatus::new(
+                        RpcStatusCode::FailedPrecondition,
+                        Some(format!("Could not find dependent root: {:?}", e)),
+                    ))
+                    .map_err(move |e| warn!(log_clone, "failed to reply {:?} : {:?}", req, e));
+                return ctx.spawn(f);
+            }
+        };
+        resp.set_dependent_root(dependent_root.as_bytes().to_vec());
+        let resp_validators = resp.mut_duties();
+
         let validator_proposers: Result<Vec<usize>, _> = epoch
             .slot_iter(T::EthSpec::slots_per_epoch())
             .map(|slot| state.get_beacon_proposer_index(slot, relative_epoch, &spec))
@@ -74,6 +253,40 @@ impl<T: BeaconChainTypes> ValidatorService for ValidatorServiceInstance<T> {
             }
         };
 
+        // The duties of the next epoch are known from the current epoch, so they are returned
+        // along with those of the current epoch. Its proposers may yet change with the effective
+        // balances at the start of the epoch.
+        let next_epoch = epoch + 1;
+        let next_epoch_proposers: Result<Option<Vec<usize>>, _> =
+            if relative_epoch == RelativeEpoch::Current {
+                next_epoch
+                    .slot_iter(T::EthSpec::slots_per_epoch())
+                    .map(|slot| state.get_beacon_proposer_index(slot, RelativeEpoch::Next, &spec))
+                    .collect::<Result<Vec<usize>, _>>()
+                    .map(Some)
+            } else {
+                Ok(None)
+            };
+        let next_epoch_proposers = match next_epoch_proposers {
+            Ok(v) => v,
+            Err(e) => {
+                let log_clone = self.log.clone();
+                let f = sink
+                    .fail(RpcStatus::new(
+                        RpcStatusCode::FailedPrecondition,
+                        Some(format!(
+                            "Could not find next epoch beacon proposers: {:?}",
+                            e
+                        )),
+                    ))
+                    .map_err(move |e| warn!(log_clone, "failed to reply {:?} : {:?}", req, e));
+                return ctx.spawn(f);
+            }
+        };
+        let mut next_epoch_duties = HashMap::new();
+
+        let mut slashed = vec![];
+
         // get the duties for each validator
         for validator_pk in validators.get_public_keys() {
             let mut active_validator = ActiveValidator::new();
@@ -102,7 +315,11 @@ impl<T: BeaconChainTypes> ValidatorService for ValidatorServiceInstance<T> {
                         "RPC requested a public key that is not in the registry: {:?}", public_key
                     );
                     active_validator.set_none(false);
-                    resp_validators.push(active_validator);
+                    if next_epoch_proposers.is_some() {
+                        next_epoch_duties
+                            .insert(public_key.as_hex_string(), active_validator.clone());
+                    }
+                    resp_validators.insert(public_key.as_hex_string(), active_validator);
                     continue;
                 }
                 // the cache is not built, throw an error
@@ -118,19 +335,57 @@ impl<T: BeaconChainTypes> ValidatorService for ValidatorServiceInstance<T> {
                 }
             };
 
+            if state.validators[val_index].slashed {
+                slashed.push(public_key.as_hex_string());
+            }
+
+            // a validator inactive during the requested epoch may be activated in the next
+            if let Some(proposers) = &next_epoch_proposers {
+                let mut next_active_validator = ActiveValidator::new();
+                let next_start_slot = next_epoch.start_slot(T::EthSpec::slots_per_epoch());
+                match validator_duty(
+                    state,
+                    val_index,
+                    RelativeEpoch::Next,
+                    proposers,
+                    next_start_slot,
+                ) {
+                    Ok(Some(duty)) => next_active_validator.set_duty(duty),
+                    Ok(None) => next_active_validator.set_none(false),
+                    Err(e) => {
+                        let log_clone = self.log.clone();
+                        let f = sink
+                            .fail(RpcStatus::new(
+                                RpcStatusCode::FailedPrecondition,
+                                Some(format!("Beacon state error {:?}", e)),
+                            ))
+                            .map_err(move |e| {
+                                warn!(log_clone, "Failed to reply {:?}: {:?}", req, e)
+                            });
+                        return ctx.spawn(f);
+                    }
+                }
+                next_epoch_duties.insert(public_key.as_hex_string(), next_active_validator);
+            }
+
             // get attestation duties and check if validator is active
-            let attestation_duties = match state
-                .get_attestation_duties(val_index, RelativeEpoch::Current)
-            {
-                Ok(Some(v)) => v,
-                Ok(_) => {
+            let start_slot = epoch.start_slot(T::EthSpec::slots_per_epoch());
+            match validator_duty(
+                state,
+                val_index,
+                relative_epoch,
+                &validator_proposers,
+                start_slot,
+            ) {
+                Ok(Some(duty)) => active_validator.set_duty(duty),
+                Ok(None) => {
                     // validator is inactive, go to the next validator
                     warn!(
                         self.log,
                         "RPC requested an inactive validator key: {:?}", public_key
                     );
                     active_validator.set_none(false);
-                    resp_validators.push(active_validator);
+                    resp_validators.insert(public_key.as_hex_string(), active_validator);
                     continue;
                 }
                 // the cache is not built, throw an error
@@ -144,33 +399,231 @@ impl<T: BeaconChainTypes> ValidatorService for ValidatorServiceInstance<T> {
                         .map_err(move |e| warn!(log_clone, "Failed to reply {:?}: {:?}", req, e));
                     return ctx.spawn(f);
                 }
-            };
+            }
 
-            // we have an active validator, set its duties
-            let mut duty = ValidatorDuty::new();
+            resp_validators.insert(public_key.as_hex_string(), active_validator);
+        }
+        resp.set_slashed(slashed.into());
+        if next_epoch_proposers.is_some() {
+            resp.set_next_epoch_duties(next_epoch_duties);
+            resp.set_has_next_epoch_duties(true);
+        }
 
-            // check if the validator needs to propose a block
-            if let Some(slot) = validator_proposers.iter().position(|&v| val_index == v) {
-                duty.set_block_production_slot(
-                    epoch.start_slot(T::EthSpec::slots_per_epoch()).as_u64() + slot as u64,
-                );
-            } else {
-                // no blocks to propose this epoch
-                duty.set_none(false)
+        let f = sink
+            .success(resp)
+            .map_err(move |e| println!("failed to reply {:?}: {:?}", req, e));
+        ctx.spawn(f)
+    }
+
+    /// Registers the fee recipient to be used in the block proposals of each validator.
+    ///
+    /// Replaces any fee recipient previously registered for the same validator. Blocks do not yet
+    /// contain an execution payload, so the fee recipients are recorded but not yet used.
+    fn prepare_beacon_proposer(
+        &mut self,
+        ctx: RpcContext,
+        req: PrepareBeaconProposerRequest,
+        sink: UnarySink<Empty>,
+    ) {
+        authorize!(self, ctx, sink);
+
+        trace!(self.log, "RPC request"; "endpoint" => "PrepareBeaconProposer", "validators" => req.get_preparations().len());
+
+        let preparations: Result<Vec<(PublicKey, Address)>, _> = req
+            .get_preparations()
+            .iter()
+            .map(|preparation| {
+                let public_key = PublicKey::from_ssz_bytes(preparation.get_public_key())
+                    .map_err(|_| "Invalid public_key".to_string())?;
+                let fee_recipient = preparation.get_fee_recipient();
+                if fee_recipient.len() != 20 {
+                    return Err(format!("Invalid fee_recipient for {:?}", public_key));
+                }
+                Ok((public_key, Address::from_slice(fee_recipient)))
+            })
+            .collect();
+
+        let preparations = match preparations {
+            Ok(v) => v,
+            Err(e) => {
+                let log_clone = self.log.clone();
+                let f = sink
+                    .fail(RpcStatus::new(RpcStatusCode::InvalidArgument, Some(e)))
+                    .map_err(move |e| warn!(log_clone, "failed to reply {:?}: {:?}", req, e));
+                return ctx.spawn(f);
             }
+        };
 
-            duty.set_committee_index(attestation_duties.committee_index as u64);
-            duty.set_attestation_slot(attestation_duties.slot.as_u64());
-            duty.set_attestation_shard(attestation_duties.shard);
-            duty.set_committee_len(attestation_duties.committee_len as u64);
+        match self.fee_recipients.write() {
+            Ok(mut fee_recipients) => {
+                for (public_key, fee_recipient) in preparations {
+                    if fee_recipients.insert(public_key.clone(), fee_recipient)
+                        != Some(fee_recipient)
+                    {
+                        info!(self.log, "Fee recipient registered"; "validator" => format!("{:?}", public_key), "fee_recipient" => format!("{:?}", fee_recipient));
+                    }
+                }
+            }
+            Err(_) => {
+                let log_clone = self.log.clone();
+                let f = sink
+                    .fail(RpcStatus::new(
+                        RpcStatusCode::Internal,
+                        Some("Fee recipients lock poisoned".to_string()),
+                    ))
+                    .map_err(move |e| warn!(log_clone, "failed to reply {:?}: {:?}", req, e));
+                return ctx.spawn(f);
+            }
+        }
 
-            active_validator.set_duty(duty);
-            resp_validators.push(active_validator);
+        let log_clone = self.log.clone();
+        let f = sink
+            .success(Empty::new())
+            .map_err(move |e| warn!(log_clone, "failed to reply {:?}: {:?}", req, e));
+        ctx.spawn(f)
+    }
+
+    /// Subscribes to the subnet of the shard of each aggregator's upcoming attestation duty, until
+    /// its slot has passed.
+    ///
+    /// Validators which do not aggregate need not receive the attestations of their subnet, so
+    /// their duties change no subscriptions.
+    fn prepare_attestation_subnets(
+        &mut self,
+        ctx: RpcContext,
+        req: PrepareAttestationSubnetsRequest,
+        sink: UnarySink<Empty>,
+    ) {
+        authorize!(self, ctx, sink);
+
+        trace!(self.log, "RPC request"; "endpoint" => "PrepareAttestationSubnets", "subscriptions" => req.get_subscriptions().len());
+
+        let current_slot = self
+            .chain
+            .read_slot_clock()
+            .unwrap_or_else(|| self.chain.present_slot());
+        let aggregations = req
+            .get_subscriptions()
+            .iter()
+            .filter(|subscription| subscription.get_aggregator())
+            .map(|subscription| (subscription.get_shard(), Slot::new(subscription.get_slot())));
+        let changes = self.subnet_subscriptions.update(current_slot, aggregations);
+
+        let topics = |shards: Vec<u64>| -> Vec<Topic> {
+            shards
+                .into_iter()
+                .map(|shard| Topic::new(format!("{}{}", SHARD_TOPIC_PREFIX, shard)))
+                .collect()
+        };
+        if !changes.subscribe.is_empty() {
+            debug!(self.log, "Subscribing to attestation subnets"; "shards" => format!("{:?}", changes.subscribe));
+            self.network_chan
+                .try_send(NetworkMessage::Subscribe(topics(changes.subscribe)))
+                .unwrap_or_else(|e| error!(self.log, "Unable to subscribe to attestation subnets"; "error" => format!("{:?}", e)));
+        }
+        if !changes.unsubscribe.is_empty() {
+            debug!(self.log, "Unsubscribing from attestation subnets"; "shards" => format!("{:?}", changes.unsubscribe));
+            self.network_chan
+                .try_send(NetworkMessage::Unsubscribe(topics(changes.unsubscribe)))
+                .unwrap_or_else(|e| error!(self.log, "Unable to unsubscribe from attestation subnets"; "error" => format!("{:?}", e)));
         }
 
         let f = sink
-            .success(resp)
+            .success(Empty::new())
             .map_err(move |e| println!("failed to reply {:?}: {:?}", req, e));
         ctx.spawn(f)
     }
+
+    /// For a list of validator public keys, returns the balance of each validator at the start of
+    /// an epoch and its duties during it, from the canonical state at its start.
+    ///
+    /// Unlike `get_validator_duties`, the epoch may be any within the state history, so that the
+    /// performance of the validators can be reviewed.
+    fn get_validator_history(
+        &mut self,
+        ctx: RpcContext,
+        req: GetValidatorHistoryRequest,
+        sink: UnarySink<GetValidatorHistoryResponse>,
+    ) {
+        authorize!(self, ctx, sink);
+
+        let epoch = Epoch::from(req.get_epoch());
+        trace!(self.log, "RPC request"; "endpoint" => "GetValidatorHistory", "epoch" => epoch.as_u64());
+
+        let resp = self.validator_history(epoch, req.get_validators().get_public_keys());
+
+        let log_clone = self.log.clone();
+        let f = match resp {
+            Ok(resp) => sink.success(resp),
+            Err((code, msg)) => sink.fail(RpcStatus::new(code, Some(msg))),
+        }
+        .map_err(move |e| warn!(log_clone, "failed to reply {:?}: {:?}", req, e));
+        ctx.spawn(f)
+    }
+}
+
+/// Returns the duties of the validator at `validator_index` during the epoch of `relative_epoch`
+/// of `state`, or `None` if it is inactive.
+///
+/// `proposers` holds the index of the proposer at each slot of the epoch, starting at
+/// `start_slot`.
+fn validator_duty<E: EthSpec>(
+    state: &BeaconState<E>,
+    validator_index: usize,
+    relative_epoch: RelativeEpoch,
+    proposers: &[usize],
+    start_slot: Slot,
+) -> Result<Option<ValidatorDuty>, BeaconStateError> {
+    let attestation_duties = match state.get_attestation_duties(validator_index, relative_epoch)? {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+
+    let mut duty = ValidatorDuty::new();
+
+    // check if the validator needs to propose blocks, possibly at several slots
+    let proposal_slots: Vec<u64> = proposers
+        .iter()
+        .enumerate()
+        .filter(|(_, v)| **v == validator_index)
+        .map(|(slot, _)| start_slot.as_u64() + slot as u64)
+        .collect();
+    match proposal_slots.first() {
+        Some(slot) => duty.set_block_production_slot(*slot),
+        // no blocks to propose this epoch
+        None => duty.set_none(false),
+    }
+    duty.set_block_production_slots(proposal_slots);
+
+    duty.set_committee_index(attestation_duties.committee_index as u64);
+    duty.set_attestation_slot(attestation_duties.slot.as_u64());
+    duty.set_attestation_shard(attestation_duties.shard);
+    duty.set_committee_len(attestation_duties.committee_len as u64);
+
+    Ok(Some(duty))
+}
+
+/// Returns the root of the block at the last slot before `epoch` on the chain of `state`, whose head
+/// is `head_root`, or of the genesis block if `epoch` is the genesis epoch.
+///
+/// The shuffling of `epoch`, and so all duties during it, are determined by the chain up to that
+/// block.
+fn dependent_root<E: EthSpec>(
+    state: &BeaconState<E>,
+    epoch: Epoch,
+    head_root: Hash256,
+) -> Result<Hash256, BeaconStateError> {
+    let dependent_slot = epoch
+        .start_slot(E::slots_per_epoch())
+        .as_u64()
+        .saturating_sub(1);
+
+    // The state only records the roots of the blocks before its slot.
+    if dependent_slot < state.slot.as_u64() {
+        state
+            .get_block_root(Slot::new(dependent_slot))
+            .map(|root| *root)
+    } else {
+        Ok(head_root)
+    }
 }
@@ -0,0 +1,90 @@
+use beacon_chain::state_regen::state_at_block_root;
+use clap::ArgMatches;
+use client::{error, ClientConfig, Eth2Config};
+use slog::info;
+use ssz::Encode;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Instant;
+use store::DiskStore;
+use types::{EthSpec, Hash256, InteropEthSpec, MainnetEthSpec, MinimalEthSpec};
+
+/// Run the `db` subcommand.
+pub fn run_db_command(
+    matches: &ArgMatches,
+    client_config: &ClientConfig,
+    eth2_config: &Eth2Config,
+    log: &slog::Logger,
+) -> error::Result<()> {
+    match matches.subcommand() {
+        ("state-at", Some(sub_matches)) => match eth2_config.spec_constants.as_str() {
+            "minimal" => state_at::<MinimalEthSpec>(sub_matches, client_config, eth2_config, log),
+            "mainnet" => state_at::<MainnetEthSpec>(sub_matches, client_config, eth2_config, log),
+            "interop" => state_at::<InteropEthSpec>(sub_matches, client_config, eth2_config, log),
+            spec => Err(format!("Unknown spec constants: {}", spec).into()),
+        },
+        _ => Err("No db subcommand provided, see --help for options".into()),
+    }
+}
+
+/// Reconstruct the post-state of `--block-root` from the on-disk database and write it to a file
+/// as SSZ.
+fn state_at<E: EthSpec>(
+    matches: &ArgMatches,
+    client_config: &ClientConfig,
+    eth2_config: &Eth2Config,
+    log: &slog::Logger,
+) -> error::Result<()> {
+    let block_root = parse_root(
+        matches
+            .value_of("block-root")
+            .ok_or_else::<error::Error, _>(|| "--block-root is required".into())?,
+    )?;
+
+    let output_path = matches
+        .value_of("output")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(format!("state_{:x}.ssz", block_root)));
+
+    let db_path = client_config
+        .db_path()
+        .ok_or_else::<error::Error, _>(|| "Unable to access database path".into())?;
+    let store =
+        DiskStore::open(&db_path).map_err(|e| format!("Unable to open database: {:?}", e))?;
+
+    let timer = Instant::now();
+    let (state, stats) = state_at_block_root::<E, _>(&store, block_root, &eth2_config.spec)
+        .map_err(|e| format!("Unable to regenerate state: {:?}", e))?;
+    let total_time = timer.elapsed();
+
+    fs::write(&output_path, state.as_ssz_bytes())
+        .map_err(|e| format!("Unable to write {:?}: {:?}", output_path, e))?;
+
+    info!(
+        log,
+        "Regenerated state";
+        "output" => format!("{:?}", output_path),
+        "state_root" => format!("{}", state.canonical_root()),
+        "slot" => state.slot,
+        "base_state_slot" => stats.base_state_slot,
+        "blocks_replayed" => stats.blocks_replayed,
+        "slots_processed" => stats.slots_processed,
+        "load_ms" => stats.load_time.as_millis() as u64,
+        "replay_ms" => stats.replay_time.as_millis() as u64,
+        "total_ms" => total_time.as_millis() as u64,
+    );
+
+    Ok(())
+}
+
+/// Parse a `0x`-prefixed (optional), hex-encoded 32-byte root.
+fn parse_root(string: &str) -> error::Result<Hash256> {
+    let hex = string.trim_start_matches("0x");
+    let bytes = hex::decode(hex).map_err(|e| format!("Invalid block root {}: {:?}", string, e))?;
+
+    if bytes.len() == 32 {
+        Ok(Hash256::from_slice(&bytes))
+    } else {
+        Err(format!("Block root must be 32 bytes, got {}", bytes.len()).into())
+    }
+}
@@ -1,6 +1,7 @@
+mod db;
 mod run;
 
-use clap::{App, Arg};
+use clap::{App, Arg, SubCommand};
 use client::{ClientConfig, Eth2Config};
 use env_logger::{Builder, Env};
 use eth2_config::{read_from_file, write_to_file};
@@ -13,8 +14,13 @@ pub const DEFAULT_DATA_DIR: &str = ".lighthouse";
 pub const CLIENT_CONFIG_FILENAME: &str = "beacon-node.toml";
 pub const ETH2_CONFIG_FILENAME: &str = "eth2-spec.toml";
 pub const TESTNET_CONFIG_FILENAME: &str = "testnet.toml";
+pub const SERVICE_NAME: &str = "lighthouse-beacon-node";
 
 fn main() {
+    system_service::start(SERVICE_NAME, run_main);
+}
+
+fn run_main() {
     // debugging output for libp2p and external crates
     Builder::from_env(Env::default()).init();
 
@@ -39,6 +45,12 @@ fn main() {
                 .help("File path where output will be written.")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("windows-service")
+                .long("windows-service")
+                .help("Run as the lighthouse-beacon-node Windows service. Only for the command line of the registered service.")
+                .takes_value(false),
+        )
         .arg(
             Arg::with_name("network-dir")
                 .long("network-dir")
@@ -64,9 +76,10 @@ fn main() {
                 .takes_value(true),
         )
         .arg(
-            Arg::with_name("maxpeers")
-                .long("maxpeers")
-                .help("The maximum number of peers (default 10).")
+            Arg::with_name("target-peers")
+                .long("target-peers")
+                .alias("maxpeers")
+                .help("The target number of peers, excluding trusted peers (default 10). Excess peers are pruned, keeping those which cover our shard subnets.")
                 .takes_value(true),
         )
         .arg(
@@ -105,6 +118,13 @@ fn main() {
                 .help("One or more comma-delimited multiaddrs to manually connect to a libp2p peer without an ENR.")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("trusted-peers")
+                .long("trusted-peers")
+                .value_name("MULTIADDR/ENR")
+                .help("One or more comma-delimited multiaddrs (ending in /p2p/<peer id>) or ENRs of peers which are always connected to and never disconnected for faults.")
+                .takes_value(true),
+        )
         /*
          * gRPC parameters.
          */
@@ -128,6 +148,34 @@ fn main() {
                 .help("Listen port for RPC endpoint.")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("rpc-tls-ca")
+                .long("rpc-tls-ca")
+                .value_name("FILE")
+                .help("PEM certificate authority which signs the certificates of validator clients. Requires --rpc-tls-cert and --rpc-tls-key, and serves the RPC over mutual TLS.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("rpc-tls-cert")
+                .long("rpc-tls-cert")
+                .value_name("FILE")
+                .help("PEM certificate presented by the RPC to validator clients.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("rpc-tls-key")
+                .long("rpc-tls-key")
+                .value_name("FILE")
+                .help("PEM private key of --rpc-tls-cert.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("rpc-auth-token")
+                .long("rpc-auth-token")
+                .value_name("FILE")
+                .help("File containing a token which every RPC call must carry. Calls without it are rejected.")
+                .takes_value(true),
+        )
         /*
          * HTTP server parameters.
          */
@@ -172,6 +220,30 @@ fn main() {
                 .help("Set the listen TCP port for the RESTful HTTP API server.")
                 .takes_value(true),
         )
+        /*
+         * Eth1 parameters.
+         */
+        .arg(
+            Arg::with_name("eth1-endpoint")
+                .long("eth1-endpoint")
+                .value_name("URL")
+                .help("Follow the deposit contract through the JSON-RPC endpoint of an eth1 node, so that produced blocks vote for eth1 data and include deposits.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("deposit-contract")
+                .long("deposit-contract")
+                .value_name("ADDRESS")
+                .help("The 0x-prefixed address of the deposit contract.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("deposit-contract-deploy-block")
+                .long("deposit-contract-deploy-block")
+                .value_name("BLOCK")
+                .help("The number of the eth1 block which deployed the deposit contract.")
+                .takes_value(true),
+        )
 
         /*
          * Database parameters.
@@ -185,6 +257,13 @@ fn main() {
                 .possible_values(&["disk", "memory"])
                 .default_value("memory"),
         )
+        .arg(
+            Arg::with_name("state-root-sample-rate")
+                .long("state-root-sample-rate")
+                .value_name("N")
+                .help("Only verify the state root of one in every N imported blocks (and of the first block of each epoch) to speed up imports. Signatures are always verified. The node is NOT VALIDATING and must not serve validators.")
+                .takes_value(true),
+        )
         /*
          * Specification/testnet params.
          */
@@ -223,6 +302,33 @@ fn main() {
                 .help("Sets the verbosity level")
                 .takes_value(true),
         )
+        /*
+         * Database utilities.
+         */
+        .subcommand(
+            SubCommand::with_name("db")
+                .about("Inspects the beacon node database.")
+                .subcommand(
+                    SubCommand::with_name("state-at")
+                        .about("Regenerates the post-state of a block, replaying from the nearest stored state.")
+                        .arg(
+                            Arg::with_name("block-root")
+                                .long("block-root")
+                                .value_name("ROOT")
+                                .help("The 0x-prefixed root of the block.")
+                                .takes_value(true)
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("output")
+                                .long("output")
+                                .short("o")
+                                .value_name("FILE")
+                                .help("File to which the SSZ-encoded state is written (default state_<root>.ssz).")
+                                .takes_value(true),
+                        ),
+                ),
+        )
         .get_matches();
 
     // build the initial logger
@@ -374,9 +480,30 @@ fn main() {
         }
     };
 
-    // check to ensure the spec constants between the client and eth2_config match
-    if eth2_config.spec_constants != client_config.spec_constants {
-        crit!(log, "Specification constants do not match."; "client_config" => format!("{}", client_config.spec_constants), "eth2_config" => format!("{}", eth2_config.spec_constants));
+    // Cross-check the final configuration, reporting all problems at once.
+    let problems = client_config.validate(&eth2_config);
+    if !problems.is_empty() {
+        for problem in &problems {
+            crit!(
+                log,
+                "Invalid configuration";
+                "setting" => &problem.setting,
+                "problem" => &problem.problem,
+                "suggestion" => &problem.suggestion
+            );
+        }
+        crit!(
+            log,
+            "Configuration has {} problem(s), exiting",
+            problems.len()
+        );
+        return;
+    }
+
+    if let Some(db_matches) = matches.subcommand_matches("db") {
+        if let Err(e) = db::run_db_command(db_matches, &client_config, &eth2_config, &log) {
+            crit!(log, "Database command failed"; "reason" => format!("{:}", e));
+        }
         return;
     }
 
@@ -1,13 +1,13 @@
 use client::{
-    error, notifier, BeaconChainTypes, Client, ClientConfig, ClientType, Eth2Config,
+    error, notifier, readiness, BeaconChainTypes, Client, ClientConfig, ClientType, Eth2Config,
     InitialiseBeaconChain,
 };
 use futures::sync::oneshot;
 use futures::Future;
 use slog::{error, info, warn};
-use std::cell::RefCell;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 use store::{DiskStore, MemoryStore};
 use tokio::runtime::Builder;
 use tokio::runtime::Runtime;
@@ -130,19 +130,18 @@ where
 
     let client: Client<T> = Client::new(client_config, eth2_config, store, log.clone(), &executor)?;
 
-    // run service until ctrl-c
+    // run service until ctrl-c, or until stopped by the Windows service control manager
     let (ctrlc_send, ctrlc_oneshot) = oneshot::channel();
-    let ctrlc_send_c = RefCell::new(Some(ctrlc_send));
-    ctrlc::set_handler(move || {
-        if let Some(ctrlc_send) = ctrlc_send_c.try_borrow_mut().unwrap().take() {
-            ctrlc_send.send(()).expect("Error sending ctrl-c message");
-        }
-    })
-    .map_err(|e| format!("Could not set ctrlc handler: {:?}", e))?;
+    let ctrlc_send = Arc::new(Mutex::new(Some(ctrlc_send)));
+    let ctrlc_send_c = ctrlc_send.clone();
+    ctrlc::set_handler(move || send_shutdown(&ctrlc_send_c))
+        .map_err(|e| format!("Could not set ctrlc handler: {:?}", e))?;
+    system_service::on_stop(move || send_shutdown(&ctrlc_send));
 
     let (exit_signal, exit) = exit_future::signal();
 
-    notifier::run(&client, executor, exit);
+    notifier::run(&client, executor.clone(), exit.clone());
+    readiness::run(&client, executor, exit);
 
     runtime
         .block_on(ctrlc_oneshot)
@@ -158,6 +157,13 @@ where
     Ok(())
 }
 
+/// Sends the shutdown message, unless it has already been sent.
+fn send_shutdown(sender: &Mutex<Option<oneshot::Sender<()>>>) {
+    if let Some(sender) = sender.lock().ok().and_then(|mut sender| sender.take()) {
+        sender.send(()).expect("Error sending shutdown message");
+    }
+}
+
 /// A convenience trait, providing a method to open a database.
 ///
 /// Panics if unable to open the database.
@@ -1,10 +1,12 @@
 mod reduced_tree;
+mod spec_store;
 
 use std::sync::Arc;
 use store::Store;
-use types::{BeaconBlock, EthSpec, Hash256, Slot};
+use types::{Attestation, AttesterSlashing, BeaconBlock, Checkpoint, EthSpec, Hash256, Slot};
 
 pub use reduced_tree::ThreadSafeReducedTree;
+pub use spec_store::{Error as SpecStoreError, LatestMessage, SpecStore};
 
 pub type Result<T> = std::result::Result<T, String>;
 
@@ -43,4 +45,76 @@ pub trait LmdGhost<S: Store, E: EthSpec>: Send + Sync {
         finalized_block: &BeaconBlock<E>,
         finalized_block_root: Hash256,
     ) -> Result<()>;
+
+    /// Returns the number of nodes currently stored by the fork choice backend.
+    fn node_count(&self) -> usize;
+
+    /// Removes the nodes which cannot become the head: those which neither descend from nor are
+    /// ancestors of `justified_block_root`, and the leaves without votes other than `head_root`.
+    ///
+    /// A removed block is added again if it later receives a vote.
+    fn prune_non_viable(
+        &self,
+        justified_block_slot: Slot,
+        justified_block_root: Hash256,
+        head_root: Hash256,
+    ) -> Result<()>;
+}
+
+/// An event which drives a `ForkChoiceStore`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step<E: EthSpec> {
+    /// The clock reached the given time (in seconds since the unix epoch).
+    Tick(u64),
+    Block(BeaconBlock<E>),
+    Attestation(Attestation<E>),
+    AttesterSlashing(AttesterSlashing<E>),
+}
+
+/// The handlers of the fork choice rule of the specification, which are driven by the clock and by
+/// the blocks, attestations and attester slashings received by the node.
+pub trait ForkChoiceStore<E: EthSpec> {
+    type Error;
+
+    /// Advance the store to `time` (in seconds since the unix epoch).
+    fn on_tick(&mut self, time: u64) -> std::result::Result<(), Self::Error>;
+
+    /// Process a block, returning its root.
+    fn on_block(&mut self, block: &BeaconBlock<E>) -> std::result::Result<Hash256, Self::Error>;
+
+    /// Process an attestation, which may have been received on the network or included in a
+    /// block.
+    fn on_attestation(
+        &mut self,
+        attestation: &Attestation<E>,
+    ) -> std::result::Result<(), Self::Error>;
+
+    /// Process an attester slashing, discounting the votes of the validators it proves to have
+    /// equivocated.
+    fn on_attester_slashing(
+        &mut self,
+        attester_slashing: &AttesterSlashing<E>,
+    ) -> std::result::Result<(), Self::Error>;
+
+    /// Returns the root of the head block.
+    fn get_head(&self) -> std::result::Result<Hash256, Self::Error>;
+
+    fn justified_checkpoint(&self) -> &Checkpoint;
+
+    fn finalized_checkpoint(&self) -> &Checkpoint;
+
+    /// Returns the time of the latest tick.
+    fn time(&self) -> u64;
+
+    /// Applies `step` with the matching handler.
+    fn apply(&mut self, step: &Step<E>) -> std::result::Result<(), Self::Error> {
+        match step {
+            Step::Tick(time) => self.on_tick(*time),
+            Step::Block(block) => self.on_block(block).map(|_| ()),
+            Step::Attestation(attestation) => self.on_attestation(attestation),
+            Step::AttesterSlashing(attester_slashing) => {
+                self.on_attester_slashing(attester_slashing)
+            }
+        }
+    }
 }
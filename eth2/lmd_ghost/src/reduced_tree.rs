@@ -5,7 +5,7 @@
 //! This implementation is incomplete and has known bugs. Do not use in production.
 use super::{LmdGhost, Result as SuperResult};
 use parking_lot::RwLock;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::marker::PhantomData;
 use std::sync::Arc;
@@ -109,6 +109,22 @@ where
             .update_root(new_block.slot, new_root)
             .map_err(|e| format!("update_finalized_root failed: {:?}", e))
     }
+
+    fn node_count(&self) -> usize {
+        self.core.read().nodes.len()
+    }
+
+    fn prune_non_viable(
+        &self,
+        justified_block_slot: Slot,
+        justified_block_root: Hash256,
+        head_root: Hash256,
+    ) -> SuperResult<()> {
+        self.core
+            .write()
+            .prune_non_viable(justified_block_slot, justified_block_root, head_root)
+            .map_err(|e| format!("prune_non_viable failed: {:?}", e))
+    }
 }
 
 struct ReducedTree<T, E> {
@@ -197,6 +213,99 @@ where
         Ok(())
     }
 
+    /// Removes all nodes which neither descend from nor are ancestors of `justified_root`, then
+    /// all leaves without votes other than `head_root` and `justified_root`, and finally any node
+    /// which is left redundant (see `maybe_delete_node`).
+    ///
+    /// The votes of removed nodes are forgotten, so a later vote of the same validator is always
+    /// accepted. A removed leaf is added again by `add_latest_message` if it receives a vote.
+    pub fn prune_non_viable(
+        &mut self,
+        justified_slot: Slot,
+        justified_root: Hash256,
+        head_root: Hash256,
+    ) -> Result<()> {
+        if !self.nodes.contains_key(&justified_root) {
+            self.add_weightless_node(justified_slot, justified_root)?;
+        }
+
+        if self.nodes.contains_key(&justified_root) {
+            let mut ancestors = vec![];
+            let mut parent_hash = self.get_node(justified_root)?.parent_hash;
+            while let Some(hash) = parent_hash {
+                ancestors.push(hash);
+                parent_hash = self.get_node(hash)?.parent_hash;
+            }
+
+            let mut viable: HashSet<Hash256> = ancestors.iter().cloned().collect();
+            let mut descendants = vec![justified_root];
+            while let Some(hash) = descendants.pop() {
+                descendants.extend_from_slice(&self.get_node(hash)?.children);
+                viable.insert(hash);
+            }
+
+            let non_viable = self
+                .nodes
+                .keys()
+                .filter(|hash| !viable.contains(hash))
+                .cloned()
+                .collect::<Vec<_>>();
+            for hash in non_viable {
+                self.remove_node_and_votes(hash);
+            }
+            for hash in ancestors {
+                self.get_mut_node(hash)?
+                    .children
+                    .retain(|child| viable.contains(child));
+            }
+        }
+
+        loop {
+            let leaves = self
+                .nodes
+                .values()
+                .filter(|node| {
+                    node.parent_hash.is_some()
+                        && node.does_not_have_children()
+                        && !node.has_votes()
+                        && node.block_hash != head_root
+                        && node.block_hash != justified_root
+                })
+                .map(|node| node.block_hash)
+                .collect::<Vec<_>>();
+            if leaves.is_empty() {
+                break;
+            }
+
+            for hash in leaves {
+                if let Some(parent_hash) = self.get_node(hash)?.parent_hash {
+                    self.get_mut_node(parent_hash)?.remove_child(hash)?;
+                    self.nodes.remove(&hash);
+                }
+            }
+        }
+
+        // Any node left with a single child and no votes is no longer needed.
+        let hashes = self.nodes.keys().cloned().collect::<Vec<_>>();
+        for hash in hashes {
+            if self.nodes.contains_key(&hash) {
+                self.maybe_delete_node(hash)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes the node with `hash` from `self.nodes` and forgets the votes it holds, without
+    /// updating its parent or children.
+    fn remove_node_and_votes(&mut self, hash: Hash256) {
+        if let Some(node) = self.nodes.remove(&hash) {
+            for voter in node.voters {
+                self.latest_votes.insert(voter, None);
+            }
+        }
+    }
+
     pub fn process_message(
         &mut self,
         validator_index: usize,
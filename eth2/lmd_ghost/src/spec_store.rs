@@ -0,0 +1,508 @@
+//! A direct implementation of the fork choice `Store` of the specification.
+//!
+//! Every block and post-block state is held in memory, so this implementation is intended as a
+//! reference for testing and cross-checking other fork choice implementations, rather than for
+//! production.
+//!
+//! In addition to the handlers of the specification, validators which are proven to have
+//! equivocated by an `AttesterSlashing` have their votes discounted, and attestations from the
+//! current slot are queued until the slot has passed, instead of being rejected.
+use super::ForkChoiceStore;
+use state_processing::common::get_indexed_attestation;
+use state_processing::per_block_processing::errors::{
+    AttestationValidationError, AttesterSlashingValidationError, BlockProcessingError,
+    IndexedAttestationValidationError,
+};
+use state_processing::per_block_processing::{
+    is_valid_indexed_attestation, verify_attester_slashing,
+};
+use state_processing::{per_block_processing, per_slot_processing, SlotProcessingError};
+use std::collections::{HashMap, HashSet};
+use types::{
+    Attestation, AttesterSlashing, BeaconBlock, BeaconState, BeaconStateError, ChainSpec,
+    Checkpoint, Epoch, EthSpec, Hash256, IndexedAttestation, Slot,
+};
+
+type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// The parent of the block is not in the store.
+    UnknownParent(Hash256),
+    /// The target root of the attestation is not in the store.
+    UnknownTarget(Hash256),
+    /// The block voted for by the attestation is not in the store.
+    UnknownBlock(Hash256),
+    /// The slot of the block has not yet started.
+    FutureBlock {
+        slot: Slot,
+        current_slot: Slot,
+    },
+    /// The target epoch of the attestation has not yet started.
+    FutureTarget {
+        epoch: Epoch,
+        current_slot: Slot,
+    },
+    /// The attestation is from a slot later than the current slot.
+    FutureAttestation {
+        slot: Slot,
+        current_slot: Slot,
+    },
+    /// The block is not later than the finalized checkpoint.
+    NotLaterThanFinalized {
+        slot: Slot,
+        finalized_slot: Slot,
+    },
+    /// The block does not descend from the finalized block.
+    NotDescendantOfFinalized(Hash256),
+    /// The state root of the block is not the root of its post-state.
+    StateRootMismatch {
+        block: Hash256,
+        state: Hash256,
+    },
+    MissingBlock(Hash256),
+    MissingState(Hash256),
+    MissingCheckpointState(Checkpoint),
+    InvalidBlock(BlockProcessingError),
+    InvalidAttestation(AttestationValidationError),
+    InvalidIndexedAttestation(IndexedAttestationValidationError),
+    InvalidAttesterSlashing(AttesterSlashingValidationError),
+    SlotProcessingError(SlotProcessingError),
+    BeaconStateError(BeaconStateError),
+}
+
+/// The latest vote of a validator.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LatestMessage {
+    pub epoch: Epoch,
+    pub root: Hash256,
+}
+
+/// A verified attestation from a slot which had not passed when it was received.
+#[derive(Debug, Clone, PartialEq)]
+struct QueuedAttestation {
+    slot: Slot,
+    target_epoch: Epoch,
+    block_root: Hash256,
+    attesting_indices: Vec<u64>,
+}
+
+pub struct SpecStore<E: EthSpec> {
+    spec: ChainSpec,
+    genesis_time: u64,
+    time: u64,
+    justified_checkpoint: Checkpoint,
+    finalized_checkpoint: Checkpoint,
+    blocks: HashMap<Hash256, BeaconBlock<E>>,
+    block_states: HashMap<Hash256, BeaconState<E>>,
+    checkpoint_states: HashMap<Checkpoint, BeaconState<E>>,
+    latest_messages: HashMap<u64, LatestMessage>,
+    /// Validators proven to have equivocated, whose votes are ignored.
+    equivocating_indices: HashSet<u64>,
+    queued_attestations: Vec<QueuedAttestation>,
+}
+
+impl<E: EthSpec> SpecStore<E> {
+    /// Creates a store from the genesis state, as per `get_genesis_store`.
+    ///
+    /// Spec v0.8.1
+    pub fn from_genesis(genesis_state: BeaconState<E>, spec: ChainSpec) -> Self {
+        let mut genesis_block = BeaconBlock::empty(&spec);
+        genesis_block.state_root = genesis_state.canonical_root();
+        let root = genesis_block.canonical_root();
+
+        let checkpoint = Checkpoint {
+            epoch: E::genesis_epoch(),
+            root,
+        };
+
+        let mut blocks = HashMap::new();
+        blocks.insert(root, genesis_block);
+        let mut checkpoint_states = HashMap::new();
+        checkpoint_states.insert(checkpoint.clone(), genesis_state.clone());
+        let mut block_states = HashMap::new();
+        let genesis_time = genesis_state.genesis_time;
+        block_states.insert(root, genesis_state);
+
+        Self {
+            spec,
+            genesis_time,
+            time: genesis_time,
+            justified_checkpoint: checkpoint.clone(),
+            finalized_checkpoint: checkpoint,
+            blocks,
+            block_states,
+            checkpoint_states,
+            latest_messages: HashMap::new(),
+            equivocating_indices: HashSet::new(),
+            queued_attestations: vec![],
+        }
+    }
+
+    /// Returns the slot at `self.time`.
+    pub fn current_slot(&self) -> Slot {
+        self.spec.genesis_slot
+            + self.time.saturating_sub(self.genesis_time) / self.spec.seconds_per_slot
+    }
+
+    /// Returns the latest vote of the validator with `validator_index`, if any.
+    pub fn latest_message(&self, validator_index: u64) -> Option<LatestMessage> {
+        self.latest_messages.get(&validator_index).copied()
+    }
+
+    /// Returns the number of attestations awaiting the end of their slot.
+    pub fn queued_attestation_count(&self) -> usize {
+        self.queued_attestations.len()
+    }
+
+    /// Returns the ancestor of `root` at `slot`, or `None` if `root` is prior to `slot`.
+    ///
+    /// Spec v0.8.1
+    pub fn get_ancestor(&self, root: Hash256, slot: Slot) -> Result<Option<Hash256>> {
+        let mut root = root;
+        loop {
+            let block = self.block(root)?;
+            if block.slot > slot {
+                root = block.parent_root;
+            } else if block.slot == slot {
+                return Ok(Some(root));
+            } else {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Returns the total effective balance of the validators whose latest vote supports `root`.
+    ///
+    /// Votes of equivocating validators are not counted.
+    ///
+    /// Spec v0.8.1
+    pub fn get_latest_attesting_balance(&self, root: Hash256) -> Result<u64> {
+        let state = self
+            .checkpoint_states
+            .get(&self.justified_checkpoint)
+            .ok_or_else(|| Error::MissingCheckpointState(self.justified_checkpoint.clone()))?;
+        let block_slot = self.block(root)?.slot;
+
+        let mut balance = 0;
+        for index in state.get_active_validator_indices(state.current_epoch()) {
+            if self.equivocating_indices.contains(&(index as u64)) {
+                continue;
+            }
+            if let Some(message) = self.latest_messages.get(&(index as u64)) {
+                if self.get_ancestor(message.root, block_slot)? == Some(root) {
+                    balance += state.validators[index].effective_balance;
+                }
+            }
+        }
+        Ok(balance)
+    }
+
+    /// Returns the block with `root`.
+    pub fn block(&self, root: Hash256) -> Result<&BeaconBlock<E>> {
+        self.blocks
+            .get(&root)
+            .ok_or_else(|| Error::MissingBlock(root))
+    }
+
+    /// Returns the unix time at the start of `slot`.
+    fn slot_start_time(&self, slot: Slot) -> u64 {
+        self.genesis_time + (slot - self.spec.genesis_slot).as_u64() * self.spec.seconds_per_slot
+    }
+
+    /// Updates the latest messages of `attesting_indices`, ignoring equivocating validators.
+    fn apply_votes(&mut self, attesting_indices: &[u64], target_epoch: Epoch, block_root: Hash256) {
+        for &index in attesting_indices {
+            if self.equivocating_indices.contains(&index) {
+                continue;
+            }
+            let is_newer = self
+                .latest_messages
+                .get(&index)
+                .map_or(true, |message| target_epoch > message.epoch);
+            if is_newer {
+                self.latest_messages.insert(
+                    index,
+                    LatestMessage {
+                        epoch: target_epoch,
+                        root: block_root,
+                    },
+                );
+            }
+        }
+    }
+}
+
+impl<E: EthSpec> ForkChoiceStore<E> for SpecStore<E> {
+    type Error = Error;
+
+    /// Spec v0.8.1
+    fn on_tick(&mut self, time: u64) -> Result<()> {
+        self.time = time;
+
+        // Votes from slots which have now passed may be counted.
+        let current_slot = self.current_slot();
+        let (ready, queued) = self
+            .queued_attestations
+            .drain(..)
+            .partition::<Vec<_>, _>(|attestation| attestation.slot < current_slot);
+        self.queued_attestations = queued;
+        for attestation in ready {
+            self.apply_votes(
+                &attestation.attesting_indices,
+                attestation.target_epoch,
+                attestation.block_root,
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Spec v0.8.1
+    fn on_block(&mut self, block: &BeaconBlock<E>) -> Result<Hash256> {
+        let mut state = self
+            .block_states
+            .get(&block.parent_root)
+            .ok_or_else(|| Error::UnknownParent(block.parent_root))?
+            .clone();
+
+        // Blocks cannot be in the future.
+        if self.time < self.slot_start_time(block.slot) {
+            return Err(Error::FutureBlock {
+                slot: block.slot,
+                current_slot: self.current_slot(),
+            });
+        }
+
+        // The block must be later than, and descend from, the finalized block.
+        let finalized_slot = self
+            .finalized_checkpoint
+            .epoch
+            .start_slot(E::slots_per_epoch());
+        if block.slot <= finalized_slot {
+            return Err(Error::NotLaterThanFinalized {
+                slot: block.slot,
+                finalized_slot,
+            });
+        }
+        // The finalized block may be earlier than the start of its epoch, if that slot was skipped.
+        let finalized_block_slot = self.block(self.finalized_checkpoint.root)?.slot;
+        if self.get_ancestor(block.parent_root, finalized_block_slot)?
+            != Some(self.finalized_checkpoint.root)
+        {
+            return Err(Error::NotDescendantOfFinalized(block.parent_root));
+        }
+
+        // Spec: state_transition
+        while state.slot < block.slot {
+            per_slot_processing(&mut state, &self.spec)?;
+        }
+        per_block_processing(&mut state, block, &self.spec)?;
+        let state_root = state.canonical_root();
+        if block.state_root != state_root {
+            return Err(Error::StateRootMismatch {
+                block: block.state_root,
+                state: state_root,
+            });
+        }
+
+        let root = block.canonical_root();
+        self.blocks.insert(root, block.clone());
+
+        if state.current_justified_checkpoint.epoch > self.justified_checkpoint.epoch {
+            self.justified_checkpoint = state.current_justified_checkpoint.clone();
+        }
+        if state.finalized_checkpoint.epoch > self.finalized_checkpoint.epoch {
+            self.finalized_checkpoint = state.finalized_checkpoint.clone();
+        }
+        self.block_states.insert(root, state);
+
+        // The justified state is required to weigh votes.
+        let justified = self.justified_checkpoint.clone();
+        if !self.checkpoint_states.contains_key(&justified) {
+            let mut justified_state = self
+                .block_states
+                .get(&justified.root)
+                .ok_or_else(|| Error::MissingState(justified.root))?
+                .clone();
+            let justified_slot = justified.epoch.start_slot(E::slots_per_epoch());
+            while justified_state.slot < justified_slot {
+                per_slot_processing(&mut justified_state, &self.spec)?;
+            }
+            self.checkpoint_states.insert(justified, justified_state);
+        }
+
+        Ok(root)
+    }
+
+    /// Spec v0.8.1
+    fn on_attestation(&mut self, attestation: &Attestation<E>) -> Result<()> {
+        let target = &attestation.data.target;
+        let target_slot = target.epoch.start_slot(E::slots_per_epoch());
+
+        if !self.blocks.contains_key(&target.root) {
+            return Err(Error::UnknownTarget(target.root));
+        }
+        if !self
+            .blocks
+            .contains_key(&attestation.data.beacon_block_root)
+        {
+            return Err(Error::UnknownBlock(attestation.data.beacon_block_root));
+        }
+        // Attestations cannot be from future epochs.
+        if self.time < self.slot_start_time(target_slot) {
+            return Err(Error::FutureTarget {
+                epoch: target.epoch,
+                current_slot: self.current_slot(),
+            });
+        }
+
+        // Store the target checkpoint state if not yet seen.
+        if !self.checkpoint_states.contains_key(target) {
+            let mut base_state = self
+                .block_states
+                .get(&target.root)
+                .ok_or_else(|| Error::MissingState(target.root))?
+                .clone();
+            while base_state.slot < target_slot {
+                per_slot_processing(&mut base_state, &self.spec)?;
+            }
+            base_state.build_all_caches(&self.spec)?;
+            self.checkpoint_states.insert(target.clone(), base_state);
+        }
+        let target_state = &self.checkpoint_states[target];
+
+        let attestation_slot = target_state.get_attestation_data_slot(&attestation.data)?;
+        let current_slot = self.current_slot();
+        if attestation_slot > current_slot {
+            return Err(Error::FutureAttestation {
+                slot: attestation_slot,
+                current_slot,
+            });
+        }
+
+        let indexed_attestation = get_indexed_attestation(target_state, attestation)?;
+        is_valid_indexed_attestation(target_state, &indexed_attestation, &self.spec)?;
+        let attesting_indices: Vec<u64> = indexed_attestation
+            .custody_bit_0_indices
+            .iter()
+            .chain(indexed_attestation.custody_bit_1_indices.iter())
+            .cloned()
+            .collect();
+
+        // Attestations can only affect the fork choice of subsequent slots.
+        if attestation_slot == current_slot {
+            self.queued_attestations.push(QueuedAttestation {
+                slot: attestation_slot,
+                target_epoch: target.epoch,
+                block_root: attestation.data.beacon_block_root,
+                attesting_indices,
+            });
+        } else {
+            self.apply_votes(
+                &attesting_indices,
+                target.epoch,
+                attestation.data.beacon_block_root,
+            );
+        }
+
+        Ok(())
+    }
+
+    fn on_attester_slashing(&mut self, attester_slashing: &AttesterSlashing<E>) -> Result<()> {
+        let state = self
+            .block_states
+            .get(&self.justified_checkpoint.root)
+            .ok_or_else(|| Error::MissingState(self.justified_checkpoint.root))?;
+        verify_attester_slashing(state, attester_slashing, true, &self.spec)?;
+
+        let indices = |attestation: &IndexedAttestation<E>| -> HashSet<u64> {
+            attestation
+                .custody_bit_0_indices
+                .iter()
+                .chain(attestation.custody_bit_1_indices.iter())
+                .cloned()
+                .collect()
+        };
+        let equivocating =
+            &indices(&attester_slashing.attestation_1) & &indices(&attester_slashing.attestation_2);
+        self.equivocating_indices.extend(equivocating);
+
+        Ok(())
+    }
+
+    /// Spec v0.8.1
+    fn get_head(&self) -> Result<Hash256> {
+        let mut head = self.justified_checkpoint.root;
+        let justified_slot = self
+            .justified_checkpoint
+            .epoch
+            .start_slot(E::slots_per_epoch());
+
+        loop {
+            let mut best: Option<(u64, Hash256)> = None;
+            for (root, block) in &self.blocks {
+                if block.parent_root == head && block.slot > justified_slot {
+                    // Ties are broken by the greatest root.
+                    let candidate = (self.get_latest_attesting_balance(*root)?, *root);
+                    if best.map_or(true, |best| candidate > best) {
+                        best = Some(candidate);
+                    }
+                }
+            }
+
+            match best {
+                Some((_, root)) => head = root,
+                None => return Ok(head),
+            }
+        }
+    }
+
+    fn justified_checkpoint(&self) -> &Checkpoint {
+        &self.justified_checkpoint
+    }
+
+    fn finalized_checkpoint(&self) -> &Checkpoint {
+        &self.finalized_checkpoint
+    }
+
+    fn time(&self) -> u64 {
+        self.time
+    }
+}
+
+impl From<BlockProcessingError> for Error {
+    fn from(e: BlockProcessingError) -> Error {
+        Error::InvalidBlock(e)
+    }
+}
+
+impl From<AttestationValidationError> for Error {
+    fn from(e: AttestationValidationError) -> Error {
+        Error::InvalidAttestation(e)
+    }
+}
+
+impl From<IndexedAttestationValidationError> for Error {
+    fn from(e: IndexedAttestationValidationError) -> Error {
+        Error::InvalidIndexedAttestation(e)
+    }
+}
+
+impl From<AttesterSlashingValidationError> for Error {
+    fn from(e: AttesterSlashingValidationError) -> Error {
+        Error::InvalidAttesterSlashing(e)
+    }
+}
+
+impl From<SlotProcessingError> for Error {
+    fn from(e: SlotProcessingError) -> Error {
+        Error::SlotProcessingError(e)
+    }
+}
+
+impl From<BeaconStateError> for Error {
+    fn from(e: BeaconStateError) -> Error {
+        Error::BeaconStateError(e)
+    }
+}
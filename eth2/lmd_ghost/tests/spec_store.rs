@@ -0,0 +1,289 @@
+use lmd_ghost::{ForkChoiceStore, SpecStore, SpecStoreError};
+use state_processing::{
+    per_block_processing_without_verifying_block_signature, per_slot_processing,
+};
+use types::test_utils::{
+    TestingAttestationBuilder, TestingAttesterSlashingBuilder, TestingBeaconBlockBuilder,
+    TestingBeaconStateBuilder,
+};
+use types::*;
+
+type TestEthSpec = MinimalEthSpec;
+
+pub const VALIDATOR_COUNT: usize = 16;
+
+struct Harness {
+    store: SpecStore<TestEthSpec>,
+    genesis_state: BeaconState<TestEthSpec>,
+    genesis_root: Hash256,
+    keypairs: Vec<Keypair>,
+    spec: ChainSpec,
+}
+
+impl Harness {
+    fn new() -> Self {
+        let spec = TestEthSpec::default_spec();
+        let (genesis_state, keypairs) =
+            TestingBeaconStateBuilder::from_deterministic_keypairs(VALIDATOR_COUNT, &spec).build();
+        let store = SpecStore::from_genesis(genesis_state.clone(), spec.clone());
+        let genesis_root = store.get_head().expect("should find head");
+
+        Self {
+            store,
+            genesis_state,
+            genesis_root,
+            keypairs,
+            spec,
+        }
+    }
+
+    /// Advances the store to the start of `slot`.
+    fn tick(&mut self, slot: u64) {
+        let time = self.genesis_state.genesis_time + slot * self.spec.seconds_per_slot;
+        self.store.on_tick(time).expect("should tick");
+    }
+
+    /// Returns a signed block at `slot` on top of `parent_state`, along with its post-state.
+    ///
+    /// Blocks with a different `graffiti` have different roots.
+    fn build_block(
+        &self,
+        parent_state: &BeaconState<TestEthSpec>,
+        slot: u64,
+        graffiti: u8,
+    ) -> (BeaconBlock<TestEthSpec>, BeaconState<TestEthSpec>) {
+        let spec = &self.spec;
+        let slot = Slot::new(slot);
+        let mut state = parent_state.clone();
+        while state.slot < slot {
+            per_slot_processing(&mut state, spec).expect("should process slot");
+        }
+        state
+            .build_committee_cache(RelativeEpoch::Current, spec)
+            .expect("should build committee cache");
+        let proposer_index = state
+            .get_beacon_proposer_index(slot, RelativeEpoch::Current, spec)
+            .expect("should get proposer");
+        let sk = &self.keypairs[proposer_index].sk;
+
+        let mut builder = TestingBeaconBlockBuilder::new(spec);
+        builder.set_slot(slot);
+        builder.set_parent_root(state.latest_block_header.canonical_root());
+        builder.set_randao_reveal(sk, &state.fork, spec);
+        let mut block = builder.build_without_signing();
+        block.body.graffiti = [graffiti; 32];
+
+        per_block_processing_without_verifying_block_signature(&mut state, &block, spec)
+            .expect("should process block");
+        block.state_root = state.canonical_root();
+        self.sign_block(&mut block, proposer_index);
+
+        (block, state)
+    }
+
+    /// Signs `block` with the key of the validator with `proposer_index`.
+    fn sign_block(&self, block: &mut BeaconBlock<TestEthSpec>, proposer_index: usize) {
+        let epoch = block.slot.epoch(TestEthSpec::slots_per_epoch());
+        let fork = &self.genesis_state.fork;
+        let domain = self.spec.get_domain(epoch, Domain::BeaconProposer, fork);
+        let sk = &self.keypairs[proposer_index].sk;
+        block.signature = Signature::new(block.canonical_root().as_bytes(), domain, sk);
+    }
+
+    /// Returns the index of the proposer at `slot` of the first epoch.
+    fn proposer_index(&self, slot: Slot) -> usize {
+        let mut state = self.genesis_state.clone();
+        state
+            .build_committee_cache(RelativeEpoch::Current, &self.spec)
+            .expect("should build committee cache");
+        state
+            .get_beacon_proposer_index(slot, RelativeEpoch::Current, &self.spec)
+            .expect("should get proposer")
+    }
+
+    /// Returns an attestation to the head of `head_state` by the first committee at `slot` (the
+    /// slot of `head_state`), and the indices of that committee.
+    fn build_attestation(
+        &self,
+        head_state: &BeaconState<TestEthSpec>,
+    ) -> (Attestation<TestEthSpec>, Vec<u64>) {
+        let spec = &self.spec;
+        let slot = head_state.slot;
+
+        // Attestation data is read from the block roots of the state, which are only set for
+        // prior slots.
+        let mut state = head_state.clone();
+        per_slot_processing(&mut state, spec).expect("should process slot");
+        state
+            .build_committee_cache(RelativeEpoch::Current, spec)
+            .expect("should build committee cache");
+
+        let committees = state
+            .get_crosslink_committees_at_slot(slot)
+            .expect("should get committees");
+        let committee = &committees[0];
+        let secret_keys: Vec<&SecretKey> = committee
+            .committee
+            .iter()
+            .map(|&index| &self.keypairs[index].sk)
+            .collect();
+
+        let mut builder = TestingAttestationBuilder::new(
+            &state,
+            committee.committee,
+            slot,
+            committee.shard,
+            spec,
+        );
+        builder.sign(committee.committee, &secret_keys, &state.fork, spec, false);
+
+        let mut indices: Vec<u64> = committee.committee.iter().map(|&i| i as u64).collect();
+        indices.sort();
+        (builder.build(), indices)
+    }
+
+    /// Imports two competing blocks at slot 1, returning their roots and post-states, with the
+    /// block which wins the tie-break (the greater root) last.
+    fn import_competing_blocks(
+        &mut self,
+    ) -> (
+        (Hash256, BeaconState<TestEthSpec>),
+        (Hash256, BeaconState<TestEthSpec>),
+    ) {
+        self.tick(1);
+        let genesis_state = self.genesis_state.clone();
+        let mut forks: Vec<_> = (1..3)
+            .map(|graffiti| {
+                let (block, state) = self.build_block(&genesis_state, 1, graffiti);
+                let root = self.store.on_block(&block).expect("should import block");
+                (root, state)
+            })
+            .collect();
+        forks.sort_by_key(|(root, _)| *root);
+
+        let winner = forks.pop().expect("two forks");
+        let loser = forks.pop().expect("two forks");
+        (loser, winner)
+    }
+}
+
+#[test]
+fn head_is_the_latest_block() {
+    let mut harness = Harness::new();
+    assert_eq!(harness.store.get_head(), Ok(harness.genesis_root));
+
+    let mut state = harness.genesis_state.clone();
+    for slot in 1..4 {
+        harness.tick(slot);
+        let (block, post_state) = harness.build_block(&state, slot, 0);
+        let root = harness.store.on_block(&block).expect("should import block");
+        assert_eq!(harness.store.get_head(), Ok(root));
+        state = post_state;
+    }
+
+    let genesis_checkpoint = Checkpoint {
+        epoch: Epoch::new(0),
+        root: harness.genesis_root,
+    };
+    assert_eq!(harness.store.justified_checkpoint(), &genesis_checkpoint);
+    assert_eq!(harness.store.finalized_checkpoint(), &genesis_checkpoint);
+}
+
+#[test]
+fn rejects_invalid_blocks() {
+    let mut harness = Harness::new();
+    let (block, _) = harness.build_block(&harness.genesis_state, 1, 0);
+
+    assert_eq!(
+        harness.store.on_block(&block),
+        Err(SpecStoreError::FutureBlock {
+            slot: Slot::new(1),
+            current_slot: Slot::new(0),
+        })
+    );
+
+    harness.tick(1);
+
+    let mut orphan = block.clone();
+    orphan.parent_root = Hash256::repeat_byte(42);
+    assert_eq!(
+        harness.store.on_block(&orphan),
+        Err(SpecStoreError::UnknownParent(orphan.parent_root))
+    );
+
+    let mut wrong_state_root = block.clone();
+    wrong_state_root.state_root = Hash256::repeat_byte(42);
+    harness.sign_block(&mut wrong_state_root, harness.proposer_index(block.slot));
+    match harness.store.on_block(&wrong_state_root) {
+        Err(SpecStoreError::StateRootMismatch { .. }) => {}
+        other => panic!("should reject the state root, got {:?}", other),
+    }
+
+    assert!(harness.store.on_block(&block).is_ok());
+}
+
+#[test]
+fn votes_are_counted_after_their_slot() {
+    let mut harness = Harness::new();
+    let ((loser, loser_state), (winner, _)) = harness.import_competing_blocks();
+    assert_eq!(harness.store.get_head(), Ok(winner));
+
+    let (attestation, indices) = harness.build_attestation(&loser_state);
+
+    // The attestation cannot be received before its slot.
+    harness.tick(0);
+    assert_eq!(
+        harness.store.on_attestation(&attestation),
+        Err(SpecStoreError::FutureAttestation {
+            slot: Slot::new(1),
+            current_slot: Slot::new(0),
+        })
+    );
+
+    // Received during its slot, the attestation is queued.
+    harness.tick(1);
+    harness
+        .store
+        .on_attestation(&attestation)
+        .expect("should accept attestation");
+    assert_eq!(harness.store.queued_attestation_count(), 1);
+    assert_eq!(harness.store.latest_message(indices[0]), None);
+    assert_eq!(harness.store.get_head(), Ok(winner));
+
+    harness.tick(2);
+    assert_eq!(harness.store.queued_attestation_count(), 0);
+    assert_eq!(
+        harness.store.latest_message(indices[0]).map(|m| m.root),
+        Some(loser)
+    );
+    assert_eq!(harness.store.get_head(), Ok(loser));
+}
+
+#[test]
+fn equivocating_votes_are_discounted() {
+    let mut harness = Harness::new();
+    let ((loser, loser_state), (winner, _)) = harness.import_competing_blocks();
+
+    let (attestation, indices) = harness.build_attestation(&loser_state);
+    harness.tick(2);
+    harness
+        .store
+        .on_attestation(&attestation)
+        .expect("should accept attestation");
+    assert_eq!(harness.store.get_head(), Ok(loser));
+
+    let spec = &harness.spec;
+    let keypairs = &harness.keypairs;
+    let fork = &harness.genesis_state.fork;
+    let attester_slashing: AttesterSlashing<TestEthSpec> =
+        TestingAttesterSlashingBuilder::double_vote(&indices, |index, message, epoch, domain| {
+            let domain = spec.get_domain(epoch, domain, fork);
+            Signature::new(message, domain, &keypairs[index as usize].sk)
+        });
+
+    harness
+        .store
+        .on_attester_slashing(&attester_slashing)
+        .expect("should accept attester slashing");
+    assert_eq!(harness.store.get_head(), Ok(winner));
+}
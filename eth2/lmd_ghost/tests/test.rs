@@ -357,3 +357,88 @@ fn update_finalized_root_honest() {
 
     test_update_finalized_root(&harness.honest_roots)
 }
+
+/// Returns the block with `root` from the store of the harness.
+fn get_block(harness: &ForkedHarness, root: Hash256) -> BeaconBlock<TestEthSpec> {
+    harness
+        .store_clone()
+        .get::<BeaconBlock<TestEthSpec>>(&root)
+        .expect("block should exist")
+        .expect("db should not error")
+}
+
+/// Pruning removes the blocks without votes which are not the head, and a removed block which
+/// later receives a vote may still become the head.
+#[test]
+fn prune_non_viable_leaves() {
+    let harness = &FORKED_HARNESS;
+    let (honest_head, _) = *harness.honest_roots.first().unwrap();
+    let (faulty_head, faulty_slot) = *harness.faulty_roots.first().unwrap();
+    let genesis_slot = harness.genesis_block.slot;
+
+    let lmd = harness.new_fork_choice();
+    for (root, _slot) in harness.all_block_roots() {
+        lmd.process_block(&get_block(harness, root), root)
+            .expect("fork choice should accept blocks");
+    }
+    let head = lmd
+        .find_head(
+            genesis_slot,
+            harness.genesis_block_root,
+            ForkedHarness::weight_function,
+        )
+        .expect("should find head");
+    assert!(head == honest_head || head == faulty_head);
+
+    lmd.prune_non_viable(genesis_slot, harness.genesis_block_root, honest_head)
+        .expect("should prune");
+    assert_eq!(lmd.verify_integrity(), Ok(()));
+    // Only the root and the head remain.
+    assert_eq!(lmd.node_count(), 2);
+
+    lmd.process_attestation(0, faulty_head, faulty_slot)
+        .expect("fork choice should accept a vote for a pruned block");
+    assert_eq!(lmd.verify_integrity(), Ok(()));
+    assert_eq!(
+        lmd.find_head(
+            genesis_slot,
+            harness.genesis_block_root,
+            ForkedHarness::weight_function
+        ),
+        Ok(faulty_head)
+    );
+}
+
+/// Pruning removes the branches which do not descend from the justified block, along with their
+/// votes.
+#[test]
+fn prune_non_viable_branches() {
+    let harness = &FORKED_HARNESS;
+    let (honest_head, honest_slot) = *harness.honest_roots.first().unwrap();
+    let (faulty_head, faulty_slot) = *harness.faulty_roots.first().unwrap();
+    let genesis_slot = harness.genesis_block.slot;
+
+    let lmd = harness.new_fork_choice();
+    lmd.process_attestation(0, faulty_head, faulty_slot)
+        .expect("should accept a vote for the faulty head");
+    lmd.process_attestation(1, honest_head, honest_slot)
+        .expect("should accept a vote for the honest head");
+
+    lmd.prune_non_viable(honest_slot, honest_head, honest_head)
+        .expect("should prune");
+    assert_eq!(lmd.verify_integrity(), Ok(()));
+    assert_eq!(lmd.node_count(), 2);
+
+    // The pruned vote is forgotten, so the validator may vote again at any slot.
+    lmd.process_attestation(0, honest_head, honest_slot)
+        .expect("should accept a vote from a validator whose vote was pruned");
+    assert_eq!(lmd.verify_integrity(), Ok(()));
+    assert_eq!(
+        lmd.find_head(
+            genesis_slot,
+            harness.genesis_block_root,
+            ForkedHarness::weight_function
+        ),
+        Ok(honest_head)
+    );
+}
@@ -0,0 +1,354 @@
+use crate::attestation_id::AttestationId;
+use std::collections::{hash_map, BTreeMap, HashMap};
+use types::{Attestation, EthSpec, Slot};
+
+/// The maximum number of attestations (aggregated or not) stored for any single slot.
+///
+/// Attestations that cannot be aggregated into an existing attestation are dropped once a shard
+/// has reached this limit.
+pub const DEFAULT_MAX_ATTESTATIONS_PER_SLOT: usize = 4_096;
+
+/// The maximum number of slots for which attestations are stored at any one time.
+///
+/// When an attestation for a new slot would exceed this limit the oldest slot is dropped in its
+/// entirety.
+pub const DEFAULT_MAX_SLOTS: usize = 128;
+
+/// The outcome of inserting an attestation into an `AttestationShards`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ShardInsertOutcome {
+    /// The attestation was stored as a new entry.
+    Fresh,
+    /// The attestation was aggregated into (or was identical to) an existing attestation.
+    Aggregated,
+    /// The shard for the attestation's slot was full, the attestation was dropped.
+    ShardFull,
+    /// The attestation was older than all stored slots and the store was full, it was dropped.
+    TooOld,
+}
+
+/// Metrics describing the contents of a single slot's shard.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct ShardMetrics {
+    /// The number of distinct `AttestationId`s in the shard.
+    pub num_ids: usize,
+    /// The total number of attestations stored in the shard.
+    pub num_attestations: usize,
+    /// The number of attestations dropped because the shard was full.
+    pub num_rejected: usize,
+}
+
+/// All the attestations for a single slot.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SlotShard<T: EthSpec> {
+    attestations: HashMap<AttestationId, Vec<Attestation<T>>>,
+    num_attestations: usize,
+    num_rejected: usize,
+}
+
+impl<T: EthSpec> Default for SlotShard<T> {
+    fn default() -> Self {
+        Self {
+            attestations: HashMap::new(),
+            num_attestations: 0,
+            num_rejected: 0,
+        }
+    }
+}
+
+impl<T: EthSpec> SlotShard<T> {
+    /// Insert an attestation, aggregating it with existing attestations if possible.
+    fn insert(
+        &mut self,
+        id: AttestationId,
+        attestation: Attestation<T>,
+        max_attestations: usize,
+    ) -> ShardInsertOutcome {
+        let is_full = self.num_attestations >= max_attestations;
+
+        let existing_attestations = match self.attestations.entry(id) {
+            hash_map::Entry::Vacant(entry) => {
+                if is_full {
+                    self.num_rejected += 1;
+                    return ShardInsertOutcome::ShardFull;
+                }
+                entry.insert(vec![attestation]);
+                self.num_attestations += 1;
+                return ShardInsertOutcome::Fresh;
+            }
+            hash_map::Entry::Occupied(entry) => entry.into_mut(),
+        };
+
+        let mut aggregated = false;
+        for existing_attestation in existing_attestations.iter_mut() {
+            if existing_attestation.signers_disjoint_from(&attestation) {
+                existing_attestation.aggregate(&attestation);
+                aggregated = true;
+            } else if *existing_attestation == attestation {
+                aggregated = true;
+            }
+        }
+
+        if aggregated {
+            ShardInsertOutcome::Aggregated
+        } else if is_full {
+            self.num_rejected += 1;
+            ShardInsertOutcome::ShardFull
+        } else {
+            existing_attestations.push(attestation);
+            self.num_attestations += 1;
+            ShardInsertOutcome::Fresh
+        }
+    }
+
+    /// Iterate over all `(id, attestations)` pairs in the shard.
+    pub fn iter(&self) -> impl Iterator<Item = (&AttestationId, &Vec<Attestation<T>>)> {
+        self.attestations.iter()
+    }
+
+    /// Returns metrics describing the shard.
+    pub fn metrics(&self) -> ShardMetrics {
+        ShardMetrics {
+            num_ids: self.attestations.len(),
+            num_attestations: self.num_attestations,
+            num_rejected: self.num_rejected,
+        }
+    }
+}
+
+/// Attestation storage, sharded by attestation slot.
+///
+/// Each slot is stored independently with a hard cap on the number of attestations it may hold,
+/// and the number of slots stored is also capped. Expiring old attestations removes whole slots
+/// at a time, without inspecting individual attestations.
+#[derive(Debug, PartialEq, Clone)]
+pub struct AttestationShards<T: EthSpec> {
+    shards: BTreeMap<Slot, SlotShard<T>>,
+    max_attestations_per_slot: usize,
+    max_slots: usize,
+}
+
+impl<T: EthSpec> Default for AttestationShards<T> {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_ATTESTATIONS_PER_SLOT, DEFAULT_MAX_SLOTS)
+    }
+}
+
+impl<T: EthSpec> AttestationShards<T> {
+    /// Create an empty store with the given limits.
+    pub fn new(max_attestations_per_slot: usize, max_slots: usize) -> Self {
+        Self {
+            shards: BTreeMap::new(),
+            max_attestations_per_slot,
+            max_slots,
+        }
+    }
+
+    /// Insert an attestation for `slot`, creating a shard for that slot if required.
+    ///
+    /// If creating a new shard would exceed the maximum number of slots, the oldest shard is
+    /// dropped. Attestations older than every stored slot are rejected when the store is full.
+    pub fn insert(
+        &mut self,
+        slot: Slot,
+        id: AttestationId,
+        attestation: Attestation<T>,
+    ) -> ShardInsertOutcome {
+        if !self.shards.contains_key(&slot) && self.shards.len() >= self.max_slots {
+            match self.shards.keys().next().cloned() {
+                Some(oldest) if oldest < slot => {
+                    self.shards.remove(&oldest);
+                }
+                _ => return ShardInsertOutcome::TooOld,
+            }
+        }
+
+        self.shards
+            .entry(slot)
+            .or_default()
+            .insert(id, attestation, self.max_attestations_per_slot)
+    }
+
+    /// Replace the limits of the store, immediately dropping the oldest shards if there are more
+    /// than `max_slots`.
+    ///
+    /// Existing shards which hold more than `max_attestations_per_slot` are not truncated, they
+    /// will simply reject new attestations.
+    pub fn set_limits(&mut self, max_attestations_per_slot: usize, max_slots: usize) {
+        self.max_attestations_per_slot = max_attestations_per_slot;
+        self.max_slots = max_slots;
+
+        if self.shards.len() > max_slots {
+            let first_kept = max_slots
+                .checked_sub(1)
+                .and_then(|n| self.shards.keys().rev().nth(n).cloned());
+
+            match first_kept {
+                Some(slot) => self.prune_before(slot),
+                None => self.shards.clear(),
+            }
+        }
+    }
+
+    /// Drop all shards for slots prior to `slot`.
+    pub fn prune_before(&mut self, slot: Slot) {
+        let keep = self.shards.split_off(&slot);
+        self.shards = keep;
+    }
+
+    /// Iterate over the shards for all slots in the inclusive range `start..=end`.
+    ///
+    /// Returns an empty iterator if `start > end`.
+    pub fn range(&self, start: Slot, end: Slot) -> impl Iterator<Item = (&Slot, &SlotShard<T>)> {
+        self.shards
+            .range(start..)
+            .take_while(move |(slot, _)| **slot <= end)
+    }
+
+    /// Iterate over all shards, in ascending slot order.
+    pub fn iter(&self) -> impl Iterator<Item = (&Slot, &SlotShard<T>)> {
+        self.shards.iter()
+    }
+
+    /// The total number of attestations stored across all shards.
+    pub fn num_attestations(&self) -> usize {
+        self.shards
+            .values()
+            .map(|shard| shard.num_attestations)
+            .sum()
+    }
+
+    /// The total number of distinct `AttestationId`s across all shards.
+    pub fn num_ids(&self) -> usize {
+        self.shards
+            .values()
+            .map(|shard| shard.attestations.len())
+            .sum()
+    }
+
+    /// Returns per-slot metrics for all stored shards, in ascending slot order.
+    pub fn metrics(&self) -> Vec<(Slot, ShardMetrics)> {
+        self.shards
+            .iter()
+            .map(|(slot, shard)| (*slot, shard.metrics()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use types::test_utils::*;
+    use types::*;
+
+    type E = MinimalEthSpec;
+
+    fn random_attestation(rng: &mut XorShiftRng) -> (AttestationId, Attestation<E>) {
+        let spec = E::default_spec();
+        let state: BeaconState<E> = BeaconState::random_for_test(rng);
+        let attestation = Attestation::random_for_test(rng);
+        let id = AttestationId::from_data(&attestation.data, &state, &spec);
+        (id, attestation)
+    }
+
+    #[test]
+    fn duplicate_is_aggregated() {
+        let rng = &mut XorShiftRng::from_seed([42; 16]);
+        let mut shards = AttestationShards::<E>::new(4, 4);
+        let (id, att) = random_attestation(rng);
+
+        assert_eq!(
+            shards.insert(Slot::new(1), id.clone(), att.clone()),
+            ShardInsertOutcome::Fresh
+        );
+        assert_eq!(
+            shards.insert(Slot::new(1), id, att),
+            ShardInsertOutcome::Aggregated
+        );
+        assert_eq!(shards.num_attestations(), 1);
+    }
+
+    #[test]
+    fn shard_cap_is_enforced() {
+        let rng = &mut XorShiftRng::from_seed([42; 16]);
+        let mut shards = AttestationShards::<E>::new(2, 4);
+        let slot = Slot::new(3);
+
+        for _ in 0..2 {
+            let (id, att) = random_attestation(rng);
+            assert_eq!(shards.insert(slot, id, att), ShardInsertOutcome::Fresh);
+        }
+        let (id, att) = random_attestation(rng);
+        assert_eq!(shards.insert(slot, id, att), ShardInsertOutcome::ShardFull);
+
+        assert_eq!(
+            shards.metrics(),
+            vec![(
+                slot,
+                ShardMetrics {
+                    num_ids: 2,
+                    num_attestations: 2,
+                    num_rejected: 1,
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn slot_cap_evicts_oldest() {
+        let rng = &mut XorShiftRng::from_seed([42; 16]);
+        let mut shards = AttestationShards::<E>::new(4, 2);
+
+        for slot in 5..8 {
+            let (id, att) = random_attestation(rng);
+            assert_eq!(
+                shards.insert(Slot::new(slot), id, att),
+                ShardInsertOutcome::Fresh
+            );
+        }
+        let slots: Vec<Slot> = shards.iter().map(|(slot, _)| *slot).collect();
+        assert_eq!(slots, vec![Slot::new(6), Slot::new(7)]);
+
+        let (id, att) = random_attestation(rng);
+        assert_eq!(
+            shards.insert(Slot::new(1), id, att),
+            ShardInsertOutcome::TooOld
+        );
+    }
+
+    #[test]
+    fn prune_before_drops_whole_slots() {
+        let rng = &mut XorShiftRng::from_seed([42; 16]);
+        let mut shards = AttestationShards::<E>::new(4, 8);
+
+        for slot in 0..6 {
+            let (id, att) = random_attestation(rng);
+            shards.insert(Slot::new(slot), id, att);
+        }
+        shards.prune_before(Slot::new(4));
+
+        assert_eq!(shards.num_attestations(), 2);
+        assert_eq!(shards.range(Slot::new(0), Slot::new(3)).count(), 0);
+    }
+
+    #[test]
+    fn set_limits_drops_oldest_slots() {
+        let rng = &mut XorShiftRng::from_seed([42; 16]);
+        let mut shards = AttestationShards::<E>::new(4, 8);
+
+        for slot in 0..6 {
+            let (id, att) = random_attestation(rng);
+            shards.insert(Slot::new(slot), id, att);
+        }
+        shards.set_limits(1, 2);
+
+        let slots: Vec<Slot> = shards.iter().map(|(slot, _)| *slot).collect();
+        assert_eq!(slots, vec![Slot::new(4), Slot::new(5)]);
+
+        let (id, att) = random_attestation(rng);
+        assert_eq!(
+            shards.insert(Slot::new(5), id, att),
+            ShardInsertOutcome::ShardFull
+        );
+    }
+}
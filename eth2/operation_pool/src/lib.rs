@@ -1,12 +1,17 @@
 mod attestation;
 mod attestation_id;
+mod attestation_shards;
 mod max_cover;
 mod persistence;
 
+pub use attestation_shards::{
+    ShardInsertOutcome, ShardMetrics, DEFAULT_MAX_ATTESTATIONS_PER_SLOT, DEFAULT_MAX_SLOTS,
+};
 pub use persistence::PersistedOperationPool;
 
 use attestation::{earliest_attestation_validators, AttMaxCover};
 use attestation_id::AttestationId;
+use attestation_shards::AttestationShards;
 use itertools::Itertools;
 use max_cover::maximum_cover;
 use parking_lot::RwLock;
@@ -19,17 +24,18 @@ use state_processing::per_block_processing::{
     verify_attester_slashing, verify_exit, verify_exit_time_independent_only,
     verify_proposer_slashing, verify_transfer, verify_transfer_time_independent_only,
 };
-use std::collections::{btree_map::Entry, hash_map, BTreeMap, HashMap, HashSet};
+use std::collections::{btree_map::Entry, BTreeMap, HashMap, HashSet};
 use std::marker::PhantomData;
 use types::{
     typenum::Unsigned, Attestation, AttesterSlashing, BeaconState, ChainSpec, Deposit, EthSpec,
-    ProposerSlashing, Transfer, Validator, VoluntaryExit,
+    ProposerSlashing, Slot, Transfer, Validator, VoluntaryExit,
 };
 
 #[derive(Default, Debug)]
 pub struct OperationPool<T: EthSpec + Default> {
-    /// Map from attestation ID (see below) to vectors of attestations.
-    attestations: RwLock<HashMap<AttestationId, Vec<Attestation<T>>>>,
+    /// Attestations sharded by slot, each shard mapping attestation ID (see below) to vectors of
+    /// attestations.
+    attestations: RwLock<AttestationShards<T>>,
     /// Map from deposit index to deposit data.
     // NOTE: We assume that there is only one deposit per index
     // because the Eth1 data is updated (at most) once per epoch,
@@ -64,48 +70,68 @@ impl<T: EthSpec> OperationPool<T> {
     }
 
     /// Insert an attestation into the pool, aggregating it with existing attestations if possible.
+    ///
+    /// Attestations are stored in per-slot shards of bounded size. If the shard for the
+    /// attestation's slot is full (or the attestation is older than all stored slots) the
+    /// attestation is dropped and the returned outcome indicates why.
     pub fn insert_attestation(
         &self,
         attestation: Attestation<T>,
         state: &BeaconState<T>,
         spec: &ChainSpec,
-    ) -> Result<(), AttestationValidationError> {
+    ) -> Result<ShardInsertOutcome, AttestationValidationError> {
         // Check that attestation signatures are valid.
         verify_attestation_time_independent_only(state, &attestation, spec)?;
 
+        let slot = state.get_attestation_data_slot(&attestation.data)?;
         let id = AttestationId::from_data(&attestation.data, state, spec);
 
-        // Take a write lock on the attestations map.
-        let mut attestations = self.attestations.write();
-
-        let existing_attestations = match attestations.entry(id) {
-            hash_map::Entry::Vacant(entry) => {
-                entry.insert(vec![attestation]);
-                return Ok(());
-            }
-            hash_map::Entry::Occupied(entry) => entry.into_mut(),
-        };
-
-        let mut aggregated = false;
-        for existing_attestation in existing_attestations.iter_mut() {
-            if existing_attestation.signers_disjoint_from(&attestation) {
-                existing_attestation.aggregate(&attestation);
-                aggregated = true;
-            } else if *existing_attestation == attestation {
-                aggregated = true;
-            }
-        }
-
-        if !aggregated {
-            existing_attestations.push(attestation);
-        }
+        Ok(self.attestations.write().insert(slot, id, attestation))
+    }
 
-        Ok(())
+    /// Replace the limits on attestation storage, dropping the oldest slots if there are now too
+    /// many.
+    ///
+    /// See `DEFAULT_MAX_ATTESTATIONS_PER_SLOT` and `DEFAULT_MAX_SLOTS` for the default limits.
+    pub fn set_attestation_limits(&self, max_attestations_per_slot: usize, max_slots: usize) {
+        self.attestations
+            .write()
+            .set_limits(max_attestations_per_slot, max_slots);
     }
 
     /// Total number of attestations in the pool, including attestations for the same data.
     pub fn num_attestations(&self) -> usize {
-        self.attestations.read().values().map(Vec::len).sum()
+        self.attestations.read().num_attestations()
+    }
+
+    /// Total number of distinct attestation IDs in the pool.
+    pub fn num_attestation_ids(&self) -> usize {
+        self.attestations.read().num_ids()
+    }
+
+    /// Returns metrics for each per-slot attestation shard, in ascending slot order.
+    pub fn attestation_shard_metrics(&self) -> Vec<(Slot, ShardMetrics)> {
+        self.attestations.read().metrics()
+    }
+
+    /// Returns `true` if the pool holds an attestation for `slot` and `shard` which is signed by
+    /// the committee member at `committee_index`.
+    ///
+    /// Allows the validator of that member to avoid signing an attestation which would only
+    /// duplicate one already known (e.g., one published by a redundant validator client).
+    pub fn contains_attester(&self, slot: Slot, shard: u64, committee_index: usize) -> bool {
+        self.attestations
+            .read()
+            .range(slot, slot)
+            .flat_map(|(_, slot_shard)| slot_shard.iter())
+            .flat_map(|(_, attestations)| attestations)
+            .any(|attestation| {
+                attestation.data.crosslink.shard == shard
+                    && attestation
+                        .aggregation_bits
+                        .get(committee_index)
+                        .unwrap_or(false)
+            })
     }
 
     /// Get a list of attestations for inclusion in a block.
@@ -121,7 +147,14 @@ impl<T: EthSpec> OperationPool<T> {
         let curr_domain_bytes = AttestationId::compute_domain_bytes(current_epoch, state, spec);
         let reader = self.attestations.read();
         let valid_attestations = reader
-            .iter()
+            // Only shards for slots which may be included in a block at this state.
+            .range(
+                prev_epoch.start_slot(T::slots_per_epoch()),
+                state
+                    .slot
+                    .saturating_sub(spec.min_attestation_inclusion_delay),
+            )
+            .flat_map(|(_, shard)| shard.iter())
             .filter(|(key, _)| {
                 key.domain_bytes_match(&prev_domain_bytes)
                     || key.domain_bytes_match(&curr_domain_bytes)
@@ -138,15 +171,13 @@ impl<T: EthSpec> OperationPool<T> {
     pub fn prune_attestations(&self, finalized_state: &BeaconState<T>) {
         // We know we can include an attestation if:
         // state.slot <= attestation_slot + SLOTS_PER_EPOCH
-        // We approximate this check using the attestation's epoch, to avoid computing
-        // the slot or relying on the committee cache of the finalized state.
-        self.attestations.write().retain(|_, attestations| {
-            // All the attestations in this bucket have the same data, so we only need to
-            // check the first one.
-            attestations.first().map_or(false, |att| {
-                finalized_state.current_epoch() <= att.data.target.epoch + 1
-            })
-        });
+        // We approximate this check using the attestation's epoch, dropping all shards prior to
+        // the start of the finalized state's previous epoch.
+        let min_slot = finalized_state
+            .current_epoch()
+            .saturating_sub(1u64)
+            .start_slot(T::slots_per_epoch());
+        self.attestations.write().prune_before(min_slot);
     }
 
     /// Add a deposit to the pool.
@@ -738,7 +769,7 @@ mod tests {
                 }
             }
 
-            assert_eq!(op_pool.attestations.read().len(), committees.len());
+            assert_eq!(op_pool.num_attestation_ids(), committees.len());
             assert_eq!(op_pool.num_attestations(), committees.len());
 
             // Before the min attestation inclusion delay, get_attestations shouldn't return anything.
@@ -804,6 +835,44 @@ mod tests {
             assert_eq!(op_pool.num_attestations(), committees.len());
         }
 
+        /// The pool should report the committee members which have signed a known attestation.
+        #[test]
+        fn attestation_contains_attester() {
+            let (ref mut state, ref keypairs, ref spec) =
+                attestation_test_state::<MainnetEthSpec>(1);
+
+            let op_pool = OperationPool::new();
+
+            let slot = state.slot - 1;
+            let committees = state
+                .get_crosslink_committees_at_slot(slot)
+                .unwrap()
+                .into_iter()
+                .map(CrosslinkCommittee::into_owned)
+                .collect::<Vec<_>>();
+
+            for cc in &committees {
+                let att = signed_attestation(
+                    &cc.committee,
+                    cc.shard,
+                    keypairs,
+                    ..2,
+                    slot,
+                    state,
+                    spec,
+                    None,
+                );
+                op_pool.insert_attestation(att, state, spec).unwrap();
+            }
+
+            for cc in &committees {
+                assert!(op_pool.contains_attester(slot, cc.shard, 0));
+                assert!(op_pool.contains_attester(slot, cc.shard, 1));
+                assert!(!op_pool.contains_attester(slot, cc.shard, 2));
+                assert!(!op_pool.contains_attester(slot - 1, cc.shard, 0));
+            }
+        }
+
         /// Adding lots of attestations that only intersect pairwise should lead to two aggregate
         /// attestations.
         #[test]
@@ -842,7 +911,7 @@ mod tests {
 
             // The attestations should get aggregated into two attestations that comprise all
             // validators.
-            assert_eq!(op_pool.attestations.read().len(), committees.len());
+            assert_eq!(op_pool.num_attestation_ids(), committees.len());
             assert_eq!(op_pool.num_attestations(), 2 * committees.len());
         }
 
@@ -899,7 +968,7 @@ mod tests {
             let num_small = target_committee_size / small_step_size;
             let num_big = target_committee_size / big_step_size;
 
-            assert_eq!(op_pool.attestations.read().len(), committees.len());
+            assert_eq!(op_pool.num_attestation_ids(), committees.len());
             assert_eq!(
                 op_pool.num_attestations(),
                 (num_small + num_big) * committees.len()
@@ -1,4 +1,5 @@
 use crate::attestation_id::AttestationId;
+use crate::attestation_shards::AttestationShards;
 use crate::OperationPool;
 use parking_lot::RwLock;
 use ssz_derive::{Decode, Encode};
@@ -10,10 +11,10 @@ use types::*;
 /// of this type (or its encoded form) for equality. Convert back to an `OperationPool` first.
 #[derive(Encode, Decode)]
 pub struct PersistedOperationPool<T: EthSpec> {
-    /// Mapping from attestation ID to attestation mappings.
+    /// Mapping from attestation slot and ID to attestation mappings.
     // We could save space by not storing the attestation ID, but it might
     // be difficult to make that roundtrip due to eager aggregation.
-    attestations: Vec<(AttestationId, Vec<Attestation<T>>)>,
+    attestations: Vec<(Slot, AttestationId, Vec<Attestation<T>>)>,
     deposits: Vec<(u64, Deposit)>,
     /// Attester slashings.
     attester_slashings: Vec<AttesterSlashing<T>>,
@@ -32,7 +33,11 @@ impl<T: EthSpec> PersistedOperationPool<T> {
             .attestations
             .read()
             .iter()
-            .map(|(att_id, att)| (att_id.clone(), att.clone()))
+            .flat_map(|(slot, shard)| {
+                shard
+                    .iter()
+                    .map(move |(att_id, att)| (*slot, att_id.clone(), att.clone()))
+            })
             .collect();
 
         let deposits = operation_pool
@@ -77,7 +82,13 @@ impl<T: EthSpec> PersistedOperationPool<T> {
 
     /// Reconstruct an `OperationPool`.
     pub fn into_operation_pool(self, state: &BeaconState<T>, spec: &ChainSpec) -> OperationPool<T> {
-        let attestations = RwLock::new(self.attestations.into_iter().collect());
+        let mut attestations = AttestationShards::default();
+        for (slot, att_id, atts) in self.attestations {
+            for att in atts {
+                attestations.insert(slot, att_id.clone(), att);
+            }
+        }
+        let attestations = RwLock::new(attestations);
         let deposits = RwLock::new(self.deposits.into_iter().collect());
         let attester_slashings = RwLock::new(
             self.attester_slashings
@@ -0,0 +1,323 @@
+use criterion::Criterion;
+use criterion::{black_box, criterion_group, criterion_main, Benchmark};
+use state_processing::per_block_processing::BlockSignatureStrategy;
+use state_processing::per_epoch_processing::process_crosslinks;
+use state_processing::per_epoch_processing::single_pass::{
+    process_validators, process_validators_in_steps,
+};
+use state_processing::per_epoch_processing::validator_statuses::ValidatorStatuses;
+use state_processing::{
+    per_block_processing_with_cache, per_block_processing_with_strategy,
+    per_epoch_processing_with_validator_statuses, CommitteeCacheArena, PubkeyAggregateCache,
+};
+use tree_hash::SignedRoot;
+use types::test_utils::{TestingBeaconBlockBuilder, TestingBeaconStateBuilder};
+use types::{
+    BeaconState, ChainSpec, EthSpec, Hash256, Keypair, MainnetEthSpec, RelativeEpoch, SecretKey,
+    Unsigned,
+};
+
+/// Returns a state with `validator_count` validators, in the fourth epoch after genesis.
+fn get_state<T: EthSpec>(validator_count: usize, spec: &ChainSpec) -> BeaconState<T> {
+    let keypair = Keypair::random();
+    let mut builder: TestingBeaconStateBuilder<T> =
+        TestingBeaconStateBuilder::from_single_keypair(validator_count, &keypair, spec);
+    builder.teleport_to_slot((T::genesis_epoch() + 4).start_slot(T::slots_per_epoch()));
+    builder.build().0
+}
+
+/// Benchmarks building all committee caches of a state from scratch, against copying them from a
+/// `CommitteeCacheArena` which has already seen a state with the same shuffling.
+fn build_committee_caches<T: EthSpec>(c: &mut Criterion, spec_desc: &str, validator_count: usize) {
+    let spec = T::default_spec();
+    let state: BeaconState<T> = get_state(validator_count, &spec);
+
+    let arena = CommitteeCacheArena::default();
+    arena
+        .build_committee_caches(&mut state.clone(), &spec)
+        .expect("should build caches");
+
+    let uncached_state = state.clone();
+    let uncached_spec = spec.clone();
+
+    c.bench(
+        &format!("{}/{}_validators", spec_desc, validator_count),
+        Benchmark::new("build_committee_caches", move |b| {
+            b.iter_with_setup(
+                || uncached_state.clone(),
+                |mut state| {
+                    for relative_epoch in &[
+                        RelativeEpoch::Previous,
+                        RelativeEpoch::Current,
+                        RelativeEpoch::Next,
+                    ] {
+                        state
+                            .build_committee_cache(*relative_epoch, &uncached_spec)
+                            .expect("should build cache");
+                    }
+                    black_box(state)
+                },
+            )
+        })
+        .with_function("build_committee_caches_from_arena", move |b| {
+            b.iter_with_setup(
+                || state.clone(),
+                |mut state| {
+                    arena
+                        .build_committee_caches(&mut state, &spec)
+                        .expect("should build caches");
+                    black_box(state)
+                },
+            )
+        })
+        .sample_size(10),
+    );
+}
+
+/// Benchmarks computing the validator statuses of a state with full participation at the end of an
+/// epoch, from scratch and from statuses which were updated as each attestation was included.
+fn validator_statuses<T: EthSpec>(c: &mut Criterion, spec_desc: &str, validator_count: usize) {
+    let spec = T::default_spec();
+    let mut builder: TestingBeaconStateBuilder<T> =
+        TestingBeaconStateBuilder::from_single_keypair(validator_count, &Keypair::random(), &spec);
+    builder.teleport_to_slot((T::genesis_epoch() + 4).end_slot(T::slots_per_epoch()));
+    builder.insert_attestations(&spec);
+    let (state, _keypairs) = builder.build();
+
+    let mut incremental = ValidatorStatuses::new(&state, &spec).expect("should build statuses");
+    incremental
+        .process_attestations(&state, &spec)
+        .expect("should process attestations");
+
+    let from_scratch_state = state.clone();
+    let from_scratch_spec = spec.clone();
+
+    c.bench(
+        &format!("{}/{}_validators", spec_desc, validator_count),
+        Benchmark::new("validator_statuses_from_scratch", move |b| {
+            b.iter(|| {
+                let mut statuses = ValidatorStatuses::new(&from_scratch_state, &from_scratch_spec)
+                    .expect("should build statuses");
+                statuses
+                    .process_attestations(&from_scratch_state, &from_scratch_spec)
+                    .expect("should process attestations");
+                black_box(statuses)
+            })
+        })
+        .with_function("validator_statuses_update", move |b| {
+            b.iter_with_setup(
+                || incremental.clone(),
+                |mut statuses| {
+                    statuses
+                        .update(&state, &spec)
+                        .expect("should update statuses");
+                    black_box(statuses)
+                },
+            )
+        })
+        .sample_size(10),
+    );
+}
+
+/// Benchmarks computing the validator statuses of a state with full participation, and processing
+/// its epoch with them, serially and across threads.
+fn parallel_epoch_processing<T: EthSpec>(
+    c: &mut Criterion,
+    spec_desc: &str,
+    validator_count: usize,
+) {
+    let spec = T::default_spec();
+    let mut builder: TestingBeaconStateBuilder<T> =
+        TestingBeaconStateBuilder::from_single_keypair(validator_count, &Keypair::random(), &spec);
+    builder.teleport_to_slot((T::genesis_epoch() + 4).end_slot(T::slots_per_epoch()));
+    builder.insert_attestations(&spec);
+    let (state, _keypairs) = builder.build();
+
+    let statuses = |parallel| {
+        let state = state.clone();
+        let spec = spec.clone();
+        move |b: &mut criterion::Bencher| {
+            b.iter(|| {
+                let mut statuses = ValidatorStatuses::new_with_parallelism(&state, &spec, parallel)
+                    .expect("should build statuses");
+                statuses
+                    .process_attestations(&state, &spec)
+                    .expect("should process attestations");
+                black_box(statuses)
+            })
+        }
+    };
+    let epoch_processing = |parallel| {
+        let state = state.clone();
+        let spec = spec.clone();
+        move |b: &mut criterion::Bencher| {
+            b.iter_with_setup(
+                || state.clone(),
+                |mut state| {
+                    let statuses = ValidatorStatuses::new_with_parallelism(&state, &spec, parallel)
+                        .expect("should build statuses");
+                    per_epoch_processing_with_validator_statuses(&mut state, statuses, &spec)
+                        .expect("should process epoch");
+                    black_box(state)
+                },
+            )
+        }
+    };
+
+    c.bench(
+        &format!("{}/{}_validators", spec_desc, validator_count),
+        Benchmark::new("validator_statuses_serial", statuses(false))
+            .with_function("validator_statuses_parallel", statuses(true))
+            .with_function("per_epoch_processing_serial", epoch_processing(false))
+            .with_function("per_epoch_processing_parallel", epoch_processing(true))
+            .sample_size(10),
+    );
+}
+
+/// Benchmarks the rewards and penalties, registry updates, slashings and effective balance updates
+/// of a state with full participation, in a single pass and in the separate steps of the spec.
+fn single_pass_epoch_processing<T: EthSpec>(
+    c: &mut Criterion,
+    spec_desc: &str,
+    validator_count: usize,
+) {
+    let spec = T::default_spec();
+    let mut builder: TestingBeaconStateBuilder<T> =
+        TestingBeaconStateBuilder::from_single_keypair(validator_count, &Keypair::random(), &spec);
+    builder.teleport_to_slot((T::genesis_epoch() + 4).end_slot(T::slots_per_epoch()));
+    builder.insert_attestations(&spec);
+    let (mut state, _keypairs) = builder.build();
+
+    for &relative_epoch in &[
+        RelativeEpoch::Previous,
+        RelativeEpoch::Current,
+        RelativeEpoch::Next,
+    ] {
+        state
+            .build_committee_cache(relative_epoch, &spec)
+            .expect("should build committee cache");
+    }
+    state
+        .exit_cache
+        .build_from_registry(&state.validators, &spec);
+    let mut statuses = ValidatorStatuses::new(&state, &spec).expect("should build statuses");
+    statuses
+        .process_attestations(&state, &spec)
+        .expect("should process attestations");
+    let winning_root_for_shards =
+        process_crosslinks(&mut state, &spec).expect("should process crosslinks");
+
+    let processing = |single_pass| {
+        let state = state.clone();
+        let statuses = statuses.clone();
+        let winning_root_for_shards = winning_root_for_shards.clone();
+        let spec = spec.clone();
+        move |b: &mut criterion::Bencher| {
+            b.iter_with_setup(
+                || (state.clone(), statuses.clone()),
+                |(mut state, mut statuses)| {
+                    let process_validators = if single_pass {
+                        process_validators::<T>
+                    } else {
+                        process_validators_in_steps::<T>
+                    };
+                    process_validators(&mut state, &mut statuses, &winning_root_for_shards, &spec)
+                        .expect("should process validators");
+                    black_box(state)
+                },
+            )
+        }
+    };
+
+    c.bench(
+        &format!("{}/{}_validators", spec_desc, validator_count),
+        Benchmark::new("process_validators_single_pass", processing(true))
+            .with_function("process_validators_in_steps", processing(false))
+            .sample_size(10),
+    );
+}
+
+/// Benchmarks processing a block with the maximum number of attestations, verifying its signatures
+/// in bulk with a `PubkeyAggregateCache` built for the block, and with one which has already seen
+/// the committees of the block (as the cache of the `BeaconChain` has when importing a gossip
+/// block).
+fn block_processing_with_cache<T: EthSpec>(
+    c: &mut Criterion,
+    spec_desc: &str,
+    validator_count: usize,
+) {
+    let spec = T::default_spec();
+    let keypair = Keypair::random();
+    let mut builder: TestingBeaconStateBuilder<T> =
+        TestingBeaconStateBuilder::from_single_keypair(validator_count, &keypair, &spec);
+    builder.teleport_to_slot(
+        (T::genesis_epoch() + 4).start_slot(T::slots_per_epoch()) + T::slots_per_epoch() / 2,
+    );
+    let (mut state, _keypairs) = builder.build();
+    state.build_all_caches(&spec).expect("should build caches");
+
+    let mut block_builder: TestingBeaconBlockBuilder<T> = TestingBeaconBlockBuilder::new(&spec);
+    block_builder.set_slot(state.slot);
+    block_builder.set_parent_root(Hash256::from_slice(
+        &state.latest_block_header.signed_root(),
+    ));
+    block_builder.set_randao_reveal(&keypair.sk, &state.fork, &spec);
+    let secret_keys: Vec<&SecretKey> = vec![&keypair.sk; validator_count];
+    block_builder
+        .insert_attestations(&state, &secret_keys, T::MaxAttestations::to_usize(), &spec)
+        .expect("should insert attestations");
+    let block = block_builder.build(&keypair.sk, &state.fork, &spec);
+
+    let warm_cache = PubkeyAggregateCache::new();
+    per_block_processing_with_cache(&mut state.clone(), &block, &warm_cache, &spec)
+        .expect("should process block");
+
+    let cold_state = state.clone();
+    let cold_block = block.clone();
+    let cold_spec = spec.clone();
+
+    c.bench(
+        &format!("{}/{}_validators", spec_desc, validator_count),
+        Benchmark::new("per_block_processing_verify_bulk", move |b| {
+            b.iter_with_setup(
+                || cold_state.clone(),
+                |mut state| {
+                    per_block_processing_with_strategy(
+                        &mut state,
+                        &cold_block,
+                        BlockSignatureStrategy::VerifyBulk,
+                        &cold_spec,
+                    )
+                    .expect("should process block");
+                    black_box(state)
+                },
+            )
+        })
+        .with_function("per_block_processing_with_warm_cache", move |b| {
+            b.iter_with_setup(
+                || state.clone(),
+                |mut state| {
+                    per_block_processing_with_cache(&mut state, &block, &warm_cache, &spec)
+                        .expect("should process block");
+                    black_box(state)
+                },
+            )
+        })
+        .sample_size(10),
+    );
+}
+
+fn all_benches(c: &mut Criterion) {
+    build_committee_caches::<MainnetEthSpec>(c, "mainnet", 16_384);
+    build_committee_caches::<MainnetEthSpec>(c, "mainnet", 65_536);
+
+    validator_statuses::<MainnetEthSpec>(c, "mainnet", 16_384);
+
+    parallel_epoch_processing::<MainnetEthSpec>(c, "mainnet", 300_000);
+    single_pass_epoch_processing::<MainnetEthSpec>(c, "mainnet", 300_000);
+
+    block_processing_with_cache::<MainnetEthSpec>(c, "mainnet", 16_384);
+}
+
+criterion_group!(benches, all_benches,);
+criterion_main!(benches);
@@ -0,0 +1,276 @@
+//! A cache of committee caches which may be shared between states.
+//!
+//! Building a `CommitteeCache` requires shuffling every active validator, which dominates the
+//! cost of building the caches of a state. Many states share the same shuffling (e.g., the pre-
+//! and post-states of successive EF test cases, or competing heads in the same epoch), so the
+//! `CommitteeCacheArena` stores each shuffling it computes and hands out copies to any other state
+//! with the same shuffling inputs.
+use eth2_hashing::hash;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use types::{
+    BeaconState, BeaconStateError, ChainSpec, CommitteeCache, Epoch, EthSpec, Hash256,
+    RelativeEpoch,
+};
+
+/// The number of committee caches stored by `CommitteeCacheArena::default`.
+///
+/// Each state requires up to three (previous, current and next epoch).
+pub const DEFAULT_CAPACITY: usize = 16;
+
+/// Uniquely identifies the inputs to a `CommitteeCache`.
+///
+/// Rather than the tree hash root of the whole validator registry, which is more expensive to
+/// compute than the shuffling itself, the registry is identified by a hash of the indices of the
+/// validators which are active at `epoch` (the only part of the registry which is shuffled)
+/// and its length.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct CommitteeCacheKey {
+    pub active_validators_root: Hash256,
+    pub validator_count: usize,
+    pub epoch: Epoch,
+    pub seed: Hash256,
+    pub start_shard: u64,
+    pub slots_per_epoch: u64,
+    pub shard_count: u64,
+}
+
+impl CommitteeCacheKey {
+    /// Returns the key of the committee cache of `state` at `relative_epoch`.
+    ///
+    /// Spec v0.8.1
+    pub fn for_state<T: EthSpec>(
+        state: &BeaconState<T>,
+        relative_epoch: RelativeEpoch,
+        spec: &ChainSpec,
+    ) -> Result<Self, BeaconStateError> {
+        let epoch = relative_epoch.into_epoch(state.current_epoch());
+        let active_validator_indices = state.get_active_validator_indices(epoch);
+
+        let mut bytes = Vec::with_capacity(active_validator_indices.len() * 8);
+        for i in &active_validator_indices {
+            bytes.extend_from_slice(&(*i as u64).to_le_bytes());
+        }
+
+        Ok(Self {
+            active_validators_root: Hash256::from_slice(&hash(&bytes)),
+            validator_count: state.validators.len(),
+            epoch,
+            seed: state.get_seed(epoch, spec)?,
+            start_shard: CommitteeCache::compute_start_shard(
+                state,
+                relative_epoch,
+                active_validator_indices.len(),
+                spec,
+            ),
+            slots_per_epoch: T::slots_per_epoch(),
+            shard_count: T::shard_count() as u64,
+        })
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    caches: HashMap<CommitteeCacheKey, Arc<CommitteeCache>>,
+    /// The keys of `caches`, in the order they were inserted.
+    insertion_order: VecDeque<CommitteeCacheKey>,
+}
+
+/// Stores up to `capacity` committee caches, evicting the oldest when full.
+///
+/// The arena is `Sync`, so it may be shared between threads (e.g., in a `lazy_static`).
+pub struct CommitteeCacheArena {
+    inner: Mutex<Inner>,
+    capacity: usize,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl Default for CommitteeCacheArena {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl CommitteeCacheArena {
+    /// Create an empty arena which stores up to `capacity` committee caches.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Mutex::new(Inner::default()),
+            capacity,
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        }
+    }
+
+    /// Build the committee cache of `state` at `relative_epoch`, unless it is already built.
+    ///
+    /// The cache is copied from the arena if a state with the same shuffling has been seen before,
+    /// otherwise it is computed and stored in the arena.
+    pub fn build_committee_cache<T: EthSpec>(
+        &self,
+        state: &mut BeaconState<T>,
+        relative_epoch: RelativeEpoch,
+        spec: &ChainSpec,
+    ) -> Result<(), BeaconStateError> {
+        if state.committee_cache(relative_epoch).is_ok() {
+            return Ok(());
+        }
+
+        let epoch = relative_epoch.into_epoch(state.current_epoch());
+        let key = CommitteeCacheKey::for_state(state, relative_epoch, spec)?;
+
+        let cache = match self.get(&key) {
+            Some(cache) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                cache
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                let cache = Arc::new(CommitteeCache::initialized(state, epoch, spec)?);
+                self.insert(key, cache.clone());
+                cache
+            }
+        };
+
+        state.set_committee_cache(relative_epoch, (*cache).clone())
+    }
+
+    /// Build the previous, current and next epoch committee caches of `state`.
+    pub fn build_committee_caches<T: EthSpec>(
+        &self,
+        state: &mut BeaconState<T>,
+        spec: &ChainSpec,
+    ) -> Result<(), BeaconStateError> {
+        self.build_committee_cache(state, RelativeEpoch::Previous, spec)?;
+        self.build_committee_cache(state, RelativeEpoch::Current, spec)?;
+        self.build_committee_cache(state, RelativeEpoch::Next, spec)
+    }
+
+    /// The number of committee caches stored in the arena.
+    pub fn len(&self) -> usize {
+        self.lock().caches.len()
+    }
+
+    /// Returns `true` if the arena stores no committee caches.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of committee caches which have been copied from the arena.
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// The number of committee caches which had to be computed.
+    pub fn misses(&self) -> usize {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    fn get(&self, key: &CommitteeCacheKey) -> Option<Arc<CommitteeCache>> {
+        self.lock().caches.get(key).cloned()
+    }
+
+    fn insert(&self, key: CommitteeCacheKey, cache: Arc<CommitteeCache>) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut inner = self.lock();
+        // Another thread may have computed the same cache concurrently.
+        if inner.caches.insert(key, cache).is_some() {
+            return;
+        }
+        inner.insertion_order.push_back(key);
+
+        while inner.caches.len() > self.capacity {
+            match inner.insertion_order.pop_front() {
+                Some(oldest) => {
+                    inner.caches.remove(&oldest);
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// A panic whilst holding the lock cannot leave the map inconsistent, so a poisoned lock is
+    /// still used.
+    fn lock(&self) -> std::sync::MutexGuard<Inner> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use types::test_utils::TestingBeaconStateBuilder;
+    use types::MinimalEthSpec;
+
+    fn state(validator_count: usize, spec: &ChainSpec) -> BeaconState<MinimalEthSpec> {
+        let mut builder: TestingBeaconStateBuilder<MinimalEthSpec> =
+            TestingBeaconStateBuilder::from_deterministic_keypairs(validator_count, spec);
+        builder.teleport_to_slot(
+            (MinimalEthSpec::genesis_epoch() + 4).start_slot(MinimalEthSpec::slots_per_epoch()),
+        );
+        builder.build().0
+    }
+
+    #[test]
+    fn reuses_committee_caches_between_states() {
+        let spec = MinimalEthSpec::default_spec();
+        let arena = CommitteeCacheArena::default();
+
+        let mut first = state(16, &spec);
+        arena.build_committee_caches(&mut first, &spec).unwrap();
+        assert_eq!((arena.hits(), arena.misses()), (0, 3));
+
+        let mut second = state(16, &spec);
+        arena.build_committee_caches(&mut second, &spec).unwrap();
+        assert_eq!((arena.hits(), arena.misses()), (3, 3));
+
+        let mut expected = state(16, &spec);
+        expected.build_all_caches(&spec).unwrap();
+        assert_eq!(second.committee_caches, expected.committee_caches);
+    }
+
+    #[test]
+    fn distinguishes_different_shufflings() {
+        let spec = MinimalEthSpec::default_spec();
+        let arena = CommitteeCacheArena::default();
+
+        let mut first = state(16, &spec);
+        arena.build_committee_caches(&mut first, &spec).unwrap();
+
+        let mut second = state(16, &spec);
+        second.validators[3].exit_epoch = second.current_epoch() - 1;
+        arena.build_committee_caches(&mut second, &spec).unwrap();
+        assert_eq!(arena.misses(), 6);
+
+        let mut expected = second.clone();
+        expected.drop_all_caches();
+        expected.build_all_caches(&spec).unwrap();
+        assert_eq!(second.committee_caches, expected.committee_caches);
+    }
+
+    #[test]
+    fn evicts_the_oldest_cache() {
+        let spec = MinimalEthSpec::default_spec();
+        let arena = CommitteeCacheArena::new(2);
+
+        let mut state = state(16, &spec);
+        arena.build_committee_caches(&mut state, &spec).unwrap();
+        assert_eq!(arena.len(), 2);
+
+        // The previous epoch cache was inserted first, so it is the one evicted.
+        let key =
+            |relative_epoch| CommitteeCacheKey::for_state(&state, relative_epoch, &spec).unwrap();
+        let previous = key(RelativeEpoch::Previous);
+        assert_eq!(previous.epoch, state.previous_epoch());
+        assert!(arena.get(&previous).is_none());
+        assert!(arena.get(&key(RelativeEpoch::Current)).is_some());
+        assert!(arena.get(&key(RelativeEpoch::Next)).is_some());
+    }
+}
@@ -1,16 +1,24 @@
 #[macro_use]
 mod macros;
 
+pub mod committee_cache_arena;
 pub mod common;
 pub mod genesis;
 pub mod per_block_processing;
 pub mod per_epoch_processing;
 pub mod per_slot_processing;
+pub mod pubkey_aggregate_cache;
 
+pub use committee_cache_arena::CommitteeCacheArena;
 pub use genesis::{initialize_beacon_state_from_eth1, is_valid_genesis_state};
 pub use per_block_processing::{
     errors::{BlockInvalid, BlockProcessingError},
-    per_block_processing, per_block_processing_without_verifying_block_signature,
+    per_block_processing, per_block_processing_with_cache, per_block_processing_with_strategy,
+    per_block_processing_without_verifying_block_signature, BlockSignatureStrategy,
+};
+pub use per_epoch_processing::{
+    errors::EpochProcessingError, per_epoch_processing,
+    per_epoch_processing_with_validator_statuses,
 };
-pub use per_epoch_processing::{errors::EpochProcessingError, per_epoch_processing};
 pub use per_slot_processing::{per_slot_processing, Error as SlotProcessingError};
+pub use pubkey_aggregate_cache::PubkeyAggregateCache;
@@ -1,4 +1,5 @@
 use crate::common::{initiate_validator_exit, slash_validator};
+use crate::pubkey_aggregate_cache::PubkeyAggregateCache;
 use errors::{BlockInvalid as Invalid, BlockProcessingError as Error, IntoWithIndex};
 use rayon::prelude::*;
 use std::collections::HashSet;
@@ -7,10 +8,14 @@ use std::iter::FromIterator;
 use tree_hash::{SignedRoot, TreeHash};
 use types::*;
 
+pub use self::block_signature_verifier::BlockSignatureVerifier;
+pub use self::epoch_proposers::{verify_block_signature_with_proposers, EpochProposers};
 pub use self::verify_attester_slashing::{
     get_slashable_indices, get_slashable_indices_modular, verify_attester_slashing,
 };
-pub use self::verify_proposer_slashing::verify_proposer_slashing;
+pub use self::verify_proposer_slashing::{
+    verify_proposer_slashing, verify_proposer_slashing_without_signatures,
+};
 pub use is_valid_indexed_attestation::{
     is_valid_indexed_attestation, is_valid_indexed_attestation_without_signature,
 };
@@ -21,12 +26,17 @@ pub use verify_attestation::{
 pub use verify_deposit::{
     get_existing_validator_index, verify_deposit_merkle_proof, verify_deposit_signature,
 };
-pub use verify_exit::{verify_exit, verify_exit_time_independent_only};
+pub use verify_exit::{
+    verify_exit, verify_exit_time_independent_only, verify_exit_without_signature,
+};
 pub use verify_transfer::{
     execute_transfer, verify_transfer, verify_transfer_time_independent_only,
+    verify_transfer_without_signature,
 };
 
 pub mod block_processing_builder;
+pub mod block_signature_verifier;
+mod epoch_proposers;
 pub mod errors;
 mod is_valid_indexed_attestation;
 pub mod tests;
@@ -37,6 +47,19 @@ mod verify_exit;
 mod verify_proposer_slashing;
 mod verify_transfer;
 
+/// The strategy to be used when validating the signatures of a block.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum BlockSignatureStrategy {
+    /// Verify each signature as it is encountered during block processing.
+    VerifyIndividual,
+    /// Verify all signatures in the block in parallel, before processing it.
+    ///
+    /// Each signature is still verified on its own (see `block_signature_verifier`), rather than
+    /// by randomized batch verification. If any signature is invalid, the block is processed again
+    /// with `VerifyIndividual` to identify it.
+    VerifyBulk,
+}
+
 /// Updates the state for a new block, whilst validating that the block is valid.
 ///
 /// Returns `Ok(())` if the block is valid and the state was successfully updated. Otherwise
@@ -48,7 +71,52 @@ pub fn per_block_processing<T: EthSpec>(
     block: &BeaconBlock<T>,
     spec: &ChainSpec,
 ) -> Result<(), Error> {
-    per_block_processing_signature_optional(state, block, true, spec)
+    per_block_processing_signature_optional(state, block, true, true, spec)
+}
+
+/// Updates the state for a new block, whilst validating that the block is valid, verifying its
+/// signatures according to `strategy`.
+///
+/// The result is identical to that of `per_block_processing`, regardless of `strategy`.
+///
+/// Spec v0.8.0
+pub fn per_block_processing_with_strategy<T: EthSpec>(
+    state: &mut BeaconState<T>,
+    block: &BeaconBlock<T>,
+    strategy: BlockSignatureStrategy,
+    spec: &ChainSpec,
+) -> Result<(), Error> {
+    match strategy {
+        BlockSignatureStrategy::VerifyIndividual => per_block_processing(state, block, spec),
+        BlockSignatureStrategy::VerifyBulk => {
+            per_block_processing_with_cache(state, block, &PubkeyAggregateCache::default(), spec)
+        }
+    }
+}
+
+/// Updates the state for a new block, whilst validating that the block is valid, verifying its
+/// signatures in bulk with the public keys of `cache` (which may be shared between blocks).
+///
+/// Equivalent to `per_block_processing_with_strategy` with `BlockSignatureStrategy::VerifyBulk`.
+///
+/// Spec v0.8.0
+pub fn per_block_processing_with_cache<T: EthSpec>(
+    state: &mut BeaconState<T>,
+    block: &BeaconBlock<T>,
+    cache: &PubkeyAggregateCache,
+    spec: &ChainSpec,
+) -> Result<(), Error> {
+    state.build_committee_cache(RelativeEpoch::Previous, spec)?;
+    state.build_committee_cache(RelativeEpoch::Current, spec)?;
+    state.update_pubkey_cache()?;
+
+    if BlockSignatureVerifier::verify_entire_block_with_cache(state, block, cache, spec).is_ok() {
+        per_block_processing_signature_optional(state, block, false, false, spec)
+    } else {
+        // At least one signature is invalid (or could not be verified in bulk), so verify them
+        // individually to determine if the block is invalid, and why.
+        per_block_processing(state, block, spec)
+    }
 }
 
 /// Updates the state for a new block, whilst validating that the block is valid, without actually
@@ -63,11 +131,11 @@ pub fn per_block_processing_without_verifying_block_signature<T: EthSpec>(
     block: &BeaconBlock<T>,
     spec: &ChainSpec,
 ) -> Result<(), Error> {
-    per_block_processing_signature_optional(state, block, false, spec)
+    per_block_processing_signature_optional(state, block, false, true, spec)
 }
 
 /// Updates the state for a new block, whilst validating that the block is valid, optionally
-/// checking the block proposer signature.
+/// checking the block proposer signature and the signatures of the rest of the block.
 ///
 /// Returns `Ok(())` if the block is valid and the state was successfully updated. Otherwise
 /// returns an error describing why the block was invalid or how the function failed to execute.
@@ -77,6 +145,7 @@ fn per_block_processing_signature_optional<T: EthSpec>(
     mut state: &mut BeaconState<T>,
     block: &BeaconBlock<T>,
     should_verify_block_signature: bool,
+    verify_signatures: bool,
     spec: &ChainSpec,
 ) -> Result<(), Error> {
     process_block_header(state, block, spec, should_verify_block_signature)?;
@@ -85,14 +154,34 @@ fn per_block_processing_signature_optional<T: EthSpec>(
     state.build_committee_cache(RelativeEpoch::Previous, spec)?;
     state.build_committee_cache(RelativeEpoch::Current, spec)?;
 
-    process_randao(&mut state, &block, &spec)?;
+    process_randao(&mut state, &block, &spec, verify_signatures)?;
     process_eth1_data(&mut state, &block.body.eth1_data)?;
-    process_proposer_slashings(&mut state, &block.body.proposer_slashings, spec)?;
-    process_attester_slashings(&mut state, &block.body.attester_slashings, spec)?;
-    process_attestations(&mut state, &block.body.attestations, spec)?;
-    process_deposits(&mut state, &block.body.deposits, spec)?;
-    process_exits(&mut state, &block.body.voluntary_exits, spec)?;
-    process_transfers(&mut state, &block.body.transfers, spec)?;
+    process_proposer_slashings(
+        &mut state,
+        &block.body.proposer_slashings,
+        spec,
+        verify_signatures,
+    )?;
+    process_attester_slashings(
+        &mut state,
+        &block.body.attester_slashings,
+        spec,
+        verify_signatures,
+    )?;
+    process_attestations(
+        &mut state,
+        &block.body.attestations,
+        spec,
+        verify_signatures,
+    )?;
+    process_deposits(&mut state, &block.body.deposits, spec, verify_signatures)?;
+    process_exits(
+        &mut state,
+        &block.body.voluntary_exits,
+        spec,
+        verify_signatures,
+    )?;
+    process_transfers(&mut state, &block.body.transfers, spec, verify_signatures)?;
 
     Ok(())
 }
@@ -159,7 +248,7 @@ pub fn verify_block_signature<T: EthSpec>(
     Ok(())
 }
 
-/// Verifies the `randao_reveal` against the block's proposer pubkey and updates
+/// Optionally verifies the `randao_reveal` against the block's proposer pubkey and updates
 /// `state.latest_randao_mixes`.
 ///
 /// Spec v0.8.0
@@ -167,23 +256,26 @@ pub fn process_randao<T: EthSpec>(
     state: &mut BeaconState<T>,
     block: &BeaconBlock<T>,
     spec: &ChainSpec,
+    verify_signatures: bool,
 ) -> Result<(), Error> {
-    let block_proposer = &state.validators
-        [state.get_beacon_proposer_index(block.slot, RelativeEpoch::Current, spec)?];
-
-    // Verify RANDAO reveal.
-    verify!(
-        block.body.randao_reveal.verify(
-            &state.current_epoch().tree_hash_root()[..],
-            spec.get_domain(
-                block.slot.epoch(T::slots_per_epoch()),
-                Domain::Randao,
-                &state.fork
+    if verify_signatures {
+        let block_proposer = &state.validators
+            [state.get_beacon_proposer_index(block.slot, RelativeEpoch::Current, spec)?];
+
+        // Verify RANDAO reveal.
+        verify!(
+            block.body.randao_reveal.verify(
+                &state.current_epoch().tree_hash_root()[..],
+                spec.get_domain(
+                    block.slot.epoch(T::slots_per_epoch()),
+                    Domain::Randao,
+                    &state.fork
+                ),
+                &block_proposer.pubkey
             ),
-            &block_proposer.pubkey
-        ),
-        Invalid::BadRandaoSignature
-    );
+            Invalid::BadRandaoSignature
+        );
+    }
 
     // Update the current epoch RANDAO mix.
     state.update_randao_mix(state.current_epoch(), &block.body.randao_reveal)?;
@@ -223,14 +315,19 @@ pub fn process_proposer_slashings<T: EthSpec>(
     state: &mut BeaconState<T>,
     proposer_slashings: &[ProposerSlashing],
     spec: &ChainSpec,
+    verify_signatures: bool,
 ) -> Result<(), Error> {
     // Verify proposer slashings in parallel.
     proposer_slashings
         .par_iter()
         .enumerate()
         .try_for_each(|(i, proposer_slashing)| {
-            verify_proposer_slashing(proposer_slashing, &state, spec)
-                .map_err(|e| e.into_with_index(i))
+            if verify_signatures {
+                verify_proposer_slashing(proposer_slashing, &state, spec)
+            } else {
+                verify_proposer_slashing_without_signatures(proposer_slashing, &state, spec)
+            }
+            .map_err(|e| e.into_with_index(i))
         })?;
 
     // Update the state.
@@ -251,6 +348,7 @@ pub fn process_attester_slashings<T: EthSpec>(
     state: &mut BeaconState<T>,
     attester_slashings: &[AttesterSlashing<T>],
     spec: &ChainSpec,
+    verify_signatures: bool,
 ) -> Result<(), Error> {
     // Verify the `IndexedAttestation`s in parallel (these are the resource-consuming objects, not
     // the `AttesterSlashing`s themselves).
@@ -265,8 +363,12 @@ pub fn process_attester_slashings<T: EthSpec>(
         .par_iter()
         .enumerate()
         .try_for_each(|(i, indexed_attestation)| {
-            is_valid_indexed_attestation(&state, indexed_attestation, spec)
-                .map_err(|e| e.into_with_index(i))
+            if verify_signatures {
+                is_valid_indexed_attestation(&state, indexed_attestation, spec)
+            } else {
+                is_valid_indexed_attestation_without_signature(&state, indexed_attestation, spec)
+            }
+            .map_err(|e| e.into_with_index(i))
         })?;
     let all_indexed_attestations_have_been_checked = true;
 
@@ -303,6 +405,7 @@ pub fn process_attestations<T: EthSpec>(
     state: &mut BeaconState<T>,
     attestations: &[Attestation<T>],
     spec: &ChainSpec,
+    verify_signatures: bool,
 ) -> Result<(), Error> {
     // Ensure the previous epoch cache exists.
     state.build_committee_cache(RelativeEpoch::Previous, spec)?;
@@ -312,7 +415,12 @@ pub fn process_attestations<T: EthSpec>(
         .par_iter()
         .enumerate()
         .try_for_each(|(i, attestation)| {
-            verify_attestation(state, attestation, spec).map_err(|e| e.into_with_index(i))
+            if verify_signatures {
+                verify_attestation(state, attestation, spec)
+            } else {
+                verify_attestation_without_signature(state, attestation, spec)
+            }
+            .map_err(|e| e.into_with_index(i))
         })?;
 
     // Update the state in series.
@@ -349,6 +457,7 @@ pub fn process_deposits<T: EthSpec>(
     state: &mut BeaconState<T>,
     deposits: &[Deposit],
     spec: &ChainSpec,
+    verify_signatures: bool,
 ) -> Result<(), Error> {
     verify!(
         deposits.len() as u64
@@ -370,7 +479,7 @@ pub fn process_deposits<T: EthSpec>(
 
     // Update the state in series.
     for deposit in deposits {
-        process_deposit(state, deposit, spec, false)?;
+        apply_deposit(state, deposit, spec, false, verify_signatures)?;
     }
 
     Ok(())
@@ -384,6 +493,20 @@ pub fn process_deposit<T: EthSpec>(
     deposit: &Deposit,
     spec: &ChainSpec,
     verify_merkle_proof: bool,
+) -> Result<(), Error> {
+    apply_deposit(state, deposit, spec, verify_merkle_proof, true)
+}
+
+/// Process a single deposit, optionally verifying its merkle proof and the signature of a deposit
+/// for a new validator.
+///
+/// Spec v0.8.1
+fn apply_deposit<T: EthSpec>(
+    state: &mut BeaconState<T>,
+    deposit: &Deposit,
+    spec: &ChainSpec,
+    verify_merkle_proof: bool,
+    verify_signature: bool,
 ) -> Result<(), Error> {
     let deposit_index = state.eth1_deposit_index as usize;
     if verify_merkle_proof {
@@ -414,7 +537,7 @@ pub fn process_deposit<T: EthSpec>(
     } else {
         // The signature should be checked for new validators. Return early for a bad
         // signature.
-        if verify_deposit_signature(state, deposit, spec, &pubkey).is_err() {
+        if verify_signature && verify_deposit_signature(state, deposit, spec, &pubkey).is_err() {
             return Ok(());
         }
 
@@ -449,13 +572,19 @@ pub fn process_exits<T: EthSpec>(
     state: &mut BeaconState<T>,
     voluntary_exits: &[VoluntaryExit],
     spec: &ChainSpec,
+    verify_signatures: bool,
 ) -> Result<(), Error> {
     // Verify exits in parallel.
     voluntary_exits
         .par_iter()
         .enumerate()
         .try_for_each(|(i, exit)| {
-            verify_exit(&state, exit, spec).map_err(|e| e.into_with_index(i))
+            if verify_signatures {
+                verify_exit(&state, exit, spec)
+            } else {
+                verify_exit_without_signature(&state, exit, spec)
+            }
+            .map_err(|e| e.into_with_index(i))
         })?;
 
     // Update the state in series.
@@ -476,6 +605,7 @@ pub fn process_transfers<T: EthSpec>(
     state: &mut BeaconState<T>,
     transfers: &[Transfer],
     spec: &ChainSpec,
+    verify_signatures: bool,
 ) -> Result<(), Error> {
     // Verify that there are no duplicate transfers
     verify!(
@@ -487,7 +617,12 @@ pub fn process_transfers<T: EthSpec>(
         .par_iter()
         .enumerate()
         .try_for_each(|(i, transfer)| {
-            verify_transfer(&state, transfer, spec).map_err(|e| e.into_with_index(i))
+            if verify_signatures {
+                verify_transfer(&state, transfer, spec)
+            } else {
+                verify_transfer_without_signature(&state, transfer, spec)
+            }
+            .map_err(|e| e.into_with_index(i))
         })?;
 
     for (i, transfer) in transfers.iter().enumerate() {
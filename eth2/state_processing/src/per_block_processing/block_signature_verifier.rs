@@ -0,0 +1,395 @@
+//! Collects every signature in a `BeaconBlock` so that they may all be verified in bulk, before
+//! any of the block is applied to the state.
+//!
+//! This is not batch verification: each signature set is still verified with its own pairings,
+//! in parallel, so the saving over `VerifyIndividual` comes from the parallelism and the
+//! `PubkeyAggregateCache` alone. `milagro_bls` does not provide randomized (random linear
+//! combination) multi-pairing verification, and `verify_signature_sets` is the only place which
+//! needs to change once it does.
+//!
+//! The aggregate public keys of attestations and the public keys of deposits are taken from a
+//! `PubkeyAggregateCache`, which may be shared between blocks (see `verify_entire_block_with_cache`).
+use crate::common::get_indexed_attestation;
+use crate::pubkey_aggregate_cache::PubkeyAggregateCache;
+use rayon::prelude::*;
+use std::borrow::Cow;
+use std::convert::TryInto;
+use thiserror::Error;
+use tree_hash::{SignedRoot, TreeHash};
+use types::*;
+
+#[derive(Debug, PartialEq, Error)]
+pub enum Error {
+    /// At least one signature in the block is invalid.
+    #[error("at least one signature in the block is invalid")]
+    SignatureInvalid,
+    /// A signature references a validator which is not in the state.
+    #[error("unknown validator {0}")]
+    ValidatorUnknown(u64),
+    /// The signature of a deposit for a new validator could not be decoded.
+    #[error("undecodable deposit signature")]
+    BadDepositSignatureBytes,
+    /// The attesting indices of an attestation could not be determined.
+    #[error("unable to determine the attesting indices of an attestation")]
+    AttestationInvalid,
+    #[error("beacon state error: {0:?}")]
+    BeaconStateError(BeaconStateError),
+}
+
+impl From<BeaconStateError> for Error {
+    fn from(e: BeaconStateError) -> Error {
+        Error::BeaconStateError(e)
+    }
+}
+
+/// A signature and everything required to verify it.
+enum SignatureSet<'a> {
+    /// A signature over a single message by a single public key.
+    Single {
+        signature: Cow<'a, Signature>,
+        message: Vec<u8>,
+        domain: u64,
+        pubkey: Cow<'a, PublicKey>,
+    },
+    /// An aggregate signature over several messages, each by an aggregate public key.
+    Multiple {
+        signature: &'a AggregateSignature,
+        messages: Vec<Vec<u8>>,
+        domain: u64,
+        pubkeys: Vec<AggregatePublicKey>,
+    },
+}
+
+impl<'a> SignatureSet<'a> {
+    fn is_valid(&self) -> bool {
+        match self {
+            SignatureSet::Single {
+                signature,
+                message,
+                domain,
+                pubkey,
+            } => signature.verify(message, *domain, pubkey),
+            SignatureSet::Multiple {
+                signature,
+                messages,
+                domain,
+                pubkeys,
+            } => {
+                let messages: Vec<&[u8]> = messages.iter().map(Vec::as_slice).collect();
+                let pubkeys: Vec<&AggregatePublicKey> = pubkeys.iter().collect();
+                signature.verify_multiple(&messages, *domain, &pubkeys)
+            }
+        }
+    }
+}
+
+/// Returns `true` if every signature set is valid, verifying each set individually on the rayon
+/// thread pool.
+fn verify_signature_sets(sets: &[SignatureSet]) -> bool {
+    sets.par_iter().all(SignatureSet::is_valid)
+}
+
+/// Gathers the signatures of a block against the state the block is to be applied to.
+pub struct BlockSignatureVerifier<'a, 'c, T: EthSpec> {
+    state: &'a BeaconState<T>,
+    spec: &'a ChainSpec,
+    cache: &'c PubkeyAggregateCache,
+    sets: Vec<SignatureSet<'a>>,
+}
+
+impl<'a, 'c, T: EthSpec> BlockSignatureVerifier<'a, 'c, T> {
+    /// Verify all of the signatures in `block` which `per_block_processing` would verify, except
+    /// those of deposits for validators which are already in `state` (which are never verified).
+    ///
+    /// `state` must be the state prior to `block` being applied, with the previous and current
+    /// epoch committee caches built and the pubkey cache up-to-date.
+    ///
+    /// An `Err` does not imply that `block` is invalid (e.g., a deposit with an invalid signature
+    /// is simply ignored by `per_block_processing`), only that it must be processed with
+    /// individual signature verification to find out.
+    pub fn verify_entire_block(
+        state: &'a BeaconState<T>,
+        block: &'a BeaconBlock<T>,
+        spec: &'a ChainSpec,
+    ) -> Result<(), Error> {
+        BlockSignatureVerifier::verify_entire_block_with_cache(
+            state,
+            block,
+            &PubkeyAggregateCache::default(),
+            spec,
+        )
+    }
+
+    /// Equivalent to `verify_entire_block`, but takes the aggregate public keys of attestations
+    /// and the public keys of deposits from `cache`, adding any which are missing.
+    ///
+    /// The committees of epochs before the previous epoch of `state` are pruned from `cache`.
+    pub fn verify_entire_block_with_cache(
+        state: &'a BeaconState<T>,
+        block: &'a BeaconBlock<T>,
+        cache: &'c PubkeyAggregateCache,
+        spec: &'a ChainSpec,
+    ) -> Result<(), Error> {
+        cache.prune(state.previous_epoch());
+
+        let mut verifier = Self {
+            state,
+            spec,
+            cache,
+            sets: vec![],
+        };
+
+        verifier.include_block_proposal(block)?;
+        verifier.include_randao_reveal(block)?;
+        verifier.include_proposer_slashings(block)?;
+        verifier.include_attester_slashings(block)?;
+        verifier.include_attestations(block)?;
+        verifier.include_deposits(block)?;
+        verifier.include_exits(block)?;
+        verifier.include_transfers(block)?;
+
+        if verify_signature_sets(&verifier.sets) {
+            Ok(())
+        } else {
+            Err(Error::SignatureInvalid)
+        }
+    }
+
+    fn validator_pubkey(&self, validator_index: u64) -> Result<Cow<'a, PublicKey>, Error> {
+        self.state
+            .validators
+            .get(validator_index as usize)
+            .map(|validator| Cow::Borrowed(&validator.pubkey))
+            .ok_or_else(|| Error::ValidatorUnknown(validator_index))
+    }
+
+    fn proposer_pubkey(&self, slot: Slot) -> Result<Cow<'a, PublicKey>, Error> {
+        let proposer_index =
+            self.state
+                .get_beacon_proposer_index(slot, RelativeEpoch::Current, self.spec)?;
+        self.validator_pubkey(proposer_index as u64)
+    }
+
+    fn include_single(
+        &mut self,
+        signature: &'a Signature,
+        message: Vec<u8>,
+        domain: u64,
+        pubkey: Cow<'a, PublicKey>,
+    ) {
+        self.sets.push(SignatureSet::Single {
+            signature: Cow::Borrowed(signature),
+            message,
+            domain,
+            pubkey,
+        });
+    }
+
+    /// Spec v0.8.0
+    fn include_block_proposal(&mut self, block: &'a BeaconBlock<T>) -> Result<(), Error> {
+        let domain = self.spec.get_domain(
+            block.slot.epoch(T::slots_per_epoch()),
+            Domain::BeaconProposer,
+            &self.state.fork,
+        );
+        let pubkey = self.proposer_pubkey(block.slot)?;
+        self.include_single(&block.signature, block.signed_root(), domain, pubkey);
+        Ok(())
+    }
+
+    /// Spec v0.8.0
+    fn include_randao_reveal(&mut self, block: &'a BeaconBlock<T>) -> Result<(), Error> {
+        let domain = self.spec.get_domain(
+            block.slot.epoch(T::slots_per_epoch()),
+            Domain::Randao,
+            &self.state.fork,
+        );
+        let message = self.state.current_epoch().tree_hash_root();
+        let pubkey = self.proposer_pubkey(block.slot)?;
+        self.include_single(&block.body.randao_reveal, message, domain, pubkey);
+        Ok(())
+    }
+
+    /// Spec v0.8.0
+    fn include_proposer_slashings(&mut self, block: &'a BeaconBlock<T>) -> Result<(), Error> {
+        for proposer_slashing in block.body.proposer_slashings.iter() {
+            for header in &[&proposer_slashing.header_1, &proposer_slashing.header_2] {
+                let domain = self.spec.get_domain(
+                    header.slot.epoch(T::slots_per_epoch()),
+                    Domain::BeaconProposer,
+                    &self.state.fork,
+                );
+                let pubkey = self.validator_pubkey(proposer_slashing.proposer_index)?;
+                self.include_single(&header.signature, header.signed_root(), domain, pubkey);
+            }
+        }
+        Ok(())
+    }
+
+    /// Spec v0.8.0
+    fn include_attester_slashings(&mut self, block: &'a BeaconBlock<T>) -> Result<(), Error> {
+        for attester_slashing in block.body.attester_slashings.iter() {
+            for indexed_attestation in &[
+                &attester_slashing.attestation_1,
+                &attester_slashing.attestation_2,
+            ] {
+                self.include_indexed_attestation(
+                    &indexed_attestation.signature,
+                    &indexed_attestation.data,
+                    &indexed_attestation.custody_bit_0_indices,
+                    &indexed_attestation.custody_bit_1_indices,
+                    false,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Spec v0.8.0
+    fn include_attestations(&mut self, block: &'a BeaconBlock<T>) -> Result<(), Error> {
+        for attestation in block.body.attestations.iter() {
+            let indexed_attestation = get_indexed_attestation(self.state, attestation)
+                .map_err(|_| Error::AttestationInvalid)?;
+            self.include_indexed_attestation(
+                &attestation.signature,
+                &attestation.data,
+                &indexed_attestation.custody_bit_0_indices,
+                &indexed_attestation.custody_bit_1_indices,
+                true,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// The aggregate public keys of attestations `in_committee` (i.e., whose attesters are members
+    /// of the committee of `data`, as those of the attestations of a block are) are taken from the
+    /// cache. Those of attester slashings, which may be of any committee, are aggregated in full.
+    ///
+    /// Spec v0.8.0
+    fn include_indexed_attestation(
+        &mut self,
+        signature: &'a AggregateSignature,
+        data: &AttestationData,
+        custody_bit_0_indices: &[u64],
+        custody_bit_1_indices: &[u64],
+        in_committee: bool,
+    ) -> Result<(), Error> {
+        let mut messages = Vec::with_capacity(2);
+        let mut pubkeys = Vec::with_capacity(2);
+
+        for (custody_bit, indices) in &[
+            (false, custody_bit_0_indices),
+            (true, custody_bit_1_indices),
+        ] {
+            let cached = if in_committee {
+                self.cache.committee_aggregate(
+                    self.state,
+                    data.target.epoch,
+                    data.crosslink.shard,
+                    indices,
+                )?
+            } else {
+                None
+            };
+            let pubkey = match cached {
+                Some(pubkey) => pubkey,
+                None => {
+                    let mut pubkey = AggregatePublicKey::new();
+                    for &validator_index in indices.iter() {
+                        pubkey.add(&self.validator_pubkey(validator_index)?);
+                    }
+                    pubkey
+                }
+            };
+            pubkeys.push(pubkey);
+
+            messages.push(
+                AttestationDataAndCustodyBit {
+                    data: data.clone(),
+                    custody_bit: *custody_bit,
+                }
+                .tree_hash_root(),
+            );
+        }
+
+        let domain = self
+            .spec
+            .get_domain(data.target.epoch, Domain::Attestation, &self.state.fork);
+
+        self.sets.push(SignatureSet::Multiple {
+            signature,
+            messages,
+            domain,
+            pubkeys,
+        });
+        Ok(())
+    }
+
+    /// Only deposits for new validators are included, since the signatures of top-up deposits
+    /// are not verified.
+    ///
+    /// Spec v0.8.0
+    fn include_deposits(&mut self, block: &'a BeaconBlock<T>) -> Result<(), Error> {
+        // Deposits are valid across forks, thus the deposit domain is computed with the fork
+        // zeroed.
+        let domain = self.spec.get_domain(
+            self.state.current_epoch(),
+            Domain::Deposit,
+            &Fork::default(),
+        );
+
+        for deposit in block.body.deposits.iter() {
+            // A deposit with an invalid public key is ignored, so there is nothing to verify.
+            let pubkey = match self.cache.deposit_pubkey(&deposit.data.pubkey) {
+                Some(pubkey) => pubkey,
+                None => continue,
+            };
+
+            if self.state.get_validator_index(&pubkey)?.is_some() {
+                continue;
+            }
+
+            let signature: Signature = (&deposit.data.signature)
+                .try_into()
+                .map_err(|_| Error::BadDepositSignatureBytes)?;
+
+            self.sets.push(SignatureSet::Single {
+                signature: Cow::Owned(signature),
+                message: deposit.data.signed_root(),
+                domain,
+                pubkey: Cow::Owned(pubkey),
+            });
+        }
+        Ok(())
+    }
+
+    /// Spec v0.8.0
+    fn include_exits(&mut self, block: &'a BeaconBlock<T>) -> Result<(), Error> {
+        for exit in block.body.voluntary_exits.iter() {
+            let domain = self
+                .spec
+                .get_domain(exit.epoch, Domain::VoluntaryExit, &self.state.fork);
+            let pubkey = self.validator_pubkey(exit.validator_index)?;
+            self.include_single(&exit.signature, exit.signed_root(), domain, pubkey);
+        }
+        Ok(())
+    }
+
+    /// Spec v0.8.0
+    fn include_transfers(&mut self, block: &'a BeaconBlock<T>) -> Result<(), Error> {
+        for transfer in block.body.transfers.iter() {
+            let domain = self.spec.get_domain(
+                transfer.slot.epoch(T::slots_per_epoch()),
+                Domain::Transfer,
+                &self.state.fork,
+            );
+            self.include_single(
+                &transfer.signature,
+                transfer.signed_root(),
+                domain,
+                Cow::Borrowed(&transfer.pubkey),
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,83 @@
+use super::errors::{BlockInvalid as Invalid, BlockProcessingError as Error};
+use tree_hash::SignedRoot;
+use types::*;
+
+/// The proposer of each slot in an epoch, along with the proposer signing domain of the epoch.
+///
+/// Once built from a state, the signatures of blocks in the epoch can be verified without a
+/// `BeaconState` or its committee caches (e.g., whilst verifying gossip blocks).
+///
+/// Proposers are only valid for blocks which share the shuffling and effective balances of the
+/// state from which they were built, i.e., blocks descending from the same block at the end of the
+/// previous epoch. It is the caller's responsibility to ensure this.
+#[derive(Debug, PartialEq, Clone)]
+pub struct EpochProposers {
+    epoch: Epoch,
+    /// The `Domain::BeaconProposer` signing domain of `epoch`.
+    domain: u64,
+    /// The validator index and public key of the proposer at each slot of `epoch`.
+    proposers: Vec<(usize, PublicKey)>,
+}
+
+impl EpochProposers {
+    /// Build the proposers of the current epoch of `state`.
+    ///
+    /// Requires the current epoch committee cache of `state` to be built.
+    pub fn new<T: EthSpec>(state: &BeaconState<T>, spec: &ChainSpec) -> Result<Self, Error> {
+        let epoch = state.current_epoch();
+
+        let proposers = epoch
+            .slot_iter(T::slots_per_epoch())
+            .map(|slot| {
+                let index = state.get_beacon_proposer_index(slot, RelativeEpoch::Current, spec)?;
+                let validator = state
+                    .validators
+                    .get(index)
+                    .ok_or_else(|| BeaconStateError::UnknownValidator)?;
+                Ok((index, validator.pubkey.clone()))
+            })
+            .collect::<Result<_, Error>>()?;
+
+        Ok(Self {
+            epoch,
+            domain: spec.get_domain(epoch, Domain::BeaconProposer, &state.fork),
+            proposers,
+        })
+    }
+
+    /// The epoch of the proposers.
+    pub fn epoch(&self) -> Epoch {
+        self.epoch
+    }
+
+    /// The validator index and public key of the proposer at `slot`, if `slot` is in the epoch.
+    pub fn proposer<T: EthSpec>(&self, slot: Slot) -> Option<&(usize, PublicKey)> {
+        if slot.epoch(T::slots_per_epoch()) != self.epoch {
+            return None;
+        }
+        self.proposers
+            .get((slot - self.epoch.start_slot(T::slots_per_epoch())).as_usize())
+    }
+}
+
+/// Verifies the signature of `block` against the expected proposer in `proposers`.
+///
+/// Equivalent to `verify_block_signature`, but does not require a `BeaconState`. Returns
+/// `BeaconStateError::SlotOutOfBounds` if the block is not in the epoch of `proposers`.
+pub fn verify_block_signature_with_proposers<T: EthSpec>(
+    block: &BeaconBlock<T>,
+    proposers: &EpochProposers,
+) -> Result<(), Error> {
+    let (_, pubkey) = proposers
+        .proposer::<T>(block.slot)
+        .ok_or_else(|| BeaconStateError::SlotOutOfBounds)?;
+
+    verify!(
+        block
+            .signature
+            .verify(&block.signed_root()[..], proposers.domain, pubkey),
+        Invalid::BadSignature
+    );
+
+    Ok(())
+}
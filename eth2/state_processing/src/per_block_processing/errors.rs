@@ -1,3 +1,4 @@
+use thiserror::Error;
 use types::*;
 
 macro_rules! impl_from_beacon_state_error {
@@ -53,13 +54,16 @@ pub trait IntoWithIndex<T>: Sized {
  */
 
 /// The object is invalid or validation failed.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Error)]
 pub enum BlockProcessingError {
     /// Validation completed successfully and the object is invalid.
+    #[error("invalid block: {0:?}")]
     Invalid(BlockInvalid),
     /// Encountered a `BeaconStateError` whilst attempting to determine validity.
+    #[error("beacon state error: {0:?}")]
     BeaconStateError(BeaconStateError),
     /// Encountered an `ssz_types::Error` whilst attempting to determine validity.
+    #[error("ssz types error: {0:?}")]
     SszTypesError(ssz_types::Error),
 }
 
@@ -2,6 +2,11 @@
 use super::block_processing_builder::BlockProcessingBuilder;
 use super::errors::*;
 use crate::per_block_processing;
+use crate::per_block_processing::{
+    per_block_processing_with_cache, per_block_processing_with_strategy,
+    verify_block_signature_with_proposers, BlockSignatureStrategy, EpochProposers,
+};
+use crate::PubkeyAggregateCache;
 use tree_hash::SignedRoot;
 use types::*;
 
@@ -80,6 +85,40 @@ fn invalid_block_signature() {
     );
 }
 
+#[test]
+fn block_signature_with_proposers() {
+    let spec = MainnetEthSpec::default_spec();
+    let builder = get_builder(&spec);
+    let (mut block, state) = builder.build(None, None, &spec);
+    let proposers = EpochProposers::new(&state, &spec).expect("should build proposers");
+
+    assert_eq!(
+        verify_block_signature_with_proposers(&block, &proposers),
+        Ok(())
+    );
+
+    // sign the block with a keypair that is not the expected proposer
+    let keypair = Keypair::random();
+    let message = block.signed_root();
+    let epoch = block.slot.epoch(MainnetEthSpec::slots_per_epoch());
+    let domain = spec.get_domain(epoch, Domain::BeaconProposer, &state.fork);
+    block.signature = Signature::new(&message, domain, &keypair.sk);
+
+    assert_eq!(
+        verify_block_signature_with_proposers(&block, &proposers),
+        Err(BlockProcessingError::Invalid(BlockInvalid::BadSignature))
+    );
+
+    // blocks outside of the epoch of the proposers cannot be verified
+    block.slot += MainnetEthSpec::slots_per_epoch();
+    assert_eq!(
+        verify_block_signature_with_proposers(&block, &proposers),
+        Err(BlockProcessingError::BeaconStateError(
+            BeaconStateError::SlotOutOfBounds
+        ))
+    );
+}
+
 #[test]
 fn invalid_randao_reveal_signature() {
     let spec = MainnetEthSpec::default_spec();
@@ -100,6 +139,49 @@ fn invalid_randao_reveal_signature() {
     );
 }
 
+#[test]
+fn bulk_signature_verification_matches_individual() {
+    let spec = MainnetEthSpec::default_spec();
+
+    let (valid_block, state) = get_builder(&spec).build(None, None, &spec);
+    let (bad_randao_block, _) = get_builder(&spec).build(Some(Keypair::random().sk), None, &spec);
+    let cache = PubkeyAggregateCache::new();
+
+    for block in &[valid_block, bad_randao_block] {
+        let mut individual_state = state.clone();
+        let individual = per_block_processing_with_strategy(
+            &mut individual_state,
+            block,
+            BlockSignatureStrategy::VerifyIndividual,
+            &spec,
+        );
+
+        let mut bulk_state = state.clone();
+        let bulk = per_block_processing_with_strategy(
+            &mut bulk_state,
+            block,
+            BlockSignatureStrategy::VerifyBulk,
+            &spec,
+        );
+
+        let mut cached_state = state.clone();
+        let cached = per_block_processing_with_cache(&mut cached_state, block, &cache, &spec);
+
+        assert_eq!(bulk, individual);
+        assert_eq!(cached, individual);
+        if bulk.is_ok() {
+            assert_eq!(
+                bulk_state.canonical_root(),
+                individual_state.canonical_root()
+            );
+            assert_eq!(
+                cached_state.canonical_root(),
+                individual_state.canonical_root()
+            );
+        }
+    }
+}
+
 fn get_builder(spec: &ChainSpec) -> (BlockProcessingBuilder<MainnetEthSpec>) {
     let mut builder = BlockProcessingBuilder::new(VALIDATOR_COUNT, &spec);
 
@@ -13,7 +13,7 @@ pub fn verify_exit<T: EthSpec>(
     exit: &VoluntaryExit,
     spec: &ChainSpec,
 ) -> Result<(), Error> {
-    verify_exit_parametric(state, exit, spec, false)
+    verify_exit_parametric(state, exit, spec, false, true)
 }
 
 /// Like `verify_exit` but doesn't run checks which may become true in future states.
@@ -24,10 +24,22 @@ pub fn verify_exit_time_independent_only<T: EthSpec>(
     exit: &VoluntaryExit,
     spec: &ChainSpec,
 ) -> Result<(), Error> {
-    verify_exit_parametric(state, exit, spec, true)
+    verify_exit_parametric(state, exit, spec, true, true)
 }
 
-/// Parametric version of `verify_exit` that skips some checks if `time_independent_only` is true.
+/// Like `verify_exit` but doesn't verify the signature.
+///
+/// Spec v0.8.0
+pub fn verify_exit_without_signature<T: EthSpec>(
+    state: &BeaconState<T>,
+    exit: &VoluntaryExit,
+    spec: &ChainSpec,
+) -> Result<(), Error> {
+    verify_exit_parametric(state, exit, spec, false, false)
+}
+
+/// Parametric version of `verify_exit` that skips some checks if `time_independent_only` is true,
+/// and the signature if `verify_signature` is false.
 ///
 /// Spec v0.8.0
 fn verify_exit_parametric<T: EthSpec>(
@@ -35,6 +47,7 @@ fn verify_exit_parametric<T: EthSpec>(
     exit: &VoluntaryExit,
     spec: &ChainSpec,
     time_independent_only: bool,
+    verify_signature: bool,
 ) -> Result<(), Error> {
     let validator = state
         .validators
@@ -72,13 +85,15 @@ fn verify_exit_parametric<T: EthSpec>(
     );
 
     // Verify signature.
-    let message = exit.signed_root();
-    let domain = spec.get_domain(exit.epoch, Domain::VoluntaryExit, &state.fork);
-    verify!(
-        exit.signature
-            .verify(&message[..], domain, &validator.pubkey),
-        Invalid::BadSignature
-    );
+    if verify_signature {
+        let message = exit.signed_root();
+        let domain = spec.get_domain(exit.epoch, Domain::VoluntaryExit, &state.fork);
+        verify!(
+            exit.signature
+                .verify(&message[..], domain, &validator.pubkey),
+            Invalid::BadSignature
+        );
+    }
 
     Ok(())
 }
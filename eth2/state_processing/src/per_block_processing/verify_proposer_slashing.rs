@@ -12,6 +12,30 @@ pub fn verify_proposer_slashing<T: EthSpec>(
     proposer_slashing: &ProposerSlashing,
     state: &BeaconState<T>,
     spec: &ChainSpec,
+) -> Result<(), Error> {
+    verify_proposer_slashing_parametric(proposer_slashing, state, spec, true)
+}
+
+/// Like `verify_proposer_slashing` but doesn't verify the signatures of the proposals.
+///
+/// Spec v0.8.0
+pub fn verify_proposer_slashing_without_signatures<T: EthSpec>(
+    proposer_slashing: &ProposerSlashing,
+    state: &BeaconState<T>,
+    spec: &ChainSpec,
+) -> Result<(), Error> {
+    verify_proposer_slashing_parametric(proposer_slashing, state, spec, false)
+}
+
+/// Parametric version of `verify_proposer_slashing` that skips the proposal signatures if
+/// `verify_signatures` is false.
+///
+/// Spec v0.8.0
+fn verify_proposer_slashing_parametric<T: EthSpec>(
+    proposer_slashing: &ProposerSlashing,
+    state: &BeaconState<T>,
+    spec: &ChainSpec,
+    verify_signatures: bool,
 ) -> Result<(), Error> {
     let proposer = state
         .validators
@@ -42,24 +66,26 @@ pub fn verify_proposer_slashing<T: EthSpec>(
         Invalid::ProposerNotSlashable(proposer_slashing.proposer_index)
     );
 
-    verify!(
-        verify_header_signature::<T>(
-            &proposer_slashing.header_1,
-            &proposer.pubkey,
-            &state.fork,
-            spec
-        ),
-        Invalid::BadProposal1Signature
-    );
-    verify!(
-        verify_header_signature::<T>(
-            &proposer_slashing.header_2,
-            &proposer.pubkey,
-            &state.fork,
-            spec
-        ),
-        Invalid::BadProposal2Signature
-    );
+    if verify_signatures {
+        verify!(
+            verify_header_signature::<T>(
+                &proposer_slashing.header_1,
+                &proposer.pubkey,
+                &state.fork,
+                spec
+            ),
+            Invalid::BadProposal1Signature
+        );
+        verify!(
+            verify_header_signature::<T>(
+                &proposer_slashing.header_2,
+                &proposer.pubkey,
+                &state.fork,
+                spec
+            ),
+            Invalid::BadProposal2Signature
+        );
+    }
 
     Ok(())
 }
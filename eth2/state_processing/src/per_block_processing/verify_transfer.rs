@@ -14,7 +14,7 @@ pub fn verify_transfer<T: EthSpec>(
     transfer: &Transfer,
     spec: &ChainSpec,
 ) -> Result<(), Error> {
-    verify_transfer_parametric(state, transfer, spec, false)
+    verify_transfer_parametric(state, transfer, spec, false, true)
 }
 
 /// Like `verify_transfer` but doesn't run checks which may become true in future states.
@@ -25,7 +25,18 @@ pub fn verify_transfer_time_independent_only<T: EthSpec>(
     transfer: &Transfer,
     spec: &ChainSpec,
 ) -> Result<(), Error> {
-    verify_transfer_parametric(state, transfer, spec, true)
+    verify_transfer_parametric(state, transfer, spec, true, true)
+}
+
+/// Like `verify_transfer` but doesn't verify the signature.
+///
+/// Spec v0.8.0
+pub fn verify_transfer_without_signature<T: EthSpec>(
+    state: &BeaconState<T>,
+    transfer: &Transfer,
+    spec: &ChainSpec,
+) -> Result<(), Error> {
+    verify_transfer_parametric(state, transfer, spec, false, false)
 }
 
 /// Parametric version of `verify_transfer` that allows some checks to be skipped.
@@ -37,12 +48,15 @@ pub fn verify_transfer_time_independent_only<T: EthSpec>(
 ///     present or future.
 /// - Validator transfer eligibility (e.g., is withdrawable)
 ///
+/// When `verify_signature == false`, the transfer signature is not verified.
+///
 /// Spec v0.8.0
 fn verify_transfer_parametric<T: EthSpec>(
     state: &BeaconState<T>,
     transfer: &Transfer,
     spec: &ChainSpec,
     time_independent_only: bool,
+    verify_signature: bool,
 ) -> Result<(), Error> {
     let sender_balance = *state
         .balances
@@ -132,18 +146,20 @@ fn verify_transfer_parametric<T: EthSpec>(
     );
 
     // Verify the transfer signature.
-    let message = transfer.signed_root();
-    let domain = spec.get_domain(
-        transfer.slot.epoch(T::slots_per_epoch()),
-        Domain::Transfer,
-        &state.fork,
-    );
-    verify!(
-        transfer
-            .signature
-            .verify(&message[..], domain, &transfer.pubkey),
-        Invalid::BadSignature
-    );
+    if verify_signature {
+        let message = transfer.signed_root();
+        let domain = spec.get_domain(
+            transfer.slot.epoch(T::slots_per_epoch()),
+            Domain::Transfer,
+            &state.fork,
+        );
+        verify!(
+            transfer
+                .signature
+                .verify(&message[..], domain, &transfer.pubkey),
+            Invalid::BadSignature
+        );
+    }
 
     Ok(())
 }
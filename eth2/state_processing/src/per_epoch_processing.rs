@@ -1,8 +1,6 @@
 use crate::common::get_compact_committees_root;
-use apply_rewards::process_rewards_and_penalties;
 use errors::EpochProcessingError as Error;
-use process_slashings::process_slashings;
-use registry_updates::process_registry_updates;
+use single_pass::process_validators;
 use std::collections::HashMap;
 use tree_hash::TreeHash;
 use types::*;
@@ -13,6 +11,7 @@ pub mod apply_rewards;
 pub mod errors;
 pub mod process_slashings;
 pub mod registry_updates;
+pub mod single_pass;
 pub mod tests;
 pub mod validator_statuses;
 pub mod winning_root;
@@ -37,34 +36,66 @@ pub fn per_epoch_processing<T: EthSpec>(
     state.build_committee_cache(RelativeEpoch::Current, spec)?;
     state.build_committee_cache(RelativeEpoch::Next, spec)?;
 
+    process_epoch(state, spec)
+}
+
+/// Performs per-epoch processing on some BeaconState, as per `per_epoch_processing`, using
+/// `validator_statuses` which have been updated incrementally during the epoch.
+///
+/// The `validator_statuses` are brought up to date with `state` before processing, so only the
+/// attestations included since their last `ValidatorStatuses::update` are processed.
+///
+/// Spec v0.8.0
+pub fn per_epoch_processing_with_validator_statuses<T: EthSpec>(
+    state: &mut BeaconState<T>,
+    mut validator_statuses: ValidatorStatuses,
+    spec: &ChainSpec,
+) -> Result<(), Error> {
+    // Ensure the committee caches are built.
+    state.build_committee_cache(RelativeEpoch::Previous, spec)?;
+    state.build_committee_cache(RelativeEpoch::Current, spec)?;
+    state.build_committee_cache(RelativeEpoch::Next, spec)?;
+
+    validator_statuses.update(state, spec)?;
+
+    process_epoch_with_validator_statuses(state, validator_statuses, spec)
+}
+
+/// Performs per-epoch processing on a state with all committee caches built.
+fn process_epoch<T: EthSpec>(state: &mut BeaconState<T>, spec: &ChainSpec) -> Result<(), Error> {
     // Load the struct we use to assign validators into sets based on their participation.
     //
     // E.g., attestation in the previous epoch, attested to the head, etc.
     let mut validator_statuses = ValidatorStatuses::new(state, spec)?;
     validator_statuses.process_attestations(&state, spec)?;
 
+    process_epoch_with_validator_statuses(state, validator_statuses, spec)
+}
+
+/// Performs per-epoch processing on a state with all committee caches built, given the
+/// `validator_statuses` of the state.
+fn process_epoch_with_validator_statuses<T: EthSpec>(
+    state: &mut BeaconState<T>,
+    mut validator_statuses: ValidatorStatuses,
+    spec: &ChainSpec,
+) -> Result<(), Error> {
     // Justification and finalization.
     process_justification_and_finalization(state, &validator_statuses.total_balances)?;
 
     // Crosslinks.
     let winning_root_for_shards = process_crosslinks(state, spec)?;
 
-    // Rewards and Penalties.
-    process_rewards_and_penalties(
+    // Rewards and penalties, registry updates, slashings and effective balance updates, in a
+    // single pass over the validators.
+    process_validators(
         state,
         &mut validator_statuses,
         &winning_root_for_shards,
         spec,
     )?;
 
-    // Registry Updates.
-    process_registry_updates(state, spec)?;
-
-    // Slashings.
-    process_slashings(state, validator_statuses.total_balances.current_epoch, spec)?;
-
-    // Final updates.
-    process_final_updates(state, spec)?;
+    // The rest of the final updates.
+    process_final_updates_except_effective_balances(state, spec)?;
 
     // Rotate the epoch caches to suit the epoch transition.
     state.advance_caches();
@@ -195,6 +226,55 @@ pub fn process_crosslinks<T: EthSpec>(
 pub fn process_final_updates<T: EthSpec>(
     state: &mut BeaconState<T>,
     spec: &ChainSpec,
+) -> Result<(), Error> {
+    process_effective_balance_updates(state, spec);
+    process_final_updates_except_effective_balances(state, spec)
+}
+
+/// Update effective balances with hysteresis (lag), as part of the final updates.
+///
+/// Validators are only borrowed mutably if their effective balance changes, so that the
+/// validators are not copied from states which share them.
+///
+/// Spec v0.8.0
+pub fn process_effective_balance_updates<T: EthSpec>(state: &mut BeaconState<T>, spec: &ChainSpec) {
+    for index in 0..state.validators.len() {
+        let effective_balance = state.validators[index].effective_balance;
+        if let Some(effective_balance) =
+            updated_effective_balance(effective_balance, state.balances[index], spec)
+        {
+            state.validators[index].effective_balance = effective_balance;
+        }
+    }
+}
+
+/// Returns the effective balance of a validator with `effective_balance` and `balance`, updated
+/// with hysteresis (lag), or `None` if it is unchanged.
+///
+/// Spec v0.8.0
+pub fn updated_effective_balance(
+    effective_balance: u64,
+    balance: u64,
+    spec: &ChainSpec,
+) -> Option<u64> {
+    let half_increment = spec.effective_balance_increment / 2;
+    if balance < effective_balance || effective_balance + 3 * half_increment < balance {
+        Some(std::cmp::min(
+            balance - balance % spec.effective_balance_increment,
+            spec.max_effective_balance,
+        ))
+    } else {
+        None
+    }
+}
+
+/// Finish up an epoch update, as per `process_final_updates`, with the effective balances already
+/// updated.
+///
+/// Spec v0.8.0
+fn process_final_updates_except_effective_balances<T: EthSpec>(
+    state: &mut BeaconState<T>,
+    spec: &ChainSpec,
 ) -> Result<(), Error> {
     let current_epoch = state.current_epoch();
     let next_epoch = state.next_epoch();
@@ -204,20 +284,6 @@ pub fn process_final_updates<T: EthSpec>(
         state.eth1_data_votes = VariableList::empty();
     }
 
-    // Update effective balances with hysteresis (lag).
-    for (index, validator) in state.validators.iter_mut().enumerate() {
-        let balance = state.balances[index];
-        let half_increment = spec.effective_balance_increment / 2;
-        if balance < validator.effective_balance
-            || validator.effective_balance + 3 * half_increment < balance
-        {
-            validator.effective_balance = std::cmp::min(
-                balance - balance % spec.effective_balance_increment,
-                spec.max_effective_balance,
-            );
-        }
-    }
-
     // Update start shard.
     state.start_shard = state.next_epoch_start_shard(spec)?;
 
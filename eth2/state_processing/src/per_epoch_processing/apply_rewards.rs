@@ -1,10 +1,11 @@
 use super::validator_statuses::{TotalBalances, ValidatorStatus, ValidatorStatuses};
 use super::{Error, WinningRootHashSet};
 use integer_sqrt::IntegerSquareRoot;
+use rayon::prelude::*;
 use types::*;
 
 /// Use to track the changes to a validators balance.
-#[derive(Default, Clone)]
+#[derive(Debug, PartialEq, Default, Clone)]
 pub struct Delta {
     rewards: u64,
     penalties: u64,
@@ -20,6 +21,16 @@ impl Delta {
     pub fn penalize(&mut self, penalty: u64) {
         self.penalties += penalty;
     }
+
+    /// The sum of all rewards.
+    pub fn rewards(&self) -> u64 {
+        self.rewards
+    }
+
+    /// The sum of all penalties.
+    pub fn penalties(&self) -> u64 {
+        self.penalties
+    }
 }
 
 impl std::ops::AddAssign for Delta {
@@ -30,8 +41,39 @@ impl std::ops::AddAssign for Delta {
     }
 }
 
+/// The changes to a validators balance from each component of the attestation rewards and
+/// penalties.
+#[derive(Debug, PartialEq, Default, Clone)]
+pub struct AttestationDelta {
+    /// For attesting (or not) to the expected FFG source.
+    pub source: Delta,
+    /// For attesting (or not) to the expected FFG target.
+    pub target: Delta,
+    /// For attesting (or not) to the expected head.
+    pub head: Delta,
+    /// For the speed with which an attestation was included, and the proposer reward for
+    /// including attestations.
+    pub inclusion_delay: Delta,
+    /// For failing to finalize.
+    pub inactivity: Delta,
+}
+
+impl AttestationDelta {
+    /// Returns the sum of every component.
+    pub fn total(&self) -> Delta {
+        let mut total = self.source.clone();
+        total += self.target.clone();
+        total += self.head.clone();
+        total += self.inclusion_delay.clone();
+        total += self.inactivity.clone();
+        total
+    }
+}
+
 /// Apply attester and proposer rewards.
 ///
+/// The deltas of each validator are computed across threads if `validator_statuses.is_parallel()`.
+///
 /// Spec v0.8.0
 pub fn process_rewards_and_penalties<T: EthSpec>(
     state: &mut BeaconState<T>,
@@ -50,23 +92,20 @@ pub fn process_rewards_and_penalties<T: EthSpec>(
         return Err(Error::ValidatorStatusesInconsistent);
     }
 
+    // Update statuses with the information from winning roots, without which every attester would
+    // be penalized by the crosslink deltas.
+    validator_statuses.process_winning_roots(state, winning_root_for_shards, spec)?;
+
     let mut deltas = vec![Delta::default(); state.balances.len()];
 
     get_attestation_deltas(&mut deltas, state, &validator_statuses, spec)?;
     get_crosslink_deltas(&mut deltas, state, &validator_statuses, spec)?;
-
-    get_proposer_deltas(
-        &mut deltas,
-        state,
-        validator_statuses,
-        winning_root_for_shards,
-        spec,
-    )?;
+    get_proposer_deltas(&mut deltas, state, validator_statuses, spec)?;
 
     // Apply the deltas, over-flowing but not under-flowing (saturating at 0 instead).
-    for (i, delta) in deltas.iter().enumerate() {
-        state.balances[i] += delta.rewards;
-        state.balances[i] = state.balances[i].saturating_sub(delta.penalties);
+    for (balance, delta) in state.balances.iter_mut().zip(deltas.iter()) {
+        *balance += delta.rewards;
+        *balance = balance.saturating_sub(delta.penalties);
     }
 
     Ok(())
@@ -78,13 +117,29 @@ pub fn process_rewards_and_penalties<T: EthSpec>(
 fn get_proposer_deltas<T: EthSpec>(
     deltas: &mut Vec<Delta>,
     state: &BeaconState<T>,
-    validator_statuses: &mut ValidatorStatuses,
-    winning_root_for_shards: &WinningRootHashSet,
+    validator_statuses: &ValidatorStatuses,
     spec: &ChainSpec,
 ) -> Result<(), Error> {
-    // Update statuses with the information from winning roots.
-    validator_statuses.process_winning_roots(state, winning_root_for_shards, spec)?;
+    for_each_proposer_reward(state, validator_statuses, spec, |proposer_index, reward| {
+        deltas
+            .get_mut(proposer_index)
+            .ok_or(Error::ValidatorStatusesInconsistent)?
+            .reward(reward);
+        Ok(())
+    })
+}
 
+/// Calls `f` with the proposer index and proposer reward of each attestation by an attester of
+/// the previous epoch.
+pub fn for_each_proposer_reward<T: EthSpec, F>(
+    state: &BeaconState<T>,
+    validator_statuses: &ValidatorStatuses,
+    spec: &ChainSpec,
+    mut f: F,
+) -> Result<(), Error>
+where
+    F: FnMut(usize, u64) -> Result<(), Error>,
+{
     for (index, validator) in validator_statuses.statuses.iter().enumerate() {
         if validator.is_previous_epoch_attester {
             let inclusion = validator
@@ -98,11 +153,10 @@ fn get_proposer_deltas<T: EthSpec>(
                 spec,
             )?;
 
-            if inclusion.proposer_index >= deltas.len() {
-                return Err(Error::ValidatorStatusesInconsistent);
-            }
-
-            deltas[inclusion.proposer_index].reward(base_reward / spec.proposer_reward_quotient);
+            f(
+                inclusion.proposer_index,
+                base_reward / spec.proposer_reward_quotient,
+            )?;
         }
     }
 
@@ -120,39 +174,95 @@ fn get_attestation_deltas<T: EthSpec>(
 ) -> Result<(), Error> {
     let finality_delay = (state.previous_epoch() - state.finalized_checkpoint.epoch).as_u64();
 
-    for (index, validator) in validator_statuses.statuses.iter().enumerate() {
-        let base_reward = get_base_reward(
-            state,
-            index,
-            validator_statuses.total_balances.current_epoch,
-            spec,
-        )?;
-
-        let delta = get_attestation_delta::<T>(
-            &validator,
-            &validator_statuses.total_balances,
-            base_reward,
-            finality_delay,
-            spec,
-        );
+    for_each_delta(
+        deltas,
+        validator_statuses,
+        |index, validator, validator_deltas| {
+            let base_reward = get_base_reward(
+                state,
+                index,
+                validator_statuses.total_balances.current_epoch,
+                spec,
+            )?;
+
+            let delta = get_attestation_delta::<T>(
+                &validator,
+                &validator_statuses.total_balances,
+                base_reward,
+                finality_delay,
+                spec,
+            );
 
-        deltas[index] += delta;
+            *validator_deltas += delta.total();
+            Ok(())
+        },
+    )
+}
+
+/// Returns the deltas of every validator from each component of the attestation rewards and
+/// penalties of the previous epoch, including the proposer rewards for including attestations.
+///
+/// Unlike `process_rewards_and_penalties`, the crosslink deltas are excluded. Requires
+/// `validator_statuses` to have processed the attestations of `state`.
+///
+/// Spec v0.8.0
+pub fn get_attestation_component_deltas<T: EthSpec>(
+    state: &BeaconState<T>,
+    validator_statuses: &ValidatorStatuses,
+    spec: &ChainSpec,
+) -> Result<Vec<AttestationDelta>, Error> {
+    if validator_statuses.statuses.len() != state.validators.len() {
+        return Err(Error::ValidatorStatusesInconsistent);
     }
 
-    Ok(())
+    let finality_delay = (state.previous_epoch() - state.finalized_checkpoint.epoch).as_u64();
+
+    let mut deltas = vec![AttestationDelta::default(); state.validators.len()];
+    for_each_delta(
+        &mut deltas,
+        validator_statuses,
+        |index, validator, validator_deltas| {
+            let base_reward = get_base_reward(
+                state,
+                index,
+                validator_statuses.total_balances.current_epoch,
+                spec,
+            )?;
+
+            *validator_deltas = get_attestation_delta::<T>(
+                &validator,
+                &validator_statuses.total_balances,
+                base_reward,
+                finality_delay,
+                spec,
+            );
+            Ok(())
+        },
+    )?;
+
+    for_each_proposer_reward(state, validator_statuses, spec, |proposer_index, reward| {
+        deltas
+            .get_mut(proposer_index)
+            .ok_or(Error::ValidatorStatusesInconsistent)?
+            .inclusion_delay
+            .reward(reward);
+        Ok(())
+    })?;
+
+    Ok(deltas)
 }
 
-/// Determine the delta for a single validator, sans proposer rewards.
+/// Determine the delta for a single validator from each component, sans proposer rewards.
 ///
 /// Spec v0.8.0
-fn get_attestation_delta<T: EthSpec>(
+pub fn get_attestation_delta<T: EthSpec>(
     validator: &ValidatorStatus,
     total_balances: &TotalBalances,
     base_reward: u64,
     finality_delay: u64,
     spec: &ChainSpec,
-) -> Delta {
-    let mut delta = Delta::default();
+) -> AttestationDelta {
+    let mut delta = AttestationDelta::default();
 
     // Is this validator eligible to be rewarded or penalized?
     // Spec: validator index in `eligible_validator_indices`
@@ -172,49 +282,57 @@ fn get_attestation_delta<T: EthSpec>(
     // Spec:
     // - validator index in `get_unslashed_attesting_indices(state, matching_source_attestations)`
     if validator.is_previous_epoch_attester && !validator.is_slashed {
-        delta.reward(base_reward * total_attesting_balance / total_balance);
+        delta
+            .source
+            .reward(base_reward * total_attesting_balance / total_balance);
         // Inclusion speed bonus
         let proposer_reward = base_reward / spec.proposer_reward_quotient;
         let max_attester_reward = base_reward - proposer_reward;
         let inclusion = validator
             .inclusion_info
             .expect("It is a logic error for an attester not to have an inclusion distance.");
-        delta.reward(
+        delta.inclusion_delay.reward(
             max_attester_reward
                 * (T::SlotsPerEpoch::to_u64() + spec.min_attestation_inclusion_delay
                     - inclusion.distance)
                 / T::SlotsPerEpoch::to_u64(),
         );
     } else {
-        delta.penalize(base_reward);
+        delta.source.penalize(base_reward);
     }
 
     // Expected FFG target.
     // Spec:
     // - validator index in `get_unslashed_attesting_indices(state, matching_target_attestations)`
     if validator.is_previous_epoch_target_attester && !validator.is_slashed {
-        delta.reward(base_reward * matching_target_balance / total_balance);
+        delta
+            .target
+            .reward(base_reward * matching_target_balance / total_balance);
     } else {
-        delta.penalize(base_reward);
+        delta.target.penalize(base_reward);
     }
 
     // Expected head.
     // Spec:
     // - validator index in `get_unslashed_attesting_indices(state, matching_head_attestations)`
     if validator.is_previous_epoch_head_attester && !validator.is_slashed {
-        delta.reward(base_reward * matching_head_balance / total_balance);
+        delta
+            .head
+            .reward(base_reward * matching_head_balance / total_balance);
     } else {
-        delta.penalize(base_reward);
+        delta.head.penalize(base_reward);
     }
 
     // Inactivity penalty
     if finality_delay > spec.min_epochs_to_inactivity_penalty {
         // All eligible validators are penalized
-        delta.penalize(spec.base_rewards_per_epoch * base_reward);
+        delta
+            .inactivity
+            .penalize(spec.base_rewards_per_epoch * base_reward);
 
         // Additionally, all validators whose FFG target didn't match are penalized extra
         if !validator.is_previous_epoch_target_attester {
-            delta.penalize(
+            delta.inactivity.penalize(
                 validator.current_epoch_effective_balance * finality_delay
                     / spec.inactivity_penalty_quotient,
             );
@@ -238,29 +356,67 @@ fn get_crosslink_deltas<T: EthSpec>(
     validator_statuses: &ValidatorStatuses,
     spec: &ChainSpec,
 ) -> Result<(), Error> {
-    for (index, validator) in validator_statuses.statuses.iter().enumerate() {
-        let mut delta = Delta::default();
-
-        let base_reward = get_base_reward(
-            state,
-            index,
-            validator_statuses.total_balances.current_epoch,
-            spec,
-        )?;
-
-        if let Some(ref winning_root) = validator.winning_root_info {
-            delta.reward(
-                base_reward * winning_root.total_attesting_balance
-                    / winning_root.total_committee_balance,
-            );
-        } else {
-            delta.penalize(base_reward);
-        }
+    for_each_delta(
+        deltas,
+        validator_statuses,
+        |index, validator, validator_deltas| {
+            let base_reward = get_base_reward(
+                state,
+                index,
+                validator_statuses.total_balances.current_epoch,
+                spec,
+            )?;
 
-        deltas[index] += delta;
+            *validator_deltas += get_crosslink_delta(validator, base_reward);
+            Ok(())
+        },
+    )
+}
+
+/// Determine the crosslink delta for a single validator, which requires the winning roots to have
+/// been processed by `validator_statuses`.
+///
+/// Spec v0.8.0
+pub fn get_crosslink_delta(validator: &ValidatorStatus, base_reward: u64) -> Delta {
+    let mut delta = Delta::default();
+
+    if let Some(ref winning_root) = validator.winning_root_info {
+        delta.reward(
+            base_reward * winning_root.total_attesting_balance
+                / winning_root.total_committee_balance,
+        );
+    } else {
+        delta.penalize(base_reward);
     }
 
-    Ok(())
+    delta
+}
+
+/// Calls `f` with the index, status and deltas of each validator, across threads if
+/// `validator_statuses.is_parallel()`.
+fn for_each_delta<D, F>(
+    deltas: &mut [D],
+    validator_statuses: &ValidatorStatuses,
+    f: F,
+) -> Result<(), Error>
+where
+    D: Send,
+    F: Fn(usize, &ValidatorStatus, &mut D) -> Result<(), Error> + Sync + Send,
+{
+    let statuses = &validator_statuses.statuses;
+    if validator_statuses.is_parallel() {
+        deltas
+            .par_iter_mut()
+            .zip(statuses.par_iter())
+            .enumerate()
+            .try_for_each(|(index, (delta, status))| f(index, status, delta))
+    } else {
+        deltas
+            .iter_mut()
+            .zip(statuses.iter())
+            .enumerate()
+            .try_for_each(|(index, (delta, status))| f(index, status, delta))
+    }
 }
 
 /// Returns the base reward for some validator.
@@ -273,13 +429,22 @@ fn get_base_reward<T: EthSpec>(
     total_active_balance: u64,
     spec: &ChainSpec,
 ) -> Result<u64, BeaconStateError> {
+    Ok(base_reward(
+        state.get_effective_balance(index, spec)?,
+        total_active_balance,
+        spec,
+    ))
+}
+
+/// Returns the base reward for a validator with `effective_balance`.
+///
+/// Spec v0.8.0
+pub fn base_reward(effective_balance: u64, total_active_balance: u64, spec: &ChainSpec) -> u64 {
     if total_active_balance == 0 {
-        Ok(0)
+        0
     } else {
-        Ok(
-            state.get_effective_balance(index, spec)? * spec.base_reward_factor
-                / total_active_balance.integer_sqrt()
-                / spec.base_rewards_per_epoch,
-        )
+        effective_balance * spec.base_reward_factor
+            / total_active_balance.integer_sqrt()
+            / spec.base_rewards_per_epoch
     }
 }
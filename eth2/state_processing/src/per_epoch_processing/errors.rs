@@ -1,31 +1,38 @@
+use thiserror::Error;
 use types::*;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Error)]
 pub enum EpochProcessingError {
+    #[error("unable to determine the block producer")]
     UnableToDetermineProducer,
+    #[error("no block roots")]
     NoBlockRoots,
+    #[error("the base reward quotient is zero")]
     BaseRewardQuotientIsZero,
+    #[error("no randao seed")]
     NoRandaoSeed,
+    #[error("the previous epoch total balance is zero")]
     PreviousTotalBalanceIsZero,
+    #[error("an inclusion distance is zero")]
     InclusionDistanceZero,
+    #[error("the validator statuses are inconsistent with the state")]
     ValidatorStatusesInconsistent,
+    #[error("the reward and penalty deltas are inconsistent with the state")]
     DeltasInconsistent,
     /// Unable to get the inclusion distance for a validator that should have an inclusion
     /// distance. This indicates an internal inconsistency.
     ///
     /// (validator_index)
+    #[error("no inclusion slot for validator {0}")]
     InclusionSlotsInconsistent(usize),
+    #[error("beacon state error: {0:?}")]
     BeaconStateError(BeaconStateError),
-    InclusionError(InclusionError),
+    #[error("inclusion error: {0}")]
+    InclusionError(#[from] InclusionError),
+    #[error("ssz types error: {0:?}")]
     SszTypesError(ssz_types::Error),
 }
 
-impl From<InclusionError> for EpochProcessingError {
-    fn from(e: InclusionError) -> EpochProcessingError {
-        EpochProcessingError::InclusionError(e)
-    }
-}
-
 impl From<BeaconStateError> for EpochProcessingError {
     fn from(e: BeaconStateError) -> EpochProcessingError {
         EpochProcessingError::BeaconStateError(e)
@@ -38,10 +45,12 @@ impl From<ssz_types::Error> for EpochProcessingError {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Error)]
 pub enum InclusionError {
     /// The validator did not participate in an attestation in this period.
+    #[error("the validator did not attest in this period")]
     NoAttestationsForValidator,
+    #[error("beacon state error: {0:?}")]
     BeaconStateError(BeaconStateError),
 }
 
@@ -0,0 +1,171 @@
+//! The rewards and penalties, registry updates, slashings and effective balance updates of an
+//! epoch, in a single pass over the validators.
+//!
+//! In the spec, each of these steps iterates over every validator in turn. Given the
+//! `ValidatorStatuses` of the epoch (a summary of each validator's participation), the updates of
+//! a validator by every step depend only on its own status and record, so they are made together,
+//! validator by validator. The exceptions are:
+//!
+//! - The proposer rewards, which are summed for each proposer from the statuses beforehand.
+//! - The exit queue, which `initiate_validator_exit` tracks in the exit cache as validators are
+//!   ejected in order of their index, as they are in the spec.
+//! - The activation queue, whose validators are collected during the pass and dequeued after it.
+//!
+//! The state is left exactly as by `process_validators_in_steps`, which applies the steps one
+//! after another as the spec does.
+use super::apply_rewards::{
+    base_reward, for_each_proposer_reward, get_attestation_delta, get_crosslink_delta,
+    process_rewards_and_penalties,
+};
+use super::process_slashings::process_slashings;
+use super::registry_updates::process_registry_updates;
+use super::validator_statuses::ValidatorStatuses;
+use super::{process_effective_balance_updates, updated_effective_balance};
+use super::{Error, WinningRootHashSet};
+use crate::common::initiate_validator_exit;
+use types::*;
+
+/// Applies the rewards and penalties, registry updates, slashings and effective balance updates
+/// of the epoch of `state` to each validator in a single pass.
+///
+/// Spec v0.8.0
+pub fn process_validators<T: EthSpec>(
+    state: &mut BeaconState<T>,
+    validator_statuses: &mut ValidatorStatuses,
+    winning_root_for_shards: &WinningRootHashSet,
+    spec: &ChainSpec,
+) -> Result<(), Error> {
+    // Guard against an out-of-bounds during the pass.
+    if validator_statuses.statuses.len() != state.balances.len()
+        || validator_statuses.statuses.len() != state.validators.len()
+    {
+        return Err(Error::ValidatorStatusesInconsistent);
+    }
+
+    let current_epoch = state.current_epoch();
+    let total_balances = validator_statuses.total_balances.clone();
+    let total_active_balance = total_balances.current_epoch;
+
+    // Rewards and penalties: no rewards are applied at genesis.
+    let process_rewards = current_epoch != T::genesis_epoch();
+    let finality_delay = (state.previous_epoch() - state.finalized_checkpoint.epoch).as_u64();
+    let mut proposer_rewards = vec![0; state.validators.len()];
+    if process_rewards {
+        // The crosslink deltas require the winning roots.
+        validator_statuses.process_winning_roots(state, winning_root_for_shards, spec)?;
+        for_each_proposer_reward(state, validator_statuses, spec, |proposer_index, reward| {
+            *proposer_rewards
+                .get_mut(proposer_index)
+                .ok_or(Error::ValidatorStatusesInconsistent)? += reward;
+            Ok(())
+        })?;
+    }
+
+    // Registry updates: validators dequeued for activation prior to this epoch are excluded from
+    // the activation queue.
+    let activation_queue_epoch =
+        state.compute_activation_exit_epoch(state.finalized_checkpoint.epoch, spec);
+    let mut activation_queue = vec![];
+
+    // Slashings.
+    let sum_slashings = state.get_all_slashings().iter().sum::<u64>();
+    let slashings_withdrawable_epoch = current_epoch + T::EpochsPerSlashingsVector::to_u64() / 2;
+
+    for (index, status) in validator_statuses.statuses.iter().enumerate() {
+        let mut balance = state.balances[index];
+        // Constant until the effective balance updates, at the end of the pass.
+        let effective_balance = state.validators[index].effective_balance;
+
+        // Rewards and penalties, over-flowing but not under-flowing (saturating at 0 instead).
+        if process_rewards {
+            let base_reward = base_reward(effective_balance, total_active_balance, spec);
+            let mut delta = get_attestation_delta::<T>(
+                status,
+                &total_balances,
+                base_reward,
+                finality_delay,
+                spec,
+            )
+            .total();
+            delta += get_crosslink_delta(status, base_reward);
+            delta.reward(proposer_rewards[index]);
+
+            balance += delta.rewards();
+            balance = balance.saturating_sub(delta.penalties());
+        }
+
+        // Registry updates: activation eligibility and ejections.
+        let validator = &state.validators[index];
+        if validator.activation_eligibility_epoch == spec.far_future_epoch
+            && effective_balance == spec.max_effective_balance
+        {
+            state.validators[index].activation_eligibility_epoch = current_epoch;
+        } else if validator.is_active_at(current_epoch)
+            && effective_balance <= spec.ejection_balance
+        {
+            initiate_validator_exit(state, index, spec)?;
+        }
+
+        let validator = &state.validators[index];
+        if validator.activation_eligibility_epoch != spec.far_future_epoch
+            && validator.activation_epoch >= activation_queue_epoch
+        {
+            activation_queue.push((validator.activation_eligibility_epoch, index));
+        }
+
+        // Slashings.
+        if validator.slashed && validator.withdrawable_epoch == slashings_withdrawable_epoch {
+            let increment = spec.effective_balance_increment;
+            let penalty_numerator = effective_balance / increment
+                * std::cmp::min(sum_slashings * 3, total_active_balance);
+            let penalty = penalty_numerator / total_active_balance * increment;
+
+            balance = balance.saturating_sub(penalty);
+        }
+
+        // Effective balance updates.
+        //
+        // The validator and its balance are only borrowed mutably if they change, so that they are
+        // not copied from states which share them.
+        if let Some(effective_balance) = updated_effective_balance(effective_balance, balance, spec)
+        {
+            state.validators[index].effective_balance = effective_balance;
+        }
+        if balance != state.balances[index] {
+            state.balances[index] = balance;
+        }
+    }
+
+    // Dequeue validators for activation up to the churn limit, in order of eligibility (and then of
+    // index, as the sort is stable).
+    activation_queue.sort_by_key(|&(activation_eligibility_epoch, _)| activation_eligibility_epoch);
+    let churn_limit = state.get_churn_limit(spec)? as usize;
+    let delayed_activation_epoch = state.compute_activation_exit_epoch(current_epoch, spec);
+    for (_, index) in activation_queue.into_iter().take(churn_limit) {
+        if state.validators[index].activation_epoch == spec.far_future_epoch {
+            state.validators[index].activation_epoch = delayed_activation_epoch;
+        }
+    }
+
+    Ok(())
+}
+
+/// Applies the rewards and penalties, registry updates, slashings and effective balance updates
+/// of the epoch of `state` one after another, as the spec does.
+///
+/// Equivalent to `process_validators`, which is faster.
+///
+/// Spec v0.8.0
+pub fn process_validators_in_steps<T: EthSpec>(
+    state: &mut BeaconState<T>,
+    validator_statuses: &mut ValidatorStatuses,
+    winning_root_for_shards: &WinningRootHashSet,
+    spec: &ChainSpec,
+) -> Result<(), Error> {
+    process_rewards_and_penalties(state, validator_statuses, winning_root_for_shards, spec)?;
+    process_registry_updates(state, spec)?;
+    process_slashings(state, validator_statuses.total_balances.current_epoch, spec)?;
+    process_effective_balance_updates(state, spec);
+
+    Ok(())
+}
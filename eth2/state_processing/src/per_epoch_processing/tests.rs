@@ -1,7 +1,15 @@
 #![cfg(test)]
-use crate::per_epoch_processing::per_epoch_processing;
+use crate::per_epoch_processing::apply_rewards::{
+    base_reward, for_each_proposer_reward, get_attestation_component_deltas, get_attestation_delta,
+    process_rewards_and_penalties, AttestationDelta, Delta,
+};
+use crate::per_epoch_processing::single_pass::{process_validators, process_validators_in_steps};
+use crate::per_epoch_processing::validator_statuses::{InclusionInfo, ValidatorStatuses};
+use crate::per_epoch_processing::{
+    per_epoch_processing, per_epoch_processing_with_validator_statuses, process_crosslinks,
+};
 use env_logger::{Builder, Env};
-use types::test_utils::TestingBeaconStateBuilder;
+use types::test_utils::{RngCore, SeedableRng, TestingBeaconStateBuilder, XorShiftRng};
 use types::*;
 
 #[test]
@@ -21,3 +29,365 @@ fn runs_without_error() {
 
     per_epoch_processing(&mut state, &spec).unwrap();
 }
+
+#[test]
+fn incremental_validator_statuses_match_from_scratch() {
+    let spec = MinimalEthSpec::default_spec();
+
+    let mut builder: TestingBeaconStateBuilder<MinimalEthSpec> =
+        TestingBeaconStateBuilder::from_deterministic_keypairs(16, &spec);
+
+    let target_slot =
+        (MinimalEthSpec::genesis_epoch() + 4).end_slot(MinimalEthSpec::slots_per_epoch());
+    builder.teleport_to_slot(target_slot);
+    builder.insert_attestations(&spec);
+
+    let (mut state, _keypairs) = builder.build();
+
+    // An earlier state in the same epoch, which includes fewer attestations.
+    let mut earlier = state.clone();
+    let previous_epoch_attestations = earlier.previous_epoch_attestations.to_vec();
+    earlier.previous_epoch_attestations = VariableList::from(
+        previous_epoch_attestations[..previous_epoch_attestations.len() / 2].to_vec(),
+    );
+    earlier.current_epoch_attestations = VariableList::from(vec![]);
+
+    let mut incremental = ValidatorStatuses::new(&earlier, &spec).unwrap();
+    incremental.process_attestations(&earlier, &spec).unwrap();
+
+    // Slash an attester whose attestation has already been counted.
+    let slashed = incremental
+        .statuses
+        .iter()
+        .position(|status| status.is_previous_epoch_attester)
+        .unwrap();
+    state.validators[slashed].slashed = true;
+
+    incremental.update(&state, &spec).unwrap();
+
+    let mut from_scratch = ValidatorStatuses::new(&state, &spec).unwrap();
+    from_scratch.process_attestations(&state, &spec).unwrap();
+
+    assert_eq!(incremental.statuses, from_scratch.statuses);
+    assert_eq!(incremental.total_balances, from_scratch.total_balances);
+}
+
+#[test]
+fn parallel_epoch_processing_matches_serial() {
+    let spec = MinimalEthSpec::default_spec();
+    let mut rng = XorShiftRng::from_seed([42; 16]);
+
+    for &validator_count in &[8, 16, 27, 64] {
+        let mut builder: TestingBeaconStateBuilder<MinimalEthSpec> =
+            TestingBeaconStateBuilder::from_deterministic_keypairs(validator_count, &spec);
+
+        let target_slot =
+            (MinimalEthSpec::genesis_epoch() + 4).end_slot(MinimalEthSpec::slots_per_epoch());
+        builder.teleport_to_slot(target_slot);
+        builder.insert_attestations(&spec);
+
+        let (mut state, _keypairs) = builder.build();
+
+        // Randomise participation, slashings and balances.
+        for attestation in state
+            .previous_epoch_attestations
+            .iter_mut()
+            .chain(state.current_epoch_attestations.iter_mut())
+        {
+            for i in 0..attestation.aggregation_bits.len() {
+                if rng.next_u32() % 4 == 0 {
+                    attestation.aggregation_bits.set(i, false).unwrap();
+                }
+            }
+        }
+        for i in 0..state.validators.len() {
+            if rng.next_u32() % 8 == 0 {
+                state.validators[i].slashed = true;
+            }
+            state.balances[i] = u64::from(rng.next_u32());
+        }
+
+        let process = |parallel| {
+            let mut state = state.clone();
+            let mut statuses =
+                ValidatorStatuses::new_with_parallelism(&state, &spec, parallel).unwrap();
+            statuses.process_attestations(&state, &spec).unwrap();
+            let computed = (statuses.statuses.clone(), statuses.total_balances.clone());
+
+            per_epoch_processing_with_validator_statuses(&mut state, statuses, &spec).unwrap();
+            (computed, state)
+        };
+
+        let (serial_statuses, serial_state) = process(false);
+        let (parallel_statuses, parallel_state) = process(true);
+        assert_eq!(serial_statuses, parallel_statuses);
+        assert_eq!(serial_state, parallel_state);
+    }
+}
+
+#[test]
+fn single_pass_matches_separate_steps() {
+    let spec = MinimalEthSpec::default_spec();
+    let mut rng = XorShiftRng::from_seed([42; 16]);
+
+    for &validator_count in &[8, 16, 64] {
+        let mut builder: TestingBeaconStateBuilder<MinimalEthSpec> =
+            TestingBeaconStateBuilder::from_deterministic_keypairs(validator_count, &spec);
+
+        let target_slot =
+            (MinimalEthSpec::genesis_epoch() + 4).end_slot(MinimalEthSpec::slots_per_epoch());
+        builder.teleport_to_slot(target_slot);
+        builder.insert_attestations(&spec);
+
+        let (mut state, _keypairs) = builder.build();
+        let current_epoch = state.current_epoch();
+        let slashings_withdrawable_epoch =
+            current_epoch + MinimalEthSpec::EpochsPerSlashingsVector::to_u64() / 2;
+
+        // Randomise participation, slashings, ejections and balances.
+        for attestation in state
+            .previous_epoch_attestations
+            .iter_mut()
+            .chain(state.current_epoch_attestations.iter_mut())
+        {
+            for i in 0..attestation.aggregation_bits.len() {
+                if rng.next_u32() % 4 == 0 {
+                    attestation.aggregation_bits.set(i, false).unwrap();
+                }
+            }
+        }
+        for i in 0..state.validators.len() {
+            // The first validator is slashed and the second ejected, at least.
+            match (i, rng.next_u32() % 8) {
+                (0, _) | (_, 0) if i != 1 => {
+                    state.validators[i].slashed = true;
+                    state.validators[i].exit_epoch = current_epoch + 1;
+                    state.validators[i].withdrawable_epoch = slashings_withdrawable_epoch;
+                }
+                (1, _) | (_, 1) => state.validators[i].effective_balance = spec.ejection_balance,
+                _ => (),
+            }
+            state.balances[i] = u64::from(rng.next_u32()) * 16;
+        }
+        state
+            .set_slashings(current_epoch, spec.max_effective_balance)
+            .unwrap();
+
+        // Queue some validators for activation, and make others eligible for it.
+        for i in 0..validator_count {
+            let mut validator = state.validators[i].clone();
+            validator.activation_eligibility_epoch = if i % 2 == 0 {
+                Epoch::new(u64::from(rng.next_u32() % 4))
+            } else {
+                spec.far_future_epoch
+            };
+            validator.activation_epoch = spec.far_future_epoch;
+            validator.exit_epoch = spec.far_future_epoch;
+            validator.withdrawable_epoch = spec.far_future_epoch;
+            state.validators.push(validator).unwrap();
+            state.balances.push(spec.max_effective_balance).unwrap();
+        }
+
+        state
+            .build_committee_cache(RelativeEpoch::Previous, &spec)
+            .unwrap();
+        state
+            .build_committee_cache(RelativeEpoch::Current, &spec)
+            .unwrap();
+        state
+            .build_committee_cache(RelativeEpoch::Next, &spec)
+            .unwrap();
+        state
+            .exit_cache
+            .build_from_registry(&state.validators, &spec);
+
+        let mut statuses = ValidatorStatuses::new(&state, &spec).unwrap();
+        statuses.process_attestations(&state, &spec).unwrap();
+        let winning_root_for_shards = process_crosslinks(&mut state, &spec).unwrap();
+
+        let process = |single_pass| {
+            let mut state = state.clone();
+            let mut statuses = statuses.clone();
+            let process_validators = if single_pass {
+                process_validators::<MinimalEthSpec>
+            } else {
+                process_validators_in_steps::<MinimalEthSpec>
+            };
+            process_validators(&mut state, &mut statuses, &winning_root_for_shards, &spec).unwrap();
+            (statuses.statuses, state)
+        };
+
+        let (single_pass_statuses, single_pass_state) = process(true);
+        let (in_steps_statuses, in_steps_state) = process(false);
+        assert_eq!(single_pass_statuses, in_steps_statuses);
+        assert_eq!(single_pass_state, in_steps_state);
+
+        // Every step has left its mark.
+        let validators = &single_pass_state.validators;
+        assert_eq!(
+            validators[1].withdrawable_epoch,
+            validators[1].exit_epoch + spec.min_validator_withdrawability_delay
+        );
+        assert_ne!(validators[1].exit_epoch, spec.far_future_epoch);
+        assert!(validators
+            .iter()
+            .any(|v| v.activation_epoch > current_epoch
+                && v.activation_epoch != spec.far_future_epoch));
+        assert!(validators
+            .iter()
+            .any(|v| v.activation_eligibility_epoch == current_epoch));
+        assert_ne!(single_pass_state.balances, state.balances);
+    }
+}
+
+#[test]
+fn attesters_of_winning_crosslinks_are_rewarded() {
+    let spec = MinimalEthSpec::default_spec();
+
+    let mut builder: TestingBeaconStateBuilder<MinimalEthSpec> =
+        TestingBeaconStateBuilder::from_deterministic_keypairs(16, &spec);
+
+    let target_slot =
+        (MinimalEthSpec::genesis_epoch() + 4).end_slot(MinimalEthSpec::slots_per_epoch());
+    builder.teleport_to_slot(target_slot);
+    builder.insert_attestations(&spec);
+
+    let (mut state, _keypairs) = builder.build();
+
+    let mut statuses = ValidatorStatuses::new(&state, &spec).unwrap();
+    statuses.process_attestations(&state, &spec).unwrap();
+    let winning_root_for_shards = process_crosslinks(&mut state, &spec).unwrap();
+    let balances = state.balances.clone();
+
+    process_rewards_and_penalties(&mut state, &mut statuses, &winning_root_for_shards, &spec)
+        .unwrap();
+
+    let total_balances = &statuses.total_balances;
+    let finality_delay = (state.previous_epoch() - state.finalized_checkpoint.epoch).as_u64();
+    let mut proposer_rewards = vec![0; state.validators.len()];
+    for_each_proposer_reward(&state, &statuses, &spec, |proposer_index, reward| {
+        proposer_rewards[proposer_index] += reward;
+        Ok(())
+    })
+    .unwrap();
+
+    let mut attesters = 0;
+    for (index, status) in statuses.statuses.iter().enumerate() {
+        let winning_root = match &status.winning_root_info {
+            Some(winning_root) => winning_root,
+            None => continue,
+        };
+        let base_reward = base_reward(
+            state.validators[index].effective_balance,
+            total_balances.current_epoch,
+            &spec,
+        );
+        let attestation_delta = get_attestation_delta::<MinimalEthSpec>(
+            status,
+            total_balances,
+            base_reward,
+            finality_delay,
+            &spec,
+        )
+        .total();
+        // Rewarded, rather than penalized, for the crosslink.
+        let crosslink_reward = base_reward * winning_root.total_attesting_balance
+            / winning_root.total_committee_balance;
+
+        let expected = (balances[index]
+            + attestation_delta.rewards()
+            + crosslink_reward
+            + proposer_rewards[index])
+            .saturating_sub(attestation_delta.penalties());
+        assert_eq!(state.balances[index], expected, "validator {}", index);
+        attesters += 1;
+    }
+    assert!(attesters > 0);
+}
+
+#[test]
+fn attestation_component_deltas_match_hand_computed_deltas() {
+    let spec = MinimalEthSpec::default_spec();
+    let mut builder: TestingBeaconStateBuilder<MinimalEthSpec> =
+        TestingBeaconStateBuilder::from_deterministic_keypairs(4, &spec);
+    // The previous epoch is 5 epochs after the (genesis) finalized epoch, so every validator is
+    // also penalized for the inactivity of the chain.
+    builder.teleport_to_slot(
+        (MinimalEthSpec::genesis_epoch() + 6).start_slot(MinimalEthSpec::slots_per_epoch()),
+    );
+    let (state, _keypairs) = builder.build();
+
+    // Validator 0 attests to the source, target and head, validator 1 to the source and target,
+    // validator 2 to the source only and validator 3 not at all.
+    let mut statuses = ValidatorStatuses::new(&state, &spec).unwrap();
+    let inclusions = [(1, 3), (2, 3), (1, 0)];
+    for (index, &(distance, proposer_index)) in inclusions.iter().enumerate() {
+        let status = &mut statuses.statuses[index];
+        status.is_previous_epoch_attester = true;
+        status.is_previous_epoch_target_attester = index < 2;
+        status.is_previous_epoch_head_attester = index < 1;
+        status.inclusion_info = Some(InclusionInfo {
+            slot: Slot::new(0),
+            distance,
+            proposer_index,
+        });
+    }
+    let balance = 32_000_000_000;
+    statuses.total_balances.current_epoch = 4 * balance;
+    statuses.total_balances.previous_epoch_attesters = 3 * balance;
+    statuses.total_balances.previous_epoch_target_attesters = 2 * balance;
+    statuses.total_balances.previous_epoch_head_attesters = balance;
+
+    // The base reward is 32e9 * 64 / isqrt(128e9) / 5 = 32e9 * 64 / 357_770 / 5 = 1_144_869, of
+    // which the proposer reward is 1_144_869 / 8 = 143_108, leaving an inclusion reward of
+    // 1_001_761 at a distance of 1 and 1_001_761 * 7 / 8 = 876_540 at a distance of 2. The
+    // components are rewarded in proportion to the balance attesting to them, e.g.
+    // 1_144_869 * 3 / 4 = 858_651 for the source.
+    let delta = |rewards, penalties| {
+        let mut delta = Delta::default();
+        delta.reward(rewards);
+        delta.penalize(penalties);
+        delta
+    };
+    let base_reward = 1_144_869;
+    // 5 base rewards, and 32e9 * 5 / 2^25 = 4_768 for missing the target.
+    let inactivity = 5 * base_reward;
+    let target_inactivity = 4_768;
+    let expected = vec![
+        AttestationDelta {
+            source: delta(858_651, 0),
+            target: delta(572_434, 0),
+            head: delta(286_217, 0),
+            // Including the attestation of validator 2.
+            inclusion_delay: delta(1_001_761 + 143_108, 0),
+            inactivity: delta(0, inactivity),
+        },
+        AttestationDelta {
+            source: delta(858_651, 0),
+            target: delta(572_434, 0),
+            head: delta(0, base_reward),
+            inclusion_delay: delta(876_540, 0),
+            inactivity: delta(0, inactivity),
+        },
+        AttestationDelta {
+            source: delta(858_651, 0),
+            target: delta(0, base_reward),
+            head: delta(0, base_reward),
+            inclusion_delay: delta(1_001_761, 0),
+            inactivity: delta(0, inactivity + target_inactivity),
+        },
+        AttestationDelta {
+            source: delta(0, base_reward),
+            target: delta(0, base_reward),
+            head: delta(0, base_reward),
+            // Including the attestations of validators 0 and 1.
+            inclusion_delay: delta(2 * 143_108, 0),
+            inactivity: delta(0, inactivity + target_inactivity),
+        },
+    ];
+
+    assert_eq!(
+        get_attestation_component_deltas(&state, &statuses, &spec).unwrap(),
+        expected
+    );
+}
@@ -1,7 +1,13 @@
 use super::WinningRootHashSet;
 use crate::common::get_attesting_indices;
+use rayon::prelude::*;
+use std::collections::BTreeSet;
 use types::*;
 
+/// `true` if epoch processing is spread across threads by default, with the
+/// `parallel_epoch_processing` feature.
+pub const PARALLEL_BY_DEFAULT: bool = cfg!(feature = "parallel_epoch_processing");
+
 /// Sets the boolean `var` on `self` to be true if it is true on `other`. Otherwise leaves `self`
 /// as is.
 macro_rules! set_self_if_other_is_true {
@@ -14,7 +20,7 @@ macro_rules! set_self_if_other_is_true {
 
 /// The information required to reward some validator for their participation in a "winning"
 /// crosslink root.
-#[derive(Default, Clone)]
+#[derive(Debug, Default, PartialEq, Clone)]
 pub struct WinningRootInfo {
     /// The total balance of the crosslink committee.
     pub total_committee_balance: u64,
@@ -23,7 +29,7 @@ pub struct WinningRootInfo {
 }
 
 /// The information required to reward a block producer for including an attestation in a block.
-#[derive(Clone, Copy)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub struct InclusionInfo {
     /// The earliest slot a validator had an attestation included in the previous epoch.
     pub slot: Slot,
@@ -58,7 +64,7 @@ impl InclusionInfo {
 }
 
 /// Information required to reward some validator during the current and previous epoch.
-#[derive(Default, Clone)]
+#[derive(Debug, Default, PartialEq, Clone)]
 pub struct ValidatorStatus {
     /// True if the validator has been slashed, ever.
     pub is_slashed: bool,
@@ -125,7 +131,7 @@ impl ValidatorStatus {
 
 /// The total effective balances for different sets of validators during the previous and current
 /// epochs.
-#[derive(Default, Clone, Debug)]
+#[derive(Default, Clone, Debug, PartialEq)]
 pub struct TotalBalances {
     /// The total effective balance of all active validators during the _current_ epoch.
     pub current_epoch: u64,
@@ -148,12 +154,28 @@ pub struct TotalBalances {
 
 /// Summarised information about validator participation in the _previous and _current_ epochs of
 /// some `BeaconState`.
+///
+/// Statuses may be computed from scratch at the end of an epoch (`new` followed by
+/// `process_attestations`), or incrementally: by calling `update` with each new state of the same
+/// chain during the epoch (e.g., after each block), only the attestations added since the previous
+/// update are processed, leaving little work to be done at the epoch boundary.
+///
+/// If `parallel`, the attestations and validators are profiled across threads. The result is
+/// identical to that of the serial path, since the profiles are applied in order.
 #[derive(Clone)]
 pub struct ValidatorStatuses {
     /// Information about each individual validator from the state's validator registry.
     pub statuses: Vec<ValidatorStatus>,
     /// Summed balances for various sets of validators.
     pub total_balances: TotalBalances,
+    /// The current epoch of the state the statuses were created for.
+    epoch: Epoch,
+    /// The number of `previous_epoch_attestations` which have been processed.
+    previous_epoch_attestations: usize,
+    /// The number of `current_epoch_attestations` which have been processed.
+    current_epoch_attestations: usize,
+    /// If `true`, statuses are computed (and rewards applied) across threads.
+    parallel: bool,
 }
 
 impl ValidatorStatuses {
@@ -167,36 +189,34 @@ impl ValidatorStatuses {
         state: &BeaconState<T>,
         spec: &ChainSpec,
     ) -> Result<Self, BeaconStateError> {
-        let mut statuses = Vec::with_capacity(state.validators.len());
-        let mut total_balances = TotalBalances::default();
-
-        for (i, validator) in state.validators.iter().enumerate() {
-            let effective_balance = state.get_effective_balance(i, spec)?;
-            let mut status = ValidatorStatus {
-                is_slashed: validator.slashed,
-                is_withdrawable_in_current_epoch: validator
-                    .is_withdrawable_at(state.current_epoch()),
-                current_epoch_effective_balance: effective_balance,
-                ..ValidatorStatus::default()
-            };
-
-            if validator.is_active_at(state.current_epoch()) {
-                status.is_active_in_current_epoch = true;
-                total_balances.current_epoch += effective_balance;
-            }
-
-            if validator.is_active_at(state.previous_epoch()) {
-                status.is_active_in_previous_epoch = true;
-                total_balances.previous_epoch += effective_balance;
-            }
+        Self::new_with_parallelism(state, spec, PARALLEL_BY_DEFAULT)
+    }
 
-            statuses.push(status);
-        }
+    /// As `new`, but computing the statuses across threads only if `parallel`, regardless of the
+    /// `parallel_epoch_processing` feature.
+    ///
+    /// Spec v0.8.1
+    pub fn new_with_parallelism<T: EthSpec>(
+        state: &BeaconState<T>,
+        spec: &ChainSpec,
+        parallel: bool,
+    ) -> Result<Self, BeaconStateError> {
+        let mut validator_statuses = Self {
+            statuses: Vec::with_capacity(state.validators.len()),
+            total_balances: TotalBalances::default(),
+            epoch: state.current_epoch(),
+            previous_epoch_attestations: 0,
+            current_epoch_attestations: 0,
+            parallel,
+        };
+        validator_statuses.process_new_validators(state, spec)?;
+
+        Ok(validator_statuses)
+    }
 
-        Ok(Self {
-            statuses,
-            total_balances,
-        })
+    /// Returns `true` if the statuses are computed, and rewards applied, across threads.
+    pub fn is_parallel(&self) -> bool {
+        self.parallel
     }
 
     /// Process some attestations from the given `state` updating the `statuses` and
@@ -208,77 +228,194 @@ impl ValidatorStatuses {
         state: &BeaconState<T>,
         spec: &ChainSpec,
     ) -> Result<(), BeaconStateError> {
-        for a in state
-            .previous_epoch_attestations
-            .iter()
-            .chain(state.current_epoch_attestations.iter())
+        self.process_attestation_batch(state, &state.previous_epoch_attestations[..], spec)?;
+        self.process_attestation_batch(state, &state.current_epoch_attestations[..], spec)?;
+
+        self.previous_epoch_attestations = state.previous_epoch_attestations.len();
+        self.current_epoch_attestations = state.current_epoch_attestations.len();
+
+        Ok(())
+    }
+
+    /// Brings the `statuses` and `total_balances` up to date with `state`, processing only the
+    /// validators, slashings and attestations which have been added since the previous update.
+    ///
+    /// `state` must descend from the state of the previous update (or of `new`). If it is in a
+    /// different epoch, or it cannot descend from it, the statuses are recomputed from scratch.
+    ///
+    /// Spec v0.8.1
+    pub fn update<T: EthSpec>(
+        &mut self,
+        state: &BeaconState<T>,
+        spec: &ChainSpec,
+    ) -> Result<(), BeaconStateError> {
+        if state.current_epoch() != self.epoch
+            || state.validators.len() < self.statuses.len()
+            || state.previous_epoch_attestations.len() < self.previous_epoch_attestations
+            || state.current_epoch_attestations.len() < self.current_epoch_attestations
         {
-            let attesting_indices = get_attesting_indices(state, &a.data, &a.aggregation_bits)?;
+            *self = Self::new_with_parallelism(state, spec, self.parallel)?;
+            return self.process_attestations(state, spec);
+        }
 
-            let mut status = ValidatorStatus::default();
+        self.process_slashings(state, spec)?;
+        self.process_new_validators(state, spec)?;
 
-            // Profile this attestation, updating the total balances and generating an
-            // `ValidatorStatus` object that applies to all participants in the attestation.
-            if a.data.target.epoch == state.current_epoch() {
-                status.is_current_epoch_attester = true;
+        let previous_epoch_attestations =
+            &state.previous_epoch_attestations[self.previous_epoch_attestations..];
+        self.process_attestation_batch(state, previous_epoch_attestations, spec)?;
+        let current_epoch_attestations =
+            &state.current_epoch_attestations[self.current_epoch_attestations..];
+        self.process_attestation_batch(state, current_epoch_attestations, spec)?;
 
-                if target_matches_epoch_start_block(a, state, state.current_epoch())? {
-                    status.is_current_epoch_target_attester = true;
-                }
-            } else if a.data.target.epoch == state.previous_epoch() {
-                status.is_previous_epoch_attester = true;
-
-                // The inclusion slot and distance are only required for previous epoch attesters.
-                let attestation_slot = state.get_attestation_data_slot(&a.data)?;
-                let inclusion_slot = attestation_slot + a.inclusion_delay;
-                let relative_epoch =
-                    RelativeEpoch::from_slot(state.slot, inclusion_slot, T::slots_per_epoch())?;
-                status.inclusion_info = Some(InclusionInfo {
-                    slot: inclusion_slot,
-                    distance: a.inclusion_delay,
-                    proposer_index: state.get_beacon_proposer_index(
-                        inclusion_slot,
-                        relative_epoch,
-                        spec,
-                    )?,
-                });
-
-                if target_matches_epoch_start_block(a, state, state.previous_epoch())? {
-                    status.is_previous_epoch_target_attester = true;
-                }
+        self.previous_epoch_attestations = state.previous_epoch_attestations.len();
+        self.current_epoch_attestations = state.current_epoch_attestations.len();
 
-                if has_common_beacon_block_root(a, state)? {
-                    status.is_previous_epoch_head_attester = true;
-                }
+        Ok(())
+    }
+
+    /// Adds a status for each validator in `state` which does not yet have one.
+    fn process_new_validators<T: EthSpec>(
+        &mut self,
+        state: &BeaconState<T>,
+        spec: &ChainSpec,
+    ) -> Result<(), BeaconStateError> {
+        let new_validators = self.statuses.len()..state.validators.len();
+        let statuses = if self.parallel {
+            new_validators
+                .into_par_iter()
+                .map(|i| new_validator_status(state, i, spec))
+                .collect::<Result<Vec<_>, _>>()?
+        } else {
+            new_validators
+                .map(|i| new_validator_status(state, i, spec))
+                .collect::<Result<Vec<_>, _>>()?
+        };
+
+        for status in statuses {
+            if status.is_active_in_current_epoch {
+                self.total_balances.current_epoch += status.current_epoch_effective_balance;
+            }
+            if status.is_active_in_previous_epoch {
+                self.total_balances.previous_epoch += status.current_epoch_effective_balance;
+            }
+            self.statuses.push(status);
+        }
+
+        Ok(())
+    }
+
+    /// Marks each validator which has been slashed in `state` since the statuses were last updated,
+    /// removing its balance from the attesting balances.
+    fn process_slashings<T: EthSpec>(
+        &mut self,
+        state: &BeaconState<T>,
+        spec: &ChainSpec,
+    ) -> Result<(), BeaconStateError> {
+        for (i, status) in self.statuses.iter_mut().enumerate() {
+            let validator = &state.validators[i];
+            // Slashing also changes the withdrawable epoch.
+            status.is_withdrawable_in_current_epoch =
+                validator.is_withdrawable_at(state.current_epoch());
+
+            if status.is_slashed || !validator.slashed {
+                continue;
+            }
+            status.is_slashed = true;
+
+            let balance = state.get_effective_balance(i, spec)?;
+            let totals = &mut self.total_balances;
+            if status.is_current_epoch_attester {
+                totals.current_epoch_attesters -= balance;
             }
+            if status.is_current_epoch_target_attester {
+                totals.current_epoch_target_attesters -= balance;
+            }
+            if status.is_previous_epoch_attester {
+                totals.previous_epoch_attesters -= balance;
+            }
+            if status.is_previous_epoch_target_attester {
+                totals.previous_epoch_target_attesters -= balance;
+            }
+            if status.is_previous_epoch_head_attester {
+                totals.previous_epoch_head_attesters -= balance;
+            }
+        }
 
-            // Loop through the participating validator indices and update the status vec.
-            for validator_index in attesting_indices {
-                self.statuses[validator_index].update(&status);
+        Ok(())
+    }
+
+    /// Processes `attestations` from the given `state` in order, profiling them across threads if
+    /// `self.parallel`.
+    fn process_attestation_batch<T: EthSpec>(
+        &mut self,
+        state: &BeaconState<T>,
+        attestations: &[PendingAttestation<T>],
+        spec: &ChainSpec,
+    ) -> Result<(), BeaconStateError> {
+        if self.parallel {
+            let profiles = attestations
+                .par_iter()
+                .map(|a| profile_attestation(state, a, spec))
+                .collect::<Result<Vec<_>, _>>()?;
+            for (attesting_indices, status) in profiles {
+                self.apply_attestation(state, &attesting_indices, &status, spec)?;
+            }
+        } else {
+            for a in attestations {
+                let (attesting_indices, status) = profile_attestation(state, a, spec)?;
+                self.apply_attestation(state, &attesting_indices, &status, spec)?;
             }
         }
 
-        // Compute the total balances
-        for (index, v) in self.statuses.iter().enumerate() {
+        Ok(())
+    }
+
+    /// Updates the `statuses` of the attesters of a single attestation with its profile `status`,
+    /// adding the balances of any unslashed attesters which were not already counted to the
+    /// `total_balances`.
+    ///
+    /// Spec v0.8.1
+    fn apply_attestation<T: EthSpec>(
+        &mut self,
+        state: &BeaconState<T>,
+        attesting_indices: &BTreeSet<usize>,
+        status: &ValidatorStatus,
+        spec: &ChainSpec,
+    ) -> Result<(), BeaconStateError> {
+        // Loop through the participating validator indices, updating the status vec and, for
+        // each newly-set flag, the total balances.
+        for &validator_index in attesting_indices {
+            let previous = self.statuses[validator_index].clone();
+            self.statuses[validator_index].update(status);
+
             // According to the spec, we only count unslashed validators towards the totals.
-            if !v.is_slashed {
-                let validator_balance = state.get_effective_balance(index, spec)?;
+            if previous.is_slashed {
+                continue;
+            }
+            let current = &self.statuses[validator_index];
+            let balance = state.get_effective_balance(validator_index, spec)?;
+            let totals = &mut self.total_balances;
 
-                if v.is_current_epoch_attester {
-                    self.total_balances.current_epoch_attesters += validator_balance;
-                }
-                if v.is_current_epoch_target_attester {
-                    self.total_balances.current_epoch_target_attesters += validator_balance;
-                }
-                if v.is_previous_epoch_attester {
-                    self.total_balances.previous_epoch_attesters += validator_balance;
-                }
-                if v.is_previous_epoch_target_attester {
-                    self.total_balances.previous_epoch_target_attesters += validator_balance;
-                }
-                if v.is_previous_epoch_head_attester {
-                    self.total_balances.previous_epoch_head_attesters += validator_balance;
-                }
+            if current.is_current_epoch_attester && !previous.is_current_epoch_attester {
+                totals.current_epoch_attesters += balance;
+            }
+            if current.is_current_epoch_target_attester
+                && !previous.is_current_epoch_target_attester
+            {
+                totals.current_epoch_target_attesters += balance;
+            }
+            if current.is_previous_epoch_attester && !previous.is_previous_epoch_attester {
+                totals.previous_epoch_attesters += balance;
+            }
+            if current.is_previous_epoch_target_attester
+                && !previous.is_previous_epoch_target_attester
+            {
+                totals.previous_epoch_target_attesters += balance;
+            }
+            if current.is_previous_epoch_head_attester && !previous.is_previous_epoch_head_attester
+            {
+                totals.previous_epoch_head_attesters += balance;
             }
         }
 
@@ -320,6 +457,75 @@ impl ValidatorStatuses {
     }
 }
 
+/// Returns the status of the validator at index `i` of `state`, before processing any
+/// attestations.
+fn new_validator_status<T: EthSpec>(
+    state: &BeaconState<T>,
+    i: usize,
+    spec: &ChainSpec,
+) -> Result<ValidatorStatus, BeaconStateError> {
+    let validator = &state.validators[i];
+    Ok(ValidatorStatus {
+        is_slashed: validator.slashed,
+        is_withdrawable_in_current_epoch: validator.is_withdrawable_at(state.current_epoch()),
+        is_active_in_current_epoch: validator.is_active_at(state.current_epoch()),
+        is_active_in_previous_epoch: validator.is_active_at(state.previous_epoch()),
+        current_epoch_effective_balance: state.get_effective_balance(i, spec)?,
+        ..ValidatorStatus::default()
+    })
+}
+
+/// Returns the indices of the attesters of `a`, and the `ValidatorStatus` which applies to all of
+/// them.
+///
+/// Spec v0.8.1
+fn profile_attestation<T: EthSpec>(
+    state: &BeaconState<T>,
+    a: &PendingAttestation<T>,
+    spec: &ChainSpec,
+) -> Result<(BTreeSet<usize>, ValidatorStatus), BeaconStateError> {
+    let attesting_indices = get_attesting_indices(state, &a.data, &a.aggregation_bits)?;
+
+    let mut status = ValidatorStatus::default();
+
+    // Profile this attestation, generating an `ValidatorStatus` object that applies to all
+    // participants in the attestation.
+    if a.data.target.epoch == state.current_epoch() {
+        status.is_current_epoch_attester = true;
+
+        if target_matches_epoch_start_block(a, state, state.current_epoch())? {
+            status.is_current_epoch_target_attester = true;
+        }
+    } else if a.data.target.epoch == state.previous_epoch() {
+        status.is_previous_epoch_attester = true;
+
+        // The inclusion slot and distance are only required for previous epoch attesters.
+        let attestation_slot = state.get_attestation_data_slot(&a.data)?;
+        let inclusion_slot = attestation_slot + a.inclusion_delay;
+        let relative_epoch =
+            RelativeEpoch::from_slot(state.slot, inclusion_slot, T::slots_per_epoch())?;
+        status.inclusion_info = Some(InclusionInfo {
+            slot: inclusion_slot,
+            distance: a.inclusion_delay,
+            proposer_index: state.get_beacon_proposer_index(
+                inclusion_slot,
+                relative_epoch,
+                spec,
+            )?,
+        });
+
+        if target_matches_epoch_start_block(a, state, state.previous_epoch())? {
+            status.is_previous_epoch_target_attester = true;
+        }
+
+        if has_common_beacon_block_root(a, state)? {
+            status.is_previous_epoch_head_attester = true;
+        }
+    }
+
+    Ok((attesting_indices, status))
+}
+
 /// Returns `true` if the attestation's FFG target is equal to the hash of the `state`'s first
 /// beacon block in the given `epoch`.
 ///
@@ -9,6 +9,9 @@ pub enum Error {
 
 /// Advances a state forward by one slot, performing per-epoch processing if required.
 ///
+/// If the new slot is the first of an epoch in which a fork is scheduled, `state.fork` is upgraded
+/// to that fork.
+///
 /// Spec v0.8.0
 pub fn per_slot_processing<T: EthSpec>(
     state: &mut BeaconState<T>,
@@ -22,6 +25,10 @@ pub fn per_slot_processing<T: EthSpec>(
 
     state.slot += 1;
 
+    if state.slot % T::slots_per_epoch() == 0 {
+        state.fork = spec.upgrade_fork(&state.fork, state.current_epoch());
+    }
+
     Ok(())
 }
 
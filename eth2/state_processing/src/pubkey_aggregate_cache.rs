@@ -0,0 +1,216 @@
+//! A cache of the public keys consumed by `BlockSignatureVerifier`, which may be shared between
+//! the blocks it verifies.
+//!
+//! Verifying an attestation requires the aggregate of the public keys of its attesters, which
+//! costs a point addition per attester. Attestations included in blocks are commonly signed by
+//! their whole committee, so the `PubkeyAggregateCache` precomputes the aggregate public key of
+//! each committee once and uses it for every such attestation of every block. The keys of new
+//! validators' deposits, which must be decompressed to be verified, are decompressed at most once.
+use bls::{CommitteePublicKeys, PublicKeyBytes, PublicKeyCache};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use types::{
+    AggregatePublicKey, BeaconState, BeaconStateError, Epoch, EthSpec, PublicKey, RelativeEpoch,
+};
+
+/// The public keys of a crosslink committee.
+struct CachedCommittee {
+    /// The committee, in the order of the shuffling.
+    committee: Vec<usize>,
+    /// The committee, sorted by validator index (the order of the attesting indices of an
+    /// `IndexedAttestation`) and the public keys of its members in the same order.
+    sorted: Vec<usize>,
+    pubkeys: CommitteePublicKeys,
+}
+
+/// Stores the public keys of the latest committee seen of each epoch and shard, and the
+/// decompressed public keys of deposits.
+///
+/// The cache is `Sync`, so it may be shared between threads.
+#[derive(Default)]
+pub struct PubkeyAggregateCache {
+    committees: Mutex<HashMap<(Epoch, u64), Arc<CachedCommittee>>>,
+    deposit_pubkeys: PublicKeyCache,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl PubkeyAggregateCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the aggregate public key of the validators at `validator_indices` (sorted, as in an
+    /// `IndexedAttestation`) of the crosslink committee of `shard` at `epoch` of `state`, or
+    /// `None` if one of them is not a member of the committee.
+    ///
+    /// Requires the committee cache of `epoch` to be built.
+    pub fn committee_aggregate<T: EthSpec>(
+        &self,
+        state: &BeaconState<T>,
+        epoch: Epoch,
+        shard: u64,
+        validator_indices: &[u64],
+    ) -> Result<Option<AggregatePublicKey>, BeaconStateError> {
+        let relative_epoch = RelativeEpoch::from_epoch(state.current_epoch(), epoch)?;
+        let committee = state
+            .get_crosslink_committee_for_shard(shard, relative_epoch)?
+            .committee;
+
+        // A committee of another shuffling (e.g., of a competing fork) at the same epoch and shard
+        // replaces the cached one.
+        let cached = self.lock().get(&(epoch, shard)).cloned();
+        let cached = match cached {
+            Some(ref cached) if cached.committee[..] == *committee => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                cached.clone()
+            }
+            _ => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                let mut sorted = committee.to_vec();
+                sorted.sort_unstable();
+                let pubkeys: Vec<PublicKey> = sorted
+                    .iter()
+                    .map(|&index| {
+                        state
+                            .validators
+                            .get(index)
+                            .map(|validator| validator.pubkey.clone())
+                            .ok_or_else(|| BeaconStateError::UnknownValidator)
+                    })
+                    .collect::<Result<_, _>>()?;
+                let cached = Arc::new(CachedCommittee {
+                    committee: committee.to_vec(),
+                    sorted,
+                    pubkeys: CommitteePublicKeys::new(pubkeys),
+                });
+                self.lock().insert((epoch, shard), cached.clone());
+                cached
+            }
+        };
+
+        let positions = validator_indices
+            .iter()
+            .map(|&index| cached.sorted.binary_search(&(index as usize)).ok())
+            .collect::<Option<Vec<_>>>();
+        Ok(positions.and_then(|positions| cached.pubkeys.aggregate_of(&positions)))
+    }
+
+    /// Returns the public key of a deposit, or `None` if `bytes` are not a valid public key.
+    pub fn deposit_pubkey(&self, bytes: &PublicKeyBytes) -> Option<PublicKey> {
+        self.deposit_pubkeys.decompress(bytes)
+    }
+
+    /// Removes the committees of epochs before `epoch`, which can no longer be attested to by
+    /// blocks descending from a state at the epoch after it.
+    pub fn prune(&self, epoch: Epoch) {
+        self.lock()
+            .retain(|(committee_epoch, _), _| *committee_epoch >= epoch);
+    }
+
+    /// The number of committees stored in the cache.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns `true` if the cache stores no committees.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of aggregates computed from a cached committee.
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// The number of committees whose public keys had to be gathered and aggregated.
+    pub fn misses(&self) -> usize {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    /// A panic whilst holding the lock cannot leave the map inconsistent, so a poisoned lock is
+    /// still used.
+    fn lock(&self) -> std::sync::MutexGuard<HashMap<(Epoch, u64), Arc<CachedCommittee>>> {
+        self.committees
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use types::test_utils::TestingBeaconStateBuilder;
+    use types::{ChainSpec, MinimalEthSpec};
+
+    fn state(validator_count: usize, spec: &ChainSpec) -> BeaconState<MinimalEthSpec> {
+        let mut builder: TestingBeaconStateBuilder<MinimalEthSpec> =
+            TestingBeaconStateBuilder::from_deterministic_keypairs(validator_count, spec);
+        builder.teleport_to_slot(
+            (MinimalEthSpec::genesis_epoch() + 4).start_slot(MinimalEthSpec::slots_per_epoch()),
+        );
+        let mut state = builder.build().0;
+        state.build_all_caches(spec).unwrap();
+        state
+    }
+
+    fn aggregate_bytes<T: EthSpec>(state: &BeaconState<T>, indices: &[u64]) -> Vec<u8> {
+        let mut aggregate = AggregatePublicKey::new();
+        for &index in indices {
+            aggregate.add(&state.validators[index as usize].pubkey);
+        }
+        aggregate.as_raw().as_bytes()
+    }
+
+    #[test]
+    fn aggregates_committees_once() {
+        let spec = MinimalEthSpec::default_spec();
+        let cache = PubkeyAggregateCache::new();
+        let state = state(64, &spec);
+        let epoch = state.current_epoch();
+
+        let committee = state
+            .get_crosslink_committees_at_slot(state.slot)
+            .unwrap()
+            .remove(0);
+        let mut members: Vec<u64> = committee.committee.iter().map(|&i| i as u64).collect();
+        members.sort();
+
+        let whole = cache
+            .committee_aggregate(&state, epoch, committee.shard, &members)
+            .unwrap()
+            .unwrap();
+        assert_eq!(whole.as_raw().as_bytes(), aggregate_bytes(&state, &members));
+
+        let some = &members[1..];
+        let partial = cache
+            .committee_aggregate(&state, epoch, committee.shard, some)
+            .unwrap()
+            .unwrap();
+        assert_eq!(partial.as_raw().as_bytes(), aggregate_bytes(&state, some));
+        assert_eq!((cache.hits(), cache.misses()), (1, 1));
+
+        // A validator outside of the committee.
+        let outsider = (0..64).find(|i| !members.contains(i)).unwrap();
+        assert!(cache
+            .committee_aggregate(&state, epoch, committee.shard, &[outsider])
+            .unwrap()
+            .is_none());
+
+        cache.prune(epoch + 1);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn decompresses_deposit_pubkeys() {
+        let spec = MinimalEthSpec::default_spec();
+        let cache = PubkeyAggregateCache::new();
+        let state = state(8, &spec);
+        let pubkey = state.validators[0].pubkey.clone();
+
+        let bytes = PublicKeyBytes::from(pubkey.clone());
+        assert_eq!(cache.deposit_pubkey(&bytes), Some(pubkey.clone()));
+        assert_eq!(cache.deposit_pubkey(&bytes), Some(pubkey));
+    }
+}
@@ -0,0 +1,157 @@
+use crate::*;
+use bls::Signature;
+use serde::de::Deserializer;
+use serde_derive::{Deserialize, Serialize};
+use ssz::{Decode, DecodeError, Encode};
+
+/// The shape of the blocks of a fork.
+///
+/// Blocks are not self-describing, so the variant of a block must be known (from the fork of its
+/// epoch, see `ChainSpec::block_variant`) to decode it.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockVariant {
+    /// The `BeaconBlock` of the genesis fork.
+    Base,
+}
+
+impl Default for BlockVariant {
+    fn default() -> Self {
+        BlockVariant::Base
+    }
+}
+
+/// A block of any fork of the `BeaconChain`.
+///
+/// Serializes as the block of its variant, so its encoding is that of the block alone. Decoding
+/// requires the variant, with `from_ssz_bytes` or `deserialize_variant`.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+#[serde(bound = "T: EthSpec", untagged)]
+pub enum BeaconBlockAny<T: EthSpec> {
+    Base(BeaconBlock<T>),
+}
+
+impl<T: EthSpec> BeaconBlockAny<T> {
+    /// Returns the variant of the block.
+    pub fn variant(&self) -> BlockVariant {
+        match self {
+            BeaconBlockAny::Base(_) => BlockVariant::Base,
+        }
+    }
+
+    pub fn slot(&self) -> Slot {
+        match self {
+            BeaconBlockAny::Base(block) => block.slot,
+        }
+    }
+
+    pub fn parent_root(&self) -> Hash256 {
+        match self {
+            BeaconBlockAny::Base(block) => block.parent_root,
+        }
+    }
+
+    pub fn state_root(&self) -> Hash256 {
+        match self {
+            BeaconBlockAny::Base(block) => block.state_root,
+        }
+    }
+
+    pub fn randao_reveal(&self) -> &Signature {
+        match self {
+            BeaconBlockAny::Base(block) => &block.body.randao_reveal,
+        }
+    }
+
+    pub fn signature(&self) -> &Signature {
+        match self {
+            BeaconBlockAny::Base(block) => &block.signature,
+        }
+    }
+
+    pub fn set_signature(&mut self, signature: Signature) {
+        match self {
+            BeaconBlockAny::Base(block) => block.signature = signature,
+        }
+    }
+
+    /// Returns the `signed_root` of the block, as signed by its proposer.
+    pub fn canonical_root(&self) -> Hash256 {
+        match self {
+            BeaconBlockAny::Base(block) => block.canonical_root(),
+        }
+    }
+
+    /// Returns a full `BeaconBlockHeader` of this block.
+    ///
+    /// Note: performs a full tree-hash of the block body.
+    pub fn block_header(&self) -> BeaconBlockHeader {
+        match self {
+            BeaconBlockAny::Base(block) => block.block_header(),
+        }
+    }
+
+    /// Returns the SSZ encoding of the block of this variant.
+    pub fn as_ssz_bytes(&self) -> Vec<u8> {
+        match self {
+            BeaconBlockAny::Base(block) => block.as_ssz_bytes(),
+        }
+    }
+
+    /// Decodes the SSZ encoding of a block of `variant`.
+    pub fn from_ssz_bytes(bytes: &[u8], variant: BlockVariant) -> Result<Self, DecodeError> {
+        match variant {
+            BlockVariant::Base => BeaconBlock::from_ssz_bytes(bytes).map(BeaconBlockAny::Base),
+        }
+    }
+
+    /// Deserializes a block of `variant`, as serialized by `BeaconBlockAny` (or the block of that
+    /// variant).
+    pub fn deserialize_variant<'de, D: Deserializer<'de>>(
+        deserializer: D,
+        variant: BlockVariant,
+    ) -> Result<Self, D::Error> {
+        match variant {
+            BlockVariant::Base => <BeaconBlock<T> as serde::Deserialize>::deserialize(deserializer)
+                .map(BeaconBlockAny::Base),
+        }
+    }
+}
+
+impl<T: EthSpec> From<BeaconBlock<T>> for BeaconBlockAny<T> {
+    fn from(block: BeaconBlock<T>) -> Self {
+        BeaconBlockAny::Base(block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{SeedableRng, TestRandom, XorShiftRng};
+
+    #[test]
+    fn decodes_each_variant_as_its_block() {
+        let mut rng = XorShiftRng::from_seed([42; 16]);
+        let block: BeaconBlock<MinimalEthSpec> = BeaconBlock::random_for_test(&mut rng);
+        let any = BeaconBlockAny::from(block.clone());
+
+        assert_eq!(any.variant(), BlockVariant::Base);
+        assert_eq!(any.canonical_root(), block.canonical_root());
+        assert_eq!(any.as_ssz_bytes(), block.as_ssz_bytes());
+        assert_eq!(
+            BeaconBlockAny::from_ssz_bytes(&block.as_ssz_bytes(), BlockVariant::Base),
+            Ok(any.clone())
+        );
+
+        let json = serde_json::to_value(&any).expect("should serialize");
+        assert_eq!(
+            json,
+            serde_json::to_value(&block).expect("should serialize")
+        );
+        assert_eq!(
+            BeaconBlockAny::<MinimalEthSpec>::deserialize_variant(json, BlockVariant::Base)
+                .expect("should deserialize"),
+            any
+        );
+    }
+}
@@ -7,7 +7,7 @@ use eth2_hashing::hash;
 use int_to_bytes::{int_to_bytes32, int_to_bytes8};
 use pubkey_cache::PubkeyCache;
 use serde_derive::{Deserialize, Serialize};
-use ssz::ssz_encode;
+use ssz::{ssz_encode, Decode, DecodeError};
 use ssz_derive::{Decode, Encode};
 use ssz_types::{typenum::Unsigned, BitVector, FixedVector};
 use test_random_derive::TestRandom;
@@ -15,12 +15,14 @@ use tree_hash::TreeHash;
 use tree_hash_derive::TreeHash;
 
 pub use self::committee_cache::CommitteeCache;
+pub use self::fields::BeaconStateField;
 pub use beacon_state_types::*;
 
 #[macro_use]
 mod beacon_state_types;
 mod committee_cache;
 mod exit_cache;
+pub mod fields;
 mod pubkey_cache;
 mod tests;
 
@@ -99,10 +101,10 @@ where
     pub eth1_deposit_index: u64,
 
     // Registry
-    #[compare_fields(as_slice)]
-    pub validators: VariableList<Validator, T::ValidatorRegistryLimit>,
-    #[compare_fields(as_slice)]
-    pub balances: VariableList<u64, T::ValidatorRegistryLimit>,
+    #[compare_fields(as_iter)]
+    pub validators: PersistentList<Validator, T::ValidatorRegistryLimit>,
+    #[compare_fields(as_iter)]
+    pub balances: PersistentList<u64, T::ValidatorRegistryLimit>,
 
     // Shuffling
     pub start_shard: u64,
@@ -176,8 +178,8 @@ impl<T: EthSpec> BeaconState<T> {
             eth1_deposit_index: 0,
 
             // Validator registry
-            validators: VariableList::empty(), // Set later.
-            balances: VariableList::empty(),   // Set later.
+            validators: PersistentList::empty(), // Set later.
+            balances: PersistentList::empty(),   // Set later.
 
             // Shuffling
             start_shard: 0,
@@ -227,6 +229,20 @@ impl<T: EthSpec> BeaconState<T> {
         }
     }
 
+    /// Returns the SSZ bytes of the field `F` (see `beacon_state::fields`) of the SSZ-encoded
+    /// state `bytes`, without decoding or validating any other field.
+    ///
+    /// Useful for serving a single large field (e.g., `Validators`) without the cost of
+    /// decoding the entire state.
+    pub fn get_field_bytes<F: BeaconStateField<T>>(bytes: &[u8]) -> Result<&[u8], DecodeError> {
+        ssz::container_field_bytes(bytes, &fields::ssz_layout::<T>(), F::INDEX)
+    }
+
+    /// Decodes the field `F` of the SSZ-encoded state `bytes`, without decoding any other field.
+    pub fn decode_field<F: BeaconStateField<T>>(bytes: &[u8]) -> Result<F::Value, DecodeError> {
+        <F::Value as Decode>::from_ssz_bytes(Self::get_field_bytes::<F>(bytes)?)
+    }
+
     /// If a validator pubkey exists in the validator registry, returns `Some(i)`, otherwise
     /// returns `None`.
     ///
@@ -850,6 +866,23 @@ impl<T: EthSpec> BeaconState<T> {
         Ok(())
     }
 
+    /// Replaces the cache for some `RelativeEpoch` with a cache built elsewhere (e.g., for another
+    /// state with the same shuffling).
+    ///
+    /// Returns an error if `cache` is not initialized at `relative_epoch`.
+    pub fn set_committee_cache(
+        &mut self,
+        relative_epoch: RelativeEpoch,
+        cache: CommitteeCache,
+    ) -> Result<(), Error> {
+        if cache.is_initialized_at(relative_epoch.into_epoch(self.current_epoch())) {
+            self.committee_caches[Self::cache_index(relative_epoch)] = cache;
+            Ok(())
+        } else {
+            Err(Error::CommitteeCacheUninitialized(relative_epoch))
+        }
+    }
+
     /// Advances the cache for this state into the next epoch.
     ///
     /// This should be used if the `slot` of this state is advanced beyond an epoch boundary.
@@ -873,6 +906,12 @@ impl<T: EthSpec> BeaconState<T> {
         }
     }
 
+    /// Returns the committee cache for some `RelativeEpoch`. Returns an error if the cache has not
+    /// been initialized.
+    pub fn committee_cache(&self, relative_epoch: RelativeEpoch) -> Result<&CommitteeCache, Error> {
+        self.cache(relative_epoch)
+    }
+
     /// Returns the cache for some `RelativeEpoch`. Returns an error if the cache has not been
     /// initialized.
     fn cache(&self, relative_epoch: RelativeEpoch) -> Result<&CommitteeCache, Error> {
@@ -318,10 +318,14 @@ impl CommitteeCache {
 /// `epoch`.
 ///
 /// Spec v0.8.1
-pub fn get_active_validator_indices(validators: &[Validator], epoch: Epoch) -> Vec<usize> {
-    let mut active = Vec::with_capacity(validators.len());
-
-    for (index, validator) in validators.iter().enumerate() {
+pub fn get_active_validator_indices<'a, V>(validators: V, epoch: Epoch) -> Vec<usize>
+where
+    V: IntoIterator<Item = &'a Validator>,
+{
+    let validators = validators.into_iter();
+    let mut active = Vec::with_capacity(validators.size_hint().0);
+
+    for (index, validator) in validators.enumerate() {
         if validator.is_active_at(epoch) {
             active.push(index)
         }
@@ -336,6 +340,12 @@ pub fn get_active_validator_indices(validators: &[Validator], epoch: Epoch) -> V
 /// `epoch`.
 ///
 /// Spec v0.8.1
-fn get_active_validator_count(validators: &[Validator], epoch: Epoch) -> usize {
-    validators.iter().filter(|v| v.is_active_at(epoch)).count()
+fn get_active_validator_count<'a, V>(validators: V, epoch: Epoch) -> usize
+where
+    V: IntoIterator<Item = &'a Validator>,
+{
+    validators
+        .into_iter()
+        .filter(|v| v.is_active_at(epoch))
+        .count()
 }
@@ -8,9 +8,12 @@ pub struct ExitCache(HashMap<Epoch, u64>);
 
 impl ExitCache {
     /// Add all validators with a non-trivial exit epoch to the cache.
-    pub fn build_from_registry(&mut self, validators: &[Validator], spec: &ChainSpec) {
+    pub fn build_from_registry<'a, V>(&mut self, validators: V, spec: &ChainSpec)
+    where
+        V: IntoIterator<Item = &'a Validator>,
+    {
         validators
-            .iter()
+            .into_iter()
             .filter(|validator| validator.exit_epoch != spec.far_future_epoch)
             .for_each(|validator| self.record_validator_exit(validator.exit_epoch));
     }
@@ -0,0 +1,64 @@
+//! A marker type for each SSZ field of `BeaconState`, allowing a single field to be read from the
+//! SSZ bytes of a state without decoding the rest of it (e.g., with
+//! `BeaconState::get_field_bytes::<Validators>(&bytes)`).
+use crate::{
+    BeaconBlockHeader, Checkpoint, Crosslink, EthSpec, Hash256, PendingAttestation, Validator,
+};
+use ssz::{Decode, FieldLayout};
+use ssz_types::{BitVector, FixedVector, PersistentList, VariableList};
+
+/// A field of the SSZ encoding of a `BeaconState<T>`.
+pub trait BeaconStateField<T: EthSpec> {
+    /// The type of the field.
+    type Value: Decode;
+    /// The position of the field in the SSZ container.
+    const INDEX: usize;
+}
+
+macro_rules! beacon_state_fields {
+    ($($index: expr => $name: ident: $value: ty,)*) => {
+        $(
+            pub struct $name;
+
+            impl<T: EthSpec> BeaconStateField<T> for $name {
+                type Value = $value;
+                const INDEX: usize = $index;
+            }
+        )*
+
+        /// The layout of each field of the SSZ encoding of a `BeaconState<T>`, in order.
+        pub fn ssz_layout<T: EthSpec>() -> Vec<FieldLayout> {
+            vec![$(FieldLayout::of::<<$name as BeaconStateField<T>>::Value>()),*]
+        }
+    };
+}
+
+beacon_state_fields! {
+    0 => GenesisTime: u64,
+    1 => Slot: crate::Slot,
+    2 => Fork: crate::Fork,
+    3 => LatestBlockHeader: BeaconBlockHeader,
+    4 => BlockRoots: FixedVector<Hash256, T::SlotsPerHistoricalRoot>,
+    5 => StateRoots: FixedVector<Hash256, T::SlotsPerHistoricalRoot>,
+    6 => HistoricalRoots: VariableList<Hash256, T::HistoricalRootsLimit>,
+    7 => Eth1Data: crate::Eth1Data,
+    8 => Eth1DataVotes: VariableList<crate::Eth1Data, T::SlotsPerEth1VotingPeriod>,
+    9 => Eth1DepositIndex: u64,
+    10 => Validators: PersistentList<Validator, T::ValidatorRegistryLimit>,
+    11 => Balances: PersistentList<u64, T::ValidatorRegistryLimit>,
+    12 => StartShard: u64,
+    13 => RandaoMixes: FixedVector<Hash256, T::EpochsPerHistoricalVector>,
+    14 => ActiveIndexRoots: FixedVector<Hash256, T::EpochsPerHistoricalVector>,
+    15 => CompactCommitteesRoots: FixedVector<Hash256, T::EpochsPerHistoricalVector>,
+    16 => Slashings: FixedVector<u64, T::EpochsPerSlashingsVector>,
+    17 => PreviousEpochAttestations:
+        VariableList<PendingAttestation<T>, T::MaxPendingAttestations>,
+    18 => CurrentEpochAttestations:
+        VariableList<PendingAttestation<T>, T::MaxPendingAttestations>,
+    19 => PreviousCrosslinks: FixedVector<Crosslink, T::ShardCount>,
+    20 => CurrentCrosslinks: FixedVector<Crosslink, T::ShardCount>,
+    21 => JustificationBits: BitVector<T::JustificationBitsLength>,
+    22 => PreviousJustifiedCheckpoint: Checkpoint,
+    23 => CurrentJustifiedCheckpoint: Checkpoint,
+    24 => FinalizedCheckpoint: Checkpoint,
+}
@@ -198,6 +198,70 @@ fn tree_hash_cache() {
     assert_eq!(root.as_bytes(), &state.tree_hash_root()[..]);
 }
 
+#[test]
+fn clones_do_not_share_modifications() {
+    let spec = MinimalEthSpec::default_spec();
+    let builder: TestingBeaconStateBuilder<MinimalEthSpec> =
+        TestingBeaconStateBuilder::from_deterministic_keypairs(16, &spec);
+    let (state, _keypairs) = builder.build();
+
+    let mut clone = state.clone();
+    assert_eq!(clone, state);
+
+    clone.validators[3].slashed = true;
+    clone.balances[5] += 1;
+    assert!(!state.validators[3].slashed);
+    assert_eq!(state.balances[5] + 1, clone.balances[5]);
+    assert_ne!(clone.validators, state.validators);
+    assert_ne!(clone.tree_hash_root(), state.tree_hash_root());
+}
+
+#[test]
+fn decode_single_fields() {
+    use crate::beacon_state::fields::*;
+    use crate::test_utils::{SeedableRng, TestRandom, XorShiftRng};
+    use ssz::Encode;
+
+    let mut rng = XorShiftRng::from_seed([42; 16]);
+    let state: BeaconState<MinimalEthSpec> = BeaconState::random_for_test(&mut rng);
+    let bytes = state.as_ssz_bytes();
+
+    type State = BeaconState<MinimalEthSpec>;
+    assert_eq!(
+        State::decode_field::<GenesisTime>(&bytes),
+        Ok(state.genesis_time)
+    );
+    assert_eq!(State::decode_field::<Slot>(&bytes), Ok(state.slot));
+    assert_eq!(
+        State::decode_field::<HistoricalRoots>(&bytes),
+        Ok(state.historical_roots.clone())
+    );
+    assert_eq!(
+        State::decode_field::<Validators>(&bytes),
+        Ok(state.validators.clone())
+    );
+    assert_eq!(
+        State::decode_field::<Balances>(&bytes),
+        Ok(state.balances.clone())
+    );
+    assert_eq!(
+        State::decode_field::<Slashings>(&bytes),
+        Ok(state.slashings.clone())
+    );
+    assert_eq!(
+        State::decode_field::<CurrentEpochAttestations>(&bytes),
+        Ok(state.current_epoch_attestations.clone())
+    );
+    assert_eq!(
+        State::decode_field::<FinalizedCheckpoint>(&bytes),
+        Ok(state.finalized_checkpoint.clone())
+    );
+    assert_eq!(
+        State::get_field_bytes::<Balances>(&bytes),
+        Ok(&state.balances.as_ssz_bytes()[..])
+    );
+}
+
 /// Tests committee-specific components
 #[cfg(test)]
 mod committees {
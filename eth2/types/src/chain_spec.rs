@@ -1,7 +1,7 @@
 use crate::*;
 use int_to_bytes::int_to_bytes4;
 use serde_derive::{Deserialize, Serialize};
-use utils::{u8_from_hex_str, u8_to_hex_str};
+use utils::{fork_from_hex_str, fork_to_hex_str, u8_from_hex_str, u8_to_hex_str};
 
 /// Each of the BLS signature domains.
 ///
@@ -13,6 +13,36 @@ pub enum Domain {
     Deposit,
     VoluntaryExit,
     Transfer,
+    /// The selection proofs by which validators are selected to aggregate attestations.
+    SelectionProof,
+}
+
+/// The domain of validator registrations with external block builders, `DomainType('0x00000001')`.
+///
+/// Registrations are not processed by the beacon chain, so the domain is not configurable.
+pub const DOMAIN_APPLICATION_BUILDER: u32 = 0x0100_0000;
+
+/// The domain of selection proofs, `DomainType('0x06000000')`.
+///
+/// Selection proofs are not processed by the beacon chain, so the domain is not configurable.
+pub const DOMAIN_SELECTION_PROOF: u32 = 6;
+
+/// A fork which is scheduled to begin at some epoch.
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledFork {
+    /// A human-readable name for the fork.
+    pub name: String,
+    /// The first epoch of the fork.
+    pub epoch: Epoch,
+    /// The fork version used from `epoch` onwards.
+    #[serde(
+        serialize_with = "fork_to_hex_str",
+        deserialize_with = "fork_from_hex_str"
+    )]
+    pub version: [u8; 4],
+    /// The shape of blocks from `epoch` onwards.
+    #[serde(default)]
+    pub block_variant: BlockVariant,
 }
 
 /// Holds all the "constants" for a BeaconChain.
@@ -93,6 +123,11 @@ pub struct ChainSpec {
 
     pub boot_nodes: Vec<String>,
     pub network_id: u8,
+
+    /// Forks which begin after genesis, in ascending epoch order.
+    ///
+    /// Must be the last field, since TOML requires arrays of tables to follow all other values.
+    pub scheduled_forks: Vec<ScheduledFork>,
 }
 
 impl ChainSpec {
@@ -107,6 +142,7 @@ impl ChainSpec {
             Domain::Deposit => self.domain_deposit,
             Domain::VoluntaryExit => self.domain_voluntary_exit,
             Domain::Transfer => self.domain_transfer,
+            Domain::SelectionProof => DOMAIN_SELECTION_PROOF,
         };
 
         let mut bytes: Vec<u8> = int_to_bytes4(domain_constant);
@@ -118,6 +154,52 @@ impl ChainSpec {
         u64::from_le_bytes(fork_and_domain)
     }
 
+    /// Get the domain of validator registrations with external block builders.
+    ///
+    /// Registrations are signed with the genesis fork version, so they remain valid across forks.
+    pub fn get_builder_domain(&self) -> u64 {
+        let genesis_fork = Fork::genesis(Epoch::new(0));
+
+        let mut bytes: Vec<u8> = int_to_bytes4(DOMAIN_APPLICATION_BUILDER);
+        bytes.append(&mut genesis_fork.current_version.to_vec());
+
+        let mut fork_and_domain = [0; 8];
+        fork_and_domain.copy_from_slice(&bytes);
+
+        u64::from_le_bytes(fork_and_domain)
+    }
+
+    /// Returns `fork` advanced through all scheduled forks which begin after `fork.epoch`, up to
+    /// and including `epoch`.
+    ///
+    /// If no such forks exist, `fork` is returned unchanged.
+    pub fn upgrade_fork(&self, fork: &Fork, epoch: Epoch) -> Fork {
+        self.scheduled_forks
+            .iter()
+            .filter(|scheduled| scheduled.epoch > fork.epoch && scheduled.epoch <= epoch)
+            .fold(fork.clone(), |fork, scheduled| Fork {
+                previous_version: fork.current_version,
+                current_version: scheduled.version,
+                epoch: scheduled.epoch,
+            })
+    }
+
+    /// Returns the shape of blocks in `epoch`, that of the latest fork to begin by `epoch`.
+    pub fn block_variant(&self, epoch: Epoch) -> BlockVariant {
+        self.scheduled_forks
+            .iter()
+            .rev()
+            .find(|scheduled| scheduled.epoch <= epoch)
+            .map_or(BlockVariant::Base, |scheduled| scheduled.block_variant)
+    }
+
+    /// Returns the first fork scheduled to begin after `epoch`, if any.
+    pub fn next_fork(&self, epoch: Epoch) -> Option<&ScheduledFork> {
+        self.scheduled_forks
+            .iter()
+            .find(|scheduled| scheduled.epoch > epoch)
+    }
+
     /// Returns a `ChainSpec` compatible with the Ethereum Foundation specification.
     ///
     /// Spec v0.8.1
@@ -191,6 +273,7 @@ impl ChainSpec {
              */
             boot_nodes: vec![],
             network_id: 1, // mainnet network id
+            scheduled_forks: vec![],
         }
     }
 
@@ -269,5 +352,65 @@ mod tests {
         test_domain(Domain::Deposit, spec.domain_deposit, &spec);
         test_domain(Domain::VoluntaryExit, spec.domain_voluntary_exit, &spec);
         test_domain(Domain::Transfer, spec.domain_transfer, &spec);
+        test_domain(Domain::SelectionProof, DOMAIN_SELECTION_PROOF, &spec);
+    }
+
+    #[test]
+    fn test_get_builder_domain() {
+        let spec = ChainSpec::mainnet();
+
+        assert_eq!(
+            int_to_bytes8(spec.get_builder_domain()),
+            vec![0, 0, 0, 1, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_upgrade_fork() {
+        let scheduled_fork = |epoch, version| ScheduledFork {
+            name: format!("fork_{}", epoch),
+            epoch: Epoch::new(epoch),
+            version,
+            block_variant: BlockVariant::Base,
+        };
+        let spec = ChainSpec {
+            scheduled_forks: vec![scheduled_fork(4, [1; 4]), scheduled_fork(8, [2; 4])],
+            ..ChainSpec::mainnet()
+        };
+        let genesis = Fork::genesis(Epoch::new(0));
+
+        assert_eq!(spec.upgrade_fork(&genesis, Epoch::new(3)), genesis);
+
+        let first = spec.upgrade_fork(&genesis, Epoch::new(4));
+        assert_eq!(
+            first,
+            Fork {
+                previous_version: [0; 4],
+                current_version: [1; 4],
+                epoch: Epoch::new(4),
+            }
+        );
+        assert_eq!(spec.upgrade_fork(&first, Epoch::new(7)), first);
+
+        let second = Fork {
+            previous_version: [1; 4],
+            current_version: [2; 4],
+            epoch: Epoch::new(8),
+        };
+        assert_eq!(spec.upgrade_fork(&first, Epoch::new(8)), second);
+        assert_eq!(spec.upgrade_fork(&genesis, Epoch::new(9)), second);
+
+        assert_eq!(
+            spec.next_fork(Epoch::new(4)),
+            Some(&spec.scheduled_forks[1])
+        );
+        assert_eq!(spec.next_fork(Epoch::new(8)), None);
+        assert_eq!(spec.block_variant(Epoch::new(9)), BlockVariant::Base);
+
+        // Domains switch to the new fork version at the fork epoch.
+        assert_ne!(
+            spec.get_domain(Epoch::new(3), Domain::BeaconProposer, &first),
+            spec.get_domain(Epoch::new(4), Domain::BeaconProposer, &first)
+        );
     }
 }
@@ -0,0 +1,144 @@
+use crate::*;
+
+/// An error returned when a `ChainSpecBuilder` is unable to build a `ChainSpec`.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Error {
+    /// Slots must last at least one second.
+    ZeroSecondsPerSlot,
+    /// The epoch length requested differs from that of the `EthSpec`, which fixes it at compile
+    /// time.
+    SlotsPerEpochMismatch { requested: u64, eth_spec: u64 },
+}
+
+/// Builds a `ChainSpec` from a preset, overriding the parameters most often changed for tests and
+/// devnets.
+///
+/// ## Example
+///
+/// ```
+/// use types::{ChainSpecBuilder, MinimalEthSpec};
+///
+/// let spec = ChainSpecBuilder::minimal()
+///     .seconds_per_slot(2)
+///     .genesis_time(1_567_000_000)
+///     .build::<MinimalEthSpec>()
+///     .expect("should build spec");
+///
+/// assert_eq!(spec.seconds_per_slot, 2);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ChainSpecBuilder {
+    spec: ChainSpec,
+    slots_per_epoch: Option<u64>,
+}
+
+impl ChainSpecBuilder {
+    /// Starts from `spec`.
+    pub fn from_spec(spec: ChainSpec) -> Self {
+        Self {
+            spec,
+            slots_per_epoch: None,
+        }
+    }
+
+    /// Starts from the Ethereum Foundation mainnet preset.
+    pub fn mainnet() -> Self {
+        Self::from_spec(ChainSpec::mainnet())
+    }
+
+    /// Starts from the Ethereum Foundation minimal preset.
+    pub fn minimal() -> Self {
+        Self::from_spec(ChainSpec::minimal())
+    }
+
+    /// Starts from the interop testing preset.
+    pub fn interop() -> Self {
+        Self::from_spec(ChainSpec::interop())
+    }
+
+    /// Sets the duration of each slot.
+    pub fn seconds_per_slot(mut self, seconds_per_slot: u64) -> Self {
+        self.spec.seconds_per_slot = seconds_per_slot;
+        self
+    }
+
+    /// Sets `min_genesis_time`, which is the genesis time of states built for testing (e.g., by
+    /// `TestingBeaconStateBuilder`).
+    pub fn genesis_time(mut self, genesis_time: u64) -> Self {
+        self.spec.min_genesis_time = genesis_time;
+        self
+    }
+
+    /// Requires the built spec to be used with `slots_per_epoch` slots in each epoch.
+    ///
+    /// The epoch length is a type-level parameter of the `EthSpec`, so it is checked when
+    /// building rather than set.
+    pub fn slots_per_epoch(mut self, slots_per_epoch: u64) -> Self {
+        self.slots_per_epoch = Some(slots_per_epoch);
+        self
+    }
+
+    /// Returns the spec, if it is consistent with `E`.
+    pub fn build<E: EthSpec>(self) -> Result<ChainSpec, Error> {
+        if self.spec.seconds_per_slot == 0 {
+            return Err(Error::ZeroSecondsPerSlot);
+        }
+
+        match self.slots_per_epoch {
+            Some(requested) if requested != E::slots_per_epoch() => {
+                Err(Error::SlotsPerEpochMismatch {
+                    requested,
+                    eth_spec: E::slots_per_epoch(),
+                })
+            }
+            _ => Ok(self.spec),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_presets() {
+        let spec = ChainSpecBuilder::interop()
+            .seconds_per_slot(3)
+            .genesis_time(42)
+            .slots_per_epoch(MinimalEthSpec::slots_per_epoch())
+            .build::<MinimalEthSpec>()
+            .expect("should build spec");
+
+        assert_eq!(
+            spec,
+            ChainSpec {
+                seconds_per_slot: 3,
+                min_genesis_time: 42,
+                ..ChainSpec::interop()
+            }
+        );
+        assert_eq!(
+            ChainSpecBuilder::minimal().build::<MinimalEthSpec>(),
+            Ok(ChainSpec::minimal())
+        );
+    }
+
+    #[test]
+    fn rejects_inconsistent_specs() {
+        assert_eq!(
+            ChainSpecBuilder::mainnet()
+                .seconds_per_slot(0)
+                .build::<MainnetEthSpec>(),
+            Err(Error::ZeroSecondsPerSlot)
+        );
+        assert_eq!(
+            ChainSpecBuilder::mainnet()
+                .slots_per_epoch(8)
+                .build::<MainnetEthSpec>(),
+            Err(Error::SlotsPerEpochMismatch {
+                requested: 8,
+                eth_spec: 64,
+            })
+        );
+    }
+}
@@ -12,10 +12,12 @@ pub mod attestation_data_and_custody_bit;
 pub mod attestation_duty;
 pub mod attester_slashing;
 pub mod beacon_block;
+pub mod beacon_block_any;
 pub mod beacon_block_body;
 pub mod beacon_block_header;
 pub mod beacon_state;
 pub mod chain_spec;
+pub mod chain_spec_builder;
 pub mod checkpoint;
 pub mod compact_committee;
 pub mod crosslink;
@@ -38,6 +40,7 @@ pub mod relative_epoch;
 pub mod slot_epoch;
 pub mod slot_height;
 pub mod validator;
+pub mod validator_registration;
 
 use ethereum_types::{H160, H256, U256};
 use std::collections::HashMap;
@@ -48,10 +51,12 @@ pub use crate::attestation_data_and_custody_bit::AttestationDataAndCustodyBit;
 pub use crate::attestation_duty::AttestationDuty;
 pub use crate::attester_slashing::AttesterSlashing;
 pub use crate::beacon_block::BeaconBlock;
+pub use crate::beacon_block_any::{BeaconBlockAny, BlockVariant};
 pub use crate::beacon_block_body::BeaconBlockBody;
 pub use crate::beacon_block_header::BeaconBlockHeader;
 pub use crate::beacon_state::{Error as BeaconStateError, *};
-pub use crate::chain_spec::{ChainSpec, Domain};
+pub use crate::chain_spec::{ChainSpec, Domain, ScheduledFork};
+pub use crate::chain_spec_builder::{ChainSpecBuilder, Error as ChainSpecBuilderError};
 pub use crate::checkpoint::Checkpoint;
 pub use crate::compact_committee::CompactCommittee;
 pub use crate::crosslink::Crosslink;
@@ -70,6 +75,7 @@ pub use crate::slot_epoch::{Epoch, Slot};
 pub use crate::slot_height::SlotHeight;
 pub use crate::transfer::Transfer;
 pub use crate::validator::Validator;
+pub use crate::validator_registration::ValidatorRegistration;
 pub use crate::voluntary_exit::VoluntaryExit;
 
 pub type Shard = u64;
@@ -87,4 +93,7 @@ pub type AttesterMap = HashMap<(u64, u64), Vec<usize>>;
 pub type ProposerMap = HashMap<u64, usize>;
 
 pub use bls::{AggregatePublicKey, AggregateSignature, Keypair, PublicKey, SecretKey, Signature};
-pub use ssz_types::{typenum, typenum::Unsigned, BitList, BitVector, FixedVector, VariableList};
+pub use ssz_types::{
+    length, typenum, typenum::Unsigned, BitList, BitVector, Bitfield, FixedVector, PersistentList,
+    VariableList,
+};
@@ -0,0 +1,41 @@
+use crate::{test_utils::TestRandom, Address, PublicKey};
+use bls::Signature;
+
+use serde_derive::{Deserialize, Serialize};
+use ssz_derive::{Decode, Encode};
+use test_random_derive::TestRandom;
+use tree_hash::TreeHash;
+use tree_hash_derive::{SignedRoot, TreeHash};
+
+/// The preferences of a validator for its block proposals, registered with external block
+/// builders.
+///
+/// Signed with `ChainSpec::get_builder_domain()`.
+#[derive(
+    Debug,
+    PartialEq,
+    Clone,
+    Serialize,
+    Deserialize,
+    Encode,
+    Decode,
+    TreeHash,
+    TestRandom,
+    SignedRoot,
+)]
+pub struct ValidatorRegistration {
+    pub fee_recipient: Address,
+    pub gas_limit: u64,
+    /// The time of the registration, in seconds since the UNIX epoch. Builders use the latest.
+    pub timestamp: u64,
+    pub pubkey: PublicKey,
+    #[signed_root(skip_hashing)]
+    pub signature: Signature,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    ssz_tests!(ValidatorRegistration);
+}
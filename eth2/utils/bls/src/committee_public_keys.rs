@@ -0,0 +1,92 @@
+use super::{AggregatePublicKey, PublicKey};
+
+/// The public keys of a committee, with their aggregate precomputed.
+///
+/// Attestations are commonly signed by every member of their committee, in which case the
+/// precomputed aggregate is used rather than adding the key of each member again.
+#[derive(Debug, Clone)]
+pub struct CommitteePublicKeys {
+    keys: Vec<PublicKey>,
+    aggregate: AggregatePublicKey,
+}
+
+impl CommitteePublicKeys {
+    pub fn new(keys: Vec<PublicKey>) -> Self {
+        let mut aggregate = AggregatePublicKey::new();
+        for key in &keys {
+            aggregate.add(key);
+        }
+        Self { keys, aggregate }
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Returns the aggregate public key of every member.
+    pub fn aggregate(&self) -> &AggregatePublicKey {
+        &self.aggregate
+    }
+
+    /// Returns the aggregate public key of the members at `positions` (their indices in the
+    /// committee), or `None` if a position is not in the committee.
+    pub fn aggregate_of(&self, positions: &[usize]) -> Option<AggregatePublicKey> {
+        if positions.len() == self.keys.len()
+            && positions
+                .iter()
+                .enumerate()
+                .all(|(i, &position)| i == position)
+        {
+            return Some(self.aggregate.clone());
+        }
+
+        let mut aggregate = AggregatePublicKey::new();
+        for &position in positions {
+            aggregate.add(self.keys.get(position)?);
+        }
+        Some(aggregate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Keypair;
+    use super::*;
+
+    fn aggregate_bytes(keys: &[&PublicKey]) -> Vec<u8> {
+        let mut aggregate = AggregatePublicKey::new();
+        for key in keys {
+            aggregate.add(key);
+        }
+        aggregate.as_raw().as_bytes()
+    }
+
+    #[test]
+    pub fn test_aggregates_members() {
+        let keys: Vec<PublicKey> = (0..4).map(|_| Keypair::random().pk).collect();
+        let committee = CommitteePublicKeys::new(keys.clone());
+
+        let all: Vec<&PublicKey> = keys.iter().collect();
+        assert_eq!(
+            committee.aggregate().as_raw().as_bytes(),
+            aggregate_bytes(&all)
+        );
+        assert_eq!(
+            committee
+                .aggregate_of(&[0, 1, 2, 3])
+                .unwrap()
+                .as_raw()
+                .as_bytes(),
+            aggregate_bytes(&all)
+        );
+        assert_eq!(
+            committee.aggregate_of(&[1, 3]).unwrap().as_raw().as_bytes(),
+            aggregate_bytes(&[&keys[1], &keys[3]])
+        );
+        assert!(committee.aggregate_of(&[1, 4]).is_none());
+    }
+}
@@ -3,13 +3,17 @@ extern crate ssz;
 
 #[macro_use]
 mod macros;
+mod committee_public_keys;
 mod keypair;
 mod public_key_bytes;
+mod public_key_cache;
 mod secret_key;
 mod signature_bytes;
 
+pub use crate::committee_public_keys::CommitteePublicKeys;
 pub use crate::keypair::Keypair;
 pub use crate::public_key_bytes::PublicKeyBytes;
+pub use crate::public_key_cache::PublicKeyCache;
 pub use crate::secret_key::SecretKey;
 pub use crate::signature_bytes::SignatureBytes;
 pub use milagro_bls::{compress_g2, hash_on_g2};
@@ -123,6 +123,12 @@ macro_rules! bytes_struct {
 
         impl Eq for $name {}
 
+        impl std::hash::Hash for $name {
+            fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+                self.0[..].hash(state)
+            }
+        }
+
         impl std::convert::TryInto<$type> for &$name {
             type Error = ssz::DecodeError;
 
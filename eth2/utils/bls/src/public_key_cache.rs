@@ -0,0 +1,122 @@
+use super::{PublicKey, PublicKeyBytes};
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::sync::RwLock;
+
+/// The number of keys a `PublicKeyCache::new()` stores before it is emptied.
+pub const DEFAULT_CAPACITY: usize = 16_384;
+
+/// Decompresses each `PublicKeyBytes` at most once.
+///
+/// Decompressing a public key (recovering the y coordinate of its point) is far more costly than
+/// looking it up, so keys which are verified repeatedly (e.g., the keys of deposits, which are
+/// verified again for each block which includes them) are decompressed on first use and cloned
+/// from the cache thereafter. Bytes which are not a valid public key are cached too.
+///
+/// The cache stores at most `capacity` keys: it is emptied before a key is inserted into a full
+/// cache, so that bytes which are never seen again (e.g., invalid keys) cannot grow it without
+/// bound.
+#[derive(Debug)]
+pub struct PublicKeyCache {
+    keys: RwLock<HashMap<PublicKeyBytes, Option<PublicKey>>>,
+    capacity: usize,
+}
+
+impl Default for PublicKeyCache {
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+}
+
+impl PublicKeyCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a cache which stores at most `capacity` keys.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            keys: RwLock::new(HashMap::new()),
+            capacity,
+        }
+    }
+
+    /// Returns the public key of `bytes`, or `None` if they are not a valid public key.
+    pub fn decompress(&self, bytes: &PublicKeyBytes) -> Option<PublicKey> {
+        if let Some(pubkey) = self
+            .keys
+            .read()
+            .expect("public key cache lock is not poisoned")
+            .get(bytes)
+        {
+            return pubkey.clone();
+        }
+
+        let pubkey: Option<PublicKey> = bytes.try_into().ok();
+        let mut keys = self
+            .keys
+            .write()
+            .expect("public key cache lock is not poisoned");
+        if keys.len() >= self.capacity && !keys.contains_key(bytes) {
+            keys.clear();
+        }
+        keys.insert(bytes.clone(), pubkey.clone());
+        pubkey
+    }
+
+    /// Returns the number of cached keys, including invalid ones.
+    pub fn len(&self) -> usize {
+        self.keys
+            .read()
+            .expect("public key cache lock is not poisoned")
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Keypair, BLS_PUBLIC_KEY_BYTE_SIZE};
+    use super::*;
+
+    #[test]
+    pub fn test_decompresses_once() {
+        let cache = PublicKeyCache::new();
+        let keypair = Keypair::random();
+        let bytes = PublicKeyBytes::from(keypair.pk.clone());
+
+        assert_eq!(cache.decompress(&bytes), Some(keypair.pk.clone()));
+        assert_eq!(cache.decompress(&bytes), Some(keypair.pk));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    #[cfg(not(feature = "fake_crypto"))]
+    pub fn test_caches_invalid_keys() {
+        let cache = PublicKeyCache::new();
+        let mut invalid = [0; BLS_PUBLIC_KEY_BYTE_SIZE];
+        invalid[0] = 255;
+        let bytes = PublicKeyBytes::from_bytes(&invalid).unwrap();
+
+        assert_eq!(cache.decompress(&bytes), None);
+        assert_eq!(cache.decompress(&bytes), None);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    pub fn test_never_exceeds_capacity() {
+        let cache = PublicKeyCache::with_capacity(2);
+        let keys: Vec<PublicKeyBytes> = (0..5)
+            .map(|_| PublicKeyBytes::from(Keypair::random().pk))
+            .collect();
+
+        for (i, bytes) in keys.iter().enumerate() {
+            assert!(cache.decompress(bytes).is_some());
+            assert!(cache.len() <= 2);
+            assert_eq!(cache.len(), i % 2 + 1);
+        }
+    }
+}
@@ -112,17 +112,29 @@ impl Comparison {
     }
 
     pub fn from_slice<T: Debug + PartialEq<T>>(field_name: String, a: &[T], b: &[T]) -> Self {
+        Self::from_iter(field_name, a.iter(), b.iter())
+    }
+
+    /// As `from_slice`, for collections which are not slices (e.g., `PersistentList`).
+    pub fn from_iter<'a, T, I>(field_name: String, a: I, b: I) -> Self
+    where
+        T: Debug + PartialEq<T> + 'a,
+        I: Iterator<Item = &'a T>,
+    {
         let mut children = vec![];
+        let mut equal = true;
+        let (mut a, mut b) = (a.fuse(), b.fuse());
 
-        for i in 0..std::cmp::max(a.len(), b.len()) {
-            children.push(FieldComparison::new(
-                format!("{:}", i),
-                &a.get(i),
-                &b.get(i),
-            ));
+        for i in 0.. {
+            let (left, right) = (a.next(), b.next());
+            if left.is_none() && right.is_none() {
+                break;
+            }
+            equal &= left == right;
+            children.push(FieldComparison::new(format!("{:}", i), &left, &right));
         }
 
-        Self::parent(field_name, a == b, children)
+        Self::parent(field_name, equal, children)
     }
 
     pub fn retain_children<F>(&mut self, f: F)
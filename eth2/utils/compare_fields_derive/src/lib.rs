@@ -6,12 +6,15 @@ use quote::quote;
 use syn::{parse_macro_input, DeriveInput};
 
 fn is_slice(field: &syn::Field) -> bool {
-    for attr in &field.attrs {
-        if attr.tts.to_string() == "( as_slice )" {
-            return true;
-        }
-    }
-    false
+    has_attr(field, "( as_slice )")
+}
+
+fn is_iter(field: &syn::Field) -> bool {
+    has_attr(field, "( as_iter )")
+}
+
+fn has_attr(field: &syn::Field, tts: &str) -> bool {
+    field.attrs.iter().any(|attr| attr.tts.to_string() == tts)
 }
 
 #[proc_macro_derive(CompareFields, attributes(compare_fields))]
@@ -45,6 +48,14 @@ pub fn compare_fields_derive(input: TokenStream) -> TokenStream {
                         &b.#ident_b)
                 );
             }
+        } else if is_iter(field) {
+            quote! {
+                comparisons.push(compare_fields::Comparison::from_iter(
+                        #field_name.to_string(),
+                        self.#ident_a.iter(),
+                        b.#ident_b.iter())
+                );
+            }
         } else {
             quote! {
                 comparisons.push(
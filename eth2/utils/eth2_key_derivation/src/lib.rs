@@ -0,0 +1,214 @@
+//! Hierarchical, deterministic derivation of BLS12-381 secret keys from a seed, as per
+//! [EIP-2333](https://eips.ethereum.org/EIPS/eip-2333).
+//!
+//! Also provides the standard paths of validator keys, as per
+//! [EIP-2334](https://eips.ethereum.org/EIPS/eip-2334):
+//!
+//! - Withdrawal keys: `m/12381/3600/<validator index>/0`
+//! - Signing (voting) keys: `m/12381/3600/<validator index>/0/0`
+
+use bls::{SecretKey, BLS_SECRET_KEY_BYTE_SIZE};
+use hkdf::Hkdf;
+use num_bigint::BigUint;
+use sha2::{Digest, Sha256};
+
+/// The order of the BLS12-381 curve, in decimal.
+const CURVE_ORDER: &[u8] =
+    b"52435875175126190479447740508185965837690552500527637822603658699938581184513";
+/// The salt of `HKDF_mod_r`, which is hashed before each attempt.
+const HKDF_MOD_R_SALT: &[u8] = b"BLS-SIG-KEYGEN-SALT-";
+/// The length of the output keying material of `HKDF_mod_r`.
+const HKDF_MOD_R_L: usize = 48;
+/// The number of chunks in a Lamport secret key.
+const LAMPORT_CHUNKS: usize = 255;
+const HASH_BYTES: usize = 32;
+
+/// The minimum length of a seed from which a master key may be derived.
+pub const MIN_SEED_BYTES: usize = 32;
+/// The `purpose` of EIP-2334 paths.
+pub const PURPOSE: u32 = 12381;
+/// The `coin_type` of Eth2 keys in EIP-2334 paths.
+pub const COIN_TYPE: u32 = 3600;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Error {
+    /// The seed is shorter than `MIN_SEED_BYTES`.
+    SeedTooShort(usize),
+    /// The path is not of the form `m/<index>/<index>/..`.
+    InvalidPath(String),
+}
+
+/// A secret key within the tree of keys derived from some seed.
+#[derive(PartialEq, Clone)]
+pub struct DerivedKey(BigUint);
+
+impl DerivedKey {
+    /// Derive the master key (i.e., `m`) of `seed`.
+    pub fn from_seed(seed: &[u8]) -> Result<Self, Error> {
+        if seed.len() < MIN_SEED_BYTES {
+            return Err(Error::SeedTooShort(seed.len()));
+        }
+
+        Ok(DerivedKey(hkdf_mod_r(seed)))
+    }
+
+    /// Derive the key at `path` (e.g., `m/12381/3600/0/0/0`) from `seed`.
+    pub fn from_path(seed: &[u8], path: &str) -> Result<Self, Error> {
+        let master = Self::from_seed(seed)?;
+
+        Ok(parse_path(path)?
+            .into_iter()
+            .fold(master, |key, index| key.child(index)))
+    }
+
+    /// Derive the child of `self` at `index`.
+    pub fn child(&self, index: u32) -> Self {
+        DerivedKey(hkdf_mod_r(&parent_sk_to_lamport_pk(&self.0, index)))
+    }
+
+    /// Returns the key as a BLS secret key.
+    pub fn secret_key(&self) -> SecretKey {
+        let bytes = self.0.to_bytes_be();
+        let mut padded = [0; BLS_SECRET_KEY_BYTE_SIZE];
+        padded[BLS_SECRET_KEY_BYTE_SIZE - bytes.len()..].copy_from_slice(&bytes);
+
+        SecretKey::from_bytes(&padded).expect("keys are always less than the curve order")
+    }
+}
+
+/// Returns the EIP-2334 path of the withdrawal key of the validator at `index`.
+pub fn withdrawal_key_path(index: u32) -> String {
+    format!("m/{}/{}/{}/0", PURPOSE, COIN_TYPE, index)
+}
+
+/// Returns the EIP-2334 path of the signing key of the validator at `index`.
+pub fn signing_key_path(index: u32) -> String {
+    format!("{}/0", withdrawal_key_path(index))
+}
+
+/// Parses a path of the form `m/<index>/<index>/..` into its indices.
+pub fn parse_path(path: &str) -> Result<Vec<u32>, Error> {
+    let mut parts = path.split('/');
+
+    if parts.next() != Some("m") {
+        return Err(Error::InvalidPath(path.to_string()));
+    }
+
+    parts
+        .map(|part| {
+            part.parse::<u32>()
+                .map_err(|_| Error::InvalidPath(path.to_string()))
+        })
+        .collect()
+}
+
+fn sha256(bytes: &[u8]) -> Vec<u8> {
+    Sha256::digest(bytes).to_vec()
+}
+
+/// Maps `ikm` to a non-zero integer less than the curve order.
+fn hkdf_mod_r(ikm: &[u8]) -> BigUint {
+    let curve_order = BigUint::parse_bytes(CURVE_ORDER, 10).expect("curve order is valid");
+
+    let mut ikm = ikm.to_vec();
+    ikm.push(0);
+    let info = (HKDF_MOD_R_L as u16).to_be_bytes();
+
+    let mut salt = HKDF_MOD_R_SALT.to_vec();
+    loop {
+        salt = sha256(&salt);
+
+        let mut okm = [0; HKDF_MOD_R_L];
+        Hkdf::<Sha256>::new(Some(&salt), &ikm)
+            .expand(&info, &mut okm)
+            .expect("HKDF_MOD_R_L is a valid length");
+
+        let key = BigUint::from_bytes_be(&okm) % &curve_order;
+        if key != BigUint::from(0_u8) {
+            break key;
+        }
+    }
+}
+
+/// Expands `ikm` into the chunks of a Lamport secret key.
+fn ikm_to_lamport_sk(ikm: &[u8], salt: &[u8]) -> Vec<u8> {
+    let mut okm = vec![0; LAMPORT_CHUNKS * HASH_BYTES];
+    Hkdf::<Sha256>::new(Some(salt), ikm)
+        .expand(&[], &mut okm)
+        .expect("a Lamport key is a valid length");
+    okm
+}
+
+/// Returns the compressed Lamport public key from which the child of `parent` at `index` is
+/// derived.
+fn parent_sk_to_lamport_pk(parent: &BigUint, index: u32) -> Vec<u8> {
+    let salt = index.to_be_bytes();
+
+    let bytes = parent.to_bytes_be();
+    let mut ikm = vec![0; HASH_BYTES];
+    ikm[HASH_BYTES - bytes.len()..].copy_from_slice(&bytes);
+    let not_ikm: Vec<u8> = ikm.iter().map(|byte| !byte).collect();
+
+    let lamport_0 = ikm_to_lamport_sk(&ikm, &salt);
+    let lamport_1 = ikm_to_lamport_sk(&not_ikm, &salt);
+
+    let lamport_pk: Vec<u8> = lamport_0
+        .chunks(HASH_BYTES)
+        .chain(lamport_1.chunks(HASH_BYTES))
+        .flat_map(sha256)
+        .collect();
+
+    sha256(&lamport_pk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int(decimal: &str) -> BigUint {
+        BigUint::parse_bytes(decimal.as_bytes(), 10).unwrap()
+    }
+
+    /// Test case 0 of EIP-2333.
+    #[test]
+    fn eip_2333_test_case_0() {
+        let seed = hex::decode(
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e5349553\
+             1f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04",
+        )
+        .unwrap();
+
+        let master = DerivedKey::from_seed(&seed).unwrap();
+        assert_eq!(
+            master.0,
+            int("6083874454709270928345386274498605044986640685124978867557563392430687146096")
+        );
+        assert_eq!(
+            master.child(0).0,
+            int("20397789859736650942317412262472558107875392172444076792671091975210932703118")
+        );
+    }
+
+    #[test]
+    fn derives_paths() {
+        let seed = [42; MIN_SEED_BYTES];
+        let master = DerivedKey::from_seed(&seed).unwrap();
+
+        assert_eq!(signing_key_path(7), "m/12381/3600/7/0/0");
+        assert_eq!(
+            DerivedKey::from_path(&seed, &signing_key_path(7))
+                .unwrap()
+                .0,
+            master.child(12381).child(3600).child(7).child(0).child(0).0
+        );
+        assert_eq!(DerivedKey::from_path(&seed, "m").unwrap().0, master.0);
+        assert_eq!(
+            parse_path("12381/3600"),
+            Err(Error::InvalidPath("12381/3600".to_string()))
+        );
+        assert_eq!(
+            DerivedKey::from_seed(&[0; 31]).err(),
+            Some(Error::SeedTooShort(31))
+        );
+    }
+}
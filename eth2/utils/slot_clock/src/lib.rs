@@ -6,6 +6,31 @@ pub use crate::testing_slot_clock::{Error as TestingSlotClockError, TestingSlotC
 use std::time::Duration;
 pub use types::Slot;
 
+/// A fraction of the slot duration, e.g. `SlotFraction::new(1, 3)` for the attestation deadline
+/// a third of the way through a slot.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct SlotFraction {
+    numerator: u32,
+    denominator: u32,
+}
+
+impl SlotFraction {
+    /// Create the fraction `numerator / denominator`.
+    ///
+    /// A `denominator` of zero is treated as one.
+    pub fn new(numerator: u32, denominator: u32) -> Self {
+        Self {
+            numerator,
+            denominator: denominator.max(1),
+        }
+    }
+
+    /// Returns this fraction of `slot_duration`.
+    pub fn of(self, slot_duration: Duration) -> Duration {
+        slot_duration * self.numerator / self.denominator
+    }
+}
+
 pub trait SlotClock: Send + Sync + Sized {
     type Error;
 
@@ -17,4 +42,48 @@ pub trait SlotClock: Send + Sync + Sized {
     fn present_slot(&self) -> Result<Option<Slot>, Self::Error>;
 
     fn duration_to_next_slot(&self) -> Result<Option<Duration>, Self::Error>;
+
+    /// Returns the duration of each slot.
+    fn slot_duration(&self) -> Duration;
+
+    /// Returns the present time, as a duration since the UNIX epoch.
+    fn now(&self) -> Result<Duration, Self::Error>;
+
+    /// Returns the start of `slot`, as a duration since the UNIX epoch.
+    ///
+    /// Returns `None` if `slot` is before the genesis slot, or its start is not representable.
+    fn start_of(&self, slot: Slot) -> Option<Duration>;
+
+    /// Returns the duration from now until the start of `slot`.
+    ///
+    /// Returns `None` if `slot` has already started.
+    fn duration_to_slot(&self, slot: Slot) -> Result<Option<Duration>, Self::Error> {
+        let now = self.now()?;
+        Ok(self
+            .start_of(slot)
+            .and_then(|start| start.checked_sub(now))
+            .filter(|duration| *duration > Duration::from_secs(0)))
+    }
+
+    /// Returns the next slot in which `frac` of the slot has yet to elapse, and the duration from
+    /// now until it does.
+    ///
+    /// This is the present slot if it has not yet reached `frac`, otherwise the next slot. E.g.,
+    /// with a `frac` of one third, the slot of the next attestation deadline and the duration
+    /// until it. Returns `None` before genesis.
+    fn now_with_offset(&self, frac: SlotFraction) -> Result<Option<(Slot, Duration)>, Self::Error> {
+        let now = self.now()?;
+        let slot = match self.present_slot()? {
+            Some(slot) => slot,
+            None => return Ok(None),
+        };
+        let offset = frac.of(self.slot_duration());
+        let until = |slot: Slot| {
+            self.start_of(slot)
+                .and_then(|start| (start + offset).checked_sub(now))
+                .map(|duration| (slot, duration))
+        };
+
+        Ok(until(slot).or_else(|| until(slot + 1)))
+    }
 }
@@ -1,5 +1,6 @@
 use super::SlotClock;
-use std::time::{Duration, SystemTime};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime};
 use types::Slot;
 
 pub use std::time::SystemTimeError;
@@ -8,14 +9,80 @@ pub use std::time::SystemTimeError;
 pub enum Error {
     SlotDurationIsZero,
     SystemTimeError(String),
+    /// The system time went backwards (e.g., an NTP step or a VM resume), so that the present
+    /// slot would be earlier than `max_slot`, the latest slot previously observed.
+    ClockWentBackwards {
+        present_slot: Slot,
+        max_slot: Slot,
+    },
 }
 
 /// Determines the present slot based upon the present system time.
+///
+/// The present slot never goes backwards: once a slot has been observed, `present_slot` returns
+/// the latest slot observed until the system time passes it again. A validator, which must not
+/// sign in a slot again, uses `checked_present_slot` instead, which refuses an earlier present slot
+/// with `Error::ClockWentBackwards`, and `duration_to_next_slot` measures to the slot after the
+/// latest slot observed.
+///
+/// If the system time is before the UNIX epoch, the time is instead derived from the monotonic
+/// clock, measured from the system time when the clock was created. A system time which went
+/// backwards but is still after the UNIX epoch is used as it is.
 #[derive(Clone)]
 pub struct SystemTimeSlotClock {
     genesis_slot: Slot,
     genesis_seconds: u64,
     slot_duration_seconds: u64,
+    /// The system time when the clock was created, as a duration since the UNIX epoch.
+    anchor: Option<(Instant, Duration)>,
+    /// The latest present slot observed, shared with any clones.
+    max_slot: Arc<Mutex<Option<Slot>>>,
+}
+
+impl SystemTimeSlotClock {
+    /// Returns the present slot, unless it is earlier than a slot previously observed, in which
+    /// case `Error::ClockWentBackwards` is returned.
+    pub fn checked_present_slot(&self) -> Result<Option<Slot>, Error> {
+        let present_slot = self.system_slot()?;
+        match (present_slot, self.observe(present_slot)?) {
+            (Some(present_slot), Some(max_slot)) if present_slot < max_slot => {
+                Err(Error::ClockWentBackwards {
+                    present_slot,
+                    max_slot,
+                })
+            }
+            _ => Ok(present_slot),
+        }
+    }
+
+    /// Records `present_slot` as observed, returning the latest slot observed before it.
+    fn observe(&self, present_slot: Option<Slot>) -> Result<Option<Slot>, Error> {
+        let mut max_slot = self.lock_max_slot()?;
+        let previous = *max_slot;
+        *max_slot = previous.max(present_slot);
+        Ok(previous)
+    }
+
+    fn lock_max_slot(&self) -> Result<MutexGuard<Option<Slot>>, Error> {
+        self.max_slot
+            .lock()
+            .map_err(|_| Error::SystemTimeError("max slot lock poisoned".to_string()))
+    }
+
+    /// Returns the slot of the system time, or `None` before genesis.
+    fn system_slot(&self) -> Result<Option<Slot>, Error> {
+        if self.slot_duration_seconds == 0 {
+            return Err(Error::SlotDurationIsZero);
+        }
+
+        let duration_since_epoch = self.now()?;
+        let duration_since_genesis =
+            duration_since_epoch.checked_sub(Duration::from_secs(self.genesis_seconds));
+
+        Ok(duration_since_genesis
+            .and_then(|d| slot_from_duration(self.slot_duration_seconds, d))
+            .map(|slot| slot + self.genesis_slot))
+    }
 }
 
 impl SlotClock for SystemTimeSlotClock {
@@ -29,28 +96,57 @@ impl SlotClock for SystemTimeSlotClock {
             genesis_slot,
             genesis_seconds,
             slot_duration_seconds,
+            anchor: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .ok()
+                .map(|since_epoch| (Instant::now(), since_epoch)),
+            max_slot: Arc::new(Mutex::new(None)),
         }
     }
 
+    /// Returns the present slot, or the latest slot previously observed if it is later (i.e., if
+    /// the system time went backwards).
     fn present_slot(&self) -> Result<Option<Slot>, Error> {
-        if self.slot_duration_seconds == 0 {
-            return Err(Error::SlotDurationIsZero);
+        let present_slot = self.system_slot()?;
+        Ok(self.observe(present_slot)?.max(present_slot))
+    }
+
+    /// Returns the duration to the slot after the present slot, which is held at the latest slot
+    /// observed if the system time went backwards.
+    fn duration_to_next_slot(&self) -> Result<Option<Duration>, Error> {
+        let now = self.now()?;
+        let max_slot = *self.lock_max_slot()?;
+
+        match max_slot.and_then(|slot| self.start_of(slot + 1)) {
+            Some(next_slot_start) if next_slot_start > now => Ok(Some(next_slot_start - now)),
+            _ => duration_to_next_slot(now, self.genesis_seconds, self.slot_duration_seconds),
         }
+    }
 
-        let syslot_time = SystemTime::now();
-        let duration_since_epoch = syslot_time.duration_since(SystemTime::UNIX_EPOCH)?;
-        let duration_since_genesis =
-            duration_since_epoch.checked_sub(Duration::from_secs(self.genesis_seconds));
+    fn slot_duration(&self) -> Duration {
+        Duration::from_secs(self.slot_duration_seconds)
+    }
 
-        match duration_since_genesis {
-            None => Ok(None),
-            Some(d) => Ok(slot_from_duration(self.slot_duration_seconds, d)
-                .and_then(|s| Some(s + self.genesis_slot))),
+    /// Returns the system time, or the monotonic time since the clock was created if the system
+    /// time is before the UNIX epoch.
+    fn now(&self) -> Result<Duration, Error> {
+        match (
+            SystemTime::now().duration_since(SystemTime::UNIX_EPOCH),
+            self.anchor,
+        ) {
+            (Ok(since_epoch), _) => Ok(since_epoch),
+            (Err(_), Some((instant, since_epoch))) => Ok(since_epoch + instant.elapsed()),
+            (Err(e), None) => Err(e.into()),
         }
     }
 
-    fn duration_to_next_slot(&self) -> Result<Option<Duration>, Error> {
-        duration_to_next_slot(self.genesis_seconds, self.slot_duration_seconds)
+    fn start_of(&self, slot: Slot) -> Option<Duration> {
+        start_of(
+            self.genesis_slot,
+            self.genesis_seconds,
+            self.slot_duration_seconds,
+            slot,
+        )
     }
 }
 
@@ -60,17 +156,33 @@ impl From<SystemTimeError> for Error {
     }
 }
 
+/// Returns the start of `slot` as a duration since the UNIX epoch, for a clock whose
+/// `genesis_slot` starts at `genesis_seconds`.
+pub(crate) fn start_of(
+    genesis_slot: Slot,
+    genesis_seconds: u64,
+    slot_duration_seconds: u64,
+    slot: Slot,
+) -> Option<Duration> {
+    let seconds = slot
+        .as_u64()
+        .checked_sub(genesis_slot.as_u64())?
+        .checked_mul(slot_duration_seconds)?
+        .checked_add(genesis_seconds)?;
+    Some(Duration::from_secs(seconds))
+}
+
 fn slot_from_duration(slot_duration_seconds: u64, duration: Duration) -> Option<Slot> {
     Some(Slot::new(
         duration.as_secs().checked_div(slot_duration_seconds)?,
     ))
 }
-// calculate the duration to the next slot
+// calculate the duration from `now` to the next slot
 fn duration_to_next_slot(
+    now: Duration,
     genesis_time: u64,
     seconds_per_slot: u64,
 ) -> Result<Option<Duration>, Error> {
-    let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?;
     let genesis_time = Duration::from_secs(genesis_time);
 
     if now < genesis_time {
@@ -108,28 +220,75 @@ mod tests {
 
         let genesis = since_epoch.as_secs() - slot_time * 89;
 
-        let clock = SystemTimeSlotClock {
-            genesis_slot,
-            genesis_seconds: genesis,
-            slot_duration_seconds: slot_time,
-        };
+        let clock = SystemTimeSlotClock::new(genesis_slot, genesis, slot_time);
         assert_eq!(clock.present_slot().unwrap(), Some(Slot::new(89)));
 
-        let clock = SystemTimeSlotClock {
-            genesis_slot,
-            genesis_seconds: since_epoch.as_secs(),
-            slot_duration_seconds: slot_time,
-        };
+        let clock = SystemTimeSlotClock::new(genesis_slot, since_epoch.as_secs(), slot_time);
         assert_eq!(clock.present_slot().unwrap(), Some(Slot::new(0)));
 
-        let clock = SystemTimeSlotClock {
+        let clock = SystemTimeSlotClock::new(
             genesis_slot,
-            genesis_seconds: since_epoch.as_secs() - slot_time * 42 - 5,
-            slot_duration_seconds: slot_time,
-        };
+            since_epoch.as_secs() - slot_time * 42 - 5,
+            slot_time,
+        );
         assert_eq!(clock.present_slot().unwrap(), Some(Slot::new(42)));
     }
 
+    #[test]
+    fn test_clock_went_backwards() {
+        let clock = SystemTimeSlotClock::new(Slot::new(0), 0, 6);
+        let present_slot = clock.checked_present_slot().unwrap().unwrap();
+
+        // A clone shares the slots observed, and the present slot may repeat.
+        let clone = clock.clone();
+        assert_eq!(clone.observe(Some(present_slot)), Ok(Some(present_slot)));
+        assert_eq!(
+            clone.observe(Some(present_slot - 1)),
+            Ok(Some(present_slot))
+        );
+
+        // The system time is behind a slot observed, as if it had been stepped back.
+        assert_eq!(
+            clock.observe(Some(present_slot + 10)),
+            Ok(Some(present_slot))
+        );
+        match clock.checked_present_slot() {
+            Err(Error::ClockWentBackwards { max_slot, .. }) => {
+                assert_eq!(max_slot, present_slot + 10)
+            }
+            other => panic!("expected the clock to go backwards, found {:?}", other),
+        }
+
+        // Whereas the present slot stays at the latest slot observed, and the next slot follows it.
+        assert_eq!(clock.present_slot(), Ok(Some(present_slot + 10)));
+        let duration_to_next_slot = clock.duration_to_next_slot().unwrap().unwrap();
+        assert!(duration_to_next_slot > Duration::from_secs(6 * 10));
+        assert!(duration_to_next_slot <= Duration::from_secs(6 * 11));
+    }
+
+    #[test]
+    fn test_start_of() {
+        let clock = SystemTimeSlotClock::new(Slot::new(10), 1000, 6);
+
+        assert_eq!(clock.start_of(Slot::new(9)), None);
+        assert_eq!(
+            clock.start_of(Slot::new(10)),
+            Some(Duration::from_secs(1000))
+        );
+        assert_eq!(
+            clock.start_of(Slot::new(12)),
+            Some(Duration::from_secs(1012))
+        );
+        assert_eq!(clock.start_of(Slot::new(u64::max_value())), None);
+
+        // Genesis was long ago, so the genesis slot has started and a distant slot has not.
+        assert_eq!(clock.duration_to_slot(Slot::new(10)), Ok(None));
+        assert!(clock
+            .duration_to_slot(Slot::new(u64::max_value() / 12))
+            .unwrap()
+            .is_some());
+    }
+
     #[test]
     fn test_slot_from_duration() {
         let slot_time = 100;
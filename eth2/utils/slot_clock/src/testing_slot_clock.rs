@@ -1,3 +1,4 @@
+use super::system_time_slot_clock::start_of;
 use super::SlotClock;
 use std::sync::RwLock;
 use std::time::Duration;
@@ -6,9 +7,14 @@ use types::Slot;
 #[derive(Debug, PartialEq)]
 pub enum Error {}
 
-/// Determines the present slot based upon the present system time.
+/// A slot clock whose present slot is set manually.
+///
+/// The present time is always the start of the present slot.
 pub struct TestingSlotClock {
     slot: RwLock<Slot>,
+    genesis_slot: Slot,
+    genesis_seconds: u64,
+    slot_duration_seconds: u64,
 }
 
 impl TestingSlotClock {
@@ -25,9 +31,12 @@ impl SlotClock for TestingSlotClock {
     type Error = Error;
 
     /// Create a new `TestingSlotClock` at `genesis_slot`.
-    fn new(genesis_slot: Slot, _genesis_seconds: u64, _slot_duration_seconds: u64) -> Self {
+    fn new(genesis_slot: Slot, genesis_seconds: u64, slot_duration_seconds: u64) -> Self {
         TestingSlotClock {
             slot: RwLock::new(genesis_slot),
+            genesis_slot,
+            genesis_seconds,
+            slot_duration_seconds,
         }
     }
 
@@ -40,11 +49,31 @@ impl SlotClock for TestingSlotClock {
     fn duration_to_next_slot(&self) -> Result<Option<Duration>, Error> {
         Ok(Some(Duration::from_secs(1)))
     }
+
+    fn slot_duration(&self) -> Duration {
+        Duration::from_secs(self.slot_duration_seconds)
+    }
+
+    /// Returns the start of the present slot.
+    fn now(&self) -> Result<Duration, Error> {
+        let slot = *self.slot.read().expect("TestingSlotClock poisoned.");
+        Ok(self.start_of(slot).unwrap_or_default())
+    }
+
+    fn start_of(&self, slot: Slot) -> Option<Duration> {
+        start_of(
+            self.genesis_slot,
+            self.genesis_seconds,
+            self.slot_duration_seconds,
+            slot,
+        )
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::SlotFraction;
 
     #[test]
     fn test_slot_now() {
@@ -55,4 +84,36 @@ mod tests {
         clock.set_slot(123);
         assert_eq!(clock.present_slot(), Ok(Some(Slot::new(123))));
     }
+
+    #[test]
+    fn test_slot_offsets() {
+        let clock = TestingSlotClock::new(Slot::new(0), 100, 12);
+        clock.set_slot(5);
+
+        assert_eq!(clock.start_of(Slot::new(5)), Some(Duration::from_secs(160)));
+        assert_eq!(clock.duration_to_slot(Slot::new(5)), Ok(None));
+        assert_eq!(
+            clock.duration_to_slot(Slot::new(7)),
+            Ok(Some(Duration::from_secs(24)))
+        );
+
+        // The attestation and aggregation deadlines of the present slot are yet to pass.
+        assert_eq!(
+            clock.now_with_offset(SlotFraction::new(1, 3)),
+            Ok(Some((Slot::new(5), Duration::from_secs(4))))
+        );
+        assert_eq!(
+            clock.now_with_offset(SlotFraction::new(2, 3)),
+            Ok(Some((Slot::new(5), Duration::from_secs(8))))
+        );
+        // The start of the present slot is now, the end of it is the start of the next.
+        assert_eq!(
+            clock.now_with_offset(SlotFraction::new(0, 3)),
+            Ok(Some((Slot::new(5), Duration::from_secs(0))))
+        );
+        assert_eq!(
+            clock.now_with_offset(SlotFraction::new(1, 1)),
+            Ok(Some((Slot::new(5), Duration::from_secs(12))))
+        );
+    }
 }
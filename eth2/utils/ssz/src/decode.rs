@@ -20,6 +20,8 @@ pub enum DecodeError {
     OutOfBoundsByte { i: usize },
     /// The given bytes were invalid for some application-level reason.
     BytesInvalid(String),
+    /// The selector of a union did not select any of its types.
+    InvalidUnionSelector { index: usize },
 }
 
 /// Provides SSZ decoding (de-serialization) via the `from_ssz_bytes(&bytes)` method.
@@ -218,12 +220,105 @@ impl<'a> SszDecoder<'a> {
     }
 }
 
+/// Describes where a field of an SSZ container is stored.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FieldLayout {
+    /// The field occupies the given number of bytes in the fixed-length portion of the container.
+    Fixed(usize),
+    /// The field is stored after the fixed-length portion, at the offset stored in the
+    /// fixed-length portion.
+    Variable,
+}
+
+impl FieldLayout {
+    /// Returns the layout of a field of type `T`.
+    pub fn of<T: Decode>() -> Self {
+        if T::is_ssz_fixed_len() {
+            FieldLayout::Fixed(T::ssz_fixed_len())
+        } else {
+            FieldLayout::Variable
+        }
+    }
+
+    /// The number of bytes the field occupies in the fixed-length portion of the container.
+    fn fixed_len(self) -> usize {
+        match self {
+            FieldLayout::Fixed(len) => len,
+            FieldLayout::Variable => BYTES_PER_LENGTH_OFFSET,
+        }
+    }
+}
+
+/// Returns the bytes of the `index`'th field of the SSZ container in `bytes`, each of whose fields
+/// is described (in order) by `layout`.
+///
+/// Only the offsets required to locate the field are read; no other field is decoded or
+/// validated. The bytes of a variable-length field end at the offset of the next variable-length
+/// field, or at the end of `bytes`.
+pub fn container_field_bytes<'a>(
+    bytes: &'a [u8],
+    layout: &[FieldLayout],
+    index: usize,
+) -> Result<&'a [u8], DecodeError> {
+    let fixed_len: usize = layout.iter().map(|field| field.fixed_len()).sum();
+    if bytes.len() < fixed_len {
+        return Err(DecodeError::InvalidByteLength {
+            len: bytes.len(),
+            expected: fixed_len,
+        });
+    }
+
+    let field = *layout.get(index).ok_or_else(|| {
+        DecodeError::BytesInvalid(format!("container has no field at index {}", index))
+    })?;
+    let position: usize = layout[..index].iter().map(|field| field.fixed_len()).sum();
+
+    match field {
+        FieldLayout::Fixed(len) => Ok(&bytes[position..position + len]),
+        FieldLayout::Variable => {
+            let start = read_offset(&bytes[position..])?;
+
+            let mut next_position = position + BYTES_PER_LENGTH_OFFSET;
+            let mut end = bytes.len();
+            for field in &layout[index + 1..] {
+                if *field == FieldLayout::Variable {
+                    end = read_offset(&bytes[next_position..])?;
+                    break;
+                }
+                next_position += field.fixed_len();
+            }
+
+            if start < fixed_len || start > bytes.len() {
+                Err(DecodeError::OutOfBoundsByte { i: start })
+            } else if end < start || end > bytes.len() {
+                Err(DecodeError::OutOfBoundsByte { i: end })
+            } else {
+                Ok(&bytes[start..end])
+            }
+        }
+    }
+}
+
 /// Reads a `BYTES_PER_LENGTH_OFFSET`-byte union index from `bytes`, where `bytes.len() >=
 /// BYTES_PER_LENGTH_OFFSET`.
 pub fn read_union_index(bytes: &[u8]) -> Result<usize, DecodeError> {
     read_offset(bytes)
 }
 
+/// Splits the SSZ encoding of a union into its selector (the index of the type of its value) and
+/// the encoding of its value.
+pub fn split_union_bytes(bytes: &[u8]) -> Result<(usize, &[u8]), DecodeError> {
+    if bytes.len() < BYTES_PER_LENGTH_OFFSET {
+        return Err(DecodeError::InvalidByteLength {
+            len: bytes.len(),
+            expected: BYTES_PER_LENGTH_OFFSET,
+        });
+    }
+
+    let (index_bytes, value_bytes) = bytes.split_at(BYTES_PER_LENGTH_OFFSET);
+    Ok((read_union_index(index_bytes)?, value_bytes))
+}
+
 /// Reads a `BYTES_PER_LENGTH_OFFSET`-byte length from `bytes`, where `bytes.len() >=
 /// BYTES_PER_LENGTH_OFFSET`.
 fn read_offset(bytes: &[u8]) -> Result<usize, DecodeError> {
@@ -1,6 +1,6 @@
 use super::*;
 use core::num::NonZeroUsize;
-use ethereum_types::{H256, U128, U256};
+use ethereum_types::{H160, H256, U128, U256};
 
 macro_rules! impl_decodable_for_uint {
     ($type: ident, $bit_size: expr) => {
@@ -246,25 +246,34 @@ impl<T: Decode> Decode for Option<T> {
     }
 
     fn from_ssz_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
-        if bytes.len() < BYTES_PER_LENGTH_OFFSET {
-            return Err(DecodeError::InvalidByteLength {
-                len: bytes.len(),
-                expected: BYTES_PER_LENGTH_OFFSET,
-            });
+        match split_union_bytes(bytes)? {
+            (0, []) => Ok(None),
+            (0, _) => Err(DecodeError::BytesInvalid(
+                "the None of an Option<T> has no value".to_string(),
+            )),
+            (1, value_bytes) => Ok(Some(T::from_ssz_bytes(value_bytes)?)),
+            (index, _) => Err(DecodeError::InvalidUnionSelector { index }),
         }
+    }
+}
+
+impl Decode for H160 {
+    fn is_ssz_fixed_len() -> bool {
+        true
+    }
+
+    fn ssz_fixed_len() -> usize {
+        20
+    }
 
-        let (index_bytes, value_bytes) = bytes.split_at(BYTES_PER_LENGTH_OFFSET);
+    fn from_ssz_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let len = bytes.len();
+        let expected = <Self as Decode>::ssz_fixed_len();
 
-        let index = read_union_index(index_bytes)?;
-        if index == 0 {
-            Ok(None)
-        } else if index == 1 {
-            Ok(Some(T::from_ssz_bytes(value_bytes)?))
+        if len != expected {
+            Err(DecodeError::InvalidByteLength { len, expected })
         } else {
-            Err(DecodeError::BytesInvalid(format!(
-                "{} is not a valid union index for Option<T>",
-                index
-            )))
+            Ok(H160::from_slice(bytes))
         }
     }
 }
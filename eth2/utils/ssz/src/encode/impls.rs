@@ -1,6 +1,6 @@
 use super::*;
 use core::num::NonZeroUsize;
-use ethereum_types::{H256, U128, U256};
+use ethereum_types::{H160, H256, U128, U256};
 
 macro_rules! impl_encodable_for_uint {
     ($type: ident, $bit_size: expr) => {
@@ -248,6 +248,20 @@ impl Encode for NonZeroUsize {
     }
 }
 
+impl Encode for H160 {
+    fn is_ssz_fixed_len() -> bool {
+        true
+    }
+
+    fn ssz_fixed_len() -> usize {
+        20
+    }
+
+    fn ssz_append(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self.as_bytes());
+    }
+}
+
 impl Encode for H256 {
     fn is_ssz_fixed_len() -> bool {
         true
@@ -421,6 +435,12 @@ mod tests {
         assert_eq!(false.as_ssz_bytes(), vec![0]);
     }
 
+    #[test]
+    fn ssz_encode_h160() {
+        assert_eq!(H160::from(&[0; 20]).as_ssz_bytes(), vec![0; 20]);
+        assert_eq!(H160::from(&[1; 20]).as_ssz_bytes(), vec![1; 20]);
+    }
+
     #[test]
     fn ssz_encode_h256() {
         assert_eq!(H256::from(&[0; 32]).as_ssz_bytes(), vec![0; 32]);
@@ -1,4 +1,4 @@
-use ethereum_types::H256;
+use ethereum_types::{H160, H256};
 use ssz::{Decode, DecodeError, Encode};
 use ssz_derive::{Decode, Encode};
 
@@ -26,6 +26,13 @@ mod round_trip {
         round_trip(items);
     }
 
+    #[test]
+    fn h160() {
+        let items: Vec<H160> = vec![H160::zero(), H160::from([1; 20]), H160::random()];
+
+        round_trip(items);
+    }
+
     #[test]
     fn h256() {
         let items: Vec<H256> = vec![H256::zero(), H256::from([1; 32]), H256::random()];
@@ -377,3 +384,142 @@ mod round_trip {
         round_trip(vec);
     }
 }
+
+mod container_field_bytes {
+    use super::*;
+    use ssz::{container_field_bytes, FieldLayout};
+
+    #[derive(Debug, PartialEq, Encode, Decode)]
+    struct Mixed {
+        a: u16,
+        b: Vec<u16>,
+        c: u8,
+        d: Vec<u8>,
+    }
+
+    fn layout() -> Vec<FieldLayout> {
+        vec![
+            FieldLayout::of::<u16>(),
+            FieldLayout::of::<Vec<u16>>(),
+            FieldLayout::of::<u8>(),
+            FieldLayout::of::<Vec<u8>>(),
+        ]
+    }
+
+    #[test]
+    fn reads_each_field() {
+        let item = Mixed {
+            a: 42,
+            b: vec![1, 2, 3],
+            c: 7,
+            d: vec![4, 5],
+        };
+        let bytes = item.as_ssz_bytes();
+        let field = |index| container_field_bytes(&bytes, &layout(), index);
+
+        assert_eq!(u16::from_ssz_bytes(field(0).unwrap()), Ok(item.a));
+        assert_eq!(Vec::<u16>::from_ssz_bytes(field(1).unwrap()), Ok(item.b));
+        assert_eq!(u8::from_ssz_bytes(field(2).unwrap()), Ok(item.c));
+        assert_eq!(Vec::<u8>::from_ssz_bytes(field(3).unwrap()), Ok(item.d));
+        assert!(field(4).is_err());
+    }
+
+    #[test]
+    fn invalid_offsets() {
+        let bytes = vec![
+            //  1   2   3   4   5   6   7   8   9   10  11  12
+            //      | offset        |   | offset        | variable
+            42, 00, 20, 00, 00, 00, 07, 11, 00, 00, 00, 00,
+        ];
+
+        assert_eq!(
+            container_field_bytes(&bytes, &layout(), 1),
+            Err(DecodeError::OutOfBoundsByte { i: 20 })
+        );
+        assert_eq!(
+            container_field_bytes(&bytes[..4], &layout(), 0),
+            Err(DecodeError::InvalidByteLength {
+                len: 4,
+                expected: 11
+            })
+        );
+    }
+}
+
+mod union {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Encode, Decode)]
+    enum Union {
+        Null,
+        A(u16),
+        B(Vec<u8>),
+    }
+
+    #[derive(Debug, PartialEq, Encode, Decode)]
+    struct Container {
+        a: u8,
+        b: Union,
+    }
+
+    #[test]
+    fn encoding() {
+        assert_eq!(Union::Null.as_ssz_bytes(), vec![00, 00, 00, 00]);
+        assert_eq!(Union::A(42).as_ssz_bytes(), vec![01, 00, 00, 00, 42, 00]);
+        assert_eq!(
+            Union::B(vec![1, 2]).as_ssz_bytes(),
+            vec![02, 00, 00, 00, 01, 02]
+        );
+
+        let container = Container {
+            a: 7,
+            b: Union::A(42),
+        };
+        assert_eq!(
+            container.as_ssz_bytes(),
+            //  1   2   3   4   5   6   7   8   9   10  11
+            //  | offset            | selector      | value
+            vec![07, 05, 00, 00, 00, 01, 00, 00, 00, 42, 00]
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        for item in vec![Union::Null, Union::A(0), Union::A(42), Union::B(vec![])] {
+            assert_eq!(Union::from_ssz_bytes(&item.as_ssz_bytes()), Ok(item));
+        }
+
+        let container = Container {
+            a: 7,
+            b: Union::B(vec![1, 3, 3, 7]),
+        };
+        assert_eq!(
+            Container::from_ssz_bytes(&container.as_ssz_bytes()),
+            Ok(container)
+        );
+    }
+
+    #[test]
+    fn invalid_bytes() {
+        assert_eq!(
+            Union::from_ssz_bytes(&[03, 00, 00, 00]),
+            Err(DecodeError::InvalidUnionSelector { index: 3 })
+        );
+        assert_eq!(
+            Union::from_ssz_bytes(&[00, 00, 00]),
+            Err(DecodeError::InvalidByteLength {
+                len: 3,
+                expected: 4
+            })
+        );
+        // The null type has no value.
+        assert!(Union::from_ssz_bytes(&[00, 00, 00, 00, 01]).is_err());
+        assert!(Option::<u16>::from_ssz_bytes(&[00, 00, 00, 00, 01]).is_err());
+        assert_eq!(
+            Option::<u16>::from_ssz_bytes(&[02, 00, 00, 00]),
+            Err(DecodeError::InvalidUnionSelector { index: 2 })
+        );
+        // The value of `A` must be exactly a `u16`.
+        assert!(Union::from_ssz_bytes(&[01, 00, 00, 00, 42]).is_err());
+    }
+}
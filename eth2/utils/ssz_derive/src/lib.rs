@@ -1,7 +1,8 @@
 #![recursion_limit = "128"]
 //! Provides procedural derive macros for the `Encode` and `Decode` traits of the `eth2_ssz` crate.
 //!
-//! Supports field attributes, see each derive macro for more information.
+//! Structs are encoded as SSZ containers and enums as SSZ unions. Supports field attributes, see
+//! each derive macro for more information.
 
 extern crate proc_macro;
 
@@ -49,6 +50,29 @@ fn get_serializable_field_types<'a>(struct_data: &'a syn::DataStruct) -> Vec<&'a
         .collect()
 }
 
+/// Returns the variants of an enum which is encoded as an SSZ union, with the type of each
+/// variant's value (or `None` for a unit variant). The selector of each variant is its index.
+///
+/// # Panics
+/// Any variant with named fields, or more than one field, will raise a panic at compile time.
+fn get_union_variants<'a>(
+    enum_data: &'a syn::DataEnum,
+) -> Vec<(&'a syn::Ident, Option<&'a syn::Type>)> {
+    enum_data
+        .variants
+        .iter()
+        .map(|variant| match &variant.fields {
+            syn::Fields::Unit => (&variant.ident, None),
+            syn::Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                (&variant.ident, Some(&fields.unnamed[0].ty))
+            }
+            _ => panic!(
+                "ssz_derive only supports enum variants with no fields or a single unnamed field."
+            ),
+        })
+        .collect()
+}
+
 /// Returns true if some field has an attribute declaring it should not be serialized.
 ///
 /// The field attribute is: `#[ssz(skip_serializing)]`
@@ -61,10 +85,13 @@ fn should_skip_serializing(field: &syn::Field) -> bool {
     false
 }
 
-/// Implements `ssz::Encode` for some `struct`.
+/// Implements `ssz::Encode` for some `struct` or `enum`.
 ///
 /// Fields are encoded in the order they are defined.
 ///
+/// An `enum` is encoded as a union: the index of its variant, then the value of the variant (if
+/// any). Each variant must have either no fields or a single unnamed field.
+///
 /// ## Field attributes
 ///
 /// - `#[ssz(skip_serializing)]`: the field will not be serialized.
@@ -77,7 +104,8 @@ pub fn ssz_encode_derive(input: TokenStream) -> TokenStream {
 
     let struct_data = match &item.data {
         syn::Data::Struct(s) => s,
-        _ => panic!("ssz_derive only supports structs."),
+        syn::Data::Enum(e) => return ssz_encode_derive_enum(&item, e),
+        _ => panic!("ssz_derive only supports structs and enums."),
     };
 
     let field_idents = get_serializable_named_field_idents(&struct_data);
@@ -124,6 +152,43 @@ pub fn ssz_encode_derive(input: TokenStream) -> TokenStream {
     output.into()
 }
 
+/// Implements `ssz::Encode` for an `enum`, as a union.
+fn ssz_encode_derive_enum(item: &DeriveInput, enum_data: &syn::DataEnum) -> TokenStream {
+    let name = &item.ident;
+    let (impl_generics, ty_generics, where_clause) = &item.generics.split_for_impl();
+
+    let arms = get_union_variants(enum_data).into_iter().enumerate().map(
+        |(index, (variant, ty))| match ty {
+            None => quote! {
+                #name::#variant => buf.append(&mut ssz::encode_union_index(#index)),
+            },
+            Some(_) => quote! {
+                #name::#variant(ref value) => {
+                    buf.append(&mut ssz::encode_union_index(#index));
+                    ssz::Encode::ssz_append(value, buf);
+                }
+            },
+        },
+    );
+
+    let output = quote! {
+        impl #impl_generics ssz::Encode for #name #ty_generics #where_clause {
+            fn is_ssz_fixed_len() -> bool {
+                false
+            }
+
+            fn ssz_append(&self, buf: &mut Vec<u8>) {
+                match *self {
+                    #(
+                        #arms
+                    )*
+                }
+            }
+        }
+    };
+    output.into()
+}
+
 /// Returns true if some field has an attribute declaring it should not be deserialized.
 ///
 /// The field attribute is: `#[ssz(skip_deserializing)]`
@@ -136,9 +201,10 @@ fn should_skip_deserializing(field: &syn::Field) -> bool {
     false
 }
 
-/// Implements `ssz::Decode` for some `struct`.
+/// Implements `ssz::Decode` for some `struct` or `enum`.
 ///
-/// Fields are decoded in the order they are defined.
+/// Fields are decoded in the order they are defined, and an `enum` is decoded as a union (see
+/// `Encode`). A union selector which is not the index of a variant is an error.
 ///
 /// ## Field attributes
 ///
@@ -154,7 +220,8 @@ pub fn ssz_decode_derive(input: TokenStream) -> TokenStream {
 
     let struct_data = match &item.data {
         syn::Data::Struct(s) => s,
-        _ => panic!("ssz_derive only supports structs."),
+        syn::Data::Enum(e) => return ssz_decode_derive_enum(&item, e),
+        _ => panic!("ssz_derive only supports structs and enums."),
     };
 
     let mut register_types = vec![];
@@ -235,3 +302,47 @@ pub fn ssz_decode_derive(input: TokenStream) -> TokenStream {
     };
     output.into()
 }
+
+/// Implements `ssz::Decode` for an `enum`, as a union.
+fn ssz_decode_derive_enum(item: &DeriveInput, enum_data: &syn::DataEnum) -> TokenStream {
+    let name = &item.ident;
+    let (impl_generics, ty_generics, where_clause) = &item.generics.split_for_impl();
+
+    let arms = get_union_variants(enum_data).into_iter().enumerate().map(
+        |(index, (variant, ty))| match ty {
+            None => quote! {
+                (#index, value_bytes) => if value_bytes.is_empty() {
+                    Ok(#name::#variant)
+                } else {
+                    Err(ssz::DecodeError::InvalidByteLength {
+                        len: bytes.len(),
+                        expected: ssz::BYTES_PER_LENGTH_OFFSET,
+                    })
+                },
+            },
+            Some(ty) => quote! {
+                (#index, value_bytes) => {
+                    Ok(#name::#variant(<#ty as ssz::Decode>::from_ssz_bytes(value_bytes)?))
+                }
+            },
+        },
+    );
+
+    let output = quote! {
+        impl #impl_generics ssz::Decode for #name #ty_generics #where_clause {
+            fn is_ssz_fixed_len() -> bool {
+                false
+            }
+
+            fn from_ssz_bytes(bytes: &[u8]) -> Result<Self, ssz::DecodeError> {
+                match ssz::split_union_bytes(bytes)? {
+                    #(
+                        #arms
+                    )*
+                    (index, _) => Err(ssz::DecodeError::InvalidUnionSelector { index }),
+                }
+            }
+        }
+    };
+    output.into()
+}
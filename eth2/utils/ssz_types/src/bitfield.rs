@@ -159,9 +159,20 @@ impl<N: Unsigned + Clone> Bitfield<Variable<N>> {
     /// Instantiates a new instance from `bytes`. Consumes the same format that `self.into_bytes()`
     /// produces (SSZ).
     ///
-    /// Returns `None` if `bytes` are not a valid encoding.
+    /// Returns `None` if `bytes` are not a valid encoding, including if they hold more than
+    /// `Self::max_len()` bits.
     pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, Error> {
         let bytes_len = bytes.len();
+
+        // The length bit is one beyond the last bit, so even the longest bitfield fits in
+        // `bytes_for_bit_len(max_len + 1)` bytes. Refuse anything longer before scanning it.
+        if bytes_len > bytes_for_bit_len(Self::max_len() + 1) {
+            return Err(Error::OutOfBounds {
+                i: bytes_len * 8 - 1,
+                len: Self::max_len(),
+            });
+        }
+
         let mut initial_bitfield: Bitfield<Variable<N>> = {
             let num_bits = bytes.len() * 8;
             Bitfield::from_raw_bytes(bytes, num_bits)?
@@ -191,7 +202,7 @@ impl<N: Unsigned + Clone> Bitfield<Variable<N>> {
             Self::from_raw_bytes(bytes, len)
         } else {
             Err(Error::OutOfBounds {
-                i: Self::max_len(),
+                i: len,
                 len: Self::max_len(),
             })
         }
@@ -790,6 +801,21 @@ mod bitlist {
         assert!(BitList8::from_ssz_bytes(&[0b0000_0001, 0b0000_0100]).is_err());
     }
 
+    #[test]
+    fn ssz_decode_beyond_max_len() {
+        assert_eq!(
+            BitList8::from_bytes(vec![0, 0b0000_0010]),
+            Err(Error::OutOfBounds { i: 9, len: 8 })
+        );
+        assert_eq!(
+            BitList8::from_bytes(vec![0; 3]),
+            Err(Error::OutOfBounds { i: 23, len: 8 })
+        );
+        assert!(BitList16::from_ssz_bytes(&[0xff, 0xff, 0b0000_0001]).is_ok());
+        assert!(BitList16::from_ssz_bytes(&[0xff, 0xff, 0b0000_0010]).is_err());
+        assert!(BitList1024::from_ssz_bytes(&vec![0; 1024]).is_err());
+    }
+
     #[test]
     fn ssz_decode_extra_bytes() {
         assert!(BitList0::from_ssz_bytes(&[0b0000_0001, 0b0000_0000]).is_err());
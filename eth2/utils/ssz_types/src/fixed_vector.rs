@@ -163,7 +163,7 @@ where
     }
 
     fn tree_hash_root(&self) -> Vec<u8> {
-        vec_tree_hash_root::<T, N>(&self.vec)
+        vec_tree_hash_root::<T, N, _>(self.vec.iter())
     }
 }
 
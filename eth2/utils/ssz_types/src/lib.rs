@@ -2,6 +2,8 @@
 //!
 //! - `FixedVector`: A heap-allocated list with a size that is fixed at compile time.
 //! - `VariableList`: A heap-allocated list that cannot grow past a type-level maximum length.
+//! - `PersistentList`: A `VariableList` which shares its values between clones, copying only
+//!   those which are modified.
 //! - `BitList`: A heap-allocated bitfield that with a type-level _maximum_ length.
 //! - `BitVector`: A heap-allocated bitfield that with a type-level _fixed__ length.
 //!
@@ -40,11 +42,13 @@
 #[macro_use]
 mod bitfield;
 mod fixed_vector;
+mod persistent_list;
 mod tree_hash;
 mod variable_list;
 
 pub use bitfield::{BitList, BitVector, Bitfield};
 pub use fixed_vector::FixedVector;
+pub use persistent_list::PersistentList;
 pub use typenum;
 pub use variable_list::VariableList;
 
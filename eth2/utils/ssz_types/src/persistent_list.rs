@@ -0,0 +1,389 @@
+use crate::tree_hash::vec_tree_hash_root;
+use crate::Error;
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Index, IndexMut};
+use std::sync::Arc;
+use typenum::Unsigned;
+
+/// The number of values in each chunk of a `PersistentList`, and so the number of values copied
+/// when a value of a shared chunk is first modified.
+pub const CHUNK_LEN: usize = 256;
+
+/// Emulates a SSZ `List`, as `VariableList` does, but is cheap to clone.
+///
+/// The values are held in reference-counted chunks of `CHUNK_LEN` values, which are shared between
+/// clones. Cloning copies only a pointer, and modifying a value copies only the chunk holding it
+/// (and, once after each clone, the list of pointers to chunks). This suits large lists which are
+/// cloned often but of which few values change between clones, such as the validators and
+/// balances of a `BeaconState`.
+///
+/// Functions which visit every value mutably (e.g., `iter_mut`) copy every shared chunk.
+///
+/// ## Example
+///
+/// ```
+/// use ssz_types::{PersistentList, typenum};
+///
+/// let mut list: PersistentList<u64, typenum::U1024> = PersistentList::from(vec![1, 2, 3]);
+/// let clone = list.clone();
+///
+/// list[0] = 4;
+/// assert_eq!(list.to_vec(), vec![4, 2, 3]);
+/// assert_eq!(clone.to_vec(), vec![1, 2, 3]);
+///
+/// list.push(5).unwrap();
+/// assert_eq!(list.len(), 4);
+/// ```
+pub struct PersistentList<T, N> {
+    chunks: Arc<Vec<Arc<Vec<T>>>>,
+    len: usize,
+    _phantom: PhantomData<N>,
+}
+
+impl<T: Clone, N: Unsigned> PersistentList<T, N> {
+    /// Returns `Ok` if the given `vec` is no longer than the maximum length of `Self`. Otherwise
+    /// returns `Err(OutOfBounds { .. })`.
+    pub fn new(vec: Vec<T>) -> Result<Self, Error> {
+        if vec.len() <= N::to_usize() {
+            let len = vec.len();
+            let chunks = vec
+                .chunks(CHUNK_LEN)
+                .map(|chunk| Arc::new(chunk.to_vec()))
+                .collect();
+            Ok(Self {
+                chunks: Arc::new(chunks),
+                len,
+                _phantom: PhantomData,
+            })
+        } else {
+            Err(Error::OutOfBounds {
+                i: vec.len(),
+                len: Self::max_len(),
+            })
+        }
+    }
+
+    /// Create an empty list.
+    pub fn empty() -> Self {
+        Self {
+            chunks: Arc::new(vec![]),
+            len: 0,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Returns the number of values presently in `self`.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// True if `self` does not contain any values.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the type-level maximum length.
+    pub fn max_len() -> usize {
+        N::to_usize()
+    }
+
+    /// Returns a reference to the value at index `i`, if any.
+    pub fn get(&self, i: usize) -> Option<&T> {
+        if i < self.len {
+            Some(&self.chunks[i / CHUNK_LEN][i % CHUNK_LEN])
+        } else {
+            None
+        }
+    }
+
+    /// Returns a mutable reference to the value at index `i`, if any, first copying the chunk
+    /// holding it if the chunk is shared.
+    pub fn get_mut(&mut self, i: usize) -> Option<&mut T> {
+        if i < self.len {
+            let chunk = &mut Arc::make_mut(&mut self.chunks)[i / CHUNK_LEN];
+            Some(&mut Arc::make_mut(chunk)[i % CHUNK_LEN])
+        } else {
+            None
+        }
+    }
+
+    /// Appends `value` to the back of `self`.
+    ///
+    /// Returns `Err(OutOfBounds { .. })` when appending `value` would exceed the maximum length.
+    pub fn push(&mut self, value: T) -> Result<(), Error> {
+        if self.len < Self::max_len() {
+            let chunks = Arc::make_mut(&mut self.chunks);
+            match chunks.last_mut() {
+                Some(chunk) if chunk.len() < CHUNK_LEN => Arc::make_mut(chunk).push(value),
+                _ => chunks.push(Arc::new(vec![value])),
+            }
+            self.len += 1;
+            Ok(())
+        } else {
+            Err(Error::OutOfBounds {
+                i: self.len + 1,
+                len: Self::max_len(),
+            })
+        }
+    }
+
+    /// Returns an iterator over the values in `self`.
+    pub fn iter(&self) -> Iter<T> {
+        Iter {
+            chunks: self.chunks.iter(),
+            chunk: <&[T]>::default().iter(),
+            remaining: self.len,
+        }
+    }
+
+    /// Returns an iterator over mutable references to the values in `self`, first copying every
+    /// shared chunk.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.chunks_mut().flat_map(|chunk| chunk.iter_mut())
+    }
+
+    /// Returns the chunks of `self` as mutable slices, each of `CHUNK_LEN` values except the last,
+    /// first copying every shared chunk.
+    pub fn chunks_mut(&mut self) -> impl Iterator<Item = &mut [T]> {
+        Arc::make_mut(&mut self.chunks)
+            .iter_mut()
+            .map(|chunk| &mut Arc::make_mut(chunk)[..])
+    }
+
+    /// Copies the values of `self` into a `Vec`.
+    pub fn to_vec(&self) -> Vec<T> {
+        self.iter().cloned().collect()
+    }
+}
+
+impl<T, N> Clone for PersistentList<T, N> {
+    fn clone(&self) -> Self {
+        Self {
+            chunks: self.chunks.clone(),
+            len: self.len,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Clone + PartialEq, N: Unsigned> PartialEq for PersistentList<T, N> {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.chunks, &other.chunks)
+            || (self.len == other.len && self.iter().eq(other.iter()))
+    }
+}
+
+impl<T: Clone + fmt::Debug, N: Unsigned> fmt::Debug for PersistentList<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T: Clone, N: Unsigned> From<Vec<T>> for PersistentList<T, N> {
+    /// Truncates `vec` to the maximum length of `Self`, as `VariableList` does.
+    fn from(mut vec: Vec<T>) -> Self {
+        vec.truncate(N::to_usize());
+
+        Self::new(vec).expect("Vec is within max length")
+    }
+}
+
+impl<T: Clone, N: Unsigned> Index<usize> for PersistentList<T, N> {
+    type Output = T;
+
+    #[inline]
+    fn index(&self, i: usize) -> &T {
+        let len = self.len;
+        self.get(i)
+            .unwrap_or_else(|| panic!("index {} out of range for list of length {}", i, len))
+    }
+}
+
+impl<T: Clone, N: Unsigned> IndexMut<usize> for PersistentList<T, N> {
+    #[inline]
+    fn index_mut(&mut self, i: usize) -> &mut T {
+        let len = self.len;
+        self.get_mut(i)
+            .unwrap_or_else(|| panic!("index {} out of range for list of length {}", i, len))
+    }
+}
+
+/// An iterator over the values of a `PersistentList`.
+pub struct Iter<'a, T> {
+    chunks: std::slice::Iter<'a, Arc<Vec<T>>>,
+    chunk: std::slice::Iter<'a, T>,
+    remaining: usize,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        loop {
+            if let Some(value) = self.chunk.next() {
+                self.remaining -= 1;
+                return Some(value);
+            }
+            self.chunk = self.chunks.next()?.iter();
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+impl<'a, T: Clone, N: Unsigned> IntoIterator for &'a PersistentList<T, N> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T: Clone + Serialize, N: Unsigned> Serialize for PersistentList<T, N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+impl<'de, T: Clone + Deserialize<'de>, N: Unsigned> Deserialize<'de> for PersistentList<T, N> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let vec = Vec::<T>::deserialize(deserializer)?;
+
+        Self::new(vec).map_err(|e| serde::de::Error::custom(format!("PersistentList {:?}", e)))
+    }
+}
+
+impl<T, N: Unsigned> tree_hash::TreeHash for PersistentList<T, N>
+where
+    T: Clone + tree_hash::TreeHash,
+{
+    fn tree_hash_type() -> tree_hash::TreeHashType {
+        tree_hash::TreeHashType::List
+    }
+
+    fn tree_hash_packed_encoding(&self) -> Vec<u8> {
+        unreachable!("List should never be packed.")
+    }
+
+    fn tree_hash_packing_factor() -> usize {
+        unreachable!("List should never be packed.")
+    }
+
+    fn tree_hash_root(&self) -> Vec<u8> {
+        let root = vec_tree_hash_root::<T, N, _>(self.iter());
+
+        tree_hash::mix_in_length(&root, self.len())
+    }
+}
+
+impl<T, N: Unsigned> ssz::Encode for PersistentList<T, N>
+where
+    T: Clone + ssz::Encode,
+{
+    fn is_ssz_fixed_len() -> bool {
+        <Vec<T>>::is_ssz_fixed_len()
+    }
+
+    fn ssz_fixed_len() -> usize {
+        <Vec<T>>::ssz_fixed_len()
+    }
+
+    fn ssz_append(&self, buf: &mut Vec<u8>) {
+        // As `Vec<T>` is encoded.
+        if T::is_ssz_fixed_len() {
+            buf.reserve(T::ssz_fixed_len() * self.len());
+
+            for item in self {
+                item.ssz_append(buf);
+            }
+        } else {
+            let mut encoder = ssz::SszEncoder::list(buf, self.len() * ssz::BYTES_PER_LENGTH_OFFSET);
+
+            for item in self {
+                encoder.append(item);
+            }
+
+            encoder.finalize();
+        }
+    }
+}
+
+impl<T, N: Unsigned> ssz::Decode for PersistentList<T, N>
+where
+    T: Clone + ssz::Decode,
+{
+    fn is_ssz_fixed_len() -> bool {
+        <Vec<T>>::is_ssz_fixed_len()
+    }
+
+    fn ssz_fixed_len() -> usize {
+        <Vec<T>>::ssz_fixed_len()
+    }
+
+    fn from_ssz_bytes(bytes: &[u8]) -> Result<Self, ssz::DecodeError> {
+        let vec = <Vec<T>>::from_ssz_bytes(bytes)?;
+
+        Self::new(vec)
+            .map_err(|e| ssz::DecodeError::BytesInvalid(format!("PersistentList {:?}", e)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::VariableList;
+    use ssz::*;
+    use tree_hash::TreeHash;
+    use typenum::*;
+
+    #[test]
+    fn new() {
+        assert!(PersistentList::<u64, U4>::new(vec![42; 5]).is_err());
+        assert!(PersistentList::<u64, U4>::new(vec![42; 4]).is_ok());
+        assert_eq!(PersistentList::<u64, U4>::from(vec![42; 5]).len(), 4);
+    }
+
+    #[test]
+    fn clones_share_unmodified_chunks() {
+        let vec: Vec<u64> = (0..CHUNK_LEN as u64 * 3 + 1).collect();
+        let mut list: PersistentList<u64, U8192> = PersistentList::from(vec.clone());
+        let clone = list.clone();
+        assert_eq!(list, clone);
+
+        list[CHUNK_LEN + 1] = 0;
+        list.push(42).unwrap();
+        assert_eq!(clone.to_vec(), vec);
+        assert_eq!(list.len(), vec.len() + 1);
+        assert_eq!(list[CHUNK_LEN + 1], 0);
+        assert_eq!(list.iter().len(), list.len());
+        assert_ne!(list, clone);
+
+        // Only the modified chunks were copied.
+        assert!(Arc::ptr_eq(&list.chunks[0], &clone.chunks[0]));
+        assert!(!Arc::ptr_eq(&list.chunks[1], &clone.chunks[1]));
+        assert!(Arc::ptr_eq(&list.chunks[2], &clone.chunks[2]));
+        assert!(!Arc::ptr_eq(&list.chunks[3], &clone.chunks[3]));
+    }
+
+    #[test]
+    fn encodes_and_hashes_as_variable_list() {
+        let vec: Vec<u64> = (0..CHUNK_LEN as u64 + 7).collect();
+        let list: PersistentList<u64, U8192> = vec.clone().into();
+        let variable_list: VariableList<u64, U8192> = vec.into();
+
+        assert_eq!(list.as_ssz_bytes(), variable_list.as_ssz_bytes());
+        assert_eq!(
+            PersistentList::<u64, U8192>::from_ssz_bytes(&list.as_ssz_bytes()),
+            Ok(list.clone())
+        );
+        assert_eq!(list.tree_hash_root(), variable_list.tree_hash_root());
+    }
+}
@@ -2,11 +2,12 @@ use tree_hash::{merkle_root, TreeHash, TreeHashType, BYTES_PER_CHUNK};
 use typenum::Unsigned;
 
 /// A helper function providing common functionality between the `TreeHash` implementations for
-/// `FixedVector` and `VariableList`.
-pub fn vec_tree_hash_root<T, N>(vec: &[T]) -> Vec<u8>
+/// `FixedVector`, `VariableList` and `PersistentList`.
+pub fn vec_tree_hash_root<'a, T, N, I>(vec: I) -> Vec<u8>
 where
-    T: TreeHash,
+    T: TreeHash + 'a,
     N: Unsigned,
+    I: ExactSizeIterator<Item = &'a T>,
 {
     let (leaves, minimum_chunk_count) = match T::tree_hash_type() {
         TreeHashType::Basic => {
@@ -22,7 +23,10 @@ where
 
             (leaves, minimum_chunk_count)
         }
-        TreeHashType::Container | TreeHashType::List | TreeHashType::Vector => {
+        TreeHashType::Container
+        | TreeHashType::List
+        | TreeHashType::Vector
+        | TreeHashType::Union => {
             let mut leaves = Vec::with_capacity(vec.len() * BYTES_PER_CHUNK);
 
             for item in vec {
@@ -190,7 +190,7 @@ where
     }
 
     fn tree_hash_root(&self) -> Vec<u8> {
-        let root = vec_tree_hash_root::<T, N>(&self.vec);
+        let root = vec_tree_hash_root::<T, N, _>(self.vec.iter());
 
         tree_hash::mix_in_length(&root, self.len())
     }
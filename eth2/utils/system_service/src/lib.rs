@@ -0,0 +1,249 @@
+//! Integration with the service managers which run Lighthouse in production: systemd (via the
+//! `sd_notify` protocol) and the Windows service control manager.
+//!
+//! The service manager is told that the node is ready only once the node itself says so (e.g., a
+//! beacon node once it is synced, a validator client once its duties are loaded), rather than as
+//! soon as the process starts. Whilst the node is live, the systemd watchdog is fed, so that a hung
+//! node is restarted.
+//!
+//! A systemd unit enables both with, e.g.:
+//!
+//! ```text
+//! [Service]
+//! Type=notify
+//! WatchdogSec=60
+//! TimeoutStartSec=infinity
+//! Restart=on-failure
+//! ExecStart=/usr/local/bin/beacon_node
+//! ```
+//!
+//! A beacon node is only ready once synced, which may take far longer than the default start
+//! timeout of systemd (90 seconds), after which the node would be killed and restarted before it
+//! could ever sync. Hence `TimeoutStartSec=infinity` (or a timeout longer than a sync may take);
+//! the progress of the start is reported in the status of the unit meanwhile.
+#[cfg(unix)]
+mod systemd;
+#[cfg(windows)]
+pub mod windows;
+
+use futures::{Future, Stream};
+use slog::{info, warn};
+use std::io;
+use std::time::{Duration, Instant};
+use tokio_timer::Interval;
+
+/// The argument with which a registered Windows service must be started.
+pub const WINDOWS_SERVICE_FLAG: &str = "--windows-service";
+
+/// The interval between probes if the service manager does not set a watchdog interval.
+const DEFAULT_PROBE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The state of a node, as reported to the service manager.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Probe {
+    /// `false` if the node is hung and should be restarted.
+    pub live: bool,
+    /// `true` if the node is able to perform its role (e.g., it is synced).
+    pub ready: bool,
+    /// A short, human-readable description of the state.
+    pub status: String,
+}
+
+/// A message to the service manager.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Notification {
+    /// The node has finished starting.
+    Ready,
+    /// The node is live.
+    Watchdog,
+    /// The description of the state of the node has changed.
+    Status(String),
+}
+
+/// Decides which notifications to send after each probe.
+#[derive(Debug, Default)]
+pub struct Monitor {
+    ready: bool,
+    status: Option<String>,
+}
+
+impl Monitor {
+    /// Returns the notifications to send, given the latest `probe`.
+    ///
+    /// Readiness is only reported once, since service managers have no notion of a service
+    /// becoming unready. Until then, the watchdog is fed regardless of liveness so that a slow
+    /// start is bounded by the start timeout of the service manager rather than its watchdog.
+    pub fn notifications(&mut self, probe: Probe) -> Vec<Notification> {
+        let mut notifications = vec![];
+
+        if self.status.as_ref() != Some(&probe.status) {
+            notifications.push(Notification::Status(probe.status.clone()));
+            self.status = Some(probe.status);
+        }
+
+        if probe.ready && !self.ready {
+            notifications.push(Notification::Ready);
+            self.ready = true;
+        }
+
+        if probe.live || !self.ready {
+            notifications.push(Notification::Watchdog);
+        }
+
+        notifications
+    }
+}
+
+/// The service manager which started the process.
+pub enum ServiceManager {
+    #[cfg(unix)]
+    Systemd(systemd::Notifier),
+    #[cfg(windows)]
+    Windows(windows::StatusReporter),
+}
+
+impl ServiceManager {
+    /// Returns the service manager which started the process, or `None` if it was not started by
+    /// one which is supported.
+    pub fn detect() -> io::Result<Option<Self>> {
+        #[cfg(unix)]
+        {
+            if let Some(notifier) = systemd::Notifier::from_env()? {
+                return Ok(Some(ServiceManager::Systemd(notifier)));
+            }
+        }
+
+        #[cfg(windows)]
+        {
+            if let Some(reporter) = windows::StatusReporter::current() {
+                return Ok(Some(ServiceManager::Windows(reporter)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Returns the interval at which the node should be probed.
+    fn probe_interval(&self) -> Duration {
+        match self {
+            #[cfg(unix)]
+            ServiceManager::Systemd(notifier) => notifier
+                .watchdog_interval()
+                .map(|interval| interval / 2)
+                .unwrap_or(DEFAULT_PROBE_INTERVAL),
+            #[cfg(windows)]
+            ServiceManager::Windows(_) => DEFAULT_PROBE_INTERVAL,
+        }
+    }
+
+    fn notify(&self, notification: &Notification) -> io::Result<()> {
+        match self {
+            #[cfg(unix)]
+            ServiceManager::Systemd(notifier) => notifier.notify(notification),
+            #[cfg(windows)]
+            ServiceManager::Windows(reporter) => reporter.notify(notification),
+        }
+    }
+}
+
+/// Returns a future which periodically calls `probe` and notifies `manager` of the result, until
+/// the timer fails.
+///
+/// A `probe` which blocks (e.g., on a deadlocked lock) also stops the watchdog being fed.
+pub fn monitor<F>(
+    manager: ServiceManager,
+    mut probe: F,
+    log: slog::Logger,
+) -> impl Future<Item = (), Error = ()>
+where
+    F: FnMut() -> Probe + Send + 'static,
+{
+    let mut monitor = Monitor::default();
+    let error_log = log.clone();
+
+    Interval::new(Instant::now(), manager.probe_interval())
+        .map_err(
+            move |e| warn!(error_log, "Service manager timer failed"; "error" => e.to_string()),
+        )
+        .for_each(move |_| {
+            for notification in monitor.notifications(probe()) {
+                if notification == Notification::Ready {
+                    info!(log, "Notifying the service manager of readiness");
+                }
+                if let Err(e) = manager.notify(&notification) {
+                    warn!(log, "Unable to notify the service manager"; "error" => e.to_string());
+                }
+            }
+            Ok(())
+        })
+}
+
+/// Runs `main`, as the Windows service `name` if the process was started with
+/// `WINDOWS_SERVICE_FLAG`.
+///
+/// `main` is called directly on other platforms.
+pub fn start(name: &'static str, main: fn()) {
+    #[cfg(windows)]
+    {
+        if std::env::args().any(|arg| arg == WINDOWS_SERVICE_FLAG) {
+            if let Err(e) = windows::run(name, main) {
+                eprintln!("Unable to start the {} service: {}", name, e);
+            }
+            return;
+        }
+    }
+
+    let _ = name;
+    main()
+}
+
+/// Registers `hook` to be called when the Windows service control manager stops the service, in
+/// place of exiting the process.
+///
+/// systemd stops a service with `SIGTERM`, which should be handled as `Ctrl+C` is.
+pub fn on_stop<F: FnOnce() + Send + 'static>(hook: F) {
+    #[cfg(windows)]
+    windows::set_stop_hook(Box::new(hook));
+    #[cfg(not(windows))]
+    drop(hook);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn probe(live: bool, ready: bool, status: &str) -> Probe {
+        Probe {
+            live,
+            ready,
+            status: status.to_string(),
+        }
+    }
+
+    #[test]
+    fn reports_readiness_once_and_liveness_after() {
+        let mut monitor = Monitor::default();
+
+        assert_eq!(
+            monitor.notifications(probe(false, false, "starting")),
+            vec![
+                Notification::Status("starting".to_string()),
+                Notification::Watchdog
+            ]
+        );
+        assert_eq!(
+            monitor.notifications(probe(true, true, "synced")),
+            vec![
+                Notification::Status("synced".to_string()),
+                Notification::Ready,
+                Notification::Watchdog
+            ]
+        );
+        assert_eq!(
+            monitor.notifications(probe(true, false, "synced")),
+            vec![Notification::Watchdog]
+        );
+        // Once ready, a node which is not live no longer feeds the watchdog.
+        assert_eq!(monitor.notifications(probe(false, true, "synced")), vec![]);
+    }
+}
@@ -0,0 +1,136 @@
+//! The `sd_notify` protocol, by which a service started with `Type=notify` reports its state to
+//! systemd.
+use crate::Notification;
+use std::env;
+use std::io::{self, Error, ErrorKind};
+use std::os::unix::net::UnixDatagram;
+use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
+
+/// Sends notifications to the socket given by systemd.
+pub struct Notifier {
+    socket: UnixDatagram,
+    path: PathBuf,
+    /// The interval within which systemd expects the watchdog to be fed, if it is enabled.
+    watchdog_interval: Option<Duration>,
+}
+
+impl Notifier {
+    /// Returns a notifier if systemd has given the process a notification socket.
+    pub fn from_env() -> io::Result<Option<Self>> {
+        let path = match env::var_os("NOTIFY_SOCKET") {
+            Some(path) => PathBuf::from(path),
+            None => return Ok(None),
+        };
+
+        if path.to_string_lossy().starts_with('@') {
+            return Err(Error::new(
+                ErrorKind::Other,
+                "abstract notification sockets are not supported",
+            ));
+        }
+
+        let watchdog_interval = watchdog_interval(
+            env::var("WATCHDOG_USEC").ok(),
+            env::var("WATCHDOG_PID").ok(),
+            process::id(),
+        );
+
+        Ok(Some(Self {
+            socket: UnixDatagram::unbound()?,
+            path,
+            watchdog_interval,
+        }))
+    }
+
+    pub fn watchdog_interval(&self) -> Option<Duration> {
+        self.watchdog_interval
+    }
+
+    pub fn notify(&self, notification: &Notification) -> io::Result<()> {
+        let message = match notification {
+            Notification::Ready => "READY=1".to_string(),
+            Notification::Watchdog if self.watchdog_interval.is_some() => "WATCHDOG=1".to_string(),
+            Notification::Watchdog => return Ok(()),
+            Notification::Status(status) => format!("STATUS={}", status.replace('\n', " ")),
+        };
+
+        self.socket
+            .send_to(message.as_bytes(), &self.path)
+            .map(|_| ())
+    }
+}
+
+/// Returns the watchdog interval given by `WATCHDOG_USEC`, unless `WATCHDOG_PID` shows that it is
+/// meant for a process other than `pid`.
+fn watchdog_interval(
+    usec: Option<String>,
+    watchdog_pid: Option<String>,
+    pid: u32,
+) -> Option<Duration> {
+    if let Some(watchdog_pid) = watchdog_pid {
+        if watchdog_pid.parse::<u32>().ok()? != pid {
+            return None;
+        }
+    }
+
+    match usec?.parse::<u64>().ok()? {
+        0 => None,
+        usec => Some(Duration::from_micros(usec)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn parses_watchdog_interval() {
+        let some = |s: &str| Some(s.to_string());
+
+        assert_eq!(
+            watchdog_interval(some("30000000"), None, 7),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            watchdog_interval(some("30000000"), some("7"), 7),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(watchdog_interval(some("30000000"), some("8"), 7), None);
+        assert_eq!(watchdog_interval(some("0"), None, 7), None);
+        assert_eq!(watchdog_interval(None, None, 7), None);
+    }
+
+    #[test]
+    fn sends_notifications() {
+        let dir = tempdir().expect("should create temp dir");
+        let path = dir.path().join("notify");
+        let systemd = UnixDatagram::bind(&path).expect("should bind socket");
+
+        let notifier = Notifier {
+            socket: UnixDatagram::unbound().expect("should create socket"),
+            path,
+            watchdog_interval: None,
+        };
+        let mut buf = [0; 64];
+        let mut receive = || {
+            let len = systemd.recv(&mut buf).expect("should receive");
+            String::from_utf8(buf[..len].to_vec()).expect("should be utf8")
+        };
+
+        // The watchdog is not fed unless systemd enables it.
+        notifier
+            .notify(&Notification::Watchdog)
+            .expect("should notify");
+        notifier
+            .notify(&Notification::Status("head slot 3\nsynced".to_string()))
+            .expect("should notify");
+        assert_eq!(receive(), "STATUS=head slot 3 synced");
+        notifier
+            .notify(&Notification::Ready)
+            .expect("should notify");
+        assert_eq!(receive(), "READY=1");
+    }
+}
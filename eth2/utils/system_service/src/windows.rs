@@ -0,0 +1,163 @@
+//! Running as a Windows service.
+//!
+//! The service must be registered with `WINDOWS_SERVICE_FLAG` in its command line, e.g.:
+//!
+//! ```text
+//! sc create lighthouse-beacon-node binPath= "C:\lighthouse\beacon_node.exe --windows-service"
+//! ```
+//!
+//! The service remains start pending until the node is ready. The service control manager has no
+//! watchdog, so each watchdog notification instead advances the start checkpoint.
+use crate::Notification;
+use lazy_static::lazy_static;
+use std::ffi::OsString;
+use std::io::{self, Error, ErrorKind};
+use std::process;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use windows_service::service::{
+    ServiceControl, ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus, ServiceType,
+};
+use windows_service::service_control_handler::{
+    self, ServiceControlHandlerResult, ServiceStatusHandle,
+};
+use windows_service::{define_windows_service, service_dispatcher};
+
+/// The time for which the service control manager is asked to wait for the next status update
+/// whilst the service is pending.
+const WAIT_HINT: Duration = Duration::from_secs(30);
+
+lazy_static! {
+    /// The name and entry point of the service.
+    static ref SERVICE: Mutex<Option<(&'static str, fn())>> = Mutex::new(None);
+    /// The handle with which the status of the service is reported, once registered.
+    static ref STATUS_HANDLE: Mutex<Option<ServiceStatusHandle>> = Mutex::new(None);
+    /// Called in place of exiting when the service is stopped.
+    static ref STOP_HOOK: Mutex<Option<Box<dyn FnOnce() + Send>>> = Mutex::new(None);
+}
+
+/// `true` whilst the service is start pending.
+static STARTING: AtomicBool = AtomicBool::new(false);
+/// The checkpoint of the latest pending status.
+static CHECKPOINT: AtomicU32 = AtomicU32::new(0);
+
+define_windows_service!(ffi_service_main, service_main);
+
+/// Runs `main` as the service `name`, returning once the service has stopped.
+pub fn run(name: &'static str, main: fn()) -> Result<(), windows_service::Error> {
+    if let Ok(mut service) = SERVICE.lock() {
+        *service = Some((name, main));
+    }
+    service_dispatcher::start(name, ffi_service_main)
+}
+
+pub fn set_stop_hook(hook: Box<dyn FnOnce() + Send>) {
+    if let Ok(mut stop_hook) = STOP_HOOK.lock() {
+        *stop_hook = Some(hook);
+    }
+}
+
+/// Reports the state of the service to the service control manager.
+pub struct StatusReporter {
+    handle: ServiceStatusHandle,
+}
+
+impl StatusReporter {
+    /// Returns a reporter if the process is running as a service.
+    pub fn current() -> Option<Self> {
+        let handle = (*STATUS_HANDLE.lock().ok()?)?;
+        Some(Self { handle })
+    }
+
+    pub fn notify(&self, notification: &Notification) -> io::Result<()> {
+        match notification {
+            Notification::Ready => set_state(self.handle, ServiceState::Running),
+            Notification::Watchdog if STARTING.load(Ordering::SeqCst) => {
+                set_state(self.handle, ServiceState::StartPending)
+            }
+            Notification::Watchdog => Ok(()),
+            // The service control manager does not record a status message.
+            Notification::Status(_) => Ok(()),
+        }
+    }
+}
+
+fn service_main(_arguments: Vec<OsString>) {
+    let (name, main) = match SERVICE.lock().ok().and_then(|service| *service) {
+        Some(service) => service,
+        None => return,
+    };
+
+    let handle = match service_control_handler::register(name, handle_control) {
+        Ok(handle) => handle,
+        Err(e) => {
+            eprintln!("Unable to register the {} service: {}", name, e);
+            return;
+        }
+    };
+    if let Ok(mut status_handle) = STATUS_HANDLE.lock() {
+        *status_handle = Some(handle);
+    }
+
+    if let Err(e) = set_state(handle, ServiceState::StartPending) {
+        eprintln!("Unable to report the {} service as starting: {}", name, e);
+    }
+    main();
+    let _ = set_state(handle, ServiceState::Stopped);
+}
+
+fn handle_control(control: ServiceControl) -> ServiceControlHandlerResult {
+    match control {
+        ServiceControl::Stop | ServiceControl::Shutdown => {
+            let handle = STATUS_HANDLE.lock().ok().and_then(|handle| *handle);
+            if let Some(handle) = handle {
+                let _ = set_state(handle, ServiceState::StopPending);
+            }
+
+            match STOP_HOOK.lock().ok().and_then(|mut hook| hook.take()) {
+                Some(hook) => hook(),
+                None => {
+                    if let Some(handle) = handle {
+                        let _ = set_state(handle, ServiceState::Stopped);
+                    }
+                    process::exit(0)
+                }
+            }
+            ServiceControlHandlerResult::NoError
+        }
+        ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+        _ => ServiceControlHandlerResult::NotImplemented,
+    }
+}
+
+/// Reports `state`, advancing the checkpoint if it is pending.
+fn set_state(handle: ServiceStatusHandle, state: ServiceState) -> io::Result<()> {
+    let pending = state == ServiceState::StartPending || state == ServiceState::StopPending;
+    STARTING.store(state == ServiceState::StartPending, Ordering::SeqCst);
+    let checkpoint = if pending {
+        CHECKPOINT.fetch_add(1, Ordering::SeqCst) + 1
+    } else {
+        CHECKPOINT.store(0, Ordering::SeqCst);
+        0
+    };
+
+    handle
+        .set_service_status(ServiceStatus {
+            service_type: ServiceType::OwnProcess,
+            current_state: state,
+            controls_accepted: if state == ServiceState::StopPending {
+                ServiceControlAccept::empty()
+            } else {
+                ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN
+            },
+            exit_code: ServiceExitCode::Win32(0),
+            checkpoint,
+            wait_hint: if pending {
+                WAIT_HINT
+            } else {
+                Duration::default()
+            },
+        })
+        .map_err(|e| Error::new(ErrorKind::Other, e.to_string()))
+}
@@ -1,5 +1,5 @@
 use super::*;
-use ethereum_types::H256;
+use ethereum_types::{H160, H256};
 
 macro_rules! impl_for_bitsize {
     ($type: ident, $bit_size: expr) => {
@@ -73,6 +73,24 @@ macro_rules! impl_for_u8_array {
 impl_for_u8_array!(4);
 impl_for_u8_array!(32);
 
+impl TreeHash for H160 {
+    fn tree_hash_type() -> TreeHashType {
+        TreeHashType::Vector
+    }
+
+    fn tree_hash_packed_encoding(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+
+    fn tree_hash_packing_factor() -> usize {
+        1
+    }
+
+    fn tree_hash_root(&self) -> Vec<u8> {
+        merkle_root(&self.as_bytes().to_vec(), 0)
+    }
+}
+
 impl TreeHash for H256 {
     fn tree_hash_type() -> TreeHashType {
         TreeHashType::Vector
@@ -91,6 +109,30 @@ impl TreeHash for H256 {
     }
 }
 
+/// The SSZ "union" type, of `None` (the null type) and `T`.
+///
+/// The root of `None` is the zero chunk.
+impl<T: TreeHash> TreeHash for Option<T> {
+    fn tree_hash_type() -> TreeHashType {
+        TreeHashType::Union
+    }
+
+    fn tree_hash_packed_encoding(&self) -> Vec<u8> {
+        unreachable!("Union should never be packed.")
+    }
+
+    fn tree_hash_packing_factor() -> usize {
+        unreachable!("Union should never be packed.")
+    }
+
+    fn tree_hash_root(&self) -> Vec<u8> {
+        match self {
+            None => mix_in_type(&[0; BYTES_PER_CHUNK], 0),
+            Some(value) => mix_in_type(&value.tree_hash_root(), 1),
+        }
+    }
+}
+
 // TODO: this implementation always panics, it only exists to allow us to compile whilst
 // refactoring tree hash. Should be removed.
 macro_rules! impl_for_list {
@@ -143,6 +185,19 @@ mod test {
         assert_eq!(false.tree_hash_root(), false_bytes);
     }
 
+    #[test]
+    fn option() {
+        assert_eq!(
+            None::<u64>.tree_hash_root(),
+            mix_in_type(&[0; BYTES_PER_CHUNK], 0)
+        );
+        assert_eq!(
+            Some(42_u64).tree_hash_root(),
+            mix_in_type(&42_u64.tree_hash_root(), 1)
+        );
+        assert_ne!(None::<u64>.tree_hash_root(), Some(0_u64).tree_hash_root());
+    }
+
     #[test]
     fn int_to_bytes() {
         assert_eq!(&int_to_bytes32(0), &[0; 32]);
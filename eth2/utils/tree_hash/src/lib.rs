@@ -30,12 +30,21 @@ pub fn mix_in_length(root: &[u8], length: usize) -> Vec<u8> {
     merkleize_padded::hash_concat(root, &length_bytes)
 }
 
+/// Returns the node created by hashing `root` and `type_index`.
+///
+/// Used in `TreeHash` for inserting the type index (selector) of a union above the root of its
+/// value.
+pub fn mix_in_type(root: &[u8], type_index: usize) -> Vec<u8> {
+    mix_in_length(root, type_index)
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum TreeHashType {
     Basic,
     Vector,
     List,
     Container,
+    Union,
 }
 
 pub trait TreeHash {
@@ -113,4 +122,16 @@ mod test {
 
         assert_eq!(mix_in_length(&[42; BYTES_PER_CHUNK], 42), hash);
     }
+
+    #[test]
+    fn mix_type() {
+        let hash = {
+            let mut preimage = vec![0; BYTES_PER_CHUNK];
+            preimage.append(&mut vec![1]);
+            preimage.append(&mut vec![0; BYTES_PER_CHUNK - 1]);
+            eth2_hashing::hash(&preimage)
+        };
+
+        assert_eq!(mix_in_type(&[0; BYTES_PER_CHUNK], 1), hash);
+    }
 }
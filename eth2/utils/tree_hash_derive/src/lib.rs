@@ -37,9 +37,13 @@ fn should_skip_hashing(field: &syn::Field) -> bool {
         .any(|attr| attr.into_token_stream().to_string() == "# [ tree_hash ( skip_hashing ) ]")
 }
 
-/// Implements `tree_hash::TreeHash` for some `struct`.
+/// Implements `tree_hash::TreeHash` for some `struct` or `enum`.
 ///
 /// Fields are hashed in the order they are defined.
+///
+/// An `enum` is hashed as a union: the root of the value of its variant (or the zero chunk, for a
+/// variant with no fields) mixed in with the index of the variant. Each variant must have either
+/// no fields or a single unnamed field.
 #[proc_macro_derive(TreeHash, attributes(tree_hash))]
 pub fn tree_hash_derive(input: TokenStream) -> TokenStream {
     let item = parse_macro_input!(input as DeriveInput);
@@ -49,7 +53,8 @@ pub fn tree_hash_derive(input: TokenStream) -> TokenStream {
 
     let struct_data = match &item.data {
         syn::Data::Struct(s) => s,
-        _ => panic!("tree_hash_derive only supports structs."),
+        syn::Data::Enum(e) => return tree_hash_derive_enum(&item, e),
+        _ => panic!("tree_hash_derive only supports structs and enums."),
     };
 
     let idents = get_hashable_named_field_idents(&struct_data);
@@ -82,6 +87,54 @@ pub fn tree_hash_derive(input: TokenStream) -> TokenStream {
     output.into()
 }
 
+/// Implements `tree_hash::TreeHash` for an `enum`, as a union.
+fn tree_hash_derive_enum(item: &DeriveInput, enum_data: &syn::DataEnum) -> TokenStream {
+    let name = &item.ident;
+    let (impl_generics, ty_generics, where_clause) = &item.generics.split_for_impl();
+
+    let arms = enum_data.variants.iter().enumerate().map(|(index, variant)| {
+        let ident = &variant.ident;
+        match &variant.fields {
+            syn::Fields::Unit => quote! {
+                #name::#ident => tree_hash::mix_in_type(&[0; tree_hash::BYTES_PER_CHUNK], #index),
+            },
+            syn::Fields::Unnamed(fields) if fields.unnamed.len() == 1 => quote! {
+                #name::#ident(ref value) => {
+                    tree_hash::mix_in_type(&tree_hash::TreeHash::tree_hash_root(value), #index)
+                }
+            },
+            _ => panic!(
+                "tree_hash_derive only supports enum variants with no fields or a single unnamed field."
+            ),
+        }
+    });
+
+    let output = quote! {
+        impl #impl_generics tree_hash::TreeHash for #name #ty_generics #where_clause {
+            fn tree_hash_type() -> tree_hash::TreeHashType {
+                tree_hash::TreeHashType::Union
+            }
+
+            fn tree_hash_packed_encoding(&self) -> Vec<u8> {
+                unreachable!("Union should never be packed.")
+            }
+
+            fn tree_hash_packing_factor() -> usize {
+                unreachable!("Union should never be packed.")
+            }
+
+            fn tree_hash_root(&self) -> Vec<u8> {
+                match *self {
+                    #(
+                        #arms
+                    )*
+                }
+            }
+        }
+    };
+    output.into()
+}
+
 #[proc_macro_derive(SignedRoot, attributes(signed_root))]
 pub fn tree_hash_signed_root_derive(input: TokenStream) -> TokenStream {
     let item = parse_macro_input!(input as DeriveInput);
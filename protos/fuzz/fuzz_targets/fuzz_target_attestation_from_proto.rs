@@ -0,0 +1,20 @@
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+extern crate protobuf;
+extern crate protos;
+extern crate types;
+
+use protos::convert::{FromProto, ToProto};
+use protos::services;
+use types::{Attestation, MinimalEthSpec};
+
+// Fuzz Attestation::from_proto()
+fuzz_target!(|data: &[u8]| {
+    if let Ok(proto) = protobuf::parse_from_bytes::<services::Attestation>(data) {
+        if let Ok(attestation) = Attestation::<MinimalEthSpec>::from_proto(&proto) {
+            // A decoded attestation should survive another round trip
+            let decoded = Attestation::from_proto(&attestation.to_proto()).unwrap();
+            assert_eq!(attestation, decoded);
+        }
+    }
+});
@@ -0,0 +1,20 @@
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+extern crate protobuf;
+extern crate protos;
+extern crate types;
+
+use protos::convert::{block_from_proto, ToProto};
+use protos::services::BeaconBlock;
+use types::{BlockVariant, MinimalEthSpec};
+
+// Fuzz block_from_proto()
+fuzz_target!(|data: &[u8]| {
+    if let Ok(proto) = protobuf::parse_from_bytes::<BeaconBlock>(data) {
+        if let Ok(block) = block_from_proto::<MinimalEthSpec>(&proto, |_| BlockVariant::Base) {
+            // A decoded block should survive another round trip
+            let decoded = block_from_proto(&block.to_proto(), |_| BlockVariant::Base).unwrap();
+            assert_eq!(block, decoded);
+        }
+    }
+});
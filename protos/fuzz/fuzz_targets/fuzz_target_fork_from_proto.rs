@@ -0,0 +1,24 @@
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+extern crate protobuf;
+extern crate protos;
+extern crate types;
+
+use protos::convert::{FromProto, ToProto};
+use protos::services;
+use types::Fork;
+
+// Fuzz Fork::from_proto()
+fuzz_target!(|data: &[u8]| {
+    if let Ok(proto) = protobuf::parse_from_bytes::<services::Fork>(data) {
+        let result = Fork::from_proto(&proto);
+        if proto.get_previous_version().len() == 4 && proto.get_current_version().len() == 4 {
+            // Should have valid result
+            let fork = result.unwrap();
+            assert_eq!(Fork::from_proto(&fork.to_proto()), Ok(fork));
+        } else {
+            // Versions of any other length should return error
+            assert!(result.is_err());
+        }
+    }
+});
@@ -0,0 +1,153 @@
+//! Authentication of the gRPC link between validator clients and beacon nodes.
+//!
+//! The link may be secured in two independent ways:
+//!
+//! - Mutual TLS, where each peer presents a certificate signed by a certificate authority which
+//!   the other trusts.
+//! - A shared token, which the validator client sends with every call and the beacon node checks
+//!   before handling the call.
+use grpcio::{
+    CallOption, CertificateRequestType, ChannelCredentials, ChannelCredentialsBuilder,
+    MetadataBuilder, RpcContext, RpcStatus, RpcStatusCode, ServerCredentials,
+    ServerCredentialsBuilder,
+};
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+/// The request header which carries the token.
+pub const AUTHORIZATION_HEADER: &str = "authorization";
+
+/// PEM encoded TLS credentials of one end of the link.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TlsCredentials {
+    /// The certificate authority which signs the certificate of the other end.
+    ca: Vec<u8>,
+    cert: Vec<u8>,
+    key: Vec<u8>,
+}
+
+impl TlsCredentials {
+    /// Reads the certificate authority, certificate and private key from PEM files.
+    pub fn load(ca: &Path, cert: &Path, key: &Path) -> Result<Self, Error> {
+        Ok(Self {
+            ca: fs::read(ca)?,
+            cert: fs::read(cert)?,
+            key: fs::read(key)?,
+        })
+    }
+
+    /// Returns the credentials of a server which only accepts clients with a certificate signed
+    /// by the certificate authority.
+    pub fn server(&self) -> ServerCredentials {
+        ServerCredentialsBuilder::new()
+            .root_cert(
+                self.ca.clone(),
+                CertificateRequestType::RequestAndRequireClientCertificateAndVerify,
+            )
+            .add_cert(self.cert.clone(), self.key.clone())
+            .build()
+    }
+
+    /// Returns the credentials of a client which only trusts servers with a certificate signed by
+    /// the certificate authority.
+    pub fn channel(&self) -> ChannelCredentials {
+        ChannelCredentialsBuilder::new()
+            .root_cert(self.ca.clone())
+            .cert(self.cert.clone(), self.key.clone())
+            .build()
+    }
+}
+
+/// Reads a token from `path`, ignoring surrounding whitespace.
+///
+/// Returns an error if the token is empty or cannot be sent in a header.
+pub fn read_token(path: &Path) -> Result<String, Error> {
+    let token = fs::read_to_string(path)?.trim().to_string();
+    if token.is_empty() || !token.bytes().all(|byte| byte.is_ascii_graphic()) {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "the token must be non-empty printable ASCII",
+        ));
+    }
+    Ok(token)
+}
+
+/// A gRPC client whose calls carry a token, if one is configured.
+#[derive(Clone)]
+pub struct Authenticated<C> {
+    client: C,
+    token: Option<String>,
+}
+
+impl<C> Authenticated<C> {
+    pub fn new(client: C, token: Option<String>) -> Self {
+        Self { client, token }
+    }
+
+    pub fn client(&self) -> &C {
+        &self.client
+    }
+
+    /// Returns the options with which every call must be made.
+    pub fn call_option(&self) -> CallOption {
+        let call_option = CallOption::default();
+        let token = match &self.token {
+            Some(token) => token,
+            None => return call_option,
+        };
+
+        let mut headers = MetadataBuilder::new();
+        // `read_token` ensures the token is a valid header value.
+        match headers.add_str(AUTHORIZATION_HEADER, &bearer(token)) {
+            Ok(_) => call_option.headers(headers.build()),
+            Err(_) => call_option,
+        }
+    }
+}
+
+/// Checks that the call of `ctx` carries `token`, if one is configured.
+///
+/// Returns the status with which to fail the call if it does not.
+pub fn check_token(ctx: &RpcContext, token: Option<&str>) -> Result<(), RpcStatus> {
+    let expected = match token {
+        Some(token) => bearer(token),
+        None => return Ok(()),
+    };
+
+    let authorized = ctx.request_headers().iter().any(|(key, value)| {
+        key == AUTHORIZATION_HEADER && tokens_match(value, expected.as_bytes())
+    });
+
+    if authorized {
+        Ok(())
+    } else {
+        Err(RpcStatus::new(
+            RpcStatusCode::Unauthenticated,
+            Some("missing or invalid authentication token".to_string()),
+        ))
+    }
+}
+
+fn bearer(token: &str) -> String {
+    format!("Bearer {}", token)
+}
+
+/// Compares `a` and `b` in time independent of their contents, so that a token may not be guessed
+/// by timing failed calls.
+fn tokens_match(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_tokens() {
+        assert!(tokens_match(b"Bearer secret", b"Bearer secret"));
+        assert!(!tokens_match(b"Bearer secret", b"Bearer secreT"));
+        assert!(!tokens_match(b"Bearer secret", b"Bearer secret2"));
+        assert!(!tokens_match(b"", b"Bearer secret"));
+    }
+}
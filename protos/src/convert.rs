@@ -0,0 +1,276 @@
+//! Conversions between the messages of `services.proto` and their `types` equivalents, in both
+//! directions.
+//!
+//! Most objects are exchanged as their SSZ encoding, wrapped in a message of the same name. Every
+//! conversion from a message checks the fields it reads (e.g., that a root is 32 bytes), returning
+//! an `Error` rather than panicking or truncating, so that a peer with an incompatible wire format
+//! is detected rather than silently corrupting what it sends.
+//!
+//! ## Versioning
+//!
+//! The messages belong to the package `PACKAGE`. Adding a field to a message is compatible, but a
+//! change to the meaning or encoding of an existing field (e.g., changing the SSZ of a message to
+//! that of another type) must be made in a message of a new package version (e.g.,
+//! `ethereum.beacon.rpc.v2`), so that older peers reject it rather than misinterpreting it.
+//!
+//! Since blocks are not self-describing, a `BeaconBlock` message also carries the `BlockVariant`
+//! of its SSZ, and a block is refused unless it is of the variant that the receiver expects at its
+//! slot.
+use crate::services;
+use ssz::{Decode, DecodeError, Encode};
+use types::{
+    Attestation, AttestationData, AttesterSlashing, BeaconBlockAny, BeaconBlockHeader,
+    BlockVariant, EthSpec, Fork, Hash256, ProposerSlashing, Slot, ValidatorRegistration,
+};
+
+/// The package of the messages of `services.proto`, which is their wire format version.
+pub const PACKAGE: &str = "ethereum.beacon.rpc.v1";
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// The SSZ of the message could not be decoded.
+    Ssz(DecodeError),
+    /// A field of fixed length has the wrong length.
+    InvalidLength {
+        field: &'static str,
+        expected: usize,
+        found: usize,
+    },
+    /// The block variant of a block is not known to this version.
+    UnknownVariant(u32),
+    /// A block is not of the variant expected at its slot.
+    WrongVariant {
+        expected: BlockVariant,
+        found: BlockVariant,
+    },
+}
+
+impl From<DecodeError> for Error {
+    fn from(e: DecodeError) -> Error {
+        Error::Ssz(e)
+    }
+}
+
+/// A `types` struct which may be converted to the message `P`.
+pub trait ToProto<P> {
+    fn to_proto(&self) -> P;
+}
+
+/// A `types` struct which may be converted from the message `P`.
+pub trait FromProto<P>: Sized {
+    fn from_proto(proto: &P) -> Result<Self, Error>;
+}
+
+/// Implements `ToProto` and `FromProto` for a type exchanged as its SSZ, in a message with an
+/// `ssz` field.
+macro_rules! ssz_message {
+    ($type: ty, $proto: ty) => {
+        ssz_message!($type, $proto,);
+    };
+    ($type: ty, $proto: ty, $($generic: ident),*) => {
+        impl<$($generic: EthSpec),*> ToProto<$proto> for $type {
+            fn to_proto(&self) -> $proto {
+                let mut proto = <$proto>::new();
+                proto.set_ssz(self.as_ssz_bytes());
+                proto
+            }
+        }
+
+        impl<$($generic: EthSpec),*> FromProto<$proto> for $type {
+            fn from_proto(proto: &$proto) -> Result<Self, Error> {
+                Ok(<$type>::from_ssz_bytes(proto.get_ssz())?)
+            }
+        }
+    };
+}
+
+ssz_message!(AttestationData, services::AttestationData);
+ssz_message!(Attestation<E>, services::Attestation, E);
+ssz_message!(BeaconBlockHeader, services::BeaconBlockHeader);
+ssz_message!(ProposerSlashing, services::ProposerSlashing);
+ssz_message!(AttesterSlashing<E>, services::AttesterSlashing, E);
+ssz_message!(ValidatorRegistration, services::ValidatorRegistration);
+
+impl ToProto<services::Fork> for Fork {
+    fn to_proto(&self) -> services::Fork {
+        let mut proto = services::Fork::new();
+        proto.set_previous_version(self.previous_version.to_vec());
+        proto.set_current_version(self.current_version.to_vec());
+        proto.set_epoch(self.epoch.as_u64());
+        proto
+    }
+}
+
+impl FromProto<services::Fork> for Fork {
+    fn from_proto(proto: &services::Fork) -> Result<Self, Error> {
+        Ok(Fork {
+            previous_version: version("previous_version", proto.get_previous_version())?,
+            current_version: version("current_version", proto.get_current_version())?,
+            epoch: proto.get_epoch().into(),
+        })
+    }
+}
+
+impl<E: EthSpec> ToProto<services::BeaconBlock> for BeaconBlockAny<E> {
+    fn to_proto(&self) -> services::BeaconBlock {
+        let mut proto = services::BeaconBlock::new();
+        proto.set_ssz(self.as_ssz_bytes());
+        proto.set_variant(variant_to_proto(self.variant()));
+        proto
+    }
+}
+
+/// Decodes the block of `proto`, which must be of the variant `variant_at` its slot (that of the
+/// fork at the slot, see `ChainSpec::block_variant`).
+pub fn block_from_proto<E: EthSpec>(
+    proto: &services::BeaconBlock,
+    variant_at: impl FnOnce(Slot) -> BlockVariant,
+) -> Result<BeaconBlockAny<E>, Error> {
+    let found = variant_from_proto(proto.get_variant())?;
+    let block = BeaconBlockAny::from_ssz_bytes(proto.get_ssz(), found)?;
+    let expected = variant_at(block.slot());
+    if found != expected {
+        return Err(Error::WrongVariant { expected, found });
+    }
+    Ok(block)
+}
+
+/// Returns the wire encoding of `variant`.
+///
+/// The base variant is zero, so blocks from peers which do not set the variant are base blocks.
+pub fn variant_to_proto(variant: BlockVariant) -> u32 {
+    match variant {
+        BlockVariant::Base => 0,
+    }
+}
+
+/// Returns the variant of its wire encoding, `proto`.
+pub fn variant_from_proto(proto: u32) -> Result<BlockVariant, Error> {
+    match proto {
+        0 => Ok(BlockVariant::Base),
+        other => Err(Error::UnknownVariant(other)),
+    }
+}
+
+/// Decodes the 32 byte root in `field`.
+pub fn root_from_proto(field: &'static str, bytes: &[u8]) -> Result<Hash256, Error> {
+    if bytes.len() != 32 {
+        return Err(Error::InvalidLength {
+            field,
+            expected: 32,
+            found: bytes.len(),
+        });
+    }
+    Ok(Hash256::from_slice(bytes))
+}
+
+/// Decodes the 4 byte fork version in `field`.
+fn version(field: &'static str, bytes: &[u8]) -> Result<[u8; 4], Error> {
+    if bytes.len() != 4 {
+        return Err(Error::InvalidLength {
+            field,
+            expected: 4,
+            found: bytes.len(),
+        });
+    }
+    let mut version = [0; 4];
+    version.copy_from_slice(bytes);
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt::Debug;
+    use types::test_utils::{SeedableRng, TestRandom, XorShiftRng};
+    use types::{BeaconBlock, MinimalEthSpec};
+
+    /// The number of random instances of each type which are round-tripped.
+    const CASES: u8 = 32;
+
+    /// Checks that random instances of `T` survive conversion to `P` and back, and that the
+    /// conversion of a message whose `bytes` have been truncated does not panic.
+    fn round_trip<T, P>(bytes: impl Fn(&mut P) -> &mut Vec<u8>)
+    where
+        T: TestRandom + ToProto<P> + FromProto<P> + PartialEq + Debug,
+    {
+        for seed in 0..CASES {
+            let mut rng = XorShiftRng::from_seed([seed; 16]);
+            let original = T::random_for_test(&mut rng);
+            let mut proto = original.to_proto();
+            assert_eq!(T::from_proto(&proto), Ok(original));
+
+            // A truncated bitfield may still be valid, so the conversion may succeed.
+            bytes(&mut proto).pop();
+            let _ = T::from_proto(&proto);
+        }
+    }
+
+    #[test]
+    fn ssz_messages_round_trip() {
+        round_trip::<AttestationData, _>(services::AttestationData::mut_ssz);
+        round_trip::<Attestation<MinimalEthSpec>, _>(services::Attestation::mut_ssz);
+        round_trip::<BeaconBlockHeader, _>(services::BeaconBlockHeader::mut_ssz);
+        round_trip::<ProposerSlashing, _>(services::ProposerSlashing::mut_ssz);
+        round_trip::<AttesterSlashing<MinimalEthSpec>, _>(services::AttesterSlashing::mut_ssz);
+        round_trip::<ValidatorRegistration, _>(services::ValidatorRegistration::mut_ssz);
+    }
+
+    #[test]
+    fn forks_round_trip() {
+        round_trip::<Fork, _>(services::Fork::mut_current_version);
+        let mut rng = XorShiftRng::from_seed([0; 16]);
+        let mut proto = Fork::random_for_test(&mut rng).to_proto();
+        proto.mut_current_version().pop();
+        assert!(Fork::from_proto(&proto).is_err());
+
+        let mut proto = Fork::default().to_proto();
+        proto.set_previous_version(vec![0; 5]);
+        assert_eq!(
+            Fork::from_proto(&proto),
+            Err(Error::InvalidLength {
+                field: "previous_version",
+                expected: 4,
+                found: 5,
+            })
+        );
+    }
+
+    #[test]
+    fn blocks_round_trip_as_their_variant() {
+        for seed in 0..CASES {
+            let mut rng = XorShiftRng::from_seed([seed; 16]);
+            let block: BeaconBlockAny<MinimalEthSpec> =
+                BeaconBlock::<MinimalEthSpec>::random_for_test(&mut rng).into();
+            let mut proto = block.to_proto();
+            assert_eq!(
+                block_from_proto(&proto, |_| BlockVariant::Base),
+                Ok(block.clone())
+            );
+
+            proto.set_variant(1);
+            assert_eq!(
+                block_from_proto::<MinimalEthSpec>(&proto, |_| BlockVariant::Base),
+                Err(Error::UnknownVariant(1))
+            );
+
+            proto.set_variant(0);
+            proto.mut_ssz().truncate(8);
+            assert!(block_from_proto::<MinimalEthSpec>(&proto, |_| BlockVariant::Base).is_err());
+        }
+    }
+
+    #[test]
+    fn roots_have_32_bytes() {
+        let root = Hash256::repeat_byte(7);
+        assert_eq!(root_from_proto("root", root.as_bytes()), Ok(root));
+        assert_eq!(
+            root_from_proto("root", &[]),
+            Err(Error::InvalidLength {
+                field: "root",
+                expected: 32,
+                found: 0,
+            })
+        );
+    }
+}
@@ -1,3 +1,6 @@
+pub mod auth;
+pub mod convert;
+
 // The protobuf code-generator is not up-to-date with clippy, therefore we silence some warnings.
 #[allow(renamed_and_removed_lints)]
 pub mod services;
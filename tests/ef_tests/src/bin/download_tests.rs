@@ -0,0 +1,153 @@
+//! Downloads the spec test vectors pinned in `spec-tests.lock` into `eth2.0-spec-tests`.
+//!
+//! Each archive is checked against its pinned SHA-256 before anything is unpacked, and an archive
+//! without a pinned checksum is refused. The lock is only written when asked to: `--pin` pins the
+//! archives without a checksum, and `--version` pins (and downloads) another release. The vectors
+//! are unpacked beside the existing ones and swapped in once complete, so a failed download leaves
+//! the existing vectors as they were.
+use clap::{App, Arg};
+use ef_tests::spec_tests::{crate_dir, Lock, LOCK_FILENAME, SPEC_TESTS_DIR, VERSION_FILENAME};
+use flate2::read::GzDecoder;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::process;
+
+fn main() {
+    let matches = App::new("download_tests")
+        .about("Downloads the pinned spec test vectors for the ef_tests runner.")
+        .arg(
+            Arg::with_name("version")
+                .long("version")
+                .value_name("TAG")
+                .takes_value(true)
+                .help("Pins and downloads this release of eth2.0-spec-tests instead (e.g., v0.8.3), discarding the pinned checksums."),
+        )
+        .arg(
+            Arg::with_name("pin")
+                .long("pin")
+                .help("Pins the checksums of the archives which have none in the lock, as downloaded."),
+        )
+        .arg(
+            Arg::with_name("force")
+                .long("force")
+                .help("Replaces the test vectors even if they are a git submodule checkout."),
+        )
+        .get_matches();
+
+    let version = matches.value_of("version");
+    let pin = matches.is_present("pin") || version.is_some();
+    if let Err(e) = run(version, pin, matches.is_present("force")) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run(version: Option<&str>, pin: bool, force: bool) -> Result<(), String> {
+    let lock_path = crate_dir().join(LOCK_FILENAME);
+    let mut lock = match version {
+        Some(version) => Lock::new(version.to_string()),
+        None => Lock::load(&lock_path)?,
+    };
+
+    let unpinned: Vec<&str> = lock
+        .archives
+        .iter()
+        .filter(|(_, checksum)| checksum.is_none())
+        .map(|(archive, _)| archive.as_str())
+        .collect();
+    if !unpinned.is_empty() && !pin {
+        return Err(format!(
+            "{} pins no checksum of {}; pass --pin to pin them as downloaded, and commit the lock",
+            LOCK_FILENAME,
+            unpinned.join(", ")
+        ));
+    }
+
+    let spec_tests_dir = crate_dir().join(SPEC_TESTS_DIR);
+    if spec_tests_dir.join(".git").exists() && !force {
+        return Err(format!(
+            "{:?} is a git submodule checkout; run `git submodule update` instead, or pass --force to replace it",
+            spec_tests_dir
+        ));
+    }
+
+    let staging_dir = crate_dir().join(format!("{}.download", SPEC_TESTS_DIR));
+    if staging_dir.exists() {
+        fs::remove_dir_all(&staging_dir)
+            .map_err(|e| format!("unable to remove {:?}: {}", staging_dir, e))?;
+    }
+    fs::create_dir_all(&staging_dir)
+        .map_err(|e| format!("unable to create {:?}: {}", staging_dir, e))?;
+
+    let mut pinned_new = false;
+    for i in 0..lock.archives.len() {
+        let (archive, pinned) = lock.archives[i].clone();
+        let url = lock.url(&archive);
+        println!("Downloading {}", url);
+        let bytes = download(&url)?;
+
+        let checksum = hex::encode(Sha256::digest(&bytes));
+        match pinned {
+            Some(ref pinned) if *pinned != checksum => {
+                return Err(format!(
+                    "the SHA-256 of {} is {}, but {} is pinned in {}",
+                    url, checksum, pinned, LOCK_FILENAME
+                ));
+            }
+            Some(_) => println!("Verified {} ({})", archive, checksum),
+            None => {
+                println!("Pinned {} ({})", archive, checksum);
+                lock.archives[i].1 = Some(checksum);
+                pinned_new = true;
+            }
+        }
+
+        unpack(&bytes, &staging_dir).map_err(|e| format!("unable to unpack {}: {}", url, e))?;
+    }
+
+    fs::write(
+        staging_dir.join(VERSION_FILENAME),
+        format!("{}\n", lock.version),
+    )
+    .map_err(|e| format!("unable to write the version of the test vectors: {}", e))?;
+    if spec_tests_dir.exists() {
+        fs::remove_dir_all(&spec_tests_dir)
+            .map_err(|e| format!("unable to remove {:?}: {}", spec_tests_dir, e))?;
+    }
+    fs::rename(&staging_dir, &spec_tests_dir).map_err(|e| {
+        format!(
+            "unable to move the test vectors into {:?}: {}",
+            spec_tests_dir, e
+        )
+    })?;
+
+    if pinned_new {
+        lock.save(&lock_path)
+            .map_err(|e| format!("unable to write {:?}: {}", lock_path, e))?;
+        println!(
+            "Updated {:?}; commit it to pin these test vectors",
+            lock_path
+        );
+    }
+    println!("Test vectors {} are in {:?}", lock.version, spec_tests_dir);
+    Ok(())
+}
+
+/// Returns the body of a successful GET of `url`.
+fn download(url: &str) -> Result<Vec<u8>, String> {
+    let mut response = reqwest::get(url)
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("unable to download {}: {}", url, e))?;
+    let mut bytes = vec![];
+    response
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("unable to download {}: {}", url, e))?;
+    Ok(bytes)
+}
+
+/// Unpacks the gzipped tarball `bytes` into `dir`.
+fn unpack(bytes: &[u8], dir: &Path) -> std::io::Result<()> {
+    tar::Archive::new(GzDecoder::new(bytes)).unpack(dir)
+}
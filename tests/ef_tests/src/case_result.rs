@@ -20,6 +20,15 @@ impl CaseResult {
             result,
         }
     }
+
+    /// The result of a case which could not be decoded, and so was not run.
+    pub fn failed_to_decode(case_index: usize, error: Error) -> Self {
+        CaseResult {
+            case_index,
+            desc: "unable to decode".to_string(),
+            result: Err(error),
+        }
+    }
 }
 
 /// Same as `compare_result_detailed`, however it drops the caches on both states before
@@ -1,5 +1,10 @@
 use super::*;
+use crate::yaml_decode::decode_yaml;
+use lazy_static::lazy_static;
+use state_processing::{BlockProcessingError, CommitteeCacheArena};
 use std::fmt::Debug;
+use std::path::Path;
+use types::{BeaconState, BeaconStateError, ChainSpec};
 
 mod bls_aggregate_pubkeys;
 mod bls_aggregate_sigs;
@@ -12,6 +17,7 @@ mod epoch_processing_final_updates;
 mod epoch_processing_justification_and_finalization;
 mod epoch_processing_registry_updates;
 mod epoch_processing_slashings;
+mod fork_choice;
 mod genesis_initialization;
 mod genesis_validity;
 mod operations_attestation;
@@ -21,6 +27,7 @@ mod operations_deposit;
 mod operations_exit;
 mod operations_proposer_slashing;
 mod operations_transfer;
+mod rewards;
 mod sanity_blocks;
 mod sanity_slots;
 mod shuffling;
@@ -38,6 +45,7 @@ pub use epoch_processing_final_updates::*;
 pub use epoch_processing_justification_and_finalization::*;
 pub use epoch_processing_registry_updates::*;
 pub use epoch_processing_slashings::*;
+pub use fork_choice::*;
 pub use genesis_initialization::*;
 pub use genesis_validity::*;
 pub use operations_attestation::*;
@@ -47,12 +55,45 @@ pub use operations_deposit::*;
 pub use operations_exit::*;
 pub use operations_proposer_slashing::*;
 pub use operations_transfer::*;
+pub use rewards::*;
 pub use sanity_blocks::*;
 pub use sanity_slots::*;
 pub use shuffling::*;
 pub use ssz_generic::*;
 pub use ssz_static::*;
 
+lazy_static! {
+    /// Shared by all cases, so that consecutive cases with the same shuffling only shuffle once.
+    static ref COMMITTEE_CACHE_ARENA: CommitteeCacheArena = CommitteeCacheArena::default();
+}
+
+/// Build all caches of `state`, copying its committee caches from `COMMITTEE_CACHE_ARENA` where
+/// possible.
+pub fn build_caches<E: EthSpec>(
+    state: &mut BeaconState<E>,
+    spec: &ChainSpec,
+) -> Result<(), BeaconStateError> {
+    COMMITTEE_CACHE_ARENA.build_committee_caches(state, spec)?;
+    state.build_all_caches(spec)
+}
+
+/// Apply `process` to `state`, returning the post-state.
+///
+/// Processing requires the epoch cache, which is built first via `build_caches`, so that it may
+/// be shared with previous cases.
+pub fn process_operation<E: EthSpec, F>(
+    mut state: BeaconState<E>,
+    spec: &ChainSpec,
+    process: F,
+) -> Result<BeaconState<E>, BlockProcessingError>
+where
+    F: FnOnce(&mut BeaconState<E>) -> Result<(), BlockProcessingError>,
+{
+    build_caches(&mut state, spec)?;
+    process(&mut state)?;
+    Ok(state)
+}
+
 pub trait Case: Debug {
     /// An optional field for implementing a custom description.
     ///
@@ -68,9 +109,16 @@ pub trait Case: Debug {
     fn result(&self, case_index: usize) -> Result<(), Error>;
 }
 
+/// A case which is loaded from the files of its directory, rather than decoded from a YAML list of
+/// cases.
+pub trait LoadCase: Sized {
+    fn load_from_dir(path: &Path) -> Result<Self, Error>;
+}
+
 #[derive(Debug)]
 pub struct Cases<T> {
-    pub test_cases: Vec<T>,
+    /// Each case, or the reason it could not be decoded.
+    pub test_cases: Vec<Result<T, Error>>,
 }
 
 impl<T> EfTest for Cases<T>
@@ -81,14 +129,20 @@ where
         self.test_cases
             .iter()
             .enumerate()
-            .map(|(i, tc)| CaseResult::new(i, tc, tc.result(i)))
+            .map(|(i, tc)| match tc {
+                Ok(tc) => CaseResult::new(i, tc, tc.result(i)),
+                Err(e) => CaseResult::failed_to_decode(i, e.clone()),
+            })
             .collect()
     }
 }
 
-impl<T: YamlDecode> YamlDecode for Cases<T> {
-    /// Decodes a YAML list of test cases
-    fn yaml_decode(yaml: &str) -> Result<Self, Error> {
+impl<T: YamlDecode> Cases<T> {
+    /// Decodes a YAML list of test cases, the first of which starts on line `first_line` of the
+    /// document.
+    ///
+    /// A case which cannot be decoded does not prevent the others from being decoded.
+    pub fn decode(yaml: &str, first_line: usize) -> Self {
         let mut p = 0;
         let mut elems: Vec<&str> = yaml
             .match_indices("\n- ")
@@ -104,17 +158,32 @@ impl<T: YamlDecode> YamlDecode for Cases<T> {
 
         elems.push(&yaml[p..]);
 
+        let mut line = first_line;
         let test_cases = elems
             .iter()
-            .map(|s| {
+            .enumerate()
+            .map(|(i, s)| {
+                let start_line = line;
+                line += s.matches('\n').count();
+
                 // Remove the `- ` prefix.
-                let s = &s[2..];
+                let s = s.get(2..).unwrap_or("");
                 // Remove a single level of indenting.
-                s.replace("\n  ", "\n")
+                let s = s.replace("\n  ", "\n");
+
+                T::yaml_decode(&s).map_err(|e| {
+                    e.in_context(format!("case[{}] (starting on line {})", i, start_line))
+                })
             })
-            .map(|s| T::yaml_decode(&s.to_string()).unwrap())
             .collect();
 
-        Ok(Self { test_cases })
+        Self { test_cases }
+    }
+}
+
+impl<T: YamlDecode> YamlDecode for Cases<T> {
+    /// Decodes a YAML list of test cases
+    fn yaml_decode(yaml: &str) -> Result<Self, Error> {
+        Ok(Self::decode(yaml, 1))
     }
 }
@@ -11,7 +11,7 @@ pub struct BlsAggregateSigs {
 
 impl YamlDecode for BlsAggregateSigs {
     fn yaml_decode(yaml: &str) -> Result<Self, Error> {
-        Ok(serde_yaml::from_str(yaml).unwrap())
+        decode_yaml(yaml)
     }
 }
 
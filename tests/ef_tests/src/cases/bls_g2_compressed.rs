@@ -17,7 +17,7 @@ pub struct BlsG2Compressed {
 
 impl YamlDecode for BlsG2Compressed {
     fn yaml_decode(yaml: &str) -> Result<Self, Error> {
-        Ok(serde_yaml::from_str(yaml).unwrap())
+        decode_yaml(yaml)
     }
 }
 
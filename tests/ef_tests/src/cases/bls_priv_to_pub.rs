@@ -11,7 +11,7 @@ pub struct BlsPrivToPub {
 
 impl YamlDecode for BlsPrivToPub {
     fn yaml_decode(yaml: &str) -> Result<Self, Error> {
-        Ok(serde_yaml::from_str(yaml).unwrap())
+        decode_yaml(yaml)
     }
 }
 
@@ -18,7 +18,7 @@ pub struct BlsSign {
 
 impl YamlDecode for BlsSign {
     fn yaml_decode(yaml: &str) -> Result<Self, Error> {
-        Ok(serde_yaml::from_str(yaml).unwrap())
+        decode_yaml(yaml)
     }
 }
 
@@ -14,7 +14,7 @@ pub struct EpochProcessingCrosslinks<E: EthSpec> {
 
 impl<E: EthSpec> YamlDecode for EpochProcessingCrosslinks<E> {
     fn yaml_decode(yaml: &str) -> Result<Self, Error> {
-        Ok(serde_yaml::from_str(yaml).unwrap())
+        decode_yaml(yaml)
     }
 }
 
@@ -27,8 +27,8 @@ impl<E: EthSpec> Case for EpochProcessingCrosslinks<E> {
         let mut state = self.pre.clone();
         let mut expected = self.post.clone();
 
-        // Processing requires the epoch cache.
-        state.build_all_caches(&E::default_spec()).unwrap();
+        // Processing requires the epoch cache, which may be shared with previous cases.
+        build_caches(&mut state, &E::default_spec()).unwrap();
 
         let mut result = process_crosslinks(&mut state, &E::default_spec()).map(|_| state);
 
@@ -14,7 +14,7 @@ pub struct EpochProcessingFinalUpdates<E: EthSpec> {
 
 impl<E: EthSpec> YamlDecode for EpochProcessingFinalUpdates<E> {
     fn yaml_decode(yaml: &str) -> Result<Self, Error> {
-        Ok(serde_yaml::from_str(yaml).unwrap())
+        decode_yaml(yaml)
     }
 }
 
@@ -30,8 +30,8 @@ impl<E: EthSpec> Case for EpochProcessingFinalUpdates<E> {
         let spec = &E::default_spec();
 
         let mut result = (|| {
-            // Processing requires the epoch cache.
-            state.build_all_caches(spec)?;
+            // Processing requires the epoch cache, which may be shared with previous cases.
+            build_caches(&mut state, spec)?;
 
             process_final_updates(&mut state, spec).map(|_| state)
         })();
@@ -16,7 +16,7 @@ pub struct EpochProcessingJustificationAndFinalization<E: EthSpec> {
 
 impl<E: EthSpec> YamlDecode for EpochProcessingJustificationAndFinalization<E> {
     fn yaml_decode(yaml: &str) -> Result<Self, Error> {
-        Ok(serde_yaml::from_str(yaml).unwrap())
+        decode_yaml(yaml)
     }
 }
 
@@ -31,8 +31,8 @@ impl<E: EthSpec> Case for EpochProcessingJustificationAndFinalization<E> {
 
         let spec = &E::default_spec();
 
-        // Processing requires the epoch cache.
-        state.build_all_caches(spec).unwrap();
+        // Processing requires the epoch cache, which may be shared with previous cases.
+        build_caches(&mut state, spec).unwrap();
 
         let mut result = (|| {
             let mut validator_statuses = ValidatorStatuses::new(&state, spec)?;
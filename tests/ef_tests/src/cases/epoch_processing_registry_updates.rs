@@ -14,7 +14,7 @@ pub struct EpochProcessingRegistryUpdates<E: EthSpec> {
 
 impl<E: EthSpec> YamlDecode for EpochProcessingRegistryUpdates<E> {
     fn yaml_decode(yaml: &str) -> Result<Self, Error> {
-        Ok(serde_yaml::from_str(yaml).unwrap())
+        decode_yaml(yaml)
     }
 }
 
@@ -28,8 +28,8 @@ impl<E: EthSpec> Case for EpochProcessingRegistryUpdates<E> {
         let mut expected = self.post.clone();
         let spec = &E::default_spec();
 
-        // Processing requires the epoch cache.
-        state.build_all_caches(spec).unwrap();
+        // Processing requires the epoch cache, which may be shared with previous cases.
+        build_caches(&mut state, spec).unwrap();
 
         let mut result = process_registry_updates(&mut state, spec).map(|_| state);
 
@@ -16,7 +16,7 @@ pub struct EpochProcessingSlashings<E: EthSpec> {
 
 impl<E: EthSpec> YamlDecode for EpochProcessingSlashings<E> {
     fn yaml_decode(yaml: &str) -> Result<Self, Error> {
-        Ok(serde_yaml::from_str(yaml).unwrap())
+        decode_yaml(yaml)
     }
 }
 
@@ -32,8 +32,8 @@ impl<E: EthSpec> Case for EpochProcessingSlashings<E> {
         let spec = &E::default_spec();
 
         let mut result = (|| {
-            // Processing requires the epoch cache.
-            state.build_all_caches(spec)?;
+            // Processing requires the epoch cache, which may be shared with previous cases.
+            build_caches(&mut state, spec)?;
 
             let mut validator_statuses = ValidatorStatuses::new(&state, spec)?;
             validator_statuses.process_attestations(&state, spec)?;
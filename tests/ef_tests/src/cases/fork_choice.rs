@@ -0,0 +1,213 @@
+use super::*;
+use crate::ssz_snappy::{load_case_input, load_required_input};
+use crate::yaml_decode::decode_yaml;
+use lmd_ghost::{ForkChoiceStore, SpecStore, Step};
+use serde_derive::Deserialize;
+use std::fs;
+use std::path::Path;
+use types::{BeaconBlock, BeaconState, Checkpoint, EthSpec, Hash256, Slot};
+
+/// The file listing the steps of a case.
+pub const STEPS_FILE: &str = "steps.yaml";
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Head {
+    pub slot: Slot,
+    pub root: Hash256,
+}
+
+/// The expected state of the store after the preceding steps.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+pub struct Checks {
+    pub head: Option<Head>,
+    pub time: Option<u64>,
+    pub justified_checkpoint: Option<Checkpoint>,
+    pub finalized_checkpoint: Option<Checkpoint>,
+}
+
+/// A step as listed in `STEPS_FILE`, which names the file of each block, attestation or attester
+/// slashing.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum StepFile {
+    Tick {
+        tick: u64,
+    },
+    Block {
+        block: String,
+        #[serde(default = "valid")]
+        valid: bool,
+    },
+    Attestation {
+        attestation: String,
+        #[serde(default = "valid")]
+        valid: bool,
+    },
+    AttesterSlashing {
+        attester_slashing: String,
+        #[serde(default = "valid")]
+        valid: bool,
+    },
+    Checks {
+        checks: Checks,
+    },
+}
+
+/// Steps are valid unless stated otherwise.
+fn valid() -> bool {
+    true
+}
+
+#[derive(Debug, Clone)]
+pub enum ForkChoiceStep<E: EthSpec> {
+    /// Apply `step`, which should fail unless `valid`.
+    Apply {
+        step: Step<E>,
+        valid: bool,
+    },
+    Check(Checks),
+}
+
+/// Feeds ticks, blocks, attestations and attester slashings to a `SpecStore` created from the
+/// anchor state, checking the head, time and checkpoints of the store between steps.
+#[derive(Debug, Clone)]
+pub struct ForkChoice<E: EthSpec> {
+    pub description: String,
+    pub anchor_state: BeaconState<E>,
+    /// The block of the anchor state, whose root should be the initial head.
+    pub anchor_block: Option<BeaconBlock<E>>,
+    pub steps: Vec<ForkChoiceStep<E>>,
+}
+
+impl<E: EthSpec> LoadCase for ForkChoice<E> {
+    fn load_from_dir(path: &Path) -> Result<Self, Error> {
+        let anchor_state = load_required_input(path, "anchor_state")?;
+        let anchor_block = load_case_input(path, "anchor_block")?;
+
+        let steps_path = path.join(STEPS_FILE);
+        let steps_yaml = fs::read_to_string(&steps_path).map_err(|e| {
+            Error::FailedToParseTest(format!("unable to read {}: {}", steps_path.display(), e))
+        })?;
+        let step_files: Vec<StepFile> =
+            decode_yaml(&steps_yaml).map_err(|e| e.in_context(steps_path.display()))?;
+
+        let steps = step_files
+            .into_iter()
+            .map(|step_file| {
+                Ok(match step_file {
+                    StepFile::Tick { tick } => ForkChoiceStep::Apply {
+                        step: Step::Tick(tick),
+                        valid: true,
+                    },
+                    StepFile::Block { block, valid } => ForkChoiceStep::Apply {
+                        step: Step::Block(load_required_input(path, &block)?),
+                        valid,
+                    },
+                    StepFile::Attestation { attestation, valid } => ForkChoiceStep::Apply {
+                        step: Step::Attestation(load_required_input(path, &attestation)?),
+                        valid,
+                    },
+                    StepFile::AttesterSlashing {
+                        attester_slashing,
+                        valid,
+                    } => ForkChoiceStep::Apply {
+                        step: Step::AttesterSlashing(load_required_input(
+                            path,
+                            &attester_slashing,
+                        )?),
+                        valid,
+                    },
+                    StepFile::Checks { checks } => ForkChoiceStep::Check(checks),
+                })
+            })
+            .collect::<Result<_, Error>>()?;
+
+        Ok(Self {
+            description: path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            anchor_state,
+            anchor_block,
+            steps,
+        })
+    }
+}
+
+impl<E: EthSpec> Case for ForkChoice<E> {
+    fn description(&self) -> String {
+        self.description.clone()
+    }
+
+    fn result(&self, _case_index: usize) -> Result<(), Error> {
+        let mut store = SpecStore::from_genesis(self.anchor_state.clone(), E::default_spec());
+
+        if let Some(anchor_block) = &self.anchor_block {
+            check(
+                "anchor",
+                store.get_head(),
+                Ok(anchor_block.canonical_root()),
+            )?;
+        }
+
+        for (i, step) in self.steps.iter().enumerate() {
+            match step {
+                ForkChoiceStep::Apply { step, valid } => match (store.apply(step), valid) {
+                    (Ok(()), true) | (Err(_), false) => (),
+                    (Err(e), true) => {
+                        return Err(Error::NotEqual(format!("step {} failed: {:?}", i, e)))
+                    }
+                    (Ok(()), false) => {
+                        return Err(Error::DidntFail(format!("step {} should have failed", i)))
+                    }
+                },
+                ForkChoiceStep::Check(checks) => check_store(&store, checks)
+                    .map_err(|e| Error::NotEqual(format!("step {}: {}", i, e.message())))?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Compares the store with each of `checks`.
+fn check_store<E: EthSpec>(store: &SpecStore<E>, checks: &Checks) -> Result<(), Error> {
+    if let Some(expected) = &checks.head {
+        let head = store.get_head().and_then(|root| {
+            Ok(Head {
+                slot: store.block(root)?.slot,
+                root,
+            })
+        });
+        check("head", head, Ok(expected.clone()))?;
+    }
+    if let Some(expected) = checks.time {
+        check("time", store.time(), expected)?;
+    }
+    if let Some(expected) = &checks.justified_checkpoint {
+        check(
+            "justified checkpoint",
+            store.justified_checkpoint(),
+            expected,
+        )?;
+    }
+    if let Some(expected) = &checks.finalized_checkpoint {
+        check(
+            "finalized checkpoint",
+            store.finalized_checkpoint(),
+            expected,
+        )?;
+    }
+    Ok(())
+}
+
+fn check<T: PartialEq + Debug>(name: &str, got: T, expected: T) -> Result<(), Error> {
+    if got == expected {
+        Ok(())
+    } else {
+        Err(Error::NotEqual(format!(
+            "{}: got {:?}, expected {:?}",
+            name, got, expected
+        )))
+    }
+}
@@ -18,7 +18,7 @@ pub struct GenesisInitialization<E: EthSpec> {
 
 impl<E: EthSpec> YamlDecode for GenesisInitialization<E> {
     fn yaml_decode(yaml: &str) -> Result<Self, Error> {
-        Ok(serde_yaml::from_str(yaml).unwrap())
+        decode_yaml(yaml)
     }
 }
 
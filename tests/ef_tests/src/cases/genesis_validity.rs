@@ -15,7 +15,7 @@ pub struct GenesisValidity<E: EthSpec> {
 
 impl<E: EthSpec> YamlDecode for GenesisValidity<E> {
     fn yaml_decode(yaml: &str) -> Result<Self, Error> {
-        Ok(serde_yaml::from_str(yaml).unwrap())
+        decode_yaml(yaml)
     }
 }
 
@@ -17,7 +17,7 @@ pub struct OperationsAttestation<E: EthSpec> {
 
 impl<E: EthSpec> YamlDecode for OperationsAttestation<E> {
     fn yaml_decode(yaml: &str) -> Result<Self, Error> {
-        Ok(serde_yaml::from_str(&yaml).unwrap())
+        decode_yaml(yaml)
     }
 }
 
@@ -31,16 +31,13 @@ impl<E: EthSpec> Case for OperationsAttestation<E> {
 
         self.bls_setting.unwrap_or_default().check()?;
 
-        let mut state = self.pre.clone();
+        let state = self.pre.clone();
         let attestation = self.attestation.clone();
         let mut expected = self.post.clone();
 
-        // Processing requires the epoch cache.
-        state.build_all_caches(spec).unwrap();
-
-        let result = process_attestations(&mut state, &[attestation], spec);
-
-        let mut result = result.and_then(|_| Ok(state));
+        let mut result = process_operation(state, spec, |state| {
+            process_attestations(state, &[attestation], spec, true)
+        });
 
         compare_beacon_state_results_without_caches(&mut result, &mut expected)
     }
@@ -19,7 +19,7 @@ pub struct OperationsAttesterSlashing<E: EthSpec> {
 
 impl<E: EthSpec> YamlDecode for OperationsAttesterSlashing<E> {
     fn yaml_decode(yaml: &str) -> Result<Self, Error> {
-        Ok(serde_yaml::from_str(yaml).unwrap())
+        decode_yaml(yaml)
     }
 }
 
@@ -31,17 +31,15 @@ impl<E: EthSpec> Case for OperationsAttesterSlashing<E> {
     fn result(&self, _case_index: usize) -> Result<(), Error> {
         self.bls_setting.unwrap_or_default().check()?;
 
-        let mut state = self.pre.clone();
+        let state = self.pre.clone();
         let attester_slashing = self.attester_slashing.clone();
         let mut expected = self.post.clone();
 
-        // Processing requires the epoch cache.
-        state.build_all_caches(&E::default_spec()).unwrap();
+        let spec = &E::default_spec();
 
-        let result =
-            process_attester_slashings(&mut state, &[attester_slashing], &E::default_spec());
-
-        let mut result = result.and_then(|_| Ok(state));
+        let mut result = process_operation(state, spec, |state| {
+            process_attester_slashings(state, &[attester_slashing], spec, true)
+        });
 
         compare_beacon_state_results_without_caches(&mut result, &mut expected)
     }
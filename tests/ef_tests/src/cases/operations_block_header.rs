@@ -17,7 +17,7 @@ pub struct OperationsBlockHeader<E: EthSpec> {
 
 impl<E: EthSpec> YamlDecode for OperationsBlockHeader<E> {
     fn yaml_decode(yaml: &str) -> Result<Self, Error> {
-        Ok(serde_yaml::from_str(yaml).unwrap())
+        decode_yaml(yaml)
     }
 }
 
@@ -31,13 +31,12 @@ impl<E: EthSpec> Case for OperationsBlockHeader<E> {
 
         self.bls_setting.unwrap_or_default().check()?;
 
-        let mut state = self.pre.clone();
+        let state = self.pre.clone();
         let mut expected = self.post.clone();
 
-        // Processing requires the epoch cache.
-        state.build_all_caches(spec).unwrap();
-
-        let mut result = process_block_header(&mut state, &self.block, spec, true).map(|_| state);
+        let mut result = process_operation(state, spec, |state| {
+            process_block_header(state, &self.block, spec, true)
+        });
 
         compare_beacon_state_results_without_caches(&mut result, &mut expected)
     }
@@ -17,7 +17,7 @@ pub struct OperationsDeposit<E: EthSpec> {
 
 impl<E: EthSpec> YamlDecode for OperationsDeposit<E> {
     fn yaml_decode(yaml: &str) -> Result<Self, Error> {
-        Ok(serde_yaml::from_str(yaml).unwrap())
+        decode_yaml(yaml)
     }
 }
 
@@ -33,7 +33,7 @@ impl<E: EthSpec> Case for OperationsDeposit<E> {
         let deposit = self.deposit.clone();
         let mut expected = self.post.clone();
 
-        let result = process_deposits(&mut state, &[deposit], &E::default_spec());
+        let result = process_deposits(&mut state, &[deposit], &E::default_spec(), true);
 
         let mut result = result.and_then(|_| Ok(state));
 
@@ -17,7 +17,7 @@ pub struct OperationsExit<E: EthSpec> {
 
 impl<E: EthSpec> YamlDecode for OperationsExit<E> {
     fn yaml_decode(yaml: &str) -> Result<Self, Error> {
-        Ok(serde_yaml::from_str(yaml).unwrap())
+        decode_yaml(yaml)
     }
 }
 
@@ -29,16 +29,15 @@ impl<E: EthSpec> Case for OperationsExit<E> {
     fn result(&self, _case_index: usize) -> Result<(), Error> {
         self.bls_setting.unwrap_or_default().check()?;
 
-        let mut state = self.pre.clone();
+        let state = self.pre.clone();
         let exit = self.voluntary_exit.clone();
         let mut expected = self.post.clone();
 
-        // Exit processing requires the epoch cache.
-        state.build_all_caches(&E::default_spec()).unwrap();
+        let spec = &E::default_spec();
 
-        let result = process_exits(&mut state, &[exit], &E::default_spec());
-
-        let mut result = result.and_then(|_| Ok(state));
+        let mut result = process_operation(state, spec, |state| {
+            process_exits(state, &[exit], spec, true)
+        });
 
         compare_beacon_state_results_without_caches(&mut result, &mut expected)
     }
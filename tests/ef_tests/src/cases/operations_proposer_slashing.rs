@@ -17,7 +17,7 @@ pub struct OperationsProposerSlashing<E: EthSpec> {
 
 impl<E: EthSpec> YamlDecode for OperationsProposerSlashing<E> {
     fn yaml_decode(yaml: &str) -> Result<Self, Error> {
-        Ok(serde_yaml::from_str(yaml).unwrap())
+        decode_yaml(yaml)
     }
 }
 
@@ -29,17 +29,15 @@ impl<E: EthSpec> Case for OperationsProposerSlashing<E> {
     fn result(&self, _case_index: usize) -> Result<(), Error> {
         self.bls_setting.unwrap_or_default().check()?;
 
-        let mut state = self.pre.clone();
+        let state = self.pre.clone();
         let proposer_slashing = self.proposer_slashing.clone();
         let mut expected = self.post.clone();
 
-        // Processing requires the epoch cache.
-        state.build_all_caches(&E::default_spec()).unwrap();
+        let spec = &E::default_spec();
 
-        let result =
-            process_proposer_slashings(&mut state, &[proposer_slashing], &E::default_spec());
-
-        let mut result = result.and_then(|_| Ok(state));
+        let mut result = process_operation(state, spec, |state| {
+            process_proposer_slashings(state, &[proposer_slashing], spec, true)
+        });
 
         compare_beacon_state_results_without_caches(&mut result, &mut expected)
     }
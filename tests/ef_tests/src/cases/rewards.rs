@@ -0,0 +1,125 @@
+use super::*;
+use crate::ssz_snappy::load_required_input;
+use serde_derive::Deserialize;
+use ssz_derive::Decode;
+use state_processing::per_epoch_processing::{
+    apply_rewards::{get_attestation_component_deltas, AttestationDelta, Delta},
+    validator_statuses::ValidatorStatuses,
+};
+use std::path::Path;
+use types::{BeaconState, EthSpec, VariableList};
+
+/// The rewards and penalties of every validator from a single component of the attestation
+/// rewards and penalties.
+#[derive(Debug, Clone, PartialEq, Decode, Deserialize)]
+#[serde(bound = "E: EthSpec")]
+pub struct Deltas<E: EthSpec> {
+    pub rewards: VariableList<u64, E::ValidatorRegistryLimit>,
+    pub penalties: VariableList<u64, E::ValidatorRegistryLimit>,
+}
+
+/// Checks the deltas of each component of the attestation rewards and penalties of the pre state,
+/// each given by the file of its name in the case directory.
+#[derive(Debug, Clone)]
+pub struct Rewards<E: EthSpec> {
+    pub description: String,
+    pub pre: BeaconState<E>,
+    pub source_deltas: Deltas<E>,
+    pub target_deltas: Deltas<E>,
+    pub head_deltas: Deltas<E>,
+    pub inclusion_delay_deltas: Deltas<E>,
+    pub inactivity_penalty_deltas: Deltas<E>,
+}
+
+impl<E: EthSpec> LoadCase for Rewards<E> {
+    fn load_from_dir(path: &Path) -> Result<Self, Error> {
+        Ok(Self {
+            description: path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            pre: load_required_input(path, "pre")?,
+            source_deltas: load_required_input(path, "source_deltas")?,
+            target_deltas: load_required_input(path, "target_deltas")?,
+            head_deltas: load_required_input(path, "head_deltas")?,
+            inclusion_delay_deltas: load_required_input(path, "inclusion_delay_deltas")?,
+            inactivity_penalty_deltas: load_required_input(path, "inactivity_penalty_deltas")?,
+        })
+    }
+}
+
+impl<E: EthSpec> Case for Rewards<E> {
+    fn description(&self) -> String {
+        self.description.clone()
+    }
+
+    fn result(&self, _case_index: usize) -> Result<(), Error> {
+        let mut state = self.pre.clone();
+        let spec = &E::default_spec();
+
+        let deltas = (|| {
+            build_caches(&mut state, spec)?;
+
+            let mut validator_statuses = ValidatorStatuses::new(&state, spec)?;
+            validator_statuses.process_attestations(&state, spec)?;
+            get_attestation_component_deltas(&state, &validator_statuses, spec)
+        })()
+        .map_err(|e| Error::NotEqual(format!("unable to compute deltas: {:?}", e)))?;
+
+        let components = vec![
+            (
+                "source",
+                &self.source_deltas,
+                component(&deltas, |d| &d.source),
+            ),
+            (
+                "target",
+                &self.target_deltas,
+                component(&deltas, |d| &d.target),
+            ),
+            ("head", &self.head_deltas, component(&deltas, |d| &d.head)),
+            (
+                "inclusion delay",
+                &self.inclusion_delay_deltas,
+                component(&deltas, |d| &d.inclusion_delay),
+            ),
+            (
+                "inactivity penalty",
+                &self.inactivity_penalty_deltas,
+                component(&deltas, |d| &d.inactivity),
+            ),
+        ];
+
+        for (name, expected, got) in components {
+            if got != *expected {
+                return Err(Error::NotEqual(format!(
+                    "{} deltas: got {:?}, expected {:?}",
+                    name, got, expected
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the `component` of the delta of every validator.
+fn component<E: EthSpec>(
+    deltas: &[AttestationDelta],
+    component: impl Fn(&AttestationDelta) -> &Delta,
+) -> Deltas<E> {
+    Deltas {
+        rewards: VariableList::from(
+            deltas
+                .iter()
+                .map(|d| component(d).rewards())
+                .collect::<Vec<_>>(),
+        ),
+        penalties: VariableList::from(
+            deltas
+                .iter()
+                .map(|d| component(d).penalties())
+                .collect::<Vec<_>>(),
+        ),
+    }
+}
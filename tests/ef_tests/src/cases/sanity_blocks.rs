@@ -3,7 +3,8 @@ use crate::bls_setting::BlsSetting;
 use crate::case_result::compare_beacon_state_results_without_caches;
 use serde_derive::Deserialize;
 use state_processing::{
-    per_block_processing, per_slot_processing, BlockInvalid, BlockProcessingError,
+    per_block_processing_with_strategy, per_slot_processing, BlockInvalid, BlockProcessingError,
+    BlockSignatureStrategy,
 };
 use types::{BeaconBlock, BeaconState, EthSpec, RelativeEpoch};
 
@@ -19,7 +20,7 @@ pub struct SanityBlocks<E: EthSpec> {
 
 impl<E: EthSpec> YamlDecode for SanityBlocks<E> {
     fn yaml_decode(yaml: &str) -> Result<Self, Error> {
-        Ok(serde_yaml::from_str(yaml).unwrap())
+        decode_yaml(yaml)
     }
 }
 
@@ -50,7 +51,12 @@ impl<E: EthSpec> Case for SanityBlocks<E> {
                     .build_committee_cache(RelativeEpoch::Current, spec)
                     .unwrap();
 
-                per_block_processing(&mut state, block, spec)?;
+                per_block_processing_with_strategy(
+                    &mut state,
+                    block,
+                    BlockSignatureStrategy::VerifyBulk,
+                    spec,
+                )?;
 
                 if block.state_root == state.canonical_root() {
                     Ok(())
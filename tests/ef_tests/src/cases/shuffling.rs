@@ -15,7 +15,7 @@ pub struct Shuffling<T> {
 
 impl<T> YamlDecode for Shuffling<T> {
     fn yaml_decode(yaml: &str) -> Result<Self, Error> {
-        Ok(serde_yaml::from_str(yaml).unwrap())
+        decode_yaml(yaml)
     }
 }
 
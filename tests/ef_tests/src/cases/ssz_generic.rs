@@ -16,7 +16,7 @@ pub struct SszGeneric {
 
 impl YamlDecode for SszGeneric {
     fn yaml_decode(yaml: &str) -> Result<Self, Error> {
-        Ok(serde_yaml::from_str(yaml).unwrap())
+        decode_yaml(yaml)
     }
 }
 
@@ -2,10 +2,12 @@ use crate::case_result::CaseResult;
 use crate::cases::*;
 use crate::doc_header::DocHeader;
 use crate::error::Error;
-use crate::yaml_decode::{yaml_split_header_and_cases, YamlDecode};
+use crate::yaml_decode::{decode_yaml, yaml_split_header_and_cases, YamlDecode};
 use crate::EfTest;
+use rayon::prelude::*;
 use serde_derive::Deserialize;
-use std::{fs::File, io::prelude::*, path::PathBuf};
+use std::fs;
+use std::path::{Path, PathBuf};
 use types::{MainnetEthSpec, MinimalEthSpec};
 
 #[derive(Debug, Deserialize)]
@@ -15,24 +17,64 @@ pub struct Doc {
     pub path: PathBuf,
 }
 
-impl Doc {
-    fn from_path(path: PathBuf) -> Self {
-        let mut file = File::open(path.clone()).unwrap();
-
-        let mut yaml = String::new();
-        file.read_to_string(&mut yaml).unwrap();
+/// The number of cases which passed, failed or were skipped.
+#[derive(Debug, Default)]
+struct Summary {
+    passed: usize,
+    failed: usize,
+    skipped: usize,
+    /// The documents which contained a failing case.
+    failed_docs: Vec<PathBuf>,
+}
 
-        let (header_yaml, cases_yaml) = yaml_split_header_and_cases(yaml.clone());
+impl Summary {
+    fn of(path: PathBuf, results: &[CaseResult]) -> Self {
+        let (failed, skipped_bls, skipped_known_failures) = categorize_results(results);
+        let skipped = skipped_bls.len() + skipped_known_failures.len();
 
         Self {
+            passed: results.len() - failed.len() - skipped,
+            failed: failed.len(),
+            skipped,
+            failed_docs: if failed.is_empty() {
+                vec![]
+            } else {
+                vec![path]
+            },
+        }
+    }
+
+    fn add(mut self, other: Self) -> Self {
+        self.passed += other.passed;
+        self.failed += other.failed;
+        self.skipped += other.skipped;
+        self.failed_docs.extend(other.failed_docs);
+        self
+    }
+}
+
+impl Doc {
+    fn from_path(path: PathBuf) -> Result<Self, Error> {
+        let yaml = fs::read_to_string(&path)
+            .map_err(|e| Error::FailedToParseTest(format!("unable to read: {}", e)))?;
+
+        let (header_yaml, cases_yaml) = yaml_split_header_and_cases(yaml)?;
+
+        Ok(Self {
             header_yaml,
             cases_yaml,
             path,
-        }
+        })
     }
 
+    /// Returns the result of every case in the document.
+    ///
+    /// If the header cannot be decoded, the only result is the failure to decode it.
     pub fn test_results(&self) -> Vec<CaseResult> {
-        let header: DocHeader = serde_yaml::from_str(&self.header_yaml.as_str()).unwrap();
+        let header = match self.header() {
+            Ok(header) => header,
+            Err(e) => return vec![CaseResult::failed_to_decode(0, e)],
+        };
 
         match (
             header.runner.as_ref(),
@@ -149,35 +191,119 @@ impl Doc {
         }
     }
 
-    pub fn assert_tests_pass(path: PathBuf) {
-        let doc = Self::from_path(path);
-        let results = doc.test_results();
-
-        let (failed, skipped_bls, skipped_known_failures) = categorize_results(&results);
-
-        if failed.len() + skipped_known_failures.len() > 0 {
-            print_results(
-                &doc,
-                &failed,
-                &skipped_bls,
-                &skipped_known_failures,
-                &results,
-            );
-            if !failed.is_empty() {
-                panic!("Tests failed (see above)");
+    /// Decodes the header of the document.
+    fn header(&self) -> Result<DocHeader, Error> {
+        decode_yaml(&self.header_yaml).map_err(|e| e.in_context(self.path.display()))
+    }
+
+    /// Returns the line of the document on which the first case starts.
+    fn first_case_line(&self) -> usize {
+        // The header is followed by the `test_cases:` line.
+        self.header_yaml.lines().count() + 2
+    }
+
+    /// Runs every case in the document at `path`, printing the results if any case did not pass.
+    ///
+    /// A document which cannot be decoded fails as a single case.
+    fn run(path: PathBuf) -> Summary {
+        let (doc, results) = match Self::from_path(path.clone()) {
+            Ok(doc) => {
+                let results = doc.test_results();
+                (Some(doc), results)
             }
-        } else {
-            println!("Passed {} tests in {:?}", results.len(), doc.path);
-        }
+            Err(e) => (
+                None,
+                vec![CaseResult::failed_to_decode(
+                    0,
+                    e.in_context(path.display()),
+                )],
+            ),
+        };
+
+        let title = doc
+            .and_then(|doc| doc.header().ok())
+            .map(|header| header.title);
+
+        report(path, title.as_ref().map(String::as_str), &results)
+    }
+
+    pub fn assert_tests_pass(path: PathBuf) {
+        Self::assert_all_tests_pass(vec![path])
+    }
+
+    /// Runs every case in all of the documents at `paths`, panicking after all have run if any
+    /// case failed.
+    pub fn assert_all_tests_pass(paths: Vec<PathBuf>) {
+        let summary = paths
+            .into_par_iter()
+            .map(Self::run)
+            .reduce(Summary::default, Summary::add);
+
+        assert_summary_passes(summary);
+    }
+
+    /// Runs the case in each of `dirs`, panicking after all have run if any case failed.
+    ///
+    /// A case which cannot be loaded from its directory fails.
+    pub fn assert_case_dirs_pass<T: LoadCase + Case>(dirs: Vec<PathBuf>) {
+        let summary = dirs
+            .into_par_iter()
+            .map(|dir| {
+                let result = match T::load_from_dir(&dir) {
+                    Ok(case) => CaseResult::new(0, &case, case.result(0)),
+                    Err(e) => CaseResult::failed_to_decode(0, e.in_context(dir.display())),
+                };
+
+                report(dir, None, &[result])
+            })
+            .reduce(Summary::default, Summary::add);
+
+        assert_summary_passes(summary);
+    }
+}
+
+/// Prints `results` if any did not pass, returning their summary.
+fn report(path: PathBuf, title: Option<&str>, results: &[CaseResult]) -> Summary {
+    let (failed, skipped_bls, skipped_known_failures) = categorize_results(results);
+
+    if failed.len() + skipped_known_failures.len() > 0 {
+        print_results(
+            &path,
+            title,
+            &failed,
+            &skipped_bls,
+            &skipped_known_failures,
+            results,
+        );
+    } else {
+        println!("Passed {} tests in {:?}", results.len(), path);
+    }
+
+    Summary::of(path, results)
+}
+
+fn assert_summary_passes(summary: Summary) {
+    println!(
+        "{} passed, {} failed, {} skipped",
+        summary.passed, summary.failed, summary.skipped
+    );
+
+    if !summary.failed_docs.is_empty() {
+        panic!(
+            "Tests failed in {} files (see above): {:#?}",
+            summary.failed_docs.len(),
+            summary.failed_docs
+        );
     }
 }
 
 pub fn run_test<T>(doc: &Doc) -> Vec<CaseResult>
 where
-    Cases<T>: EfTest + YamlDecode,
+    Cases<T>: EfTest,
+    T: YamlDecode,
 {
     // Pass only the "test_cases" YAML string to `yaml_decode`.
-    let test_cases: Cases<T> = Cases::yaml_decode(&doc.cases_yaml).unwrap();
+    let test_cases: Cases<T> = Cases::decode(&doc.cases_yaml, doc.first_case_line());
 
     test_cases.test_results()
 }
@@ -202,13 +328,13 @@ pub fn categorize_results(
 }
 
 pub fn print_results(
-    doc: &Doc,
+    path: &Path,
+    title: Option<&str>,
     failed: &[&CaseResult],
     skipped_bls: &[&CaseResult],
     skipped_known_failures: &[&CaseResult],
     results: &[CaseResult],
 ) {
-    let header: DocHeader = serde_yaml::from_str(&doc.header_yaml).unwrap();
     println!("--------------------------------------------------");
     println!(
         "Test {}",
@@ -218,8 +344,8 @@ pub fn print_results(
             "Failure"
         }
     );
-    println!("Title: {}", header.title);
-    println!("File: {:?}", doc.path);
+    println!("Title: {}", title.unwrap_or("unknown"));
+    println!("File: {:?}", path);
     println!(
         "{} tests, {} failed, {} skipped (known failure), {} skipped (bls), {} passed. (See below for errors)",
         results.len(),
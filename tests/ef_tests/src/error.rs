@@ -32,6 +32,15 @@ impl Error {
         }
     }
 
+    /// Prefixes the message of a `FailedToParseTest` error with `context` (e.g., the path of the
+    /// test file).
+    pub fn in_context(self, context: impl std::fmt::Display) -> Self {
+        match self {
+            Error::FailedToParseTest(m) => Error::FailedToParseTest(format!("{}: {}", context, m)),
+            other => other,
+        }
+    }
+
     pub fn is_skipped(&self) -> bool {
         match self {
             Error::SkippedBls | Error::SkippedKnownFailure => true,
@@ -1,9 +1,10 @@
 use types::EthSpec;
 
 pub use case_result::CaseResult;
-pub use cases::Case;
+pub use cases::{Case, ForkChoice, LoadCase, Rewards};
 pub use doc::Doc;
 pub use error::Error;
+pub use ssz_snappy::{decode_ssz_snappy, load_case_input};
 pub use yaml_decode::YamlDecode;
 
 mod bls_setting;
@@ -12,6 +13,8 @@ mod cases;
 mod doc;
 mod doc_header;
 mod error;
+pub mod spec_tests;
+mod ssz_snappy;
 mod yaml_decode;
 
 /// Defined where an object can return the results of some test(s) adhering to the Ethereum
@@ -0,0 +1,207 @@
+//! The location and version of the spec test vectors.
+//!
+//! The vectors are the release archives of `ethereum/eth2.0-spec-tests` pinned in
+//! `spec-tests.lock`, unpacked into `eth2.0-spec-tests` by the `download_tests` binary (or checked
+//! out there as a git submodule). The lock holds the pinned version, then the SHA-256 of each of
+//! its archives in the format of `sha256sum`:
+//!
+//! ```text
+//! version v0.8.3
+//! <sha256>  general.tar.gz
+//! <sha256>  minimal.tar.gz
+//! <sha256>  mainnet.tar.gz
+//! ```
+//!
+//! An archive without a checksum is refused by the downloader unless it is asked to pin it (with
+//! `--pin`), which is the only way the lock is rewritten. The downloader records the version it
+//! unpacked in `eth2.0-spec-tests/VERSION`, so that vectors of another version are reported as
+//! stale rather than failing in obscure ways.
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The directory of the test vectors, within this crate.
+pub const SPEC_TESTS_DIR: &str = "eth2.0-spec-tests";
+
+/// The file pinning the version and checksums of the test vectors, within this crate.
+pub const LOCK_FILENAME: &str = "spec-tests.lock";
+
+/// The file recording the version of the unpacked test vectors, within `SPEC_TESTS_DIR`.
+pub const VERSION_FILENAME: &str = "VERSION";
+
+/// The archives of each release.
+pub const ARCHIVES: &[&str] = &["general.tar.gz", "minimal.tar.gz", "mainnet.tar.gz"];
+
+/// The command which downloads the pinned test vectors.
+pub const DOWNLOAD_COMMAND: &str = "cargo run -p ef_tests --bin download_tests";
+
+/// The comment at the top of a lock.
+const LOCK_HEADER: &str = "\
+# The release of ethereum/eth2.0-spec-tests run by the tests, and the SHA-256 of its archives.
+# Archives without a checksum are refused until pinned with `cargo run -p ef_tests --bin download_tests -- --pin`.
+";
+
+/// The pinned version of the test vectors and the checksums of its archives.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Lock {
+    pub version: String,
+    /// The archives of `ARCHIVES`, with their SHA-256 (as hex) if pinned.
+    pub archives: Vec<(String, Option<String>)>,
+}
+
+impl Lock {
+    /// Returns a lock of `version`, with no checksums pinned.
+    pub fn new(version: String) -> Self {
+        Self {
+            version,
+            archives: ARCHIVES
+                .iter()
+                .map(|archive| (archive.to_string(), None))
+                .collect(),
+        }
+    }
+
+    /// Reads the lock at `path`.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| format!("unable to read {:?}: {}", path, e))?;
+        Self::parse(&contents).map_err(|e| format!("{:?} is invalid: {}", path, e))
+    }
+
+    /// Parses the contents of a lock.
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let mut lines = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+
+        let version = match lines
+            .next()
+            .map(|line| line.split_whitespace().collect::<Vec<_>>())
+        {
+            Some(ref words) if words.len() == 2 && words[0] == "version" => words[1].to_string(),
+            _ => return Err("the first line must be `version <release tag>`".to_string()),
+        };
+
+        let mut lock = Self::new(version);
+        for line in lines {
+            let words: Vec<_> = line.split_whitespace().collect();
+            let (checksum, archive) = match words.as_slice() {
+                [checksum, archive] => (checksum, archive),
+                _ => return Err(format!("expected `<sha256>  <archive>`, found {:?}", line)),
+            };
+            if checksum.len() != 64 || !checksum.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(format!("{:?} is not a SHA-256", checksum));
+            }
+            match lock
+                .archives
+                .iter_mut()
+                .find(|(name, _)| *name == **archive)
+            {
+                Some((_, pinned)) => *pinned = Some(checksum.to_lowercase()),
+                None => return Err(format!("{:?} is not an archive of a release", archive)),
+            }
+        }
+        Ok(lock)
+    }
+
+    /// Writes the lock to `path`.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    /// Returns the URL of `archive` of the pinned version.
+    pub fn url(&self, archive: &str) -> String {
+        format!(
+            "https://github.com/ethereum/eth2.0-spec-tests/releases/download/{}/{}",
+            self.version, archive
+        )
+    }
+}
+
+impl fmt::Display for Lock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", LOCK_HEADER)?;
+        writeln!(f, "version {}", self.version)?;
+        for (archive, checksum) in &self.archives {
+            if let Some(checksum) = checksum {
+                writeln!(f, "{}  {}", checksum, archive)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the path of this crate.
+pub fn crate_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+}
+
+/// Returns the `tests` directory of the test vectors, or an error which says how to obtain them
+/// if they are missing or of another version than that pinned.
+pub fn tests_dir() -> Result<PathBuf, String> {
+    let spec_tests_dir = crate_dir().join(SPEC_TESTS_DIR);
+    let tests_dir = spec_tests_dir.join("tests");
+    let lock = Lock::load(&crate_dir().join(LOCK_FILENAME))?;
+
+    if !tests_dir.is_dir() {
+        return Err(format!(
+            "Unable to locate the spec tests in {:?}. Run `{}` to download {} (or init git submodules).",
+            tests_dir, DOWNLOAD_COMMAND, lock.version
+        ));
+    }
+
+    // Vectors checked out as a submodule have no version file, and are at the submodule's commit.
+    match fs::read_to_string(spec_tests_dir.join(VERSION_FILENAME)) {
+        Ok(ref version) if version.trim() != lock.version => Err(format!(
+            "The spec tests in {:?} are {}, but {} is pinned in {}. Run `{}` to update them.",
+            spec_tests_dir,
+            version.trim(),
+            lock.version,
+            LOCK_FILENAME,
+            DOWNLOAD_COMMAND
+        )),
+        _ => Ok(tests_dir),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locks_round_trip() {
+        let checksum = "ab".repeat(32);
+        let contents = format!("version v0.8.3\n{}  minimal.tar.gz\n", checksum);
+        let lock = Lock::parse(&contents).unwrap();
+        assert_eq!(lock.version, "v0.8.3");
+        assert_eq!(
+            lock.archives,
+            vec![
+                ("general.tar.gz".to_string(), None),
+                ("minimal.tar.gz".to_string(), Some(checksum)),
+                ("mainnet.tar.gz".to_string(), None),
+            ]
+        );
+        assert_eq!(Lock::parse(&lock.to_string()), Ok(lock.clone()));
+        assert_eq!(
+            lock.url("general.tar.gz"),
+            "https://github.com/ethereum/eth2.0-spec-tests/releases/download/v0.8.3/general.tar.gz"
+        );
+    }
+
+    #[test]
+    fn invalid_locks_are_refused() {
+        assert!(Lock::parse("").is_err());
+        assert!(Lock::parse("v0.8.3\n").is_err());
+        assert!(Lock::parse("version v0.8.3\nabcd  general.tar.gz\n").is_err());
+        let checksum = "ab".repeat(32);
+        assert!(Lock::parse(&format!("version v0.8.3\n{}  other.tar.gz\n", checksum)).is_err());
+    }
+
+    #[test]
+    fn the_lock_of_this_crate_is_valid() {
+        Lock::load(&crate_dir().join(LOCK_FILENAME)).unwrap();
+    }
+}
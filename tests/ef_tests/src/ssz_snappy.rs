@@ -0,0 +1,126 @@
+//! Loads test inputs (e.g., pre and post states) which newer test vectors distribute as
+//! snappy-compressed SSZ files, rather than as fields of a YAML document.
+//!
+//! Each input is a file named after it (e.g., `pre.ssz_snappy`) in the directory of its case.
+//! Legacy vectors may instead provide the same input as YAML (e.g., `pre.yaml`).
+use crate::error::Error;
+use crate::yaml_decode::decode_yaml;
+use serde::de::DeserializeOwned;
+use ssz::Decode;
+use std::fs;
+use std::path::Path;
+
+/// The extension of snappy-compressed SSZ files.
+pub const SSZ_SNAPPY_EXTENSION: &str = "ssz_snappy";
+
+/// The extension of YAML files.
+pub const YAML_EXTENSION: &str = "yaml";
+
+/// Decompresses and decodes a snappy-compressed SSZ `T`.
+pub fn decode_ssz_snappy<T: Decode>(bytes: &[u8]) -> Result<T, Error> {
+    let ssz = snap::Decoder::new()
+        .decompress_vec(bytes)
+        .map_err(|e| Error::FailedToParseTest(format!("invalid snappy: {}", e)))?;
+
+    T::from_ssz_bytes(&ssz).map_err(|e| Error::FailedToParseTest(format!("invalid SSZ: {:?}", e)))
+}
+
+/// Loads the input `name` of the case in `dir`, preferring `name.ssz_snappy` to `name.yaml`.
+///
+/// Returns `None` if the case has no such input (e.g., the post state of a case which is expected
+/// to fail).
+pub fn load_case_input<T>(dir: &Path, name: &str) -> Result<Option<T>, Error>
+where
+    T: Decode + DeserializeOwned,
+{
+    let ssz_snappy_path = dir.join(name).with_extension(SSZ_SNAPPY_EXTENSION);
+    let yaml_path = dir.join(name).with_extension(YAML_EXTENSION);
+
+    let input = if ssz_snappy_path.exists() {
+        decode_ssz_snappy(&read(&ssz_snappy_path)?)
+    } else if yaml_path.exists() {
+        let yaml = String::from_utf8(read(&yaml_path)?)
+            .map_err(|e| Error::FailedToParseTest(format!("invalid UTF-8: {}", e)));
+        yaml.and_then(|yaml| decode_yaml(&yaml))
+    } else {
+        return Ok(None);
+    };
+
+    input
+        .map(Some)
+        .map_err(|e| e.in_context(dir.join(name).display()))
+}
+
+/// Loads the input `name` of the case in `dir`, as `load_case_input`, failing if there is none.
+pub fn load_required_input<T>(dir: &Path, name: &str) -> Result<T, Error>
+where
+    T: Decode + DeserializeOwned,
+{
+    load_case_input(dir, name)?
+        .ok_or_else(|| Error::FailedToParseTest(format!("{}: missing `{}`", dir.display(), name)))
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, Error> {
+    fs::read(path)
+        .map_err(|e| Error::FailedToParseTest(format!("unable to read {}: {}", path.display(), e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ssz::Encode;
+    use std::path::PathBuf;
+    use types::{Epoch, Fork};
+
+    fn temp_case_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "lighthouse_ef_tests_{}_{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("should create case dir");
+        dir
+    }
+
+    fn fork() -> Fork {
+        Fork {
+            previous_version: [1, 2, 3, 4],
+            current_version: [5, 6, 7, 8],
+            epoch: Epoch::new(9),
+        }
+    }
+
+    #[test]
+    fn loads_ssz_snappy_in_preference_to_yaml() {
+        let dir = temp_case_dir("ssz_snappy");
+        let compressed = snap::Encoder::new()
+            .compress_vec(&fork().as_ssz_bytes())
+            .expect("should compress");
+        fs::write(dir.join("pre.ssz_snappy"), compressed).expect("should write");
+        fs::write(dir.join("pre.yaml"), "invalid: true\n").expect("should write");
+
+        assert_eq!(load_case_input::<Fork>(&dir, "pre"), Ok(Some(fork())));
+        assert_eq!(load_case_input::<Fork>(&dir, "post"), Ok(None));
+
+        fs::write(dir.join("pre.ssz_snappy"), fork().as_ssz_bytes()).expect("should write");
+        match load_case_input::<Fork>(&dir, "pre") {
+            Err(Error::FailedToParseTest(message)) => {
+                assert!(message.contains("invalid snappy"), message)
+            }
+            other => panic!("should fail to decompress, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn falls_back_to_yaml() {
+        let dir = temp_case_dir("yaml");
+        fs::write(
+            dir.join("pre.yaml"),
+            "previous_version: '0x01020304'\ncurrent_version: '0x05060708'\nepoch: 9\n",
+        )
+        .expect("should write");
+
+        assert_eq!(load_case_input::<Fork>(&dir, "pre"), Ok(Some(fork())));
+    }
+}
@@ -2,8 +2,10 @@ use super::*;
 use ethereum_types::{U128, U256};
 use types::Fork;
 
+mod loader;
 mod utils;
 
+pub use loader::*;
 pub use utils::*;
 
 pub trait YamlDecode: Sized {
@@ -49,8 +51,7 @@ macro_rules! impl_via_serde_yaml {
     ($ty: ty) => {
         impl YamlDecode for $ty {
             fn yaml_decode(string: &str) -> Result<Self, Error> {
-                serde_yaml::from_str(string)
-                    .map_err(|e| Error::FailedToParseTest(format!("{:?}", e)))
+                decode_yaml(string)
             }
         }
     };
@@ -0,0 +1,119 @@
+use crate::error::Error;
+use lazy_static::lazy_static;
+use serde::de::DeserializeOwned;
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+lazy_static! {
+    /// The paths of unknown fields which have already been warned about.
+    static ref WARNED_FIELDS: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+}
+
+/// Decodes a `T` from the test specification YAML.
+///
+/// Fields which `T` does not know (e.g., those added by a later version of the specification)
+/// are skipped, with a warning the first time each is seen. If decoding fails, the error reports
+/// the failing field along with its line and column in `yaml`.
+pub fn decode_yaml<T: DeserializeOwned>(yaml: &str) -> Result<T, Error> {
+    let value: serde_yaml::Value =
+        serde_yaml::from_str(yaml).map_err(|e| describe_error(yaml, &e))?;
+
+    let mut unknown_fields = vec![];
+    let decoded = serde_ignored::deserialize(value, |path| unknown_fields.push(path.to_string()));
+
+    match decoded {
+        Ok(decoded) => {
+            warn_unknown_fields(unknown_fields);
+            Ok(decoded)
+        }
+        // Decoding from a `Value` loses the position of the error, so decode again from the
+        // source to find it.
+        Err(value_error) => Err(match serde_yaml::from_str::<T>(yaml) {
+            Err(e) => describe_error(yaml, &e),
+            Ok(_) => Error::FailedToParseTest(value_error.to_string()),
+        }),
+    }
+}
+
+/// Describes `error`, naming the field on the line at which it occurred (if any).
+fn describe_error(yaml: &str, error: &serde_yaml::Error) -> Error {
+    let location = match error.location() {
+        Some(location) => location,
+        None => return Error::FailedToParseTest(error.to_string()),
+    };
+
+    let field = yaml
+        .lines()
+        .nth(location.line().saturating_sub(1))
+        .and_then(|line| {
+            let key = line.trim_start().trim_start_matches("- ");
+            let end = key.find(':')?;
+            Some(key[..end].trim().to_string())
+        });
+
+    Error::FailedToParseTest(match field {
+        Some(field) => format!(
+            "field `{}` (line {}, column {}): {}",
+            field,
+            location.line(),
+            location.column(),
+            error
+        ),
+        None => format!(
+            "line {}, column {}: {}",
+            location.line(),
+            location.column(),
+            error
+        ),
+    })
+}
+
+/// Prints a warning for each of `fields` not yet warned about.
+fn warn_unknown_fields(fields: Vec<String>) {
+    if fields.is_empty() {
+        return;
+    }
+
+    if let Ok(mut warned) = WARNED_FIELDS.lock() {
+        for field in fields {
+            if warned.insert(field.clone()) {
+                eprintln!("Warning: skipping unknown field `{}` in test YAML", field);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_derive::Deserialize;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Case {
+        slot: u64,
+        root: String,
+    }
+
+    #[test]
+    fn skips_unknown_fields() {
+        let yaml = "slot: 1\nroot: '0x00'\nfuture_field: 2\n";
+        assert_eq!(
+            decode_yaml::<Case>(yaml),
+            Ok(Case {
+                slot: 1,
+                root: "0x00".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn reports_the_failing_field() {
+        let yaml = "root: '0x00'\nslot: not_a_number\n";
+        match decode_yaml::<Case>(yaml) {
+            Err(Error::FailedToParseTest(message)) => {
+                assert!(message.starts_with("field `slot` (line 2"), message)
+            }
+            other => panic!("should fail to decode, got {:?}", other),
+        }
+    }
+}
@@ -1,10 +1,13 @@
-pub fn yaml_split_header_and_cases(mut yaml: String) -> (String, String) {
-    let test_cases_start = yaml.find("\ntest_cases:\n").unwrap();
+use crate::error::Error;
+
+/// Splits a test document into its header and the list of cases following `test_cases:`.
+pub fn yaml_split_header_and_cases(mut yaml: String) -> Result<(String, String), Error> {
+    let test_cases_start = yaml
+        .find("\ntest_cases:\n")
+        .ok_or_else(|| Error::FailedToParseTest("no `test_cases` list".to_string()))?;
     // + 1 to skip the \n we used for matching.
     let mut test_cases = yaml.split_off(test_cases_start + 1);
+    let test_cases = test_cases.split_off("test_cases:\n".len());
 
-    let end_of_first_line = test_cases.find('\n').unwrap();
-    let test_cases = test_cases.split_off(end_of_first_line + 1);
-
-    (yaml, test_cases)
+    Ok((yaml, test_cases))
 }
@@ -1,19 +1,23 @@
 use ef_tests::*;
-use rayon::prelude::*;
 use std::path::{Path, PathBuf};
+use types::{MainnetEthSpec, MinimalEthSpec};
 use walkdir::WalkDir;
 
+/// Returns the `tests` directory of the spec tests, panicking with how to obtain them if they are
+/// missing or stale.
+fn spec_tests_dir() -> PathBuf {
+    spec_tests::tests_dir().unwrap_or_else(|e| panic!("{}", e))
+}
+
 fn yaml_files_in_test_dir(dir: &Path) -> Vec<PathBuf> {
-    let base_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
-        .join("eth2.0-spec-tests")
-        .join("tests")
-        .join(dir);
+    let base_path = spec_tests_dir().join(dir);
 
     assert!(
         base_path.exists(),
         format!(
-            "Unable to locate {:?}. Did you init git submodules?",
-            base_path
+            "Unable to locate {:?}. Run `{}` to download the pinned spec tests.",
+            base_path,
+            spec_tests::DOWNLOAD_COMMAND
         )
     );
 
@@ -40,186 +44,214 @@ fn yaml_files_in_test_dir(dir: &Path) -> Vec<PathBuf> {
     paths
 }
 
+/// Returns the directories of the cases under `dir` whose path contains `config` (e.g.,
+/// "minimal"), which are those containing `file`.
+///
+/// Unlike `yaml_files_in_test_dir`, a missing `dir` is not an error, since older test vectors do
+/// not include every runner.
+fn case_dirs_in_test_dir(dir: &Path, config: &str, file: &str) -> Vec<PathBuf> {
+    let base_path = spec_tests_dir().join(dir);
+
+    if !base_path.exists() {
+        println!("Skipping {:?}, which does not exist", base_path);
+        return vec![];
+    }
+
+    case_dirs(&base_path, config, file)
+}
+
+/// Returns the directories of the cases under `dir` of the vectors kept in this repository (in
+/// `local-tests`), as `case_dirs_in_test_dir`, panicking if there are none.
+fn local_case_dirs(dir: &Path, config: &str, file: &str) -> Vec<PathBuf> {
+    let base_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("local-tests")
+        .join(dir);
+
+    let dirs = case_dirs(&base_path, config, file);
+    assert!(!dirs.is_empty(), "no {} cases in {:?}", config, base_path);
+    dirs
+}
+
+fn case_dirs(base_path: &Path, config: &str, file: &str) -> Vec<PathBuf> {
+    WalkDir::new(base_path)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|entry| entry.file_type().is_file() && entry.file_name() == file)
+        .filter_map(|entry| entry.path().parent().map(Path::to_path_buf))
+        .filter(|case_dir| case_dir.iter().any(|component| component == config))
+        .collect()
+}
+
 #[test]
 #[cfg(feature = "fake_crypto")]
 fn ssz_generic() {
-    yaml_files_in_test_dir(&Path::new("ssz_generic"))
-        .into_par_iter()
-        .for_each(|file| {
-            Doc::assert_tests_pass(file);
-        });
+    Doc::assert_all_tests_pass(yaml_files_in_test_dir(&Path::new("ssz_generic")));
 }
 
 #[test]
 #[cfg(feature = "fake_crypto")]
 fn ssz_static() {
-    yaml_files_in_test_dir(&Path::new("ssz_static"))
-        .into_par_iter()
-        .for_each(|file| {
-            Doc::assert_tests_pass(file);
-        });
+    Doc::assert_all_tests_pass(yaml_files_in_test_dir(&Path::new("ssz_static")));
 }
 
 #[test]
 fn shuffling() {
-    yaml_files_in_test_dir(&Path::new("shuffling").join("core"))
-        .into_par_iter()
-        .for_each(|file| {
-            Doc::assert_tests_pass(file);
-        });
+    Doc::assert_all_tests_pass(yaml_files_in_test_dir(&Path::new("shuffling").join("core")));
 }
 
 #[test]
 fn operations_deposit() {
-    yaml_files_in_test_dir(&Path::new("operations").join("deposit"))
-        .into_par_iter()
-        .for_each(|file| {
-            Doc::assert_tests_pass(file);
-        });
+    Doc::assert_all_tests_pass(yaml_files_in_test_dir(
+        &Path::new("operations").join("deposit"),
+    ));
 }
 
 #[test]
 fn operations_transfer() {
-    yaml_files_in_test_dir(&Path::new("operations").join("transfer"))
-        .into_par_iter()
-        .rev()
-        .for_each(|file| {
-            Doc::assert_tests_pass(file);
-        });
+    Doc::assert_all_tests_pass(yaml_files_in_test_dir(
+        &Path::new("operations").join("transfer"),
+    ));
 }
 
 #[test]
 fn operations_exit() {
-    yaml_files_in_test_dir(&Path::new("operations").join("voluntary_exit"))
-        .into_par_iter()
-        .for_each(|file| {
-            Doc::assert_tests_pass(file);
-        });
+    Doc::assert_all_tests_pass(yaml_files_in_test_dir(
+        &Path::new("operations").join("voluntary_exit"),
+    ));
 }
 
 #[test]
 fn operations_proposer_slashing() {
-    yaml_files_in_test_dir(&Path::new("operations").join("proposer_slashing"))
-        .into_par_iter()
-        .for_each(|file| {
-            Doc::assert_tests_pass(file);
-        });
+    Doc::assert_all_tests_pass(yaml_files_in_test_dir(
+        &Path::new("operations").join("proposer_slashing"),
+    ));
 }
 
 #[test]
 fn operations_attester_slashing() {
-    yaml_files_in_test_dir(&Path::new("operations").join("attester_slashing"))
-        .into_par_iter()
-        .for_each(|file| {
-            Doc::assert_tests_pass(file);
-        });
+    Doc::assert_all_tests_pass(yaml_files_in_test_dir(
+        &Path::new("operations").join("attester_slashing"),
+    ));
 }
 
 #[test]
 fn operations_attestation() {
-    yaml_files_in_test_dir(&Path::new("operations").join("attestation"))
-        .into_par_iter()
-        .for_each(|file| {
-            Doc::assert_tests_pass(file);
-        });
+    Doc::assert_all_tests_pass(yaml_files_in_test_dir(
+        &Path::new("operations").join("attestation"),
+    ));
 }
 
 #[test]
 fn operations_block_header() {
-    yaml_files_in_test_dir(&Path::new("operations").join("block_header"))
-        .into_par_iter()
-        .for_each(|file| {
-            Doc::assert_tests_pass(file);
-        });
+    Doc::assert_all_tests_pass(yaml_files_in_test_dir(
+        &Path::new("operations").join("block_header"),
+    ));
 }
 
 #[test]
 fn sanity_blocks() {
-    yaml_files_in_test_dir(&Path::new("sanity").join("blocks"))
-        .into_par_iter()
-        .for_each(|file| {
-            Doc::assert_tests_pass(file);
-        });
+    Doc::assert_all_tests_pass(yaml_files_in_test_dir(&Path::new("sanity").join("blocks")));
 }
 
 #[test]
 fn sanity_slots() {
-    yaml_files_in_test_dir(&Path::new("sanity").join("slots"))
-        .into_par_iter()
-        .for_each(|file| {
-            Doc::assert_tests_pass(file);
-        });
+    Doc::assert_all_tests_pass(yaml_files_in_test_dir(&Path::new("sanity").join("slots")));
 }
 
 #[test]
 #[cfg(not(feature = "fake_crypto"))]
 fn bls() {
-    yaml_files_in_test_dir(&Path::new("bls"))
-        .into_par_iter()
-        .for_each(|file| {
-            Doc::assert_tests_pass(file);
-        });
+    Doc::assert_all_tests_pass(yaml_files_in_test_dir(&Path::new("bls")));
 }
 
 #[test]
 fn epoch_processing_justification_and_finalization() {
-    yaml_files_in_test_dir(&Path::new("epoch_processing").join("justification_and_finalization"))
-        .into_par_iter()
-        .for_each(|file| {
-            Doc::assert_tests_pass(file);
-        });
+    Doc::assert_all_tests_pass(yaml_files_in_test_dir(
+        &Path::new("epoch_processing").join("justification_and_finalization"),
+    ));
 }
 
 #[test]
 fn epoch_processing_crosslinks() {
-    yaml_files_in_test_dir(&Path::new("epoch_processing").join("crosslinks"))
-        .into_par_iter()
-        .for_each(|file| {
-            Doc::assert_tests_pass(file);
-        });
+    Doc::assert_all_tests_pass(yaml_files_in_test_dir(
+        &Path::new("epoch_processing").join("crosslinks"),
+    ));
 }
 
 #[test]
 fn epoch_processing_registry_updates() {
-    yaml_files_in_test_dir(&Path::new("epoch_processing").join("registry_updates"))
-        .into_par_iter()
-        .for_each(|file| {
-            Doc::assert_tests_pass(file);
-        });
+    Doc::assert_all_tests_pass(yaml_files_in_test_dir(
+        &Path::new("epoch_processing").join("registry_updates"),
+    ));
 }
 
 #[test]
 fn epoch_processing_slashings() {
-    yaml_files_in_test_dir(&Path::new("epoch_processing").join("slashings"))
-        .into_par_iter()
-        .for_each(|file| {
-            Doc::assert_tests_pass(file);
-        });
+    Doc::assert_all_tests_pass(yaml_files_in_test_dir(
+        &Path::new("epoch_processing").join("slashings"),
+    ));
 }
 
 #[test]
 fn epoch_processing_final_updates() {
-    yaml_files_in_test_dir(&Path::new("epoch_processing").join("final_updates"))
-        .into_par_iter()
-        .for_each(|file| {
-            Doc::assert_tests_pass(file);
-        });
+    Doc::assert_all_tests_pass(yaml_files_in_test_dir(
+        &Path::new("epoch_processing").join("final_updates"),
+    ));
 }
 
 #[test]
 fn genesis_initialization() {
-    yaml_files_in_test_dir(&Path::new("genesis").join("initialization"))
-        .into_par_iter()
-        .for_each(|file| {
-            Doc::assert_tests_pass(file);
-        });
+    Doc::assert_all_tests_pass(yaml_files_in_test_dir(
+        &Path::new("genesis").join("initialization"),
+    ));
 }
 
 #[test]
 fn genesis_validity() {
-    yaml_files_in_test_dir(&Path::new("genesis").join("validity"))
-        .into_par_iter()
-        .for_each(|file| {
-            Doc::assert_tests_pass(file);
-        });
+    Doc::assert_all_tests_pass(yaml_files_in_test_dir(
+        &Path::new("genesis").join("validity"),
+    ));
+}
+
+#[test]
+fn fork_choice() {
+    let dir = Path::new("fork_choice");
+
+    Doc::assert_case_dirs_pass::<ForkChoice<MinimalEthSpec>>(case_dirs_in_test_dir(
+        dir,
+        "minimal",
+        "steps.yaml",
+    ));
+    Doc::assert_case_dirs_pass::<ForkChoice<MainnetEthSpec>>(case_dirs_in_test_dir(
+        dir,
+        "mainnet",
+        "steps.yaml",
+    ));
+}
+
+/// The fork choice cases kept in this repository, which run whether or not the spec tests have
+/// been downloaded.
+#[test]
+fn fork_choice_local() {
+    Doc::assert_case_dirs_pass::<ForkChoice<MinimalEthSpec>>(local_case_dirs(
+        Path::new("fork_choice"),
+        "minimal",
+        "steps.yaml",
+    ));
+}
+
+#[test]
+fn rewards() {
+    let dir = Path::new("rewards");
+
+    Doc::assert_case_dirs_pass::<Rewards<MinimalEthSpec>>(case_dirs_in_test_dir(
+        dir,
+        "minimal",
+        "pre.ssz_snappy",
+    ));
+    Doc::assert_case_dirs_pass::<Rewards<MainnetEthSpec>>(case_dirs_in_test_dir(
+        dir,
+        "mainnet",
+        "pre.ssz_snappy",
+    ));
 }
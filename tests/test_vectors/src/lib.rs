@@ -0,0 +1,325 @@
+//! Generates reproducible fixtures (states, blocks and attestations) with the `types::test_utils`
+//! builders, for use by downstream tooling and benchmarks independently of the EF test vector
+//! releases.
+//!
+//! A fixture depends only on its `Scenario`, its `Config` and the `EthSpec`: validators use the
+//! deterministic interop keypairs and the genesis time is the minimum genesis time of the spec, so
+//! the same inputs always produce identical files.
+//!
+//! Each fixture is written to a directory containing:
+//!
+//! - `meta.yaml`: the scenario, config and the roots of the generated objects.
+//! - `pre_state.{ssz,yaml}`: the state.
+//! - `block.{ssz,yaml}`: a signed block which is valid on `pre_state` (`Block` only).
+//! - `attestations.{ssz,yaml}`: the attestations included in `block` (`Block` only).
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+use ssz::{Decode, Encode};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use tree_hash::SignedRoot;
+use types::test_utils::{TestingBeaconBlockBuilder, TestingBeaconStateBuilder};
+use types::*;
+
+/// The situation represented by a fixture.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scenario {
+    /// A state at the genesis slot.
+    Genesis,
+    /// A state at the last slot of `Config::epoch`, with full participation in the previous and
+    /// current epochs, such that the next slot triggers an epoch transition.
+    EpochTransition,
+    /// A state at the first slot of `Config::epoch` and a signed block for that slot, with
+    /// `Config::num_attestations` attestations.
+    Block,
+}
+
+impl Scenario {
+    pub fn all() -> Vec<Scenario> {
+        vec![
+            Scenario::Genesis,
+            Scenario::EpochTransition,
+            Scenario::Block,
+        ]
+    }
+
+    /// The name of the scenario, as used on the command line and in directory names.
+    pub fn name(self) -> &'static str {
+        match self {
+            Scenario::Genesis => "genesis",
+            Scenario::EpochTransition => "epoch_transition",
+            Scenario::Block => "block",
+        }
+    }
+}
+
+impl FromStr for Scenario {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        Scenario::all()
+            .into_iter()
+            .find(|scenario| scenario.name() == s)
+            .ok_or_else(|| format!("Unknown scenario: {}", s))
+    }
+}
+
+/// The encoding of the objects of a fixture.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Format {
+    Ssz,
+    Yaml,
+}
+
+/// The size of a fixture.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Config {
+    pub validator_count: usize,
+    /// The epoch of the state. Ignored for `Scenario::Genesis`.
+    ///
+    /// Must be at least 3, as the builders mark the three prior epochs as justified and finalized.
+    pub epoch: Epoch,
+    /// The number of attestations in the block. Only used for `Scenario::Block`.
+    pub num_attestations: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            validator_count: 256,
+            epoch: Epoch::new(4),
+            num_attestations: 16,
+        }
+    }
+}
+
+/// Describes the contents of a fixture directory.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Meta {
+    pub scenario: Scenario,
+    pub config: Config,
+    pub slots_per_epoch: u64,
+    pub pre_state_root: Hash256,
+    pub block_root: Option<Hash256>,
+}
+
+/// The objects of a fixture.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Fixture<T: EthSpec> {
+    pub scenario: Scenario,
+    pub config: Config,
+    pub pre_state: BeaconState<T>,
+    pub block: Option<BeaconBlock<T>>,
+    pub attestations: Vec<Attestation<T>>,
+}
+
+impl<T: EthSpec> Fixture<T> {
+    /// Builds the fixture of `scenario`.
+    ///
+    /// # Panics
+    ///
+    /// If the builders are unable to produce the scenario (e.g., `config.epoch` is too low).
+    pub fn build(scenario: Scenario, config: &Config, spec: &ChainSpec) -> Self {
+        let mut state_builder =
+            TestingBeaconStateBuilder::from_deterministic_keypairs(config.validator_count, spec);
+
+        match scenario {
+            Scenario::Genesis => {}
+            Scenario::EpochTransition => {
+                state_builder.teleport_to_slot(config.epoch.end_slot(T::slots_per_epoch()));
+                state_builder.insert_attestations(spec);
+            }
+            Scenario::Block => {
+                state_builder.teleport_to_slot(config.epoch.start_slot(T::slots_per_epoch()));
+            }
+        }
+
+        state_builder
+            .build_caches(spec)
+            .expect("should build caches");
+        let (pre_state, keypairs) = state_builder.build();
+
+        let block = match scenario {
+            Scenario::Block => {
+                let mut builder = TestingBeaconBlockBuilder::new(spec);
+                builder.set_slot(pre_state.slot);
+                builder.set_parent_root(Hash256::from_slice(
+                    &pre_state.latest_block_header.signed_root(),
+                ));
+
+                let secret_keys: Vec<&SecretKey> = keypairs.iter().map(|kp| &kp.sk).collect();
+                builder
+                    .insert_attestations(&pre_state, &secret_keys, config.num_attestations, spec)
+                    .expect("should insert attestations");
+
+                let proposer_index = pre_state
+                    .get_beacon_proposer_index(pre_state.slot, RelativeEpoch::Current, spec)
+                    .expect("should get proposer");
+                let sk = &keypairs[proposer_index].sk;
+                builder.set_randao_reveal(sk, &pre_state.fork, spec);
+
+                Some(builder.build(sk, &pre_state.fork, spec))
+            }
+            _ => None,
+        };
+
+        let attestations = block
+            .as_ref()
+            .map(|block| block.body.attestations.to_vec())
+            .unwrap_or_default();
+
+        Self {
+            scenario,
+            config: config.clone(),
+            pre_state,
+            block,
+            attestations,
+        }
+    }
+
+    /// Returns the description of `self`, as written to `meta.yaml`.
+    pub fn meta(&self) -> Meta {
+        Meta {
+            scenario: self.scenario,
+            config: self.config.clone(),
+            slots_per_epoch: T::slots_per_epoch(),
+            pre_state_root: self.pre_state.canonical_root(),
+            block_root: self
+                .block
+                .as_ref()
+                .map(|block| Hash256::from_slice(&block.signed_root())),
+        }
+    }
+
+    /// Writes the fixture to `dir`, creating it if required.
+    pub fn write(&self, dir: &Path, formats: &[Format]) -> Result<(), String> {
+        fs::create_dir_all(dir).map_err(|e| format!("Unable to create {:?}: {:?}", dir, e))?;
+        write_yaml(&dir.join("meta.yaml"), &self.meta())?;
+
+        for format in formats {
+            write_object(dir, "pre_state", &self.pre_state, *format)?;
+            if let Some(block) = &self.block {
+                write_object(dir, "block", block, *format)?;
+            }
+            if !self.attestations.is_empty() {
+                write_object(dir, "attestations", &self.attestations, *format)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads a fixture from `dir`, preferring SSZ files over YAML files.
+    ///
+    /// Returns an error if the roots of the objects do not match `meta.yaml`.
+    pub fn read(dir: &Path) -> Result<Self, String> {
+        let meta: Meta = read_yaml(&dir.join("meta.yaml"))?;
+        if meta.slots_per_epoch != T::slots_per_epoch() {
+            return Err(format!(
+                "Fixture has {} slots per epoch, expected {}",
+                meta.slots_per_epoch,
+                T::slots_per_epoch()
+            ));
+        }
+
+        let fixture = Self {
+            scenario: meta.scenario,
+            config: meta.config.clone(),
+            pre_state: read_object(dir, "pre_state")?
+                .ok_or_else(|| format!("Missing pre_state in {:?}", dir))?,
+            block: read_object(dir, "block")?,
+            attestations: read_object(dir, "attestations")?.unwrap_or_default(),
+        };
+
+        if fixture.meta() != meta {
+            return Err(format!("Fixture in {:?} does not match its meta.yaml", dir));
+        }
+
+        Ok(fixture)
+    }
+}
+
+fn write_yaml<O: serde::Serialize>(path: &Path, object: &O) -> Result<(), String> {
+    let file =
+        fs::File::create(path).map_err(|e| format!("Unable to create {:?}: {:?}", path, e))?;
+    serde_yaml::to_writer(file, object).map_err(|e| format!("Unable to write {:?}: {:?}", path, e))
+}
+
+fn read_yaml<O: DeserializeOwned>(path: &Path) -> Result<O, String> {
+    let file = fs::File::open(path).map_err(|e| format!("Unable to open {:?}: {:?}", path, e))?;
+    serde_yaml::from_reader(file).map_err(|e| format!("Unable to decode {:?}: {:?}", path, e))
+}
+
+fn write_object<O: Encode + serde::Serialize>(
+    dir: &Path,
+    name: &str,
+    object: &O,
+    format: Format,
+) -> Result<(), String> {
+    match format {
+        Format::Ssz => {
+            let path = dir.join(format!("{}.ssz", name));
+            fs::write(&path, object.as_ssz_bytes())
+                .map_err(|e| format!("Unable to write {:?}: {:?}", path, e))
+        }
+        Format::Yaml => write_yaml(&dir.join(format!("{}.yaml", name)), object),
+    }
+}
+
+/// Reads the object `name` from its SSZ file in `dir`, or its YAML file if there is no SSZ file.
+///
+/// Returns `Ok(None)` if neither file exists.
+fn read_object<O: Decode + DeserializeOwned>(dir: &Path, name: &str) -> Result<Option<O>, String> {
+    let ssz_path = dir.join(format!("{}.ssz", name));
+    let yaml_path = dir.join(format!("{}.yaml", name));
+
+    if ssz_path.exists() {
+        let bytes =
+            fs::read(&ssz_path).map_err(|e| format!("Unable to read {:?}: {:?}", ssz_path, e))?;
+        O::from_ssz_bytes(&bytes)
+            .map(Some)
+            .map_err(|e| format!("Unable to decode {:?}: {:?}", ssz_path, e))
+    } else if yaml_path.exists() {
+        read_yaml(&yaml_path).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use state_processing::per_block_processing;
+    use tempfile::TempDir;
+
+    #[test]
+    fn block_fixture_round_trip() {
+        let spec = MinimalEthSpec::default_spec();
+        let config = Config {
+            validator_count: 64,
+            epoch: Epoch::new(4),
+            num_attestations: 2,
+        };
+
+        let fixture: Fixture<MinimalEthSpec> = Fixture::build(Scenario::Block, &config, &spec);
+        assert_eq!(fixture.attestations.len(), 2);
+        assert_eq!(
+            fixture.meta(),
+            Fixture::<MinimalEthSpec>::build(Scenario::Block, &config, &spec).meta(),
+            "fixtures should be reproducible"
+        );
+
+        let mut state = fixture.pre_state.clone();
+        let block = fixture.block.as_ref().expect("should have block");
+        assert_eq!(per_block_processing(&mut state, block, &spec), Ok(()));
+
+        let dir = TempDir::new().expect("should create temp dir");
+        fixture
+            .write(dir.path(), &[Format::Ssz])
+            .expect("should write fixture");
+        let read = Fixture::<MinimalEthSpec>::read(dir.path()).expect("should read fixture");
+        assert_eq!(read.meta(), fixture.meta());
+    }
+}
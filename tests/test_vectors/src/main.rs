@@ -0,0 +1,144 @@
+#[macro_use]
+extern crate log;
+
+use clap::{App, Arg};
+use std::path::PathBuf;
+use test_vectors::{Config, Fixture, Format, Scenario};
+use types::{Epoch, EthSpec, MainnetEthSpec, MinimalEthSpec};
+
+fn main() {
+    simple_logger::init().expect("logger should initialize");
+
+    let matches = App::new("Lighthouse Test Vector Generator")
+        .version("0.1.0")
+        .author("Paul Hauner <paul@sigmaprime.io>")
+        .about("Generates reproducible SSZ/YAML states, blocks and attestations.")
+        .arg(
+            Arg::with_name("spec")
+                .short("s")
+                .value_name("STRING")
+                .takes_value(true)
+                .possible_values(&["minimal", "mainnet"])
+                .default_value("minimal")
+                .help("The Eth2 spec of the fixtures."),
+        )
+        .arg(
+            Arg::with_name("scenario")
+                .long("scenario")
+                .value_name("STRING")
+                .takes_value(true)
+                .possible_values(&["genesis", "epoch_transition", "block", "all"])
+                .default_value("all")
+                .help("The scenario to generate."),
+        )
+        .arg(
+            Arg::with_name("num_validators")
+                .short("n")
+                .value_name("INTEGER")
+                .takes_value(true)
+                .help("Number of validators in the state."),
+        )
+        .arg(
+            Arg::with_name("epoch")
+                .long("epoch")
+                .value_name("INTEGER")
+                .takes_value(true)
+                .help("The epoch of the state (at least 3)."),
+        )
+        .arg(
+            Arg::with_name("num_attestations")
+                .long("attestations")
+                .value_name("INTEGER")
+                .takes_value(true)
+                .help("Number of attestations in generated blocks."),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .value_name("STRING")
+                .takes_value(true)
+                .possible_values(&["ssz", "yaml", "both"])
+                .default_value("ssz")
+                .help("Encoding of the generated objects."),
+        )
+        .arg(
+            Arg::with_name("output_dir")
+                .short("o")
+                .value_name("PATH")
+                .takes_value(true)
+                .default_value("./test_vectors")
+                .help("Directory in which a <spec>/<scenario> directory is created per fixture."),
+        )
+        .get_matches();
+
+    let parse = |name: &str| -> Option<u64> {
+        matches.value_of(name).map(|string| {
+            string
+                .parse::<u64>()
+                .unwrap_or_else(|_| panic!("{} must be a valid integer", name))
+        })
+    };
+
+    let mut config = Config::default();
+    if let Some(n) = parse("num_validators") {
+        config.validator_count = n as usize;
+    }
+    if let Some(epoch) = parse("epoch") {
+        if epoch < 3 {
+            panic!("epoch must be at least 3");
+        }
+        config.epoch = Epoch::new(epoch);
+    }
+    if let Some(n) = parse("num_attestations") {
+        config.num_attestations = n as usize;
+    }
+
+    let scenarios = match matches.value_of("scenario").expect("has default value") {
+        "all" => Scenario::all(),
+        scenario => vec![scenario.parse().expect("guarded by clap possible_values")],
+    };
+
+    let formats = match matches.value_of("format").expect("has default value") {
+        "ssz" => vec![Format::Ssz],
+        "yaml" => vec![Format::Yaml],
+        "both" => vec![Format::Ssz, Format::Yaml],
+        _ => unreachable!("guarded by clap possible_values"),
+    };
+
+    let spec = matches.value_of("spec").expect("has default value");
+    let output_dir =
+        PathBuf::from(matches.value_of("output_dir").expect("has default value")).join(spec);
+
+    for scenario in scenarios {
+        let dir = output_dir.join(scenario.name());
+        match spec {
+            "minimal" => generate::<MinimalEthSpec>(scenario, &config, &formats, dir),
+            "mainnet" => generate::<MainnetEthSpec>(scenario, &config, &formats, dir),
+            _ => unreachable!("guarded by clap possible_values"),
+        }
+    }
+
+    info!("Test vectors created. Exiting successfully.");
+}
+
+/// Builds the fixture for `scenario` and writes it to `dir`.
+fn generate<T: EthSpec>(scenario: Scenario, config: &Config, formats: &[Format], dir: PathBuf) {
+    info!(
+        "Generating {} fixture with {} validators.",
+        scenario.name(),
+        config.validator_count
+    );
+
+    let fixture: Fixture<T> = Fixture::build(scenario, config, &T::default_spec());
+
+    info!(
+        "Writing {} fixture to {:?} (state root {:?}).",
+        scenario.name(),
+        dir,
+        fixture.meta().pre_state_root
+    );
+
+    fixture
+        .write(&dir, formats)
+        .unwrap_or_else(|e| panic!("unable to write fixture: {}", e));
+}
@@ -0,0 +1,140 @@
+use criterion::Criterion;
+use criterion::{black_box, criterion_group, criterion_main, Benchmark};
+use futures::Future;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tree_hash::SignedRoot;
+use types::test_utils::{SeedableRng, TestRandom, XorShiftRng};
+use types::{BeaconBlock, ChainSpec, Domain, EthSpec, Fork, Keypair, MinimalEthSpec, Slot};
+use validator_client::block_latency::PublishWindow;
+use validator_client::block_producer::test_utils::TestBeaconNode;
+use validator_client::block_producer::BlockProducer;
+use validator_client::notifier::Notifier;
+use validator_client::proposal_timing::ProposalTimings;
+use validator_client::signer::Signer;
+use validator_client::signing_lease::SigningLease;
+use validator_client::slashing_protection::SlashingProtection;
+
+/// Benchmarks a full `produce_block` (randao signing, block request, signing and publishing)
+/// against a beacon node which takes `latency` to respond to each request.
+fn produce_block<T: EthSpec>(c: &mut Criterion, spec_desc: &str, latency: Duration) {
+    let spec = Arc::new(T::default_spec());
+    let beacon_node = Arc::new(TestBeaconNode::with_latency(latency));
+    let keypair = Keypair::random();
+
+    c.bench(
+        &format!("{}/produce_block", spec_desc),
+        Benchmark::new(format!("{}ms_latency", latency.as_millis()), move |b| {
+            b.iter(|| {
+                let block_producer = block_producer::<T>(&spec, &beacon_node, &keypair);
+                black_box(block_producer.produce_block().wait())
+            })
+        })
+        .sample_size(10),
+    );
+}
+
+/// Benchmarks `handle_produce_block`, as driven by the validator service at each proposal: a full
+/// `produce_block`, followed by recording and logging its outcome.
+fn handle_produce_block<T: EthSpec>(c: &mut Criterion, spec_desc: &str, latency: Duration) {
+    let spec = Arc::new(T::default_spec());
+    let beacon_node = Arc::new(TestBeaconNode::with_latency(latency));
+    let keypair = Keypair::random();
+    let log = slog::Logger::root(slog::Discard, slog::o!());
+
+    c.bench(
+        &format!("{}/handle_produce_block", spec_desc),
+        Benchmark::new(format!("{}ms_latency", latency.as_millis()), move |b| {
+            b.iter(|| {
+                let block_producer = block_producer::<T>(&spec, &beacon_node, &keypair);
+                black_box(block_producer.handle_produce_block(log.clone()).wait())
+            })
+        })
+        .sample_size(10),
+    );
+}
+
+/// A `BlockProducer` for the slot after genesis, which has just started.
+fn block_producer<T: EthSpec>(
+    spec: &Arc<ChainSpec>,
+    beacon_node: &Arc<TestBeaconNode>,
+    keypair: &Keypair,
+) -> BlockProducer<TestBeaconNode, Keypair, T> {
+    BlockProducer {
+        fork: Fork::genesis(T::genesis_epoch()),
+        slot: Slot::new(1),
+        spec: spec.clone(),
+        beacon_node: beacon_node.clone(),
+        signer: keypair.clone(),
+        slots_per_epoch: T::slots_per_epoch(),
+        slashing_protection: Arc::new(SlashingProtection::new()),
+        signing_lease: Arc::new(SigningLease::disabled()),
+        fee_recipient: None,
+        slot_start: Instant::now(),
+        deadline: Instant::now() + Duration::from_secs(spec.seconds_per_slot),
+        timings: Arc::new(ProposalTimings::default()),
+        publish_window: PublishWindow::default(),
+        notifier: Arc::new(Notifier::disabled()),
+        dry_run: false,
+        _phantom: PhantomData,
+    }
+}
+
+/// Benchmarks signing the `signed_root` of a block.
+fn sign_block<T: EthSpec>(c: &mut Criterion, spec_desc: &str) {
+    let spec = T::default_spec();
+    let keypair = Keypair::random();
+    let block: BeaconBlock<T> = BeaconBlock::empty(&spec);
+    let domain = proposer_domain::<T>(&spec);
+    let message = block.signed_root();
+
+    c.bench(
+        &format!("{}/signing", spec_desc),
+        Benchmark::new("sign_block_root", move |b| {
+            b.iter(|| black_box(keypair.sign_message(&message, domain).wait()))
+        }),
+    );
+}
+
+/// Benchmarks computing the `signed_root` of an empty and a randomly-filled block.
+fn block_signed_root<T: EthSpec>(c: &mut Criterion, spec_desc: &str) {
+    let spec = T::default_spec();
+    let mut rng = XorShiftRng::from_seed([42; 16]);
+    let empty_block: BeaconBlock<T> = BeaconBlock::empty(&spec);
+    let random_block: BeaconBlock<T> = BeaconBlock::random_for_test(&mut rng);
+
+    c.bench(
+        &format!("{}/tree_hash", spec_desc),
+        Benchmark::new("empty_block_signed_root", move |b| {
+            b.iter(|| black_box(empty_block.signed_root()))
+        })
+        .with_function("random_block_signed_root", move |b| {
+            b.iter(|| black_box(random_block.signed_root()))
+        }),
+    );
+}
+
+fn proposer_domain<T: EthSpec>(spec: &ChainSpec) -> u64 {
+    spec.get_domain(
+        T::genesis_epoch(),
+        Domain::BeaconProposer,
+        &Fork::genesis(T::genesis_epoch()),
+    )
+}
+
+fn all_benches(c: &mut Criterion) {
+    produce_block::<MinimalEthSpec>(c, "minimal", Duration::from_millis(0));
+    produce_block::<MinimalEthSpec>(c, "minimal", Duration::from_millis(50));
+    produce_block::<MinimalEthSpec>(c, "minimal", Duration::from_millis(250));
+
+    handle_produce_block::<MinimalEthSpec>(c, "minimal", Duration::from_millis(0));
+    handle_produce_block::<MinimalEthSpec>(c, "minimal", Duration::from_millis(50));
+
+    sign_block::<MinimalEthSpec>(c, "minimal");
+
+    block_signed_root::<MinimalEthSpec>(c, "minimal");
+}
+
+criterion_group!(benches, all_benches,);
+criterion_main!(benches);
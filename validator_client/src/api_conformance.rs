@@ -0,0 +1,435 @@
+//! A conformance check of a beacon node HTTP API, so that users can verify a third-party beacon
+//! node before trusting it with proposals.
+//!
+//! Each endpoint which the validator client uses (see `beacon_api`) is requested once, and its
+//! response is checked against what the validator client expects of it. An endpoint is reported
+//! as:
+//!
+//! - Missing, if the beacon node does not serve it (`404 Not Found`, `405 Method Not Allowed` or
+//!   `501 Not Implemented`).
+//! - Deviant, if it responds with an unexpected status, a body which cannot be decoded, or values
+//!   which are inconsistent with the request (e.g., duties outside of the requested epoch).
+//!
+//! Nothing is signed. Blocks are requested with an empty randao reveal, and the block and
+//! attestation published are unsigned and at the genesis slot, so a conformant beacon node refuses
+//! them with `400 Bad Request` rather than broadcasting them.
+use crate::beacon_api::{BeaconApiClient, ForkResponse, RawResponse, ValidatorDuty};
+use crate::block_producer::BeaconNodeError;
+use crate::wss_checkpoint::CheckpointRoot;
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use slot_clock::{SlotClock, SystemTimeSlotClock};
+use ssz::Encode;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::runtime::Runtime;
+use types::{
+    AggregateSignature, Attestation, AttestationData, BeaconBlock, BeaconBlockAny, BitList,
+    ChainSpec, Checkpoint, Crosslink, Epoch, EthSpec, Hash256, Keypair, Signature,
+};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("unable to create a client for the beacon node: {0}")]
+    Client(String),
+    #[error("unable to start the runtime: {0}")]
+    Runtime(#[from] io::Error),
+}
+
+/// How an endpoint conforms to what the validator client expects of it.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Status {
+    Conformant,
+    Missing,
+    Deviant,
+}
+
+/// The result of checking a single endpoint.
+#[derive(Debug, PartialEq)]
+pub struct Check {
+    pub endpoint: &'static str,
+    pub status: Status,
+    /// What the beacon node responded, in any case.
+    pub detail: String,
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let status = match self.status {
+            Status::Conformant => "PASS",
+            Status::Missing => "MISSING",
+            Status::Deviant => "DEVIANT",
+        };
+        write!(f, "[{}] {}: {}", status, self.endpoint, self.detail)
+    }
+}
+
+/// Checks each endpoint of the beacon node HTTP API at `url`, which should be on the chain of
+/// `spec`, and returns the result of each check.
+///
+/// Returns an `Err` only if the checks were unable to run.
+pub fn run<E: EthSpec>(url: &str, spec: &ChainSpec) -> Result<Vec<Check>, Error> {
+    let client = BeaconApiClient::new(url, Arc::new(spec.clone())).map_err(Error::Client)?;
+    let mut runtime = Runtime::new()?;
+    let mut checks = vec![];
+    let mut get = |path, query: &[(&str, String)]| runtime.block_on(client.get_raw(path, query));
+
+    checks.push(check_json(
+        "GET /node/version",
+        get("/node/version", &[]),
+        |version: String| {
+            if version.is_empty() {
+                Err("the version is empty".to_string())
+            } else {
+                Ok(version)
+            }
+        },
+    ));
+
+    let mut genesis_time = None;
+    checks.push(check_json(
+        "GET /node/genesis_time",
+        get("/node/genesis_time", &[]),
+        |time: u64| {
+            genesis_time = Some(time);
+            Ok(format!("genesis at {} seconds since the UNIX epoch", time))
+        },
+    ));
+
+    checks.push(check_json(
+        "GET /node/fork",
+        get("/node/fork", &[]),
+        |ForkResponse { fork, chain_id }| {
+            if chain_id != u64::from(spec.network_id) {
+                Err(format!(
+                    "chain id {}, but the configured spec has {}",
+                    chain_id, spec.network_id
+                ))
+            } else {
+                Ok(format!("{:?} on chain {}", fork, chain_id))
+            }
+        },
+    ));
+
+    // The genesis root is optional, and only compared to the root of the genesis epoch.
+    let mut genesis_root = None;
+    checks.push(match get("/node/genesis_root", &[]) {
+        Ok(ref response) if response.status == StatusCode::NOT_FOUND => Check {
+            endpoint: "GET /node/genesis_root",
+            status: Status::Conformant,
+            detail: "not served, which is optional".to_string(),
+        },
+        response => check_json("GET /node/genesis_root", response, |root: Hash256| {
+            genesis_root = Some(root);
+            Ok(format!("{:?}", root))
+        }),
+    });
+
+    let genesis_epoch = spec.genesis_slot.epoch(E::slots_per_epoch());
+    checks.push(check_json(
+        "GET /beacon/checkpoint_root",
+        get(
+            "/beacon/checkpoint_root",
+            &[("epoch", genesis_epoch.as_u64().to_string())],
+        ),
+        |checkpoint: CheckpointRoot| match (checkpoint.root, genesis_root) {
+            (Some(root), Some(genesis_root)) if root != genesis_root => Err(format!(
+                "the root {:?} of the genesis epoch is not the genesis root {:?}",
+                root, genesis_root
+            )),
+            _ => Ok(format!("{:?}", checkpoint)),
+        },
+    ));
+
+    // Duties, blocks and attestation data are requested for the present slot (or the genesis
+    // slot, before genesis).
+    let slot = genesis_time
+        .and_then(|genesis_time| {
+            SystemTimeSlotClock::new(spec.genesis_slot, genesis_time, spec.seconds_per_slot)
+                .present_slot()
+                .ok()?
+        })
+        .unwrap_or(spec.genesis_slot);
+    let epoch = slot.epoch(E::slots_per_epoch());
+
+    // The duties of an unknown validator are still answered, so any well-formed key will do.
+    let pub_key = Keypair::random().pk;
+    checks.push(check_json(
+        "GET /validator/duties",
+        get(
+            "/validator/duties",
+            &[
+                ("validator_pubkeys", pub_key.as_hex_string()),
+                ("epoch", epoch.as_u64().to_string()),
+            ],
+        ),
+        |duties: Vec<ValidatorDuty>| {
+            for duty in &duties {
+                if duty.validator_pubkey != pub_key {
+                    return Err("duties of an unrequested validator".to_string());
+                }
+                if let Some(duty) = duty.epoch_duty() {
+                    let attestation = &duty.attestation_duty;
+                    if attestation.slot.epoch(E::slots_per_epoch()) != epoch {
+                        return Err(format!(
+                            "attestation slot {} outside of epoch {}",
+                            attestation.slot, epoch
+                        ));
+                    }
+                    if attestation.committee_index >= attestation.committee_len {
+                        return Err(format!(
+                            "committee index {} outside of a committee of {}",
+                            attestation.committee_index, attestation.committee_len
+                        ));
+                    }
+                    if let Some(slot) = duty
+                        .block_production_slots
+                        .iter()
+                        .find(|slot| slot.epoch(E::slots_per_epoch()) != epoch)
+                    {
+                        return Err(format!("proposal slot {} outside of epoch {}", slot, epoch));
+                    }
+                }
+            }
+            Ok(format!("{} duties in epoch {}", duties.len(), epoch))
+        },
+    ));
+
+    let block_slot = slot + 1;
+    let block_request = get(
+        "/validator/block",
+        &[
+            ("slot", block_slot.as_u64().to_string()),
+            (
+                "randao_reveal",
+                format!(
+                    "0x{}",
+                    hex::encode(Signature::empty_signature().as_ssz_bytes())
+                ),
+            ),
+        ],
+    );
+    checks.push(match block_request {
+        Ok(ref response) if response.status == StatusCode::BAD_REQUEST => Check {
+            endpoint: "GET /validator/block",
+            status: Status::Conformant,
+            detail: format!("refused the empty randao reveal: {}", response.body),
+        },
+        response => check_json(
+            "GET /validator/block",
+            response,
+            |block: serde_json::Value| {
+                let variant = spec.block_variant(block_slot.epoch(E::slots_per_epoch()));
+                let block = BeaconBlockAny::<E>::deserialize_variant(block, variant)
+                    .map_err(|e| format!("not a {:?} block: {}", variant, e))?;
+                if block.slot() != block_slot {
+                    Err(format!(
+                        "a block at slot {}, requested at {}",
+                        block.slot(),
+                        block_slot
+                    ))
+                } else {
+                    Ok(format!("a {:?} block at slot {}", variant, block_slot))
+                }
+            },
+        ),
+    });
+
+    checks.push(check_json(
+        "GET /validator/attestation_data",
+        get(
+            "/validator/attestation_data",
+            &[
+                ("slot", slot.as_u64().to_string()),
+                ("shard", "0".to_string()),
+            ],
+        ),
+        |data: AttestationData| {
+            if data.target.epoch != epoch {
+                Err(format!(
+                    "target epoch {}, requested in {}",
+                    data.target.epoch, epoch
+                ))
+            } else if data.source.epoch > data.target.epoch {
+                Err(format!(
+                    "source epoch {} after target epoch {}",
+                    data.source.epoch, data.target.epoch
+                ))
+            } else {
+                Ok(format!("{:?}", data))
+            }
+        },
+    ));
+
+    let block: BeaconBlockAny<E> = BeaconBlock::empty(spec).into();
+    checks.push(check_refused(
+        "POST /validator/block",
+        runtime.block_on(client.post_raw("/validator/block", &block)),
+    ));
+
+    checks.push(check_refused(
+        "POST /validator/attestation",
+        runtime.block_on(client.post_raw(
+            "/validator/attestation",
+            &unsigned_attestation::<E>(genesis_epoch),
+        )),
+    ));
+
+    Ok(checks)
+}
+
+/// Checks a response which should be successful, with a JSON body which decodes to `T` and is
+/// accepted by `expect`.
+fn check_json<T: DeserializeOwned>(
+    endpoint: &'static str,
+    response: Result<RawResponse, BeaconNodeError>,
+    expect: impl FnOnce(T) -> Result<String, String>,
+) -> Check {
+    let (status, detail) = match response {
+        Ok(response) => match unsupported(&response) {
+            Some(check) => return Check { endpoint, ..check },
+            None if response.status.is_success() => match serde_json::from_str(&response.body) {
+                Ok(body) => match expect(body) {
+                    Ok(detail) => (Status::Conformant, detail),
+                    Err(detail) => (Status::Deviant, detail),
+                },
+                Err(e) => (Status::Deviant, format!("undecodable response: {}", e)),
+            },
+            None => (Status::Deviant, unexpected(&response)),
+        },
+        Err(e) => (Status::Deviant, e.to_string()),
+    };
+    Check {
+        endpoint,
+        status,
+        detail,
+    }
+}
+
+/// Checks a response to publishing an invalid object, which should be refused.
+fn check_refused(endpoint: &'static str, response: Result<RawResponse, BeaconNodeError>) -> Check {
+    let (status, detail) = match response {
+        Ok(response) => match unsupported(&response) {
+            Some(check) => return Check { endpoint, ..check },
+            None if response.status == StatusCode::BAD_REQUEST => (
+                Status::Conformant,
+                format!("refused an unsigned object: {}", response.body),
+            ),
+            None if response.status.is_success() => (
+                Status::Deviant,
+                format!("accepted an unsigned object with {}", response.status),
+            ),
+            None => (Status::Deviant, unexpected(&response)),
+        },
+        Err(e) => (Status::Deviant, e.to_string()),
+    };
+    Check {
+        endpoint,
+        status,
+        detail,
+    }
+}
+
+/// Returns a (nameless) missing check if `response` indicates its endpoint is not served.
+fn unsupported(response: &RawResponse) -> Option<Check> {
+    match response.status {
+        StatusCode::NOT_FOUND | StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED => {
+            Some(Check {
+                endpoint: "",
+                status: Status::Missing,
+                detail: format!("not served ({})", response.status),
+            })
+        }
+        _ => None,
+    }
+}
+
+fn unexpected(response: &RawResponse) -> String {
+    format!("unexpected {}: {}", response.status, response.body)
+}
+
+/// Returns an attestation to the genesis epoch without any attesters or signature.
+fn unsigned_attestation<E: EthSpec>(genesis_epoch: Epoch) -> Attestation<E> {
+    let checkpoint = Checkpoint {
+        epoch: genesis_epoch,
+        root: Hash256::zero(),
+    };
+    let bits = || BitList::with_capacity(1).expect("a single bit is within the maximum");
+    Attestation {
+        aggregation_bits: bits(),
+        data: AttestationData {
+            beacon_block_root: Hash256::zero(),
+            source: checkpoint.clone(),
+            target: checkpoint,
+            crosslink: Crosslink::default(),
+        },
+        custody_bits: bits(),
+        signature: AggregateSignature::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(status: StatusCode, body: &str) -> Result<RawResponse, BeaconNodeError> {
+        Ok(RawResponse {
+            status,
+            body: body.to_string(),
+        })
+    }
+
+    #[test]
+    fn classifies_responses() {
+        let positive = |n: i64| {
+            if n > 0 {
+                Ok(n.to_string())
+            } else {
+                Err("not positive".to_string())
+            }
+        };
+        let status = |response, expect| check_json("GET /n", response, expect).status;
+
+        assert_eq!(
+            status(response(StatusCode::OK, "1"), positive),
+            Status::Conformant
+        );
+        assert_eq!(
+            status(response(StatusCode::OK, "-1"), positive),
+            Status::Deviant
+        );
+        assert_eq!(
+            status(response(StatusCode::OK, "\"1\""), positive),
+            Status::Deviant
+        );
+        assert_eq!(
+            status(response(StatusCode::NOT_FOUND, ""), positive),
+            Status::Missing
+        );
+        assert_eq!(
+            status(response(StatusCode::INTERNAL_SERVER_ERROR, "1"), positive),
+            Status::Deviant
+        );
+        assert_eq!(
+            status(Err(BeaconNodeError::DecodeFailure), positive),
+            Status::Deviant
+        );
+
+        let refused = |response| check_refused("POST /n", response).status;
+        assert_eq!(
+            refused(response(StatusCode::BAD_REQUEST, "")),
+            Status::Conformant
+        );
+        assert_eq!(refused(response(StatusCode::ACCEPTED, "")), Status::Deviant);
+        assert_eq!(
+            refused(response(StatusCode::METHOD_NOT_ALLOWED, "")),
+            Status::Missing
+        );
+        assert_eq!(
+            check_refused("POST /n", response(StatusCode::NOT_IMPLEMENTED, "")).endpoint,
+            "POST /n"
+        );
+    }
+}
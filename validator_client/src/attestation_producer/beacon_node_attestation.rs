@@ -1,17 +1,27 @@
 //TODO: generalise these enums to the crate
-use crate::block_producer::{BeaconNodeError, PublishOutcome};
-use types::{Attestation, AttestationData, EthSpec, Slot};
+use crate::block_producer::{BeaconNodeFuture, PublishOutcome};
+use types::{Attestation, AttestationData, EthSpec, Hash256, Slot};
+
+/// The attestations included in the canonical block at a slot.
+#[derive(Debug, PartialEq, Clone)]
+pub struct BlockAttestations<T: EthSpec> {
+    /// The root of the canonical block at the slot, or of its closest prior block if the slot was
+    /// skipped.
+    pub block_root: Hash256,
+    /// `true` if no block was produced at the slot.
+    pub skipped: bool,
+    pub attestations: Vec<Attestation<T>>,
+}
 
 /// Defines the methods required to produce and publish attestations on a Beacon Node. Abstracts the
 /// actual beacon node.
+///
+/// All requests are asynchronous, so that no thread is blocked whilst awaiting a response.
 pub trait BeaconNodeAttestation: Send + Sync {
     /// Request that the node produces the required attestation data.
     ///
-    fn produce_attestation_data(
-        &self,
-        slot: Slot,
-        shard: u64,
-    ) -> Result<AttestationData, BeaconNodeError>;
+    fn produce_attestation_data(&self, slot: Slot, shard: u64)
+        -> BeaconNodeFuture<AttestationData>;
 
     /// Request that the node publishes a attestation.
     ///
@@ -19,5 +29,24 @@ pub trait BeaconNodeAttestation: Send + Sync {
     fn publish_attestation<T: EthSpec>(
         &self,
         attestation: Attestation<T>,
-    ) -> Result<PublishOutcome, BeaconNodeError>;
+    ) -> BeaconNodeFuture<PublishOutcome>;
+
+    /// Request whether the node already knows an attestation at `slot` and `shard` signed by the
+    /// committee member at `committee_index`.
+    ///
+    /// Returns `true` if signing another attestation for the member would be redundant.
+    fn attestation_known(
+        &self,
+        slot: Slot,
+        shard: u64,
+        committee_index: usize,
+    ) -> BeaconNodeFuture<bool>;
+
+    /// Request the attestations included in the canonical block at `slot`.
+    ///
+    /// Returns `None` if `slot` is later than the head of the node.
+    fn block_attestations<T: EthSpec>(
+        &self,
+        slot: Slot,
+    ) -> BeaconNodeFuture<Option<BlockAttestations<T>>>;
 }
@@ -1,57 +1,121 @@
-use super::beacon_node_attestation::BeaconNodeAttestation;
-use crate::block_producer::{BeaconNodeError, PublishOutcome};
+use super::beacon_node_attestation::{BeaconNodeAttestation, BlockAttestations};
+use crate::block_producer::{BeaconNodeError, BeaconNodeFuture, PublishOutcome};
+use futures::{future, Future};
+use protos::auth::Authenticated;
+use protos::convert::{self, FromProto, ToProto};
 use protos::services_grpc::AttestationServiceClient;
-use ssz::{Decode, Encode};
 
 use protos::services::{
-    Attestation as GrpcAttestation, ProduceAttestationDataRequest, PublishAttestationRequest,
+    AttestationKnownRequest, BlockAttestationsRequest, ProduceAttestationDataRequest,
+    PublishAttestationRequest,
 };
 use types::{Attestation, AttestationData, EthSpec, Slot};
 
-impl BeaconNodeAttestation for AttestationServiceClient {
+impl BeaconNodeAttestation for Authenticated<AttestationServiceClient> {
     fn produce_attestation_data(
         &self,
         slot: Slot,
         shard: u64,
-    ) -> Result<AttestationData, BeaconNodeError> {
+    ) -> BeaconNodeFuture<AttestationData> {
         let mut req = ProduceAttestationDataRequest::new();
         req.set_slot(slot.as_u64());
         req.set_shard(shard);
 
-        let reply = self
-            .produce_attestation_data(&req)
-            .map_err(|err| BeaconNodeError::RemoteFailure(format!("{:?}", err)))?;
-
-        let attestation_data =
-            AttestationData::from_ssz_bytes(reply.get_attestation_data().get_ssz())
-                .map_err(|_| BeaconNodeError::DecodeFailure)?;
-        Ok(attestation_data)
+        let call = self
+            .client()
+            .produce_attestation_data_async_opt(&req, self.call_option());
+        Box::new(
+            future::result(call)
+                .flatten()
+                .map_err(|err| BeaconNodeError::RemoteFailure(format!("{:?}", err)))
+                .and_then(|reply| {
+                    AttestationData::from_proto(reply.get_attestation_data())
+                        .map_err(|_| BeaconNodeError::DecodeFailure)
+                }),
+        )
     }
 
     fn publish_attestation<T: EthSpec>(
         &self,
         attestation: Attestation<T>,
-    ) -> Result<PublishOutcome, BeaconNodeError> {
+    ) -> BeaconNodeFuture<PublishOutcome> {
         let mut req = PublishAttestationRequest::new();
+        req.set_attestation(attestation.to_proto());
 
-        let ssz = attestation.as_ssz_bytes();
+        let call = self
+            .client()
+            .publish_attestation_async_opt(&req, self.call_option());
+        Box::new(
+            future::result(call)
+                .flatten()
+                .map_err(|err| BeaconNodeError::RemoteFailure(format!("{:?}", err)))
+                .map(|reply| {
+                    if reply.get_success() {
+                        PublishOutcome::Valid
+                    } else {
+                        // TODO: distinguish between different errors
+                        PublishOutcome::InvalidAttestation("Publish failed".to_string())
+                    }
+                }),
+        )
+    }
 
-        let mut grpc_attestation = GrpcAttestation::new();
-        grpc_attestation.set_ssz(ssz);
+    fn attestation_known(
+        &self,
+        slot: Slot,
+        shard: u64,
+        committee_index: usize,
+    ) -> BeaconNodeFuture<bool> {
+        let mut req = AttestationKnownRequest::new();
+        req.set_slot(slot.as_u64());
+        req.set_shard(shard);
+        req.set_committee_index(committee_index as u64);
+
+        let call = self
+            .client()
+            .attestation_known_async_opt(&req, self.call_option());
+        Box::new(
+            future::result(call)
+                .flatten()
+                .map_err(|err| BeaconNodeError::RemoteFailure(format!("{:?}", err)))
+                .map(|reply| reply.get_known()),
+        )
+    }
+
+    fn block_attestations<T: EthSpec>(
+        &self,
+        slot: Slot,
+    ) -> BeaconNodeFuture<Option<BlockAttestations<T>>> {
+        let mut req = BlockAttestationsRequest::new();
+        req.set_slot(slot.as_u64());
 
-        req.set_attestation(grpc_attestation);
+        let call = self
+            .client()
+            .block_attestations_async_opt(&req, self.call_option());
+        Box::new(
+            future::result(call)
+                .flatten()
+                .map_err(|err| BeaconNodeError::RemoteFailure(format!("{:?}", err)))
+                .and_then(|reply| {
+                    if !reply.get_known() {
+                        return Ok(None);
+                    }
+                    let block_root = convert::root_from_proto("block_root", reply.get_block_root())
+                        .map_err(|_| BeaconNodeError::DecodeFailure)?;
 
-        let reply = self
-            .publish_attestation(&req)
-            .map_err(|err| BeaconNodeError::RemoteFailure(format!("{:?}", err)))?;
+                    let attestations = reply
+                        .get_attestations()
+                        .iter()
+                        .map(Attestation::from_proto)
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|_| BeaconNodeError::DecodeFailure)?;
 
-        if reply.get_success() {
-            Ok(PublishOutcome::Valid)
-        } else {
-            // TODO: distinguish between different errors
-            Ok(PublishOutcome::InvalidAttestation(
-                "Publish failed".to_string(),
-            ))
-        }
+                    Ok(Some(BlockAttestations {
+                        block_root,
+                        skipped: reply.get_skipped(),
+                        attestations,
+                    }))
+                }),
+        )
     }
 }
@@ -4,32 +4,50 @@ mod grpc;
 use std::sync::Arc;
 use types::{ChainSpec, Domain, EthSpec, Fork};
 //TODO: Move these higher up in the crate
-use super::block_producer::{BeaconNodeError, PublishOutcome, ValidatorEvent};
+pub use self::beacon_node_attestation::{BeaconNodeAttestation, BlockAttestations};
+use super::block_producer::{BeaconNodeError, HandleFuture, PublishOutcome, ValidatorEvent};
+use crate::inclusion::InclusionMonitor;
+use crate::notifier::{Event, Notifier};
 use crate::signer::Signer;
-use beacon_node_attestation::BeaconNodeAttestation;
+use crate::signing_lease::{Claim, SigningLease};
+use crate::slashing_protection::{SigningPermit, SlashingProtection};
 use core::marker::PhantomData;
+use futures::{future, Future};
 use slog::{error, info, warn};
+use thiserror::Error;
 use tree_hash::TreeHash;
 use types::{
     AggregateSignature, Attestation, AttestationData, AttestationDataAndCustodyBit,
-    AttestationDuty, BitList,
+    AttestationDuty, BitList, Keypair, Slot,
 };
 
-//TODO: Group these errors at a crate level
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Error)]
 pub enum Error {
-    BeaconNodeError(BeaconNodeError),
+    /// A request to the beacon node for the attestation data at `slot` failed.
+    #[error("unable to produce the attestation at slot {slot}: {source}")]
+    BeaconNode {
+        slot: Slot,
+        #[source]
+        source: BeaconNodeError,
+    },
 }
 
-impl From<BeaconNodeError> for Error {
-    fn from(e: BeaconNodeError) -> Error {
-        Error::BeaconNodeError(e)
+impl Error {
+    /// Returns a function which adds the slot of the attestation being produced to a
+    /// `BeaconNodeError`.
+    pub fn beacon_node(slot: Slot) -> impl FnOnce(BeaconNodeError) -> Error {
+        move |source| Error::BeaconNode { slot, source }
     }
 }
 
+/// A future which resolves to the outcome of producing an attestation.
+type EventFuture = Box<dyn Future<Item = ValidatorEvent, Error = Error> + Send>;
+
 /// This struct contains the logic for requesting and signing beacon attestations for a validator. The
 /// validator can abstractly sign via the Signer trait object.
-pub struct AttestationProducer<'a, B: BeaconNodeAttestation, S: Signer, E: EthSpec> {
+///
+/// Production is asynchronous: each method returns a future which owns a copy of the producer.
+pub struct AttestationProducer<B: BeaconNodeAttestation, S: Signer, E: EthSpec> {
     /// The current fork.
     pub fork: Fork,
     /// The attestation duty to perform.
@@ -39,132 +57,270 @@ pub struct AttestationProducer<'a, B: BeaconNodeAttestation, S: Signer, E: EthSp
     /// The beacon node to connect to.
     pub beacon_node: Arc<B>,
     /// The signer to sign the block.
-    pub signer: &'a S,
+    pub signer: S,
     /// Used for calculating epoch.
     pub slots_per_epoch: u64,
+    /// The signing history of all validators, used to refuse slashable attestations.
+    pub slashing_protection: Arc<SlashingProtection>,
+    /// Claims the validator from other validator clients which share it, before it is signed for.
+    pub signing_lease: Arc<SigningLease>,
+    /// Tracks the inclusion of published attestations.
+    pub inclusion_monitor: Arc<InclusionMonitor>,
+    /// Notifies operators of attestations which could not be signed.
+    pub notifier: Arc<Notifier>,
+    /// If `true`, attestations are signed with a throwaway key and logged rather than published.
+    pub dry_run: bool,
     /// Mere vessel for E.
     pub _phantom: PhantomData<E>,
 }
 
-impl<'a, B: BeaconNodeAttestation, S: Signer, E: EthSpec> AttestationProducer<'a, B, S, E> {
+impl<B: BeaconNodeAttestation, S: Signer, E: EthSpec> Clone for AttestationProducer<B, S, E> {
+    fn clone(&self) -> Self {
+        Self {
+            fork: self.fork.clone(),
+            duty: self.duty,
+            spec: self.spec.clone(),
+            beacon_node: self.beacon_node.clone(),
+            signer: self.signer.clone(),
+            slots_per_epoch: self.slots_per_epoch,
+            slashing_protection: self.slashing_protection.clone(),
+            signing_lease: self.signing_lease.clone(),
+            inclusion_monitor: self.inclusion_monitor.clone(),
+            notifier: self.notifier.clone(),
+            dry_run: self.dry_run,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<B: BeaconNodeAttestation + 'static, S: Signer + 'static, E: EthSpec>
+    AttestationProducer<B, S, E>
+{
     /// Handle outputs and results from attestation production.
-    pub fn handle_produce_attestation(&mut self, log: slog::Logger) {
-        match self.produce_attestation() {
-            Ok(ValidatorEvent::AttestationProduced(_slot)) => {
-                info!(log, "Attestation produced"; "Validator" => format!("{}", self.signer))
-            }
-            Err(e) => error!(log, "Attestation production error"; "Error" => format!("{:?}", e)),
-            Ok(ValidatorEvent::SignerRejection(_slot)) => {
-                error!(log, "Attestation production error"; "Error" => "Signer could not sign the attestation".to_string())
-            }
-            Ok(ValidatorEvent::IndexedAttestationNotProduced(_slot)) => {
-                error!(log, "Attestation production error"; "Error" => "Rejected the attestation as it could have been slashed".to_string())
-            }
-            Ok(ValidatorEvent::PublishAttestationFailed) => {
-                error!(log, "Attestation production error"; "Error" => "Beacon node was unable to publish an attestation".to_string())
-            }
-            Ok(ValidatorEvent::InvalidAttestation) => {
-                error!(log, "Attestation production error"; "Error" => "The signed attestation was invalid".to_string())
-            }
-            Ok(v) => {
-                warn!(log, "Unknown result for attestation production"; "Error" => format!("{:?}",v))
+    pub fn handle_produce_attestation(&self, log: slog::Logger) -> HandleFuture {
+        let signer = self.signer.clone();
+        let duty = self.duty;
+        let inclusion_monitor = self.inclusion_monitor.clone();
+        let notifier = self.notifier.clone();
+        Box::new(self.produce_attestation().then(move |result| -> Result<(), ()> {
+            match result {
+                Ok(ValidatorEvent::AttestationProduced(_slot)) => {
+                    info!(log, "Attestation produced"; "Validator" => format!("{}", signer));
+                    inclusion_monitor.attested(signer.to_public(), duty);
+                }
+                Err(e) => error!(log, "Attestation production error"; "Error" => e.to_string()),
+                Ok(ValidatorEvent::AttestationAlreadyKnown(slot)) => {
+                    info!(log, "Attestation already known"; "Validator" => format!("{}", signer), "slot" => slot.as_u64());
+                    inclusion_monitor.attested(signer.to_public(), duty);
+                }
+                Ok(ValidatorEvent::AttestationNotPublished(slot)) => {
+                    info!(log, "Dry run: attestation not published"; "Validator" => format!("{}", signer), "slot" => slot.as_u64(), "shard" => duty.shard);
+                }
+                Ok(ValidatorEvent::SignerRejection(slot)) => {
+                    error!(log, "Attestation production error"; "Error" => "Signer could not sign the attestation".to_string());
+                    notifier.notify(Event::SignerRejection {
+                        validator: signer.to_public().as_hex_string(),
+                        slot,
+                    });
+                }
+                Ok(ValidatorEvent::IndexedAttestationNotProduced(slot)) => {
+                    error!(log, "Attestation production error"; "Error" => "Rejected the attestation as it could have been slashed".to_string());
+                    notifier.notify(Event::SlashableMessageBlocked {
+                        validator: signer.to_public().as_hex_string(),
+                        slot,
+                        message_type: "attestation",
+                    });
+                }
+                Ok(ValidatorEvent::PublishAttestationFailed) => {
+                    error!(log, "Attestation production error"; "Error" => "Beacon node was unable to publish an attestation".to_string())
+                }
+                Ok(ValidatorEvent::InvalidAttestation) => {
+                    error!(log, "Attestation production error"; "Error" => "The signed attestation was invalid".to_string())
+                }
+                Ok(ValidatorEvent::LeaseHeld(_slot, holder)) => {
+                    info!(log, "Attestation left to another validator client"; "Validator" => format!("{}", signer), "holder" => holder);
+                }
+                Ok(ValidatorEvent::LeaseUnavailable(_slot, reason)) => {
+                    error!(log, "Attestation production error"; "Error" => format!("Unable to claim the validator: {}", reason))
+                }
+                Ok(v) => {
+                    warn!(log, "Unknown result for attestation production"; "Error" => format!("{:?}",v))
+                }
             }
-        }
+            Ok(())
+        }))
     }
 
     /// Produce an attestation, sign it and send it back
     ///
     /// Assumes that an attestation is required at this slot (does not check the duties).
     ///
-    /// Ensures the message is not slashable.
-    ///
-    /// !!! UNSAFE !!!
-    ///
-    /// The slash-protection code is not yet implemented. There is zero protection against
-    /// slashing.
-    pub fn produce_attestation(&mut self) -> Result<ValidatorEvent, Error> {
-        let epoch = self.duty.slot.epoch(self.slots_per_epoch);
-
-        let attestation = self
-            .beacon_node
-            .produce_attestation_data(self.duty.slot, self.duty.shard)?;
-        if self.safe_to_produce(&attestation) {
-            let domain = self.spec.get_domain(epoch, Domain::Attestation, &self.fork);
-            if let Some(attestation) = self.sign_attestation(attestation, self.duty, domain) {
-                match self.beacon_node.publish_attestation(attestation) {
-                    Ok(PublishOutcome::InvalidAttestation(_string)) => {
-                        Ok(ValidatorEvent::InvalidAttestation)
+    /// Ensures the message is not slashable. No attestation is produced if the beacon node already
+    /// knows one signed by the validator, or if another validator client which shares the validator
+    /// holds its lease. A dry run never claims the validator.
+    pub fn produce_attestation(&self) -> EventFuture {
+        let producer = self.clone();
+        let slot = self.duty.slot;
+        if self.dry_run {
+            return producer.produce_claimed_attestation();
+        }
+
+        Box::new(
+            self.signing_lease
+                .claim(&self.signer.to_public(), slot)
+                .then(move |claim| -> EventFuture {
+                    match claim {
+                        Ok(Claim::Granted) => producer.produce_claimed_attestation(),
+                        Ok(Claim::Held { holder }) => {
+                            Box::new(future::ok(ValidatorEvent::LeaseHeld(slot, holder)))
+                        }
+                        Err(e) => Box::new(future::ok(ValidatorEvent::LeaseUnavailable(
+                            slot,
+                            e.to_string(),
+                        ))),
                     }
-                    Ok(PublishOutcome::Valid) => {
-                        Ok(ValidatorEvent::AttestationProduced(self.duty.slot))
+                }),
+        )
+    }
+
+    /// Produces the attestation once the validator has been claimed.
+    fn produce_claimed_attestation(self) -> EventFuture {
+        let producer = self.clone();
+        let slot = self.duty.slot;
+
+        Box::new(
+            self.beacon_node
+                .attestation_known(slot, self.duty.shard, self.duty.committee_index)
+                // The check is an optimisation, so failing to make it must not prevent the
+                // attestation from being produced.
+                .or_else(|_| -> Result<bool, Error> { Ok(false) })
+                .and_then(move |known| -> EventFuture {
+                    if known {
+                        return Box::new(future::ok(ValidatorEvent::AttestationAlreadyKnown(slot)));
                     }
-                    Err(_) | Ok(_) => Ok(ValidatorEvent::PublishAttestationFailed),
-                }
-            } else {
-                Ok(ValidatorEvent::SignerRejection(self.duty.slot))
+
+                    let beacon_node = producer.beacon_node.clone();
+                    Box::new(
+                        beacon_node
+                            .produce_attestation_data(slot, producer.duty.shard)
+                            .map_err(Error::beacon_node(slot))
+                            .and_then(move |attestation| {
+                                producer.sign_and_publish_attestation(attestation)
+                            }),
+                    )
+                }),
+        )
+    }
+
+    /// Signs and publishes an attestation to `attestation`, unless it is slashable. In a dry run,
+    /// the attestation is not published.
+    fn sign_and_publish_attestation(self, attestation: AttestationData) -> EventFuture {
+        let slot = self.duty.slot;
+
+        let permit = match self.safe_to_produce(&attestation) {
+            Some(permit) => permit,
+            None => {
+                return Box::new(future::ok(ValidatorEvent::IndexedAttestationNotProduced(
+                    slot,
+                )))
             }
-        } else {
-            Ok(ValidatorEvent::IndexedAttestationNotProduced(
-                self.duty.slot,
-            ))
-        }
+        };
+        let slashing_protection = self.slashing_protection.clone();
+
+        let epoch = slot.epoch(self.slots_per_epoch);
+        let domain = self.spec.get_domain(epoch, Domain::Attestation, &self.fork);
+        let beacon_node = self.beacon_node.clone();
+        let dry_run = self.dry_run;
+
+        Box::new(self.sign_attestation(attestation, self.duty, domain).then(
+            move |attestation| -> EventFuture {
+                if attestation.is_ok() {
+                    slashing_protection.commit(permit);
+                }
+                let attestation = match attestation {
+                    Ok(_) if dry_run => {
+                        return Box::new(future::ok(ValidatorEvent::AttestationNotPublished(slot)))
+                    }
+                    Ok(attestation) => attestation,
+                    Err(()) => return Box::new(future::ok(ValidatorEvent::SignerRejection(slot))),
+                };
+
+                Box::new(beacon_node.publish_attestation(attestation).then(
+                    move |outcome| -> Result<ValidatorEvent, Error> {
+                        match outcome {
+                            Ok(PublishOutcome::InvalidAttestation(_string)) => {
+                                Ok(ValidatorEvent::InvalidAttestation)
+                            }
+                            Ok(PublishOutcome::Valid) => {
+                                Ok(ValidatorEvent::AttestationProduced(slot))
+                            }
+                            Err(_) | Ok(_) => Ok(ValidatorEvent::PublishAttestationFailed),
+                        }
+                    },
+                ))
+            },
+        ))
     }
 
     /// Consumes an attestation, returning the attestation signed by the validators private key.
     ///
+    /// In a dry run a throwaway key signs instead of the validator, so that the validator never
+    /// signs an attestation which is not published.
+    ///
     /// Important: this function will not check to ensure the attestation is not slashable. This must be
     /// done upstream.
     fn sign_attestation(
-        &mut self,
+        &self,
         attestation: AttestationData,
         duties: AttestationDuty,
         domain: u64,
-    ) -> Option<Attestation<E>> {
-        self.store_produce(&attestation);
-
-        // build the aggregate signature
-        let aggregate_signature = {
-            let message = AttestationDataAndCustodyBit {
-                data: attestation.clone(),
-                custody_bit: false,
-            }
-            .tree_hash_root();
+    ) -> Box<dyn Future<Item = Attestation<E>, Error = ()> + Send> {
+        type Bits<T> = BitList<<T as EthSpec>::MaxValidatorsPerCommittee>;
+        let bits = || -> Option<(Bits<E>, Bits<E>)> {
+            let mut aggregation_bits = BitList::with_capacity(duties.committee_len).ok()?;
+            let custody_bits = BitList::with_capacity(duties.committee_len).ok()?;
+            aggregation_bits.set(duties.committee_index, true).ok()?;
+            Some((aggregation_bits, custody_bits))
+        };
+        let (aggregation_bits, custody_bits) = match bits() {
+            Some(bits) => bits,
+            None => return Box::new(future::err(())),
+        };
 
-            let sig = self.signer.sign_message(&message, domain)?;
+        let message = AttestationDataAndCustodyBit {
+            data: attestation.clone(),
+            custody_bit: false,
+        }
+        .tree_hash_root();
 
-            let mut agg_sig = AggregateSignature::new();
-            agg_sig.add(&sig);
-            agg_sig
+        let signature = if self.dry_run {
+            Keypair::random().sign_message(&message, domain)
+        } else {
+            self.signer.sign_message(&message, domain)
         };
 
-        let mut aggregation_bits = BitList::with_capacity(duties.committee_len).ok()?;
-        let custody_bits = BitList::with_capacity(duties.committee_len).ok()?;
-        aggregation_bits.set(duties.committee_index, true).ok()?;
+        Box::new(signature.map(move |sig| {
+            // build the aggregate signature
+            let mut signature = AggregateSignature::new();
+            signature.add(&sig);
 
-        Some(Attestation {
-            aggregation_bits,
-            data: attestation,
-            custody_bits,
-            signature: aggregate_signature,
-        })
-    }
-
-    /// Returns `true` if signing an attestation is safe (non-slashable).
-    ///
-    /// !!! UNSAFE !!!
-    ///
-    /// Important: this function is presently stubbed-out. It provides ZERO SAFETY.
-    fn safe_to_produce(&self, _attestation: &AttestationData) -> bool {
-        //TODO: Implement slash protection
-        true
+            Attestation {
+                aggregation_bits,
+                data: attestation,
+                custody_bits,
+                signature,
+            }
+        }))
     }
 
-    /// Record that an attestation was produced so that slashable votes may not be made in the future.
-    ///
-    /// !!! UNSAFE !!!
+    /// Returns permission to sign an attestation if it is safe (non-slashable), or `None`
+    /// otherwise.
     ///
-    /// Important: this function is presently stubbed-out. It provides ZERO SAFETY.
-    fn store_produce(&mut self, _attestation: &AttestationData) {
-        // TODO: Implement slash protection
+    /// If the attestation is safe it is recorded in `self.slashing_protection` before the permit
+    /// is returned, so that conflicting attestations may not be signed in the future.
+    fn safe_to_produce(&self, attestation: &AttestationData) -> Option<SigningPermit> {
+        self.slashing_protection
+            .begin_sign_attestation(&self.signer.to_public(), attestation)
+            .ok()
     }
 }
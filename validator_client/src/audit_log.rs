@@ -0,0 +1,426 @@
+//! An append-only, hash-chained log of every message a validator has been asked to sign.
+//!
+//! Each validator has its own log file at
+//! `<datadir>/<network>/validators/<validator_id>/audit.log`. Every line of the file is a
+//! JSON-encoded `AuditLogEntry`. The `hash` of each entry commits to the entry contents and the
+//! `hash` of the previous entry; any modification, re-ordering or removal of entries (other than
+//! truncating the tail) is detected by `verify`.
+//!
+//! A crash whilst appending may leave a partially written final line, without a trailing newline.
+//! It is not an entry, so it is ignored by `verify` and removed when the log is next opened.
+//!
+//! The log is intended for post-incident investigations (e.g., "did this validator ever sign a
+//! conflicting block?"). It is not used for slashing protection.
+use crate::signer::{SignatureFuture, Signer};
+use eth2_hashing::hash;
+use futures::{future, Async, Future};
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use types::PublicKey;
+
+/// The name of the audit log file within each validator directory.
+pub const AUDIT_LOG_FILENAME: &str = "audit.log";
+
+/// The `previous_hash` of the first entry in every log.
+const GENESIS_HASH: [u8; 32] = [0; 32];
+
+#[derive(Debug, PartialEq, Error)]
+pub enum AuditLogError {
+    /// Unable to read or write the log file.
+    #[error("unable to read or write the audit log: {0}")]
+    Io(String),
+    /// The line at `line` (1-indexed) could not be parsed.
+    #[error("invalid entry at line {line}: {error}")]
+    InvalidEntry { line: usize, error: String },
+    /// The entry at the given index does not have the expected index.
+    #[error("expected entry {expected}, found entry {found}")]
+    UnexpectedIndex { expected: u64, found: u64 },
+    /// The entry at the given index does not reference the hash of the previous entry.
+    #[error("entry {index} does not reference the previous entry")]
+    BrokenChain { index: u64 },
+    /// The hash of the entry at the given index does not match its contents.
+    #[error("entry {index} does not match its hash")]
+    InvalidHash { index: u64 },
+    /// The audit log lock was poisoned.
+    #[error("the audit log lock was poisoned")]
+    LockPoisoned,
+}
+
+impl From<std::io::Error> for AuditLogError {
+    fn from(e: std::io::Error) -> AuditLogError {
+        AuditLogError::Io(format!("{:?}", e))
+    }
+}
+
+/// A single record in the audit log.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct AuditLogEntry {
+    /// The position of this entry in the log, starting at zero.
+    pub index: u64,
+    /// Seconds since the UNIX epoch at which the signing request was received.
+    pub timestamp: u64,
+    /// The signature domain.
+    pub domain: u64,
+    /// Hex-encoded message that was to be signed.
+    pub message: String,
+    /// Hex-encoded hash of the previous entry.
+    pub previous_hash: String,
+    /// Hex-encoded hash of this entry.
+    pub hash: String,
+}
+
+impl AuditLogEntry {
+    /// Create a new entry, computing its hash.
+    fn new(index: u64, timestamp: u64, domain: u64, message: &[u8], previous_hash: &[u8]) -> Self {
+        Self {
+            index,
+            timestamp,
+            domain,
+            message: hex::encode(message),
+            previous_hash: hex::encode(previous_hash),
+            hash: hex::encode(Self::compute_hash(
+                index,
+                timestamp,
+                domain,
+                message,
+                previous_hash,
+            )),
+        }
+    }
+
+    /// Returns `hash(index || timestamp || domain || previous_hash || message)`, where integers
+    /// are little-endian encoded.
+    fn compute_hash(
+        index: u64,
+        timestamp: u64,
+        domain: u64,
+        message: &[u8],
+        previous_hash: &[u8],
+    ) -> Vec<u8> {
+        let mut preimage = Vec::with_capacity(24 + previous_hash.len() + message.len());
+        preimage.extend_from_slice(&index.to_le_bytes());
+        preimage.extend_from_slice(&timestamp.to_le_bytes());
+        preimage.extend_from_slice(&domain.to_le_bytes());
+        preimage.extend_from_slice(previous_hash);
+        preimage.extend_from_slice(message);
+        hash(&preimage)
+    }
+
+    /// Returns `true` if the `hash` field matches the contents of the entry.
+    fn hash_is_valid(&self) -> bool {
+        match (hex::decode(&self.message), hex::decode(&self.previous_hash)) {
+            (Ok(message), Ok(previous_hash)) => {
+                hex::encode(Self::compute_hash(
+                    self.index,
+                    self.timestamp,
+                    self.domain,
+                    &message,
+                    &previous_hash,
+                )) == self.hash
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for AuditLogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // The domain is the little-endian concatenation of the domain type and fork version.
+        let domain_bytes = self.domain.to_le_bytes();
+        write!(
+            f,
+            "#{} time: {}, domain type: 0x{}, fork version: 0x{}, message: 0x{}, hash: 0x{}",
+            self.index,
+            self.timestamp,
+            hex::encode(&domain_bytes[0..4]),
+            hex::encode(&domain_bytes[4..8]),
+            self.message,
+            self.hash
+        )
+    }
+}
+
+/// An open, append-only audit log file.
+pub struct AuditLog {
+    file: File,
+    next_index: u64,
+    previous_hash: Vec<u8>,
+}
+
+impl AuditLog {
+    /// Returns the path of the audit log for the validator with `validator_id` in
+    /// `validators_dir`.
+    pub fn path(validators_dir: &Path, validator_id: &str) -> PathBuf {
+        validators_dir.join(validator_id).join(AUDIT_LOG_FILENAME)
+    }
+
+    /// Open the log at `path`, creating it if it does not exist.
+    ///
+    /// Returns an error if an existing log fails verification.
+    pub fn open(path: &Path) -> Result<Self, AuditLogError> {
+        let entries = if path.exists() {
+            let (contents, len) = read_complete_lines(path)?;
+            if contents.len() as u64 != len {
+                // Remove a partially written final line, so that the next entry is on a line of
+                // its own.
+                OpenOptions::new()
+                    .write(true)
+                    .open(path)?
+                    .set_len(contents.len() as u64)?;
+            }
+            verify_contents(&contents)?
+        } else {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            vec![]
+        };
+
+        let (next_index, previous_hash) = match entries.last() {
+            Some(last) => (
+                last.index + 1,
+                hex::decode(&last.hash).map_err(|e| AuditLogError::InvalidEntry {
+                    line: entries.len(),
+                    error: format!("{:?}", e),
+                })?,
+            ),
+            None => (0, GENESIS_HASH.to_vec()),
+        };
+
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(Self {
+            file,
+            next_index,
+            previous_hash,
+        })
+    }
+
+    /// Durably append a record of a request to sign `message` with `domain`.
+    pub fn append(&mut self, message: &[u8], domain: u64) -> Result<AuditLogEntry, AuditLogError> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let entry = AuditLogEntry::new(
+            self.next_index,
+            timestamp,
+            domain,
+            message,
+            &self.previous_hash,
+        );
+
+        let mut line = serde_json::to_string(&entry).map_err(|e| AuditLogError::InvalidEntry {
+            line: self.next_index as usize + 1,
+            error: format!("{:?}", e),
+        })?;
+        line.push('\n');
+        self.file.write_all(line.as_bytes())?;
+        self.file.sync_data()?;
+
+        self.next_index += 1;
+        self.previous_hash = hex::decode(&entry.hash).expect("hash was hex-encoded above");
+
+        Ok(entry)
+    }
+}
+
+/// Read and verify the audit log at `path`, returning all entries in order.
+///
+/// A partially written final line is ignored.
+pub fn verify(path: &Path) -> Result<Vec<AuditLogEntry>, AuditLogError> {
+    let (contents, _) = read_complete_lines(path)?;
+    verify_contents(&contents)
+}
+
+/// Reads the log at `path` up to the end of its last complete line, returning those contents and
+/// the length of the whole file.
+fn read_complete_lines(path: &Path) -> Result<(String, u64), AuditLogError> {
+    let mut bytes = fs::read(path)?;
+    let len = bytes.len() as u64;
+    let complete = bytes
+        .iter()
+        .rposition(|byte| *byte == b'\n')
+        .map_or(0, |i| i + 1);
+    bytes.truncate(complete);
+
+    let contents = String::from_utf8(bytes).map_err(|e| AuditLogError::Io(format!("{:?}", e)))?;
+    Ok((contents, len))
+}
+
+/// Verifies the complete lines of a log, returning all entries in order.
+fn verify_contents(contents: &str) -> Result<Vec<AuditLogEntry>, AuditLogError> {
+    let mut entries: Vec<AuditLogEntry> = vec![];
+    let mut previous_hash = hex::encode(GENESIS_HASH);
+
+    for (i, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let entry: AuditLogEntry =
+            serde_json::from_str(&line).map_err(|e| AuditLogError::InvalidEntry {
+                line: i + 1,
+                error: format!("{:?}", e),
+            })?;
+
+        let expected = entries.len() as u64;
+        if entry.index != expected {
+            return Err(AuditLogError::UnexpectedIndex {
+                expected,
+                found: entry.index,
+            });
+        }
+        if entry.previous_hash != previous_hash {
+            return Err(AuditLogError::BrokenChain { index: entry.index });
+        }
+        if !entry.hash_is_valid() {
+            return Err(AuditLogError::InvalidHash { index: entry.index });
+        }
+
+        previous_hash = entry.hash.clone();
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+/// A `Signer` which records every signing request in an `AuditLog` before signing.
+///
+/// If the request cannot be recorded, the message is not signed.
+#[derive(Clone)]
+pub struct AuditedSigner<S: Signer> {
+    signer: S,
+    audit_log: Arc<Mutex<AuditLog>>,
+}
+
+impl<S: Signer> AuditedSigner<S> {
+    /// Wrap `signer`, recording requests in the log at `path`.
+    pub fn new(signer: S, path: &Path) -> Result<Self, AuditLogError> {
+        Ok(Self {
+            signer,
+            audit_log: Arc::new(Mutex::new(AuditLog::open(path)?)),
+        })
+    }
+}
+
+impl<S: Signer + 'static> Signer for AuditedSigner<S> {
+    fn sign_message(&self, message: &[u8], domain: u64) -> SignatureFuture {
+        let audit_log = self.audit_log.clone();
+        let recorded_message = message.to_vec();
+        let mut record = move || {
+            audit_log
+                .lock()
+                .map_err(|_| AuditLogError::LockPoisoned)
+                .and_then(|mut audit_log| audit_log.append(&recorded_message, domain))
+        };
+        // Appending syncs the file, which blocks, so it must not hold up the executor.
+        let recorded = future::poll_fn(move || match tokio_threadpool::blocking(&mut record) {
+            Ok(Async::Ready(recorded)) => recorded.map(Async::Ready),
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            // Outside of a thread pool (e.g., in tests) there is no executor to hold up.
+            Err(_) => record().map(Async::Ready),
+        });
+
+        let signer = self.signer.clone();
+        let message = message.to_vec();
+        Box::new(
+            recorded
+                .map_err(|_| ())
+                .and_then(move |_| signer.sign_message(&message, domain)),
+        )
+    }
+
+    fn to_public(&self) -> PublicKey {
+        self.signer.to_public()
+    }
+}
+
+impl<S: Signer> fmt::Display for AuditedSigner<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.signer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn append_and_reopen() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("validator").join(AUDIT_LOG_FILENAME);
+
+        {
+            let mut log = AuditLog::open(&path).unwrap();
+            log.append(&[1, 2, 3], 42).unwrap();
+            log.append(&[4, 5, 6], 43).unwrap();
+        }
+
+        let mut log = AuditLog::open(&path).unwrap();
+        let entry = log.append(&[7], 44).unwrap();
+        assert_eq!(entry.index, 2);
+
+        let entries = verify(&path).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].previous_hash, hex::encode(GENESIS_HASH));
+        assert_eq!(entries[1].message, "040506");
+        assert_eq!(entries[2].previous_hash, entries[1].hash);
+    }
+
+    #[test]
+    fn tolerates_a_partially_written_entry() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(AUDIT_LOG_FILENAME);
+
+        {
+            let mut log = AuditLog::open(&path).unwrap();
+            log.append(&[1, 2, 3], 42).unwrap();
+        }
+
+        // A crash whilst appending the second entry leaves part of it.
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"index\":1,\"timestam").unwrap();
+        assert_eq!(verify(&path).unwrap().len(), 1);
+
+        let mut log = AuditLog::open(&path).unwrap();
+        assert_eq!(log.append(&[4, 5, 6], 43).unwrap().index, 1);
+
+        let entries = verify(&path).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].previous_hash, entries[0].hash);
+    }
+
+    #[test]
+    fn detects_tampering() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(AUDIT_LOG_FILENAME);
+
+        {
+            let mut log = AuditLog::open(&path).unwrap();
+            log.append(&[1, 2, 3], 42).unwrap();
+            log.append(&[4, 5, 6], 43).unwrap();
+        }
+
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::write(&path, contents.replace("040506", "040507")).unwrap();
+        assert_eq!(verify(&path), Err(AuditLogError::InvalidHash { index: 1 }));
+
+        let lines: Vec<&str> = contents.lines().skip(1).collect();
+        fs::write(&path, format!("{}\n", lines.join("\n"))).unwrap();
+        assert_eq!(
+            verify(&path),
+            Err(AuditLogError::UnexpectedIndex {
+                expected: 0,
+                found: 1
+            })
+        );
+    }
+}
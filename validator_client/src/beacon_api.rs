@@ -0,0 +1,464 @@
+//! A client for beacon nodes which expose the Eth2 beacon node HTTP API (REST/JSON), rather than
+//! the Lighthouse gRPC services.
+//!
+//! Only the endpoints required by the validator client are used:
+//!
+//! - `GET /node/version`, `GET /node/genesis_time` and `GET /node/fork`.
+//! - `GET /beacon/checkpoint_root?epoch=..`, if a weak subjectivity checkpoint is configured.
+//! - `GET /validator/duties?validator_pubkeys=..&epoch=..`.
+//! - `GET /validator/block?slot=..&randao_reveal=..` and `POST /validator/block`.
+//! - `GET /validator/attestation_data?slot=..&shard=..` and `POST /validator/attestation`.
+//!
+//! All objects are encoded as JSON, using the `serde` encoding of their `types` equivalents.
+//! Blocks are decoded as the block variant of the fork at their slot.
+//!
+//! Requests are made with the asynchronous `reqwest` client, so they must be polled on a runtime.
+//!
+//! Whether a beacon node serves these endpoints as expected may be checked with `api_conformance`.
+use crate::attestation_producer::{BeaconNodeAttestation, BlockAttestations};
+use crate::block_producer::{BeaconNodeBlock, BeaconNodeError, BeaconNodeFuture, PublishOutcome};
+use crate::duties::{
+    BeaconNodeDuties, BeaconNodeDutiesError, BeaconNodeDutiesFuture, DutiesResponse, EpochDuties,
+    EpochDuty, SubnetSubscription,
+};
+use crate::wss_checkpoint::CheckpointRoot;
+use futures::{future, Future};
+use reqwest::r#async::{Client, RequestBuilder};
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_derive::Deserialize;
+use ssz::Encode;
+use std::sync::Arc;
+use std::time::Duration;
+use types::{
+    Address, Attestation, AttestationData, AttestationDuty, BeaconBlockAny, ChainSpec, Epoch,
+    EthSpec, Fork, Hash256, PublicKey, Signature, Slot,
+};
+
+/// The maximum time to wait for a response to any request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The properties of a beacon node, as required to initialize the validator client.
+#[derive(Debug, PartialEq, Clone)]
+pub struct NodeInfo {
+    pub version: String,
+    pub genesis_time: u64,
+    pub chain_id: u64,
+    pub fork: Fork,
+    /// The root of the genesis block, or `None` if the beacon node does not report it.
+    pub genesis_root: Option<Hash256>,
+}
+
+/// The response to `GET /node/fork`.
+#[derive(Deserialize)]
+pub(crate) struct ForkResponse {
+    pub fork: Fork,
+    pub chain_id: u64,
+}
+
+/// An element of the response to `GET /validator/duties`.
+///
+/// The attestation fields are absent for validators which are not active in the epoch.
+#[derive(Deserialize)]
+pub(crate) struct ValidatorDuty {
+    pub validator_pubkey: PublicKey,
+    attestation_slot: Option<Slot>,
+    attestation_shard: Option<u64>,
+    /// The position of the validator in its attestation committee.
+    attestation_committee_index: Option<usize>,
+    /// The length of the validator's attestation committee.
+    attestation_committee_len: Option<usize>,
+    /// The first slot at which the validator proposes, for beacon nodes which do not return
+    /// `block_proposal_slots`.
+    block_proposal_slot: Option<Slot>,
+    #[serde(default)]
+    block_proposal_slots: Vec<Slot>,
+}
+
+impl ValidatorDuty {
+    /// Returns the `EpochDuty` of this validator, if it is active.
+    pub fn epoch_duty(&self) -> Option<EpochDuty> {
+        let block_production_slots = if self.block_proposal_slots.is_empty() {
+            self.block_proposal_slot.into_iter().collect()
+        } else {
+            self.block_proposal_slots.clone()
+        };
+        Some(EpochDuty {
+            block_production_slots,
+            attestation_duty: AttestationDuty {
+                slot: self.attestation_slot?,
+                shard: self.attestation_shard?,
+                committee_index: self.attestation_committee_index?,
+                committee_len: self.attestation_committee_len?,
+            },
+        })
+    }
+}
+
+/// The status and body of a response, uninterpreted.
+#[derive(Debug, PartialEq, Clone)]
+pub struct RawResponse {
+    pub status: StatusCode,
+    pub body: String,
+}
+
+/// A client for the beacon node HTTP API at some base URL (e.g., `http://localhost:5052`).
+pub struct BeaconApiClient {
+    client: Client,
+    url: String,
+    spec: Arc<ChainSpec>,
+}
+
+impl BeaconApiClient {
+    pub fn new(url: &str, spec: Arc<ChainSpec>) -> Result<Self, String> {
+        let client = Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| format!("Unable to build HTTP client: {:?}", e))?;
+
+        Ok(Self {
+            client,
+            url: url.trim_end_matches('/').to_string(),
+            spec,
+        })
+    }
+
+    /// Returns the base URL of the beacon node.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Requests the properties of the beacon node.
+    pub fn node_info(&self) -> BeaconNodeFuture<NodeInfo> {
+        Box::new(
+            self.get_json(self.get("/node/version"))
+                .join4(
+                    self.get_json(self.get("/node/genesis_time")),
+                    self.get_json(self.get("/node/fork")),
+                    self.get_json_opt(self.get("/node/genesis_root")),
+                )
+                .map(
+                    |(version, genesis_time, ForkResponse { fork, chain_id }, genesis_root)| {
+                        NodeInfo {
+                            version,
+                            genesis_time,
+                            chain_id,
+                            fork,
+                            genesis_root,
+                        }
+                    },
+                ),
+        )
+    }
+
+    /// Requests the canonical block root of the beacon node at the start of `epoch`.
+    pub fn checkpoint_root(&self, epoch: Epoch) -> BeaconNodeFuture<CheckpointRoot> {
+        self.get_json(
+            self.get("/beacon/checkpoint_root")
+                .query(&[("epoch", epoch.as_u64())]),
+        )
+    }
+
+    /// Requests `path` with `query`, resolving to the response whatever its status.
+    pub fn get_raw(&self, path: &str, query: &[(&str, String)]) -> BeaconNodeFuture<RawResponse> {
+        Self::send_raw(self.get(path).query(query))
+    }
+
+    /// Posts `body` as JSON to `path`, resolving to the response whatever its status.
+    pub fn post_raw<T: Serialize>(&self, path: &str, body: &T) -> BeaconNodeFuture<RawResponse> {
+        Self::send_raw(self.post(path).json(body))
+    }
+
+    fn send_raw(request: RequestBuilder) -> BeaconNodeFuture<RawResponse> {
+        Box::new(
+            request
+                .send()
+                .and_then(|mut response| {
+                    let status = response.status();
+                    response
+                        .text()
+                        .map(move |body| RawResponse { status, body })
+                })
+                .map_err(|e| BeaconNodeError::RemoteFailure(e.to_string())),
+        )
+    }
+
+    fn get(&self, path: &str) -> RequestBuilder {
+        self.client.get(&format!("{}{}", self.url, path))
+    }
+
+    fn post(&self, path: &str) -> RequestBuilder {
+        self.client.post(&format!("{}{}", self.url, path))
+    }
+
+    /// Sends `request` and decodes the JSON body of a successful response.
+    ///
+    /// Resolves to `None` if the beacon node responds with `404 Not Found`.
+    fn get_json_opt<T: DeserializeOwned + Send + 'static>(
+        &self,
+        request: RequestBuilder,
+    ) -> BeaconNodeFuture<Option<T>> {
+        Box::new(
+            request
+                .send()
+                .map_err(|e| BeaconNodeError::RemoteFailure(e.to_string()))
+                .and_then(|mut response| -> BeaconNodeFuture<Option<T>> {
+                    match response.status() {
+                        StatusCode::NOT_FOUND => Box::new(future::ok(None)),
+                        status if status.is_success() => Box::new(
+                            response
+                                .json()
+                                .map(Some)
+                                .map_err(|_| BeaconNodeError::DecodeFailure),
+                        ),
+                        status => Box::new(response.text().then(move |text| {
+                            Err(BeaconNodeError::RemoteFailure(format!(
+                                "Beacon node responded with {}: {}",
+                                status,
+                                text.unwrap_or_default()
+                            )))
+                        })),
+                    }
+                }),
+        )
+    }
+
+    /// Sends `request` and decodes the JSON body of the response, which must be successful.
+    fn get_json<T: DeserializeOwned + Send + 'static>(
+        &self,
+        request: RequestBuilder,
+    ) -> BeaconNodeFuture<T> {
+        Box::new(self.get_json_opt(request).and_then(|response| {
+            response.ok_or_else(|| {
+                BeaconNodeError::RemoteFailure(
+                    "Beacon node responded with 404 Not Found".to_string(),
+                )
+            })
+        }))
+    }
+
+    /// Sends `request`, which publishes an object, and interprets the status of the response.
+    ///
+    /// A `202 Accepted` response indicates the object was broadcast but could not be validated by
+    /// the beacon node, which is not considered a failure. A `400 Bad Request` indicates the
+    /// object is invalid, in which case the error is passed to `invalid`. A `503 Service
+    /// Unavailable` indicates the beacon node may be able to publish the object later.
+    fn publish(
+        &self,
+        request: RequestBuilder,
+        invalid: fn(String) -> PublishOutcome,
+    ) -> BeaconNodeFuture<PublishOutcome> {
+        Box::new(
+            request
+                .send()
+                .map_err(|e| BeaconNodeError::RemoteFailure(e.to_string()))
+                .and_then(move |mut response| -> BeaconNodeFuture<PublishOutcome> {
+                    match response.status() {
+                        StatusCode::OK | StatusCode::ACCEPTED => {
+                            Box::new(future::ok(PublishOutcome::Valid))
+                        }
+                        StatusCode::BAD_REQUEST => Box::new(
+                            response
+                                .text()
+                                .then(move |text| Ok(invalid(text.unwrap_or_default()))),
+                        ),
+                        StatusCode::SERVICE_UNAVAILABLE => Box::new(response.text().then(|text| {
+                            Ok(PublishOutcome::Unavailable(text.unwrap_or_default()))
+                        })),
+                        status => Box::new(response.text().then(move |text| {
+                            Err(BeaconNodeError::RemoteFailure(format!(
+                                "Beacon node responded with {}: {}",
+                                status,
+                                text.unwrap_or_default()
+                            )))
+                        })),
+                    }
+                }),
+        )
+    }
+}
+
+impl BeaconNodeDuties for BeaconApiClient {
+    /// Requests the duties of all `pub_keys` in `epoch`.
+    ///
+    /// Validators which are omitted from the response are considered inactive.
+    fn request_duties(
+        &self,
+        epoch: Epoch,
+        pub_keys: &[PublicKey],
+    ) -> BeaconNodeDutiesFuture<DutiesResponse> {
+        let mut query: Vec<(&str, String)> = pub_keys
+            .iter()
+            .map(|pub_key| ("validator_pubkeys", pub_key.as_hex_string()))
+            .collect();
+        query.push(("epoch", epoch.as_u64().to_string()));
+
+        let mut epoch_duties: EpochDuties = pub_keys.iter().map(|pk| (pk.clone(), None)).collect();
+        Box::new(
+            self.get_json(self.get("/validator/duties").query(&query))
+                .map_err(|e| BeaconNodeDutiesError::RemoteFailure(e.to_string()))
+                .map(move |duties: Vec<ValidatorDuty>| {
+                    for duty in duties {
+                        if let Some(entry) = epoch_duties.get_mut(&duty.validator_pubkey) {
+                            *entry = duty.epoch_duty();
+                        }
+                    }
+                    // The HTTP API does not report the dependent root of the duties, nor which
+                    // validators are slashed, nor the duties of the next epoch.
+                    DutiesResponse {
+                        duties: epoch_duties,
+                        dependent_root: None,
+                        slashed: vec![],
+                        next_epoch_duties: None,
+                    }
+                }),
+        )
+    }
+
+    /// The beacon node HTTP API has no means of registering fee recipients, so this is a no-op.
+    fn prepare_beacon_proposer(
+        &self,
+        _preparations: &[(PublicKey, Address)],
+    ) -> BeaconNodeDutiesFuture<()> {
+        Box::new(future::ok(()))
+    }
+
+    /// The beacon node HTTP API has no means of subscribing to attestation subnets, so this is a
+    /// no-op.
+    fn prepare_attestation_subnets(
+        &self,
+        _subscriptions: &[SubnetSubscription],
+    ) -> BeaconNodeDutiesFuture<()> {
+        Box::new(future::ok(()))
+    }
+}
+
+impl BeaconNodeBlock for BeaconApiClient {
+    /// Requests a block at `slot`.
+    ///
+    /// Resolves to `None` if the beacon node responds with `404 Not Found`. The `fee_recipient` is not
+    /// supported by the HTTP API and is ignored.
+    fn produce_beacon_block<T: EthSpec>(
+        &self,
+        slot: Slot,
+        randao_reveal: &Signature,
+        _fee_recipient: Option<Address>,
+    ) -> BeaconNodeFuture<Option<BeaconBlockAny<T>>> {
+        let variant = self.spec.block_variant(slot.epoch(T::slots_per_epoch()));
+        let request = self.get("/validator/block").query(&[
+            ("slot", slot.as_u64().to_string()),
+            (
+                "randao_reveal",
+                format!("0x{}", hex::encode(randao_reveal.as_ssz_bytes())),
+            ),
+        ]);
+
+        Box::new(
+            self.get_json_opt(request).and_then(
+                move |block: Option<serde_json::Value>| match block {
+                    Some(block) => BeaconBlockAny::deserialize_variant(block, variant)
+                        .map(Some)
+                        .map_err(|_| BeaconNodeError::DecodeFailure),
+                    None => Ok(None),
+                },
+            ),
+        )
+    }
+
+    fn publish_beacon_block<T: EthSpec>(
+        &self,
+        block: BeaconBlockAny<T>,
+    ) -> BeaconNodeFuture<PublishOutcome> {
+        self.publish(
+            self.post("/validator/block").json(&block),
+            PublishOutcome::InvalidBlock,
+        )
+    }
+}
+
+impl BeaconNodeAttestation for BeaconApiClient {
+    fn produce_attestation_data(
+        &self,
+        slot: Slot,
+        shard: u64,
+    ) -> BeaconNodeFuture<AttestationData> {
+        self.get_json(self.get("/validator/attestation_data").query(&[
+            ("slot", slot.as_u64().to_string()),
+            ("shard", shard.to_string()),
+        ]))
+    }
+
+    fn publish_attestation<T: EthSpec>(
+        &self,
+        attestation: Attestation<T>,
+    ) -> BeaconNodeFuture<PublishOutcome> {
+        self.publish(
+            self.post("/validator/attestation").json(&attestation),
+            PublishOutcome::InvalidAttestation,
+        )
+    }
+
+    /// The beacon node HTTP API has no means of querying the operation pool, so no attestation is
+    /// ever known.
+    fn attestation_known(
+        &self,
+        _slot: Slot,
+        _shard: u64,
+        _committee_index: usize,
+    ) -> BeaconNodeFuture<bool> {
+        Box::new(future::ok(false))
+    }
+
+    /// The beacon node HTTP API has no means of querying the attestations in a block.
+    fn block_attestations<T: EthSpec>(
+        &self,
+        _slot: Slot,
+    ) -> BeaconNodeFuture<Option<BlockAttestations<T>>> {
+        Box::new(future::err(BeaconNodeError::RemoteFailure(
+            "block attestations are not served by the HTTP API".to_string(),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use types::Keypair;
+
+    #[test]
+    fn inactive_validators_have_no_duty() {
+        let pub_key = Keypair::random().pk;
+        let json = |committee_index: &str| {
+            format!(
+                r#"{{
+                    "validator_pubkey": "{}",
+                    "attestation_slot": 9,
+                    "attestation_shard": 2,
+                    "attestation_committee_index": {},
+                    "attestation_committee_len": 4,
+                    "block_proposal_slot": null
+                }}"#,
+                pub_key.as_hex_string(),
+                committee_index
+            )
+        };
+
+        let active: ValidatorDuty = serde_json::from_str(&json("1")).expect("should decode");
+        assert_eq!(
+            active.epoch_duty(),
+            Some(EpochDuty {
+                block_production_slots: vec![],
+                attestation_duty: AttestationDuty {
+                    slot: Slot::new(9),
+                    shard: 2,
+                    committee_index: 1,
+                    committee_len: 4,
+                },
+            })
+        );
+
+        let inactive: ValidatorDuty = serde_json::from_str(&json("null")).expect("should decode");
+        assert_eq!(inactive.epoch_duty(), None);
+    }
+}
@@ -0,0 +1,234 @@
+//! The latency of producing and publishing blocks with each beacon node, and the timing of block
+//! production derived from it.
+//!
+//! The most recent latencies of each request to each beacon node are kept, and the expected
+//! latency is their 90th percentile. Given a target time in the slot by which blocks should be
+//! published:
+//!
+//! - Blocks are requested at the usual delay after the start of the slot, or earlier (but not
+//!   before the start of the slot) if the expected latency of the request would otherwise make
+//!   the block later than the target.
+//! - A block published after the target is logged as late.
+//! - If a cutoff is configured, a block which is expected to be published after it (given the
+//!   expected latency of publishing) is not signed, since it is unlikely to be included.
+use crate::block_producer::{BeaconNodeBlock, BeaconNodeFuture, PublishOutcome};
+use futures::Future;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use types::{Address, BeaconBlockAny, EthSpec, Signature, Slot};
+
+/// The time after the start of a slot by which blocks are published, unless configured.
+pub const DEFAULT_PUBLISH_TARGET_MS: u64 = 1_000;
+
+/// The number of latencies kept for each request to each beacon node.
+pub const DEFAULT_SAMPLES: usize = 32;
+
+/// The percentile of recent latencies which is expected.
+const PERCENTILE: f64 = 0.9;
+
+/// A request to a beacon node whose latency is measured.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Request {
+    Produce,
+    Publish,
+}
+
+/// The recent latencies of each request to each beacon node.
+pub struct BlockLatencies {
+    samples: usize,
+    latencies: Mutex<HashMap<(String, Request), VecDeque<Duration>>>,
+}
+
+impl BlockLatencies {
+    /// Create latencies which keep the `samples` most recent of each request to each node.
+    pub fn new(samples: usize) -> Self {
+        Self {
+            samples: samples.max(1),
+            latencies: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Records that `request` to the beacon node at `endpoint` took `latency`.
+    pub fn record(&self, endpoint: &str, request: Request, latency: Duration) {
+        if let Ok(mut latencies) = self.latencies.lock() {
+            let recent = latencies
+                .entry((endpoint.to_string(), request))
+                .or_insert_with(VecDeque::new);
+            if recent.len() >= self.samples {
+                recent.pop_front();
+            }
+            recent.push_back(latency);
+        }
+    }
+
+    /// Returns the expected latency of `request` to the beacon node at `endpoint`, or `None` if
+    /// it has not been measured.
+    pub fn expected(&self, endpoint: &str, request: Request) -> Option<Duration> {
+        let latencies = self.latencies.lock().ok()?;
+        let mut recent: Vec<Duration> = latencies
+            .get(&(endpoint.to_string(), request))?
+            .iter()
+            .cloned()
+            .collect();
+        recent.sort();
+        let index = ((recent.len() - 1) as f64 * PERCENTILE).ceil() as usize;
+        recent.get(index).cloned()
+    }
+}
+
+impl Default for BlockLatencies {
+    /// Latencies which keep the `DEFAULT_SAMPLES` most recent of each request.
+    fn default() -> Self {
+        Self::new(DEFAULT_SAMPLES)
+    }
+}
+
+/// When blocks should be published, relative to the start of their slot.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct PublishTarget {
+    /// Blocks published after this are logged as late.
+    pub target: Duration,
+    /// Blocks expected to be published after this are not signed, if set.
+    pub cutoff: Option<Duration>,
+}
+
+impl PublishTarget {
+    /// Returns the delay after the start of a slot at which to request its blocks, which is at
+    /// most `delay`.
+    pub fn request_delay(&self, delay: Duration, expected_produce: Option<Duration>) -> Duration {
+        match expected_produce {
+            Some(latency) => {
+                std::cmp::min(delay, self.target.checked_sub(latency).unwrap_or_default())
+            }
+            None => delay,
+        }
+    }
+
+    /// Returns the publish window of the slot which started at `slot_start`.
+    pub fn window(&self, slot_start: Instant, expected_publish: Option<Duration>) -> PublishWindow {
+        let latency = expected_publish.unwrap_or_default();
+        PublishWindow {
+            target: Some(slot_start + self.target),
+            sign_by: self
+                .cutoff
+                .map(|cutoff| slot_start + cutoff.checked_sub(latency).unwrap_or_default()),
+        }
+    }
+}
+
+/// When the block of a single slot should be published.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct PublishWindow {
+    /// Blocks published after this are logged as late, if set.
+    pub target: Option<Instant>,
+    /// Blocks are not signed after this, if set, since they are expected to be published too
+    /// late to be included.
+    pub sign_by: Option<Instant>,
+}
+
+impl PublishWindow {
+    /// Returns `true` if a block published at `now` is late.
+    pub fn is_late(&self, now: Instant) -> bool {
+        self.target.map_or(false, |target| now > target)
+    }
+
+    /// Returns `true` if a block should not be signed at `now`.
+    pub fn too_late_to_sign(&self, now: Instant) -> bool {
+        self.sign_by.map_or(false, |sign_by| now > sign_by)
+    }
+}
+
+/// A beacon node whose block requests are timed, and recorded in `latencies`.
+pub struct Timed<N> {
+    node: Arc<N>,
+    endpoint: String,
+    latencies: Arc<BlockLatencies>,
+}
+
+impl<N> Timed<N> {
+    pub fn new(node: Arc<N>, endpoint: String, latencies: Arc<BlockLatencies>) -> Self {
+        Self {
+            node,
+            endpoint,
+            latencies,
+        }
+    }
+}
+
+impl<N: BeaconNodeBlock> BeaconNodeBlock for Timed<N> {
+    fn produce_beacon_block<T: EthSpec>(
+        &self,
+        slot: Slot,
+        randao_reveal: &Signature,
+        fee_recipient: Option<Address>,
+    ) -> BeaconNodeFuture<Option<BeaconBlockAny<T>>> {
+        let started = Instant::now();
+        let endpoint = self.endpoint.clone();
+        let latencies = self.latencies.clone();
+        Box::new(
+            self.node
+                .produce_beacon_block(slot, randao_reveal, fee_recipient)
+                .map(move |block| {
+                    latencies.record(&endpoint, Request::Produce, started.elapsed());
+                    block
+                }),
+        )
+    }
+
+    fn publish_beacon_block<T: EthSpec>(
+        &self,
+        block: BeaconBlockAny<T>,
+    ) -> BeaconNodeFuture<PublishOutcome> {
+        let started = Instant::now();
+        let endpoint = self.endpoint.clone();
+        let latencies = self.latencies.clone();
+        Box::new(self.node.publish_beacon_block(block).map(move |outcome| {
+            latencies.record(&endpoint, Request::Publish, started.elapsed());
+            outcome
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_early_enough_to_publish_by_the_target() {
+        let latencies = BlockLatencies::new(10);
+        assert_eq!(latencies.expected("node", Request::Produce), None);
+        for i in 1..=20 {
+            latencies.record("node", Request::Produce, Duration::from_millis(i * 100));
+        }
+        // Only the 10 most recent are kept, of which the 90th percentile is expected.
+        assert_eq!(
+            latencies.expected("node", Request::Produce),
+            Some(Duration::from_millis(2_000))
+        );
+        assert_eq!(latencies.expected("other", Request::Produce), None);
+
+        let target = PublishTarget {
+            target: Duration::from_millis(1_000),
+            cutoff: Some(Duration::from_millis(4_000)),
+        };
+        let delay = Duration::from_millis(100);
+        assert_eq!(target.request_delay(delay, None), delay);
+        assert_eq!(
+            target.request_delay(delay, Some(Duration::from_millis(950))),
+            Duration::from_millis(50)
+        );
+        assert_eq!(
+            target.request_delay(delay, Some(Duration::from_millis(2_000))),
+            Duration::from_millis(0)
+        );
+
+        let slot_start = Instant::now();
+        let window = target.window(slot_start, Some(Duration::from_millis(1_500)));
+        assert!(!window.is_late(slot_start + Duration::from_millis(900)));
+        assert!(window.is_late(slot_start + Duration::from_millis(1_100)));
+        assert!(!window.too_late_to_sign(slot_start + Duration::from_millis(2_400)));
+        assert!(window.too_late_to_sign(slot_start + Duration::from_millis(2_600)));
+        assert!(!PublishWindow::default().too_late_to_sign(slot_start + Duration::from_secs(60)));
+    }
+}
@@ -1,34 +1,84 @@
-use types::{BeaconBlock, EthSpec, Signature, Slot};
-#[derive(Debug, PartialEq, Clone)]
+use futures::Future;
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
+use types::{
+    Address, BeaconBlockAny, BeaconBlockHeader, EthSpec, Signature, Slot, ValidatorRegistration,
+};
+
+#[derive(Debug, PartialEq, Clone, Error, Serialize, Deserialize)]
 pub enum BeaconNodeError {
+    #[error("beacon node request failed: {0}")]
     RemoteFailure(String),
+    #[error("unable to decode the beacon node response")]
     DecodeFailure,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum PublishOutcome {
     Valid,
     InvalidBlock(String),
     InvalidAttestation(String),
+    /// The beacon node was unable to publish the object for a reason which may be transient
+    /// (e.g., the parent block is not yet known), so publishing may be retried.
+    Unavailable(String),
 }
 
+/// A future which resolves to the response of a Beacon Node.
+pub type BeaconNodeFuture<T> = Box<dyn Future<Item = T, Error = BeaconNodeError> + Send>;
+
 /// Defines the methods required to produce and publish blocks on a Beacon Node. Abstracts the
 /// actual beacon node.
+///
+/// All requests are asynchronous, so that no thread is blocked whilst awaiting a response.
 pub trait BeaconNodeBlock: Send + Sync {
-    /// Request that the node produces a block.
+    /// Request that the node produces a block, paying fees to `fee_recipient` if supplied.
     ///
-    /// Returns Ok(None) if the Beacon Node is unable to produce at the given slot.
+    /// The block is of the variant of the fork at `slot` (see `ChainSpec::block_variant`).
+    /// Resolves to `None` if the Beacon Node is unable to produce at the given slot.
     fn produce_beacon_block<T: EthSpec>(
         &self,
         slot: Slot,
         randao_reveal: &Signature,
-    ) -> Result<Option<BeaconBlock<T>>, BeaconNodeError>;
+        fee_recipient: Option<Address>,
+    ) -> BeaconNodeFuture<Option<BeaconBlockAny<T>>>;
 
     /// Request that the node publishes a block.
     ///
-    /// Returns `true` if the publish was successful.
+    /// Resolves to `PublishOutcome::Valid` once the block has been imported into fork choice and
+    /// broadcast on gossip.
     fn publish_beacon_block<T: EthSpec>(
         &self,
-        block: BeaconBlock<T>,
-    ) -> Result<PublishOutcome, BeaconNodeError>;
+        block: BeaconBlockAny<T>,
+    ) -> BeaconNodeFuture<PublishOutcome>;
+}
+
+/// Defines the methods required to produce and submit blinded blocks via an external block
+/// builder.
+///
+/// A blinded block is a `BeaconBlockHeader`; it commits to the block body by its root only. Since
+/// the signed root of a header is equal to that of its block, signing the header is equivalent to
+/// signing the full block.
+pub trait BeaconNodeBlindedBlock: Send + Sync {
+    /// Request that the builder produces a blinded block, paying fees to `fee_recipient` if
+    /// supplied.
+    ///
+    /// Resolves to `None` if the builder is unable to produce at the given slot.
+    fn produce_blinded_beacon_block(
+        &self,
+        slot: Slot,
+        randao_reveal: &Signature,
+        fee_recipient: Option<Address>,
+    ) -> BeaconNodeFuture<Option<BeaconBlockHeader>>;
+
+    /// Submit a signed blinded block, which the builder reveals and publishes as a full block.
+    fn submit_blinded_block(&self, header: BeaconBlockHeader) -> BeaconNodeFuture<PublishOutcome>;
+
+    /// Register the signed proposal preferences of validators with the builder.
+    ///
+    /// Builders only retain the latest registration of each validator, so registering again is
+    /// harmless.
+    fn register_validators(
+        &self,
+        registrations: Vec<ValidatorRegistration>,
+    ) -> BeaconNodeFuture<()>;
 }
@@ -0,0 +1,105 @@
+use super::beacon_node_block::*;
+use futures::{future, Future};
+use protos::convert::{FromProto, ToProto};
+use protos::services::{
+    ProduceBeaconBlockRequest, RegisterValidatorsRequest, SubmitBlindedBeaconBlockRequest,
+};
+use protos::services_grpc::BlockBuilderServiceClient;
+use ssz::Encode;
+use std::sync::Arc;
+use types::{Address, BeaconBlockHeader, Signature, Slot, ValidatorRegistration};
+
+/// A newtype designed to wrap the gRPC-generated builder service so the `BeaconNodeBlindedBlock`
+/// trait may be implemented upon it.
+pub struct BlockBuilderGrpcClient {
+    client: Arc<BlockBuilderServiceClient>,
+}
+
+impl BlockBuilderGrpcClient {
+    pub fn new(client: Arc<BlockBuilderServiceClient>) -> Self {
+        Self { client }
+    }
+}
+
+impl BeaconNodeBlindedBlock for BlockBuilderGrpcClient {
+    /// Request a block builder to produce a new blinded block at the supplied slot.
+    ///
+    /// Resolves to `None` if the builder did not return a block.
+    fn produce_blinded_beacon_block(
+        &self,
+        slot: Slot,
+        randao_reveal: &Signature,
+        fee_recipient: Option<Address>,
+    ) -> BeaconNodeFuture<Option<BeaconBlockHeader>> {
+        let mut req = ProduceBeaconBlockRequest::new();
+        req.set_slot(slot.as_u64());
+        req.set_randao_reveal(randao_reveal.as_ssz_bytes());
+        if let Some(fee_recipient) = fee_recipient {
+            req.set_fee_recipient(fee_recipient.as_bytes().to_vec());
+        }
+
+        Box::new(
+            future::result(self.client.produce_blinded_beacon_block_async(&req))
+                .flatten()
+                .map_err(|err| BeaconNodeError::RemoteFailure(format!("{:?}", err)))
+                .and_then(
+                    |reply| -> Result<Option<BeaconBlockHeader>, BeaconNodeError> {
+                        if reply.has_header() {
+                            let header = BeaconBlockHeader::from_proto(reply.get_header())
+                                .map_err(|_| BeaconNodeError::DecodeFailure)?;
+
+                            Ok(Some(header))
+                        } else {
+                            Ok(None)
+                        }
+                    },
+                ),
+        )
+    }
+
+    /// Submit a signed blinded block to the builder for publication.
+    fn submit_blinded_block(&self, header: BeaconBlockHeader) -> BeaconNodeFuture<PublishOutcome> {
+        let mut req = SubmitBlindedBeaconBlockRequest::new();
+        req.set_header(header.to_proto());
+
+        Box::new(
+            future::result(self.client.submit_blinded_beacon_block_async(&req))
+                .flatten()
+                .map_err(|err| BeaconNodeError::RemoteFailure(format!("{:?}", err)))
+                .map(|reply| {
+                    let msg = String::from_utf8_lossy(reply.get_msg()).into_owned();
+                    if reply.get_success() {
+                        PublishOutcome::Valid
+                    } else if reply.get_retryable() {
+                        PublishOutcome::Unavailable(msg)
+                    } else {
+                        PublishOutcome::InvalidBlock(msg)
+                    }
+                }),
+        )
+    }
+
+    /// Register the signed proposal preferences of validators with the builder.
+    fn register_validators(
+        &self,
+        registrations: Vec<ValidatorRegistration>,
+    ) -> BeaconNodeFuture<()> {
+        let mut req = RegisterValidatorsRequest::new();
+        req.set_registrations(registrations.iter().map(ToProto::to_proto).collect());
+
+        Box::new(
+            future::result(self.client.register_validators_async(&req))
+                .flatten()
+                .map_err(|err| BeaconNodeError::RemoteFailure(format!("{:?}", err)))
+                .and_then(|reply| {
+                    if reply.get_success() {
+                        Ok(())
+                    } else {
+                        Err(BeaconNodeError::RemoteFailure(
+                            String::from_utf8_lossy(reply.get_msg()).into_owned(),
+                        ))
+                    }
+                }),
+        )
+    }
+}
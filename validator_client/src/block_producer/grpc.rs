@@ -1,58 +1,76 @@
 use super::beacon_node_block::*;
-use protos::services::{
-    BeaconBlock as GrpcBeaconBlock, ProduceBeaconBlockRequest, PublishBeaconBlockRequest,
-};
+use futures::{future, Future};
+use protos::auth::Authenticated;
+use protos::convert::{self, ToProto};
+use protos::services::{ProduceBeaconBlockRequest, PublishBeaconBlockRequest};
 use protos::services_grpc::BeaconBlockServiceClient;
-use ssz::{Decode, Encode};
+use ssz::Encode;
 use std::sync::Arc;
-use types::{BeaconBlock, EthSpec, Signature, Slot};
+use types::{Address, BeaconBlockAny, ChainSpec, EthSpec, Signature, Slot};
 
 //TODO: Remove this new type. Do not need to wrap
 /// A newtype designed to wrap the gRPC-generated service so the `BeaconNode` trait may be
 /// implemented upon it.
+///
+/// Blocks are exchanged as SSZ, and refused unless they are of the block variant of the fork at
+/// their slot in `spec` (see `protos::convert`).
 pub struct BeaconBlockGrpcClient {
-    client: Arc<BeaconBlockServiceClient>,
+    client: Arc<Authenticated<BeaconBlockServiceClient>>,
+    spec: Arc<ChainSpec>,
 }
 
 impl BeaconBlockGrpcClient {
-    pub fn new(client: Arc<BeaconBlockServiceClient>) -> Self {
-        Self { client }
+    pub fn new(client: Arc<Authenticated<BeaconBlockServiceClient>>, spec: Arc<ChainSpec>) -> Self {
+        Self { client, spec }
     }
 }
 
 impl BeaconNodeBlock for BeaconBlockGrpcClient {
     /// Request a Beacon Node (BN) to produce a new block at the supplied slot.
     ///
-    /// Returns `None` if it is not possible to produce at the supplied slot. For example, if the
-    /// BN is unable to find a parent block.
+    /// Resolves to `None` if it is not possible to produce at the supplied slot. For example, if
+    /// the BN is unable to find a parent block.
     fn produce_beacon_block<T: EthSpec>(
         &self,
         slot: Slot,
         randao_reveal: &Signature,
-    ) -> Result<Option<BeaconBlock<T>>, BeaconNodeError> {
+        fee_recipient: Option<Address>,
+    ) -> BeaconNodeFuture<Option<BeaconBlockAny<T>>> {
         // request a beacon block from the node
         let mut req = ProduceBeaconBlockRequest::new();
         req.set_slot(slot.as_u64());
         req.set_randao_reveal(randao_reveal.as_ssz_bytes());
+        if let Some(fee_recipient) = fee_recipient {
+            req.set_fee_recipient(fee_recipient.as_bytes().to_vec());
+        }
+
+        let spec = self.spec.clone();
 
         //TODO: Determine if we want an explicit timeout
-        let reply = self
+        let call = self
             .client
-            .produce_beacon_block(&req)
-            .map_err(|err| BeaconNodeError::RemoteFailure(format!("{:?}", err)))?;
-
-        // format the reply
-        if reply.has_block() {
-            let block = reply.get_block();
-            let ssz = block.get_ssz();
+            .client()
+            .produce_beacon_block_async_opt(&req, self.client.call_option());
+        Box::new(
+            future::result(call)
+                .flatten()
+                .map_err(|err| BeaconNodeError::RemoteFailure(format!("{:?}", err)))
+                .and_then(
+                    move |reply| -> Result<Option<BeaconBlockAny<T>>, BeaconNodeError> {
+                        // format the reply
+                        if reply.has_block() {
+                            let block = convert::block_from_proto(reply.get_block(), |slot| {
+                                spec.block_variant(slot.epoch(T::slots_per_epoch()))
+                            })
+                            .map_err(|_| BeaconNodeError::DecodeFailure)?;
 
-            let block =
-                BeaconBlock::from_ssz_bytes(&ssz).map_err(|_| BeaconNodeError::DecodeFailure)?;
-
-            Ok(Some(block))
-        } else {
-            Ok(None)
-        }
+                            Ok(Some(block))
+                        } else {
+                            Ok(None)
+                        }
+                    },
+                ),
+        )
     }
 
     /// Request a Beacon Node (BN) to publish a block.
@@ -61,27 +79,29 @@ impl BeaconNodeBlock for BeaconBlockGrpcClient {
     /// been completed (signed) by the validator client.
     fn publish_beacon_block<T: EthSpec>(
         &self,
-        block: BeaconBlock<T>,
-    ) -> Result<PublishOutcome, BeaconNodeError> {
+        block: BeaconBlockAny<T>,
+    ) -> BeaconNodeFuture<PublishOutcome> {
         let mut req = PublishBeaconBlockRequest::new();
+        req.set_block(block.to_proto());
 
-        let ssz = block.as_ssz_bytes();
-
-        let mut grpc_block = GrpcBeaconBlock::new();
-        grpc_block.set_ssz(ssz);
-
-        req.set_block(grpc_block);
-
-        let reply = self
+        let call = self
             .client
-            .publish_beacon_block(&req)
-            .map_err(|err| BeaconNodeError::RemoteFailure(format!("{:?}", err)))?;
-
-        if reply.get_success() {
-            Ok(PublishOutcome::Valid)
-        } else {
-            // TODO: distinguish between different errors
-            Ok(PublishOutcome::InvalidBlock("Publish failed".to_string()))
-        }
+            .client()
+            .publish_beacon_block_async_opt(&req, self.client.call_option());
+        Box::new(
+            future::result(call)
+                .flatten()
+                .map_err(|err| BeaconNodeError::RemoteFailure(format!("{:?}", err)))
+                .map(|reply| {
+                    let msg = String::from_utf8_lossy(reply.get_msg()).into_owned();
+                    if reply.get_success() {
+                        PublishOutcome::Valid
+                    } else if reply.get_retryable() {
+                        PublishOutcome::Unavailable(msg)
+                    } else {
+                        PublishOutcome::InvalidBlock(msg)
+                    }
+                }),
+        )
     }
 }
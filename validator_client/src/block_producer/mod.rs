@@ -1,19 +1,75 @@
 mod beacon_node_block;
+mod builder_grpc;
 mod grpc;
+pub mod pool;
+pub mod replay;
+pub mod test_utils;
 
-use self::beacon_node_block::BeaconNodeBlock;
-pub use self::beacon_node_block::{BeaconNodeError, PublishOutcome};
+pub use self::beacon_node_block::{
+    BeaconNodeBlindedBlock, BeaconNodeBlock, BeaconNodeError, BeaconNodeFuture, PublishOutcome,
+};
+pub use self::builder_grpc::BlockBuilderGrpcClient;
 pub use self::grpc::BeaconBlockGrpcClient;
-use crate::signer::Signer;
+use crate::block_latency::PublishWindow;
+use crate::notifier::{Event, Notifier};
+use crate::proposal_timing::{ProposalTimings, Stage};
+use crate::signer::{SignatureFuture, Signer};
+use crate::signing_lease::{Claim, SigningLease};
+use crate::slashing_protection::{SigningPermit, SlashingProtection};
 use core::marker::PhantomData;
+use futures::{future, Future};
 use slog::{error, info, warn};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::timer::Delay;
 use tree_hash::{SignedRoot, TreeHash};
-use types::{BeaconBlock, ChainSpec, Domain, EthSpec, Fork, Slot};
+use types::{
+    Address, BeaconBlockAny, BeaconBlockHeader, BlockVariant, ChainSpec, Domain, EthSpec, Fork,
+    Hash256, Keypair, Signature, Slot,
+};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Error)]
 pub enum Error {
-    BeaconNodeError(BeaconNodeError),
+    /// A request to the beacon node for the block at `slot` failed.
+    #[error("unable to produce the block at slot {slot}: {source}")]
+    BeaconNode {
+        slot: Slot,
+        #[source]
+        source: BeaconNodeError,
+    },
+}
+
+impl Error {
+    /// Returns a function which adds the slot of the block being produced to a `BeaconNodeError`.
+    pub fn beacon_node(slot: Slot) -> impl FnOnce(BeaconNodeError) -> Error {
+        move |source| Error::BeaconNode { slot, source }
+    }
+}
+
+/// A reason that a block produced by the beacon node is invalid and must not be signed.
+#[derive(Debug, PartialEq, Clone, Error)]
+pub enum BlockViolation {
+    /// The block is not at the slot which was requested.
+    #[error("block is at slot {block}, not the requested slot {requested}")]
+    WrongSlot { requested: Slot, block: Slot },
+    /// The block is not of the variant of the fork at the requested slot, so its signed root
+    /// would not be that of a block of the fork.
+    #[error("block is of variant {block:?}, not {expected:?} of the fork at the requested slot")]
+    WrongVariant {
+        expected: BlockVariant,
+        block: BlockVariant,
+    },
+    /// The parent root is zero, so the block does not build upon any block.
+    #[error("block has no known parent")]
+    UnknownParent,
+    /// The state root is zero, so the beacon node did not compute the post-state.
+    #[error("block has a zero state root")]
+    ZeroStateRoot,
+    /// The randao reveal differs from the one requested, so the block was not built for this
+    /// validator.
+    #[error("block has an incorrect randao reveal")]
+    IncorrectRandaoReveal,
 }
 
 #[derive(Debug, PartialEq)]
@@ -26,19 +82,48 @@ pub enum ValidatorEvent {
     SlashableBlockNotProduced(Slot),
     /// An attestation was not produced as it would have been slashable.
     IndexedAttestationNotProduced(Slot),
+    /// An attestation was not produced as the beacon node already knows one from the validator
+    /// (e.g., published by a redundant validator client).
+    AttestationAlreadyKnown(Slot),
     /// The Beacon Node was unable to produce a block at that slot.
     BeaconNodeUnableToProduceBlock(Slot),
-    /// The signer failed to sign the message.
+    /// The Beacon Node produced an invalid block, which was not signed.
+    InvalidBlockFromBeaconNode(Slot, BlockViolation),
+    /// The signer failed to sign the message, or returned a signature which does not verify.
     SignerRejection(Slot),
+    /// A block with this header was produced and signed in a dry run, but not published.
+    BlockNotPublished(BeaconBlockHeader),
+    /// An attestation was produced and signed in a dry run, but not published.
+    AttestationNotPublished(Slot),
+    /// A signed block could not be published before the end of its slot, for the given reason.
+    PublishBlockFailed(Slot, String),
+    /// The block was not signed, as it was expected to be published too late to be included.
+    BlockTooLate(Slot),
+    /// The message was not signed, as the given validator client holds the lease of the validator.
+    LeaseHeld(Slot, String),
+    /// The message was not signed, as the validator could not be claimed for the given reason.
+    LeaseUnavailable(Slot, String),
     /// Publishing an attestation failed.
     PublishAttestationFailed,
     /// Beacon node rejected the attestation.
     InvalidAttestation,
 }
 
+/// The time to wait before publishing a block again, after a transient failure.
+const PUBLISH_RETRY_DELAY: Duration = Duration::from_millis(250);
+
+/// A future which resolves to the outcome of producing a block.
+type EventFuture = Box<dyn Future<Item = ValidatorEvent, Error = Error> + Send>;
+
+/// A future which performs a duty and logs its outcome.
+pub type HandleFuture = Box<dyn Future<Item = (), Error = ()> + Send>;
+
 /// This struct contains the logic for requesting and signing beacon blocks for a validator. The
 /// validator can abstractly sign via the Signer trait object.
-pub struct BlockProducer<'a, B: BeaconNodeBlock, S: Signer, E: EthSpec> {
+///
+/// Production is asynchronous: each method returns a future which owns a copy of the producer, so
+/// that many blocks may be produced concurrently on a single runtime.
+pub struct BlockProducer<B: BeaconNodeBlock, S: Signer, E: EthSpec> {
     /// The current fork.
     pub fork: Fork,
     /// The current slot to produce a block for.
@@ -48,33 +133,191 @@ pub struct BlockProducer<'a, B: BeaconNodeBlock, S: Signer, E: EthSpec> {
     /// The beacon node to connect to.
     pub beacon_node: Arc<B>,
     /// The signer to sign the block.
-    pub signer: &'a S,
+    pub signer: S,
     /// Used for calculating epoch.
     pub slots_per_epoch: u64,
+    /// The signing history of all validators, used to refuse slashable blocks.
+    pub slashing_protection: Arc<SlashingProtection>,
+    /// Claims the validator from other validator clients which share it, before it is signed for.
+    pub signing_lease: Arc<SigningLease>,
+    /// The address to which the fees of the block should be paid, if any.
+    pub fee_recipient: Option<Address>,
+    /// The start of `slot`, from which the timing of the proposal is measured.
+    pub slot_start: Instant,
+    /// The end of `slot`, after which publishing a block is no longer retried.
+    pub deadline: Instant,
+    /// Records the time taken to reach each stage of the proposal.
+    pub timings: Arc<ProposalTimings>,
+    /// When the block should be published, and the latest it may be signed.
+    pub publish_window: PublishWindow,
+    /// Notifies operators of blocks which could not be produced.
+    pub notifier: Arc<Notifier>,
+    /// If `true`, blocks are signed with a throwaway key and logged rather than published.
+    pub dry_run: bool,
     /// Mere vessel for E.
     pub _phantom: PhantomData<E>,
 }
 
-impl<'a, B: BeaconNodeBlock, S: Signer, E: EthSpec> BlockProducer<'a, B, S, E> {
+impl<B: BeaconNodeBlock, S: Signer, E: EthSpec> Clone for BlockProducer<B, S, E> {
+    fn clone(&self) -> Self {
+        Self {
+            fork: self.fork.clone(),
+            slot: self.slot,
+            spec: self.spec.clone(),
+            beacon_node: self.beacon_node.clone(),
+            signer: self.signer.clone(),
+            slots_per_epoch: self.slots_per_epoch,
+            slashing_protection: self.slashing_protection.clone(),
+            signing_lease: self.signing_lease.clone(),
+            fee_recipient: self.fee_recipient,
+            slot_start: self.slot_start,
+            deadline: self.deadline,
+            timings: self.timings.clone(),
+            publish_window: self.publish_window,
+            notifier: self.notifier.clone(),
+            dry_run: self.dry_run,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<B: BeaconNodeBlock + 'static, S: Signer + 'static, E: EthSpec> BlockProducer<B, S, E> {
     /// Handle outputs and results from block production.
-    pub fn handle_produce_block(&mut self, log: slog::Logger) {
-        match self.produce_block() {
+    pub fn handle_produce_block(&self, log: slog::Logger) -> HandleFuture {
+        let producer = self.clone();
+        Box::new(self.produce_block().then(move |result| -> Result<(), ()> {
+            producer.log_produce_block(result, log);
+            Ok(())
+        }))
+    }
+
+    /// Handle outputs and results from block production via an external `builder`.
+    ///
+    /// If the builder is unable to provide a valid block, the block is produced by the beacon node.
+    pub fn handle_produce_block_via_builder<C: BeaconNodeBlindedBlock + 'static>(
+        &self,
+        builder: Arc<C>,
+        log: slog::Logger,
+    ) -> HandleFuture {
+        let producer = self.clone();
+        Box::new(
+            self.produce_blinded_block(builder)
+                .then(move |result| -> HandleFuture {
+                    match result {
+                        Ok(ValidatorEvent::BeaconNodeUnableToProduceBlock(_))
+                        | Ok(ValidatorEvent::InvalidBlockFromBeaconNode(..))
+                        | Err(_) => {
+                            warn!(log, "Builder unable to produce block, using beacon node"; "Validator" => format!("{}", producer.signer));
+                            producer.handle_produce_block(log)
+                        }
+                        result => {
+                            producer.log_produce_block(result, log);
+                            Box::new(future::ok(()))
+                        }
+                    }
+                }),
+        )
+    }
+
+    fn log_produce_block(&self, result: Result<ValidatorEvent, Error>, log: slog::Logger) {
+        let missed = |reason: String| Event::MissedProposal {
+            validator: self.signer.to_public().as_hex_string(),
+            slot: self.slot,
+            reason,
+        };
+        let outcome = match &result {
+            Ok(event) => format!("{:?}", event),
+            Err(e) => e.to_string(),
+        };
+        self.timings
+            .record_outcome(self.slot, &self.signer.to_public(), outcome);
+        let event = match result {
             Ok(ValidatorEvent::BlockProduced(_slot)) => {
-                info!(log, "Block produced"; "Validator" => format!("{}", self.signer))
+                info!(log, "Block produced"; "Validator" => format!("{}", self.signer));
+                if self.publish_window.is_late(Instant::now()) {
+                    warn!(
+                        log,
+                        "Block published after the publish target";
+                        "Validator" => format!("{}", self.signer),
+                        "published_ms" => self.slot_start.elapsed().as_millis() as u64,
+                    );
+                }
+                None
+            }
+            Err(e) => {
+                error!(log, "Block production error"; "Error" => e.to_string());
+                Some(missed(e.to_string()))
+            }
+            Ok(ValidatorEvent::BlockNotPublished(header)) => {
+                info!(
+                    log,
+                    "Dry run: block not published";
+                    "Validator" => format!("{}", self.signer),
+                    "slot" => header.slot.as_u64(),
+                    "block_root" => format!("{:?}", header.canonical_root()),
+                    "parent_root" => format!("{:?}", header.parent_root),
+                    "state_root" => format!("{:?}", header.state_root),
+                    "body_root" => format!("{:?}", header.body_root),
+                );
+                None
             }
-            Err(e) => error!(log, "Block production error"; "Error" => format!("{:?}", e)),
-            Ok(ValidatorEvent::SignerRejection(_slot)) => {
-                error!(log, "Block production error"; "Error" => "Signer Could not sign the block".to_string())
+            Ok(ValidatorEvent::SignerRejection(slot)) => {
+                error!(log, "Block production error"; "Error" => "Signer Could not sign the block".to_string());
+                Some(Event::SignerRejection {
+                    validator: self.signer.to_public().as_hex_string(),
+                    slot,
+                })
             }
-            Ok(ValidatorEvent::SlashableBlockNotProduced(_slot)) => {
-                error!(log, "Block production error"; "Error" => "Rejected the block as it could have been slashed".to_string())
+            Ok(ValidatorEvent::SlashableBlockNotProduced(slot)) => {
+                error!(log, "Block production error"; "Error" => "Rejected the block as it could have been slashed".to_string());
+                Some(Event::SlashableMessageBlocked {
+                    validator: self.signer.to_public().as_hex_string(),
+                    slot,
+                    message_type: "block",
+                })
             }
             Ok(ValidatorEvent::BeaconNodeUnableToProduceBlock(_slot)) => {
-                error!(log, "Block production error"; "Error" => "Beacon node was unable to produce a block".to_string())
+                error!(log, "Block production error"; "Error" => "Beacon node was unable to produce a block".to_string());
+                Some(missed(
+                    "beacon node was unable to produce a block".to_string(),
+                ))
+            }
+            Ok(ValidatorEvent::InvalidBlockFromBeaconNode(_slot, violation)) => {
+                error!(log, "Block production error"; "Error" => format!("Beacon node produced an invalid block: {}", violation));
+                Some(missed(format!(
+                    "beacon node produced an invalid block: {}",
+                    violation
+                )))
+            }
+            Ok(ValidatorEvent::BlockTooLate(_slot)) => {
+                error!(log, "Block production error"; "Error" => "The block would have been published too late to be included".to_string());
+                Some(missed(
+                    "the block would have been published too late to be included".to_string(),
+                ))
+            }
+            Ok(ValidatorEvent::PublishBlockFailed(_slot, reason)) => {
+                error!(log, "Block production error"; "Error" => format!("Beacon node was unable to publish the block: {}", reason));
+                Some(missed(format!(
+                    "beacon node was unable to publish the block: {}",
+                    reason
+                )))
+            }
+            Ok(ValidatorEvent::LeaseHeld(_slot, holder)) => {
+                info!(log, "Block left to another validator client"; "Validator" => format!("{}", self.signer), "holder" => holder);
+                None
+            }
+            Ok(ValidatorEvent::LeaseUnavailable(_slot, reason)) => {
+                error!(log, "Block production error"; "Error" => format!("Unable to claim the validator: {}", reason));
+                Some(missed(format!("unable to claim the validator: {}", reason)))
             }
             Ok(v) => {
-                warn!(log, "Unknown result for block production"; "Error" => format!("{:?}",v))
+                warn!(log, "Unknown result for block production"; "Error" => format!("{:?}",v));
+                None
             }
+        };
+
+        if let Some(event) = event {
+            self.notifier.notify(event)
         }
     }
 
@@ -83,86 +326,405 @@ impl<'a, B: BeaconNodeBlock, S: Signer, E: EthSpec> BlockProducer<'a, B, S, E> {
     /// Assumes that a block is required at this slot (does not check the duties).
     ///
     /// Ensures the message is not slashable.
+    pub fn produce_block(&self) -> EventFuture {
+        self.claimed(|producer| {
+            let slot = producer.slot;
+            Box::new(
+                producer
+                    .randao_reveal()
+                    .then(move |randao_reveal| -> EventFuture {
+                        match randao_reveal {
+                            Ok(randao_reveal) => producer.request_block(randao_reveal),
+                            Err(()) => Box::new(future::ok(ValidatorEvent::SignerRejection(slot))),
+                        }
+                    }),
+            )
+        })
+    }
+
+    /// Claims the validator at `self.slot` from any other validator clients which share it, then
+    /// performs `produce` unless the claim is refused.
     ///
-    /// !!! UNSAFE !!!
+    /// A dry run never claims the validator, so that it never takes over from a live validator
+    /// client.
+    fn claimed<F>(&self, produce: F) -> EventFuture
+    where
+        F: FnOnce(Self) -> EventFuture + Send + 'static,
+    {
+        let producer = self.clone();
+        let slot = self.slot;
+        if self.dry_run {
+            return produce(producer);
+        }
+
+        Box::new(
+            self.signing_lease
+                .claim(&self.signer.to_public(), slot)
+                .then(move |claim| -> EventFuture {
+                    match claim {
+                        Ok(Claim::Granted) => produce(producer),
+                        Ok(Claim::Held { holder }) => {
+                            Box::new(future::ok(ValidatorEvent::LeaseHeld(slot, holder)))
+                        }
+                        Err(e) => Box::new(future::ok(ValidatorEvent::LeaseUnavailable(
+                            slot,
+                            e.to_string(),
+                        ))),
+                    }
+                }),
+        )
+    }
+
+    /// Requests a block with `randao_reveal` from the beacon node, then signs and publishes it.
     ///
-    /// The slash-protection code is not yet implemented. There is zero protection against
-    /// slashing.
-    pub fn produce_block(&mut self) -> Result<ValidatorEvent, Error> {
-        let epoch = self.slot.epoch(self.slots_per_epoch);
+    /// The block is not signed if `check_block` finds it to be invalid.
+    fn request_block(self, randao_reveal: Signature) -> EventFuture {
+        let slot = self.slot;
 
-        let message = epoch.tree_hash_root();
-        let randao_reveal = match self.signer.sign_message(
-            &message,
-            self.spec.get_domain(epoch, Domain::Randao, &self.fork),
-        ) {
-            None => return Ok(ValidatorEvent::SignerRejection(self.slot)),
-            Some(signature) => signature,
+        Box::new(
+            self.beacon_node
+                .produce_beacon_block(slot, &randao_reveal, self.fee_recipient)
+                .map_err(Error::beacon_node(slot))
+                .and_then(move |block| -> EventFuture {
+                    match block {
+                        Some(block) => match self.check_block(&block, &randao_reveal) {
+                            Ok(()) => {
+                                self.record(Stage::Received);
+                                self.sign_and_publish_block(block)
+                            }
+                            Err(violation) => Box::new(future::ok(
+                                ValidatorEvent::InvalidBlockFromBeaconNode(slot, violation),
+                            )),
+                        },
+                        None => Box::new(future::ok(
+                            ValidatorEvent::BeaconNodeUnableToProduceBlock(slot),
+                        )),
+                    }
+                }),
+        )
+    }
+
+    /// Signs and publishes `block`, unless it is slashable. In a dry run, the block is not
+    /// published.
+    fn sign_and_publish_block(self, block: BeaconBlockAny<E>) -> EventFuture {
+        let slot = self.slot;
+
+        if self.publish_window.too_late_to_sign(Instant::now()) {
+            return Box::new(future::ok(ValidatorEvent::BlockTooLate(slot)));
+        }
+
+        let permit = match self.safe_to_produce(block.slot(), block.canonical_root().as_bytes()) {
+            Some(permit) => permit,
+            None => return Box::new(future::ok(ValidatorEvent::SlashableBlockNotProduced(slot))),
         };
 
-        if let Some(block) = self
-            .beacon_node
-            .produce_beacon_block(self.slot, &randao_reveal)?
-        {
-            if self.safe_to_produce(&block) {
-                let domain = self
-                    .spec
-                    .get_domain(epoch, Domain::BeaconProposer, &self.fork);
-                if let Some(block) = self.sign_block(block, domain) {
-                    self.beacon_node.publish_beacon_block(block)?;
-                    Ok(ValidatorEvent::BlockProduced(self.slot))
-                } else {
-                    Ok(ValidatorEvent::SignerRejection(self.slot))
+        let producer = self.clone();
+        Box::new(self.sign_block(block).then(move |block| -> EventFuture {
+            if block.is_ok() {
+                producer.slashing_protection.commit(permit);
+                producer.record(Stage::Signed);
+            }
+            match block {
+                Ok(block) if producer.dry_run => Box::new(future::ok(
+                    ValidatorEvent::BlockNotPublished(block.block_header()),
+                )),
+                Ok(block) => producer.publish_block(block),
+                Err(()) => Box::new(future::ok(ValidatorEvent::SignerRejection(slot))),
+            }
+        }))
+    }
+
+    /// Publishes a signed `block`.
+    ///
+    /// If the beacon node is unavailable, or is unable to publish the block for a transient
+    /// reason, publishing is retried until `self.deadline`.
+    fn publish_block(self, block: BeaconBlockAny<E>) -> EventFuture {
+        let slot = self.slot;
+
+        Box::new(self.beacon_node.publish_beacon_block(block.clone()).then(
+            move |outcome| -> EventFuture {
+                let reason = match outcome {
+                    Ok(PublishOutcome::Valid) => {
+                        self.record(Stage::Published);
+                        return Box::new(future::ok(ValidatorEvent::BlockProduced(slot)));
+                    }
+                    Ok(PublishOutcome::Unavailable(reason)) => reason,
+                    Err(e) => e.to_string(),
+                    Ok(PublishOutcome::InvalidBlock(reason)) => {
+                        return Box::new(future::ok(ValidatorEvent::PublishBlockFailed(
+                            slot, reason,
+                        )))
+                    }
+                    Ok(outcome) => {
+                        return Box::new(future::ok(ValidatorEvent::PublishBlockFailed(
+                            slot,
+                            format!("{:?}", outcome),
+                        )))
+                    }
+                };
+
+                let retry_at = Instant::now() + PUBLISH_RETRY_DELAY;
+                if retry_at >= self.deadline {
+                    return Box::new(future::ok(ValidatorEvent::PublishBlockFailed(slot, reason)));
                 }
+
+                Box::new(Delay::new(retry_at).then(move |result| -> EventFuture {
+                    match result {
+                        Ok(()) => self.publish_block(block),
+                        Err(_) => {
+                            Box::new(future::ok(ValidatorEvent::PublishBlockFailed(slot, reason)))
+                        }
+                    }
+                }))
+            },
+        ))
+    }
+
+    /// Produce a blinded block at some slot via an external `builder`.
+    ///
+    /// Only the block header is signed; the builder is responsible for revealing and publishing
+    /// the full block. Has the same slashing protection as `produce_block`.
+    pub fn produce_blinded_block<C: BeaconNodeBlindedBlock + 'static>(
+        &self,
+        builder: Arc<C>,
+    ) -> EventFuture {
+        self.claimed(move |producer| {
+            let slot = producer.slot;
+            Box::new(
+                producer
+                    .randao_reveal()
+                    .then(move |randao_reveal| -> EventFuture {
+                        match randao_reveal {
+                            Ok(randao_reveal) => {
+                                producer.request_blinded_block(builder, randao_reveal)
+                            }
+                            Err(()) => Box::new(future::ok(ValidatorEvent::SignerRejection(slot))),
+                        }
+                    }),
+            )
+        })
+    }
+
+    /// Requests a blinded block with `randao_reveal` from the `builder`, then signs and submits
+    /// it.
+    fn request_blinded_block<C: BeaconNodeBlindedBlock + 'static>(
+        self,
+        builder: Arc<C>,
+        randao_reveal: Signature,
+    ) -> EventFuture {
+        let slot = self.slot;
+
+        Box::new(
+            builder
+                .produce_blinded_beacon_block(slot, &randao_reveal, self.fee_recipient)
+                .map_err(Error::beacon_node(slot))
+                .and_then(move |header| -> EventFuture {
+                    match header {
+                        Some(header) => match self.check_header(&header) {
+                            Ok(()) => {
+                                self.record(Stage::Received);
+                                self.sign_and_submit_header(builder, header)
+                            }
+                            Err(violation) => Box::new(future::ok(
+                                ValidatorEvent::InvalidBlockFromBeaconNode(slot, violation),
+                            )),
+                        },
+                        None => Box::new(future::ok(
+                            ValidatorEvent::BeaconNodeUnableToProduceBlock(slot),
+                        )),
+                    }
+                }),
+        )
+    }
+
+    /// Signs and submits `header` to the `builder`, unless it is slashable.
+    fn sign_and_submit_header<C: BeaconNodeBlindedBlock + 'static>(
+        self,
+        builder: Arc<C>,
+        mut header: BeaconBlockHeader,
+    ) -> EventFuture {
+        let slot = self.slot;
+
+        if self.publish_window.too_late_to_sign(Instant::now()) {
+            return Box::new(future::ok(ValidatorEvent::BlockTooLate(slot)));
+        }
+
+        let permit = match self.safe_to_produce(header.slot, &header.signed_root()) {
+            Some(permit) => permit,
+            None => return Box::new(future::ok(ValidatorEvent::SlashableBlockNotProduced(slot))),
+        };
+
+        let producer = self.clone();
+        let signature = self.sign_proposal(&header.signed_root()[..]);
+        Box::new(signature.then(move |signature| -> EventFuture {
+            match signature {
+                Ok(signature) => {
+                    producer.slashing_protection.commit(permit);
+                    producer.record(Stage::Signed);
+                    header.signature = signature;
+                    if producer.dry_run {
+                        return Box::new(future::ok(ValidatorEvent::BlockNotPublished(header)));
+                    }
+                    Box::new(
+                        builder
+                            .submit_blinded_block(header)
+                            .map_err(Error::beacon_node(slot))
+                            .map(move |_| {
+                                producer.record(Stage::Published);
+                                ValidatorEvent::BlockProduced(slot)
+                            }),
+                    )
+                }
+                Err(()) => Box::new(future::ok(ValidatorEvent::SignerRejection(slot))),
+            }
+        }))
+    }
+
+    /// Records that the proposal has reached `stage`.
+    fn record(&self, stage: Stage) {
+        self.timings
+            .record(self.slot, &self.signer.to_public(), self.slot_start, stage)
+    }
+
+    /// Returns the randao reveal for the epoch of `self.slot`.
+    fn randao_reveal(&self) -> SignatureFuture {
+        let epoch = self.slot.epoch(self.slots_per_epoch);
+        let message = epoch.tree_hash_root();
+        let domain = self.spec.get_domain(epoch, Domain::Randao, &self.fork);
+
+        self.verified(self.signer.sign_message(&message, domain), message, domain)
+    }
+
+    /// Resolves to the result of `signature`, failing if it is not a signature of `message` by
+    /// the validator, so that a faulty signer cannot cause an invalid block to be published.
+    fn verified(
+        &self,
+        signature: SignatureFuture,
+        message: Vec<u8>,
+        domain: u64,
+    ) -> SignatureFuture {
+        let public_key = self.signer.to_public();
+        Box::new(signature.and_then(move |signature| {
+            if signature.verify(&message, domain, &public_key) {
+                Ok(signature)
             } else {
-                Ok(ValidatorEvent::SlashableBlockNotProduced(self.slot))
+                Err(())
             }
-        } else {
-            Ok(ValidatorEvent::BeaconNodeUnableToProduceBlock(self.slot))
-        }
+        }))
     }
 
-    /// Consumes a block, returning that block signed by the validators private key.
+    /// Checks that `block` could be a valid block from this validator at `self.slot`, containing
+    /// `randao_reveal`.
     ///
-    /// Important: this function will not check to ensure the block is not slashable. This must be
-    /// done upstream.
-    fn sign_block(&mut self, mut block: BeaconBlock<E>, domain: u64) -> Option<BeaconBlock<E>> {
-        self.store_produce(&block);
+    /// The validator client has no view of the chain, so only a parent which cannot exist (a zero
+    /// parent root) is detected. The randao reveal is signed by the proposer, so a block with the
+    /// expected reveal was built for this validator.
+    fn check_block(
+        &self,
+        block: &BeaconBlockAny<E>,
+        randao_reveal: &Signature,
+    ) -> Result<(), BlockViolation> {
+        let expected = self
+            .spec
+            .block_variant(self.slot.epoch(self.slots_per_epoch));
+        if block.variant() != expected {
+            return Err(BlockViolation::WrongVariant {
+                expected,
+                block: block.variant(),
+            });
+        }
 
-        match self.signer.sign_message(&block.signed_root()[..], domain) {
-            None => None,
-            Some(signature) => {
-                block.signature = signature;
-                Some(block)
-            }
+        self.check_roots(block.slot(), block.parent_root(), block.state_root())?;
+
+        if block.randao_reveal() != randao_reveal {
+            return Err(BlockViolation::IncorrectRandaoReveal);
         }
+
+        Ok(())
+    }
+
+    /// Checks `header` as `check_block` does, except for the randao reveal (which is committed to
+    /// by the body root only).
+    fn check_header(&self, header: &BeaconBlockHeader) -> Result<(), BlockViolation> {
+        self.check_roots(header.slot, header.parent_root, header.state_root)
     }
 
-    /// Returns `true` if signing a block is safe (non-slashable).
+    fn check_roots(
+        &self,
+        slot: Slot,
+        parent_root: Hash256,
+        state_root: Hash256,
+    ) -> Result<(), BlockViolation> {
+        if slot != self.slot {
+            Err(BlockViolation::WrongSlot {
+                requested: self.slot,
+                block: slot,
+            })
+        } else if parent_root.is_zero() {
+            Err(BlockViolation::UnknownParent)
+        } else if state_root.is_zero() {
+            Err(BlockViolation::ZeroStateRoot)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns the domain of block proposals in the epoch of `self.slot`.
+    fn proposer_domain(&self) -> u64 {
+        let epoch = self.slot.epoch(self.slots_per_epoch);
+        self.spec
+            .get_domain(epoch, Domain::BeaconProposer, &self.fork)
+    }
+
+    /// Consumes a block, returning that block signed by the validators private key.
     ///
-    /// !!! UNSAFE !!!
+    /// The signed root is that of the variant of the block.
     ///
-    /// Important: this function is presently stubbed-out. It provides ZERO SAFETY.
-    fn safe_to_produce(&self, _block: &BeaconBlock<E>) -> bool {
-        // TODO: ensure the producer doesn't produce slashable blocks.
-        // https://github.com/sigp/lighthouse/issues/160
-        true
+    /// Important: this function will not check to ensure the block is not slashable. This must be
+    /// done upstream.
+    fn sign_block(
+        &self,
+        mut block: BeaconBlockAny<E>,
+    ) -> Box<dyn Future<Item = BeaconBlockAny<E>, Error = ()> + Send> {
+        Box::new(
+            self.sign_proposal(block.canonical_root().as_bytes())
+                .map(move |signature| {
+                    block.set_signature(signature);
+                    block
+                }),
+        )
     }
 
-    /// Record that a block was produced so that slashable votes may not be made in the future.
-    ///
-    /// !!! UNSAFE !!!
+    /// Signs the `signed_root` of a block or block header.
     ///
-    /// Important: this function is presently stubbed-out. It provides ZERO SAFETY.
-    fn store_produce(&mut self, _block: &BeaconBlock<E>) {
-        // TODO: record this block production to prevent future slashings.
-        // https://github.com/sigp/lighthouse/issues/160
+    /// In a dry run a throwaway key signs instead of the validator, so that the validator never
+    /// signs a block which is not published.
+    fn sign_proposal(&self, signed_root: &[u8]) -> SignatureFuture {
+        if self.dry_run {
+            Keypair::random().sign_message(signed_root, self.proposer_domain())
+        } else {
+            let domain = self.proposer_domain();
+            self.verified(
+                self.signer.sign_message(signed_root, domain),
+                signed_root.to_vec(),
+                domain,
+            )
+        }
     }
-}
 
-impl From<BeaconNodeError> for Error {
-    fn from(e: BeaconNodeError) -> Error {
-        Error::BeaconNodeError(e)
+    /// Returns permission to sign a block if it is safe (non-slashable), or `None` otherwise.
+    ///
+    /// Accepts the slot and signed root of either a block or a block header (which share the same
+    /// signed root). If the block is safe it is recorded in `self.slashing_protection` before the
+    /// permit is returned, so that conflicting blocks may not be signed in the future.
+    fn safe_to_produce(&self, slot: Slot, signed_root: &[u8]) -> Option<SigningPermit> {
+        self.slashing_protection
+            .begin_sign_block(
+                &self.signer.to_public(),
+                slot,
+                Hash256::from_slice(signed_root),
+            )
+            .ok()
     }
 }
 
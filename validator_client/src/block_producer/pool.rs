@@ -0,0 +1,226 @@
+//! A fixed pool of workers which produces the blocks of all validators.
+//!
+//! Productions are queued in the order of their deadlines, so that when more validators propose
+//! at once than there are workers, the production which is due soonest is started first. A
+//! production is only constructed once a worker takes it, so no beacon node request is made for a
+//! production which is still queued.
+use futures::sync::mpsc;
+use futures::{future, Future, Stream};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// The number of blocks which are produced at once by the validator service.
+pub const DEFAULT_WORKERS: usize = 4;
+
+/// A future which produces (and publishes) a single block.
+pub type Production = Box<dyn Future<Item = (), Error = ()> + Send>;
+
+/// A function which constructs a production once a worker is available.
+type ProductionFn = Box<dyn FnOnce() -> Production + Send>;
+
+struct Job {
+    deadline: Instant,
+    /// The order in which the job was submitted, so that jobs with the same deadline are produced
+    /// in the order they were submitted.
+    sequence: u64,
+    produce: ProductionFn,
+}
+
+impl Ord for Job {
+    /// The job with the earliest deadline is the greatest, so that it is the first to be popped
+    /// from a `BinaryHeap`.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .deadline
+            .cmp(&self.deadline)
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+impl PartialOrd for Job {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Job {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Job {}
+
+#[derive(Default)]
+struct Queue {
+    jobs: BinaryHeap<Job>,
+    next_sequence: u64,
+}
+
+/// Runs block productions on at most `workers` workers, earliest deadline first.
+pub struct ProductionPool {
+    workers: usize,
+    queue: Arc<Mutex<Queue>>,
+    /// Wakes a worker for each submitted job.
+    sender: mpsc::UnboundedSender<()>,
+    receiver: Mutex<Option<mpsc::UnboundedReceiver<()>>>,
+}
+
+impl ProductionPool {
+    /// Create a pool of `workers` workers, which run once `workers()` is spawned.
+    pub fn new(workers: usize) -> Self {
+        let (sender, receiver) = mpsc::unbounded();
+        Self {
+            workers: workers.max(1),
+            queue: Arc::new(Mutex::new(Queue::default())),
+            sender,
+            receiver: Mutex::new(Some(receiver)),
+        }
+    }
+
+    /// Queues the production constructed by `produce`, which must complete by `deadline`.
+    pub fn submit<F>(&self, deadline: Instant, produce: F)
+    where
+        F: FnOnce() -> Production + Send + 'static,
+    {
+        if let Ok(mut queue) = self.queue.lock() {
+            let sequence = queue.next_sequence;
+            queue.next_sequence += 1;
+            queue.jobs.push(Job {
+                deadline,
+                sequence,
+                produce: Box::new(produce),
+            });
+        }
+        // The workers have stopped if they are unable to receive, and the job is dropped with the
+        // pool.
+        let _ = self.sender.unbounded_send(());
+    }
+
+    /// Drops every production which is waiting for a worker, returning the number dropped.
+    ///
+    /// Productions which a worker has already started are unaffected.
+    pub fn cancel_queued(&self) -> usize {
+        self.queue
+            .lock()
+            .map(|mut queue| queue.jobs.drain().count())
+            .unwrap_or(0)
+    }
+
+    /// Returns the number of productions which are waiting for a worker.
+    pub fn queued(&self) -> usize {
+        self.queue.lock().map(|queue| queue.jobs.len()).unwrap_or(0)
+    }
+
+    /// Returns a future which runs the workers until the pool is dropped and all queued
+    /// productions are complete.
+    ///
+    /// The workers only run once, so later calls return a future which completes immediately.
+    pub fn workers(&self) -> Box<dyn Future<Item = (), Error = ()> + Send> {
+        let receiver = match self.receiver.lock().ok().and_then(|mut r| r.take()) {
+            Some(receiver) => receiver,
+            None => return Box::new(future::ok(())),
+        };
+        let queue = self.queue.clone();
+
+        Box::new(
+            receiver
+                // Each wake-up is only taken once a worker is free, at which point the job with
+                // the earliest deadline is popped.
+                .map(move |()| -> Production {
+                    let job = queue.lock().ok().and_then(|mut queue| queue.jobs.pop());
+                    match job {
+                        Some(job) => (job.produce)(),
+                        None => Box::new(future::ok(())),
+                    }
+                })
+                .buffer_unordered(self.workers)
+                .for_each(|()| Ok(())),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::runtime::Runtime;
+    use tokio::timer::Delay;
+
+    #[test]
+    fn produces_earliest_deadline_first() {
+        let pool = ProductionPool::new(1);
+        let produced = Arc::new(Mutex::new(vec![]));
+        let now = Instant::now();
+
+        for (name, offset) in &[("c", 3), ("a", 1), ("b", 2), ("a again", 1)] {
+            let produced = produced.clone();
+            let name = *name;
+            pool.submit(now + Duration::from_secs(*offset), move || {
+                produced.lock().unwrap().push(name);
+                Box::new(future::ok(()))
+            });
+        }
+        assert_eq!(pool.queued(), 4);
+
+        let workers = pool.workers();
+        drop(pool);
+        let mut runtime = Runtime::new().expect("should create runtime");
+        runtime.block_on(workers).expect("should run workers");
+
+        assert_eq!(*produced.lock().unwrap(), vec!["a", "a again", "b", "c"]);
+    }
+
+    #[test]
+    fn bounds_concurrent_productions() {
+        let pool = ProductionPool::new(2);
+        let running = Arc::new(Mutex::new((0, 0)));
+        let deadline = Instant::now() + Duration::from_secs(1);
+
+        for _ in 0..5 {
+            let running = running.clone();
+            pool.submit(deadline, move || {
+                {
+                    let (current, max) = &mut *running.lock().unwrap();
+                    *current += 1;
+                    *max = (*max).max(*current);
+                }
+                Box::new(
+                    Delay::new(Instant::now() + Duration::from_millis(20))
+                        .map_err(|_| ())
+                        .map(move |()| running.lock().unwrap().0 -= 1),
+                )
+            });
+        }
+
+        let workers = pool.workers();
+        drop(pool);
+        let mut runtime = Runtime::new().expect("should create runtime");
+        runtime.block_on(workers).expect("should run workers");
+
+        assert_eq!(*running.lock().unwrap(), (0, 2));
+    }
+
+    #[test]
+    fn cancels_queued_productions() {
+        let pool = ProductionPool::new(1);
+        let produced = Arc::new(Mutex::new(0));
+        for _ in 0..3 {
+            let produced = produced.clone();
+            pool.submit(Instant::now(), move || {
+                *produced.lock().unwrap() += 1;
+                Box::new(future::ok(()))
+            });
+        }
+        assert_eq!(pool.cancel_queued(), 3);
+        assert_eq!(pool.queued(), 0);
+
+        let workers = pool.workers();
+        drop(pool);
+        let mut runtime = Runtime::new().expect("should create runtime");
+        runtime.block_on(workers).expect("should run workers");
+        assert_eq!(*produced.lock().unwrap(), 0);
+    }
+}
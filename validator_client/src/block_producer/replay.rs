@@ -0,0 +1,395 @@
+//! Recording and deterministic replay of block production, for debugging missed proposals.
+//!
+//! A `Recording` wraps a beacon node, appending every response to a block production or
+//! publishing request to a trace file (one JSON `TraceEvent` per line). The service also records
+//! the start of each slot. The trace may then be replayed offline with `replay`, which runs the
+//! same `BlockProducer` logic against a `Replay` node that returns the recorded responses in
+//! order.
+//!
+//! Responses from an external block builder are not recorded, and signing is replayed with a
+//! random key, so only the decisions made from beacon node responses are reproduced.
+use super::{
+    BeaconNodeBlock, BeaconNodeError, BeaconNodeFuture, BlockProducer, Error as ProducerError,
+    PublishOutcome, ValidatorEvent, PUBLISH_RETRY_DELAY,
+};
+use crate::block_latency::PublishWindow;
+use crate::notifier::Notifier;
+use crate::proposal_timing::ProposalTimings;
+use crate::signing_lease::SigningLease;
+use crate::slashing_protection::SlashingProtection;
+use futures::{future, Future};
+use serde_derive::{Deserialize, Serialize};
+use slog::warn;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::marker::PhantomData;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::runtime::current_thread::Runtime;
+use types::{
+    Address, BeaconBlockAny, BlockVariant, ChainSpec, EthSpec, Fork, Keypair, Signature, Slot,
+};
+
+/// Returned by `Replay` once every recorded response to publish a block has been returned.
+pub const END_OF_TRACE: &str = "no further responses were recorded";
+
+/// A recorded response to a request to produce a block, with the block as JSON.
+type ProduceResponse = Result<Option<serde_json::Value>, BeaconNodeError>;
+/// A recorded response to a request to publish a block.
+type PublishResponse = Result<PublishOutcome, BeaconNodeError>;
+
+/// A single event in a trace.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum TraceEvent {
+    /// The validator client started processing `slot`.
+    SlotStart { slot: Slot },
+    /// The response to a request to produce a block at `slot`, with the block (of `variant`) as
+    /// JSON.
+    ProduceBlock {
+        slot: Slot,
+        #[serde(default)]
+        variant: BlockVariant,
+        response: ProduceResponse,
+    },
+    /// The response to a request to publish the block at `slot`.
+    PublishBlock {
+        slot: Slot,
+        response: PublishResponse,
+    },
+}
+
+/// Appends `TraceEvent`s to a trace file.
+#[derive(Clone)]
+pub struct Recorder {
+    file: Arc<Mutex<File>>,
+    log: slog::Logger,
+}
+
+impl Recorder {
+    /// Opens the trace file at `path`, appending to it if it exists.
+    pub fn open(path: &Path, log: slog::Logger) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(Self {
+            file: Arc::new(Mutex::new(file)),
+            log,
+        })
+    }
+
+    /// Appends `event` to the trace, logging any failure to do so.
+    pub fn record(&self, event: &TraceEvent) {
+        let result = serde_json::to_string(event)
+            .map_err(io::Error::from)
+            .and_then(|line| {
+                let mut file = self
+                    .file
+                    .lock()
+                    .map_err(|_| io::Error::new(io::ErrorKind::Other, "trace lock poisoned"))?;
+                writeln!(file, "{}", line)
+            });
+
+        if let Err(e) = result {
+            warn!(self.log, "Unable to record trace event"; "error" => e.to_string());
+        }
+    }
+}
+
+/// A beacon node which records its responses, if a `Recorder` is supplied.
+pub struct Recording<N> {
+    node: Arc<N>,
+    recorder: Option<Recorder>,
+}
+
+impl<N> Recording<N> {
+    pub fn new(node: Arc<N>, recorder: Option<Recorder>) -> Self {
+        Self { node, recorder }
+    }
+}
+
+impl<N: BeaconNodeBlock> BeaconNodeBlock for Recording<N> {
+    fn produce_beacon_block<T: EthSpec>(
+        &self,
+        slot: Slot,
+        randao_reveal: &Signature,
+        fee_recipient: Option<Address>,
+    ) -> BeaconNodeFuture<Option<BeaconBlockAny<T>>> {
+        let response = self
+            .node
+            .produce_beacon_block(slot, randao_reveal, fee_recipient);
+
+        match self.recorder.clone() {
+            Some(recorder) => Box::new(response.then(move |response| {
+                let variant = match &response {
+                    Ok(Some(block)) => block.variant(),
+                    _ => BlockVariant::default(),
+                };
+                let recorded = response.clone().map(|block| {
+                    block.map(|block| {
+                        serde_json::to_value(&block).unwrap_or(serde_json::Value::Null)
+                    })
+                });
+                recorder.record(&TraceEvent::ProduceBlock {
+                    slot,
+                    variant,
+                    response: recorded,
+                });
+                response
+            })),
+            None => response,
+        }
+    }
+
+    fn publish_beacon_block<T: EthSpec>(
+        &self,
+        block: BeaconBlockAny<T>,
+    ) -> BeaconNodeFuture<PublishOutcome> {
+        let slot = block.slot();
+        let response = self.node.publish_beacon_block(block);
+
+        match self.recorder.clone() {
+            Some(recorder) => Box::new(response.then(move |response| {
+                recorder.record(&TraceEvent::PublishBlock {
+                    slot,
+                    response: response.clone(),
+                });
+                response
+            })),
+            None => response,
+        }
+    }
+}
+
+/// A beacon node which returns the responses recorded in a trace, in order, for each slot.
+#[derive(Default)]
+pub struct Replay {
+    produce: Mutex<HashMap<Slot, VecDeque<(BlockVariant, ProduceResponse)>>>,
+    publish: Mutex<HashMap<Slot, VecDeque<PublishResponse>>>,
+    /// The slots in which a block was requested, in the order first requested.
+    proposal_slots: Vec<Slot>,
+    /// The slots which the validator client started processing.
+    started_slots: HashSet<Slot>,
+}
+
+/// The outcome of replaying the proposal at `slot`.
+#[derive(Debug, PartialEq)]
+pub struct ReplayedProposal {
+    pub slot: Slot,
+    /// `false` if the validator client did not record the start of the slot (e.g., because it
+    /// was processing a previous slot for too long).
+    pub slot_started: bool,
+    pub outcome: Result<ValidatorEvent, ProducerError>,
+}
+
+impl Replay {
+    pub fn new(events: Vec<TraceEvent>) -> Self {
+        let mut replay = Self::default();
+        {
+            let produce = replay.produce.get_mut().expect("lock is not shared");
+            let publish = replay.publish.get_mut().expect("lock is not shared");
+
+            for event in events {
+                match event {
+                    TraceEvent::SlotStart { slot } => {
+                        replay.started_slots.insert(slot);
+                    }
+                    TraceEvent::ProduceBlock {
+                        slot,
+                        variant,
+                        response,
+                    } => {
+                        if !produce.contains_key(&slot) {
+                            replay.proposal_slots.push(slot);
+                        }
+                        produce
+                            .entry(slot)
+                            .or_default()
+                            .push_back((variant, response));
+                    }
+                    TraceEvent::PublishBlock { slot, response } => {
+                        publish.entry(slot).or_default().push_back(response);
+                    }
+                }
+            }
+        }
+        replay
+    }
+
+    /// Reads the trace file at `path`.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let events = BufReader::new(File::open(path)?)
+            .lines()
+            .filter(|line| line.as_ref().map_or(true, |line| !line.trim().is_empty()))
+            .map(|line| -> io::Result<TraceEvent> { Ok(serde_json::from_str(&line?)?) })
+            .collect::<io::Result<Vec<TraceEvent>>>()?;
+
+        Ok(Self::new(events))
+    }
+
+    /// Returns the number of publish responses recorded at `slot`.
+    fn publish_count(&self, slot: Slot) -> usize {
+        self.publish
+            .lock()
+            .ok()
+            .and_then(|publish| publish.get(&slot).map(VecDeque::len))
+            .unwrap_or(0)
+    }
+}
+
+impl BeaconNodeBlock for Replay {
+    fn produce_beacon_block<T: EthSpec>(
+        &self,
+        slot: Slot,
+        _randao_reveal: &Signature,
+        _fee_recipient: Option<Address>,
+    ) -> BeaconNodeFuture<Option<BeaconBlockAny<T>>> {
+        let (variant, response) = self
+            .produce
+            .lock()
+            .ok()
+            .and_then(|mut produce| produce.get_mut(&slot)?.pop_front())
+            .unwrap_or_else(|| {
+                (
+                    BlockVariant::default(),
+                    Err(BeaconNodeError::RemoteFailure(END_OF_TRACE.to_string())),
+                )
+            });
+        let response = response.and_then(|block| match block {
+            Some(block) => BeaconBlockAny::deserialize_variant(block, variant)
+                .map(Some)
+                .map_err(|_| BeaconNodeError::DecodeFailure),
+            None => Ok(None),
+        });
+
+        Box::new(future::result(response))
+    }
+
+    fn publish_beacon_block<T: EthSpec>(
+        &self,
+        block: BeaconBlockAny<T>,
+    ) -> BeaconNodeFuture<PublishOutcome> {
+        let response = self
+            .publish
+            .lock()
+            .ok()
+            .and_then(|mut publish| publish.get_mut(&block.slot())?.pop_front())
+            .unwrap_or_else(|| Ok(PublishOutcome::InvalidBlock(END_OF_TRACE.to_string())));
+
+        Box::new(future::result(response))
+    }
+}
+
+/// Replays every proposal in `replay`, in the order they were recorded.
+///
+/// Proposals share a single slashing protection history, as they did when recorded.
+pub fn replay<E: EthSpec>(replay: Replay, spec: ChainSpec) -> io::Result<Vec<ReplayedProposal>> {
+    let mut runtime = Runtime::new()?;
+    let replay = Arc::new(replay);
+    let spec = Arc::new(spec);
+    let signer = Keypair::random();
+    let slashing_protection = Arc::new(SlashingProtection::new());
+
+    Ok(replay
+        .proposal_slots
+        .iter()
+        .map(|&slot| {
+            // Allow enough time to retry publishing once for each recorded response.
+            let retries = replay.publish_count(slot) as u32 + 1;
+            let producer = BlockProducer {
+                fork: Fork::default(),
+                slot,
+                spec: spec.clone(),
+                beacon_node: replay.clone(),
+                signer: signer.clone(),
+                slots_per_epoch: E::slots_per_epoch(),
+                slashing_protection: slashing_protection.clone(),
+                signing_lease: Arc::new(SigningLease::disabled()),
+                fee_recipient: None,
+                slot_start: Instant::now(),
+                deadline: Instant::now() + PUBLISH_RETRY_DELAY * retries + Duration::from_secs(1),
+                timings: Arc::new(ProposalTimings::default()),
+                publish_window: PublishWindow::default(),
+                notifier: Arc::new(Notifier::disabled()),
+                dry_run: false,
+                _phantom: PhantomData::<E>,
+            };
+
+            ReplayedProposal {
+                slot,
+                slot_started: replay.started_slots.contains(&slot),
+                outcome: runtime.block_on(producer.produce_block()),
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_producer::test_utils::TestBeaconNode;
+    use slog::o;
+    use tempfile::TempDir;
+    use types::MinimalEthSpec;
+
+    #[test]
+    fn replays_recorded_decisions() {
+        let dir = TempDir::new().expect("should create temp dir");
+        let path = dir.path().join("trace.jsonl");
+        let log = slog::Logger::root(slog::Discard, o!());
+        let recorder = Recorder::open(&path, log).expect("should open trace");
+
+        let node = Arc::new(TestBeaconNode::default());
+        node.fail_publish(Slot::new(2), 1);
+        let recording = Arc::new(Recording::new(node, Some(recorder.clone())));
+
+        let mut runtime = Runtime::new().expect("should create runtime");
+        let spec = MinimalEthSpec::default_spec();
+        let signer = Keypair::random();
+        let slashing_protection = Arc::new(SlashingProtection::new());
+
+        let recorded: Vec<_> = (1..4)
+            .map(|slot| {
+                let slot = Slot::new(slot);
+                if slot != Slot::new(3) {
+                    recorder.record(&TraceEvent::SlotStart { slot });
+                }
+                let producer = BlockProducer {
+                    fork: Fork::default(),
+                    slot,
+                    spec: Arc::new(spec.clone()),
+                    beacon_node: recording.clone(),
+                    signer: signer.clone(),
+                    slots_per_epoch: MinimalEthSpec::slots_per_epoch(),
+                    slashing_protection: slashing_protection.clone(),
+                    signing_lease: Arc::new(SigningLease::disabled()),
+                    fee_recipient: None,
+                    slot_start: Instant::now(),
+                    deadline: Instant::now() + Duration::from_secs(5),
+                    timings: Arc::new(ProposalTimings::default()),
+                    publish_window: PublishWindow::default(),
+                    notifier: Arc::new(Notifier::disabled()),
+                    dry_run: false,
+                    _phantom: PhantomData::<MinimalEthSpec>,
+                };
+                runtime.block_on(producer.produce_block())
+            })
+            .collect();
+
+        let replay = Replay::load(&path).expect("should load trace");
+        let replayed = super::replay::<MinimalEthSpec>(replay, spec).expect("should replay");
+
+        assert_eq!(
+            replayed.iter().map(|p| p.slot).collect::<Vec<_>>(),
+            vec![Slot::new(1), Slot::new(2), Slot::new(3)]
+        );
+        assert_eq!(
+            replayed.iter().map(|p| p.slot_started).collect::<Vec<_>>(),
+            vec![true, true, false]
+        );
+        assert_eq!(
+            replayed.into_iter().map(|p| p.outcome).collect::<Vec<_>>(),
+            recorded
+        );
+    }
+}
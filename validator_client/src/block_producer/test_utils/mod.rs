@@ -0,0 +1,304 @@
+mod signer;
+mod simulation;
+
+pub use self::signer::{SignerFault, TestSigner};
+pub use self::simulation::{SignedProposal, Simulation, SimulationReport};
+
+use super::beacon_node_block::{
+    BeaconNodeBlindedBlock, BeaconNodeBlock, BeaconNodeError, BeaconNodeFuture, PublishOutcome,
+};
+use super::BlockViolation;
+use futures::future;
+use std::collections::{HashMap, VecDeque};
+use std::sync::RwLock;
+use std::thread;
+use std::time::Duration;
+use tree_hash::SignedRoot;
+use types::{
+    Address, BeaconBlock, BeaconBlockAny, BeaconBlockHeader, EthSpec, Hash256, MinimalEthSpec,
+    Signature, Slot, ValidatorRegistration,
+};
+
+/// A fault which a `TestBeaconNode` injects into its response to a block production request.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Fault {
+    /// Return `BeaconNodeError::RemoteFailure`.
+    RemoteFailure,
+    /// Return `Ok(None)`, as if the node were unable to produce a block.
+    Unavailable,
+    /// Respond normally, but only after an additional delay.
+    Late(Duration),
+    /// Return a block with the given flaw.
+    InvalidBlock(BlockViolation),
+    /// Return the block most recently produced for the slot again, rather than a new one.
+    Duplicate,
+    /// Return the first block produced for an earlier slot, as a lagging node might.
+    Old(Slot),
+}
+
+/// A test-only struct used to simulate a Beacon Node.
+///
+/// Every request blocks for the configured `latency` before returning a resolved future, which
+/// allows the block production path to be exercised (and benchmarked) under realistic network
+/// conditions without a runtime.
+///
+/// Faults may be scripted per slot with `push_fault`. Repeated requests for the same slot return
+/// distinct blocks, as a real beacon node would if it had received new operations, unless
+/// `Fault::Duplicate` is injected.
+#[derive(Default)]
+pub struct TestBeaconNode {
+    /// The time each request takes to complete.
+    pub latency: Duration,
+    /// The slot and signed root of all blocks that have been published to this node.
+    published_blocks: RwLock<Vec<(Slot, Hash256)>>,
+    /// Faults to inject into the next requests for each slot, in order.
+    faults: RwLock<HashMap<Slot, VecDeque<Fault>>>,
+    /// The number of blocks produced for each slot.
+    produce_counts: RwLock<HashMap<Slot, u64>>,
+    /// The number of remaining attempts to publish a block at each slot which will fail.
+    publish_failures: RwLock<HashMap<Slot, u64>>,
+    /// All validator registrations submitted to this node, in order.
+    registrations: RwLock<Vec<ValidatorRegistration>>,
+}
+
+impl TestBeaconNode {
+    /// Create a node which responds to each request after `latency`.
+    pub fn with_latency(latency: Duration) -> Self {
+        Self {
+            latency,
+            ..Self::default()
+        }
+    }
+
+    /// Inject `fault` into the next block production request for `slot` which does not already
+    /// have a fault injected.
+    pub fn push_fault(&self, slot: Slot, fault: Fault) {
+        self.faults
+            .write()
+            .expect("faults lock should not be poisoned")
+            .entry(slot)
+            .or_default()
+            .push_back(fault);
+    }
+
+    /// Respond to the next `count` attempts to publish a block at `slot` with
+    /// `PublishOutcome::Unavailable`, as if the block could not yet be imported.
+    pub fn fail_publish(&self, slot: Slot, count: u64) {
+        *self
+            .publish_failures
+            .write()
+            .expect("publish_failures lock should not be poisoned")
+            .entry(slot)
+            .or_insert(0) += count;
+    }
+
+    /// Returns the slots of all blocks published to this node, in order.
+    pub fn published_slots(&self) -> Vec<Slot> {
+        self.published_blocks()
+            .into_iter()
+            .map(|(slot, _)| slot)
+            .collect()
+    }
+
+    /// Returns the slot and signed root of all blocks published to this node, in order.
+    pub fn published_blocks(&self) -> Vec<(Slot, Hash256)> {
+        self.published_blocks
+            .read()
+            .expect("published_blocks lock should not be poisoned")
+            .clone()
+    }
+
+    /// Returns all validator registrations submitted to this node, in order.
+    pub fn registrations(&self) -> Vec<ValidatorRegistration> {
+        self.registrations
+            .read()
+            .expect("registrations lock should not be poisoned")
+            .clone()
+    }
+
+    fn simulate_latency(&self, latency: Duration) {
+        if latency > Duration::from_millis(0) {
+            thread::sleep(latency);
+        }
+    }
+
+    fn record_published(&self, slot: Slot, signed_root: &[u8]) -> Result<(), BeaconNodeError> {
+        self.published_blocks
+            .write()
+            .map_err(|_| BeaconNodeError::RemoteFailure("Lock poisoned".to_string()))?
+            .push((slot, Hash256::from_slice(signed_root)));
+        Ok(())
+    }
+
+    /// Returns an empty block at `slot` containing `randao_reveal`, unless a fault has been
+    /// injected.
+    ///
+    /// Blocks are determined by their slot, graffiti and randao reveal, so a duplicate or old
+    /// block is identical to the one first returned if the randao reveal is the same.
+    ///
+    /// The parent and state roots are arbitrary, but non-zero.
+    ///
+    /// The graffiti of the block is the number of blocks previously produced for `slot`.
+    fn produce_block<T: EthSpec>(
+        &self,
+        slot: Slot,
+        randao_reveal: &Signature,
+    ) -> Result<Option<BeaconBlock<T>>, BeaconNodeError> {
+        let fault = self.next_fault(slot);
+        match fault {
+            Some(Fault::RemoteFailure) => {
+                self.simulate_latency(self.latency);
+                return Err(BeaconNodeError::RemoteFailure("Injected fault".to_string()));
+            }
+            Some(Fault::Unavailable) => {
+                self.simulate_latency(self.latency);
+                return Ok(None);
+            }
+            Some(Fault::Late(delay)) => self.simulate_latency(self.latency + delay),
+            _ => self.simulate_latency(self.latency),
+        }
+
+        let count = {
+            let mut counts = self
+                .produce_counts
+                .write()
+                .map_err(|_| BeaconNodeError::RemoteFailure("Lock poisoned".to_string()))?;
+            let count = counts.entry(slot).or_insert(0);
+            match fault {
+                Some(Fault::Duplicate) if *count > 0 => *count - 1,
+                Some(Fault::Old(_)) => 0,
+                _ => {
+                    *count += 1;
+                    *count - 1
+                }
+            }
+        };
+
+        let mut block = BeaconBlock::empty(&T::default_spec());
+        block.slot = match fault {
+            Some(Fault::Old(old)) => old,
+            _ => slot,
+        };
+        block.parent_root = Hash256::repeat_byte(1);
+        block.state_root = Hash256::repeat_byte(2);
+        block.body.randao_reveal = randao_reveal.clone();
+        block.body.graffiti[0..8].copy_from_slice(&count.to_le_bytes());
+
+        match fault {
+            Some(Fault::InvalidBlock(BlockViolation::WrongSlot { block: wrong, .. })) => {
+                block.slot = wrong
+            }
+            Some(Fault::InvalidBlock(BlockViolation::UnknownParent)) => {
+                block.parent_root = Hash256::zero()
+            }
+            Some(Fault::InvalidBlock(BlockViolation::ZeroStateRoot)) => {
+                block.state_root = Hash256::zero()
+            }
+            Some(Fault::InvalidBlock(BlockViolation::IncorrectRandaoReveal)) => {
+                block.body.randao_reveal = Signature::empty_signature()
+            }
+            _ => (),
+        }
+
+        Ok(Some(block))
+    }
+
+    /// Returns `true` if the next attempt to publish a block at `slot` should fail.
+    fn next_publish_fails(&self, slot: Slot) -> bool {
+        match self
+            .publish_failures
+            .write()
+            .expect("publish_failures lock should not be poisoned")
+            .get_mut(&slot)
+        {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn next_fault(&self, slot: Slot) -> Option<Fault> {
+        self.faults
+            .write()
+            .ok()?
+            .get_mut(&slot)
+            .and_then(VecDeque::pop_front)
+    }
+}
+
+impl BeaconNodeBlock for TestBeaconNode {
+    /// Resolves to the block returned by `produce_block`.
+    fn produce_beacon_block<T: EthSpec>(
+        &self,
+        slot: Slot,
+        randao_reveal: &Signature,
+        _fee_recipient: Option<Address>,
+    ) -> BeaconNodeFuture<Option<BeaconBlockAny<T>>> {
+        Box::new(future::result(
+            self.produce_block(slot, randao_reveal)
+                .map(|block| block.map(BeaconBlockAny::from)),
+        ))
+    }
+
+    /// Records the slot and signed root of `block` and returns `PublishOutcome::Valid`, unless
+    /// publishing has been scripted to fail with `fail_publish`.
+    fn publish_beacon_block<T: EthSpec>(
+        &self,
+        block: BeaconBlockAny<T>,
+    ) -> BeaconNodeFuture<PublishOutcome> {
+        self.simulate_latency(self.latency);
+
+        if self.next_publish_fails(block.slot()) {
+            return Box::new(future::ok(PublishOutcome::Unavailable(
+                "Injected publish failure".to_string(),
+            )));
+        }
+
+        Box::new(future::result(
+            self.record_published(block.slot(), block.canonical_root().as_bytes())
+                .map(|()| PublishOutcome::Valid),
+        ))
+    }
+}
+
+impl BeaconNodeBlindedBlock for TestBeaconNode {
+    /// Returns the header of the block that `produce_beacon_block` would return.
+    fn produce_blinded_beacon_block(
+        &self,
+        slot: Slot,
+        randao_reveal: &Signature,
+        _fee_recipient: Option<Address>,
+    ) -> BeaconNodeFuture<Option<BeaconBlockHeader>> {
+        Box::new(future::result(
+            self.produce_block::<MinimalEthSpec>(slot, randao_reveal)
+                .map(|block| block.map(|block| block.block_header())),
+        ))
+    }
+
+    /// Records the slot and signed root of `header` and returns `PublishOutcome::Valid`.
+    fn submit_blinded_block(&self, header: BeaconBlockHeader) -> BeaconNodeFuture<PublishOutcome> {
+        self.simulate_latency(self.latency);
+
+        Box::new(future::result(
+            self.record_published(header.slot, &header.signed_root())
+                .map(|()| PublishOutcome::Valid),
+        ))
+    }
+
+    /// Records `registrations`.
+    fn register_validators(
+        &self,
+        registrations: Vec<ValidatorRegistration>,
+    ) -> BeaconNodeFuture<()> {
+        self.simulate_latency(self.latency);
+
+        Box::new(future::result(
+            self.registrations
+                .write()
+                .map_err(|_| BeaconNodeError::RemoteFailure("Lock poisoned".to_string()))
+                .map(|mut recorded| recorded.extend(registrations)),
+        ))
+    }
+}
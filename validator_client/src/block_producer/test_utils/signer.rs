@@ -0,0 +1,76 @@
+use crate::signer::{SignatureFuture, Signer};
+use futures::future;
+use std::collections::VecDeque;
+use std::fmt::{self, Display};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use types::{Keypair, PublicKey, Signature};
+
+/// A fault which a `TestSigner` injects into its response to a signing request.
+#[derive(Debug, PartialEq, Clone)]
+pub enum SignerFault {
+    /// Refuse to sign, as a remote signer or HSM might.
+    Reject,
+    /// Sign normally, but only after a delay.
+    Late(Duration),
+    /// Return a signature which is not of the message.
+    Malformed,
+}
+
+/// A test-only signer which signs with a keypair, unless a fault has been scripted with
+/// `push_fault`.
+///
+/// Faults are injected into signing requests in order, regardless of the message, so that
+/// intermittent failures may be scripted by interleaving `None`. Clones share their faults.
+#[derive(Clone)]
+pub struct TestSigner {
+    keypair: Keypair,
+    faults: Arc<Mutex<VecDeque<Option<SignerFault>>>>,
+}
+
+impl TestSigner {
+    pub fn new(keypair: Keypair) -> Self {
+        Self {
+            keypair,
+            faults: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+
+    /// Inject `fault` into the next signing request which does not already have a fault
+    /// injected, or let that request succeed if `fault` is `None`.
+    pub fn push_fault(&self, fault: Option<SignerFault>) {
+        self.faults
+            .lock()
+            .expect("faults lock should not be poisoned")
+            .push_back(fault);
+    }
+
+    fn next_fault(&self) -> Option<SignerFault> {
+        self.faults.lock().ok()?.pop_front().and_then(|fault| fault)
+    }
+}
+
+impl Signer for TestSigner {
+    fn to_public(&self) -> PublicKey {
+        self.keypair.pk.clone()
+    }
+
+    fn sign_message(&self, message: &[u8], domain: u64) -> SignatureFuture {
+        match self.next_fault() {
+            Some(SignerFault::Reject) => return Box::new(future::err(())),
+            Some(SignerFault::Malformed) => {
+                return Box::new(future::ok(Signature::empty_signature()))
+            }
+            Some(SignerFault::Late(delay)) => thread::sleep(delay),
+            None => (),
+        }
+        self.keypair.sign_message(message, domain)
+    }
+}
+
+impl Display for TestSigner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.keypair)
+    }
+}
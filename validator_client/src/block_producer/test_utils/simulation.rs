@@ -0,0 +1,630 @@
+//! A deterministic simulation of many validators proposing blocks over many epochs.
+//!
+//! All duties are executed sequentially, in slot order, against a single scripted
+//! `TestBeaconNode`. Every block published to the node is attributed to the validator whose duty
+//! published it, so the report can be checked for slashable proposals.
+use super::{Fault, TestBeaconNode};
+use crate::block_latency::PublishWindow;
+use crate::block_producer::{BlockProducer, Error, ValidatorEvent};
+use crate::notifier::Notifier;
+use crate::proposal_timing::ProposalTimings;
+use crate::signing_lease::SigningLease;
+use crate::slashing_protection::SlashingProtection;
+use futures::Future;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::runtime::current_thread::Runtime;
+use types::test_utils::generate_deterministic_keypairs;
+use types::{ChainSpec, EthSpec, Fork, Hash256, Keypair, Slot};
+
+/// A block which was signed and published during a simulation.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SignedProposal {
+    /// The index of the validator which signed the block.
+    pub validator_index: usize,
+    /// The slot of the block.
+    pub slot: Slot,
+    /// The signed root of the block.
+    pub signed_root: Hash256,
+}
+
+/// The results of `Simulation::run`.
+#[derive(Debug, PartialEq)]
+pub struct SimulationReport {
+    /// The slot, validator index and result of every duty, in execution order.
+    pub outcomes: Vec<(Slot, usize, Result<ValidatorEvent, Error>)>,
+    /// Every block published during the simulation, in order.
+    pub proposals: Vec<SignedProposal>,
+}
+
+impl SimulationReport {
+    /// Returns all pairs of proposals which would be slashable (i.e., distinct blocks signed by
+    /// the same validator at the same slot).
+    pub fn slashable_proposals(&self) -> Vec<(&SignedProposal, &SignedProposal)> {
+        let mut slashable = vec![];
+        for (i, a) in self.proposals.iter().enumerate() {
+            for b in &self.proposals[i + 1..] {
+                if a.validator_index == b.validator_index
+                    && a.slot == b.slot
+                    && a.signed_root != b.signed_root
+                {
+                    slashable.push((a, b));
+                }
+            }
+        }
+        slashable
+    }
+
+    /// Returns the number of duties which resulted in `event`.
+    pub fn num_events(&self, event: &ValidatorEvent) -> usize {
+        self.outcomes
+            .iter()
+            .filter(|(_, _, result)| result.as_ref() == Ok(event))
+            .count()
+    }
+}
+
+/// Drives `validator_count` validators through a number of epochs of block proposals.
+///
+/// By default the proposer at each slot is `slot % validator_count`, each proposer has exactly
+/// one duty per slot and the beacon node never fails. Duplicate duties and beacon node faults may
+/// be scripted before calling `run`.
+pub struct Simulation<E: EthSpec> {
+    spec: Arc<ChainSpec>,
+    validators: Vec<Keypair>,
+    beacon_node: Arc<TestBeaconNode>,
+    slashing_protection: Arc<SlashingProtection>,
+    /// The slot and validator index of each duty, in execution order.
+    duties: Vec<(Slot, usize)>,
+    _phantom: PhantomData<E>,
+}
+
+impl<E: EthSpec> Simulation<E> {
+    /// Create a simulation with a proposal duty for every slot (excluding genesis) in the first
+    /// `epochs` epochs.
+    pub fn new(validator_count: usize, epochs: u64) -> Self {
+        let duties = (1..epochs * E::slots_per_epoch())
+            .map(|slot| (Slot::new(slot), slot as usize % validator_count))
+            .collect();
+
+        Self {
+            spec: Arc::new(E::default_spec()),
+            validators: generate_deterministic_keypairs(validator_count),
+            beacon_node: Arc::new(TestBeaconNode::default()),
+            slashing_protection: Arc::new(SlashingProtection::new()),
+            signing_lease: Arc::new(SigningLease::disabled()),
+            duties,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Execute the proposal duty at `slot` a second time, immediately after the first.
+    pub fn duplicate_duty(mut self, slot: Slot) -> Self {
+        if let Some(i) = self.duties.iter().position(|(s, _)| *s == slot) {
+            let duty = self.duties[i];
+            self.duties.insert(i + 1, duty);
+        }
+        self
+    }
+
+    /// Inject `fault` into the next block production request for `slot`.
+    pub fn inject_fault(self, slot: Slot, fault: Fault) -> Self {
+        self.beacon_node.push_fault(slot, fault);
+        self
+    }
+
+    /// Fail the next `count` attempts to publish a block at `slot`.
+    pub fn fail_publish(self, slot: Slot, count: u64) -> Self {
+        self.beacon_node.fail_publish(slot, count);
+        self
+    }
+
+    /// Returns the beacon node used by the simulation.
+    pub fn beacon_node(&self) -> &TestBeaconNode {
+        &self.beacon_node
+    }
+
+    /// Execute all duties, in order.
+    ///
+    /// Each duty has a full slot in which to publish its block.
+    pub fn run(&self) -> SimulationReport {
+        let mut runtime = Runtime::new().expect("should build runtime");
+        let slot_duration = Duration::from_secs(self.spec.seconds_per_slot);
+        let mut outcomes = vec![];
+        let mut proposals = vec![];
+
+        for &(slot, validator_index) in &self.duties {
+            let num_published = self.beacon_node.published_blocks().len();
+
+            let block_producer = BlockProducer {
+                fork: Fork::genesis(E::genesis_epoch()),
+                slot,
+                spec: self.spec.clone(),
+                beacon_node: self.beacon_node.clone(),
+                signer: self.validators[validator_index].clone(),
+                slots_per_epoch: E::slots_per_epoch(),
+                slashing_protection: self.slashing_protection.clone(),
+                signing_lease: Arc::new(SigningLease::disabled()),
+                fee_recipient: None,
+                slot_start: Instant::now(),
+                deadline: Instant::now() + slot_duration,
+                timings: Arc::new(ProposalTimings::default()),
+                publish_window: PublishWindow::default(),
+                notifier: Arc::new(Notifier::disabled()),
+                dry_run: false,
+                _phantom: PhantomData::<E>,
+            };
+            outcomes.push((
+                slot,
+                validator_index,
+                runtime.block_on(block_producer.produce_block()),
+            ));
+
+            // Duties are executed sequentially, so any new blocks were published by this duty.
+            proposals.extend(
+                self.beacon_node.published_blocks()[num_published..]
+                    .iter()
+                    .map(|(slot, signed_root)| SignedProposal {
+                        validator_index,
+                        slot: *slot,
+                        signed_root: *signed_root,
+                    }),
+            );
+        }
+
+        SimulationReport {
+            outcomes,
+            proposals,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_producer::test_utils::{SignerFault, TestSigner};
+    use crate::block_producer::{BeaconNodeError, BlockViolation};
+    use crate::signing_lease::{LeaseBackend, SigningLeaseConfig};
+    use tree_hash::SignedRoot;
+    use types::MinimalEthSpec;
+
+    type E = MinimalEthSpec;
+
+    const VALIDATOR_COUNT: usize = 4;
+    const EPOCHS: u64 = 2;
+
+    fn num_slots() -> usize {
+        (EPOCHS * E::slots_per_epoch()) as usize - 1
+    }
+
+    #[test]
+    fn all_duties_produce_blocks() {
+        let report = Simulation::<E>::new(VALIDATOR_COUNT, EPOCHS).run();
+
+        assert_eq!(report.outcomes.len(), num_slots());
+        assert_eq!(report.proposals.len(), num_slots());
+        assert!(report.slashable_proposals().is_empty());
+    }
+
+    #[test]
+    fn duplicate_duties_are_not_slashable() {
+        let slot = Slot::new(3);
+        let report = Simulation::<E>::new(VALIDATOR_COUNT, EPOCHS)
+            .duplicate_duty(slot)
+            .duplicate_duty(slot)
+            .run();
+
+        assert_eq!(report.outcomes.len(), num_slots() + 2);
+        assert_eq!(
+            report.num_events(&ValidatorEvent::SlashableBlockNotProduced(slot)),
+            2
+        );
+        assert_eq!(report.proposals.len(), num_slots());
+        assert!(report.slashable_proposals().is_empty());
+    }
+
+    #[test]
+    fn injected_failures_are_reported() {
+        let failed = Slot::new(2);
+        let unavailable = Slot::new(5);
+        let retried = Slot::new(7);
+        let report = Simulation::<E>::new(VALIDATOR_COUNT, EPOCHS)
+            .inject_fault(failed, Fault::RemoteFailure)
+            .inject_fault(unavailable, Fault::Unavailable)
+            .inject_fault(retried, Fault::RemoteFailure)
+            .duplicate_duty(retried)
+            .run();
+
+        let outcome = |slot: Slot| {
+            report
+                .outcomes
+                .iter()
+                .filter(|(s, _, _)| *s == slot)
+                .map(|(_, _, result)| result)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            outcome(failed),
+            vec![&Err(Error::BeaconNode {
+                slot: failed,
+                source: BeaconNodeError::RemoteFailure("Injected fault".to_string())
+            })]
+        );
+        assert_eq!(
+            outcome(unavailable),
+            vec![&Ok(ValidatorEvent::BeaconNodeUnableToProduceBlock(
+                unavailable
+            ))]
+        );
+        assert_eq!(
+            outcome(retried)[1],
+            &Ok(ValidatorEvent::BlockProduced(retried))
+        );
+        assert_eq!(report.proposals.len(), num_slots() - 2);
+        assert!(report.slashable_proposals().is_empty());
+    }
+
+    #[test]
+    fn blinded_and_full_blocks_share_slashing_protection() {
+        let simulation = Simulation::<E>::new(VALIDATOR_COUNT, EPOCHS);
+        let slot = Slot::new(3);
+        let block_producer = BlockProducer {
+            fork: Fork::genesis(E::genesis_epoch()),
+            slot,
+            spec: simulation.spec.clone(),
+            beacon_node: simulation.beacon_node.clone(),
+            signer: simulation.validators[0].clone(),
+            slots_per_epoch: E::slots_per_epoch(),
+            slashing_protection: simulation.slashing_protection.clone(),
+            signing_lease: Arc::new(SigningLease::disabled()),
+            fee_recipient: None,
+            slot_start: Instant::now(),
+            deadline: Instant::now(),
+            timings: Arc::new(ProposalTimings::default()),
+            publish_window: PublishWindow::default(),
+            notifier: Arc::new(Notifier::disabled()),
+            dry_run: false,
+            _phantom: PhantomData::<E>,
+        };
+
+        assert_eq!(
+            block_producer
+                .produce_blinded_block(simulation.beacon_node.clone())
+                .wait(),
+            Ok(ValidatorEvent::BlockProduced(slot))
+        );
+        // The beacon node returns a distinct block for the second request at this slot.
+        assert_eq!(
+            block_producer.produce_block().wait(),
+            Ok(ValidatorEvent::SlashableBlockNotProduced(slot))
+        );
+        assert_eq!(simulation.beacon_node().published_slots(), vec![slot]);
+    }
+
+    #[test]
+    fn only_the_holder_of_a_lease_proposes() {
+        let simulation = Simulation::<E>::new(VALIDATOR_COUNT, EPOCHS);
+        let dir = tempfile::tempdir().expect("should create temp dir");
+        let slot = Slot::new(3);
+        // Each instance has its own slashing protection, which cannot prevent the other from
+        // proposing a conflicting block.
+        let block_producer = |owner: &str| {
+            let mut config = SigningLeaseConfig::new(LeaseBackend::File(dir.path().to_path_buf()));
+            config.owner = Some(owner.to_string());
+            BlockProducer {
+                fork: Fork::genesis(E::genesis_epoch()),
+                slot,
+                spec: simulation.spec.clone(),
+                beacon_node: simulation.beacon_node.clone(),
+                signer: simulation.validators[0].clone(),
+                slots_per_epoch: E::slots_per_epoch(),
+                slashing_protection: Arc::new(SlashingProtection::new()),
+                signing_lease: Arc::new(
+                    SigningLease::new(&config, 6, 8).expect("should create signing lease"),
+                ),
+                fee_recipient: None,
+                slot_start: Instant::now(),
+                deadline: Instant::now(),
+                timings: Arc::new(ProposalTimings::default()),
+                publish_window: PublishWindow::default(),
+                notifier: Arc::new(Notifier::disabled()),
+                dry_run: false,
+                _phantom: PhantomData::<E>,
+            }
+        };
+
+        assert_eq!(
+            block_producer("active").produce_block().wait(),
+            Ok(ValidatorEvent::BlockProduced(slot))
+        );
+        assert_eq!(
+            block_producer("standby").produce_block().wait(),
+            Ok(ValidatorEvent::LeaseHeld(slot, "active".to_string()))
+        );
+        assert_eq!(simulation.beacon_node().published_slots(), vec![slot]);
+    }
+
+    #[test]
+    fn records_proposal_timings() {
+        let simulation = Simulation::<E>::new(VALIDATOR_COUNT, EPOCHS);
+        let timings = Arc::new(ProposalTimings::default());
+        let block_producer = BlockProducer {
+            fork: Fork::genesis(E::genesis_epoch()),
+            slot: Slot::new(3),
+            spec: simulation.spec.clone(),
+            beacon_node: simulation.beacon_node.clone(),
+            signer: simulation.validators[0].clone(),
+            slots_per_epoch: E::slots_per_epoch(),
+            slashing_protection: simulation.slashing_protection.clone(),
+            signing_lease: Arc::new(SigningLease::disabled()),
+            fee_recipient: None,
+            slot_start: Instant::now(),
+            deadline: Instant::now(),
+            timings: timings.clone(),
+            publish_window: PublishWindow::default(),
+            notifier: Arc::new(Notifier::disabled()),
+            dry_run: false,
+            _phantom: PhantomData::<E>,
+        };
+
+        assert_eq!(
+            block_producer.produce_block().wait(),
+            Ok(ValidatorEvent::BlockProduced(Slot::new(3)))
+        );
+        let recent = timings.recent();
+        assert_eq!(recent.len(), 1);
+        let timing = &recent[0];
+        assert_eq!(timing.slot, Slot::new(3));
+        assert_eq!(
+            timing.validator,
+            simulation.validators[0].pk.as_hex_string()
+        );
+        assert!(timing.received_ms <= timing.signed_ms);
+        assert!(timing.signed_ms <= timing.published_ms);
+        assert!(timing.received_ms.is_some());
+    }
+
+    #[test]
+    fn dry_run_blocks_are_not_published() {
+        let simulation = Simulation::<E>::new(VALIDATOR_COUNT, EPOCHS);
+        let block_producer = |slot| BlockProducer {
+            fork: Fork::genesis(E::genesis_epoch()),
+            slot,
+            spec: simulation.spec.clone(),
+            beacon_node: simulation.beacon_node.clone(),
+            signer: simulation.validators[0].clone(),
+            slots_per_epoch: E::slots_per_epoch(),
+            slashing_protection: simulation.slashing_protection.clone(),
+            signing_lease: Arc::new(SigningLease::disabled()),
+            fee_recipient: None,
+            slot_start: Instant::now(),
+            deadline: Instant::now(),
+            timings: Arc::new(ProposalTimings::default()),
+            publish_window: PublishWindow::default(),
+            notifier: Arc::new(Notifier::disabled()),
+            dry_run: true,
+            _phantom: PhantomData::<E>,
+        };
+
+        let full = block_producer(Slot::new(3));
+        let blinded = block_producer(Slot::new(4));
+        for (producer, outcome) in vec![
+            (&full, full.produce_block().wait()),
+            (
+                &blinded,
+                blinded
+                    .produce_blinded_block(simulation.beacon_node.clone())
+                    .wait(),
+            ),
+        ] {
+            match outcome {
+                Ok(ValidatorEvent::BlockNotPublished(header)) => {
+                    assert_eq!(header.slot, producer.slot);
+                    // The block is signed by a throwaway key, rather than the validator.
+                    assert!(!header.signature.verify(
+                        &header.signed_root(),
+                        producer.proposer_domain(),
+                        &simulation.validators[0].pk
+                    ));
+                }
+                other => panic!("block should not be published, got {:?}", other),
+            }
+        }
+        assert!(simulation.beacon_node().published_slots().is_empty());
+    }
+
+    #[test]
+    fn invalid_blocks_are_not_signed() {
+        let slot = Slot::new(5);
+        let violations = vec![
+            BlockViolation::WrongSlot {
+                requested: slot,
+                block: slot + 1,
+            },
+            BlockViolation::UnknownParent,
+            BlockViolation::ZeroStateRoot,
+            BlockViolation::IncorrectRandaoReveal,
+        ];
+
+        let mut simulation = Simulation::<E>::new(VALIDATOR_COUNT, EPOCHS);
+        for violation in &violations {
+            simulation = simulation
+                .inject_fault(slot, Fault::InvalidBlock(violation.clone()))
+                .duplicate_duty(slot);
+        }
+        let report = simulation.run();
+
+        for violation in violations {
+            assert_eq!(
+                report.num_events(&ValidatorEvent::InvalidBlockFromBeaconNode(slot, violation)),
+                1
+            );
+        }
+        // The rejected blocks were not recorded as signed, so the final duty is not refused.
+        assert_eq!(report.num_events(&ValidatorEvent::BlockProduced(slot)), 1);
+        assert_eq!(report.proposals.len(), num_slots());
+        assert!(report.slashable_proposals().is_empty());
+    }
+
+    #[test]
+    fn late_responses_are_not_slashable() {
+        let slot = Slot::new(4);
+        let report = Simulation::<E>::new(VALIDATOR_COUNT, EPOCHS)
+            .inject_fault(slot, Fault::Late(Duration::from_millis(10)))
+            .duplicate_duty(slot)
+            .run();
+
+        assert_eq!(report.num_events(&ValidatorEvent::BlockProduced(slot)), 1);
+        assert!(report.slashable_proposals().is_empty());
+    }
+
+    #[test]
+    fn transient_publish_failures_are_retried() {
+        let slot = Slot::new(6);
+        let report = Simulation::<E>::new(VALIDATOR_COUNT, EPOCHS)
+            .fail_publish(slot, 2)
+            .run();
+
+        assert_eq!(report.num_events(&ValidatorEvent::BlockProduced(slot)), 1);
+        assert_eq!(report.proposals.len(), num_slots());
+        assert!(report.slashable_proposals().is_empty());
+    }
+
+    #[test]
+    fn publish_failures_are_reported_at_the_deadline() {
+        let slot = Slot::new(3);
+        let simulation = Simulation::<E>::new(VALIDATOR_COUNT, EPOCHS).fail_publish(slot, 1);
+        let block_producer = BlockProducer {
+            fork: Fork::genesis(E::genesis_epoch()),
+            slot,
+            spec: simulation.spec.clone(),
+            beacon_node: simulation.beacon_node.clone(),
+            signer: simulation.validators[0].clone(),
+            slots_per_epoch: E::slots_per_epoch(),
+            slashing_protection: simulation.slashing_protection.clone(),
+            signing_lease: Arc::new(SigningLease::disabled()),
+            fee_recipient: None,
+            slot_start: Instant::now(),
+            deadline: Instant::now(),
+            timings: Arc::new(ProposalTimings::default()),
+            publish_window: PublishWindow::default(),
+            notifier: Arc::new(Notifier::disabled()),
+            dry_run: false,
+            _phantom: PhantomData::<E>,
+        };
+
+        assert_eq!(
+            block_producer.produce_block().wait(),
+            Ok(ValidatorEvent::PublishBlockFailed(
+                slot,
+                "Injected publish failure".to_string()
+            ))
+        );
+        assert!(simulation.beacon_node().published_slots().is_empty());
+    }
+
+    #[test]
+    fn signer_faults_are_reported() {
+        let simulation = Simulation::<E>::new(VALIDATOR_COUNT, EPOCHS);
+        let signer = TestSigner::new(simulation.validators[0].clone());
+        let block_producer = |slot| BlockProducer {
+            fork: Fork::genesis(E::genesis_epoch()),
+            slot: Slot::new(slot),
+            spec: simulation.spec.clone(),
+            beacon_node: simulation.beacon_node.clone(),
+            signer: signer.clone(),
+            slots_per_epoch: E::slots_per_epoch(),
+            slashing_protection: simulation.slashing_protection.clone(),
+            signing_lease: Arc::new(SigningLease::disabled()),
+            fee_recipient: None,
+            slot_start: Instant::now(),
+            deadline: Instant::now(),
+            timings: Arc::new(ProposalTimings::default()),
+            publish_window: PublishWindow::default(),
+            notifier: Arc::new(Notifier::disabled()),
+            dry_run: false,
+            _phantom: PhantomData::<E>,
+        };
+
+        // Each block is signed twice: first the randao reveal, then the block itself.
+        let cases = vec![
+            (Some(SignerFault::Reject), None),
+            (None, Some(SignerFault::Reject)),
+            (Some(SignerFault::Malformed), None),
+            (None, Some(SignerFault::Malformed)),
+        ];
+        for (slot, (randao_fault, block_fault)) in (1..).zip(cases) {
+            signer.push_fault(randao_fault);
+            signer.push_fault(block_fault);
+            assert_eq!(
+                block_producer(slot).produce_block().wait(),
+                Ok(ValidatorEvent::SignerRejection(Slot::new(slot)))
+            );
+        }
+        assert!(simulation.beacon_node().published_slots().is_empty());
+
+        // Late signatures are still valid, and a signer may recover from intermittent faults.
+        signer.push_fault(Some(SignerFault::Late(Duration::from_millis(10))));
+        assert_eq!(
+            block_producer(5).produce_block().wait(),
+            Ok(ValidatorEvent::BlockProduced(Slot::new(5)))
+        );
+        signer.push_fault(Some(SignerFault::Reject));
+        assert_eq!(
+            block_producer(6).produce_block().wait(),
+            Ok(ValidatorEvent::SignerRejection(Slot::new(6)))
+        );
+        assert_eq!(
+            block_producer(7).produce_block().wait(),
+            Ok(ValidatorEvent::BlockProduced(Slot::new(7)))
+        );
+        assert_eq!(
+            simulation.beacon_node().published_slots(),
+            vec![Slot::new(5), Slot::new(7)]
+        );
+    }
+
+    #[test]
+    fn duplicate_and_old_blocks_are_not_slashable() {
+        let duplicated = Slot::new(3);
+        let old = Slot::new(2);
+        let returned_old = Slot::new(6);
+        let report = Simulation::<E>::new(VALIDATOR_COUNT, EPOCHS)
+            .duplicate_duty(duplicated)
+            .inject_fault(duplicated, Fault::Late(Duration::from_millis(1)))
+            .inject_fault(duplicated, Fault::Duplicate)
+            .inject_fault(returned_old, Fault::Old(old))
+            .duplicate_duty(returned_old)
+            .run();
+
+        // The duplicate block is identical to the one already signed, so it may be signed again.
+        assert_eq!(
+            report.num_events(&ValidatorEvent::BlockProduced(duplicated)),
+            2
+        );
+        let duplicates = report
+            .proposals
+            .iter()
+            .filter(|proposal| proposal.slot == duplicated)
+            .collect::<Vec<_>>();
+        assert_eq!(duplicates.len(), 2);
+        assert_eq!(duplicates[0].signed_root, duplicates[1].signed_root);
+        assert_eq!(
+            report.num_events(&ValidatorEvent::InvalidBlockFromBeaconNode(
+                returned_old,
+                BlockViolation::WrongSlot {
+                    requested: returned_old,
+                    block: old,
+                }
+            )),
+            1
+        );
+        assert_eq!(
+            report.num_events(&ValidatorEvent::BlockProduced(returned_old)),
+            1
+        );
+        assert!(report.slashable_proposals().is_empty());
+    }
+}
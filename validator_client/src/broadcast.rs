@@ -0,0 +1,376 @@
+//! Publishes signed blocks and attestations to several beacon nodes at once.
+//!
+//! A message published only to the beacon node used for duties (the "primary") is lost if that
+//! node fails to propagate it (e.g., because it has few peers). A `Broadcast` wraps the clients of
+//! the primary and any number of other beacon nodes: requests for data are sent to a single node,
+//! whilst signed messages are published to every node concurrently. A message is published if any
+//! node accepts it.
+//!
+//! Every request is scored (see `endpoint_scores`), and requests for data are sent to whichever
+//! node scores best for that kind of request, which is the primary unless it is failing, slower
+//! than another node or not synced. Some requests for data are also copied to another node, whose
+//! response is only scored.
+use crate::attestation_producer::{BeaconNodeAttestation, BlockAttestations};
+use crate::block_producer::{BeaconNodeBlock, BeaconNodeError, BeaconNodeFuture, PublishOutcome};
+use crate::endpoint_scores::{EndpointScores, RequestKind};
+use futures::{future, Future};
+use slog::{debug, warn};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::executor::{DefaultExecutor, Executor};
+use types::{Address, Attestation, AttestationData, BeaconBlockAny, EthSpec, Signature, Slot};
+
+/// The result of publishing a message to a single beacon node.
+pub type NodeResult = (String, Result<PublishOutcome, BeaconNodeError>);
+
+/// The number of messages each beacon node has accepted or failed to accept.
+#[derive(Default)]
+pub struct NodeStats {
+    pub accepted: AtomicUsize,
+    pub failed: AtomicUsize,
+}
+
+impl NodeStats {
+    fn record(&self, result: &Result<PublishOutcome, BeaconNodeError>) {
+        match result {
+            Ok(PublishOutcome::Valid) => self.accepted.fetch_add(1, Ordering::Relaxed),
+            _ => self.failed.fetch_add(1, Ordering::Relaxed),
+        };
+    }
+}
+
+struct Node<N> {
+    name: String,
+    client: Arc<N>,
+    stats: Arc<NodeStats>,
+}
+
+impl<N> Node<N> {
+    fn new(name: String, client: Arc<N>) -> Self {
+        Self {
+            name,
+            client,
+            stats: Arc::new(NodeStats::default()),
+        }
+    }
+}
+
+/// Publishes to the primary beacon node and every other node.
+pub struct Broadcast<N> {
+    primary: Node<N>,
+    others: Vec<Node<N>>,
+    scores: Arc<EndpointScores>,
+    log: slog::Logger,
+}
+
+impl<N> Broadcast<N> {
+    /// Create a `Broadcast` which publishes to `primary` and each of `others`, each named by its
+    /// address.
+    pub fn new(
+        primary: (String, Arc<N>),
+        others: Vec<(String, Arc<N>)>,
+        log: slog::Logger,
+    ) -> Self {
+        Self {
+            primary: Node::new(primary.0, primary.1),
+            others: others
+                .into_iter()
+                .map(|(name, client)| Node::new(name, client))
+                .collect(),
+            scores: Arc::new(EndpointScores::default()),
+            log,
+        }
+    }
+
+    /// Records the requests to each node in `scores`, by which requests for data are routed.
+    pub fn with_scores(mut self, scores: Arc<EndpointScores>) -> Self {
+        for node in self.nodes() {
+            scores.add_endpoint(&node.name);
+        }
+        self.scores = scores;
+        self
+    }
+
+    /// Returns the name and publishing statistics of each node, starting with the primary.
+    pub fn stats(&self) -> Vec<(String, Arc<NodeStats>)> {
+        self.nodes()
+            .map(|node| (node.name.clone(), node.stats.clone()))
+            .collect()
+    }
+
+    fn nodes(&self) -> impl Iterator<Item = &Node<N>> {
+        std::iter::once(&self.primary).chain(self.others.iter())
+    }
+
+    /// Sends a request of `kind` with `request` to the node which scores best for it.
+    ///
+    /// If the scores call for a probe, the request is also sent to another node in the background
+    /// (on the default executor, if any), so that the scores of that node are kept up to date.
+    fn request<T, F>(&self, kind: RequestKind, request: F) -> BeaconNodeFuture<T>
+    where
+        T: Send + 'static,
+        F: Fn(&N) -> BeaconNodeFuture<T>,
+    {
+        let names: Vec<&str> = self.nodes().map(|node| node.name.as_str()).collect();
+        let node = self
+            .nodes()
+            .nth(self.scores.best(kind, &names))
+            .unwrap_or(&self.primary);
+
+        if let Some(probed) = self
+            .scores
+            .probe(kind, &names)
+            .and_then(|index| self.nodes().nth(index))
+        {
+            let probe = self
+                .scored(probed, kind, request(&probed.client))
+                .then(|_| -> Result<(), ()> { Ok(()) });
+            if DefaultExecutor::current().spawn(Box::new(probe)).is_err() {
+                debug!(self.log, "Unable to probe beacon node"; "node" => &probed.name);
+            }
+        }
+
+        self.scored(node, kind, request(&node.client))
+    }
+
+    /// Records the outcome and latency of `request` to `node` once it completes.
+    fn scored<T: Send + 'static>(
+        &self,
+        node: &Node<N>,
+        kind: RequestKind,
+        request: BeaconNodeFuture<T>,
+    ) -> BeaconNodeFuture<T> {
+        let started = Instant::now();
+        let name = node.name.clone();
+        let scores = self.scores.clone();
+        Box::new(request.then(move |result| {
+            scores.record(&name, kind, result.is_ok(), started.elapsed());
+            result
+        }))
+    }
+
+    /// Publishes a message with `publish` to every node concurrently, resolving to the aggregate
+    /// outcome once every node has responded.
+    fn broadcast<F>(
+        &self,
+        message: &'static str,
+        kind: RequestKind,
+        publish: F,
+    ) -> BeaconNodeFuture<PublishOutcome>
+    where
+        F: Fn(&N) -> BeaconNodeFuture<PublishOutcome>,
+    {
+        let publishes: Vec<_> = self
+            .nodes()
+            .map(|node| {
+                let started = Instant::now();
+                let name = node.name.clone();
+                let stats = node.stats.clone();
+                let scores = self.scores.clone();
+                publish(&node.client).then(move |result| -> Result<NodeResult, BeaconNodeError> {
+                    stats.record(&result);
+                    // A message which is invalid is not the fault of the node.
+                    let available = match &result {
+                        Ok(PublishOutcome::Unavailable(_)) | Err(_) => false,
+                        Ok(_) => true,
+                    };
+                    scores.record(&name, kind, available, started.elapsed());
+                    Ok((name, result))
+                })
+            })
+            .collect();
+
+        let log = self.log.clone();
+        Box::new(future::join_all(publishes).and_then(move |results| {
+            for (node, result) in &results {
+                match result {
+                    Ok(PublishOutcome::Valid) => {
+                        debug!(log, "Published to beacon node"; "message" => message, "node" => node)
+                    }
+                    Ok(outcome) => {
+                        warn!(log, "Beacon node did not publish"; "message" => message, "node" => node, "outcome" => format!("{:?}", outcome))
+                    }
+                    Err(e) => {
+                        warn!(log, "Unable to publish to beacon node"; "message" => message, "node" => node, "error" => e.to_string())
+                    }
+                }
+            }
+            aggregate(results)
+        }))
+    }
+}
+
+/// Combines the results of publishing a message to each node, the first of which is the primary.
+///
+/// The outcome is `Valid` if any node accepted the message, otherwise it is the result of the
+/// primary (i.e., the same as if the message had not been broadcast).
+pub fn aggregate(results: Vec<NodeResult>) -> Result<PublishOutcome, BeaconNodeError> {
+    if results
+        .iter()
+        .any(|(_, result)| *result == Ok(PublishOutcome::Valid))
+    {
+        return Ok(PublishOutcome::Valid);
+    }
+
+    results
+        .into_iter()
+        .next()
+        .map(|(_, result)| result)
+        .unwrap_or_else(|| {
+            Err(BeaconNodeError::RemoteFailure(
+                "No beacon nodes".to_string(),
+            ))
+        })
+}
+
+impl<N: BeaconNodeBlock> BeaconNodeBlock for Broadcast<N> {
+    /// Requests a block from the best node.
+    fn produce_beacon_block<T: EthSpec>(
+        &self,
+        slot: Slot,
+        randao_reveal: &Signature,
+        fee_recipient: Option<Address>,
+    ) -> BeaconNodeFuture<Option<BeaconBlockAny<T>>> {
+        self.request(RequestKind::ProduceBlock, |client| {
+            client.produce_beacon_block(slot, randao_reveal, fee_recipient)
+        })
+    }
+
+    /// Publishes `block` to every node.
+    fn publish_beacon_block<T: EthSpec>(
+        &self,
+        block: BeaconBlockAny<T>,
+    ) -> BeaconNodeFuture<PublishOutcome> {
+        self.broadcast("block", RequestKind::PublishBlock, |client| {
+            client.publish_beacon_block(block.clone())
+        })
+    }
+}
+
+impl<N: BeaconNodeAttestation> BeaconNodeAttestation for Broadcast<N> {
+    /// Requests attestation data from the best node.
+    fn produce_attestation_data(
+        &self,
+        slot: Slot,
+        shard: u64,
+    ) -> BeaconNodeFuture<AttestationData> {
+        self.request(RequestKind::AttestationData, |client| {
+            client.produce_attestation_data(slot, shard)
+        })
+    }
+
+    /// Publishes `attestation` to every node.
+    fn publish_attestation<T: EthSpec>(
+        &self,
+        attestation: Attestation<T>,
+    ) -> BeaconNodeFuture<PublishOutcome> {
+        self.broadcast("attestation", RequestKind::PublishAttestation, |client| {
+            client.publish_attestation(attestation.clone())
+        })
+    }
+
+    /// Requests whether the best node knows the attestation.
+    fn attestation_known(
+        &self,
+        slot: Slot,
+        shard: u64,
+        committee_index: usize,
+    ) -> BeaconNodeFuture<bool> {
+        self.request(RequestKind::Lookup, |client| {
+            client.attestation_known(slot, shard, committee_index)
+        })
+    }
+
+    /// Requests the attestations in a block from the best node.
+    fn block_attestations<T: EthSpec>(
+        &self,
+        slot: Slot,
+    ) -> BeaconNodeFuture<Option<BlockAttestations<T>>> {
+        self.request(RequestKind::Lookup, |client| {
+            client.block_attestations(slot)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_producer::test_utils::TestBeaconNode;
+    use slog::o;
+    use types::{BeaconBlock, MinimalEthSpec};
+
+    fn broadcast(nodes: usize) -> (Broadcast<TestBeaconNode>, Vec<Arc<TestBeaconNode>>) {
+        let clients: Vec<_> = (0..nodes)
+            .map(|_| Arc::new(TestBeaconNode::default()))
+            .collect();
+        let mut named = clients
+            .iter()
+            .enumerate()
+            .map(|(i, client)| (format!("node-{}", i), client.clone()));
+        let primary = named.next().expect("should have a primary");
+        let log = slog::Logger::root(slog::Discard, o!());
+
+        (Broadcast::new(primary, named.collect(), log), clients)
+    }
+
+    fn block(slot: u64) -> BeaconBlockAny<MinimalEthSpec> {
+        let mut block = BeaconBlock::empty(&MinimalEthSpec::default_spec());
+        block.slot = Slot::new(slot);
+        block.into()
+    }
+
+    #[test]
+    fn publishes_to_every_node() {
+        let (broadcast, clients) = broadcast(3);
+
+        assert_eq!(
+            broadcast.publish_beacon_block(block(1)).wait(),
+            Ok(PublishOutcome::Valid)
+        );
+        for client in &clients {
+            assert_eq!(client.published_slots(), vec![Slot::new(1)]);
+        }
+    }
+
+    #[test]
+    fn valid_if_any_node_accepts() {
+        let (broadcast, clients) = broadcast(2);
+        clients[0].fail_publish(Slot::new(1), 1);
+
+        assert_eq!(
+            broadcast.publish_beacon_block(block(1)).wait(),
+            Ok(PublishOutcome::Valid)
+        );
+
+        let stats: Vec<_> = broadcast
+            .stats()
+            .into_iter()
+            .map(|(name, stats)| {
+                (
+                    name,
+                    stats.accepted.load(Ordering::Relaxed),
+                    stats.failed.load(Ordering::Relaxed),
+                )
+            })
+            .collect();
+        assert_eq!(
+            stats,
+            vec![("node-0".to_string(), 0, 1), ("node-1".to_string(), 1, 0)]
+        );
+    }
+
+    #[test]
+    fn primary_outcome_if_no_node_accepts() {
+        let unavailable = || Ok(PublishOutcome::Unavailable("unavailable".to_string()));
+        let results = vec![
+            ("node-0".to_string(), unavailable()),
+            (
+                "node-1".to_string(),
+                Err(BeaconNodeError::RemoteFailure("failed".to_string())),
+            ),
+        ];
+
+        assert_eq!(aggregate(results), unavailable());
+    }
+}
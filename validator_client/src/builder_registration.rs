@@ -0,0 +1,301 @@
+//! Registration of the proposal preferences (fee recipient and gas limit) of each validator with
+//! an external block builder.
+//!
+//! Registrations are submitted at the first duties update of every epoch, so that a builder which
+//! has restarted learns of them promptly. A registration is only signed again once the preferences
+//! of its validator change, since builders keep the registration with the latest timestamp.
+//!
+//! Validators without a fee recipient are not registered.
+use crate::block_producer::{BeaconNodeBlindedBlock, BeaconNodeError};
+use crate::fee_recipient::FeeRecipients;
+use crate::gas_limit::GasLimits;
+use crate::signer::Signer;
+use futures::{future, Future};
+use slog::{debug, warn};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tree_hash::SignedRoot;
+use types::{ChainSpec, Epoch, PublicKey, Signature, ValidatorRegistration};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("the builder refused the registrations: {0}")]
+    Builder(#[source] BeaconNodeError),
+    #[error("a lock was poisoned whilst registering validators")]
+    LockPoisoned,
+}
+
+/// The outcome of registering validators with the builder.
+#[derive(Debug, PartialEq)]
+pub enum RegistrationOutcome {
+    /// The validators have already been registered in this epoch, or none have a fee recipient.
+    NothingToRegister,
+    /// `registered` validators were registered. The signers of `unsigned` refused to sign.
+    Registered {
+        registered: usize,
+        unsigned: Vec<PublicKey>,
+    },
+}
+
+/// A future which resolves to a signed registration, or to the public key of a signer which
+/// refused to sign.
+type SigningFuture =
+    Box<dyn Future<Item = Result<ValidatorRegistration, PublicKey>, Error = Error> + Send>;
+
+/// Registers all validators with the builder, once per epoch.
+pub struct BuilderRegistrar<C, S> {
+    builder: Arc<C>,
+    signers: Arc<Vec<S>>,
+    fee_recipients: Arc<FeeRecipients>,
+    gas_limits: GasLimits,
+    /// The builder domain, which is the same in every fork.
+    domain: u64,
+    /// The latest signed registration of each validator.
+    signed: RwLock<HashMap<PublicKey, ValidatorRegistration>>,
+    /// The latest epoch in which the validators were registered.
+    registered_epoch: RwLock<Option<Epoch>>,
+}
+
+impl<C: BeaconNodeBlindedBlock + 'static, S: Signer + 'static> BuilderRegistrar<C, S> {
+    pub fn new(
+        builder: Arc<C>,
+        signers: Arc<Vec<S>>,
+        fee_recipients: Arc<FeeRecipients>,
+        gas_limits: GasLimits,
+        spec: &ChainSpec,
+    ) -> Self {
+        Self {
+            builder,
+            signers,
+            fee_recipients,
+            gas_limits,
+            domain: spec.get_builder_domain(),
+            signed: RwLock::new(HashMap::new()),
+            registered_epoch: RwLock::new(None),
+        }
+    }
+
+    /// Registers all validators with a fee recipient with the builder, unless they have already
+    /// been registered in `epoch`.
+    pub fn register(
+        self: Arc<Self>,
+        epoch: Epoch,
+    ) -> Box<dyn Future<Item = RegistrationOutcome, Error = Error> + Send> {
+        let signings = match self.signings(epoch) {
+            Ok(signings) => signings,
+            Err(e) => return Box::new(future::err(e)),
+        };
+
+        if signings.is_empty() {
+            return Box::new(future::ok(RegistrationOutcome::NothingToRegister));
+        }
+
+        Box::new(future::join_all(signings).and_then(
+            move |signed| -> Box<dyn Future<Item = RegistrationOutcome, Error = Error> + Send> {
+                let mut registrations = vec![];
+                let mut unsigned = vec![];
+                for result in signed {
+                    match result {
+                        Ok(registration) => registrations.push(registration),
+                        Err(pubkey) => unsigned.push(pubkey),
+                    }
+                }
+
+                if let Err(e) = self.cache(&registrations) {
+                    return Box::new(future::err(e));
+                }
+
+                let registered = registrations.len();
+                if registered == 0 {
+                    return Box::new(future::ok(RegistrationOutcome::Registered {
+                        registered,
+                        unsigned,
+                    }));
+                }
+
+                Box::new(
+                    self.builder
+                        .register_validators(registrations)
+                        .map_err(Error::Builder)
+                        .and_then(move |()| {
+                            *self
+                                .registered_epoch
+                                .write()
+                                .map_err(|_| Error::LockPoisoned)? = Some(epoch);
+                            Ok(RegistrationOutcome::Registered {
+                                registered,
+                                unsigned,
+                            })
+                        }),
+                )
+            },
+        ))
+    }
+
+    /// A future wrapping around `register()`, which logs its outcome.
+    pub fn run_registration(
+        self: Arc<Self>,
+        epoch: Epoch,
+        log: slog::Logger,
+    ) -> Box<dyn Future<Item = (), Error = ()> + Send> {
+        Box::new(self.register(epoch).then(move |result| {
+            match result {
+                Err(e) => {
+                    warn!(log, "Unable to register validators with the builder"; "error" => e.to_string())
+                }
+                Ok(RegistrationOutcome::Registered {
+                    registered,
+                    unsigned,
+                }) => {
+                    for pubkey in unsigned {
+                        warn!(log, "Unable to sign builder registration"; "validator" => pubkey.concatenated_hex_id());
+                    }
+                    debug!(log, "Registered validators with the builder"; "epoch" => epoch, "count" => registered);
+                }
+                Ok(RegistrationOutcome::NothingToRegister) => {}
+            }
+            Ok(())
+        }))
+    }
+
+    /// Returns a future resolving to the registration of each validator with a fee recipient, or
+    /// nothing if the validators have already been registered in `epoch`.
+    ///
+    /// Cached registrations are reused if the preferences of their validator are unchanged.
+    fn signings(&self, epoch: Epoch) -> Result<Vec<SigningFuture>, Error> {
+        if *self
+            .registered_epoch
+            .read()
+            .map_err(|_| Error::LockPoisoned)?
+            == Some(epoch)
+        {
+            return Ok(vec![]);
+        }
+
+        let signed = self.signed.read().map_err(|_| Error::LockPoisoned)?;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+
+        Ok(self
+            .signers
+            .iter()
+            .filter_map(|signer| {
+                let pubkey = signer.to_public();
+                let fee_recipient = self.fee_recipients.get(&pubkey)?;
+                let gas_limit = self.gas_limits.get(&pubkey);
+
+                if let Some(registration) = signed.get(&pubkey) {
+                    if registration.fee_recipient == fee_recipient
+                        && registration.gas_limit == gas_limit
+                    {
+                        let signing: SigningFuture = Box::new(future::ok(Ok(registration.clone())));
+                        return Some(signing);
+                    }
+                }
+
+                let mut registration = ValidatorRegistration {
+                    fee_recipient,
+                    gas_limit,
+                    timestamp,
+                    pubkey: pubkey.clone(),
+                    signature: Signature::empty_signature(),
+                };
+                let signing: SigningFuture = Box::new(
+                    signer
+                        .sign_message(&registration.signed_root(), self.domain)
+                        .then(move |result| {
+                            Ok(result
+                                .map(|signature| {
+                                    registration.signature = signature;
+                                    registration
+                                })
+                                .map_err(|()| pubkey))
+                        }),
+                );
+                Some(signing)
+            })
+            .collect())
+    }
+
+    /// Stores `registrations`, so they may be reused in later epochs.
+    fn cache(&self, registrations: &[ValidatorRegistration]) -> Result<(), Error> {
+        let mut signed = self.signed.write().map_err(|_| Error::LockPoisoned)?;
+        for registration in registrations {
+            signed.insert(registration.pubkey.clone(), registration.clone());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_producer::test_utils::TestBeaconNode;
+    use tokio::runtime::current_thread::Runtime;
+    use types::{Address, EthSpec, Keypair, MinimalEthSpec};
+
+    #[test]
+    fn registers_once_per_epoch() {
+        let spec = MinimalEthSpec::default_spec();
+        let keypairs = vec![Keypair::random(), Keypair::random()];
+        let listed = keypairs[0].pk.clone();
+
+        let mut per_validator = HashMap::new();
+        per_validator.insert(listed.clone(), Address::from_low_u64_be(1));
+        let fee_recipients = Arc::new(FeeRecipients {
+            default: None,
+            per_validator,
+        });
+
+        let builder = Arc::new(TestBeaconNode::default());
+        let registrar = Arc::new(BuilderRegistrar::new(
+            builder.clone(),
+            Arc::new(keypairs),
+            fee_recipients,
+            GasLimits::default(),
+            &spec,
+        ));
+
+        let mut runtime = Runtime::new().expect("should create runtime");
+        let mut register = |epoch| {
+            runtime
+                .block_on(registrar.clone().register(Epoch::new(epoch)))
+                .expect("should register")
+        };
+
+        // Only the validator with a fee recipient is registered.
+        assert_eq!(
+            register(1),
+            RegistrationOutcome::Registered {
+                registered: 1,
+                unsigned: vec![],
+            }
+        );
+        assert_eq!(register(1), RegistrationOutcome::NothingToRegister);
+        assert_eq!(
+            register(2),
+            RegistrationOutcome::Registered {
+                registered: 1,
+                unsigned: vec![],
+            }
+        );
+
+        // The registration is signed once, and re-submitted each epoch.
+        let registrations = builder.registrations();
+        assert_eq!(registrations.len(), 2);
+        assert_eq!(registrations[0], registrations[1]);
+
+        let registration = &registrations[0];
+        assert_eq!(registration.pubkey, listed);
+        assert_eq!(registration.gas_limit, crate::gas_limit::DEFAULT_GAS_LIMIT);
+        assert!(registration.signature.verify(
+            &registration.signed_root(),
+            spec.get_builder_domain(),
+            &listed
+        ));
+    }
+}
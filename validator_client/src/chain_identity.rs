@@ -0,0 +1,225 @@
+//! Pins the validator client to the chain of the first beacon node it signs with, so that a
+//! change of `--server` to a node of another network does not cause the validators to sign on it.
+//!
+//! The identity of the chain is written to `<datadir>/<network>/chain_identity.json` on the first
+//! start and compared with the beacon node on each later start. A beacon node with another chain
+//! id, genesis time or genesis root is refused. A fork whose previous version is the pinned version
+//! is an upgrade of the same chain, and updates the pin.
+//!
+//! To move the validators to another chain on purpose, delete the file.
+use serde_derive::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use types::{Fork, Hash256};
+
+/// The name of the chain identity file within the data directory.
+pub const CHAIN_IDENTITY_FILENAME: &str = "chain_identity.json";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The file could not be read or written.
+    #[error("unable to access the chain identity file {path:?}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    /// The file is invalid.
+    #[error("the chain identity file {path:?} is invalid: {source}")]
+    InvalidFile {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+    /// The beacon node is on another chain to the one pinned.
+    #[error("the beacon node is on another chain ({field} is {found}, but {expected} is pinned)")]
+    WrongChain {
+        field: &'static str,
+        expected: String,
+        found: String,
+    },
+}
+
+/// The outcome of a successful `ChainIdentity::check`.
+#[derive(Debug, PartialEq)]
+pub enum Check {
+    /// There was no pinned chain, so the chain of the beacon node was pinned.
+    Pinned,
+    /// The beacon node is on the pinned chain.
+    Matched,
+    /// The beacon node is on the pinned chain, which has since forked (or reported its genesis
+    /// root for the first time), and the pin was updated.
+    Updated,
+    /// The beacon node does not report its genesis root, so it is unknown whether it has the
+    /// pinned one.
+    Unverified,
+}
+
+/// The properties of a beacon node which identify its chain.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct ChainIdentity {
+    pub chain_id: u64,
+    pub genesis_time: u64,
+    /// `None` if the beacon node does not report its genesis root.
+    pub genesis_root: Option<Hash256>,
+    pub fork: Fork,
+}
+
+impl ChainIdentity {
+    /// Returns the path of the chain identity file in `data_dir`.
+    pub fn path(data_dir: &Path) -> PathBuf {
+        data_dir.join(CHAIN_IDENTITY_FILENAME)
+    }
+
+    /// Reads the pinned chain, or `None` if there is none.
+    pub fn load(data_dir: &Path) -> Result<Option<Self>, Error> {
+        let path = Self::path(data_dir);
+        match File::open(&path) {
+            Ok(file) => serde_json::from_reader(file)
+                .map(Some)
+                .map_err(|source| Error::InvalidFile { path, source }),
+            Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(source) => Err(Error::Io { path, source }),
+        }
+    }
+
+    /// Pins this chain in `data_dir`.
+    pub fn save(&self, data_dir: &Path) -> Result<(), Error> {
+        // Write to a temporary file and rename, so the pin is never partially written.
+        let path = Self::path(data_dir);
+        let temp_path = path.with_extension("json.tmp");
+        let io_error = |source| Error::Io {
+            path: path.clone(),
+            source,
+        };
+        let file = File::create(&temp_path).map_err(io_error)?;
+        serde_json::to_writer_pretty(file, self).map_err(|source| Error::InvalidFile {
+            path: path.clone(),
+            source,
+        })?;
+        fs::rename(&temp_path, &path).map_err(io_error)
+    }
+
+    /// Compares this chain, of a beacon node, with the chain pinned in `data_dir`, pinning this
+    /// chain if there is none.
+    pub fn check(&self, data_dir: &Path) -> Result<Check, Error> {
+        let pinned = match Self::load(data_dir)? {
+            Some(pinned) => pinned,
+            None => {
+                self.save(data_dir)?;
+                return Ok(Check::Pinned);
+            }
+        };
+
+        if self.chain_id != pinned.chain_id {
+            return Err(wrong_chain("chain id", pinned.chain_id, self.chain_id));
+        }
+        if self.genesis_time != pinned.genesis_time {
+            return Err(wrong_chain(
+                "genesis time",
+                pinned.genesis_time,
+                self.genesis_time,
+            ));
+        }
+        let check = match (pinned.genesis_root, self.genesis_root) {
+            (Some(expected), Some(found)) if expected != found => {
+                return Err(wrong_chain(
+                    "genesis root",
+                    format!("{:?}", expected),
+                    format!("{:?}", found),
+                ));
+            }
+            (_, None) => Check::Unverified,
+            (None, Some(_)) => Check::Updated,
+            (Some(_), Some(_)) => Check::Matched,
+        };
+        let check = if self.fork == pinned.fork {
+            check
+        } else if self.fork.previous_version == pinned.fork.current_version
+            && self.fork.epoch > pinned.fork.epoch
+        {
+            Check::Updated
+        } else {
+            return Err(wrong_chain(
+                "fork",
+                format!("{:?}", pinned.fork),
+                format!("{:?}", self.fork),
+            ));
+        };
+
+        if check == Check::Updated {
+            ChainIdentity {
+                genesis_root: self.genesis_root.or(pinned.genesis_root),
+                ..self.clone()
+            }
+            .save(data_dir)?;
+        }
+        Ok(check)
+    }
+}
+
+fn wrong_chain<T: ToString>(field: &'static str, expected: T, found: T) -> Error {
+    Error::WrongChain {
+        field,
+        expected: expected.to_string(),
+        found: found.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use types::Epoch;
+
+    fn identity() -> ChainIdentity {
+        ChainIdentity {
+            chain_id: 1,
+            genesis_time: 1_564_620_118,
+            genesis_root: Some(Hash256::from_low_u64_be(42)),
+            fork: Fork::default(),
+        }
+    }
+
+    #[test]
+    fn refuses_beacon_nodes_of_other_chains() {
+        let dir = TempDir::new().expect("should create temp dir");
+        let data_dir = dir.path();
+        let pinned = identity();
+
+        assert_eq!(pinned.check(data_dir).ok(), Some(Check::Pinned));
+        assert_eq!(pinned.check(data_dir).ok(), Some(Check::Matched));
+
+        let other_root = ChainIdentity {
+            genesis_root: Some(Hash256::from_low_u64_be(43)),
+            ..identity()
+        };
+        assert!(other_root.check(data_dir).is_err());
+        let other_chain_id = ChainIdentity {
+            chain_id: 2,
+            ..identity()
+        };
+        assert!(other_chain_id.check(data_dir).is_err());
+
+        let no_root = ChainIdentity {
+            genesis_root: None,
+            ..identity()
+        };
+        assert_eq!(no_root.check(data_dir).ok(), Some(Check::Unverified));
+
+        let upgraded = ChainIdentity {
+            fork: Fork {
+                previous_version: [0; 4],
+                current_version: [1, 0, 0, 0],
+                epoch: Epoch::new(10),
+            },
+            ..identity()
+        };
+        assert_eq!(upgraded.check(data_dir).ok(), Some(Check::Updated));
+        assert_eq!(ChainIdentity::load(data_dir).ok(), Some(Some(upgraded)));
+        // The pinned chain is now the upgraded one.
+        assert!(pinned.check(data_dir).is_err());
+    }
+}
@@ -0,0 +1,225 @@
+//! Detection of chain splits between the beacon node and a set of cross-check beacon nodes.
+//!
+//! Each slot, the head and finalized checkpoint of every cross-check node are compared with those
+//! of the beacon node used for duties (the "primary"). A node has split from the primary if their
+//! finalized checkpoints conflict, or if their heads have differed for more than a threshold of
+//! consecutive checks (heads may differ briefly whilst blocks propagate).
+//!
+//! If more nodes have split from the primary than agree with it, the primary is assumed to be
+//! following a minority fork (e.g., due to misconfiguration).
+use crate::block_producer::BeaconNodeError;
+use protos::auth::Authenticated;
+use protos::services::Empty;
+use protos::services_grpc::BeaconNodeServiceClient;
+use types::{Epoch, Hash256, Slot};
+
+/// The canonical head and finalized checkpoint of a beacon node.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ChainHead {
+    pub slot: Slot,
+    pub block_root: Hash256,
+    pub finalized_epoch: Epoch,
+    pub finalized_root: Hash256,
+}
+
+impl ChainHead {
+    /// Returns `true` if `self` and `other` have finalized different blocks in the same epoch.
+    fn finality_conflicts_with(&self, other: &ChainHead) -> bool {
+        self.finalized_epoch == other.finalized_epoch && self.finalized_root != other.finalized_root
+    }
+}
+
+/// Defines the methods required to obtain the chain head of a beacon node.
+pub trait BeaconNodeChainHead: Send + Sync {
+    fn chain_head(&self) -> Result<ChainHead, BeaconNodeError>;
+}
+
+impl BeaconNodeChainHead for Authenticated<BeaconNodeServiceClient> {
+    fn chain_head(&self) -> Result<ChainHead, BeaconNodeError> {
+        let reply = self
+            .client()
+            .chain_head_opt(&Empty::new(), self.call_option())
+            .map_err(|err| BeaconNodeError::RemoteFailure(format!("{:?}", err)))?;
+
+        let root = |bytes: &[u8]| {
+            if bytes.len() == 32 {
+                Ok(Hash256::from_slice(bytes))
+            } else {
+                Err(BeaconNodeError::DecodeFailure)
+            }
+        };
+
+        Ok(ChainHead {
+            slot: Slot::from(reply.get_slot()),
+            block_root: root(reply.get_block_root())?,
+            finalized_epoch: Epoch::from(reply.get_finalized_epoch()),
+            finalized_root: root(reply.get_finalized_root())?,
+        })
+    }
+}
+
+/// The result of cross-checking the primary with all other nodes.
+#[derive(Debug, PartialEq, Clone)]
+pub enum ChainSplit {
+    /// No reachable node has split from the primary.
+    None,
+    /// The nodes with the given names have split from the primary, which agrees with the majority.
+    Nodes(Vec<String>),
+    /// The primary has split from the majority of reachable nodes.
+    Primary,
+}
+
+/// Cross-checks a primary beacon node with other beacon nodes.
+pub struct ChainSplitDetector<C> {
+    /// The name (e.g., address) and client of the primary.
+    primary: (String, C),
+    /// The name and client of each cross-check node.
+    nodes: Vec<(String, C)>,
+    /// The number of consecutive checks for which heads may differ before a node is considered to
+    /// have split.
+    threshold: u64,
+    /// The number of consecutive checks for which the head of each node has differed from the
+    /// primary.
+    divergent_checks: Vec<u64>,
+    /// The name of the primary and each node, and its head slot at the latest check (`None` if it
+    /// could not be reached).
+    head_slots: Vec<(String, Option<Slot>)>,
+}
+
+impl<C: BeaconNodeChainHead> ChainSplitDetector<C> {
+    pub fn new(primary: (String, C), nodes: Vec<(String, C)>, threshold: u64) -> Self {
+        Self {
+            primary,
+            divergent_checks: vec![0; nodes.len()],
+            nodes,
+            threshold,
+            head_slots: vec![],
+        }
+    }
+
+    /// Requests the chain head of every node and compares them to the primary.
+    ///
+    /// Nodes which cannot be reached are ignored. Returns an error if the primary cannot be
+    /// reached.
+    pub fn check(&mut self) -> Result<ChainSplit, BeaconNodeError> {
+        let primary = self.primary.1.chain_head();
+        let heads: Vec<Option<ChainHead>> = self
+            .nodes
+            .iter()
+            .map(|(_, node)| node.chain_head().ok())
+            .collect();
+        let mut head_slots = vec![(
+            self.primary.0.clone(),
+            primary.as_ref().ok().map(|head| head.slot),
+        )];
+        for ((name, _), head) in self.nodes.iter().zip(&heads) {
+            head_slots.push((name.clone(), head.as_ref().map(|head| head.slot)));
+        }
+        self.head_slots = head_slots;
+
+        Ok(self.assess(&primary?, &heads))
+    }
+
+    /// Returns the name of the primary and each node, and its head slot at the latest check
+    /// (`None` if it could not be reached).
+    pub fn head_slots(&self) -> &[(String, Option<Slot>)] {
+        &self.head_slots
+    }
+
+    /// Compares the chain head of each node (`None` if unreachable) to that of the primary.
+    fn assess(&mut self, primary: &ChainHead, heads: &[Option<ChainHead>]) -> ChainSplit {
+        let mut agreeing = 0;
+        let mut split = vec![];
+
+        for (i, head) in heads.iter().enumerate() {
+            let head = match head {
+                Some(head) => head,
+                None => continue,
+            };
+
+            if head.block_root == primary.block_root {
+                self.divergent_checks[i] = 0;
+            } else {
+                self.divergent_checks[i] += 1;
+            }
+
+            if head.finality_conflicts_with(primary) || self.divergent_checks[i] > self.threshold {
+                split.push(self.nodes[i].0.clone());
+            } else {
+                agreeing += 1;
+            }
+        }
+
+        if split.is_empty() {
+            ChainSplit::None
+        } else if split.len() > agreeing {
+            ChainSplit::Primary
+        } else {
+            ChainSplit::Nodes(split)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestNode;
+
+    impl BeaconNodeChainHead for TestNode {
+        fn chain_head(&self) -> Result<ChainHead, BeaconNodeError> {
+            Err(BeaconNodeError::DecodeFailure)
+        }
+    }
+
+    fn head(block_root: u64, finalized_root: u64) -> Option<ChainHead> {
+        Some(ChainHead {
+            slot: Slot::new(block_root),
+            block_root: Hash256::from_low_u64_be(block_root),
+            finalized_epoch: Epoch::new(1),
+            finalized_root: Hash256::from_low_u64_be(finalized_root),
+        })
+    }
+
+    fn detector(num_nodes: usize, threshold: u64) -> ChainSplitDetector<TestNode> {
+        let nodes = (0..num_nodes).map(|i| (i.to_string(), TestNode)).collect();
+        ChainSplitDetector::new(("primary".to_string(), TestNode), nodes, threshold)
+    }
+
+    #[test]
+    fn heads_may_differ_up_to_threshold() {
+        let mut detector = detector(3, 1);
+        let primary = head(1, 0).unwrap();
+        let heads = vec![head(1, 0), head(1, 0), head(2, 0)];
+
+        assert_eq!(detector.assess(&primary, &heads), ChainSplit::None);
+        assert_eq!(
+            detector.assess(&primary, &heads),
+            ChainSplit::Nodes(vec!["2".to_string()])
+        );
+
+        // Agreement resets the count.
+        assert_eq!(
+            detector.assess(&primary, &[head(1, 0), head(1, 0), head(1, 0)]),
+            ChainSplit::None
+        );
+        assert_eq!(detector.assess(&primary, &heads), ChainSplit::None);
+    }
+
+    #[test]
+    fn detects_minority_primary() {
+        let mut detector = detector(3, 0);
+        let primary = head(1, 0).unwrap();
+
+        // Conflicting finality is a split regardless of the threshold; unreachable nodes are
+        // ignored.
+        assert_eq!(
+            detector.assess(&primary, &[head(1, 0), head(1, 1), None]),
+            ChainSplit::Nodes(vec!["1".to_string()])
+        );
+        assert_eq!(
+            detector.assess(&primary, &[head(2, 0), head(2, 0), head(1, 0)]),
+            ChainSplit::Primary
+        );
+    }
+}
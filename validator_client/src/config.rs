@@ -1,36 +1,214 @@
+use crate::block_latency::DEFAULT_PUBLISH_TARGET_MS;
+use crate::datadir::VALIDATORS_DIRNAME;
+use crate::fee_recipient::FeeRecipients;
+use crate::gas_limit::{GasLimits, DEFAULT_GAS_LIMIT};
+use crate::hsm::{self, HsmConfig, HsmKind};
+use crate::keystore::{Error as KeystoreError, Keystore, KEYSTORE_FILENAME};
+use crate::metrics_push::{self, MetricsPushConfig, PushAuth};
+use crate::notifier::{Webhook, DEFAULT_UNREACHABLE_SLOTS};
+use crate::password::{self, PasswordSources};
+use crate::signing_lease::{min_lease_secs, SigningLeaseConfig};
+use crate::slashing_protection::DEFAULT_RETENTION_EPOCHS;
+use crate::wss_checkpoint;
 use bincode;
-use bls::Keypair;
+use bls::{Keypair, PublicKey};
 use clap::ArgMatches;
+use eth2_config::Eth2Config;
+use protos::auth::{read_token, TlsCredentials};
 use serde_derive::{Deserialize, Serialize};
-use slog::{debug, error, info, o, Drain};
+use slog::{crit, debug, error, info, o, warn, Drain};
+use std::fmt;
 use std::fs::{self, File, OpenOptions};
 use std::io::{Error, ErrorKind};
-use std::path::PathBuf;
+use std::net::SocketAddr;
+use std::panic;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
-use types::{EthSpec, MainnetEthSpec};
+use std::thread;
+use types::test_utils::generate_deterministic_keypairs;
+use types::{Address, Checkpoint, EthSpec, MainnetEthSpec};
 
 /// Stores the core configuration for this validator instance.
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Config {
-    /// The data directory, which stores all validator databases
+    /// The data directory of the network, which stores all validator databases and, in
+    /// `validators`, the validators (see `datadir`).
     pub data_dir: PathBuf,
+    /// The number of interop validators to run, if any.
+    ///
+    /// If set, the deterministic interop keypairs of validators `0..interop_validators` are used
+    /// instead of the keys in the data directory. These keys are public and for testing only.
+    pub interop_validators: Option<usize>,
     /// The path where the logs will be outputted
     pub log_file: PathBuf,
     /// The server at which the Beacon Node can be contacted
     pub server: String,
+    /// The base URL of a Beacon Node HTTP API, if any.
+    ///
+    /// If set, the Beacon Node is contacted via its HTTP API instead of gRPC at `server`.
+    pub beacon_api: Option<String>,
+    /// Other beacon nodes (gRPC) which are cross-checked with `server` to detect chain splits.
+    pub cross_check_servers: Vec<String>,
+    /// The number of consecutive slots for which the head of a cross-check node may differ from
+    /// `server` before the node is considered to have split.
+    pub chain_split_threshold: u64,
+    /// If `true`, block and attestation signing is paused whilst `server` has split from the
+    /// majority of cross-check nodes.
+    pub pause_on_chain_split: bool,
+    /// If `true`, signed blocks and attestations are published to each of the cross-check nodes
+    /// as well as `server`.
+    pub broadcast: bool,
+    /// An external block builder from which blinded blocks are requested, if any.
+    ///
+    /// Blocks are produced by the Beacon Node if the builder is unable to provide one.
+    pub builder_endpoint: Option<String>,
+    /// The fee recipient of validators which are not listed in `fee_recipient_file`.
+    pub suggested_fee_recipient: Option<Address>,
+    /// A JSON file mapping validator public keys to their fee recipients.
+    pub fee_recipient_file: Option<PathBuf>,
+    /// The gas limit registered with the block builder for validators which are not listed in
+    /// `gas_limit_file`.
+    pub gas_limit: u64,
+    /// A JSON file mapping validator public keys to their gas limits.
+    pub gas_limit_file: Option<PathBuf>,
+    /// A file containing the password of all validator keystores, if any.
+    ///
+    /// Keystores with a password of their own (in `secrets_dir` or the OS keyring) do not use it.
+    pub keystore_password_file: Option<PathBuf>,
+    /// A directory containing the password of each validator keystore, in a file named by the
+    /// validator's public key.
+    pub secrets_dir: Option<PathBuf>,
+    /// If `true`, keystore passwords are looked for in the OS keyring.
+    pub keyring: bool,
+    /// If `true`, the passwords of keystores which are not found elsewhere are asked for at the
+    /// terminal.
+    pub unlock_interactive: bool,
+    /// An HSM holding the keys of further validators, if any.
+    pub hsm: Option<HsmConfig>,
+    /// A file to which the duties of all validators are written (as JSON) whenever they change,
+    /// if any.
+    pub duty_schedule_file: Option<PathBuf>,
+    /// A directory in which a snapshot of the runtime state is written every slot, and from which
+    /// it is restored on startup, if any.
+    pub snapshot_dir: Option<PathBuf>,
+    /// A file to which the beacon node's responses to block production requests are appended,
+    /// for replay with the `replay` subcommand, if any.
+    pub trace_file: Option<PathBuf>,
+    /// A file to which the attestation inclusion and effectiveness of every validator is written
+    /// as JSON each slot, if any.
+    pub inclusion_report_file: Option<PathBuf>,
+    /// The number of epochs of signing history retained when the slashing protection history is
+    /// compacted at the start of each epoch, or `None` if it is never compacted.
+    pub slashing_protection_retention: Option<u64>,
+    /// The address on which the `/health`, `/ready`, `/metrics` and `/proposal_timings` endpoints
+    /// are served, if any.
+    pub health_address: Option<SocketAddr>,
+    /// Where and how the metrics of `/metrics` are pushed, if they are (e.g., from behind NAT).
+    pub metrics_push: Option<MetricsPushConfig>,
+    /// The backend from which validators are claimed before signing, if they are shared with
+    /// other validator clients (e.g., of an active/standby deployment).
+    pub signing_lease: Option<SigningLeaseConfig>,
+    /// If `true`, blocks and attestations are produced and signed with throwaway keys, but logged
+    /// rather than published.
+    pub dry_run: bool,
+    /// If `true`, the lock on the data directory is removed at startup, even if another validator
+    /// client may hold it.
+    pub force_unlock: bool,
+    /// The webhooks notified of missed proposals, signer rejections, blocked slashable messages,
+    /// beacon node outages and slashed validators.
+    pub webhooks: Vec<Webhook>,
+    /// The number of slots the beacon node may be unreachable before the webhooks are notified.
+    pub beacon_node_unreachable_slots: u64,
+    /// The milliseconds after the start of a slot by which blocks should be published. Blocks are
+    /// requested early enough to meet it, given the latency of the beacon node.
+    pub block_publish_target_ms: u64,
+    /// The milliseconds after the start of a slot after which blocks are expected to be too late
+    /// to be included, if any. Blocks which would be published after it are not signed.
+    pub late_block_cutoff_ms: Option<u64>,
+    /// The PEM encoded certificate authority which signs the certificates of beacon nodes, if
+    /// they are contacted via gRPC over mutual TLS.
+    pub tls_ca: Option<PathBuf>,
+    /// The PEM encoded certificate presented to beacon nodes, if contacted over mutual TLS.
+    pub tls_cert: Option<PathBuf>,
+    /// The PEM encoded private key of `tls_cert`.
+    pub tls_key: Option<PathBuf>,
+    /// A file containing the token sent with every gRPC call to beacon nodes, if any.
+    pub auth_token_file: Option<PathBuf>,
+    /// A block root known to be in the canonical chain at the start of an epoch. Beacon nodes
+    /// whose finalized chain conflicts with it are refused.
+    pub wss_checkpoint: Option<Checkpoint>,
     /// The number of slots per epoch.
     pub slots_per_epoch: u64,
 }
 
-const DEFAULT_PRIVATE_KEY_FILENAME: &str = "private.key";
+/// The file of the unencrypted private key in a validator directory.
+pub const DEFAULT_PRIVATE_KEY_FILENAME: &str = "private.key";
+/// The number of threads on which validator keys are loaded.
+const KEY_LOADING_THREADS: usize = 8;
+
+/// A problem found when validating a `Config`, with a suggestion on how to fix it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ConfigProblem {
+    /// The setting at fault.
+    pub setting: &'static str,
+    /// A description of the problem.
+    pub problem: String,
+    /// A suggested fix.
+    pub suggestion: String,
+}
+
+impl fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} ({})",
+            self.setting, self.problem, self.suggestion
+        )
+    }
+}
 
 impl Default for Config {
     /// Build a new configuration from defaults.
     fn default() -> Self {
         Self {
             data_dir: PathBuf::from(".lighthouse-validator"),
+            interop_validators: None,
             log_file: PathBuf::from(""),
             server: "localhost:5051".to_string(),
+            beacon_api: None,
+            cross_check_servers: vec![],
+            chain_split_threshold: 2,
+            pause_on_chain_split: false,
+            broadcast: false,
+            builder_endpoint: None,
+            suggested_fee_recipient: None,
+            fee_recipient_file: None,
+            gas_limit: DEFAULT_GAS_LIMIT,
+            gas_limit_file: None,
+            keystore_password_file: None,
+            secrets_dir: None,
+            keyring: false,
+            unlock_interactive: false,
+            hsm: None,
+            duty_schedule_file: None,
+            snapshot_dir: None,
+            trace_file: None,
+            inclusion_report_file: None,
+            slashing_protection_retention: Some(DEFAULT_RETENTION_EPOCHS),
+            health_address: None,
+            metrics_push: None,
+            signing_lease: None,
+            dry_run: false,
+            force_unlock: false,
+            webhooks: vec![],
+            beacon_node_unreachable_slots: DEFAULT_UNREACHABLE_SLOTS,
+            block_publish_target_ms: DEFAULT_PUBLISH_TARGET_MS,
+            late_block_cutoff_ms: None,
+            tls_ca: None,
+            tls_cert: None,
+            tls_key: None,
+            auth_token_file: None,
+            wss_checkpoint: None,
             slots_per_epoch: MainnetEthSpec::slots_per_epoch(),
         }
     }
@@ -50,6 +228,14 @@ impl Config {
             self.data_dir = PathBuf::from(datadir);
         };
 
+        if let Some(count) = args.value_of("interop-validators") {
+            self.interop_validators = Some(
+                count
+                    .parse()
+                    .map_err(|_| "interop-validators is not a number of validators")?,
+            );
+        };
+
         if let Some(log_file) = args.value_of("logfile") {
             self.log_file = PathBuf::from(log_file);
             self.update_logger(log)?;
@@ -59,9 +245,678 @@ impl Config {
             self.server = srv.to_string();
         };
 
+        if let Some(beacon_api) = args.value_of("beacon-api") {
+            self.beacon_api = Some(beacon_api.to_string());
+        };
+
+        if let Some(servers) = args.value_of("cross-check-servers") {
+            self.cross_check_servers = servers
+                .split(',')
+                .map(str::trim)
+                .filter(|server| !server.is_empty())
+                .map(String::from)
+                .collect();
+        };
+
+        if let Some(threshold) = args.value_of("chain-split-threshold") {
+            self.chain_split_threshold = threshold
+                .parse()
+                .map_err(|_| "chain-split-threshold is not a number of slots")?;
+        };
+
+        if args.is_present("pause-on-chain-split") {
+            self.pause_on_chain_split = true;
+        };
+
+        if args.is_present("broadcast") {
+            self.broadcast = true;
+        };
+
+        if let Some(builder_endpoint) = args.value_of("builder-endpoint") {
+            self.builder_endpoint = Some(builder_endpoint.to_string());
+        };
+
+        if let Some(fee_recipient) = args.value_of("suggested-fee-recipient") {
+            let bytes = hex::decode(fee_recipient.trim_start_matches("0x"))
+                .map_err(|_| "suggested-fee-recipient is not valid hex")?;
+            if bytes.len() != 20 {
+                return Err("suggested-fee-recipient must be a 20-byte address");
+            }
+            self.suggested_fee_recipient = Some(Address::from_slice(&bytes));
+        };
+
+        if let Some(fee_recipient_file) = args.value_of("fee-recipient-file") {
+            self.fee_recipient_file = Some(PathBuf::from(fee_recipient_file));
+        };
+
+        if let Some(gas_limit) = args.value_of("gas-limit") {
+            self.gas_limit = gas_limit
+                .parse()
+                .map_err(|_| "gas-limit is not a valid integer")?;
+        };
+
+        if let Some(gas_limit_file) = args.value_of("gas-limit-file") {
+            self.gas_limit_file = Some(PathBuf::from(gas_limit_file));
+        };
+
+        if let Some(password_file) = args.value_of("keystore-password-file") {
+            self.keystore_password_file = Some(PathBuf::from(password_file));
+        };
+
+        if let Some(secrets_dir) = args.value_of("secrets-dir") {
+            self.secrets_dir = Some(PathBuf::from(secrets_dir));
+        };
+
+        if args.is_present("keyring") {
+            self.keyring = true;
+        }
+
+        if args.is_present("unlock") {
+            self.unlock_interactive = true;
+        }
+
+        if let Some(schedule_file) = args.value_of("duty-schedule-file") {
+            self.duty_schedule_file = Some(PathBuf::from(schedule_file));
+        };
+
+        if let Some(snapshot_dir) = args.value_of("snapshot-dir") {
+            self.snapshot_dir = Some(PathBuf::from(snapshot_dir));
+        };
+
+        if let Some(trace_file) = args.value_of("record-trace") {
+            self.trace_file = Some(PathBuf::from(trace_file));
+        };
+
+        if let Some(report_file) = args.value_of("inclusion-report") {
+            self.inclusion_report_file = Some(PathBuf::from(report_file));
+        };
+
+        if let Some(retention) = args.value_of("slashing-protection-retention") {
+            self.slashing_protection_retention = Some(
+                retention
+                    .parse()
+                    .map_err(|_| "slashing-protection-retention is not a number of epochs")?,
+            );
+        };
+
+        if let Some(health_address) = args.value_of("health-address") {
+            self.health_address = Some(
+                health_address
+                    .parse()
+                    .map_err(|_| "health-address is not a valid socket address")?,
+            );
+        };
+
+        if let Some(target) = args.value_of("metrics-push") {
+            let mut push = MetricsPushConfig::new(
+                target
+                    .parse()
+                    .map_err(|_| "metrics-push must be a [pushgateway=|remote-write=]URL")?,
+            );
+            if let Some(job) = args.value_of("metrics-push-job") {
+                push.job = job.to_string();
+            }
+            if let Some(instance) = args.value_of("metrics-push-instance") {
+                push.instance = Some(instance.to_string());
+            }
+            if !metrics_push::is_valid_grouping_value(&push.job)
+                || !push.instance.as_ref().map_or(true, |instance| {
+                    metrics_push::is_valid_grouping_value(instance)
+                })
+            {
+                return Err("metrics-push-job and metrics-push-instance may only contain letters, digits and _-.:");
+            }
+            if let Some(interval) = args.value_of("metrics-push-interval") {
+                push.interval_secs = interval
+                    .parse()
+                    .map_err(|_| "metrics-push-interval is not a number of seconds")?;
+            }
+            if let Some(file) = args.value_of("metrics-push-basic-auth") {
+                push.auth = Some(PushAuth::Basic(PathBuf::from(file)));
+            }
+            if let Some(file) = args.value_of("metrics-push-bearer-token") {
+                push.auth = Some(PushAuth::Bearer(PathBuf::from(file)));
+            }
+            self.metrics_push = Some(push);
+        };
+
+        if let Some(backend) = args.value_of("signing-lease") {
+            let mut lease = SigningLeaseConfig::new(
+                backend
+                    .parse()
+                    .map_err(|_| "signing-lease must be a directory or an HTTP(S) URL")?,
+            );
+            if let Some(owner) = args.value_of("signing-lease-owner") {
+                lease.owner = Some(owner.to_string());
+            }
+            if let Some(secs) = args.value_of("signing-lease-secs") {
+                lease.lease_secs = Some(
+                    secs.parse()
+                        .map_err(|_| "signing-lease-secs is not a number of seconds")?,
+                );
+            }
+            self.signing_lease = Some(lease);
+        };
+
+        if args.is_present("dry-run") {
+            self.dry_run = true;
+        };
+
+        if args.is_present("force-unlock") {
+            self.force_unlock = true;
+        };
+
+        if let Some(module) = args.value_of("hsm-module") {
+            let mechanism = args
+                .value_of("hsm-mechanism")
+                .ok_or("hsm-mechanism is required with hsm-module")?;
+            let mut hsm = HsmConfig {
+                kind: HsmKind::Pkcs11,
+                module: PathBuf::from(module),
+                slot: None,
+                pin_file: None,
+                mechanism: parse_u64(mechanism).ok_or("hsm-mechanism is not a valid integer")?,
+                sessions: hsm::DEFAULT_SESSIONS,
+            };
+            if let Some(slot) = args.value_of("hsm-slot") {
+                hsm.slot = Some(parse_u64(slot).ok_or("hsm-slot is not a valid integer")?);
+            }
+            if let Some(pin_file) = args.value_of("hsm-pin-file") {
+                hsm.pin_file = Some(PathBuf::from(pin_file));
+            }
+            if let Some(sessions) = args.value_of("hsm-sessions") {
+                hsm.sessions = sessions
+                    .parse()
+                    .map_err(|_| "hsm-sessions is not a number of sessions")?;
+            }
+            #[cfg(feature = "yubihsm")]
+            {
+                if let Some(auth_key_id) = args.value_of("yubihsm-auth-key") {
+                    hsm.kind = HsmKind::YubiHsm {
+                        auth_key_id: auth_key_id
+                            .parse()
+                            .map_err(|_| "yubihsm-auth-key is not a valid key id")?,
+                    };
+                }
+            }
+            self.hsm = Some(hsm);
+        };
+
+        if let Some(webhooks) = args.value_of("webhooks") {
+            self.webhooks = webhooks
+                .split(',')
+                .map(str::trim)
+                .filter(|webhook| !webhook.is_empty())
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .map_err(|_| "webhooks must be comma-separated [slack=|discord=|generic=]URLs")?;
+        };
+
+        if let Some(slots) = args.value_of("webhook-unreachable-slots") {
+            self.beacon_node_unreachable_slots = slots
+                .parse()
+                .map_err(|_| "webhook-unreachable-slots is not a number of slots")?;
+        };
+
+        if let Some(target) = args.value_of("block-publish-target") {
+            self.block_publish_target_ms = target
+                .parse()
+                .map_err(|_| "block-publish-target is not a number of milliseconds")?;
+        };
+
+        if let Some(cutoff) = args.value_of("late-block-cutoff") {
+            self.late_block_cutoff_ms = Some(
+                cutoff
+                    .parse()
+                    .map_err(|_| "late-block-cutoff is not a number of milliseconds")?,
+            );
+        };
+
+        if let Some(tls_ca) = args.value_of("tls-ca") {
+            self.tls_ca = Some(PathBuf::from(tls_ca));
+        };
+
+        if let Some(tls_cert) = args.value_of("tls-cert") {
+            self.tls_cert = Some(PathBuf::from(tls_cert));
+        };
+
+        if let Some(tls_key) = args.value_of("tls-key") {
+            self.tls_key = Some(PathBuf::from(tls_key));
+        };
+
+        if let Some(auth_token_file) = args.value_of("auth-token") {
+            self.auth_token_file = Some(PathBuf::from(auth_token_file));
+        };
+
+        if let Some(checkpoint) = args.value_of("wss-checkpoint") {
+            self.wss_checkpoint = Some(wss_checkpoint::parse(checkpoint)?);
+        };
+
         Ok(())
     }
 
+    /// Cross-check all settings against each other, `eth2_config` and the contents of the data
+    /// directory, returning every problem found.
+    pub fn validate(&self, eth2_config: &Eth2Config) -> Vec<ConfigProblem> {
+        let mut problems = vec![];
+        let mut problem = |setting, problem, suggestion: &str| {
+            problems.push(ConfigProblem {
+                setting,
+                problem,
+                suggestion: suggestion.to_string(),
+            })
+        };
+
+        if eth2_config.spec.seconds_per_slot == 0 {
+            problem(
+                "seconds_per_slot",
+                "slot duration is zero".to_string(),
+                "set seconds_per_slot to a positive value in the eth2 config",
+            );
+        }
+
+        if self.slots_per_epoch == 0 {
+            problem(
+                "slots_per_epoch",
+                "epoch length is zero".to_string(),
+                "set slots_per_epoch to a positive value in the validator config",
+            );
+        }
+
+        if self.slashing_protection_retention == Some(0) {
+            problem(
+                "slashing_protection_retention",
+                "no signing history would be retained".to_string(),
+                "set --slashing-protection-retention to a positive number of epochs",
+            );
+        }
+
+        if !is_host_and_port(&self.server) {
+            problem(
+                "server",
+                format!("{:?} is not of the form HOST:PORT", self.server),
+                "set --server to an address such as localhost:5051",
+            );
+        }
+
+        if let Some(beacon_api) = &self.beacon_api {
+            if !beacon_api.starts_with("http://") && !beacon_api.starts_with("https://") {
+                problem(
+                    "beacon_api",
+                    format!("{:?} is not an HTTP URL", beacon_api),
+                    "set --beacon-api to a URL such as http://localhost:5052",
+                );
+            }
+        }
+
+        for server in &self.cross_check_servers {
+            if !is_host_and_port(server) {
+                problem(
+                    "cross_check_servers",
+                    format!("{:?} is not of the form HOST:PORT", server),
+                    "set --cross-check-servers to addresses such as localhost:5061",
+                );
+            } else if *server == self.server {
+                problem(
+                    "cross_check_servers",
+                    "cross-check server is the beacon node server".to_string(),
+                    "set --cross-check-servers to the addresses of other beacon nodes",
+                );
+            }
+        }
+
+        if !self.cross_check_servers.is_empty() && self.beacon_api.is_some() {
+            problem(
+                "cross_check_servers",
+                "cross-checking requires the beacon node to be contacted via gRPC".to_string(),
+                "remove --beacon-api or --cross-check-servers",
+            );
+        }
+
+        if self.broadcast && self.cross_check_servers.is_empty() {
+            problem(
+                "broadcast",
+                "there are no cross-check servers to broadcast to".to_string(),
+                "set --cross-check-servers or remove --broadcast",
+            );
+        }
+
+        if let Some(builder_endpoint) = &self.builder_endpoint {
+            if !is_host_and_port(builder_endpoint) {
+                problem(
+                    "builder_endpoint",
+                    format!("{:?} is not of the form HOST:PORT", builder_endpoint),
+                    "set --builder-endpoint to an address such as localhost:18550",
+                );
+            } else if *builder_endpoint == self.server {
+                problem(
+                    "builder_endpoint",
+                    "builder endpoint is the beacon node server".to_string(),
+                    "set --builder-endpoint to the address of an external block builder",
+                );
+            }
+        }
+
+        match (&self.tls_ca, &self.tls_cert, &self.tls_key) {
+            (None, None, None) => {}
+            (Some(_), Some(_), Some(_)) => {
+                if let Err(e) = self.tls_credentials() {
+                    problem(
+                        "tls_ca",
+                        format!("unable to read the TLS credentials: {}", e),
+                        "check --tls-ca, --tls-cert and --tls-key are readable PEM files",
+                    );
+                }
+            }
+            _ => problem(
+                "tls_ca",
+                "only some of the TLS credentials are set".to_string(),
+                "set all of --tls-ca, --tls-cert and --tls-key, or none of them",
+            ),
+        }
+
+        if let Err(e) = self.auth_token() {
+            problem(
+                "auth_token_file",
+                format!("unable to read {:?}: {}", self.auth_token_file, e),
+                "check the file contains the --rpc-auth-token of the beacon node",
+            );
+        }
+
+        if let Some(push) = &self.metrics_push {
+            if push.interval_secs == 0 {
+                problem(
+                    "metrics_push",
+                    "metrics would be pushed continuously".to_string(),
+                    "set --metrics-push-interval to a positive number of seconds",
+                );
+            }
+            if let Some(Err(e)) = push.auth.as_ref().map(PushAuth::read) {
+                problem(
+                    "metrics_push",
+                    format!("unable to read the credentials: {}", e),
+                    "check the --metrics-push-basic-auth file contains `user:password`, or the --metrics-push-bearer-token file the token",
+                );
+            }
+        }
+
+        if let Some(lease) = &self.signing_lease {
+            // A shorter lease may expire between the claims of a healthy validator client, letting
+            // a standby take over.
+            let seconds_per_slot = eth2_config.spec.seconds_per_slot;
+            let lease_secs = lease.lease_secs(seconds_per_slot, self.slots_per_epoch);
+            let min_secs = min_lease_secs(seconds_per_slot, self.slots_per_epoch);
+            if lease_secs < min_secs {
+                problem(
+                    "signing_lease",
+                    format!(
+                        "leases of {} seconds may expire between the attestations of a validator",
+                        lease_secs
+                    ),
+                    &format!(
+                        "set --signing-lease-secs to at least two epochs and a slot ({} seconds)",
+                        min_secs
+                    ),
+                );
+            }
+            if lease.owner.as_ref().map_or(false, String::is_empty) {
+                problem(
+                    "signing_lease",
+                    "the owner is empty".to_string(),
+                    "set --signing-lease-owner to a name unique to this validator client",
+                );
+            }
+        }
+
+        if self.beacon_api.is_some() && (self.tls_ca.is_some() || self.auth_token_file.is_some()) {
+            problem(
+                "beacon_api",
+                "TLS credentials and auth tokens are only used via gRPC".to_string(),
+                "remove --beacon-api, or --tls-ca, --tls-cert, --tls-key and --auth-token",
+            );
+        }
+
+        if let Err(e) = self.fee_recipients() {
+            problem(
+                "fee_recipient_file",
+                format!("unable to read {:?}: {:?}", self.fee_recipient_file, e),
+                "check the file is a JSON object mapping public keys to addresses",
+            );
+        }
+
+        if let Err(e) = self.gas_limits() {
+            problem(
+                "gas_limit_file",
+                format!("unable to read {:?}: {:?}", self.gas_limit_file, e),
+                "check the file is a JSON object mapping public keys to gas limits",
+            );
+        }
+
+        let probe = self.data_dir.join(".write_test");
+        if fs::write(&probe, b"")
+            .and_then(|_| fs::remove_file(&probe))
+            .is_err()
+        {
+            problem(
+                "data_dir",
+                format!("{:?} is missing or not writable", self.data_dir),
+                "create the directory, fix its permissions or choose another --datadir",
+            );
+        }
+
+        if let Some(count) = self.interop_validators {
+            if count == 0 {
+                problem(
+                    "interop_validators",
+                    "no interop validators would be run".to_string(),
+                    "set --interop-validators to a positive number of validators",
+                );
+            }
+            // The keys in the data directory are not loaded.
+            return problems;
+        }
+
+        let sources = match self.password_sources() {
+            Ok(sources) => sources,
+            Err(e) => {
+                problem(
+                    "keystore_password_file",
+                    format!("unable to read {:?}: {}", self.keystore_password_file, e),
+                    "check the file exists and is readable",
+                );
+                PasswordSources::default()
+            }
+        };
+        if let Some(secrets_dir) = &self.secrets_dir {
+            if !secrets_dir.is_dir() {
+                problem(
+                    "secrets_dir",
+                    format!("{:?} is not a directory", secrets_dir),
+                    "set --secrets-dir to the directory of keystore password files",
+                );
+            }
+        }
+
+        let mut num_keys = 0;
+        let mut num_keystores = 0;
+        for validator_dir in fs::read_dir(self.validators_dir())
+            .into_iter()
+            .flatten()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.path().is_dir())
+        {
+            let name = validator_dir.file_name().to_string_lossy().into_owned();
+
+            let keystore_path = validator_dir.path().join(KEYSTORE_FILENAME);
+            if keystore_path.exists() {
+                num_keystores += 1;
+                match Keystore::from_file(&keystore_path).and_then(|k| k.public_key()) {
+                    Ok(pubkey) if pubkey.concatenated_hex_id() == name => {
+                        num_keys += 1;
+                        match sources.secrets_file(&pubkey) {
+                            Some(path) if !path.exists() && !sources.has_fallback() => problem(
+                                "secrets_dir",
+                                format!("no password for the keystore in {:?}", validator_dir.path()),
+                                "add its password to the secrets directory, or set --keystore-password-file",
+                            ),
+                            _ => {}
+                        }
+                    }
+                    Ok(pubkey) => problem(
+                        "data_dir",
+                        format!(
+                            "keystore in {:?} is for validator {}",
+                            validator_dir.path(),
+                            pubkey.concatenated_hex_id()
+                        ),
+                        "rename the directory to match the validator it contains",
+                    ),
+                    Err(e) => problem(
+                        "data_dir",
+                        format!("unable to read the keystore {:?}: {:?}", keystore_path, e),
+                        "replace the keystore, or remove the validator directory",
+                    ),
+                }
+                continue;
+            }
+
+            let key_path = validator_dir.path().join(DEFAULT_PRIVATE_KEY_FILENAME);
+
+            let key: Option<Keypair> = File::open(&key_path)
+                .ok()
+                .and_then(|mut file| bincode::deserialize_from(&mut file).ok());
+
+            match key {
+                Some(key) if key.identifier() == name => num_keys += 1,
+                Some(key) => problem(
+                    "data_dir",
+                    format!(
+                        "key in {:?} is for validator {}",
+                        validator_dir.path(),
+                        key.identifier()
+                    ),
+                    "rename the directory to match the validator it contains",
+                ),
+                None if key_path.exists() => problem(
+                    "data_dir",
+                    format!("unable to read the private key {:?}", key_path),
+                    "replace the key file, or remove the validator directory",
+                ),
+                None => problem(
+                    "data_dir",
+                    format!(
+                        "validator directory {:?} has no private key",
+                        validator_dir.path()
+                    ),
+                    "add the validator's private key, or remove the directory",
+                ),
+            }
+        }
+
+        if num_keystores > 0 && sources.secrets_dir.is_none() && !sources.has_fallback() {
+            problem(
+                "keystore_password_file",
+                "validator keystores require a password".to_string(),
+                "set --secrets-dir, --keystore-password-file, --keyring or --unlock",
+            );
+        }
+
+        if let Some(hsm) = &self.hsm {
+            if !hsm.module.is_file() {
+                problem(
+                    "hsm",
+                    format!("the PKCS#11 module {:?} does not exist", hsm.module),
+                    "set --hsm-module to the shared library of the HSM",
+                );
+            }
+            if hsm.sessions == 0 {
+                problem(
+                    "hsm",
+                    "no HSM sessions would be opened".to_string(),
+                    "set --hsm-sessions to a positive number of sessions",
+                );
+            }
+            if let Some(pin_file) = &hsm.pin_file {
+                if let Err(e) = password::read_password_file(pin_file) {
+                    problem(
+                        "hsm",
+                        format!("unable to read the PIN file {:?}: {}", pin_file, e),
+                        "check the file exists and is readable",
+                    );
+                }
+            }
+        }
+
+        // The keys held by an HSM are only known once it is connected to.
+        if num_keys == 0 && self.hsm.is_none() {
+            problem(
+                "data_dir",
+                format!("no validator keys found in {:?}", self.validators_dir()),
+                "generate keys with the account manager, or choose another --datadir",
+            );
+        }
+
+        problems
+    }
+
+    /// Returns the directory of the validator directories, each named by its validator's id.
+    pub fn validators_dir(&self) -> PathBuf {
+        self.data_dir.join(VALIDATORS_DIRNAME)
+    }
+
+    /// Returns the fee recipients of all validators.
+    pub fn fee_recipients(&self) -> Result<FeeRecipients, crate::fee_recipient::Error> {
+        FeeRecipients::new(
+            self.suggested_fee_recipient,
+            self.fee_recipient_file.as_ref().map(PathBuf::as_path),
+        )
+    }
+
+    /// Returns the gas limits of all validators.
+    pub fn gas_limits(&self) -> Result<GasLimits, crate::gas_limit::Error> {
+        GasLimits::new(
+            self.gas_limit,
+            self.gas_limit_file.as_ref().map(PathBuf::as_path),
+        )
+    }
+
+    /// Loads the TLS credentials with which beacon nodes are contacted, if all are set.
+    pub fn tls_credentials(&self) -> Result<Option<TlsCredentials>, Error> {
+        match (&self.tls_ca, &self.tls_cert, &self.tls_key) {
+            (Some(ca), Some(cert), Some(key)) => TlsCredentials::load(ca, cert, key).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Reads the token sent with every gRPC call from `auth_token_file`, if any.
+    pub fn auth_token(&self) -> Result<Option<String>, Error> {
+        match &self.auth_token_file {
+            Some(path) => read_token(path).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Reads the keystore password from `keystore_password_file`, if any.
+    ///
+    /// A trailing newline is not considered part of the password.
+    pub fn keystore_password(&self) -> Result<Option<String>, Error> {
+        match &self.keystore_password_file {
+            Some(path) => password::read_password_file(path).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the places in which keystore passwords are looked for, reading the shared
+    /// `keystore_password_file` if any.
+    pub fn password_sources(&self) -> Result<PasswordSources, Error> {
+        Ok(PasswordSources {
+            secrets_dir: self.secrets_dir.clone(),
+            keyring: self.keyring,
+            shared: self.keystore_password()?,
+            interactive: self.unlock_interactive,
+        })
+    }
+
     // Update the logger to output in JSON to specified file
     fn update_logger(&mut self, log: &mut slog::Logger) -> Result<(), &'static str> {
         let file = OpenOptions::new()
@@ -95,9 +950,46 @@ impl Config {
     }
 
     /// Try to load keys from validator_dir, returning None if none are found or an error.
+    ///
+    /// If `interop_validators` is set, the interop keypairs are returned instead.
+    ///
+    /// Keys are read from the `voting-keystore.json` of each validator directory, if present, or
+    /// otherwise from the unencrypted `private.key`.
     #[allow(dead_code)]
     pub fn fetch_keys(&self, log: &slog::Logger) -> Option<Vec<Keypair>> {
-        let key_pairs: Vec<Keypair> = fs::read_dir(&self.data_dir)
+        if let Some(count) = self.interop_validators {
+            warn!(
+                log,
+                "Using insecure interop validator keys";
+                "validators" => count,
+            );
+            let key_pairs = generate_deterministic_keypairs(count);
+            return if key_pairs.is_empty() {
+                None
+            } else {
+                Some(key_pairs)
+            };
+        }
+
+        let sources = match self.password_sources() {
+            Ok(sources) => sources,
+            Err(e) => {
+                error!(
+                    log,
+                    "Unable to read the keystore password file: {:?}", self.keystore_password_file;
+                    "error" => format!("{}", e)
+                );
+                PasswordSources {
+                    secrets_dir: self.secrets_dir.clone(),
+                    keyring: self.keyring,
+                    shared: None,
+                    interactive: self.unlock_interactive,
+                }
+            }
+        };
+
+        // Passwords are found before keys are loaded, so that any prompts are made in turn.
+        let validator_dirs: Vec<(PathBuf, Option<String>)> = fs::read_dir(self.validators_dir())
             .ok()?
             .filter_map(|validator_dir| {
                 let validator_dir = validator_dir.ok()?;
@@ -106,47 +998,47 @@ impl Config {
                     // Skip non-directories (i.e. no files/symlinks)
                     return None;
                 }
+                Some(validator_dir.path())
+            })
+            .map(|dir| {
+                let password = keystore_password(&dir, &sources, log);
+                (dir, password)
+            })
+            .collect();
 
-                let key_filename = validator_dir.path().join(DEFAULT_PRIVATE_KEY_FILENAME);
-
-                if !(key_filename.is_file()) {
-                    info!(
-                        log,
-                        "Private key is not a file: {:?}",
-                        key_filename.to_str()
-                    );
-                    return None;
-                }
-
-                debug!(
-                    log,
-                    "Deserializing private key from file: {:?}",
-                    key_filename.to_str()
-                );
-
-                let mut key_file = File::open(key_filename.clone()).ok()?;
-
-                let key: Keypair = if let Ok(key_ok) = bincode::deserialize_from(&mut key_file) {
-                    key_ok
-                } else {
-                    error!(
-                        log,
-                        "Unable to deserialize the private key file: {:?}", key_filename
-                    );
-                    return None;
-                };
+        // Keystore decryption is deliberately slow, so keys are loaded on several threads.
+        let chunk_size = (validator_dirs.len() + KEY_LOADING_THREADS - 1) / KEY_LOADING_THREADS;
+        let handles: Vec<_> = validator_dirs
+            .chunks(chunk_size.max(1))
+            .map(|chunk| {
+                let chunk = chunk.to_vec();
+                let dirs: Vec<PathBuf> = chunk.iter().map(|(dir, _)| dir.clone()).collect();
+                let log = log.clone();
+                let handle = thread::spawn(move || {
+                    chunk
+                        .iter()
+                        .filter_map(|(dir, password)| {
+                            load_validator_key(dir, password.as_ref().map(String::as_str), &log)
+                        })
+                        .collect::<Vec<_>>()
+                });
+                (dirs, handle)
+            })
+            .collect();
 
-                let ki = key.identifier();
-                if ki != validator_dir.file_name().into_string().ok()? {
-                    error!(
+        // A panic whilst loading keys is propagated, rather than silently starting without the
+        // validators of the thread which panicked.
+        let key_pairs: Vec<Keypair> = handles
+            .into_iter()
+            .flat_map(|(dirs, handle)| {
+                handle.join().unwrap_or_else(|payload| {
+                    crit!(
                         log,
-                        "The validator key ({:?}) did not match the directory filename {:?}.",
-                        ki,
-                        &validator_dir.path().to_string_lossy()
+                        "Panicked whilst loading validator keys";
+                        "validator_dirs" => format!("{:?}", dirs),
                     );
-                    return None;
-                }
-                Some(key)
+                    panic::resume_unwind(payload)
+                })
             })
             .collect();
 
@@ -158,10 +1050,52 @@ impl Config {
         }
     }
 
+    /// Returns the public keys of the validators of `fetch_keys`, without decrypting keystores
+    /// (whose public keys are stored in the clear).
+    pub fn fetch_public_keys(&self, log: &slog::Logger) -> Vec<PublicKey> {
+        if let Some(count) = self.interop_validators {
+            return generate_deterministic_keypairs(count)
+                .into_iter()
+                .map(|keypair| keypair.pk)
+                .collect();
+        }
+
+        let entries = match fs::read_dir(self.validators_dir()) {
+            Ok(entries) => entries,
+            Err(_) => return vec![],
+        };
+        entries
+            .filter_map(|validator_dir| {
+                let validator_dir = validator_dir.ok()?;
+                if !(validator_dir.file_type().ok()?.is_dir()) {
+                    return None;
+                }
+                let keystore_filename = validator_dir.path().join(KEYSTORE_FILENAME);
+                if keystore_filename.is_file() {
+                    match Keystore::from_file(&keystore_filename)
+                        .and_then(|keystore| keystore.public_key())
+                    {
+                        Ok(pk) => Some(pk),
+                        Err(e) => {
+                            error!(
+                                log,
+                                "Unable to read the keystore: {:?}", keystore_filename;
+                                "error" => format!("{:?}", e)
+                            );
+                            None
+                        }
+                    }
+                } else {
+                    load_private_key(&validator_dir.path(), log).map(|keypair| keypair.pk)
+                }
+            })
+            .collect()
+    }
+
     /// Saves a keypair to a file inside the appropriate validator directory. Returns the saved path filename.
     #[allow(dead_code)]
     pub fn save_key(&self, key: &Keypair) -> Result<PathBuf, Error> {
-        let validator_config_path = self.data_dir.join(key.identifier());
+        let validator_config_path = self.validators_dir().join(key.identifier());
         let key_path = validator_config_path.join(DEFAULT_PRIVATE_KEY_FILENAME);
 
         fs::create_dir_all(&validator_config_path)?;
@@ -172,4 +1106,445 @@ impl Config {
             .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
         Ok(key_path)
     }
+
+    /// Saves a keystore inside the appropriate validator directory. Returns the saved path
+    /// filename.
+    pub fn save_keystore(&self, keystore: &Keystore) -> Result<PathBuf, Error> {
+        let to_io_error = |e: KeystoreError| Error::new(ErrorKind::InvalidData, format!("{:?}", e));
+
+        let identifier = keystore
+            .public_key()
+            .map_err(to_io_error)?
+            .concatenated_hex_id();
+        let validator_config_path = self.validators_dir().join(identifier);
+        let keystore_path = validator_config_path.join(KEYSTORE_FILENAME);
+
+        fs::create_dir_all(&validator_config_path)?;
+
+        keystore.to_file(&keystore_path).map_err(to_io_error)?;
+        Ok(keystore_path)
+    }
+}
+
+/// Loads the key of the validator directory at `validator_dir`, from its keystore if it has one
+/// or otherwise from its unencrypted private key.
+fn load_validator_key(
+    validator_dir: &Path,
+    password: Option<&str>,
+    log: &slog::Logger,
+) -> Option<Keypair> {
+    let keystore_filename = validator_dir.join(KEYSTORE_FILENAME);
+
+    let key = if keystore_filename.is_file() {
+        match password {
+            Some(password) => load_keystore(&keystore_filename, password, log)?,
+            None => {
+                error!(
+                    log,
+                    "No password for keystore: {:?}", keystore_filename;
+                    "help" => "add its password to --secrets-dir, or set --keystore-password-file"
+                );
+                return None;
+            }
+        }
+    } else {
+        load_private_key(validator_dir, log)?
+    };
+
+    let ki = key.identifier();
+    if Some(ki.as_str()) != validator_dir.file_name().and_then(|name| name.to_str()) {
+        error!(
+            log,
+            "The validator key ({:?}) did not match the directory filename {:?}.",
+            ki,
+            &validator_dir.to_string_lossy()
+        );
+        return None;
+    }
+    Some(key)
+}
+
+/// Returns the password of the keystore of the validator directory at `validator_dir`, or `None`
+/// if it has none or no keystore.
+fn keystore_password(
+    validator_dir: &Path,
+    sources: &PasswordSources,
+    log: &slog::Logger,
+) -> Option<String> {
+    let keystore_filename = validator_dir.join(KEYSTORE_FILENAME);
+    if !keystore_filename.is_file() {
+        return None;
+    }
+
+    // A keystore which cannot be read is reported when it is loaded.
+    let pubkey = Keystore::from_file(&keystore_filename)
+        .and_then(|keystore| keystore.public_key())
+        .ok()?;
+    match sources.password(&pubkey) {
+        Ok(password) => password,
+        Err(e) => {
+            error!(
+                log,
+                "Unable to find the password of keystore: {:?}", keystore_filename;
+                "error" => format!("{}", e)
+            );
+            None
+        }
+    }
+}
+
+/// Decrypts the keystore at `path` with `password`.
+fn load_keystore(path: &Path, password: &str, log: &slog::Logger) -> Option<Keypair> {
+    debug!(log, "Decrypting keystore: {:?}", path.to_str());
+
+    match Keystore::from_file(path).and_then(|keystore| keystore.decrypt(password)) {
+        Ok(key) => Some(key),
+        Err(e) => {
+            error!(
+                log,
+                "Unable to decrypt the keystore: {:?}", path;
+                "error" => format!("{:?}", e)
+            );
+            None
+        }
+    }
+}
+
+/// Reads the unencrypted private key of the validator directory at `validator_dir`.
+fn load_private_key(validator_dir: &Path, log: &slog::Logger) -> Option<Keypair> {
+    let key_filename = validator_dir.join(DEFAULT_PRIVATE_KEY_FILENAME);
+
+    if !(key_filename.is_file()) {
+        info!(
+            log,
+            "Private key is not a file: {:?}",
+            key_filename.to_str()
+        );
+        return None;
+    }
+
+    debug!(
+        log,
+        "Deserializing private key from file: {:?}",
+        key_filename.to_str()
+    );
+
+    let mut key_file = File::open(key_filename.clone()).ok()?;
+
+    if let Ok(key) = bincode::deserialize_from(&mut key_file) {
+        Some(key)
+    } else {
+        error!(
+            log,
+            "Unable to deserialize the private key file: {:?}", key_filename
+        );
+        None
+    }
+}
+
+/// Parses a decimal integer, or a hexadecimal integer prefixed with `0x`.
+fn parse_u64(s: &str) -> Option<u64> {
+    if s.starts_with("0x") {
+        u64::from_str_radix(&s[2..], 16).ok()
+    } else {
+        s.parse().ok()
+    }
+}
+
+/// Returns `true` if `address` is of the form `HOST:PORT`.
+fn is_host_and_port(address: &str) -> bool {
+    let mut parts = address.rsplitn(2, ':');
+    match (parts.next(), parts.next()) {
+        (Some(port), Some(host)) => !host.is_empty() && port.parse::<u16>().is_ok(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keystore::Kdf;
+    use crate::signing_lease::LeaseBackend;
+    use tempfile::TempDir;
+
+    fn settings(problems: &[ConfigProblem]) -> Vec<&'static str> {
+        problems.iter().map(|problem| problem.setting).collect()
+    }
+
+    #[test]
+    fn reports_all_problems() {
+        let dir = TempDir::new().expect("should create temp dir");
+        fs::create_dir_all(dir.path().join(VALIDATORS_DIRNAME).join("0xdeadbeef"))
+            .expect("should create validator dir");
+
+        let config = Config {
+            data_dir: dir.path().to_path_buf(),
+            server: "localhost".to_string(),
+            beacon_api: Some("localhost:5052".to_string()),
+            cross_check_servers: vec!["localhost:5061".to_string()],
+            builder_endpoint: Some("localhost:5051".to_string()),
+            ..Config::default()
+        };
+
+        assert_eq!(
+            settings(&config.validate(&Eth2Config::minimal())),
+            vec![
+                "server",
+                "beacon_api",
+                "cross_check_servers",
+                "data_dir",
+                "data_dir"
+            ]
+        );
+    }
+
+    #[test]
+    fn valid_config() {
+        let dir = TempDir::new().expect("should create temp dir");
+        let config = Config {
+            data_dir: dir.path().to_path_buf(),
+            ..Config::default()
+        };
+        config
+            .save_key(&Keypair::random())
+            .expect("should save key");
+
+        assert_eq!(config.validate(&Eth2Config::minimal()), vec![]);
+    }
+
+    #[test]
+    fn broadcast_requires_cross_check_servers() {
+        let dir = TempDir::new().expect("should create temp dir");
+        let mut config = Config {
+            data_dir: dir.path().to_path_buf(),
+            broadcast: true,
+            ..Config::default()
+        };
+        config
+            .save_key(&Keypair::random())
+            .expect("should save key");
+
+        assert_eq!(
+            settings(&config.validate(&Eth2Config::minimal())),
+            vec!["broadcast"]
+        );
+
+        config.cross_check_servers = vec!["localhost:5061".to_string()];
+        assert_eq!(config.validate(&Eth2Config::minimal()), vec![]);
+    }
+
+    #[test]
+    fn tls_credentials_must_be_complete() {
+        let dir = TempDir::new().expect("should create temp dir");
+        let pem = |name: &str| {
+            let path = dir.path().join(name);
+            fs::write(&path, "-----BEGIN CERTIFICATE-----").expect("should write pem");
+            path
+        };
+        let token_file = dir.path().join("token.txt");
+        fs::write(&token_file, "secret\n").expect("should write token");
+
+        let mut config = Config {
+            data_dir: dir.path().to_path_buf(),
+            tls_ca: Some(pem("ca.pem")),
+            tls_cert: Some(pem("cert.pem")),
+            auth_token_file: Some(token_file),
+            ..Config::default()
+        };
+        config
+            .save_key(&Keypair::random())
+            .expect("should save key");
+
+        assert_eq!(
+            settings(&config.validate(&Eth2Config::minimal())),
+            vec!["tls_ca"]
+        );
+
+        config.tls_key = Some(pem("key.pem"));
+        assert_eq!(config.validate(&Eth2Config::minimal()), vec![]);
+        assert_eq!(config.auth_token().ok(), Some(Some("secret".to_string())));
+    }
+
+    #[test]
+    fn metrics_push_credentials_must_be_readable() {
+        let dir = TempDir::new().expect("should create temp dir");
+        let credentials = dir.path().join("credentials.txt");
+        let mut push = MetricsPushConfig::new(
+            "remote-write=https://prometheus.example/api/v1/write"
+                .parse()
+                .expect("should parse target"),
+        );
+        push.auth = Some(PushAuth::Basic(credentials.clone()));
+        let config = Config {
+            data_dir: dir.path().to_path_buf(),
+            metrics_push: Some(push),
+            ..Config::default()
+        };
+        config
+            .save_key(&Keypair::random())
+            .expect("should save key");
+
+        assert_eq!(
+            settings(&config.validate(&Eth2Config::minimal())),
+            vec!["metrics_push"]
+        );
+
+        fs::write(&credentials, "user:password\n").expect("should write credentials");
+        assert_eq!(config.validate(&Eth2Config::minimal()), vec![]);
+    }
+
+    #[test]
+    fn signing_leases_must_outlast_two_epochs() {
+        let dir = TempDir::new().expect("should create temp dir");
+        let mut lease = SigningLeaseConfig::new(LeaseBackend::File(dir.path().join("leases")));
+        let seconds_per_slot = Eth2Config::minimal().spec.seconds_per_slot;
+        let slots_per_epoch = Config::default().slots_per_epoch;
+        // Two epochs, which is a slot too few.
+        lease.lease_secs = Some(seconds_per_slot * 2 * slots_per_epoch);
+        let mut config = Config {
+            data_dir: dir.path().to_path_buf(),
+            signing_lease: Some(lease),
+            ..Config::default()
+        };
+        config
+            .save_key(&Keypair::random())
+            .expect("should save key");
+
+        assert_eq!(
+            settings(&config.validate(&Eth2Config::minimal())),
+            vec!["signing_lease"]
+        );
+
+        config.signing_lease.as_mut().unwrap().lease_secs =
+            Some(seconds_per_slot * (2 * slots_per_epoch + 1));
+        assert_eq!(config.validate(&Eth2Config::minimal()), vec![]);
+
+        // The default lease is long enough.
+        config.signing_lease.as_mut().unwrap().lease_secs = None;
+        assert_eq!(config.validate(&Eth2Config::minimal()), vec![]);
+    }
+
+    #[test]
+    fn loads_keystores() {
+        let dir = TempDir::new().expect("should create temp dir");
+        let mut config = Config {
+            data_dir: dir.path().to_path_buf(),
+            ..Config::default()
+        };
+        let log = slog::Logger::root(slog::Discard, o!());
+
+        let keypair = Keypair::random();
+        let keystore = Keystore::encrypt(&keypair, "password", "", Kdf::Pbkdf2 { c: 16 })
+            .expect("should encrypt");
+        config
+            .save_keystore(&keystore)
+            .expect("should save keystore");
+
+        assert_eq!(
+            settings(&config.validate(&Eth2Config::minimal())),
+            vec!["keystore_password_file"]
+        );
+        assert!(config.fetch_keys(&log).is_none());
+
+        let password_file = dir.path().join("password.txt");
+        fs::write(&password_file, "password\n").expect("should write password");
+        config.keystore_password_file = Some(password_file);
+
+        assert_eq!(config.validate(&Eth2Config::minimal()), vec![]);
+        let keys = config.fetch_keys(&log).expect("should decrypt keystore");
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].pk, keypair.pk);
+    }
+
+    #[test]
+    fn loads_keystores_with_their_own_passwords() {
+        let dir = TempDir::new().expect("should create temp dir");
+        let secrets_dir = dir.path().join("secrets");
+        fs::create_dir(&secrets_dir).expect("should create secrets dir");
+        let mut config = Config {
+            data_dir: dir.path().join("network"),
+            secrets_dir: Some(secrets_dir.clone()),
+            ..Config::default()
+        };
+        let log = slog::Logger::root(slog::Discard, o!());
+
+        let keypairs: Vec<_> = (0..2).map(|_| Keypair::random()).collect();
+        for (i, keypair) in keypairs.iter().enumerate() {
+            let password = format!("password {}", i);
+            let keystore = Keystore::encrypt(keypair, &password, "", Kdf::Pbkdf2 { c: 16 })
+                .expect("should encrypt");
+            config
+                .save_keystore(&keystore)
+                .expect("should save keystore");
+            if i == 0 {
+                fs::write(secrets_dir.join(keypair.pk.as_hex_string()), password)
+                    .expect("should write password");
+            }
+        }
+
+        // The second keystore has no password.
+        assert_eq!(
+            settings(&config.validate(&Eth2Config::minimal())),
+            vec!["secrets_dir"]
+        );
+        let keys = config.fetch_keys(&log).expect("should decrypt a keystore");
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].pk, keypairs[0].pk);
+
+        // The shared password is used for the second keystore only.
+        let password_file = dir.path().join("password.txt");
+        fs::write(&password_file, "password 1\n").expect("should write password");
+        config.keystore_password_file = Some(password_file);
+
+        assert_eq!(config.validate(&Eth2Config::minimal()), vec![]);
+        let keys = config.fetch_keys(&log).expect("should decrypt keystores");
+        assert_eq!(keys.len(), 2);
+    }
+
+    #[test]
+    fn interop_validators_replace_data_dir_keys() {
+        let dir = TempDir::new().expect("should create temp dir");
+        let mut config = Config {
+            data_dir: dir.path().to_path_buf(),
+            interop_validators: Some(3),
+            ..Config::default()
+        };
+        let log = slog::Logger::root(slog::Discard, o!());
+
+        assert_eq!(config.validate(&Eth2Config::minimal()), vec![]);
+        let keys = config.fetch_keys(&log).expect("should generate keys");
+        assert_eq!(keys, generate_deterministic_keypairs(3));
+
+        config.interop_validators = Some(0);
+        assert_eq!(
+            settings(&config.validate(&Eth2Config::minimal())),
+            vec!["interop_validators"]
+        );
+        assert!(config.fetch_keys(&log).is_none());
+    }
+
+    #[test]
+    fn public_keys_are_read_without_passwords() {
+        let dir = TempDir::new().expect("should create temp dir");
+        let config = Config {
+            data_dir: dir.path().to_path_buf(),
+            ..Config::default()
+        };
+        let log = slog::Logger::root(slog::Discard, o!());
+
+        let encrypted = Keypair::random();
+        let keystore = Keystore::encrypt(&encrypted, "password", "", Kdf::Pbkdf2 { c: 16 })
+            .expect("should encrypt");
+        config
+            .save_keystore(&keystore)
+            .expect("should save keystore");
+        let unencrypted = Keypair::random();
+        config.save_key(&unencrypted).expect("should save key");
+
+        let mut public_keys = config.fetch_public_keys(&log);
+        public_keys.sort_by_key(PublicKey::as_hex_string);
+        let mut expected = vec![encrypted.pk, unencrypted.pk];
+        expected.sort_by_key(PublicKey::as_hex_string);
+        assert_eq!(public_keys, expected);
+    }
 }
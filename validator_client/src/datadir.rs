@@ -0,0 +1,406 @@
+//! The layout of the data directory, which keeps the data of each network apart so that the
+//! slashing protection history, duties and chain pin of one network are never used on another.
+//!
+//! ```text
+//! <datadir>/
+//!     validator-client.toml
+//!     eth2-spec.toml
+//!     <network>/              (e.g., `minimal-2`, see `network_name`)
+//!         validator.lock
+//!         chain_identity.json
+//!         duties_cache.json
+//!         signing_log.jsonl
+//!         validators/
+//!             <validator id>/
+//! ```
+//!
+//! `Config::data_dir` is the directory of the network, so anything stored "in the data directory"
+//! belongs to that network. The configuration files are shared by all networks, and select the
+//! network which is run.
+//!
+//! Data directories of an earlier layout, with the validators and chain data directly in
+//! `<datadir>`, are moved into the directory of the configured network when it is first opened.
+//! The entries are moved into `<network>.migrating` and it is renamed to `<network>` once they all
+//! have been, so an interrupted migration is resumed by the next start rather than leaving the data
+//! split between the two layouts. Commands which only read the data directory (e.g., listing the
+//! validators) do not migrate it, but find the validators wherever they are.
+use crate::audit_log::AUDIT_LOG_FILENAME;
+use crate::chain_identity::CHAIN_IDENTITY_FILENAME;
+use crate::config::DEFAULT_PRIVATE_KEY_FILENAME;
+use crate::datadir_lock::{self, DatadirLock};
+use crate::duties::DUTIES_CACHE_FILENAME;
+use crate::keystore::KEYSTORE_FILENAME;
+use crate::signing_log::SIGNING_LOG_FILENAME;
+use crate::validator_state::VALIDATOR_STATE_FILENAME;
+use eth2_config::{read_from_file, Eth2Config};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// The name of the `Eth2Config` file within the data directory.
+pub const ETH2_CONFIG_FILENAME: &str = "eth2-spec.toml";
+
+/// The name of the directory of the validators within the directory of a network.
+pub const VALIDATORS_DIRNAME: &str = "validators";
+
+/// Appended to the name of the directory of a network whilst a legacy layout is migrated into it.
+const MIGRATING_SUFFIX: &str = ".migrating";
+
+/// The files of a legacy layout which belong to the network.
+const LEGACY_FILENAMES: &[&str] = &[
+    CHAIN_IDENTITY_FILENAME,
+    DUTIES_CACHE_FILENAME,
+    SIGNING_LOG_FILENAME,
+];
+
+/// The files which identify a validator directory of a legacy layout.
+const VALIDATOR_FILENAMES: &[&str] = &[
+    KEYSTORE_FILENAME,
+    DEFAULT_PRIVATE_KEY_FILENAME,
+    VALIDATOR_STATE_FILENAME,
+    AUDIT_LOG_FILENAME,
+];
+
+#[derive(Debug, Error)]
+pub enum Error {
+    /// An entry of the data directory could not be read or moved.
+    #[error("unable to migrate {path:?}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    /// The data directory has both a legacy layout and the directory of the network, so it is
+    /// unknown which holds the network's data.
+    #[error("{network_dir:?} already exists, but the data directory also holds {legacy:?} from before it was split by network; move them into {network_dir:?} (validators into its `validators`) or remove them")]
+    Conflict {
+        network_dir: PathBuf,
+        legacy: Vec<PathBuf>,
+    },
+    /// A migration of the legacy layout was interrupted, and is only resumed by starting the
+    /// validator client.
+    #[error("the migration of the data directory into {network_dir:?} was interrupted; start the validator client to resume it")]
+    Interrupted { network_dir: PathBuf },
+    /// A validator client is running with the legacy layout.
+    #[error("unable to migrate the data directory: {0}")]
+    DatadirLock(#[from] datadir_lock::Error),
+}
+
+/// The directory of a network, opened by `open_network_dir`.
+#[derive(Debug, PartialEq)]
+pub struct NetworkDir {
+    pub path: PathBuf,
+    /// The entries of a legacy layout which were moved into `path`.
+    pub migrated: Vec<PathBuf>,
+}
+
+/// Returns the name of the directory of the network of `eth2_config`: its spec constants and
+/// network id, e.g., `mainnet-1`.
+pub fn network_name(eth2_config: &Eth2Config) -> String {
+    format!(
+        "{}-{}",
+        eth2_config.spec_constants, eth2_config.spec.network_id
+    )
+}
+
+/// The `Eth2Config` selected by `select_eth2_config`.
+#[derive(Debug)]
+pub struct SelectedEth2Config {
+    pub eth2_config: Eth2Config,
+    /// The file of the config: that given with `--eth2-spec`, or that of the data directory.
+    pub path: PathBuf,
+    /// The config is not in `path`, so a validator client starting with it writes it there.
+    pub unsaved: bool,
+    /// The config in `path` is ignored in favour of `--default-spec`.
+    pub overridden: bool,
+}
+
+/// Selects the `Eth2Config` of the data directory at `root`, as overridden by `--eth2-spec`
+/// (`spec_file`) and `--default-spec` (`default_spec`).
+///
+/// The spec named by `default_spec` is selected if given, then the config in `spec_file` (or in
+/// `root` if `None`), or else the minimal config (which the validator client writes there on its
+/// first start).
+pub fn select_eth2_config(
+    root: &Path,
+    spec_file: Option<&Path>,
+    default_spec: Option<&str>,
+) -> Result<SelectedEth2Config, String> {
+    let path = spec_file.map_or_else(|| root.join(ETH2_CONFIG_FILENAME), Path::to_path_buf);
+    let from_file = read_from_file::<Eth2Config>(path.clone())?;
+    let named = match default_spec {
+        Some("mainnet") => Some(Eth2Config::mainnet()),
+        Some("minimal") => Some(Eth2Config::minimal()),
+        Some("interop") => Some(Eth2Config::interop()),
+        _ => None,
+    };
+
+    let (eth2_config, unsaved, overridden) = match (named, from_file) {
+        (Some(named), from_file) => (named, from_file.is_none(), from_file.is_some()),
+        (None, Some(from_file)) => (from_file, false, false),
+        (None, None) => (Eth2Config::minimal(), true, false),
+    };
+    Ok(SelectedEth2Config {
+        eth2_config,
+        path,
+        unsaved,
+        overridden,
+    })
+}
+
+/// Returns the directory of the validators of the network of `eth2_config` within the data
+/// directory at `root`, without creating or migrating anything.
+///
+/// The validators of a legacy layout (which has not been migrated yet) are directly in `root`. The
+/// returned directory may not exist, if the validator client has never run.
+pub fn find_validators_dir(root: &Path, eth2_config: &Eth2Config) -> Result<PathBuf, Error> {
+    let name = network_name(eth2_config);
+    let path = root.join(&name);
+    if root.join(format!("{}{}", name, MIGRATING_SUFFIX)).exists() {
+        return Err(Error::Interrupted { network_dir: path });
+    }
+    if !root.exists() {
+        return Ok(path.join(VALIDATORS_DIRNAME));
+    }
+
+    let legacy = legacy_entries(root)?;
+    match (legacy.is_empty(), path.exists()) {
+        (true, _) => Ok(path.join(VALIDATORS_DIRNAME)),
+        (false, false) => Ok(root.to_path_buf()),
+        (false, true) => Err(Error::Conflict {
+            network_dir: path,
+            legacy,
+        }),
+    }
+}
+
+/// Opens (creating it if needed) the directory of the network of `eth2_config` within the data
+/// directory at `root`, first migrating any legacy layout into it.
+pub fn open_network_dir(root: &Path, eth2_config: &Eth2Config) -> Result<NetworkDir, Error> {
+    let name = network_name(eth2_config);
+    let path = root.join(&name);
+    let staging = root.join(format!("{}{}", name, MIGRATING_SUFFIX));
+
+    let legacy = legacy_entries(root)?;
+    if legacy.is_empty() && !staging.exists() {
+        let validators_dir = path.join(VALIDATORS_DIRNAME);
+        fs::create_dir_all(&validators_dir).map_err(io_error(&validators_dir))?;
+        return Ok(NetworkDir {
+            path,
+            migrated: vec![],
+        });
+    }
+    if path.exists() {
+        return Err(Error::Conflict {
+            network_dir: path,
+            legacy,
+        });
+    }
+
+    // A validator client of the legacy layout holds the lock on `root` whilst it runs.
+    let lock = DatadirLock::acquire(root)?;
+    let staging_validators = staging.join(VALIDATORS_DIRNAME);
+    fs::create_dir_all(&staging_validators).map_err(io_error(&staging_validators))?;
+    for entry in &legacy {
+        let file_name = entry
+            .file_name()
+            .expect("legacy entries are named entries of the data directory");
+        let destination = if entry.is_dir() {
+            staging_validators.join(file_name)
+        } else {
+            staging.join(file_name)
+        };
+        fs::rename(entry, &destination).map_err(io_error(entry))?;
+    }
+    // The lock is held until the migration is complete, so that a validator client of the legacy
+    // layout cannot start before then.
+    fs::rename(&staging, &path).map_err(io_error(&staging))?;
+    drop(lock);
+
+    Ok(NetworkDir {
+        path,
+        migrated: legacy,
+    })
+}
+
+/// Returns the entries of `root` which belong to a legacy layout: its validator directories and
+/// the files of its network.
+fn legacy_entries(root: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut legacy = vec![];
+    for entry in fs::read_dir(root).map_err(io_error(root))? {
+        let path = entry.map_err(io_error(root))?.path();
+        let is_legacy = if path.is_dir() {
+            VALIDATOR_FILENAMES
+                .iter()
+                .any(|filename| path.join(filename).exists())
+        } else {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .map_or(false, |name| LEGACY_FILENAMES.contains(&name))
+        };
+        if is_legacy {
+            legacy.push(path);
+        }
+    }
+    legacy.sort();
+    Ok(legacy)
+}
+
+fn io_error(path: &Path) -> impl Fn(io::Error) -> Error {
+    let path = path.to_path_buf();
+    move |source| Error::Io {
+        path: path.clone(),
+        source,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datadir_lock::LOCK_FILENAME;
+    use tempfile::tempdir;
+
+    fn touch(path: &Path) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, b"{}").unwrap();
+    }
+
+    #[test]
+    fn migrates_the_legacy_layout_into_the_network() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        let eth2_config = Eth2Config::minimal();
+        touch(&root.join("0xabcd").join(KEYSTORE_FILENAME));
+        touch(&root.join(SIGNING_LOG_FILENAME));
+        touch(&root.join(CHAIN_IDENTITY_FILENAME));
+        touch(&root.join(ETH2_CONFIG_FILENAME));
+        // neither a validator directory nor network data
+        fs::create_dir(root.join("slashing-drill")).unwrap();
+
+        let network_dir = open_network_dir(root, &eth2_config).unwrap();
+        let path = root.join(network_name(&eth2_config));
+        assert_eq!(network_dir.path, path);
+        assert_eq!(network_dir.migrated.len(), 3);
+        assert!(path
+            .join(VALIDATORS_DIRNAME)
+            .join("0xabcd")
+            .join(KEYSTORE_FILENAME)
+            .exists());
+        assert!(path.join(SIGNING_LOG_FILENAME).exists());
+        assert!(path.join(CHAIN_IDENTITY_FILENAME).exists());
+        assert!(root.join(ETH2_CONFIG_FILENAME).exists());
+        assert!(root.join("slashing-drill").exists());
+        assert!(!root.join(LOCK_FILENAME).exists());
+
+        // Once migrated, the directory is opened as it is.
+        assert_eq!(
+            open_network_dir(root, &eth2_config).unwrap(),
+            NetworkDir {
+                path,
+                migrated: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn keeps_networks_apart() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        let minimal = open_network_dir(root, &Eth2Config::minimal()).unwrap();
+        let mainnet = open_network_dir(root, &Eth2Config::mainnet()).unwrap();
+        assert_ne!(minimal.path, mainnet.path);
+        assert!(mainnet.path.join(VALIDATORS_DIRNAME).is_dir());
+
+        // Legacy data found alongside an existing network directory is not merged into it.
+        touch(&root.join(SIGNING_LOG_FILENAME));
+        match open_network_dir(root, &Eth2Config::minimal()) {
+            Err(Error::Conflict { legacy, .. }) => {
+                assert_eq!(legacy, vec![root.join(SIGNING_LOG_FILENAME)])
+            }
+            other => panic!("expected a conflict, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn resumes_an_interrupted_migration() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        let eth2_config = Eth2Config::minimal();
+        let staging = root.join(format!(
+            "{}{}",
+            network_name(&eth2_config),
+            MIGRATING_SUFFIX
+        ));
+        touch(
+            &staging
+                .join(VALIDATORS_DIRNAME)
+                .join("0xabcd")
+                .join(KEYSTORE_FILENAME),
+        );
+        touch(&root.join(DUTIES_CACHE_FILENAME));
+
+        let network_dir = open_network_dir(root, &eth2_config).unwrap();
+        assert!(network_dir.path.join(DUTIES_CACHE_FILENAME).exists());
+        assert!(network_dir
+            .path
+            .join(VALIDATORS_DIRNAME)
+            .join("0xabcd")
+            .exists());
+        assert!(!staging.exists());
+    }
+
+    #[test]
+    fn finds_validators_without_migrating() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        let eth2_config = Eth2Config::minimal();
+        let path = root.join(network_name(&eth2_config));
+
+        // Nothing is created for a validator client which has never run.
+        assert_eq!(
+            find_validators_dir(root, &eth2_config).unwrap(),
+            path.join(VALIDATORS_DIRNAME)
+        );
+        assert!(!path.exists());
+
+        // The validators of a legacy layout are found where they are.
+        touch(&root.join("0xabcd").join(KEYSTORE_FILENAME));
+        assert_eq!(find_validators_dir(root, &eth2_config).unwrap(), root);
+        assert!(!path.exists());
+
+        open_network_dir(root, &eth2_config).unwrap();
+        assert_eq!(
+            find_validators_dir(root, &eth2_config).unwrap(),
+            path.join(VALIDATORS_DIRNAME)
+        );
+    }
+
+    #[test]
+    fn selects_the_overridden_eth2_config() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+
+        let selected = select_eth2_config(root, None, None).unwrap();
+        assert_eq!(selected.eth2_config.spec_constants, "minimal");
+        assert!(selected.unsaved);
+
+        eth2_config::write_to_file(root.join(ETH2_CONFIG_FILENAME), &Eth2Config::mainnet())
+            .unwrap();
+        let selected = select_eth2_config(root, None, None).unwrap();
+        assert_eq!(selected.eth2_config.spec_constants, "mainnet");
+        assert!(!selected.unsaved);
+
+        // `--default-spec` overrides the file.
+        let selected = select_eth2_config(root, None, Some("interop")).unwrap();
+        assert_eq!(selected.eth2_config.spec_constants, "interop");
+        assert!(selected.overridden);
+
+        // `--eth2-spec` is read instead of the file of the data directory.
+        let spec_file = root.join("other-spec.toml");
+        eth2_config::write_to_file(spec_file.clone(), &Eth2Config::interop()).unwrap();
+        let selected = select_eth2_config(root, Some(&spec_file), None).unwrap();
+        assert_eq!(selected.eth2_config.spec_constants, "interop");
+        assert_eq!(selected.path, spec_file);
+    }
+}
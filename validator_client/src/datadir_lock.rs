@@ -0,0 +1,192 @@
+//! An exclusive lock on the data directory, so that two validator clients never sign with the same
+//! keys and slashing protection history at once.
+//!
+//! The lock is an OS file lock on `<datadir>/<network>/validator.lock`, which holds the PID and
+//! start time of the validator client holding it. The OS releases the lock when its holder exits,
+//! even if it crashes, so a leftover lockfile does not prevent starting. The lockfile is removed
+//! when the lock is released.
+//!
+//! If the lock cannot be acquired (e.g., it is held by a hung process on a network filesystem),
+//! `DatadirLock::force_unlock` removes the lockfile so that it may be acquired regardless. This is
+//! only safe if its holder is no longer signing.
+use fs2::FileExt;
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use uuid::Uuid;
+
+/// The name of the lockfile within the data directory.
+pub const LOCK_FILENAME: &str = "validator.lock";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The lockfile could not be read or written.
+    #[error("unable to access the lockfile {path:?}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    /// Another process holds the lock.
+    #[error("the data directory is in use by {holder}; stop it, or restart with --force-unlock if it is no longer running")]
+    Locked { path: PathBuf, holder: String },
+}
+
+/// The process which holds the lock.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct LockHolder {
+    pub pid: u32,
+    /// The time at which the lock was acquired, in seconds since the UNIX epoch.
+    pub started: u64,
+    /// Distinguishes the lock from any other acquired by the same process.
+    pub token: String,
+}
+
+impl LockHolder {
+    fn current() -> Self {
+        Self {
+            pid: std::process::id(),
+            started: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|since_epoch| since_epoch.as_secs())
+                .unwrap_or_default(),
+            token: Uuid::new_v4().to_string(),
+        }
+    }
+
+    /// Reads the holder from the lockfile at `path`, if it has a valid one.
+    fn read(path: &Path) -> Option<Self> {
+        serde_json::from_slice(&fs::read(path).ok()?).ok()
+    }
+}
+
+impl fmt::Display for LockHolder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "process {} (started at {} seconds since the UNIX epoch)",
+            self.pid, self.started
+        )
+    }
+}
+
+/// A held lock on a data directory, released when dropped.
+#[derive(Debug)]
+pub struct DatadirLock {
+    path: PathBuf,
+    holder: LockHolder,
+    // The lock is held for as long as the file is open.
+    _file: File,
+}
+
+impl DatadirLock {
+    /// Returns the path of the lockfile in `data_dir`.
+    pub fn path(data_dir: &Path) -> PathBuf {
+        data_dir.join(LOCK_FILENAME)
+    }
+
+    /// Locks `data_dir`, failing if another process holds the lock.
+    pub fn acquire(data_dir: &Path) -> Result<Self, Error> {
+        let path = Self::path(data_dir);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(&path)
+            .map_err(io_error(&path))?;
+
+        if let Err(e) = file.try_lock_exclusive() {
+            if e.kind() == ErrorKind::WouldBlock
+                || e.raw_os_error() == fs2::lock_contended_error().raw_os_error()
+            {
+                let holder = LockHolder::read(&path)
+                    .map_or_else(|| "another process".to_string(), |h| h.to_string());
+                return Err(Error::Locked { path, holder });
+            }
+            return Err(io_error(&path)(e));
+        }
+
+        let holder = LockHolder::current();
+        let contents = serde_json::to_vec(&holder).map_err(|e| io_error(&path)(e.into()))?;
+        file.set_len(0)
+            .and_then(|()| file.write_all(&contents))
+            .and_then(|()| file.sync_all())
+            .map_err(io_error(&path))?;
+
+        Ok(Self {
+            path,
+            holder,
+            _file: file,
+        })
+    }
+
+    /// Removes the lockfile of `data_dir` regardless of whether its lock is held, returning its
+    /// holder if it has one.
+    ///
+    /// If the holder is still running, it and the next validator client will sign at once.
+    pub fn force_unlock(data_dir: &Path) -> Result<Option<LockHolder>, Error> {
+        let path = Self::path(data_dir);
+        let holder = LockHolder::read(&path);
+        match fs::remove_file(&path) {
+            Ok(()) => Ok(holder),
+            Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(io_error(&path)(e)),
+        }
+    }
+}
+
+impl Drop for DatadirLock {
+    /// Removes the lockfile, unless it has been forcibly replaced by another process.
+    fn drop(&mut self) {
+        if LockHolder::read(&self.path).as_ref() == Some(&self.holder) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+fn io_error(path: &Path) -> impl FnOnce(io::Error) -> Error + '_ {
+    move |source| Error::Io {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn excludes_other_holders_until_forced() {
+        let dir = TempDir::new().expect("should create temp dir");
+        let path = DatadirLock::path(dir.path());
+
+        let first = DatadirLock::acquire(dir.path()).expect("should acquire lock");
+        assert_eq!(LockHolder::read(&path), Some(first.holder.clone()));
+        assert_eq!(first.holder.pid, std::process::id());
+        match DatadirLock::acquire(dir.path()) {
+            Err(Error::Locked { holder, .. }) => assert_eq!(holder, first.holder.to_string()),
+            other => panic!("expected the lock to be held, found {:?}", other),
+        }
+
+        assert_eq!(
+            DatadirLock::force_unlock(dir.path()).expect("should force unlock"),
+            Some(first.holder.clone())
+        );
+        let second = DatadirLock::acquire(dir.path()).expect("should acquire forced lock");
+
+        // Releasing the forced lock leaves the lockfile of its replacement.
+        drop(first);
+        assert!(path.exists());
+        drop(second);
+        assert!(!path.exists());
+        assert_eq!(
+            DatadirLock::force_unlock(dir.path()).expect("should force unlock"),
+            None
+        );
+    }
+}
@@ -0,0 +1,171 @@
+//! `/lighthouse/duties` and `/lighthouse/proposals` HTTP endpoints, which expose the state behind
+//! the decisions of the validator client as JSON so that support requests may be debugged.
+//!
+//! - `/lighthouse/duties` responds with the duties cache: the duties of every validator in each
+//!   epoch known, and whether those duties are stale after a re-org.
+//! - `/lighthouse/proposals` responds with the outcome of the latest proposal of each validator
+//!   (see `proposal_timing`) and the slashing protection watermarks of each validator.
+//!
+//! Both are served by the health server (see `health`) and are not intended for automation; their
+//! fields may change between releases.
+use crate::duties::{DutiesStore, DutiesStoreError, EpochDuty};
+use crate::proposal_timing::{ProposalOutcome, ProposalTimings};
+use crate::slashing_protection::{NotSafe, SlashingProtection, Watermarks};
+use serde_derive::Serialize;
+use thiserror::Error;
+use types::{Epoch, Hash256};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("unable to read the duties store: {0}")]
+    DutiesStore(#[from] DutiesStoreError),
+    #[error("unable to read slashing protection: {0:?}")]
+    SlashingProtection(NotSafe),
+}
+
+/// The duties of a single validator during a single epoch.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct ValidatorDuties {
+    pub validator: String,
+    /// `None` if the validator is not active.
+    pub duty: Option<EpochDuty>,
+    pub aggregator: bool,
+}
+
+/// The duties of every validator during a single epoch.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct Duties {
+    pub epoch: Epoch,
+    pub dependent_root: Option<Hash256>,
+    /// `true` if the duties may have been changed by a re-org, and are yet to be obtained again.
+    pub stale: bool,
+    /// The duties of each validator, ordered by validator.
+    pub validators: Vec<ValidatorDuties>,
+}
+
+/// The latest production decisions of every validator.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct Proposals {
+    pub outcomes: Vec<ProposalOutcome>,
+    pub watermarks: Vec<Watermarks>,
+}
+
+/// Returns the duties of each epoch in `store`, in ascending order.
+pub fn duties(store: &DutiesStore) -> Result<Vec<Duties>, Error> {
+    let stale = store.stale_epochs()?;
+    let mut all = vec![];
+    for epoch in store.epochs()? {
+        let epoch_duties = match store.epoch_duties(epoch)? {
+            Some(epoch_duties) => epoch_duties,
+            None => continue,
+        };
+
+        let mut validators = epoch_duties
+            .into_iter()
+            .map(|(pubkey, duty)| {
+                Ok(ValidatorDuties {
+                    aggregator: store.is_aggregator(epoch, &pubkey)?,
+                    validator: pubkey.as_hex_string(),
+                    duty,
+                })
+            })
+            .collect::<Result<Vec<_>, DutiesStoreError>>()?;
+        validators.sort_by(|a, b| a.validator.cmp(&b.validator));
+
+        all.push(Duties {
+            epoch,
+            dependent_root: store.dependent_root(epoch)?,
+            stale: stale.contains(&epoch),
+            validators,
+        });
+    }
+    Ok(all)
+}
+
+/// Returns the latest proposal outcomes in `timings` and the watermarks in `slashing_protection`.
+pub fn proposals(
+    timings: &ProposalTimings,
+    slashing_protection: &SlashingProtection,
+) -> Result<Proposals, Error> {
+    Ok(Proposals {
+        outcomes: timings.outcomes(),
+        watermarks: slashing_protection
+            .watermarks()
+            .map_err(Error::SlashingProtection)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::duties::EpochDuties;
+    use types::{AttestationDuty, Keypair, Slot};
+
+    fn duty(slot: u64) -> Option<EpochDuty> {
+        Some(EpochDuty {
+            block_production_slots: vec![Slot::new(slot)],
+            attestation_duty: AttestationDuty {
+                slot: Slot::new(slot),
+                shard: 0,
+                committee_index: 0,
+                committee_len: 1,
+            },
+        })
+    }
+
+    #[test]
+    fn exposes_duties_outcomes_and_watermarks() {
+        let store = DutiesStore::new(8);
+        let active = Keypair::random().pk;
+        let inactive = Keypair::random().pk;
+        let epoch_duties: EpochDuties = vec![(active.clone(), duty(9)), (inactive, None)]
+            .into_iter()
+            .collect();
+        store
+            .insert(
+                Epoch::new(1),
+                epoch_duties,
+                Some(Hash256::from_low_u64_be(1)),
+            )
+            .expect("should insert duties");
+        store
+            .set_aggregator(Epoch::new(1), &active, true)
+            .expect("should set aggregator");
+        store
+            .mark_stale_after_fork(Slot::new(0))
+            .expect("should mark stale");
+
+        let duties = duties(&store).expect("should read duties");
+        assert_eq!(duties.len(), 1);
+        assert_eq!(duties[0].epoch, Epoch::new(1));
+        assert_eq!(duties[0].dependent_root, Some(Hash256::from_low_u64_be(1)));
+        assert!(duties[0].stale);
+        let active_duties = duties[0]
+            .validators
+            .iter()
+            .find(|duties| duties.validator == active.as_hex_string())
+            .expect("should include the active validator");
+        assert_eq!(active_duties.duty, duty(9));
+        assert!(active_duties.aggregator);
+        assert_eq!(duties[0].validators.len(), 2);
+
+        let timings = ProposalTimings::default();
+        let slashing_protection = SlashingProtection::new();
+        timings.record_outcome(Slot::new(9), &active, "BlockProduced(9)".to_string());
+        slashing_protection
+            .check_and_insert_block(&active, Slot::new(9), Hash256::from_low_u64_be(2))
+            .expect("should insert block");
+
+        let proposals = proposals(&timings, &slashing_protection).expect("should read proposals");
+        assert_eq!(proposals.outcomes.len(), 1);
+        assert_eq!(proposals.watermarks.len(), 1);
+        assert_eq!(proposals.watermarks[0].validator, active);
+        assert_eq!(
+            proposals.watermarks[0].latest_block_slot,
+            Some(Slot::new(9))
+        );
+        assert_eq!(proposals.watermarks[0].latest_attestation_target, None);
+        assert!(serde_json::to_string(&proposals).is_ok());
+        assert!(serde_json::to_string(&duties).is_ok());
+    }
+}
@@ -1,20 +1,60 @@
-use super::EpochDuties;
-use types::{Epoch, PublicKey};
+use super::{EpochDuties, SubnetSubscription};
+use futures::Future;
+use thiserror::Error;
+use types::{Address, Epoch, Hash256, PublicKey};
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Error)]
 pub enum BeaconNodeDutiesError {
+    #[error("beacon node request failed: {0}")]
     RemoteFailure(String),
 }
 
+/// The duties of some validators during an epoch, as reported by a Beacon Node.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DutiesResponse {
+    pub duties: EpochDuties,
+    /// The root of the block at the last slot of the previous epoch, which determines the duties.
+    ///
+    /// `None` if the Beacon Node does not report it.
+    pub dependent_root: Option<Hash256>,
+    /// The requested validators which have been slashed.
+    pub slashed: Vec<PublicKey>,
+    /// The duties during the next epoch, if the Beacon Node can look ahead to it.
+    ///
+    /// Its block proposers are tentative until the epoch starts, as they depend on the effective
+    /// balances of the validators at its start.
+    pub next_epoch_duties: Option<EpochDuties>,
+}
+
+/// A future which resolves to the response of a Beacon Node to a duties request.
+pub type BeaconNodeDutiesFuture<T> =
+    Box<dyn Future<Item = T, Error = BeaconNodeDutiesError> + Send>;
+
 /// Defines the methods required to obtain a validators shuffling from a Beacon Node.
+///
+/// All requests are asynchronous, so that no thread is blocked whilst awaiting a response.
 pub trait BeaconNodeDuties: Send + Sync {
-    /// Gets the duties for all validators.
+    /// Gets the duties of all `pub_keys` in a single request.
     ///
-    /// Returns a vector of EpochDuties for each validator public key. The entry will be None for
+    /// Returns the `EpochDuties` of every validator public key. The entry will be None for
     /// validators that are not activated.
     fn request_duties(
         &self,
         epoch: Epoch,
         pub_keys: &[PublicKey],
-    ) -> Result<EpochDuties, BeaconNodeDutiesError>;
+    ) -> BeaconNodeDutiesFuture<DutiesResponse>;
+
+    /// Registers the fee recipient of each validator with the Beacon Node, to be used in their
+    /// future block proposals.
+    fn prepare_beacon_proposer(
+        &self,
+        preparations: &[(PublicKey, Address)],
+    ) -> BeaconNodeDutiesFuture<()>;
+
+    /// Informs the Beacon Node of upcoming attestation duties, so that it subscribes to the subnets
+    /// of those whose validators aggregate.
+    fn prepare_attestation_subnets(
+        &self,
+        subscriptions: &[SubnetSubscription],
+    ) -> BeaconNodeDutiesFuture<()>;
 }
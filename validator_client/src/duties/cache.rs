@@ -0,0 +1,150 @@
+//! Persists the known duties to `<datadir>/<network>/duties_cache.json`, so that a validator client
+//! which restarts mid-epoch can perform its duties (notably its proposals) before the beacon node
+//! has responded to its first duties request.
+//!
+//! The cache is written whenever the duties are checked, and holds the duties of the current and
+//! any later epochs with their dependent roots. Restored duties are marked stale, so they are used
+//! only until they have been verified against a fresh response of the beacon node.
+use super::{DutiesStore, DutiesStoreError, EpochDuties, EpochDuty};
+use serde_derive::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use types::{Epoch, Hash256, PublicKey};
+
+/// The name of the duties cache within the data directory.
+pub const DUTIES_CACHE_FILENAME: &str = "duties_cache.json";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("unable to read or write the duties cache: {0}")]
+    Io(#[from] io::Error),
+    #[error("invalid duties cache: {0}")]
+    Invalid(#[from] serde_json::Error),
+    #[error("unable to access the duties store: {0}")]
+    Store(#[from] DutiesStoreError),
+}
+
+/// The duties of every validator during `epoch`, as obtained from the beacon node.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+struct CachedEpochDuties {
+    epoch: Epoch,
+    dependent_root: Option<Hash256>,
+    duties: Vec<(PublicKey, Option<EpochDuty>)>,
+}
+
+/// The duties cache of a data directory.
+#[derive(Debug, Clone)]
+pub struct DutiesCache {
+    path: PathBuf,
+}
+
+impl DutiesCache {
+    pub fn new(data_dir: &Path) -> Self {
+        Self {
+            path: data_dir.join(DUTIES_CACHE_FILENAME),
+        }
+    }
+
+    /// Writes the duties in `store` of `current_epoch` and any later epochs to the cache.
+    pub fn save(&self, store: &DutiesStore, current_epoch: Epoch) -> Result<(), Error> {
+        let mut cached = vec![];
+        for epoch in store.epochs()? {
+            if epoch < current_epoch {
+                continue;
+            }
+            if let Some(duties) = store.epoch_duties(epoch)? {
+                cached.push(CachedEpochDuties {
+                    epoch,
+                    dependent_root: store.dependent_root(epoch)?,
+                    duties: duties.into_iter().collect(),
+                });
+            }
+        }
+
+        // Write to a temporary file and rename, so the cache is never partially written.
+        let temp_path = self.path.with_extension("json.tmp");
+        serde_json::to_writer(File::create(&temp_path)?, &cached)?;
+        fs::rename(&temp_path, &self.path)?;
+        Ok(())
+    }
+
+    /// Inserts the cached duties of `current_epoch` and any later epochs into `store`, marked
+    /// stale, returning the epochs restored.
+    pub fn restore(&self, store: &DutiesStore, current_epoch: Epoch) -> Result<Vec<Epoch>, Error> {
+        let cached: Vec<CachedEpochDuties> = match File::open(&self.path) {
+            Ok(file) => serde_json::from_reader(file)?,
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut restored = vec![];
+        for epoch_duties in cached {
+            if epoch_duties.epoch < current_epoch {
+                continue;
+            }
+            let duties: EpochDuties = epoch_duties.duties.into_iter().collect();
+            store.insert(epoch_duties.epoch, duties, epoch_duties.dependent_root)?;
+            store.mark_stale(epoch_duties.epoch)?;
+            restored.push(epoch_duties.epoch);
+        }
+        Ok(restored)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use types::{AttestationDuty, Keypair, Slot};
+
+    #[test]
+    fn restores_later_duties_as_stale() {
+        let dir = TempDir::new().expect("should create temp dir");
+        let cache = DutiesCache::new(dir.path());
+        let proposer = Keypair::random().pk;
+        let store = DutiesStore::new(8);
+        assert_eq!(
+            cache
+                .restore(&store, Epoch::new(0))
+                .expect("should restore nothing"),
+            vec![]
+        );
+
+        for epoch in 1..4 {
+            let mut duties = EpochDuties::new();
+            duties.insert(
+                proposer.clone(),
+                Some(EpochDuty {
+                    block_production_slots: vec![Epoch::new(epoch).start_slot(8) + 1],
+                    attestation_duty: AttestationDuty::default(),
+                }),
+            );
+            store
+                .insert(
+                    Epoch::new(epoch),
+                    duties,
+                    Some(Hash256::from_low_u64_be(epoch)),
+                )
+                .expect("should insert duties");
+        }
+        cache
+            .save(&store, Epoch::new(2))
+            .expect("should save duties");
+
+        let restored = DutiesStore::new(8);
+        assert_eq!(
+            cache
+                .restore(&restored, Epoch::new(2))
+                .expect("should restore duties"),
+            vec![Epoch::new(2), Epoch::new(3)]
+        );
+        assert_eq!(restored.proposers(Slot::new(17)), Ok(vec![proposer]));
+        assert_eq!(
+            restored.dependent_root(Epoch::new(2)),
+            Ok(Some(Hash256::from_low_u64_be(2)))
+        );
+        assert_eq!(restored.stale_epochs(), restored.epochs());
+    }
+}
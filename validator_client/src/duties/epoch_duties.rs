@@ -1,7 +1,7 @@
+use serde_derive::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt;
-use std::ops::{Deref, DerefMut};
-use types::{AttestationDuty, Epoch, PublicKey, Slot};
+use types::{AttestationDuty, PublicKey, Slot};
 
 /// When work needs to be performed by a validator, this type is given back to the main service
 /// which indicates all the information that required to process the work.
@@ -13,6 +13,8 @@ pub struct WorkInfo {
     pub produce_block: bool,
     /// Validator needs to produce an attestation. This supplies the required attestation data.
     pub attestation_duty: Option<AttestationDuty>,
+    /// Validator needs to aggregate the attestations of its committee.
+    pub aggregator: bool,
 }
 
 /// The information required for a validator to propose and attest during some epoch.
@@ -20,9 +22,12 @@ pub struct WorkInfo {
 /// Generally obtained from a Beacon Node, this information contains the validators canonical index
 /// (their sequence in the global validator induction process) and the "shuffling" for that index
 /// for some epoch.
-#[derive(Debug, PartialEq, Clone, Copy, Default)]
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
 pub struct EpochDuty {
-    pub block_production_slot: Option<Slot>,
+    /// The slots at which the validator proposes, in ascending order. A validator may propose at
+    /// any number of slots in an epoch.
+    #[serde(default)]
+    pub block_production_slots: Vec<Slot>,
     pub attestation_duty: AttestationDuty,
 }
 
@@ -30,10 +35,7 @@ impl EpochDuty {
     /// Returns `WorkInfo` if work needs to be done in the supplied `slot`
     pub fn is_work_slot(&self, slot: Slot) -> Option<WorkInfo> {
         // if validator is required to produce a slot return true
-        let produce_block = match self.block_production_slot {
-            Some(s) if s == slot => true,
-            _ => false,
-        };
+        let produce_block = self.block_production_slots.contains(&slot);
 
         // if the validator is required to attest to a shard, create the data
         let mut attestation_duty = None;
@@ -45,6 +47,7 @@ impl EpochDuty {
             return Some(WorkInfo {
                 produce_block,
                 attestation_duty,
+                aggregator: false,
             });
         }
         None
@@ -54,12 +57,17 @@ impl EpochDuty {
 impl fmt::Display for EpochDuty {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut display_block = String::from("None");
-        if let Some(block_slot) = self.block_production_slot {
-            display_block = block_slot.to_string();
+        if !self.block_production_slots.is_empty() {
+            display_block = self
+                .block_production_slots
+                .iter()
+                .map(Slot::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
         }
         write!(
             f,
-            "produce block slot: {}, attestation slot: {}, attestation shard: {}",
+            "produce block slots: {}, attestation slot: {}, attestation shard: {}",
             display_block, self.attestation_duty.slot, self.attestation_duty.shard
         )
     }
@@ -67,67 +75,3 @@ impl fmt::Display for EpochDuty {
 
 /// Maps a list of keypairs (many validators) to an EpochDuty.
 pub type EpochDuties = HashMap<PublicKey, Option<EpochDuty>>;
-
-pub enum EpochDutiesMapError {
-    UnknownEpoch,
-    UnknownValidator,
-}
-
-/// Maps an `epoch` to some `EpochDuties` for a single validator.
-pub struct EpochDutiesMap {
-    pub slots_per_epoch: u64,
-    pub map: HashMap<Epoch, EpochDuties>,
-}
-
-impl EpochDutiesMap {
-    pub fn new(slots_per_epoch: u64) -> Self {
-        Self {
-            slots_per_epoch,
-            map: HashMap::new(),
-        }
-    }
-}
-
-// Expose the hashmap methods
-impl Deref for EpochDutiesMap {
-    type Target = HashMap<Epoch, EpochDuties>;
-
-    fn deref(&self) -> &Self::Target {
-        &self.map
-    }
-}
-impl DerefMut for EpochDutiesMap {
-    fn deref_mut(&mut self) -> &mut HashMap<Epoch, EpochDuties> {
-        &mut self.map
-    }
-}
-
-impl EpochDutiesMap {
-    /// Checks if the validator has work to do.
-    pub fn is_work_slot(
-        &self,
-        slot: Slot,
-        signer: &PublicKey,
-    ) -> Result<Option<WorkInfo>, EpochDutiesMapError> {
-        let epoch = slot.epoch(self.slots_per_epoch);
-
-        let epoch_duties = self
-            .map
-            .get(&epoch)
-            .ok_or_else(|| EpochDutiesMapError::UnknownEpoch)?;
-        if let Some(epoch_duty) = epoch_duties.get(signer) {
-            if let Some(duty) = epoch_duty {
-                // Retrieves the duty for a validator at a given slot
-                return Ok(duty.is_work_slot(slot));
-            } else {
-                // the validator isn't active
-                return Ok(None);
-            }
-        } else {
-            // validator isn't known
-            return Err(EpochDutiesMapError::UnknownValidator);
-        }
-    }
-}
-
-// TODO: add tests.
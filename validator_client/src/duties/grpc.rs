@@ -1,21 +1,30 @@
-use super::beacon_node_duties::{BeaconNodeDuties, BeaconNodeDutiesError};
+use super::beacon_node_duties::{
+    BeaconNodeDuties, BeaconNodeDutiesError, BeaconNodeDutiesFuture, DutiesResponse,
+};
 use super::epoch_duties::{EpochDuties, EpochDuty};
+use super::subnets::SubnetSubscription;
+use futures::{future, Future};
 // to use if we manually specify a timeout
 //use grpcio::CallOption;
-use protos::services::{GetDutiesRequest, Validators};
+use protos::auth::Authenticated;
+use protos::services::{
+    ActiveValidator, GetDutiesRequest, GetDutiesResponse, PrepareAttestationSubnetsRequest,
+    PrepareBeaconProposerRequest, ProposerPreparation, SubnetSubscription as GrpcSubscription,
+    Validators,
+};
 use protos::services_grpc::ValidatorServiceClient;
 use ssz::ssz_encode;
 use std::collections::HashMap;
 // use std::time::Duration;
-use types::{AttestationDuty, Epoch, PublicKey, Slot};
+use types::{Address, AttestationDuty, Epoch, Hash256, PublicKey, Slot};
 
-impl BeaconNodeDuties for ValidatorServiceClient {
+impl BeaconNodeDuties for Authenticated<ValidatorServiceClient> {
     /// Requests all duties (block signing and committee attesting) from the Beacon Node (BN).
     fn request_duties(
         &self,
         epoch: Epoch,
         pub_keys: &[PublicKey],
-    ) -> Result<EpochDuties, BeaconNodeDutiesError> {
+    ) -> BeaconNodeDutiesFuture<DutiesResponse> {
         // Get the required duties from all validators
         // build the request
         let mut req = GetDutiesRequest::new();
@@ -28,40 +37,220 @@ impl BeaconNodeDuties for ValidatorServiceClient {
         // let call_opt = CallOption::default().timeout(Duration::from_secs(2));
 
         // send the request, get the duties reply
-        let reply = self
-            .get_validator_duties(&req)
-            .map_err(|err| BeaconNodeDutiesError::RemoteFailure(format!("{:?}", err)))?;
-
-        let mut epoch_duties: HashMap<PublicKey, Option<EpochDuty>> = HashMap::new();
-        for (index, validator_duty) in reply.get_active_validators().iter().enumerate() {
-            if !validator_duty.has_duty() {
-                // validator is inactive
-                epoch_duties.insert(pub_keys[index].clone(), None);
-                continue;
-            }
-            // active validator
-            let active_duty = validator_duty.get_duty();
-            let block_production_slot = {
-                if active_duty.has_block_production_slot() {
-                    Some(Slot::from(active_duty.get_block_production_slot()))
-                } else {
-                    None
-                }
-            };
-
-            let attestation_duty = AttestationDuty {
-                slot: Slot::from(active_duty.get_attestation_slot()),
-                shard: active_duty.get_attestation_shard(),
-                committee_index: active_duty.get_committee_index() as usize,
-                committee_len: active_duty.get_committee_len() as usize,
-            };
-
-            let epoch_duty = EpochDuty {
-                block_production_slot,
-                attestation_duty,
-            };
-            epoch_duties.insert(pub_keys[index].clone(), Some(epoch_duty));
+        let pub_keys = pub_keys.to_vec();
+        let call = self
+            .client()
+            .get_validator_duties_async_opt(&req, self.call_option());
+        Box::new(
+            future::result(call)
+                .flatten()
+                .map_err(|err| BeaconNodeDutiesError::RemoteFailure(format!("{:?}", err)))
+                .and_then(move |reply| {
+                    // beacon nodes which predate `next_epoch_duties` never look ahead
+                    let next_epoch_duties = if reply.get_has_next_epoch_duties() {
+                        Some(epoch_duties(reply.get_next_epoch_duties(), &pub_keys)?)
+                    } else {
+                        None
+                    };
+                    Ok(DutiesResponse {
+                        duties: epoch_duties(reply.get_duties(), &pub_keys)?,
+                        dependent_root: dependent_root(&reply)?,
+                        slashed: slashed(&reply, &pub_keys),
+                        next_epoch_duties,
+                    })
+                }),
+        )
+    }
+
+    /// Registers the fee recipient of each validator with the Beacon Node.
+    fn prepare_beacon_proposer(
+        &self,
+        preparations: &[(PublicKey, Address)],
+    ) -> BeaconNodeDutiesFuture<()> {
+        let mut req = PrepareBeaconProposerRequest::new();
+        req.set_preparations(
+            preparations
+                .iter()
+                .map(|(public_key, fee_recipient)| {
+                    let mut preparation = ProposerPreparation::new();
+                    preparation.set_public_key(ssz_encode(public_key));
+                    preparation.set_fee_recipient(fee_recipient.as_bytes().to_vec());
+                    preparation
+                })
+                .collect(),
+        );
+
+        let call = self
+            .client()
+            .prepare_beacon_proposer_async_opt(&req, self.call_option());
+        Box::new(
+            future::result(call)
+                .flatten()
+                .map(|_| ())
+                .map_err(|err| BeaconNodeDutiesError::RemoteFailure(format!("{:?}", err))),
+        )
+    }
+
+    /// Sends the upcoming attestation duties of the validators to the Beacon Node.
+    fn prepare_attestation_subnets(
+        &self,
+        subscriptions: &[SubnetSubscription],
+    ) -> BeaconNodeDutiesFuture<()> {
+        let mut req = PrepareAttestationSubnetsRequest::new();
+        req.set_subscriptions(
+            subscriptions
+                .iter()
+                .map(|subscription| {
+                    let mut grpc_subscription = GrpcSubscription::new();
+                    grpc_subscription.set_public_key(ssz_encode(&subscription.public_key));
+                    grpc_subscription.set_slot(subscription.slot.as_u64());
+                    grpc_subscription.set_shard(subscription.shard);
+                    grpc_subscription.set_aggregator(subscription.aggregator);
+                    grpc_subscription
+                })
+                .collect(),
+        );
+
+        let call = self
+            .client()
+            .prepare_attestation_subnets_async_opt(&req, self.call_option());
+        Box::new(
+            future::result(call)
+                .flatten()
+                .map(|_| ())
+                .map_err(|err| BeaconNodeDutiesError::RemoteFailure(format!("{:?}", err))),
+        )
+    }
+}
+
+/// Returns the dependent root of `reply`, or `None` if the Beacon Node did not set it.
+fn dependent_root(reply: &GetDutiesResponse) -> Result<Option<Hash256>, BeaconNodeDutiesError> {
+    let root = reply.get_dependent_root();
+    match root.len() {
+        0 => Ok(None),
+        32 => Ok(Some(Hash256::from_slice(root))),
+        len => Err(BeaconNodeDutiesError::RemoteFailure(format!(
+            "invalid dependent root of {} bytes",
+            len
+        ))),
+    }
+}
+
+/// Returns those of `pub_keys` which `reply` reports as slashed.
+fn slashed(reply: &GetDutiesResponse, pub_keys: &[PublicKey]) -> Vec<PublicKey> {
+    pub_keys
+        .iter()
+        .filter(|pub_key| reply.get_slashed().contains(&pub_key.as_hex_string()))
+        .cloned()
+        .collect()
+}
+
+/// Converts the duties of `pub_keys` in `duties`, the duties of a reply keyed by public key, to
+/// `EpochDuties`.
+///
+/// Returns an error if the duties of any of `pub_keys` are missing from `duties`.
+fn epoch_duties(
+    duties: &HashMap<String, ActiveValidator>,
+    pub_keys: &[PublicKey],
+) -> Result<EpochDuties, BeaconNodeDutiesError> {
+    let mut epoch_duties: HashMap<PublicKey, Option<EpochDuty>> = HashMap::new();
+    for pub_key in pub_keys {
+        let validator_duty = duties.get(&pub_key.as_hex_string()).ok_or_else(|| {
+            BeaconNodeDutiesError::RemoteFailure(format!("no duties returned for {}", pub_key))
+        })?;
+        if !validator_duty.has_duty() {
+            // validator is inactive
+            epoch_duties.insert(pub_key.clone(), None);
+            continue;
         }
-        Ok(epoch_duties)
+        // active validator
+        let active_duty = validator_duty.get_duty();
+        let mut block_production_slots: Vec<Slot> = active_duty
+            .get_block_production_slots()
+            .iter()
+            .map(|slot| Slot::from(*slot))
+            .collect();
+        // beacon nodes which predate `block_production_slots` only report the first proposal
+        if block_production_slots.is_empty() && active_duty.has_block_production_slot() {
+            block_production_slots.push(Slot::from(active_duty.get_block_production_slot()));
+        }
+
+        let attestation_duty = AttestationDuty {
+            slot: Slot::from(active_duty.get_attestation_slot()),
+            shard: active_duty.get_attestation_shard(),
+            committee_index: active_duty.get_committee_index() as usize,
+            committee_len: active_duty.get_committee_len() as usize,
+        };
+
+        let epoch_duty = EpochDuty {
+            block_production_slots,
+            attestation_duty,
+        };
+        epoch_duties.insert(pub_key.clone(), Some(epoch_duty));
+    }
+    Ok(epoch_duties)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protos::services::ValidatorDuty;
+    use types::Keypair;
+
+    #[test]
+    fn duties_are_keyed_by_public_key() {
+        let active = Keypair::random().pk;
+        let inactive = Keypair::random().pk;
+
+        let mut duty = ValidatorDuty::new();
+        duty.set_block_production_slot(9);
+        duty.set_block_production_slots(vec![9, 12]);
+        duty.set_attestation_slot(10);
+        duty.set_attestation_shard(2);
+        duty.set_committee_index(1);
+        duty.set_committee_len(4);
+        let mut active_validator = ActiveValidator::new();
+        active_validator.set_duty(duty);
+        let mut inactive_validator = ActiveValidator::new();
+        inactive_validator.set_none(false);
+
+        let mut reply = GetDutiesResponse::new();
+        reply
+            .mut_duties()
+            .insert(active.as_hex_string(), active_validator);
+        reply
+            .mut_duties()
+            .insert(inactive.as_hex_string(), inactive_validator);
+
+        // The order of the public keys does not matter.
+        let duties = epoch_duties(reply.get_duties(), &[inactive.clone(), active.clone()])
+            .expect("should find all duties");
+        assert_eq!(duties.get(&inactive), Some(&None));
+        assert_eq!(
+            duties.get(&active),
+            Some(&Some(EpochDuty {
+                block_production_slots: vec![Slot::new(9), Slot::new(12)],
+                attestation_duty: AttestationDuty {
+                    slot: Slot::new(10),
+                    shard: 2,
+                    committee_index: 1,
+                    committee_len: 4,
+                },
+            }))
+        );
+
+        assert!(epoch_duties(reply.get_duties(), &[Keypair::random().pk]).is_err());
+    }
+
+    #[test]
+    fn dependent_root_is_optional() {
+        let mut reply = GetDutiesResponse::new();
+        assert_eq!(dependent_root(&reply), Ok(None));
+
+        reply.set_dependent_root(vec![1; 32]);
+        assert_eq!(dependent_root(&reply), Ok(Some(Hash256::repeat_byte(1))));
+
+        reply.set_dependent_root(vec![1; 31]);
+        assert!(dependent_root(&reply).is_err());
     }
 }
@@ -1,20 +1,37 @@
 mod beacon_node_duties;
+mod cache;
 mod epoch_duties;
 mod grpc;
+mod schedule;
+mod store;
+mod subnets;
 // TODO: reintroduce tests
 //#[cfg(test)]
 //mod test_node;
 
-pub use self::beacon_node_duties::{BeaconNodeDuties, BeaconNodeDutiesError};
-use self::epoch_duties::{EpochDuties, EpochDutiesMapError};
-pub use self::epoch_duties::{EpochDutiesMap, WorkInfo};
+pub use self::beacon_node_duties::{
+    BeaconNodeDuties, BeaconNodeDutiesError, BeaconNodeDutiesFuture, DutiesResponse,
+};
+pub use self::cache::{DutiesCache, Error as DutiesCacheError, DUTIES_CACHE_FILENAME};
+pub use self::epoch_duties::{EpochDuties, EpochDuty, WorkInfo};
+pub use self::schedule::{DutySchedule, ValidatorSchedule};
+pub use self::store::{DutiesStore, Error as DutiesStoreError};
+pub use self::subnets::{SubnetSubscription, TARGET_AGGREGATORS_PER_COMMITTEE};
 use super::signer::Signer;
-use futures::Async;
-use slog::{debug, error, info};
+use crate::fee_recipient::FeeRecipients;
+use crate::health::Health;
+use crate::notifier::Notifier;
+use crate::slashing_watcher::SlashingWatcher;
+use futures::{future, Future};
+use slog::{debug, error, info, warn};
+use std::collections::HashSet;
 use std::fmt::Display;
+use std::io;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::RwLock;
-use types::{Epoch, PublicKey, Slot};
+use thiserror::Error;
+use types::{Address, AttestationDuty, ChainSpec, Domain, Epoch, Fork, PublicKey, Slot};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum UpdateOutcome {
@@ -27,12 +44,27 @@ pub enum UpdateOutcome {
     DutiesChanged(Epoch, EpochDuties),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Error)]
 pub enum Error {
-    DutiesMapPoisoned,
-    BeaconNodeDutiesError(BeaconNodeDutiesError),
-    UnknownEpoch,
-    UnknownValidator,
+    #[error("a lock was poisoned whilst updating duties")]
+    LockPoisoned,
+    #[error("unable to access the duties store: {0}")]
+    Store(#[from] DutiesStoreError),
+    /// A request to the beacon node concerning the duties of `epoch` failed.
+    #[error("unable to update the duties of epoch {epoch}: {source}")]
+    BeaconNode {
+        epoch: Epoch,
+        #[source]
+        source: BeaconNodeDutiesError,
+    },
+}
+
+impl Error {
+    /// Returns a function which adds the epoch of the duties being updated to a
+    /// `BeaconNodeDutiesError`.
+    pub fn beacon_node(epoch: Epoch) -> impl FnOnce(BeaconNodeDutiesError) -> Error {
+        move |source| Error::BeaconNode { epoch, source }
+    }
 }
 
 /// A polling state machine which ensures the latest `EpochDuties` are obtained from the Beacon
@@ -40,55 +72,331 @@ pub enum Error {
 ///
 /// This keeps track of all validator keys and required voting slots.
 pub struct DutiesManager<U: BeaconNodeDuties, S: Signer> {
-    pub duties_map: RwLock<EpochDutiesMap>,
+    /// The known duties of all validators, read by all producers.
+    pub store: Arc<DutiesStore>,
     /// A list of all signer objects known to the validator service.
     pub signers: Arc<Vec<S>>,
     pub beacon_node: Arc<U>,
+    /// The fee recipients of all validators, registered with the beacon node each epoch.
+    pub fee_recipients: Arc<FeeRecipients>,
+    /// The latest epoch in which the fee recipients were registered.
+    pub prepared_epoch: RwLock<Option<Epoch>>,
+    /// The subnet subscriptions of upcoming attestation duties already sent to the Beacon Node.
+    pub subscriptions: RwLock<HashSet<SubnetSubscription>>,
+    /// The fork and spec with which selection proofs are signed.
+    pub fork: Fork,
+    pub spec: Arc<ChainSpec>,
+    /// The file to which the duty schedule is written whenever duties change, if any.
+    pub schedule_file: Option<PathBuf>,
+    /// Records the outcome of each update, for the health endpoints.
+    pub health: Arc<Health>,
+    /// Notifies operators of outages of the beacon node.
+    pub notifier: Arc<Notifier>,
+    /// Disables the validators which the beacon node reports as slashed.
+    pub slashing_watcher: Arc<SlashingWatcher>,
 }
 
-impl<U: BeaconNodeDuties, S: Signer + Display> DutiesManager<U, S> {
+impl<U: BeaconNodeDuties + 'static, S: Signer + Display + 'static> DutiesManager<U, S> {
     /// Check the Beacon Node for `EpochDuties`.
     ///
     /// be a wall-clock (e.g., system time, remote server time, etc.).
-    fn update(&self, epoch: Epoch) -> Result<UpdateOutcome, Error> {
+    fn update(
+        self: Arc<Self>,
+        epoch: Epoch,
+    ) -> Box<dyn Future<Item = UpdateOutcome, Error = Error> + Send> {
         let public_keys: Vec<PublicKey> = self.signers.iter().map(Signer::to_public).collect();
-        let duties = self.beacon_node.request_duties(epoch, &public_keys)?;
+        Box::new(
+            self.beacon_node
+                .request_duties(epoch, &public_keys)
+                .map_err(Error::beacon_node(epoch))
+                .and_then(move |response| {
+                    self.slashing_watcher.validators_slashed(&response.slashed);
+                    if let Some(duties) = &response.next_epoch_duties {
+                        self.record_lookahead(epoch + 1, duties)?;
+                    }
+                    self.record_duties(epoch, response)
+                }),
+        )
+    }
+
+    /// Stores the duties obtained for `epoch`, returning how they differ from the known duties.
+    fn record_duties(
+        &self,
+        epoch: Epoch,
+        response: DutiesResponse,
+    ) -> Result<UpdateOutcome, Error> {
+        let DutiesResponse {
+            duties,
+            dependent_root,
+            ..
+        } = response;
+
+        // Duties with the same dependent root are identical, so they need only be compared if
+        // either root is unknown or the roots differ.
+        let known_duties = self.store.epoch_duties(epoch)?;
+        let unchanged = match (self.store.dependent_root(epoch)?, dependent_root) {
+            (Some(known_root), Some(root)) if known_root == root => known_duties.is_some(),
+            _ => known_duties.as_ref() == Some(&duties),
+        };
+        if unchanged {
+            self.store.confirm(epoch, dependent_root)?;
+            return Ok(UpdateOutcome::NoChange(epoch));
+        }
+        //TODO: Duties could be large here. Remove from display and avoid the clone.
+        self.store.insert(epoch, duties.clone(), dependent_root)?;
+        match known_duties {
+            None => Ok(UpdateOutcome::NewDuties(epoch, duties)),
+            // duties have changed
+            Some(_) => Ok(UpdateOutcome::DutiesChanged(epoch, duties)),
+        }
+    }
+
+    /// Stores the tentative duties of `epoch` obtained whilst requesting those of the previous epoch,
+    /// unless duties obtained for `epoch` itself are known.
+    ///
+    /// They are stored without a dependent root, so that they are compared with the duties
+    /// obtained once `epoch` starts. Identical duties are not stored again, which would reset the
+    /// aggregator status of their validators.
+    fn record_lookahead(&self, epoch: Epoch, duties: &EpochDuties) -> Result<(), Error> {
+        if self.store.dependent_root(epoch)?.is_some()
+            || self.store.epoch_duties(epoch)?.as_ref() == Some(duties)
         {
-            // If these duties were known, check to see if they're updates or identical.
-            if let Some(known_duties) = self.duties_map.read()?.get(&epoch) {
-                if *known_duties == duties {
-                    return Ok(UpdateOutcome::NoChange(epoch));
+            return Ok(());
+        }
+        self.store.insert(epoch, duties.clone(), None)?;
+        Ok(())
+    }
+
+    /// Writes the schedule of `duties` for `epoch` to the schedule file, if any.
+    fn export_schedule(&self, epoch: Epoch, duties: &EpochDuties) -> io::Result<()> {
+        let path = match &self.schedule_file {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        let public_keys: Vec<PublicKey> = self.signers.iter().map(Signer::to_public).collect();
+        DutySchedule::new(epoch, self.store.slots_per_epoch(), &public_keys, duties).save(path)
+    }
+
+    /// Register the fee recipients of all validators with the Beacon Node, once per `epoch`.
+    ///
+    /// Resolves to `false` if there was nothing to register, or the fee recipients have already
+    /// been registered this epoch.
+    fn prepare_proposers(
+        self: Arc<Self>,
+        epoch: Epoch,
+    ) -> Box<dyn Future<Item = bool, Error = Error> + Send> {
+        let preparations = match self.preparations(epoch) {
+            Ok(preparations) => preparations,
+            Err(e) => return Box::new(future::err(e)),
+        };
+
+        if preparations.is_empty() {
+            return Box::new(future::ok(false));
+        }
+
+        Box::new(
+            self.beacon_node
+                .prepare_beacon_proposer(&preparations)
+                .map_err(Error::beacon_node(epoch))
+                .and_then(move |()| -> Result<bool, Error> {
+                    *self.prepared_epoch.write()? = Some(epoch);
+                    Ok(true)
+                }),
+        )
+    }
+
+    /// Returns the fee recipient of each validator which has one, or nothing if the fee
+    /// recipients have already been registered in `epoch`.
+    fn preparations(&self, epoch: Epoch) -> Result<Vec<(PublicKey, Address)>, Error> {
+        if *self.prepared_epoch.read()? == Some(epoch) {
+            return Ok(vec![]);
+        }
+
+        Ok(self
+            .signers
+            .iter()
+            .filter_map(|signer| {
+                let public_key = signer.to_public();
+                let fee_recipient = self.fee_recipients.get(&public_key)?;
+                Some((public_key, fee_recipient))
+            })
+            .collect())
+    }
+
+    /// Informs the Beacon Node of every known attestation duty at or after `slot` which it has not
+    /// yet been informed of, determining whether each validator aggregates.
+    ///
+    /// Resolves to the number of subscriptions sent.
+    pub fn prepare_subnets(
+        self: Arc<Self>,
+        slot: Slot,
+    ) -> Box<dyn Future<Item = usize, Error = Error> + Send> {
+        let duties = match self.unsubscribed_duties(slot) {
+            Ok(duties) => duties,
+            Err(e) => return Box::new(future::err(e)),
+        };
+
+        if duties.is_empty() {
+            return Box::new(future::ok(0));
+        }
+
+        let proofs = duties.into_iter().map(|(epoch, signer, duty)| {
+            let domain = self
+                .spec
+                .get_domain(epoch, Domain::SelectionProof, &self.fork);
+            signer
+                .sign_message(&subnets::selection_message(duty.slot), domain)
+                .then(move |proof| -> Result<_, Error> {
+                    // A validator whose selection proof cannot be signed cannot aggregate.
+                    let aggregator = proof
+                        .map(|proof| subnets::is_aggregator(duty.committee_len, &proof))
+                        .unwrap_or(false);
+                    Ok((
+                        epoch,
+                        SubnetSubscription {
+                            public_key: signer.to_public(),
+                            slot: duty.slot,
+                            shard: duty.shard,
+                            aggregator,
+                        },
+                    ))
+                })
+        });
+
+        Box::new(
+            future::join_all(proofs)
+                .and_then(move |subscriptions| -> Result<_, Error> {
+                    for (epoch, subscription) in &subscriptions {
+                        self.store.set_aggregator(
+                            *epoch,
+                            &subscription.public_key,
+                            subscription.aggregator,
+                        )?;
+                    }
+                    let subscriptions: Vec<SubnetSubscription> = subscriptions
+                        .into_iter()
+                        .map(|(_, subscription)| subscription)
+                        .collect();
+
+                    let epoch = slot.epoch(self.store.slots_per_epoch());
+                    Ok(self
+                        .beacon_node
+                        .prepare_attestation_subnets(&subscriptions)
+                        .map_err(Error::beacon_node(epoch))
+                        .and_then(move |()| -> Result<usize, Error> {
+                            let mut sent = self.subscriptions.write()?;
+                            sent.retain(|subscription| subscription.slot >= slot);
+                            sent.extend(subscriptions.iter().cloned());
+                            Ok(subscriptions.len())
+                        }))
+                })
+                .flatten(),
+        )
+    }
+
+    /// Returns the attestation duty, and its epoch and signer, of every validator whose duty at or
+    /// after `slot` has not been sent to the Beacon Node.
+    ///
+    /// The aggregator status of those already sent is restored in the store, since it is reset
+    /// whenever the duties of their epoch are replaced.
+    fn unsubscribed_duties(&self, slot: Slot) -> Result<Vec<(Epoch, S, AttestationDuty)>, Error> {
+        let current_epoch = slot.epoch(self.store.slots_per_epoch());
+        let sent = self.subscriptions.read()?;
+        let mut duties = vec![];
+        for epoch in self.store.epochs()? {
+            if epoch < current_epoch {
+                continue;
+            }
+            for signer in self.signers.iter() {
+                let public_key = signer.to_public();
+                let duty = match self.store.attestation_duty(epoch, &public_key) {
+                    Ok(Some(duty)) if duty.slot >= slot => duty,
+                    _ => continue,
+                };
+                let subscription = |aggregator| SubnetSubscription {
+                    public_key: public_key.clone(),
+                    slot: duty.slot,
+                    shard: duty.shard,
+                    aggregator,
+                };
+                if let Some(aggregator) = [false, true]
+                    .iter()
+                    .cloned()
+                    .find(|aggregator| sent.contains(&subscription(*aggregator)))
+                {
+                    self.store.set_aggregator(epoch, &public_key, aggregator)?;
+                } else {
+                    duties.push((epoch, signer.clone(), duty));
                 }
             }
         }
-        if !self.duties_map.read()?.contains_key(&epoch) {
-            //TODO: Remove clone by removing duties from outcome
-            self.duties_map.write()?.insert(epoch, duties.clone());
-            return Ok(UpdateOutcome::NewDuties(epoch, duties));
-        }
-        // duties have changed
-        //TODO: Duties could be large here. Remove from display and avoid the clone.
-        self.duties_map.write()?.insert(epoch, duties.clone());
-        Ok(UpdateOutcome::DutiesChanged(epoch, duties))
+        Ok(duties)
     }
 
     /// A future wrapping around `update()`. This will perform logic based upon the update
     /// process and complete once the update has completed.
-    pub fn run_update(&self, epoch: Epoch, log: slog::Logger) -> Result<Async<()>, ()> {
-        match self.update(epoch) {
-            Err(error) => error!(log, "Epoch duties poll error"; "error" => format!("{:?}", error)),
-            Ok(UpdateOutcome::NoChange(epoch)) => {
-                debug!(log, "No change in duties"; "epoch" => epoch)
-            }
-            Ok(UpdateOutcome::DutiesChanged(epoch, duties)) => {
-                info!(log, "Duties changed (potential re-org)"; "epoch" => epoch, "duties" => format!("{:?}", duties))
-            }
-            Ok(UpdateOutcome::NewDuties(epoch, duties)) => {
-                info!(log, "New duties obtained"; "epoch" => epoch);
-                print_duties(&log, duties);
-            }
-        };
-        Ok(Async::Ready(()))
+    pub fn run_update(
+        self: Arc<Self>,
+        epoch: Epoch,
+        log: slog::Logger,
+    ) -> Box<dyn Future<Item = (), Error = ()> + Send> {
+        let manager = self.clone();
+        let exporter = self.clone();
+        let prepare_log = log.clone();
+
+        Box::new(
+            self.prepare_proposers(epoch)
+                .then(move |result| {
+                    match result {
+                        Err(error) => {
+                            warn!(prepare_log, "Unable to register fee recipients"; "error" => error.to_string())
+                        }
+                        Ok(true) => debug!(prepare_log, "Registered fee recipients"; "epoch" => epoch),
+                        Ok(false) => {}
+                    };
+                    manager.update(epoch)
+                })
+                .then(move |result| -> Result<(), ()> {
+                    if let Ok(UpdateOutcome::NewDuties(epoch, duties))
+                    | Ok(UpdateOutcome::DutiesChanged(epoch, duties)) = &result
+                    {
+                        if let Err(e) = exporter.export_schedule(*epoch, duties) {
+                            warn!(log, "Unable to export the duty schedule"; "path" => format!("{:?}", exporter.schedule_file), "error" => e.to_string())
+                        }
+                    }
+
+                    match &result {
+                        Err(error) => {
+                            exporter.health.beacon_node_failed(error.to_string());
+                            exporter.notifier.beacon_node_failed();
+                        }
+                        Ok(UpdateOutcome::NoChange(epoch))
+                        | Ok(UpdateOutcome::DutiesChanged(epoch, _))
+                        | Ok(UpdateOutcome::NewDuties(epoch, _)) => {
+                            exporter.notifier.beacon_node_reached();
+                            if let Some(elapsed) = exporter.health.duties_updated(*epoch) {
+                                info!(log, "First duties obtained"; "time_to_first_duty_ms" => elapsed.as_millis() as u64)
+                            }
+                        }
+                    };
+
+                    match result {
+                        Err(error) => error!(log, "Epoch duties poll error"; "error" => error.to_string()),
+                        Ok(UpdateOutcome::NoChange(epoch)) => {
+                            debug!(log, "No change in duties"; "epoch" => epoch)
+                        }
+                        Ok(UpdateOutcome::DutiesChanged(epoch, duties)) => {
+                            info!(log, "Duties changed (potential re-org)"; "epoch" => epoch, "duties" => format!("{:?}", duties))
+                        }
+                        Ok(UpdateOutcome::NewDuties(epoch, duties)) => {
+                            info!(log, "New duties obtained"; "epoch" => epoch);
+                            print_duties(&log, duties);
+                        }
+                    };
+                    Ok(())
+                }),
+        )
     }
 
     /// Returns a list of (index, WorkInfo) indicating all the validators that have work to perform
@@ -96,15 +404,12 @@ impl<U: BeaconNodeDuties, S: Signer + Display> DutiesManager<U, S> {
     pub fn get_current_work(&self, slot: Slot) -> Option<Vec<(usize, WorkInfo)>> {
         let mut current_work: Vec<(usize, WorkInfo)> = Vec::new();
 
-        // if the map is poisoned, return None
-        let duties = self.duties_map.read().ok()?;
-
         for (index, validator_signer) in self.signers.iter().enumerate() {
-            match duties.is_work_slot(slot, &validator_signer.to_public()) {
+            match self.store.work(slot, &validator_signer.to_public()) {
                 Ok(Some(work_type)) => current_work.push((index, work_type)),
                 Ok(None) => {} // No work for this validator
                 //TODO: This should really log an error, as we shouldn't end up with an err here.
-                Err(_) => {} // Unknown epoch or validator, no work
+                Err(_) => {} // Unknown epoch or validator, or a poisoned lock, no work
             }
         }
         if current_work.is_empty() {
@@ -114,25 +419,9 @@ impl<U: BeaconNodeDuties, S: Signer + Display> DutiesManager<U, S> {
     }
 }
 
-//TODO: Use error_chain to handle errors
-impl From<BeaconNodeDutiesError> for Error {
-    fn from(e: BeaconNodeDutiesError) -> Error {
-        Error::BeaconNodeDutiesError(e)
-    }
-}
-
-//TODO: Use error_chain to handle errors
 impl<T> From<std::sync::PoisonError<T>> for Error {
     fn from(_e: std::sync::PoisonError<T>) -> Error {
-        Error::DutiesMapPoisoned
-    }
-}
-impl From<EpochDutiesMapError> for Error {
-    fn from(e: EpochDutiesMapError) -> Error {
-        match e {
-            EpochDutiesMapError::UnknownEpoch => Error::UnknownEpoch,
-            EpochDutiesMapError::UnknownValidator => Error::UnknownValidator,
-        }
+        Error::LockPoisoned
     }
 }
 
@@ -146,69 +435,285 @@ fn print_duties(log: &slog::Logger, duties: EpochDuties) {
     }
 }
 
-/* TODO: Modify tests for new Duties Manager form
 #[cfg(test)]
 mod tests {
-    use super::test_node::TestBeaconNode;
     use super::*;
-    use bls::Keypair;
-    use slot_clock::TestingSlotClock;
-    use types::Slot;
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant};
+    use types::{AttestationDuty, Hash256, Keypair};
+
+    const SLOTS_PER_EPOCH: u64 = 8;
+
+    /// Responds to every duties request with the latest response it was given.
+    #[derive(Default)]
+    struct TestDutiesNode {
+        response: Mutex<Option<DutiesResponse>>,
+        /// Every subnet subscription sent to the node.
+        subscriptions: Mutex<Vec<SubnetSubscription>>,
+    }
 
-    // TODO: implement more thorough testing.
-    // https://github.com/sigp/lighthouse/issues/160
-    //
-    // These tests should serve as a good example for future tests.
+    impl TestDutiesNode {
+        fn set_response(&self, response: DutiesResponse) {
+            *self.response.lock().unwrap() = Some(response);
+        }
+    }
+
+    impl BeaconNodeDuties for TestDutiesNode {
+        fn request_duties(
+            &self,
+            _epoch: Epoch,
+            _pub_keys: &[PublicKey],
+        ) -> BeaconNodeDutiesFuture<DutiesResponse> {
+            let response = self.response.lock().unwrap().clone();
+            Box::new(future::result(response.ok_or_else(|| {
+                BeaconNodeDutiesError::RemoteFailure("no response".to_string())
+            })))
+        }
+
+        fn prepare_beacon_proposer(
+            &self,
+            _preparations: &[(PublicKey, Address)],
+        ) -> BeaconNodeDutiesFuture<()> {
+            Box::new(future::ok(()))
+        }
+
+        fn prepare_attestation_subnets(
+            &self,
+            subscriptions: &[SubnetSubscription],
+        ) -> BeaconNodeDutiesFuture<()> {
+            self.subscriptions
+                .lock()
+                .unwrap()
+                .extend(subscriptions.iter().cloned());
+            Box::new(future::ok(()))
+        }
+    }
+
+    fn manager(
+        keypair: &Keypair,
+    ) -> (
+        Arc<DutiesManager<TestDutiesNode, Keypair>>,
+        Arc<TestDutiesNode>,
+    ) {
+        let beacon_node = Arc::new(TestDutiesNode::default());
+        let manager = Arc::new(DutiesManager {
+            store: Arc::new(DutiesStore::new(SLOTS_PER_EPOCH)),
+            signers: Arc::new(vec![keypair.clone()]),
+            beacon_node: beacon_node.clone(),
+            fee_recipients: Arc::new(FeeRecipients::default()),
+            prepared_epoch: RwLock::new(None),
+            subscriptions: RwLock::new(HashSet::new()),
+            fork: Fork::default(),
+            spec: Arc::new(ChainSpec::minimal()),
+            schedule_file: None,
+            health: Arc::new(Health::new(
+                Duration::from_secs(6),
+                SLOTS_PER_EPOCH,
+                Instant::now(),
+            )),
+            notifier: Arc::new(Notifier::disabled()),
+            slashing_watcher: Arc::new(SlashingWatcher::new(
+                PathBuf::new(),
+                Arc::new(Notifier::disabled()),
+                slog::Logger::root(slog::Discard, slog::o!()),
+            )),
+        });
+        (manager, beacon_node)
+    }
 
+    /// The response of a beacon node whose chain has the block with `dependent_root` at the last
+    /// slot of epoch 1, on which the validator attests at `attestation_slot` during epoch 2.
+    fn response(keypair: &Keypair, attestation_slot: u64, dependent_root: u64) -> DutiesResponse {
+        let mut duties = EpochDuties::new();
+        duties.insert(
+            keypair.pk.clone(),
+            Some(EpochDuty {
+                block_production_slots: vec![],
+                attestation_duty: AttestationDuty {
+                    slot: Slot::new(attestation_slot),
+                    ..AttestationDuty::default()
+                },
+            }),
+        );
+        DutiesResponse {
+            duties,
+            dependent_root: Some(Hash256::from_low_u64_be(dependent_root)),
+            slashed: vec![],
+            next_epoch_duties: None,
+        }
+    }
 
     #[test]
-    pub fn polling() {
-        let spec = Arc::new(ChainSpec::mainnet());
-        let duties_map = Arc::new(EpochDutiesMap::new(T::slots_per_epoch()));
+    fn late_reorgs_change_shuffling() {
         let keypair = Keypair::random();
-        let slot_clock = Arc::new(TestingSlotClock::new(0));
-        let beacon_node = Arc::new(TestBeaconNode::default());
-
-        let manager = DutiesManager {
-            spec: spec.clone(),
-            pubkey: keypair.pk.clone(),
-            duties_map: duties_map.clone(),
-            slot_clock: slot_clock.clone(),
-            beacon_node: beacon_node.clone(),
-        };
+        let (manager, beacon_node) = manager(&keypair);
+        let epoch = Epoch::new(2);
+        let update = || manager.clone().update(epoch).wait();
 
-        // Configure response from the BeaconNode.
-        let duties = EpochDuties {
-            validator_index: 0,
-            block_production_slot: Some(Slot::new(10)),
-        };
-        beacon_node.set_next_shuffling_result(Ok(Some(duties)));
+        let first = response(&keypair, 17, 1);
+        beacon_node.set_response(first.clone());
+        assert_eq!(update(), Ok(UpdateOutcome::NewDuties(epoch, first.duties)));
+        assert_eq!(update(), Ok(UpdateOutcome::NoChange(epoch)));
 
-        // Get the duties for the first time...
+        // A re-org after the dependent slot (15) cannot change the shuffling.
         assert_eq!(
-            manager.poll(),
-            Ok(PollOutcome::NewDuties(Epoch::new(0), duties))
+            manager.store.mark_stale_after_fork(Slot::new(15)),
+            Ok(vec![])
         );
-        // Get the same duties again...
-        assert_eq!(manager.poll(), Ok(PollOutcome::NoChange(Epoch::new(0))));
 
-        // Return new duties.
-        let duties = EpochDuties {
-            validator_index: 0,
-            block_production_slot: Some(Slot::new(11)),
-        };
-        beacon_node.set_next_shuffling_result(Ok(Some(duties)));
+        // A late re-org replaces the block at the dependent slot, which changes the shuffling.
+        assert_eq!(
+            manager.store.mark_stale_after_fork(Slot::new(13)),
+            Ok(vec![epoch])
+        );
+        let reorged = response(&keypair, 20, 2);
+        beacon_node.set_response(reorged.clone());
+        assert_eq!(
+            update(),
+            Ok(UpdateOutcome::DutiesChanged(epoch, reorged.duties.clone()))
+        );
+        assert_eq!(manager.store.stale_epochs(), Ok(vec![]));
+        assert_eq!(
+            manager.store.dependent_root(epoch),
+            Ok(reorged.dependent_root)
+        );
+        assert_eq!(
+            manager.store.attestation_duty(epoch, &keypair.pk),
+            Ok(Some(AttestationDuty {
+                slot: Slot::new(20),
+                ..AttestationDuty::default()
+            }))
+        );
+
+        // Another late re-org may restore a different dependent block without changing the
+        // duties of this validator.
+        manager
+            .store
+            .mark_stale_after_fork(Slot::new(13))
+            .expect("should mark stale");
+        beacon_node.set_response(response(&keypair, 20, 3));
+        assert_eq!(update(), Ok(UpdateOutcome::NoChange(epoch)));
+        assert_eq!(manager.store.stale_epochs(), Ok(vec![]));
+        assert_eq!(
+            manager.store.dependent_root(epoch),
+            Ok(Some(Hash256::from_low_u64_be(3)))
+        );
+    }
+
+    #[test]
+    fn duties_with_the_same_dependent_root_are_not_compared() {
+        let keypair = Keypair::random();
+        let (manager, beacon_node) = manager(&keypair);
+        let epoch = Epoch::new(2);
+
+        beacon_node.set_response(response(&keypair, 17, 1));
+        assert!(manager.clone().update(epoch).wait().is_ok());
+
+        // Without dependent roots, differing duties are a change.
+        let mut without_root = response(&keypair, 18, 1);
+        without_root.dependent_root = None;
+        beacon_node.set_response(without_root.clone());
+        assert_eq!(
+            manager.clone().update(epoch).wait(),
+            Ok(UpdateOutcome::DutiesChanged(epoch, without_root.duties))
+        );
+
+        // Once both roots are known, duties depending on the same block are the same.
+        beacon_node.set_response(response(&keypair, 18, 1));
         assert_eq!(
-            manager.poll(),
-            Ok(PollOutcome::DutiesChanged(Epoch::new(0), duties))
+            manager.clone().update(epoch).wait(),
+            Ok(UpdateOutcome::NoChange(epoch))
         );
+        beacon_node.set_response(response(&keypair, 19, 1));
+        assert_eq!(
+            manager.clone().update(epoch).wait(),
+            Ok(UpdateOutcome::NoChange(epoch))
+        );
+    }
+
+    #[test]
+    fn sends_each_upcoming_attestation_duty_once() {
+        let keypair = Keypair::random();
+        let (manager, beacon_node) = manager(&keypair);
+        let epoch = Epoch::new(2);
+        let prepare = |slot| manager.clone().prepare_subnets(Slot::new(slot)).wait();
 
-        // Return no duties.
-        beacon_node.set_next_shuffling_result(Ok(None));
+        beacon_node.set_response(response(&keypair, 17, 1));
+        assert!(manager.clone().update(epoch).wait().is_ok());
+
+        // A committee smaller than the target number of aggregators is aggregated by every member.
+        assert_eq!(prepare(16), Ok(1));
+        assert_eq!(prepare(17), Ok(0));
+        assert_eq!(
+            *beacon_node.subscriptions.lock().unwrap(),
+            vec![SubnetSubscription {
+                public_key: keypair.pk.clone(),
+                slot: Slot::new(17),
+                shard: 0,
+                aggregator: true,
+            }]
+        );
+        assert_eq!(manager.store.is_aggregator(epoch, &keypair.pk), Ok(true));
+
+        // Replacing the duties resets the aggregator status, which is restored without sending the
+        // duty again.
+        manager
+            .store
+            .insert(epoch, response(&keypair, 17, 1).duties, None)
+            .expect("should insert duties");
+        assert_eq!(manager.store.is_aggregator(epoch, &keypair.pk), Ok(false));
+        assert_eq!(prepare(17), Ok(0));
+        assert_eq!(manager.store.is_aggregator(epoch, &keypair.pk), Ok(true));
+
+        // A changed duty is sent, unless its slot has passed.
+        beacon_node.set_response(response(&keypair, 20, 2));
+        assert!(manager.clone().update(epoch).wait().is_ok());
+        assert_eq!(prepare(21), Ok(0));
+        assert_eq!(prepare(18), Ok(1));
+        assert_eq!(beacon_node.subscriptions.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn lookahead_duties_are_kept_until_the_epoch_is_requested() {
+        let keypair = Keypair::random();
+        let (manager, beacon_node) = manager(&keypair);
+        let epoch = Epoch::new(2);
+        let next_epoch = epoch + 1;
+
+        let mut current = response(&keypair, 17, 1);
+        current.next_epoch_duties = Some(response(&keypair, 26, 0).duties);
+        beacon_node.set_response(current.clone());
+        assert!(manager.clone().update(epoch).wait().is_ok());
+        assert_eq!(manager.store.epochs(), Ok(vec![epoch, next_epoch]));
+        assert_eq!(manager.store.dependent_root(next_epoch), Ok(None));
+
+        // The upcoming duty of the next epoch is sent along with that of the current epoch.
+        assert_eq!(manager.clone().prepare_subnets(Slot::new(16)).wait(), Ok(2));
+
+        // Identical lookahead duties are not stored again, which would reset their aggregator
+        // status.
+        assert!(manager.clone().update(epoch).wait().is_ok());
+        assert_eq!(
+            manager.store.is_aggregator(next_epoch, &keypair.pk),
+            Ok(true)
+        );
+
+        // The duties obtained for the next epoch itself replace its lookahead duties, and are not
+        // replaced by later lookahead duties.
+        let next = response(&keypair, 27, 2);
+        beacon_node.set_response(next.clone());
+        assert_eq!(
+            manager.clone().update(next_epoch).wait(),
+            Ok(UpdateOutcome::DutiesChanged(
+                next_epoch,
+                next.duties.clone()
+            ))
+        );
+        beacon_node.set_response(current);
+        assert!(manager.clone().update(epoch).wait().is_ok());
         assert_eq!(
-            manager.poll(),
-            Ok(PollOutcome::UnknownValidatorOrEpoch(Epoch::new(0)))
+            manager.store.epoch_duties(next_epoch),
+            Ok(Some(next.duties))
         );
     }
 }
-*/
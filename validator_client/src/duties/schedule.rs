@@ -0,0 +1,140 @@
+//! A machine-readable export of the duties of every validator for an epoch, for consumption by
+//! external tools (e.g., to silence alerts whilst a validator is idle, or to time relay
+//! registrations before a proposal).
+//!
+//! The schedule is written as JSON each time new or changed duties are obtained from the beacon
+//! node, replacing the previous schedule.
+use super::EpochDuties;
+use serde_derive::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+use types::{Epoch, PublicKey, Shard, Slot};
+
+/// The duties of all validators during `epoch`.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct DutySchedule {
+    pub epoch: Epoch,
+    pub start_slot: Slot,
+    pub end_slot: Slot,
+    pub validators: Vec<ValidatorSchedule>,
+}
+
+/// The duties of a single validator during an epoch.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct ValidatorSchedule {
+    pub pubkey: PublicKey,
+    /// `false` if the validator is not active, in which case it has no duties.
+    pub active: bool,
+    /// The slots at which the validator proposes, in ascending order.
+    pub block_production_slots: Vec<Slot>,
+    pub attestation_slot: Option<Slot>,
+    pub attestation_shard: Option<Shard>,
+}
+
+impl DutySchedule {
+    /// Builds the schedule of `epoch` from `duties`, listing validators in the order of `pubkeys`.
+    ///
+    /// Validators for which the beacon node returned no duties are omitted.
+    pub fn new(
+        epoch: Epoch,
+        slots_per_epoch: u64,
+        pubkeys: &[PublicKey],
+        duties: &EpochDuties,
+    ) -> Self {
+        let validators = pubkeys
+            .iter()
+            .filter_map(|pubkey| {
+                let duty = duties.get(pubkey)?.as_ref();
+                Some(ValidatorSchedule {
+                    pubkey: pubkey.clone(),
+                    active: duty.is_some(),
+                    block_production_slots: duty
+                        .map(|duty| duty.block_production_slots.clone())
+                        .unwrap_or_default(),
+                    attestation_slot: duty.map(|duty| duty.attestation_duty.slot),
+                    attestation_shard: duty.map(|duty| duty.attestation_duty.shard),
+                })
+            })
+            .collect();
+
+        Self {
+            epoch,
+            start_slot: epoch.start_slot(slots_per_epoch),
+            end_slot: epoch.end_slot(slots_per_epoch),
+            validators,
+        }
+    }
+
+    /// Writes the schedule to `path`.
+    ///
+    /// The schedule is written to a temporary file which is then renamed, so that a reader never
+    /// observes a partially-written schedule.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let temp_path = path.with_extension("json.tmp");
+        let file = File::create(&temp_path)?;
+        serde_json::to_writer_pretty(file, self)?;
+        fs::rename(&temp_path, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::duties::EpochDuty;
+    use tempfile::TempDir;
+    use types::{AttestationDuty, Keypair};
+
+    #[test]
+    fn save_schedule() {
+        let slots_per_epoch = 8;
+        let epoch = Epoch::new(2);
+        let pubkeys: Vec<PublicKey> = (0..3).map(|_| Keypair::random().pk).collect();
+
+        let mut duties = EpochDuties::new();
+        duties.insert(
+            pubkeys[0].clone(),
+            Some(EpochDuty {
+                block_production_slots: vec![Slot::new(17), Slot::new(19)],
+                attestation_duty: AttestationDuty {
+                    slot: Slot::new(20),
+                    shard: 3,
+                    committee_index: 0,
+                    committee_len: 1,
+                },
+            }),
+        );
+        duties.insert(pubkeys[1].clone(), None);
+
+        let schedule = DutySchedule::new(epoch, slots_per_epoch, &pubkeys, &duties);
+        assert_eq!(
+            schedule.validators,
+            vec![
+                ValidatorSchedule {
+                    pubkey: pubkeys[0].clone(),
+                    active: true,
+                    block_production_slots: vec![Slot::new(17), Slot::new(19)],
+                    attestation_slot: Some(Slot::new(20)),
+                    attestation_shard: Some(3),
+                },
+                ValidatorSchedule {
+                    pubkey: pubkeys[1].clone(),
+                    active: false,
+                    block_production_slots: vec![],
+                    attestation_slot: None,
+                    attestation_shard: None,
+                },
+            ]
+        );
+        assert_eq!(schedule.start_slot, Slot::new(16));
+        assert_eq!(schedule.end_slot, Slot::new(23));
+
+        let dir = TempDir::new().expect("should create temp dir");
+        let path = dir.path().join("duty_schedule.json");
+        schedule.save(&path).expect("should save schedule");
+
+        let file = File::open(&path).expect("should open schedule");
+        let loaded: DutySchedule = serde_json::from_reader(file).expect("should parse schedule");
+        assert_eq!(loaded, schedule);
+    }
+}
@@ -0,0 +1,438 @@
+use super::epoch_duties::{EpochDuties, EpochDuty, WorkInfo};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::RwLock;
+use thiserror::Error;
+use types::{AttestationDuty, Epoch, Hash256, PublicKey, Slot};
+
+#[derive(Debug, PartialEq, Clone, Error)]
+pub enum Error {
+    #[error("the duties store lock was poisoned")]
+    LockPoisoned,
+    #[error("no duties are known for the epoch")]
+    UnknownEpoch,
+    #[error("no duties are known for the validator")]
+    UnknownValidator,
+}
+
+impl<T> From<std::sync::PoisonError<T>> for Error {
+    fn from(_e: std::sync::PoisonError<T>) -> Error {
+        Error::LockPoisoned
+    }
+}
+
+/// The duties of a single validator during a single epoch.
+#[derive(Debug, PartialEq, Clone, Default)]
+struct Entry {
+    /// `None` if the validator is not active.
+    duty: Option<EpochDuty>,
+    /// `true` if the validator aggregates the attestations of its committee.
+    aggregator: bool,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    /// The epochs for which duties have been obtained.
+    epochs: BTreeSet<Epoch>,
+    /// The dependent root of the duties of each epoch, where known.
+    dependent_roots: HashMap<Epoch, Hash256>,
+    /// The epochs whose duties may have been changed by a re-org, until they are obtained again.
+    stale: BTreeSet<Epoch>,
+    duties: HashMap<(Epoch, PublicKey), Entry>,
+}
+
+impl Inner {
+    fn epochs_affected_by_fork(
+        &self,
+        slots_per_epoch: u64,
+        common_ancestor_slot: Slot,
+    ) -> impl Iterator<Item = Epoch> + '_ {
+        self.epochs.iter().cloned().filter(move |epoch| {
+            let start_slot = epoch.start_slot(slots_per_epoch);
+            start_slot > common_ancestor_slot + 1
+        })
+    }
+}
+
+/// The known duties of every validator, keyed by epoch and public key.
+///
+/// The store may be read concurrently by all producers whilst the duties manager updates it.
+#[derive(Debug)]
+pub struct DutiesStore {
+    slots_per_epoch: u64,
+    inner: RwLock<Inner>,
+}
+
+impl DutiesStore {
+    pub fn new(slots_per_epoch: u64) -> Self {
+        Self {
+            slots_per_epoch,
+            inner: RwLock::new(Inner::default()),
+        }
+    }
+
+    pub fn slots_per_epoch(&self) -> u64 {
+        self.slots_per_epoch
+    }
+
+    /// Replaces the duties of `epoch` with `duties`, which depend on the block with
+    /// `dependent_root` (if known).
+    ///
+    /// The aggregator status of every validator is reset, since it depends on the duties.
+    pub fn insert(
+        &self,
+        epoch: Epoch,
+        duties: EpochDuties,
+        dependent_root: Option<Hash256>,
+    ) -> Result<(), Error> {
+        let mut inner = self.inner.write()?;
+        Self::set_dependent_root(&mut inner, epoch, dependent_root);
+        inner
+            .duties
+            .retain(|(known_epoch, _), _| *known_epoch != epoch);
+        for (pubkey, duty) in duties {
+            inner.duties.insert(
+                (epoch, pubkey),
+                Entry {
+                    duty,
+                    aggregator: false,
+                },
+            );
+        }
+        inner.epochs.insert(epoch);
+        Ok(())
+    }
+
+    /// Records that the known duties of `epoch` were obtained again, depending on the block with
+    /// `dependent_root` (if known), so that they are no longer stale.
+    pub fn confirm(&self, epoch: Epoch, dependent_root: Option<Hash256>) -> Result<(), Error> {
+        let mut inner = self.inner.write()?;
+        if !inner.epochs.contains(&epoch) {
+            return Err(Error::UnknownEpoch);
+        }
+        Self::set_dependent_root(&mut inner, epoch, dependent_root);
+        Ok(())
+    }
+
+    fn set_dependent_root(inner: &mut Inner, epoch: Epoch, dependent_root: Option<Hash256>) {
+        match dependent_root {
+            Some(root) => inner.dependent_roots.insert(epoch, root),
+            None => inner.dependent_roots.remove(&epoch),
+        };
+        inner.stale.remove(&epoch);
+    }
+
+    /// Returns the dependent root of the duties of `epoch`, if known.
+    pub fn dependent_root(&self, epoch: Epoch) -> Result<Option<Hash256>, Error> {
+        Ok(self.inner.read()?.dependent_roots.get(&epoch).cloned())
+    }
+
+    /// Returns the epochs for which duties are known, in ascending order.
+    pub fn epochs(&self) -> Result<Vec<Epoch>, Error> {
+        Ok(self.inner.read()?.epochs.iter().cloned().collect())
+    }
+
+    /// Returns the known epochs whose duties may differ on a chain which forked from the known
+    /// chain after `common_ancestor_slot`.
+    ///
+    /// The duties of an epoch are determined by the chain up to the last slot of the previous
+    /// epoch, so forks after that slot do not change them.
+    pub fn epochs_affected_by_fork(&self, common_ancestor_slot: Slot) -> Result<Vec<Epoch>, Error> {
+        Ok(self
+            .inner
+            .read()?
+            .epochs_affected_by_fork(self.slots_per_epoch, common_ancestor_slot)
+            .collect())
+    }
+
+    /// Marks the duties of the epochs affected by a fork after `common_ancestor_slot` as stale,
+    /// returning those epochs.
+    ///
+    /// The duties remain usable, but should be obtained again since the ancestor of the head at
+    /// their dependent slot may have changed.
+    pub fn mark_stale_after_fork(&self, common_ancestor_slot: Slot) -> Result<Vec<Epoch>, Error> {
+        let inner = &mut *self.inner.write()?;
+        let epochs: Vec<Epoch> = inner
+            .epochs_affected_by_fork(self.slots_per_epoch, common_ancestor_slot)
+            .collect();
+        inner.stale.extend(epochs.iter().cloned());
+        Ok(epochs)
+    }
+
+    /// Marks the known duties of `epoch` as stale, so that they are obtained again before they are
+    /// trusted (e.g., when they were restored from a previous run).
+    pub fn mark_stale(&self, epoch: Epoch) -> Result<(), Error> {
+        let mut inner = self.inner.write()?;
+        if !inner.epochs.contains(&epoch) {
+            return Err(Error::UnknownEpoch);
+        }
+        inner.stale.insert(epoch);
+        Ok(())
+    }
+
+    /// Returns the epochs whose duties are stale, in ascending order.
+    pub fn stale_epochs(&self) -> Result<Vec<Epoch>, Error> {
+        Ok(self.inner.read()?.stale.iter().cloned().collect())
+    }
+
+    /// Returns the duties of every validator during `epoch`, or `None` if they are not known.
+    pub fn epoch_duties(&self, epoch: Epoch) -> Result<Option<EpochDuties>, Error> {
+        let inner = self.inner.read()?;
+        if !inner.epochs.contains(&epoch) {
+            return Ok(None);
+        }
+
+        Ok(Some(
+            inner
+                .duties
+                .iter()
+                .filter(|((known_epoch, _), _)| *known_epoch == epoch)
+                .map(|((_, pubkey), entry)| (pubkey.clone(), entry.duty.clone()))
+                .collect(),
+        ))
+    }
+
+    /// Returns the duty of the validator with `pubkey` during `epoch`, or `None` if it is not
+    /// active.
+    pub fn duty(&self, epoch: Epoch, pubkey: &PublicKey) -> Result<Option<EpochDuty>, Error> {
+        self.entry(epoch, pubkey).map(|entry| entry.duty)
+    }
+
+    /// Returns the slots at which the validator with `pubkey` proposes during `epoch`, in
+    /// ascending order.
+    pub fn proposer_slots(&self, epoch: Epoch, pubkey: &PublicKey) -> Result<Vec<Slot>, Error> {
+        Ok(self
+            .duty(epoch, pubkey)?
+            .map(|duty| duty.block_production_slots)
+            .unwrap_or_default())
+    }
+
+    /// Returns the public keys of the known validators which propose at `slot`.
+    ///
+    /// Several validators may propose at the same slot if the beacon node reports conflicting
+    /// duties (e.g., during a re-org), in which case the blocks of all of them are produced.
+    pub fn proposers(&self, slot: Slot) -> Result<Vec<PublicKey>, Error> {
+        let epoch = slot.epoch(self.slots_per_epoch);
+        let inner = self.inner.read()?;
+        if !inner.epochs.contains(&epoch) {
+            return Err(Error::UnknownEpoch);
+        }
+
+        Ok(inner
+            .duties
+            .iter()
+            .filter(|((known_epoch, _), entry)| {
+                *known_epoch == epoch
+                    && entry
+                        .duty
+                        .as_ref()
+                        .map_or(false, |duty| duty.block_production_slots.contains(&slot))
+            })
+            .map(|((_, pubkey), _)| pubkey.clone())
+            .collect())
+    }
+
+    /// Returns the committee and slot at which the validator with `pubkey` attests during
+    /// `epoch`, if it is active.
+    pub fn attestation_duty(
+        &self,
+        epoch: Epoch,
+        pubkey: &PublicKey,
+    ) -> Result<Option<AttestationDuty>, Error> {
+        Ok(self.duty(epoch, pubkey)?.map(|duty| duty.attestation_duty))
+    }
+
+    /// Returns `true` if the validator with `pubkey` aggregates the attestations of its committee
+    /// during `epoch`.
+    pub fn is_aggregator(&self, epoch: Epoch, pubkey: &PublicKey) -> Result<bool, Error> {
+        self.entry(epoch, pubkey).map(|entry| entry.aggregator)
+    }
+
+    /// Records whether the validator with `pubkey` aggregates the attestations of its committee
+    /// during `epoch`.
+    pub fn set_aggregator(
+        &self,
+        epoch: Epoch,
+        pubkey: &PublicKey,
+        aggregator: bool,
+    ) -> Result<(), Error> {
+        let inner = &mut *self.inner.write()?;
+        match inner.duties.get_mut(&(epoch, pubkey.clone())) {
+            Some(entry) => {
+                entry.aggregator = aggregator;
+                Ok(())
+            }
+            None if inner.epochs.contains(&epoch) => Err(Error::UnknownValidator),
+            None => Err(Error::UnknownEpoch),
+        }
+    }
+
+    /// Returns the work of the validator with `pubkey` during `slot`, if it has any.
+    pub fn work(&self, slot: Slot, pubkey: &PublicKey) -> Result<Option<WorkInfo>, Error> {
+        let entry = self.entry(slot.epoch(self.slots_per_epoch), pubkey)?;
+        let aggregator = entry.aggregator;
+        Ok(entry
+            .duty
+            .and_then(|duty| duty.is_work_slot(slot))
+            .map(|work| WorkInfo {
+                aggregator: aggregator && work.attestation_duty.is_some(),
+                ..work
+            }))
+    }
+
+    fn entry(&self, epoch: Epoch, pubkey: &PublicKey) -> Result<Entry, Error> {
+        let inner = self.inner.read()?;
+        match inner.duties.get(&(epoch, pubkey.clone())) {
+            Some(entry) => Ok(entry.clone()),
+            None if inner.epochs.contains(&epoch) => Err(Error::UnknownValidator),
+            None => Err(Error::UnknownEpoch),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use types::Keypair;
+
+    #[test]
+    fn queries_duties() {
+        let store = DutiesStore::new(8);
+        let proposer = Keypair::random().pk;
+        let inactive = Keypair::random().pk;
+        let attestation_duty = AttestationDuty {
+            slot: Slot::new(10),
+            shard: 1,
+            committee_index: 0,
+            committee_len: 4,
+        };
+
+        let mut duties = EpochDuties::new();
+        duties.insert(
+            proposer.clone(),
+            Some(EpochDuty {
+                block_production_slots: vec![Slot::new(9), Slot::new(12)],
+                attestation_duty,
+            }),
+        );
+        duties.insert(inactive.clone(), None);
+        store
+            .insert(Epoch::new(1), duties.clone(), None)
+            .expect("should insert");
+
+        assert_eq!(store.epochs(), Ok(vec![Epoch::new(1)]));
+        assert_eq!(store.epoch_duties(Epoch::new(1)), Ok(Some(duties.clone())));
+        assert_eq!(store.epoch_duties(Epoch::new(2)), Ok(None));
+
+        assert_eq!(
+            store.proposer_slots(Epoch::new(1), &proposer),
+            Ok(vec![Slot::new(9), Slot::new(12)])
+        );
+        assert_eq!(store.proposers(Slot::new(12)), Ok(vec![proposer.clone()]));
+        assert_eq!(store.proposers(Slot::new(10)), Ok(vec![]));
+        assert_eq!(store.proposers(Slot::new(16)), Err(Error::UnknownEpoch));
+        assert_eq!(
+            store.attestation_duty(Epoch::new(1), &proposer),
+            Ok(Some(attestation_duty))
+        );
+        assert_eq!(store.duty(Epoch::new(1), &inactive), Ok(None));
+        assert_eq!(
+            store.duty(Epoch::new(2), &proposer),
+            Err(Error::UnknownEpoch)
+        );
+        assert_eq!(
+            store.duty(Epoch::new(1), &Keypair::random().pk),
+            Err(Error::UnknownValidator)
+        );
+
+        let work = store
+            .work(Slot::new(10), &proposer)
+            .expect("should find work")
+            .expect("should attest");
+        assert!(!work.produce_block);
+        assert!(!work.aggregator);
+
+        store
+            .set_aggregator(Epoch::new(1), &proposer, true)
+            .expect("should set aggregator");
+        assert_eq!(store.is_aggregator(Epoch::new(1), &proposer), Ok(true));
+        let work = store
+            .work(Slot::new(10), &proposer)
+            .expect("should find work")
+            .expect("should attest");
+        assert!(work.aggregator);
+
+        // Replacing the duties of an epoch resets the aggregators.
+        store
+            .insert(Epoch::new(1), duties, None)
+            .expect("should insert");
+        assert_eq!(store.is_aggregator(Epoch::new(1), &proposer), Ok(false));
+    }
+
+    #[test]
+    fn finds_epochs_affected_by_forks() {
+        let store = DutiesStore::new(8);
+        for epoch in 1..4 {
+            store
+                .insert(Epoch::new(epoch), EpochDuties::new(), None)
+                .expect("should insert");
+        }
+
+        // The duties of epoch 2 are determined by the chain up to slot 15.
+        assert_eq!(
+            store.epochs_affected_by_fork(Slot::new(14)),
+            Ok(vec![Epoch::new(2), Epoch::new(3)])
+        );
+        assert_eq!(
+            store.epochs_affected_by_fork(Slot::new(15)),
+            Ok(vec![Epoch::new(3)])
+        );
+        assert_eq!(store.epochs_affected_by_fork(Slot::new(23)), Ok(vec![]));
+        assert_eq!(
+            store.epochs_affected_by_fork(Slot::new(0)),
+            Ok(vec![Epoch::new(1), Epoch::new(2), Epoch::new(3)])
+        );
+    }
+
+    #[test]
+    fn late_reorgs_mark_duties_stale() {
+        let store = DutiesStore::new(8);
+        for epoch in 1..4 {
+            store
+                .insert(
+                    Epoch::new(epoch),
+                    EpochDuties::new(),
+                    Some(Hash256::from_low_u64_be(epoch)),
+                )
+                .expect("should insert");
+        }
+        assert_eq!(
+            store.dependent_root(Epoch::new(2)),
+            Ok(Some(Hash256::from_low_u64_be(2)))
+        );
+        assert_eq!(store.dependent_root(Epoch::new(4)), Ok(None));
+
+        // A re-org after the dependent slot of epoch 2 (slot 15) only affects epoch 3.
+        assert_eq!(
+            store.mark_stale_after_fork(Slot::new(15)),
+            Ok(vec![Epoch::new(3)])
+        );
+        // A late re-org, which forks from before the last slot of epoch 1, affects epoch 2 too.
+        assert_eq!(
+            store.mark_stale_after_fork(Slot::new(14)),
+            Ok(vec![Epoch::new(2), Epoch::new(3)])
+        );
+        assert_eq!(store.stale_epochs(), Ok(vec![Epoch::new(2), Epoch::new(3)]));
+
+        // Duties are fresh once they are obtained again, whether or not they changed.
+        store
+            .confirm(Epoch::new(2), Some(Hash256::from_low_u64_be(2)))
+            .expect("should confirm");
+        store
+            .insert(Epoch::new(3), EpochDuties::new(), None)
+            .expect("should insert");
+        assert_eq!(store.stale_epochs(), Ok(vec![]));
+        assert_eq!(store.dependent_root(Epoch::new(3)), Ok(None));
+
+        assert_eq!(store.confirm(Epoch::new(4), None), Err(Error::UnknownEpoch));
+    }
+}
@@ -0,0 +1,64 @@
+//! The attestation subnet subscriptions sent to the Beacon Node ahead of every attestation duty.
+//!
+//! A validator aggregates the attestations of its committee if the hash of its signature of the
+//! attestation slot (its selection proof) is selected, so that `TARGET_AGGREGATORS_PER_COMMITTEE`
+//! members of each committee aggregate on average. The Beacon Node is informed of each duty, and
+//! whether its validator aggregates, as soon as the duty is known, so that it may subscribe to the
+//! subnet of the duty's shard before the attestations of the committee are published.
+use eth2_hashing::hash;
+use ssz::Encode;
+use std::cmp;
+use tree_hash::TreeHash;
+use types::{PublicKey, Signature, Slot};
+
+/// The number of members of each committee which aggregate its attestations, on average.
+pub const TARGET_AGGREGATORS_PER_COMMITTEE: usize = 16;
+
+/// The upcoming attestation duty of a validator, which is attested to the subnet of its `shard`.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct SubnetSubscription {
+    pub public_key: PublicKey,
+    pub slot: Slot,
+    pub shard: u64,
+    /// `true` if the validator aggregates the attestations of its committee, so the Beacon Node
+    /// must subscribe to the subnet.
+    pub aggregator: bool,
+}
+
+/// Returns the message which a validator signs to produce its selection proof for `slot`.
+pub fn selection_message(slot: Slot) -> Vec<u8> {
+    slot.tree_hash_root()
+}
+
+/// Returns `true` if the validator with `selection_proof` aggregates the attestations of its
+/// committee of `committee_len` members.
+pub fn is_aggregator(committee_len: usize, selection_proof: &Signature) -> bool {
+    let modulo = cmp::max(1, committee_len / TARGET_AGGREGATORS_PER_COMMITTEE) as u64;
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&hash(&selection_proof.as_ssz_bytes())[0..8]);
+    u64::from_le_bytes(bytes) % modulo == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use types::Keypair;
+
+    #[test]
+    fn selects_about_the_target_number_of_aggregators() {
+        let keypair = Keypair::random();
+        let proofs: Vec<Signature> = (0..256)
+            .map(|slot| Signature::new(&selection_message(Slot::new(slot)), 0, &keypair.sk))
+            .collect();
+
+        // Every member of a small committee aggregates.
+        assert!(proofs.iter().all(|proof| is_aggregator(8, proof)));
+
+        let committee_len = TARGET_AGGREGATORS_PER_COMMITTEE * 8;
+        let selected = proofs
+            .iter()
+            .filter(|proof| is_aggregator(committee_len, proof))
+            .count();
+        assert!(selected > 0 && selected < 128, "selected {}", selected);
+    }
+}
@@ -0,0 +1,481 @@
+//! Scores of the availability and latency of each beacon node endpoint, by which the endpoint
+//! serving each request is selected.
+//!
+//! The outcome and latency of the most recent requests of each kind to each endpoint are kept, as
+//! is whether the endpoint is synced (if its head is cross-checked, see `chain_split`). `Broadcast`
+//! sends each request for data to the best endpoint (see `EndpointScores::best`):
+//!
+//! 1. Endpoints whose head lags the current slot, or has not been seen to be synced, are avoided.
+//! 2. Of the rest, those with the highest success rate of recent requests are preferred.
+//! 3. Of those, the endpoint with the lowest 90th percentile latency is chosen. Endpoints which
+//!    have not served the request yet are only chosen if no other has, preferring the primary.
+//!
+//! To avoid flapping between endpoints of similar scores, the endpoint last selected for a kind of
+//! request is kept unless another is synced whilst it is not, fails notably fewer requests
+//! (`FAILURE_HYSTERESIS_PER_MILLE`) or is notably faster (`LATENCY_HYSTERESIS`). Requests are only
+//! sent to the selected endpoint, so every `PROBE_INTERVAL` requests one is also copied to another
+//! endpoint (see `EndpointScores::probe`), so that an endpoint which was demoted is re-measured.
+//!
+//! The scores are exposed as Prometheus gauges, and served as JSON on `/lighthouse/beacon-nodes`:
+//!
+//! ```json
+//! [{"endpoint": "localhost:5051", "synced": true, "head_slot": 17, "requests": [{"request":
+//!   "produce_block", "samples": 4, "success_rate": 1.0, "latency_p50_ms": 120, "latency_p90_ms": 210}]}]
+//! ```
+use prometheus::{GaugeVec, Opts, Registry};
+use serde_derive::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+use types::Slot;
+
+pub use prometheus::Error;
+
+/// The number of requests of each kind to each endpoint which are kept.
+pub const DEFAULT_SAMPLES: usize = 32;
+
+/// The number of slots the head of an endpoint may lag the current slot whilst it is synced.
+pub const SYNC_TOLERANCE_SLOTS: u64 = 2;
+
+/// The fraction by which the latency of an endpoint must be lower than that of the selected
+/// endpoint to replace it.
+pub const LATENCY_HYSTERESIS: f64 = 0.2;
+
+/// The number of failures per thousand requests by which an endpoint must fail fewer requests than
+/// the selected endpoint to replace it.
+pub const FAILURE_HYSTERESIS_PER_MILLE: u64 = 50;
+
+/// The number of requests of each kind, one of which is copied to an endpoint other than the
+/// selected one.
+pub const PROBE_INTERVAL: u64 = 16;
+
+/// A kind of request to a beacon node.
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestKind {
+    ProduceBlock,
+    PublishBlock,
+    AttestationData,
+    PublishAttestation,
+    /// Queries of the chain, such as whether an attestation is known.
+    Lookup,
+}
+
+impl RequestKind {
+    fn label(self) -> &'static str {
+        match self {
+            RequestKind::ProduceBlock => "produce_block",
+            RequestKind::PublishBlock => "publish_block",
+            RequestKind::AttestationData => "attestation_data",
+            RequestKind::PublishAttestation => "publish_attestation",
+            RequestKind::Lookup => "lookup",
+        }
+    }
+}
+
+/// The outcome of a single request.
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    success: bool,
+    latency: Duration,
+}
+
+#[derive(Debug, Default)]
+struct Endpoint {
+    head_slot: Option<Slot>,
+    /// `None` if the head of the endpoint has not been checked.
+    synced: Option<bool>,
+    requests: BTreeMap<RequestKind, VecDeque<Sample>>,
+}
+
+/// The endpoint last selected for a kind of request.
+#[derive(Debug, Default)]
+struct Selection {
+    endpoint: Option<String>,
+    /// The number of times an endpoint has been selected.
+    requests: u64,
+}
+
+/// The rank of an endpoint for a kind of request, lower being better.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+struct Rank {
+    /// `true` if the endpoint lags the current slot or has not been checked.
+    unsynced: bool,
+    failures_per_mille: u64,
+    latency_p90_ms: u64,
+}
+
+impl Rank {
+    fn new(endpoint: Option<&Endpoint>, kind: RequestKind) -> Self {
+        let score = endpoint
+            .and_then(|endpoint| endpoint.requests.get(&kind))
+            .map(|samples| RequestScore::new(kind, samples));
+        Self {
+            unsynced: endpoint.map_or(true, |endpoint| endpoint.synced != Some(true)),
+            failures_per_mille: score.as_ref().map_or(0, |score| {
+                ((1.0 - score.success_rate) * 1_000.0).round() as u64
+            }),
+            latency_p90_ms: score
+                .and_then(|score| score.latency_p90_ms)
+                .unwrap_or(u64::max_value()),
+        }
+    }
+
+    /// Returns `true` if `self` is better enough than the rank of the `selected` endpoint to
+    /// replace it.
+    fn replaces(&self, selected: &Rank) -> bool {
+        if self.unsynced != selected.unsynced {
+            return !self.unsynced;
+        }
+        if self.failures_per_mille + FAILURE_HYSTERESIS_PER_MILLE < selected.failures_per_mille {
+            return true;
+        }
+        if self.failures_per_mille > selected.failures_per_mille + FAILURE_HYSTERESIS_PER_MILLE {
+            return false;
+        }
+        (self.latency_p90_ms as f64) < selected.latency_p90_ms as f64 * (1.0 - LATENCY_HYSTERESIS)
+    }
+}
+
+/// The score of a single kind of request to an endpoint.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct RequestScore {
+    pub request: RequestKind,
+    pub samples: usize,
+    /// The fraction of recent requests which succeeded, which is `1.0` if there are none.
+    pub success_rate: f64,
+    /// The median latency of recent successful requests, if any.
+    pub latency_p50_ms: Option<u64>,
+    /// The 90th percentile latency of recent successful requests, if any.
+    pub latency_p90_ms: Option<u64>,
+}
+
+impl RequestScore {
+    fn new(request: RequestKind, samples: &VecDeque<Sample>) -> Self {
+        let mut latencies: Vec<Duration> = samples
+            .iter()
+            .filter(|sample| sample.success)
+            .map(|sample| sample.latency)
+            .collect();
+        latencies.sort();
+        let millis = |latency: Duration| latency.as_millis() as u64;
+
+        Self {
+            request,
+            samples: samples.len(),
+            success_rate: if samples.is_empty() {
+                1.0
+            } else {
+                latencies.len() as f64 / samples.len() as f64
+            },
+            latency_p50_ms: percentile(&latencies, 0.5).map(millis),
+            latency_p90_ms: percentile(&latencies, 0.9).map(millis),
+        }
+    }
+}
+
+/// The scores of a single endpoint.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct EndpointScore {
+    pub endpoint: String,
+    /// `None` if the head of the endpoint has not been checked.
+    pub synced: Option<bool>,
+    pub head_slot: Option<Slot>,
+    pub requests: Vec<RequestScore>,
+}
+
+/// The scores of every endpoint, updated as requests complete.
+pub struct EndpointScores {
+    samples: usize,
+    endpoints: Mutex<BTreeMap<String, Endpoint>>,
+    selections: Mutex<BTreeMap<RequestKind, Selection>>,
+    success_rate: GaugeVec,
+    latency_p50: GaugeVec,
+    latency_p90: GaugeVec,
+    synced: GaugeVec,
+}
+
+impl EndpointScores {
+    /// Create scores which keep the `samples` most recent requests of each kind to each endpoint.
+    pub fn new(samples: usize) -> Result<Self, Error> {
+        let labels = &["endpoint", "request"];
+        Ok(Self {
+            samples: samples.max(1),
+            endpoints: Mutex::new(BTreeMap::new()),
+            selections: Mutex::new(BTreeMap::new()),
+            success_rate: GaugeVec::new(
+                Opts::new(
+                    "beacon_node_success_ratio",
+                    "fraction_of_recent_requests_which_succeeded",
+                ),
+                labels,
+            )?,
+            latency_p50: GaugeVec::new(
+                Opts::new(
+                    "beacon_node_latency_p50_seconds",
+                    "median_latency_of_recent_successful_requests",
+                ),
+                labels,
+            )?,
+            latency_p90: GaugeVec::new(
+                Opts::new(
+                    "beacon_node_latency_p90_seconds",
+                    "90th_percentile_latency_of_recent_successful_requests",
+                ),
+                labels,
+            )?,
+            synced: GaugeVec::new(
+                Opts::new(
+                    "beacon_node_synced",
+                    "1_if_the_head_is_near_the_current_slot",
+                ),
+                &["endpoint"],
+            )?,
+        })
+    }
+
+    /// Registers the gauges with `registry`.
+    pub fn register(&self, registry: &Registry) -> Result<(), Error> {
+        registry.register(Box::new(self.success_rate.clone()))?;
+        registry.register(Box::new(self.latency_p50.clone()))?;
+        registry.register(Box::new(self.latency_p90.clone()))?;
+        registry.register(Box::new(self.synced.clone()))?;
+        Ok(())
+    }
+
+    /// Adds `endpoint`, so that it is scored before any request to it completes.
+    pub fn add_endpoint(&self, endpoint: &str) {
+        if let Ok(mut endpoints) = self.endpoints.lock() {
+            endpoints.entry(endpoint.to_string()).or_default();
+        }
+    }
+
+    /// Records that a request of `kind` to `endpoint` took `latency`, and whether it succeeded.
+    pub fn record(&self, endpoint: &str, kind: RequestKind, success: bool, latency: Duration) {
+        let mut endpoints = match self.endpoints.lock() {
+            Ok(endpoints) => endpoints,
+            Err(_) => return,
+        };
+        let samples = endpoints
+            .entry(endpoint.to_string())
+            .or_default()
+            .requests
+            .entry(kind)
+            .or_insert_with(VecDeque::new);
+        if samples.len() >= self.samples {
+            samples.pop_front();
+        }
+        samples.push_back(Sample { success, latency });
+
+        let score = RequestScore::new(kind, samples);
+        let labels = &[endpoint, kind.label()];
+        let seconds = |millis: Option<u64>| millis.map_or(0.0, |millis| millis as f64 / 1_000.0);
+        self.success_rate
+            .with_label_values(labels)
+            .set(score.success_rate);
+        self.latency_p50
+            .with_label_values(labels)
+            .set(seconds(score.latency_p50_ms));
+        self.latency_p90
+            .with_label_values(labels)
+            .set(seconds(score.latency_p90_ms));
+    }
+
+    /// Records the head of `endpoint` at `current_slot`, or `None` if it could not be reached.
+    pub fn record_head(&self, endpoint: &str, head_slot: Option<Slot>, current_slot: Slot) {
+        let synced = head_slot.map(|head_slot| head_slot + SYNC_TOLERANCE_SLOTS >= current_slot);
+        if let Ok(mut endpoints) = self.endpoints.lock() {
+            let state = endpoints.entry(endpoint.to_string()).or_default();
+            state.head_slot = head_slot.or(state.head_slot);
+            state.synced = synced;
+        }
+        self.synced
+            .with_label_values(&[endpoint])
+            .set(if synced == Some(true) { 1.0 } else { 0.0 });
+    }
+
+    /// Selects the best of `endpoints` to serve a request of `kind` and returns its index.
+    ///
+    /// The best is the first of the best ranked if several are equal, unless the endpoint last
+    /// selected for `kind` ranks almost as well, in which case it is selected again.
+    pub fn best(&self, kind: RequestKind, endpoints: &[&str]) -> usize {
+        if endpoints.is_empty() {
+            return 0;
+        }
+        let ranks: Vec<Rank> = match self.endpoints.lock() {
+            Ok(known) => endpoints
+                .iter()
+                .map(|endpoint| Rank::new(known.get(*endpoint), kind))
+                .collect(),
+            Err(_) => return 0,
+        };
+        let best = ranks
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, rank)| *rank)
+            .map_or(0, |(index, _)| index);
+
+        let mut selections = match self.selections.lock() {
+            Ok(selections) => selections,
+            Err(_) => return best,
+        };
+        let selection = selections.entry(kind).or_default();
+        let selected = selection
+            .endpoint
+            .as_ref()
+            .and_then(|selected| endpoints.iter().position(|endpoint| endpoint == selected));
+        let index = match selected {
+            Some(selected) if !ranks[best].replaces(&ranks[selected]) => selected,
+            _ => best,
+        };
+        selection.endpoint = Some(endpoints[index].to_string());
+        selection.requests += 1;
+        index
+    }
+
+    /// Returns the index of one of `endpoints` to which the request of `kind` for which `best` was
+    /// just called should also be sent, once in every `PROBE_INTERVAL` requests.
+    ///
+    /// Each of the endpoints other than the selected one is probed in turn, so that the scores of
+    /// endpoints which are not selected are kept up to date.
+    pub fn probe(&self, kind: RequestKind, endpoints: &[&str]) -> Option<usize> {
+        let selections = self.selections.lock().ok()?;
+        let selection = selections.get(&kind)?;
+        if selection.requests % PROBE_INTERVAL != 0 {
+            return None;
+        }
+        let selected = selection.endpoint.as_ref().map(String::as_str);
+        let others: Vec<usize> = (0..endpoints.len())
+            .filter(|&index| Some(endpoints[index]) != selected)
+            .collect();
+        if others.is_empty() {
+            return None;
+        }
+        let round = (selection.requests / PROBE_INTERVAL) as usize;
+        Some(others[round % others.len()])
+    }
+
+    /// Returns the scores of every endpoint, ordered by endpoint.
+    pub fn scores(&self) -> Vec<EndpointScore> {
+        let endpoints = match self.endpoints.lock() {
+            Ok(endpoints) => endpoints,
+            Err(_) => return vec![],
+        };
+        endpoints
+            .iter()
+            .map(|(endpoint, state)| EndpointScore {
+                endpoint: endpoint.clone(),
+                synced: state.synced,
+                head_slot: state.head_slot,
+                requests: state
+                    .requests
+                    .iter()
+                    .map(|(kind, samples)| RequestScore::new(*kind, samples))
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+impl Default for EndpointScores {
+    /// Scores which keep the `DEFAULT_SAMPLES` most recent requests.
+    fn default() -> Self {
+        Self::new(DEFAULT_SAMPLES).expect("the gauge options are valid")
+    }
+}
+
+/// Returns the `p` percentile of `sorted`, if it is not empty.
+fn percentile(sorted: &[Duration], p: f64) -> Option<Duration> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let index = ((sorted.len() - 1) as f64 * p).ceil() as usize;
+    sorted.get(index).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selects_the_best_endpoint() {
+        let scores = EndpointScores::new(4).expect("should create scores");
+        let endpoints = ["primary", "other"];
+        let ms = Duration::from_millis;
+        let kind = RequestKind::AttestationData;
+        scores.add_endpoint("primary");
+        assert_eq!(scores.best(kind, &endpoints), 0);
+
+        // An endpoint which has not been seen to be synced is avoided.
+        scores.record_head("other", Some(Slot::new(19)), Slot::new(20));
+        assert_eq!(scores.best(kind, &endpoints), 1);
+
+        // An equally good endpoint does not replace the selected one.
+        scores.record_head("primary", Some(Slot::new(20)), Slot::new(20));
+        assert_eq!(scores.best(kind, &endpoints), 1);
+        assert_eq!(scores.best(RequestKind::ProduceBlock, &endpoints), 0);
+
+        // A measured endpoint is preferred to one which has not served the request.
+        scores.record("primary", kind, true, ms(300));
+        assert_eq!(scores.best(kind, &endpoints), 0);
+
+        // An endpoint which is barely faster does not replace the selected one, one which is
+        // notably faster does.
+        scores.record("other", kind, true, ms(280));
+        assert_eq!(scores.best(kind, &endpoints), 0);
+        for _ in 0..4 {
+            scores.record("other", kind, true, ms(100));
+        }
+        assert_eq!(scores.best(kind, &endpoints), 1);
+
+        // A failing endpoint is avoided regardless of its latency.
+        scores.record("other", kind, false, ms(5));
+        assert_eq!(scores.best(kind, &endpoints), 0);
+
+        // As is one which is not synced.
+        scores.record_head("primary", Some(Slot::new(10)), Slot::new(20));
+        assert_eq!(scores.best(kind, &endpoints), 1);
+        scores.record_head("primary", Some(Slot::new(19)), Slot::new(20));
+        assert_eq!(scores.best(kind, &endpoints), 0);
+
+        // Only the most recent requests are scored.
+        for _ in 0..4 {
+            scores.record("other", kind, true, ms(100));
+        }
+        assert_eq!(scores.best(kind, &endpoints), 1);
+
+        let all = scores.scores();
+        assert_eq!(
+            all.iter().map(|s| s.endpoint.as_str()).collect::<Vec<_>>(),
+            vec!["other", "primary"]
+        );
+        assert_eq!(all[1].synced, Some(true));
+        assert_eq!(all[1].head_slot, Some(Slot::new(19)));
+        assert_eq!(
+            all[0].requests,
+            vec![RequestScore {
+                request: kind,
+                samples: 4,
+                success_rate: 1.0,
+                latency_p50_ms: Some(100),
+                latency_p90_ms: Some(100),
+            }]
+        );
+        assert!(serde_json::to_string(&all).is_ok());
+    }
+
+    #[test]
+    fn probes_the_endpoints_which_are_not_selected() {
+        let scores = EndpointScores::new(4).expect("should create scores");
+        let endpoints = ["primary", "second", "third"];
+        let kind = RequestKind::ProduceBlock;
+
+        let mut probes = vec![];
+        for _ in 0..PROBE_INTERVAL * 2 {
+            assert_eq!(scores.best(kind, &endpoints), 0);
+            probes.extend(scores.probe(kind, &endpoints));
+        }
+        assert_eq!(probes, vec![2, 1]);
+        assert_eq!(scores.probe(RequestKind::Lookup, &endpoints), None);
+    }
+}
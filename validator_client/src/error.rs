@@ -1,19 +1,86 @@
-use slot_clock;
+//! Errors which prevent the validator service from starting, or stop it whilst running.
+//!
+//! Errors from the components of the service (e.g., `block_producer::Error`) are typed in the
+//! same way and carry the slot, epoch or beacon node they concern, so that they may be logged as
+//! they are without additional context.
+use crate::audit_log::AuditLogError;
+use crate::chain_identity;
+use crate::datadir_lock;
+use crate::fee_recipient;
+use crate::gas_limit;
+use crate::hsm;
+use crate::signing_log;
+use crate::wss_checkpoint;
+use slot_clock::SystemTimeSlotClockError;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use thiserror::Error;
+use types::Slot;
 
-use error_chain::error_chain;
-
-error_chain! {
-   links  { }
-
-   errors {
-    SlotClockError(e: slot_clock::SystemTimeSlotClockError) {
-        description("Error reading system time"),
-        display("SlotClockError: '{:?}'", e)
-    }
-
-    SystemTimeError(t: String ) {
-        description("Error reading system time"),
-        display("SystemTimeError: '{}'", t)
-    }
-   }
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("unable to build the tokio runtime: {0}")]
+    Runtime(#[source] std::io::Error),
+    #[error("unable to lock the data directory: {0}")]
+    DatadirLock(#[from] datadir_lock::Error),
+    #[error("unable to connect to the beacon node HTTP API at {url}: {reason}")]
+    BeaconApi { url: String, reason: String },
+    #[error("the beacon node sent invalid node info: {0:?}")]
+    NodeInfo(protos::convert::Error),
+    #[error("the beacon node has the wrong chain id (expected {expected}, found {found})")]
+    WrongChainId { expected: u64, found: u64 },
+    #[error("refusing to sign with this beacon node: {0}")]
+    ChainIdentity(#[from] chain_identity::Error),
+    #[error("refusing to sign with this beacon node: {0}")]
+    WssCheckpoint(#[from] wss_checkpoint::Error),
+    #[error("unable to read the slot clock: {0:?}")]
+    SlotClock(SystemTimeSlotClockError),
+    #[error("genesis is not in the past")]
+    GenesisNotInPast,
+    #[error("unable to locate validator key pairs, nothing to do")]
+    NoKeypairs,
+    #[error("unable to open the audit log {path:?}: {source}")]
+    AuditLog {
+        path: PathBuf,
+        #[source]
+        source: AuditLogError,
+    },
+    #[error("unable to open the trace file {path:?}: {source}")]
+    Trace {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("unable to load fee recipients: {0}")]
+    FeeRecipients(#[source] fee_recipient::Error),
+    #[error("unable to load gas limits: {0}")]
+    GasLimits(#[source] gas_limit::Error),
+    #[error("unable to load the gRPC TLS credentials or auth token: {0}")]
+    GrpcCredentials(#[source] std::io::Error),
+    #[error("unable to serve the health endpoints on {address}: {source}")]
+    Health {
+        address: SocketAddr,
+        #[source]
+        source: hyper::Error,
+    },
+    #[error("unable to sign with the HSM: {0}")]
+    Hsm(#[source] hsm::Error),
+    #[error("unable to register the metrics: {0}")]
+    Metrics(#[source] prometheus::Error),
+    #[error("unable to open the signing log: {0}")]
+    SigningLog(#[from] signing_log::Error),
+    #[error("unable to create the webhook notifier: {0}")]
+    Notifier(String),
+    #[error("unable to push metrics: {0}")]
+    MetricsPush(String),
+    #[error("unable to set up the signing lease: {0}")]
+    SigningLease(String),
+    #[error("the service failed: {0}")]
+    Service(String),
+    #[error("unable to handle shutdown signals: {0}")]
+    Signals(String),
+    #[error("slot {slot} has already been processed, the previous slot was likely missed")]
+    DuplicateSlot { slot: Slot },
 }
+
+pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,94 @@
+//! The fee recipient to be used in the block proposals of each validator.
+//!
+//! Validators use the fee recipient listed for them in the fee recipient file, if any. Otherwise,
+//! they use the `--suggested-fee-recipient`, if any.
+//!
+//! Blocks do not yet contain an execution payload, so the beacon node records the fee recipients
+//! but does not yet use them.
+//!
+//! The fee recipient file is a JSON object mapping public keys to addresses:
+//!
+//! ```json
+//! {
+//!     "0xa572cbeb...": "0x00000000219ab540356cbb839cbe05303d7705fa"
+//! }
+//! ```
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+use thiserror::Error;
+use types::{Address, PublicKey};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The fee recipient file could not be opened.
+    #[error("unable to open the fee recipient file: {0}")]
+    UnableToOpenFile(#[source] std::io::Error),
+    /// The fee recipient file is not a valid mapping of public keys to addresses.
+    #[error("invalid fee recipient file: {0}")]
+    InvalidFile(#[source] serde_json::Error),
+}
+
+/// The fee recipients of all validators.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FeeRecipients {
+    /// The fee recipient of validators not listed in `per_validator`.
+    pub default: Option<Address>,
+    /// The fee recipients of specific validators.
+    pub per_validator: HashMap<PublicKey, Address>,
+}
+
+impl FeeRecipients {
+    /// Create an instance with the given `default` and the per-validator fee recipients read from
+    /// `path`, if any.
+    pub fn new(default: Option<Address>, path: Option<&Path>) -> Result<Self, Error> {
+        let per_validator = match path {
+            Some(path) => {
+                let file = File::open(path).map_err(Error::UnableToOpenFile)?;
+                serde_json::from_reader(file).map_err(Error::InvalidFile)?
+            }
+            None => HashMap::new(),
+        };
+
+        Ok(Self {
+            default,
+            per_validator,
+        })
+    }
+
+    /// Returns the fee recipient of `validator`, if any.
+    pub fn get(&self, validator: &PublicKey) -> Option<Address> {
+        self.per_validator.get(validator).cloned().or(self.default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+    use types::Keypair;
+
+    #[test]
+    fn file_overrides_default() {
+        let listed = Keypair::random().pk;
+        let unlisted = Keypair::random().pk;
+        let default = Address::from_low_u64_be(1);
+        let address = Address::from_low_u64_be(2);
+
+        let dir = TempDir::new().expect("should create temp dir");
+        let path = dir.path().join("fee_recipients.json");
+        fs::write(
+            &path,
+            format!(r#"{{"{}": "{:?}"}}"#, listed.as_hex_string(), address),
+        )
+        .expect("should write file");
+
+        let fee_recipients =
+            FeeRecipients::new(Some(default), Some(&path)).expect("should read file");
+
+        assert_eq!(fee_recipients.get(&listed), Some(address));
+        assert_eq!(fee_recipients.get(&unlisted), Some(default));
+        assert_eq!(FeeRecipients::default().get(&listed), None);
+    }
+}
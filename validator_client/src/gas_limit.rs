@@ -0,0 +1,104 @@
+//! The gas limit preferred for the block proposals of each validator, which is registered with
+//! external block builders.
+//!
+//! Validators use the gas limit listed for them in the gas limit file, if any. Otherwise, they
+//! use the `--gas-limit`.
+//!
+//! The gas limit file is a JSON object mapping public keys to gas limits:
+//!
+//! ```json
+//! {
+//!     "0xa572cbeb...": 36000000
+//! }
+//! ```
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+use thiserror::Error;
+use types::PublicKey;
+
+/// The gas limit of validators which are not listed in the gas limit file, unless configured.
+pub const DEFAULT_GAS_LIMIT: u64 = 30_000_000;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The gas limit file could not be opened.
+    #[error("unable to open the gas limit file: {0}")]
+    UnableToOpenFile(#[source] std::io::Error),
+    /// The gas limit file is not a valid mapping of public keys to gas limits.
+    #[error("invalid gas limit file: {0}")]
+    InvalidFile(#[source] serde_json::Error),
+}
+
+/// The gas limits of all validators.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GasLimits {
+    /// The gas limit of validators not listed in `per_validator`.
+    pub default: u64,
+    /// The gas limits of specific validators.
+    pub per_validator: HashMap<PublicKey, u64>,
+}
+
+impl Default for GasLimits {
+    fn default() -> Self {
+        Self {
+            default: DEFAULT_GAS_LIMIT,
+            per_validator: HashMap::new(),
+        }
+    }
+}
+
+impl GasLimits {
+    /// Create an instance with the given `default` and the per-validator gas limits read from
+    /// `path`, if any.
+    pub fn new(default: u64, path: Option<&Path>) -> Result<Self, Error> {
+        let per_validator = match path {
+            Some(path) => {
+                let file = File::open(path).map_err(Error::UnableToOpenFile)?;
+                serde_json::from_reader(file).map_err(Error::InvalidFile)?
+            }
+            None => HashMap::new(),
+        };
+
+        Ok(Self {
+            default,
+            per_validator,
+        })
+    }
+
+    /// Returns the gas limit of `validator`.
+    pub fn get(&self, validator: &PublicKey) -> u64 {
+        self.per_validator
+            .get(validator)
+            .cloned()
+            .unwrap_or(self.default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+    use types::Keypair;
+
+    #[test]
+    fn file_overrides_default() {
+        let listed = Keypair::random().pk;
+        let unlisted = Keypair::random().pk;
+
+        let dir = TempDir::new().expect("should create temp dir");
+        let path = dir.path().join("gas_limits.json");
+        fs::write(
+            &path,
+            format!(r#"{{"{}": 36000000}}"#, listed.as_hex_string()),
+        )
+        .expect("should write file");
+
+        let gas_limits = GasLimits::new(25_000_000, Some(&path)).expect("should read file");
+
+        assert_eq!(gas_limits.get(&listed), 36_000_000);
+        assert_eq!(gas_limits.get(&unlisted), 25_000_000);
+        assert_eq!(GasLimits::default().get(&listed), DEFAULT_GAS_LIMIT);
+    }
+}
@@ -0,0 +1,403 @@
+//! `/health` and `/ready` HTTP endpoints, for liveness and readiness probes (e.g., in Kubernetes).
+//!
+//! Both endpoints respond with a JSON `HealthReport`:
+//!
+//! - `/health` responds `200 OK` whilst the slot clock is sane and slots are being processed,
+//!   otherwise `503 Service Unavailable`. A failing liveness probe should restart the process.
+//! - `/ready` responds `200 OK` only once every check passes: the beacon node answered the latest
+//!   duties request, the duties of the current epoch are known and keystores are loaded. A failing
+//!   readiness probe should withhold traffic (or a failover) rather than restart the process.
+//!
+//! The same server also serves `/metrics`, in the Prometheus text format,
+//! `/proposal_timings`, the timings of recent proposals as JSON (see `proposal_timing`),
+//! `/lighthouse/duties` and `/lighthouse/proposals` (see `debug_api`) and
+//! `/lighthouse/beacon-nodes`, the scores of each beacon node (see `endpoint_scores`).
+use crate::debug_api;
+use crate::duties::DutiesStore;
+use crate::endpoint_scores::EndpointScores;
+use crate::proposal_timing::ProposalTimings;
+use crate::slashing_protection::SlashingProtection;
+use futures::Future;
+use hyper::service::service_fn_ok;
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use prometheus::{Encoder, IntCounter, Registry, TextEncoder};
+use serde_derive::Serialize;
+use slog::{info, warn};
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use types::{Epoch, Slot};
+
+/// The number of slots which may pass without one being processed before the validator client is
+/// considered unhealthy.
+const MAX_SLOTS_WITHOUT_PROCESSING: u32 = 2;
+
+/// The result of a single check.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct Check {
+    pub ok: bool,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(ok: bool, detail: String) -> Self {
+        Self { ok, detail }
+    }
+}
+
+/// The result of every check.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct HealthReport {
+    pub beacon_node: Check,
+    pub slot_clock: Check,
+    pub keystores: Check,
+    pub duties: Check,
+    /// The time from startup to the first duties being obtained, once they have been.
+    pub time_to_first_duty_ms: Option<u64>,
+    /// The number of re-orgs of the beacon node observed since startup.
+    pub reorgs: u64,
+}
+
+impl HealthReport {
+    /// Returns `true` if the validator client is live (i.e., should not be restarted).
+    pub fn is_healthy(&self) -> bool {
+        self.slot_clock.ok
+    }
+
+    /// Returns `true` if the validator client is able to perform its duties.
+    pub fn is_ready(&self) -> bool {
+        self.beacon_node.ok && self.slot_clock.ok && self.keystores.ok && self.duties.ok
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// The time at which the latest slot was processed, and the slot.
+    processed_slot: Option<(Instant, Slot)>,
+    /// The latest error reading the slot clock, if the latest read failed.
+    slot_clock_error: Option<String>,
+    /// The time at which the beacon node last answered a duties request.
+    beacon_node_contact: Option<Instant>,
+    /// The latest beacon node error, if the latest request failed.
+    beacon_node_error: Option<String>,
+    /// The latest epoch for which duties are known.
+    duties_epoch: Option<Epoch>,
+    /// The number of keystores loaded.
+    keystores: usize,
+    /// The time from startup to the first duties being obtained.
+    time_to_first_duty: Option<Duration>,
+    /// The number of re-orgs observed.
+    reorgs: u64,
+}
+
+/// The health of the validator client, updated by the service as it runs.
+#[derive(Debug)]
+pub struct Health {
+    slot_duration: Duration,
+    slots_per_epoch: u64,
+    /// The time at which the validator client started.
+    started: Instant,
+    state: RwLock<State>,
+    /// The number of re-orgs observed, as a Prometheus counter.
+    reorg_count: IntCounter,
+}
+
+impl Health {
+    pub fn new(slot_duration: Duration, slots_per_epoch: u64, started: Instant) -> Self {
+        Self {
+            slot_duration,
+            slots_per_epoch,
+            started,
+            state: RwLock::new(State::default()),
+            reorg_count: IntCounter::new("beacon_node_reorgs_total", "number_of_head_reorgs")
+                .expect("the counter options are valid"),
+        }
+    }
+
+    /// Registers the re-org counter with `registry`.
+    pub fn register(&self, registry: &Registry) -> Result<(), prometheus::Error> {
+        registry.register(Box::new(self.reorg_count.clone()))
+    }
+
+    /// Records that `slot` has started to be processed.
+    pub fn slot_processed(&self, slot: Slot) {
+        self.update(|state| {
+            state.processed_slot = Some((Instant::now(), slot));
+            state.slot_clock_error = None;
+        })
+    }
+
+    /// Records a failure to read the slot clock.
+    pub fn slot_clock_failed(&self, error: String) {
+        self.update(|state| state.slot_clock_error = Some(error))
+    }
+
+    /// Records that the beacon node returned the duties of `epoch`.
+    ///
+    /// Returns the time since startup if these are the first duties obtained.
+    pub fn duties_updated(&self, epoch: Epoch) -> Option<Duration> {
+        let now = Instant::now();
+        let mut first = None;
+        self.update(|state| {
+            state.beacon_node_contact = Some(now);
+            state.beacon_node_error = None;
+            state.duties_epoch = Some(epoch);
+            if state.time_to_first_duty.is_none() {
+                first = Some(now.duration_since(self.started));
+                state.time_to_first_duty = first;
+            }
+        });
+        first
+    }
+
+    /// Records a failure to obtain duties from the beacon node.
+    pub fn beacon_node_failed(&self, error: String) {
+        self.update(|state| state.beacon_node_error = Some(error))
+    }
+
+    /// Records a re-org of the beacon node.
+    pub fn reorg_observed(&self) {
+        self.reorg_count.inc();
+        self.update(|state| state.reorgs += 1)
+    }
+
+    /// Records the number of keystores loaded.
+    pub fn keystores_loaded(&self, count: usize) {
+        self.update(|state| state.keystores = count)
+    }
+
+    /// Returns the result of every check.
+    pub fn report(&self) -> HealthReport {
+        self.report_at(Instant::now())
+    }
+
+    fn report_at(&self, now: Instant) -> HealthReport {
+        let state = match self.state.read() {
+            Ok(state) => state,
+            Err(_) => {
+                let poisoned = Check::new(false, "health lock poisoned".to_string());
+                return HealthReport {
+                    beacon_node: poisoned.clone(),
+                    slot_clock: poisoned.clone(),
+                    keystores: poisoned.clone(),
+                    duties: poisoned,
+                    time_to_first_duty_ms: None,
+                    reorgs: 0,
+                };
+            }
+        };
+
+        let since = |instant: Instant| now.duration_since(instant).as_secs();
+
+        let slot_clock = match (&state.slot_clock_error, state.processed_slot) {
+            (Some(error), _) => Check::new(false, format!("unable to read slot clock: {}", error)),
+            (None, None) => Check::new(false, "no slot processed yet".to_string()),
+            (None, Some((instant, slot))) => Check::new(
+                now.duration_since(instant) <= self.slot_duration * MAX_SLOTS_WITHOUT_PROCESSING,
+                format!("slot {} processed {}s ago", slot, since(instant)),
+            ),
+        };
+
+        let beacon_node = match (&state.beacon_node_error, state.beacon_node_contact) {
+            (Some(error), Some(instant)) => Check::new(
+                false,
+                format!("{} (last contact {}s ago)", error, since(instant)),
+            ),
+            (Some(error), None) => Check::new(false, error.clone()),
+            (None, None) => Check::new(false, "beacon node not yet contacted".to_string()),
+            (None, Some(instant)) => {
+                Check::new(true, format!("last contact {}s ago", since(instant)))
+            }
+        };
+
+        let current_epoch = state
+            .processed_slot
+            .map(|(_, slot)| slot.epoch(self.slots_per_epoch));
+        let duties = match (state.duties_epoch, current_epoch) {
+            (Some(known), Some(current)) => Check::new(
+                known >= current,
+                format!(
+                    "duties known for epoch {}, current epoch {}",
+                    known, current
+                ),
+            ),
+            (Some(known), None) => Check::new(false, format!("duties known for epoch {}", known)),
+            (None, _) => Check::new(false, "no duties known".to_string()),
+        };
+
+        let keystores = Check::new(
+            state.keystores > 0,
+            format!("{} keystores loaded", state.keystores),
+        );
+
+        HealthReport {
+            beacon_node,
+            slot_clock,
+            keystores,
+            duties,
+            time_to_first_duty_ms: state
+                .time_to_first_duty
+                .map(|duration| duration.as_millis() as u64),
+            reorgs: state.reorgs,
+        }
+    }
+
+    fn update(&self, f: impl FnOnce(&mut State)) {
+        if let Ok(mut state) = self.state.write() {
+            f(&mut state)
+        }
+    }
+}
+
+/// The state served by the health server, besides the health of the validator client.
+#[derive(Clone)]
+pub struct Sources {
+    pub timings: Arc<ProposalTimings>,
+    pub registry: Registry,
+    pub duties: Arc<DutiesStore>,
+    pub slashing_protection: Arc<SlashingProtection>,
+    pub endpoint_scores: Arc<EndpointScores>,
+}
+
+/// Returns a future which serves `/health`, `/ready`, `/metrics`, `/proposal_timings` and the
+/// debug endpoints on `address` until it fails.
+pub fn serve(
+    address: &SocketAddr,
+    health: Arc<Health>,
+    sources: Sources,
+    log: slog::Logger,
+) -> Result<impl Future<Item = (), Error = ()>, hyper::Error> {
+    let server = Server::try_bind(address)?.serve(move || {
+        let health = health.clone();
+        let sources = sources.clone();
+        service_fn_ok(move |req: Request<Body>| route(&health, &sources, &req))
+    });
+
+    info!(log, "Serving health endpoints"; "address" => format!("{}", address));
+    Ok(server.map_err(move |e| warn!(log, "Health endpoint failed"; "error" => e.to_string())))
+}
+
+fn route(health: &Health, sources: &Sources, req: &Request<Body>) -> Response<Body> {
+    match (req.method(), req.uri().path()) {
+        (&Method::GET, "/metrics") => metrics(&sources.registry),
+        (&Method::GET, "/proposal_timings") => json(
+            StatusCode::OK,
+            serde_json::to_string(&sources.timings.recent()).unwrap_or_default(),
+        ),
+        (&Method::GET, "/lighthouse/duties") => debug(debug_api::duties(&sources.duties)),
+        (&Method::GET, "/lighthouse/proposals") => debug(debug_api::proposals(
+            &sources.timings,
+            &sources.slashing_protection,
+        )),
+        (&Method::GET, "/lighthouse/beacon-nodes") => json(
+            StatusCode::OK,
+            serde_json::to_string(&sources.endpoint_scores.scores()).unwrap_or_default(),
+        ),
+        _ => respond(health, req),
+    }
+}
+
+/// Responds with the state read by a debug endpoint, or `500 Internal Server Error` if it could
+/// not be read.
+fn debug<T: serde::Serialize>(result: Result<T, debug_api::Error>) -> Response<Body> {
+    match result {
+        Ok(state) => json(
+            StatusCode::OK,
+            serde_json::to_string(&state).unwrap_or_default(),
+        ),
+        Err(e) => json(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            serde_json::json!({ "error": e.to_string() }).to_string(),
+        ),
+    }
+}
+
+fn respond(health: &Health, req: &Request<Body>) -> Response<Body> {
+    let report = health.report();
+    let ok = match (req.method(), req.uri().path()) {
+        (&Method::GET, "/health") => report.is_healthy(),
+        (&Method::GET, "/ready") => report.is_ready(),
+        _ => {
+            return Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(Body::empty())
+                .expect("response should always be created")
+        }
+    };
+
+    let status = if ok {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    json(status, serde_json::to_string(&report).unwrap_or_default())
+}
+
+fn json(status: StatusCode, body: String) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .body(Body::from(body))
+        .expect("response should always be created")
+}
+
+fn metrics(registry: &Registry) -> Response<Body> {
+    let encoder = TextEncoder::new();
+    let mut buffer = vec![];
+    let status = match encoder.encode(&registry.gather(), &mut buffer) {
+        Ok(()) => StatusCode::OK,
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    Response::builder()
+        .status(status)
+        .header("Content-Type", encoder.format_type())
+        .body(Body::from(buffer))
+        .expect("response should always be created")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ready_once_all_checks_pass() {
+        let health = Health::new(Duration::from_secs(6), 8, Instant::now());
+        let report = health.report();
+        assert!(!report.is_healthy());
+        assert!(!report.is_ready());
+
+        health.keystores_loaded(2);
+        health.slot_processed(Slot::new(17));
+        assert!(health.duties_updated(Epoch::new(1)).is_some());
+        let report = health.report();
+        assert!(report.is_healthy());
+        // The duties of epoch 2 are not yet known.
+        assert!(!report.duties.ok);
+        assert!(!report.is_ready());
+
+        assert_eq!(health.duties_updated(Epoch::new(2)), None);
+        let report = health.report();
+        assert!(report.is_ready());
+        assert!(report.time_to_first_duty_ms.is_some());
+
+        let registry = Registry::new();
+        health.register(&registry).expect("should register");
+        health.reorg_observed();
+        assert_eq!(health.report().reorgs, 1);
+        let families = registry.gather();
+        let reorgs = families
+            .iter()
+            .find(|family| family.get_name() == "beacon_node_reorgs_total")
+            .expect("should gather the re-org counter");
+        assert_eq!(reorgs.get_metric()[0].get_counter().get_value() as u64, 1);
+
+        health.beacon_node_failed("connection refused".to_string());
+        let report = health.report();
+        assert!(report.is_healthy());
+        assert!(!report.is_ready());
+
+        // Slots which are not processed make the validator client unhealthy.
+        let later = Instant::now() + Duration::from_secs(6 * 3);
+        assert!(!health.report_at(later).is_healthy());
+    }
+}
@@ -0,0 +1,364 @@
+//! Signs with BLS keys held in a hardware security module (HSM), so that the keys of validators
+//! never leave the hardware.
+//!
+//! Each `Hsm` runs a fixed number of workers, each with its own session, so at most that many
+//! signatures are requested from the HSM at once. A worker whose session fails opens another for
+//! its next request, logging in with the same PIN.
+//!
+//! There is no standard PKCS#11 mechanism for BLS12-381, so the mechanism of the HSM (or its
+//! firmware) is configured. The HSM signs the message followed by the domain (8 bytes,
+//! little-endian), as `Signature::new(message, domain, sk)` does, and every signature is verified
+//! before it is used.
+pub mod pkcs11;
+#[cfg(feature = "yubihsm")]
+pub mod yubihsm;
+
+use crate::signer::{SignatureFuture, Signer};
+use futures::sync::oneshot;
+use futures::{future, Future};
+use serde_derive::{Deserialize, Serialize};
+use slog::{error, info, warn};
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use thiserror::Error;
+use types::{PublicKey, Signature};
+
+/// The number of sessions opened with the HSM, and so the number of signatures requested at once.
+pub const DEFAULT_SESSIONS: usize = 4;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The PKCS#11 module could not be loaded.
+    #[error("unable to load the PKCS#11 module {path:?}: {reason}")]
+    Module { path: PathBuf, reason: String },
+    /// The HSM has no token in the configured slot.
+    #[error("no token found in slot {0:?}")]
+    NoToken(Option<u64>),
+    /// The PIN could not be read.
+    #[error("unable to read the HSM PIN: {0}")]
+    Pin(#[source] std::io::Error),
+    /// A session could not be opened or logged in to.
+    #[error("unable to open a session with the HSM: {0}")]
+    Session(String),
+    /// The HSM refused or failed to sign.
+    #[error("the HSM was unable to sign: {0}")]
+    Sign(String),
+    /// The HSM holds no key for the validator.
+    #[error("the HSM holds no key for validator {0}")]
+    UnknownKey(String),
+    /// The HSM returned a signature which does not verify with the public key of the validator.
+    #[error("the HSM returned an invalid signature for validator {0}")]
+    InvalidSignature(String),
+}
+
+/// The kind of HSM.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HsmKind {
+    /// Any HSM with a PKCS#11 module.
+    Pkcs11,
+    /// A YubiHSM 2, through its PKCS#11 module, which logs in with the id of an authentication
+    /// key and its password.
+    #[cfg(feature = "yubihsm")]
+    #[serde(rename = "yubihsm")]
+    YubiHsm { auth_key_id: u16 },
+}
+
+/// The configuration of an HSM.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct HsmConfig {
+    pub kind: HsmKind,
+    /// The PKCS#11 module (shared library) of the HSM.
+    pub module: PathBuf,
+    /// The slot of the token holding the keys, or the first slot with a token if `None`.
+    pub slot: Option<u64>,
+    /// A file containing the PIN of the token, or the PIN is asked for at the terminal if `None`.
+    pub pin_file: Option<PathBuf>,
+    /// The (vendor-defined) PKCS#11 mechanism which produces BLS signatures.
+    pub mechanism: u64,
+    /// The number of sessions opened with the HSM.
+    pub sessions: usize,
+}
+
+impl HsmConfig {
+    /// Reads the PIN from `pin_file`, or asks for it at the terminal.
+    ///
+    /// A trailing newline is not considered part of the PIN.
+    pub fn pin(&self) -> Result<String, Error> {
+        match &self.pin_file {
+            Some(path) => crate::password::read_password_file(path),
+            None => rpassword::read_password_from_tty(Some("HSM PIN: ")),
+        }
+        .map_err(Error::Pin)
+    }
+
+    /// Loads the backend of the HSM, logging in with `pin`.
+    pub fn backend(&self, pin: String) -> Result<pkcs11::Pkcs11Backend, Error> {
+        let pin = match self.kind {
+            HsmKind::Pkcs11 => pin,
+            #[cfg(feature = "yubihsm")]
+            HsmKind::YubiHsm { auth_key_id } => yubihsm::pin(auth_key_id, &pin),
+        };
+        pkcs11::Pkcs11Backend::new(&self.module, self.slot, pin, self.mechanism)
+    }
+}
+
+/// A connection to an HSM, through which keys are found and messages signed.
+pub trait HsmBackend: Send + Sync + 'static {
+    type Session;
+
+    /// Opens and logs in to a new session.
+    fn open_session(&self) -> Result<Self::Session, Error>;
+
+    /// Closes a session which has failed.
+    fn close_session(&self, _session: Self::Session) {}
+
+    /// Returns the public keys of all BLS keys held by the HSM.
+    fn public_keys(&self, session: &mut Self::Session) -> Result<Vec<PublicKey>, Error>;
+
+    /// Signs `message` with `domain` using the key of `validator`, blocking until the HSM
+    /// replies.
+    fn sign(
+        &self,
+        session: &mut Self::Session,
+        validator: &PublicKey,
+        message: &[u8],
+        domain: u64,
+    ) -> Result<Signature, Error>;
+}
+
+/// A request for a worker to sign a message.
+struct Request {
+    validator: PublicKey,
+    message: Vec<u8>,
+    domain: u64,
+    reply: oneshot::Sender<Result<Signature, Error>>,
+}
+
+/// The workers signing with a single HSM.
+pub struct Hsm {
+    sender: Mutex<mpsc::Sender<Request>>,
+    public_keys: Vec<PublicKey>,
+}
+
+impl Hsm {
+    /// Finds the keys held by `backend` and starts `sessions` workers to sign with them.
+    pub fn start<B: HsmBackend>(
+        backend: B,
+        sessions: usize,
+        log: slog::Logger,
+    ) -> Result<Arc<Self>, Error> {
+        let mut session = backend.open_session()?;
+        let public_keys = backend.public_keys(&mut session)?;
+        info!(log, "Connected to HSM"; "keys" => public_keys.len(), "sessions" => sessions);
+
+        let backend = Arc::new(backend);
+        let (sender, receiver) = mpsc::channel();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let mut first_session = Some(session);
+        for _ in 0..sessions.max(1) {
+            let backend = backend.clone();
+            let receiver = receiver.clone();
+            let session = first_session.take();
+            let log = log.clone();
+            thread::spawn(move || work(&*backend, session, &receiver, &log));
+        }
+
+        Ok(Arc::new(Self {
+            sender: Mutex::new(sender),
+            public_keys,
+        }))
+    }
+
+    /// Returns a signer for each key held by the HSM.
+    pub fn signers(hsm: &Arc<Self>) -> Vec<HsmSigner> {
+        hsm.public_keys
+            .iter()
+            .map(|validator| HsmSigner {
+                validator: validator.clone(),
+                hsm: hsm.clone(),
+            })
+            .collect()
+    }
+
+    fn sign(&self, validator: &PublicKey, message: &[u8], domain: u64) -> SignatureFuture {
+        let (reply, response) = oneshot::channel();
+        let request = Request {
+            validator: validator.clone(),
+            message: message.to_vec(),
+            domain,
+            reply,
+        };
+        let sent = self
+            .sender
+            .lock()
+            .map_err(|_| ())
+            .and_then(|sender| sender.send(request).map_err(|_| ()));
+        if sent.is_err() {
+            return Box::new(future::err(()));
+        }
+
+        Box::new(
+            response
+                .map_err(|_| ())
+                .and_then(|signature| signature.map_err(|_| ())),
+        )
+    }
+}
+
+/// Signs each request taken from `receiver` until the `Hsm` is dropped, re-opening the session
+/// whenever it fails.
+fn work<B: HsmBackend>(
+    backend: &B,
+    mut session: Option<B::Session>,
+    receiver: &Mutex<mpsc::Receiver<Request>>,
+    log: &slog::Logger,
+) {
+    loop {
+        let request = match receiver.lock().map(|receiver| receiver.recv()) {
+            Ok(Ok(request)) => request,
+            _ => return,
+        };
+
+        let signature = match session.take().map_or_else(|| backend.open_session(), Ok) {
+            Ok(mut open) => {
+                let signature = backend.sign(
+                    &mut open,
+                    &request.validator,
+                    &request.message,
+                    request.domain,
+                );
+                match &signature {
+                    Err(Error::Session(_)) => backend.close_session(open),
+                    _ => session = Some(open),
+                }
+                signature
+            }
+            Err(e) => {
+                warn!(log, "Unable to open an HSM session"; "error" => e.to_string());
+                Err(e)
+            }
+        };
+
+        let signature = signature.and_then(|signature| {
+            if signature.verify(&request.message, request.domain, &request.validator) {
+                Ok(signature)
+            } else {
+                Err(Error::InvalidSignature(request.validator.as_hex_string()))
+            }
+        });
+        if let Err(e) = &signature {
+            error!(
+                log,
+                "HSM signing failed";
+                "validator" => request.validator.as_hex_string(),
+                "error" => e.to_string(),
+            );
+        }
+        // The production which requested the signature may have been cancelled.
+        let _ = request.reply.send(signature);
+    }
+}
+
+/// Signs as a single validator whose key is held in an HSM.
+#[derive(Clone)]
+pub struct HsmSigner {
+    validator: PublicKey,
+    hsm: Arc<Hsm>,
+}
+
+impl Signer for HsmSigner {
+    fn sign_message(&self, message: &[u8], domain: u64) -> SignatureFuture {
+        self.hsm.sign(&self.validator, message, domain)
+    }
+
+    fn to_public(&self) -> PublicKey {
+        self.validator.clone()
+    }
+}
+
+impl fmt::Display for HsmSigner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.validator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use slog::o;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use types::Keypair;
+
+    /// An HSM holding `keypairs`, whose sessions fail after `signatures_per_session` signatures.
+    struct TestBackend {
+        keypairs: Vec<Keypair>,
+        signatures_per_session: usize,
+        sessions_opened: Arc<AtomicUsize>,
+    }
+
+    impl HsmBackend for TestBackend {
+        type Session = usize;
+
+        fn open_session(&self) -> Result<usize, Error> {
+            self.sessions_opened.fetch_add(1, Ordering::SeqCst);
+            Ok(0)
+        }
+
+        fn public_keys(&self, _session: &mut usize) -> Result<Vec<PublicKey>, Error> {
+            Ok(self
+                .keypairs
+                .iter()
+                .map(|keypair| keypair.pk.clone())
+                .collect())
+        }
+
+        fn sign(
+            &self,
+            session: &mut usize,
+            validator: &PublicKey,
+            message: &[u8],
+            domain: u64,
+        ) -> Result<Signature, Error> {
+            if *session >= self.signatures_per_session {
+                return Err(Error::Session("session closed".to_string()));
+            }
+            *session += 1;
+            self.keypairs
+                .iter()
+                .find(|keypair| keypair.pk == *validator)
+                .map(|keypair| Signature::new(message, domain, &keypair.sk))
+                .ok_or_else(|| Error::UnknownKey(validator.as_hex_string()))
+        }
+    }
+
+    #[test]
+    fn signs_with_each_key_and_reopens_failed_sessions() {
+        let keypairs: Vec<_> = (0..2).map(|_| Keypair::random()).collect();
+        let sessions_opened = Arc::new(AtomicUsize::new(0));
+        let backend = TestBackend {
+            keypairs: keypairs.clone(),
+            signatures_per_session: 2,
+            sessions_opened: sessions_opened.clone(),
+        };
+        let log = slog::Logger::root(slog::Discard, o!());
+        let hsm = Hsm::start(backend, 1, log).expect("should start");
+        let signers = Hsm::signers(&hsm);
+        assert_eq!(signers.len(), 2);
+        assert_eq!(signers[0].to_public(), keypairs[0].pk);
+
+        let message = [7; 32];
+        let signed: Vec<_> = (0..5)
+            .map(|i| signers[i % 2].sign_message(&message, 3).wait())
+            .collect();
+        let expected = |i: usize| Ok(Signature::new(&message, 3, &keypairs[i % 2].sk));
+        // The third signature fails as its session has closed, and the next opens a new session.
+        assert_eq!(
+            signed,
+            vec![expected(0), expected(1), Err(()), expected(3), expected(4)]
+        );
+        assert_eq!(sessions_opened.load(Ordering::SeqCst), 2);
+    }
+}
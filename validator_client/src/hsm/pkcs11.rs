@@ -0,0 +1,245 @@
+//! An HSM reached through its PKCS#11 module.
+//!
+//! The BLS keys are the private key objects on the token whose `CKA_ID` is the compressed public
+//! key (48 bytes) of a validator. Other keys on the token are ignored.
+//!
+//! PKCS#11 logs in to the token rather than to a session, so only the first session opened logs
+//! in; the login of each later session returns `CKR_USER_ALREADY_LOGGED_IN`, which is accepted.
+use super::{Error, HsmBackend};
+use pkcs11::errors::Error as Pkcs11Error;
+use pkcs11::types::{
+    CKA_CLASS, CKA_ID, CKF_SERIAL_SESSION, CKO_PRIVATE_KEY, CKR_DEVICE_REMOVED, CKR_OK,
+    CKR_SESSION_CLOSED, CKR_SESSION_HANDLE_INVALID, CKR_TOKEN_NOT_PRESENT,
+    CKR_USER_ALREADY_LOGGED_IN, CKR_USER_NOT_LOGGED_IN, CKU_USER, CK_ATTRIBUTE, CK_MECHANISM,
+    CK_OBJECT_HANDLE, CK_SESSION_HANDLE, CK_SLOT_ID, CK_ULONG, CK_VOID_PTR,
+};
+use pkcs11::Ctx;
+use ssz::Encode;
+use std::collections::HashMap;
+use std::path::Path;
+use std::ptr;
+use types::{PublicKey, Signature};
+
+/// The length of a compressed BLS public key, and so of the `CKA_ID` of a BLS key.
+const PUBLIC_KEY_LEN: usize = 48;
+
+/// The maximum number of objects found at once.
+const FIND_BATCH: usize = 64;
+
+/// A logged in session, and the keys found through it.
+pub struct Session {
+    handle: CK_SESSION_HANDLE,
+    keys: HashMap<Vec<u8>, CK_OBJECT_HANDLE>,
+}
+
+/// The PKCS#11 module of an HSM, logged in to the token of a single slot.
+pub struct Pkcs11Backend {
+    ctx: Ctx,
+    slot: CK_SLOT_ID,
+    pin: String,
+    mechanism: u64,
+}
+
+impl Pkcs11Backend {
+    /// Loads the module at `path`, to sign with `mechanism` on the token in `slot` (or the first
+    /// slot with a token).
+    pub fn new(path: &Path, slot: Option<u64>, pin: String, mechanism: u64) -> Result<Self, Error> {
+        let ctx = Ctx::new_and_initialize(path).map_err(|e| Error::Module {
+            path: path.to_path_buf(),
+            reason: format!("{:?}", e),
+        })?;
+        let slots = ctx
+            .get_slot_list(true)
+            .map_err(|e| Error::Session(format!("{:?}", e)))?;
+        let slot = match slot {
+            Some(slot) => slots.into_iter().find(|s| u64::from(*s) == slot),
+            None => slots.into_iter().next(),
+        }
+        .ok_or_else(|| Error::NoToken(slot))?;
+
+        Ok(Self {
+            ctx,
+            slot,
+            pin,
+            mechanism,
+        })
+    }
+
+    /// Returns the private key objects with the given `CKA_ID`, or all private keys if `None`.
+    fn find_keys(
+        &self,
+        session: CK_SESSION_HANDLE,
+        id: Option<&[u8]>,
+    ) -> Result<Vec<CK_OBJECT_HANDLE>, Pkcs11Error> {
+        let mut template = vec![CK_ATTRIBUTE::new(CKA_CLASS).with_ck_ulong(&CKO_PRIVATE_KEY)];
+        if let Some(id) = id {
+            template.push(CK_ATTRIBUTE::new(CKA_ID).with_bytes(id));
+        }
+
+        self.ctx.find_objects_init(session, &template)?;
+        let mut objects = vec![];
+        let found = loop {
+            match self.ctx.find_objects(session, FIND_BATCH) {
+                Ok(batch) if batch.is_empty() => break Ok(()),
+                Ok(batch) => objects.extend(batch),
+                Err(e) => break Err(e),
+            }
+        };
+        self.ctx.find_objects_final(session)?;
+        found.map(|()| objects)
+    }
+
+    /// Returns the `CKA_ID` of `key`, if it is the length of a public key.
+    fn key_id(&self, session: CK_SESSION_HANDLE, key: CK_OBJECT_HANDLE) -> Option<Vec<u8>> {
+        let mut id = vec![0; PUBLIC_KEY_LEN];
+        // The module writes the value through `pValue`, so it must point to a mutable buffer.
+        let mut template = vec![CK_ATTRIBUTE {
+            attrType: CKA_ID,
+            pValue: id.as_mut_ptr() as CK_VOID_PTR,
+            ulValueLen: id.len() as CK_ULONG,
+        }];
+        let (rv, template) = self
+            .ctx
+            .get_attribute_value(session, key, &mut template)
+            .ok()?;
+
+        // A longer id leaves `CKR_BUFFER_TOO_SMALL` and a shorter one a shorter `ulValueLen`.
+        if rv == CKR_OK && template[0].ulValueLen as usize == PUBLIC_KEY_LEN {
+            Some(id)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the key of the validator with the compressed public key `id`.
+    fn key(&self, session: &mut Session, id: &[u8]) -> Result<CK_OBJECT_HANDLE, Error> {
+        if let Some(key) = session.keys.get(id) {
+            return Ok(*key);
+        }
+
+        let key = self
+            .find_keys(session.handle, Some(id))
+            .map_err(to_error)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| Error::UnknownKey(hex::encode(id)))?;
+        session.keys.insert(id.to_vec(), key);
+        Ok(key)
+    }
+}
+
+impl HsmBackend for Pkcs11Backend {
+    type Session = Session;
+
+    fn open_session(&self) -> Result<Session, Error> {
+        let handle = self
+            .ctx
+            .open_session(self.slot, CKF_SERIAL_SESSION, None, None)
+            .map_err(|e| Error::Session(format!("{:?}", e)))?;
+        if let Err(e) = logged_in(self.ctx.login(handle, CKU_USER, Some(&self.pin))) {
+            let _ = self.ctx.close_session(handle);
+            return Err(Error::Session(format!("unable to log in: {:?}", e)));
+        }
+
+        Ok(Session {
+            handle,
+            keys: HashMap::new(),
+        })
+    }
+
+    fn close_session(&self, session: Session) {
+        let _ = self.ctx.close_session(session.handle);
+    }
+
+    fn public_keys(&self, session: &mut Session) -> Result<Vec<PublicKey>, Error> {
+        let keys = self.find_keys(session.handle, None).map_err(to_error)?;
+
+        Ok(keys
+            .into_iter()
+            .filter_map(|key| {
+                let id = self.key_id(session.handle, key)?;
+                let public_key = PublicKey::from_bytes(&id).ok()?;
+                session.keys.insert(id, key);
+                Some(public_key)
+            })
+            .collect())
+    }
+
+    fn sign(
+        &self,
+        session: &mut Session,
+        validator: &PublicKey,
+        message: &[u8],
+        domain: u64,
+    ) -> Result<Signature, Error> {
+        let key = self.key(session, &validator.as_ssz_bytes())?;
+        let mechanism = CK_MECHANISM {
+            mechanism: self.mechanism as _,
+            pParameter: ptr::null_mut(),
+            ulParameterLen: 0,
+        };
+        let mut data = message.to_vec();
+        data.extend_from_slice(&domain.to_le_bytes());
+
+        self.ctx
+            .sign_init(session.handle, &mechanism, key)
+            .map_err(to_error)?;
+        let signature = self.ctx.sign(session.handle, &data).map_err(to_error)?;
+        Signature::from_bytes(&signature).map_err(|e| {
+            Error::Sign(format!(
+                "the HSM returned an undecodable signature: {:?}",
+                e
+            ))
+        })
+    }
+}
+
+/// Accepts the login of a session to a token which an earlier session has already logged in to.
+fn logged_in(login: Result<(), Pkcs11Error>) -> Result<(), Pkcs11Error> {
+    match login {
+        Err(Pkcs11Error::Pkcs11(rv)) if rv == CKR_USER_ALREADY_LOGGED_IN => Ok(()),
+        login => login,
+    }
+}
+
+/// Reports errors which end the session as `Error::Session`, so that a new session is opened.
+fn to_error(e: Pkcs11Error) -> Error {
+    match e {
+        Pkcs11Error::Pkcs11(rv)
+            if rv == CKR_SESSION_HANDLE_INVALID
+                || rv == CKR_SESSION_CLOSED
+                || rv == CKR_USER_NOT_LOGGED_IN
+                || rv == CKR_DEVICE_REMOVED
+                || rv == CKR_TOKEN_NOT_PRESENT =>
+        {
+            Error::Session(format!("{:?}", e))
+        }
+        e => Error::Sign(format!("{:?}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hsm::DEFAULT_SESSIONS;
+    use pkcs11::types::CKR_PIN_INCORRECT;
+
+    #[test]
+    fn every_session_is_logged_in() {
+        // The token is only logged in to by the first session.
+        let mut token_logged_in = false;
+        let mut login = || {
+            if token_logged_in {
+                Err(Pkcs11Error::Pkcs11(CKR_USER_ALREADY_LOGGED_IN))
+            } else {
+                token_logged_in = true;
+                Ok(())
+            }
+        };
+
+        for _ in 0..DEFAULT_SESSIONS {
+            assert!(logged_in(login()).is_ok());
+        }
+        assert!(logged_in(Err(Pkcs11Error::Pkcs11(CKR_PIN_INCORRECT))).is_err());
+    }
+}
@@ -0,0 +1,21 @@
+//! A YubiHSM 2, reached through its PKCS#11 module (`yubihsm_pkcs11`), which connects to the
+//! `yubihsm-connector` named in the module's configuration file (`YUBIHSM_PKCS11_CONF`).
+//!
+//! The module logs in with the id of an authentication key followed by its password, rather than
+//! a PIN.
+
+/// The PIN with which the YubiHSM module logs in with authentication key `auth_key_id`.
+pub fn pin(auth_key_id: u16, password: &str) -> String {
+    format!("{:04x}{}", auth_key_id, password)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefixes_the_auth_key_id() {
+        assert_eq!(pin(1, "password"), "0001password");
+        assert_eq!(pin(0x1a2b, "pw"), "1a2bpw");
+    }
+}
@@ -0,0 +1,660 @@
+//! Monitoring of the inclusion of attestations in the canonical chain.
+//!
+//! Each attestation produced by a validator is tracked until it is found in a canonical block or
+//! its inclusion window (the `SLOTS_PER_EPOCH` slots after its slot) has passed. Each slot, the
+//! blocks produced since the previous check are requested from the beacon node and searched for
+//! the tracked attestations.
+//!
+//! The effectiveness of an included attestation is the ratio of the earliest possible inclusion
+//! delay (the delay to the first block in which it could have been included) to its actual delay,
+//! so that an attestation is not penalised for skipped slots. A missed attestation has an
+//! effectiveness of zero. The effectiveness of a validator is the mean over all of its tracked
+//! attestations.
+use crate::attestation_producer::{BeaconNodeAttestation, BlockAttestations};
+use crate::block_producer::BeaconNodeError;
+use futures::{future, stream, Future, Stream};
+use serde_derive::Serialize;
+use slog::{debug, info, warn};
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use types::{AttestationDuty, EthSpec, Hash256, PublicKey, Slot};
+
+/// The maximum number of blocks requested from the beacon node in a single check, so that a
+/// backlog (e.g., after the beacon node was unavailable) is worked through over several slots.
+const MAX_BLOCKS_PER_CHECK: u64 = 16;
+
+/// The number of recent misses recorded for each validator.
+const MAX_RECENT_MISSES: usize = 16;
+
+/// The reason an attestation was not included.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MissReason {
+    /// Blocks were produced within the inclusion window, but none included the attestation (e.g.,
+    /// it was published too late or not propagated).
+    NotIncluded,
+    /// Every slot of the inclusion window was skipped.
+    NoBlocks,
+}
+
+/// An attestation which was not included.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct Miss {
+    pub slot: Slot,
+    pub reason: MissReason,
+}
+
+/// The fate of a tracked attestation.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Inclusion {
+    Included {
+        /// The number of slots between the attestation and the block which included it.
+        delay: u64,
+        /// The number of slots between the attestation and the first block which could have
+        /// included it.
+        optimal_delay: u64,
+        /// `false` if the attestation voted for a block which is not canonical at its slot, or
+        /// `None` if the canonical block is unknown.
+        correct_head: Option<bool>,
+    },
+    Missed(MissReason),
+}
+
+/// The fate of an attestation by `validator` at `slot`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Outcome {
+    pub validator: PublicKey,
+    pub slot: Slot,
+    pub inclusion: Inclusion,
+}
+
+/// The inclusion record of a single validator.
+#[derive(Debug, PartialEq, Clone, Default, Serialize)]
+pub struct Effectiveness {
+    pub included: u64,
+    pub missed: u64,
+    /// The number of included attestations which voted for a non-canonical head.
+    pub incorrect_head: u64,
+    /// The mean inclusion delay of included attestations, in slots.
+    pub mean_inclusion_delay: f64,
+    /// The mean effectiveness of all attestations, between `0` and `1`.
+    pub effectiveness: f64,
+    /// The most recent misses, oldest first.
+    pub recent_misses: VecDeque<Miss>,
+    #[serde(skip)]
+    total_delay: u64,
+    #[serde(skip)]
+    total_score: f64,
+}
+
+impl Effectiveness {
+    fn record(&mut self, slot: Slot, inclusion: &Inclusion) {
+        match inclusion {
+            Inclusion::Included {
+                delay,
+                optimal_delay,
+                correct_head,
+            } => {
+                self.included += 1;
+                self.total_delay += delay;
+                self.total_score += *optimal_delay as f64 / (*delay).max(1) as f64;
+                if *correct_head == Some(false) {
+                    self.incorrect_head += 1;
+                }
+            }
+            Inclusion::Missed(reason) => {
+                self.missed += 1;
+                self.recent_misses.push_back(Miss {
+                    slot,
+                    reason: *reason,
+                });
+                if self.recent_misses.len() > MAX_RECENT_MISSES {
+                    self.recent_misses.pop_front();
+                }
+            }
+        }
+
+        if self.included > 0 {
+            self.mean_inclusion_delay = self.total_delay as f64 / self.included as f64;
+        }
+        self.effectiveness = self.total_score / (self.included + self.missed) as f64;
+    }
+}
+
+/// The inclusion record of a validator, as exported.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct ValidatorReport {
+    pub pubkey: PublicKey,
+    #[serde(flatten)]
+    pub effectiveness: Effectiveness,
+}
+
+/// The inclusion record of every validator, written to the report file after each check.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct InclusionReport {
+    /// The latest slot whose block has been checked, if any.
+    pub checked_slot: Option<Slot>,
+    pub validators: Vec<ValidatorReport>,
+}
+
+impl InclusionReport {
+    /// Writes the report to `path` via a temporary file, so that a reader never observes a
+    /// partially-written report.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let temp_path = path.with_extension("json.tmp");
+        let file = File::create(&temp_path)?;
+        serde_json::to_writer_pretty(file, self)?;
+        fs::rename(&temp_path, path)
+    }
+}
+
+/// An attestation which has been produced but not yet found or missed.
+struct Pending {
+    validator: PublicKey,
+    duty: AttestationDuty,
+    /// The delay to the first block in which the attestation could have been included, once it
+    /// has been checked.
+    optimal_delay: Option<u64>,
+}
+
+/// Matches tracked attestations against the canonical blocks which follow them.
+pub struct Tracker {
+    slots_per_epoch: u64,
+    min_inclusion_delay: u64,
+    /// The next slot whose block is to be checked.
+    next_slot: Slot,
+    pending: Vec<Pending>,
+    /// The canonical block root at the slot of each pending attestation, once checked.
+    head_roots: HashMap<Slot, Hash256>,
+    validators: HashMap<PublicKey, Effectiveness>,
+}
+
+impl Tracker {
+    pub fn new(slots_per_epoch: u64, min_inclusion_delay: u64) -> Self {
+        Self {
+            slots_per_epoch,
+            min_inclusion_delay,
+            next_slot: Slot::new(0),
+            pending: vec![],
+            head_roots: HashMap::new(),
+            validators: HashMap::new(),
+        }
+    }
+
+    /// Starts tracking the attestation of `validator` for `duty`.
+    pub fn attested(&mut self, validator: PublicKey, duty: AttestationDuty) {
+        let tracked = self
+            .pending
+            .iter()
+            .any(|pending| pending.validator == validator && pending.duty.slot == duty.slot);
+        if !tracked {
+            self.pending.push(Pending {
+                validator,
+                duty,
+                optimal_delay: None,
+            });
+        }
+    }
+
+    /// Returns the range of slots whose blocks should be checked before `current_slot`, if any.
+    ///
+    /// Blocks earlier than every pending attestation are not checked.
+    pub fn slots_to_check(&self, current_slot: Slot) -> Option<(Slot, Slot)> {
+        let earliest = self.pending.iter().map(|pending| pending.duty.slot).min()?;
+        let start = std::cmp::max(self.next_slot, earliest);
+        let end = std::cmp::min(current_slot, start + MAX_BLOCKS_PER_CHECK);
+        if start < end {
+            Some((start, end))
+        } else {
+            None
+        }
+    }
+
+    /// Stops tracking attestations whose inclusion window ended more than an epoch before
+    /// `current_slot` without being checked (e.g., because the beacon node was unavailable).
+    ///
+    /// Returns the number of attestations which are no longer tracked.
+    pub fn abandon_stale(&mut self, current_slot: Slot) -> usize {
+        let horizon = 2 * self.slots_per_epoch;
+        let before = self.pending.len();
+        self.pending
+            .retain(|pending| pending.duty.slot + horizon >= current_slot);
+        before - self.pending.len()
+    }
+
+    /// Searches the canonical block at `slot` for pending attestations, returning the fate of
+    /// each attestation which was included or whose inclusion window has passed.
+    pub fn process_block<E: EthSpec>(
+        &mut self,
+        slot: Slot,
+        block: &BlockAttestations<E>,
+    ) -> Vec<Outcome> {
+        if self.pending.iter().any(|pending| pending.duty.slot == slot) {
+            self.head_roots.insert(slot, block.block_root);
+        }
+
+        let mut outcomes = vec![];
+        let slots_per_epoch = self.slots_per_epoch;
+        let min_inclusion_delay = self.min_inclusion_delay;
+        let head_roots = &self.head_roots;
+
+        self.pending.retain(|pending| {
+            let duty = &pending.duty;
+            let window_end = duty.slot + slots_per_epoch;
+            if slot < duty.slot + min_inclusion_delay {
+                return true;
+            }
+
+            if !block.skipped && slot <= window_end {
+                let delay = (slot - duty.slot).as_u64();
+                let optimal_delay = pending.optimal_delay.unwrap_or(delay);
+                let included = block.attestations.iter().find(|attestation| {
+                    attestation.data.target.epoch == duty.slot.epoch(slots_per_epoch)
+                        && attestation.data.crosslink.shard == duty.shard
+                        && attestation.aggregation_bits.get(duty.committee_index) == Ok(true)
+                });
+
+                if let Some(attestation) = included {
+                    outcomes.push(Outcome {
+                        validator: pending.validator.clone(),
+                        slot: duty.slot,
+                        inclusion: Inclusion::Included {
+                            delay,
+                            optimal_delay,
+                            correct_head: head_roots
+                                .get(&duty.slot)
+                                .map(|root| *root == attestation.data.beacon_block_root),
+                        },
+                    });
+                    return false;
+                }
+            }
+
+            if slot >= window_end {
+                let reason = if pending.optimal_delay.is_some() || !block.skipped {
+                    MissReason::NotIncluded
+                } else {
+                    MissReason::NoBlocks
+                };
+                outcomes.push(Outcome {
+                    validator: pending.validator.clone(),
+                    slot: duty.slot,
+                    inclusion: Inclusion::Missed(reason),
+                });
+                return false;
+            }
+
+            true
+        });
+
+        // The first block after the minimum delay bounds the best possible inclusion delay.
+        if !block.skipped {
+            for pending in &mut self.pending {
+                if pending.optimal_delay.is_none()
+                    && slot >= pending.duty.slot + min_inclusion_delay
+                {
+                    pending.optimal_delay = Some((slot - pending.duty.slot).as_u64());
+                }
+            }
+        }
+
+        for outcome in &outcomes {
+            self.validators
+                .entry(outcome.validator.clone())
+                .or_default()
+                .record(outcome.slot, &outcome.inclusion);
+        }
+
+        let pending = &self.pending;
+        self.head_roots
+            .retain(|root_slot, _| pending.iter().any(|p| p.duty.slot == *root_slot));
+        self.next_slot = std::cmp::max(self.next_slot, slot + 1);
+
+        outcomes
+    }
+
+    /// Returns the inclusion record of every validator, ordered by public key.
+    pub fn report(&self) -> InclusionReport {
+        let mut validators = self
+            .validators
+            .iter()
+            .map(|(pubkey, effectiveness)| ValidatorReport {
+                pubkey: pubkey.clone(),
+                effectiveness: effectiveness.clone(),
+            })
+            .collect::<Vec<_>>();
+        validators.sort_by_key(|validator| validator.pubkey.as_hex_string());
+
+        InclusionReport {
+            checked_slot: self.next_slot.as_u64().checked_sub(1).map(Slot::new),
+            validators,
+        }
+    }
+}
+
+/// Monitors the inclusion of the attestations of all validators, logging misses and writing an
+/// `InclusionReport` to `report_file`, if any.
+pub struct InclusionMonitor {
+    tracker: Mutex<Tracker>,
+    report_file: Option<PathBuf>,
+    log: slog::Logger,
+}
+
+impl InclusionMonitor {
+    pub fn new(
+        slots_per_epoch: u64,
+        min_inclusion_delay: u64,
+        report_file: Option<PathBuf>,
+        log: slog::Logger,
+    ) -> Self {
+        Self {
+            tracker: Mutex::new(Tracker::new(slots_per_epoch, min_inclusion_delay)),
+            report_file,
+            log,
+        }
+    }
+
+    /// Starts tracking the attestation of `validator` for `duty`.
+    pub fn attested(&self, validator: PublicKey, duty: AttestationDuty) {
+        if let Ok(mut tracker) = self.tracker.lock() {
+            tracker.attested(validator, duty);
+        }
+    }
+
+    /// Returns a future which checks the blocks produced before `current_slot` for tracked
+    /// attestations.
+    ///
+    /// Blocks are requested in order, stopping at the head of the beacon node, so a slot is only
+    /// checked once it is known. A failed request leaves the remaining blocks for the next check.
+    pub fn check<A, E>(
+        monitor: Arc<Self>,
+        beacon_node: Arc<A>,
+        current_slot: Slot,
+    ) -> Box<dyn Future<Item = (), Error = ()> + Send>
+    where
+        A: BeaconNodeAttestation + 'static,
+        E: EthSpec,
+    {
+        let range = match monitor.tracker.lock() {
+            Ok(mut tracker) => {
+                let abandoned = tracker.abandon_stale(current_slot);
+                if abandoned > 0 {
+                    debug!(
+                        monitor.log,
+                        "Unable to check the inclusion of attestations";
+                        "attestations" => abandoned,
+                    );
+                }
+                tracker.slots_to_check(current_slot)
+            }
+            Err(_) => None,
+        };
+        let (start, end) = match range {
+            Some(range) => range,
+            None => return Box::new(future::ok(())),
+        };
+
+        let process_monitor = monitor.clone();
+        Box::new(
+            stream::iter_ok::<_, BeaconNodeError>(start.as_u64()..end.as_u64())
+                .and_then(move |slot| {
+                    let slot = Slot::new(slot);
+                    beacon_node
+                        .block_attestations::<E>(slot)
+                        .map(move |block| block.map(|block| (slot, block)))
+                })
+                .take_while(|block| Ok(block.is_some()))
+                .filter_map(|block| block)
+                .for_each(move |(slot, block)| {
+                    process_monitor.process_block(slot, &block);
+                    Ok(())
+                })
+                .then(move |result| -> Result<(), ()> {
+                    if let Err(e) = result {
+                        debug!(monitor.log, "Unable to check attestation inclusion"; "error" => e.to_string());
+                    }
+                    monitor.save_report();
+                    Ok(())
+                }),
+        )
+    }
+
+    fn process_block<E: EthSpec>(&self, slot: Slot, block: &BlockAttestations<E>) {
+        let outcomes = match self.tracker.lock() {
+            Ok(mut tracker) => tracker.process_block(slot, block),
+            Err(_) => return,
+        };
+
+        for outcome in outcomes {
+            let validator = format!("{}", outcome.validator);
+            let attestation_slot = outcome.slot.as_u64();
+            match outcome.inclusion {
+                Inclusion::Included {
+                    correct_head: Some(false),
+                    delay,
+                    ..
+                } => warn!(
+                    self.log,
+                    "Attestation included with an incorrect head vote";
+                    "validator" => validator,
+                    "slot" => attestation_slot,
+                    "delay" => delay,
+                ),
+                Inclusion::Included {
+                    delay,
+                    optimal_delay,
+                    ..
+                } => info!(
+                    self.log,
+                    "Attestation included";
+                    "validator" => validator,
+                    "slot" => attestation_slot,
+                    "delay" => delay,
+                    "optimal_delay" => optimal_delay,
+                ),
+                Inclusion::Missed(reason) => warn!(
+                    self.log,
+                    "Attestation missed";
+                    "validator" => validator,
+                    "slot" => attestation_slot,
+                    "reason" => format!("{:?}", reason),
+                ),
+            }
+        }
+    }
+
+    /// Writes the `InclusionReport` to the report file, if any.
+    pub fn save_report(&self) {
+        let path = match &self.report_file {
+            Some(path) => path,
+            None => return,
+        };
+        let report = match self.tracker.lock() {
+            Ok(tracker) => tracker.report(),
+            Err(_) => return,
+        };
+        if let Err(e) = report.save(path) {
+            warn!(self.log, "Unable to save inclusion report"; "path" => format!("{:?}", path), "error" => e.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use types::{
+        AggregateSignature, Attestation, AttestationData, BitList, Checkpoint, Crosslink, Epoch,
+        Keypair, MinimalEthSpec,
+    };
+
+    const SLOTS_PER_EPOCH: u64 = 8;
+
+    fn duty(slot: u64, committee_index: usize) -> AttestationDuty {
+        AttestationDuty {
+            slot: Slot::new(slot),
+            shard: 1,
+            committee_index,
+            committee_len: 4,
+        }
+    }
+
+    fn attestation(
+        duty: &AttestationDuty,
+        beacon_block_root: Hash256,
+    ) -> Attestation<MinimalEthSpec> {
+        let mut aggregation_bits =
+            BitList::with_capacity(duty.committee_len).expect("should create bits");
+        aggregation_bits
+            .set(duty.committee_index, true)
+            .expect("should set bit");
+
+        Attestation {
+            aggregation_bits,
+            data: AttestationData {
+                beacon_block_root,
+                source: Checkpoint::default(),
+                target: Checkpoint {
+                    epoch: Epoch::new(duty.slot.as_u64() / SLOTS_PER_EPOCH),
+                    root: Hash256::zero(),
+                },
+                crosslink: Crosslink {
+                    shard: duty.shard,
+                    ..Crosslink::default()
+                },
+            },
+            custody_bits: BitList::with_capacity(duty.committee_len).expect("should create bits"),
+            signature: AggregateSignature::new(),
+        }
+    }
+
+    fn block(
+        root: u64,
+        attestations: Vec<Attestation<MinimalEthSpec>>,
+    ) -> BlockAttestations<MinimalEthSpec> {
+        BlockAttestations {
+            block_root: Hash256::from_low_u64_be(root),
+            skipped: false,
+            attestations,
+        }
+    }
+
+    fn skipped(root: u64) -> BlockAttestations<MinimalEthSpec> {
+        BlockAttestations {
+            skipped: true,
+            ..block(root, vec![])
+        }
+    }
+
+    #[test]
+    fn scores_inclusion_delay_and_misses() {
+        let mut tracker = Tracker::new(SLOTS_PER_EPOCH, 1);
+        let on_time = Keypair::random().pk;
+        let late = Keypair::random().pk;
+        let missed = Keypair::random().pk;
+        for (validator, committee_index) in vec![(&on_time, 0), (&late, 1), (&missed, 2)] {
+            tracker.attested(validator.clone(), duty(10, committee_index));
+        }
+
+        assert_eq!(
+            tracker.slots_to_check(Slot::new(12)),
+            Some((Slot::new(10), Slot::new(12)))
+        );
+        assert!(tracker
+            .process_block(Slot::new(10), &block(10, vec![]))
+            .is_empty());
+        // Slot 11 is skipped, so inclusion at slot 12 is the best possible.
+        assert!(tracker
+            .process_block(Slot::new(11), &skipped(10))
+            .is_empty());
+
+        let root = Hash256::from_low_u64_be(10);
+        let outcomes = tracker.process_block(
+            Slot::new(12),
+            &block(12, vec![attestation(&duty(10, 0), root)]),
+        );
+        assert_eq!(
+            outcomes,
+            vec![Outcome {
+                validator: on_time.clone(),
+                slot: Slot::new(10),
+                inclusion: Inclusion::Included {
+                    delay: 2,
+                    optimal_delay: 2,
+                    correct_head: Some(true),
+                },
+            }]
+        );
+
+        let outcomes = tracker.process_block(
+            Slot::new(14),
+            &block(14, vec![attestation(&duty(10, 1), Hash256::zero())]),
+        );
+        assert_eq!(
+            outcomes[0].inclusion,
+            Inclusion::Included {
+                delay: 4,
+                optimal_delay: 2,
+                correct_head: Some(false),
+            }
+        );
+
+        // The inclusion window ends a full epoch after the attestation.
+        assert!(tracker
+            .process_block(Slot::new(17), &block(17, vec![]))
+            .is_empty());
+        let outcomes = tracker.process_block(Slot::new(18), &skipped(17));
+        assert_eq!(
+            outcomes,
+            vec![Outcome {
+                validator: missed.clone(),
+                slot: Slot::new(10),
+                inclusion: Inclusion::Missed(MissReason::NotIncluded),
+            }]
+        );
+        assert_eq!(tracker.slots_to_check(Slot::new(30)), None);
+
+        let report = tracker.report();
+        assert_eq!(report.checked_slot, Some(Slot::new(18)));
+        let effectiveness = |pubkey: &PublicKey| {
+            report
+                .validators
+                .iter()
+                .find(|validator| validator.pubkey == *pubkey)
+                .map(|validator| validator.effectiveness.clone())
+                .expect("should report validator")
+        };
+        assert_eq!(effectiveness(&on_time).effectiveness, 1.0);
+        assert_eq!(effectiveness(&late).effectiveness, 0.5);
+        assert_eq!(effectiveness(&late).incorrect_head, 1);
+        assert_eq!(effectiveness(&missed).effectiveness, 0.0);
+        assert_eq!(
+            effectiveness(&missed).recent_misses,
+            vec![Miss {
+                slot: Slot::new(10),
+                reason: MissReason::NotIncluded,
+            }]
+        );
+    }
+
+    #[test]
+    fn attributes_misses_to_skipped_slots() {
+        let mut tracker = Tracker::new(SLOTS_PER_EPOCH, 1);
+        let validator = Keypair::random().pk;
+        tracker.attested(validator.clone(), duty(3, 0));
+
+        let outcomes = (3..=11)
+            .flat_map(|slot| tracker.process_block(Slot::new(slot), &skipped(2)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            outcomes,
+            vec![Outcome {
+                validator,
+                slot: Slot::new(3),
+                inclusion: Inclusion::Missed(MissReason::NoBlocks),
+            }]
+        );
+    }
+}
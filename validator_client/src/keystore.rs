@@ -0,0 +1,338 @@
+//! Password-encrypted validator secret keys, stored in the JSON format of
+//! [EIP-2335](https://eips.ethereum.org/EIPS/eip-2335).
+//!
+//! A keystore may be stored as `voting-keystore.json` in a validator directory, in place of the
+//! unencrypted `private.key`.
+use aes_ctr::stream_cipher::generic_array::GenericArray;
+use aes_ctr::stream_cipher::{NewStreamCipher, SyncStreamCipher};
+use aes_ctr::Aes128Ctr;
+use bls::{Keypair, PublicKey, SecretKey, BLS_SECRET_KEY_BYTE_SIZE};
+use hmac::Hmac;
+use rand::Rng;
+use serde_derive::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use ssz::{Decode, Encode};
+use std::fs::File;
+use std::path::Path;
+use unicode_normalization::UnicodeNormalization;
+use uuid::Uuid;
+
+/// The name of the keystore file within a validator directory.
+pub const KEYSTORE_FILENAME: &str = "voting-keystore.json";
+
+/// The version of EIP-2335 keystores.
+const VERSION: u32 = 4;
+/// The length of the key derived from the password.
+const DKLEN: usize = 32;
+/// The length of the stored secret key (i.e., the secret key without leading padding).
+const SECRET_BYTES: usize = 32;
+const SALT_BYTES: usize = 32;
+const IV_BYTES: usize = 16;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The password does not decrypt the keystore.
+    InvalidPassword,
+    /// The keystore uses a function which is not supported.
+    UnsupportedFunction(String),
+    /// The parameters of a function are invalid.
+    InvalidParams(String),
+    /// The decrypted secret key is invalid, or does not match the public key of the keystore.
+    InvalidSecretKey,
+    /// The public key of the keystore is invalid.
+    InvalidPublicKey,
+    /// The keystore is not valid JSON.
+    InvalidJson(serde_json::Error),
+    /// The keystore file could not be read or written.
+    Io(std::io::Error),
+}
+
+/// The key derivation function used to obtain the encryption key from the password.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Kdf {
+    Scrypt { n: u32, r: u32, p: u32 },
+    Pbkdf2 { c: u32 },
+}
+
+impl Default for Kdf {
+    /// The scrypt parameters recommended by EIP-2335.
+    fn default() -> Self {
+        Kdf::Scrypt {
+            n: 262_144,
+            r: 8,
+            p: 1,
+        }
+    }
+}
+
+/// A keystore, as per EIP-2335.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Keystore {
+    crypto: Crypto,
+    pub description: String,
+    /// The hex-encoded public key, without a `0x` prefix.
+    pub pubkey: String,
+    /// The EIP-2334 path from which the key was derived, if any.
+    pub path: String,
+    pub uuid: String,
+    pub version: u32,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+struct Crypto {
+    kdf: Module<serde_json::Value>,
+    checksum: Module<serde_json::Value>,
+    cipher: Module<CipherParams>,
+}
+
+/// A cryptographic function, its parameters and its output or input.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+struct Module<P> {
+    function: String,
+    params: P,
+    message: String,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+struct CipherParams {
+    iv: String,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+struct ScryptParams {
+    dklen: usize,
+    n: u32,
+    r: u32,
+    p: u32,
+    salt: String,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+struct Pbkdf2Params {
+    dklen: usize,
+    c: u32,
+    prf: String,
+    salt: String,
+}
+
+impl Keystore {
+    /// Encrypt the secret key of `keypair` with `password`.
+    ///
+    /// `path` is the EIP-2334 path of the key, or an empty string if it was not derived.
+    pub fn encrypt(keypair: &Keypair, password: &str, path: &str, kdf: Kdf) -> Result<Self, Error> {
+        let mut rng = rand::thread_rng();
+        let salt: [u8; SALT_BYTES] = rng.gen();
+        let iv: [u8; IV_BYTES] = rng.gen();
+
+        let kdf = match kdf {
+            Kdf::Scrypt { n, r, p } => Module {
+                function: "scrypt".to_string(),
+                params: json(&ScryptParams {
+                    dklen: DKLEN,
+                    n,
+                    r,
+                    p,
+                    salt: hex::encode(salt),
+                }),
+                message: String::new(),
+            },
+            Kdf::Pbkdf2 { c } => Module {
+                function: "pbkdf2".to_string(),
+                params: json(&Pbkdf2Params {
+                    dklen: DKLEN,
+                    c,
+                    prf: "hmac-sha256".to_string(),
+                    salt: hex::encode(salt),
+                }),
+                message: String::new(),
+            },
+        };
+        let decryption_key = derive_key(&kdf, password)?;
+
+        let mut secret =
+            keypair.sk.as_ssz_bytes()[BLS_SECRET_KEY_BYTE_SIZE - SECRET_BYTES..].to_vec();
+        aes_128_ctr(&decryption_key, &iv, &mut secret);
+
+        Ok(Self {
+            crypto: Crypto {
+                kdf,
+                checksum: Module {
+                    function: "sha256".to_string(),
+                    params: serde_json::Value::Object(Default::default()),
+                    message: hex::encode(checksum(&decryption_key, &secret)),
+                },
+                cipher: Module {
+                    function: "aes-128-ctr".to_string(),
+                    params: CipherParams {
+                        iv: hex::encode(iv),
+                    },
+                    message: hex::encode(secret),
+                },
+            },
+            description: String::new(),
+            pubkey: hex::encode(keypair.pk.as_ssz_bytes()),
+            path: path.to_string(),
+            uuid: Uuid::new_v4().to_string(),
+            version: VERSION,
+        })
+    }
+
+    /// Decrypt the keypair with `password`.
+    pub fn decrypt(&self, password: &str) -> Result<Keypair, Error> {
+        if self.crypto.checksum.function != "sha256" {
+            return Err(Error::UnsupportedFunction(
+                self.crypto.checksum.function.clone(),
+            ));
+        }
+        if self.crypto.cipher.function != "aes-128-ctr" {
+            return Err(Error::UnsupportedFunction(
+                self.crypto.cipher.function.clone(),
+            ));
+        }
+
+        let decryption_key = derive_key(&self.crypto.kdf, password)?;
+        let mut secret = decode_hex(&self.crypto.cipher.message)?;
+        let iv = decode_hex(&self.crypto.cipher.params.iv)?;
+
+        if checksum(&decryption_key, &secret) != decode_hex(&self.crypto.checksum.message)? {
+            return Err(Error::InvalidPassword);
+        }
+        if secret.len() != SECRET_BYTES || iv.len() != IV_BYTES {
+            return Err(Error::InvalidParams(
+                "invalid secret or iv length".to_string(),
+            ));
+        }
+
+        aes_128_ctr(&decryption_key, &iv, &mut secret);
+
+        let mut padded = vec![0; BLS_SECRET_KEY_BYTE_SIZE - SECRET_BYTES];
+        padded.append(&mut secret);
+        let sk = SecretKey::from_bytes(&padded).map_err(|_| Error::InvalidSecretKey)?;
+        let pk = PublicKey::from_secret_key(&sk);
+
+        if pk != self.public_key()? {
+            return Err(Error::InvalidSecretKey);
+        }
+
+        Ok(Keypair { sk, pk })
+    }
+
+    /// The public key of the encrypted secret key.
+    pub fn public_key(&self) -> Result<PublicKey, Error> {
+        PublicKey::from_ssz_bytes(&decode_hex(&self.pubkey)?).map_err(|_| Error::InvalidPublicKey)
+    }
+
+    /// Read a keystore from the JSON file at `path`.
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        let file = File::open(path).map_err(Error::Io)?;
+        serde_json::from_reader(file).map_err(Error::InvalidJson)
+    }
+
+    /// Write `self` to a JSON file at `path`.
+    pub fn to_file(&self, path: &Path) -> Result<(), Error> {
+        let file = File::create(path).map_err(Error::Io)?;
+        serde_json::to_writer_pretty(file, self).map_err(Error::InvalidJson)
+    }
+}
+
+fn json<T: serde::Serialize>(params: &T) -> serde_json::Value {
+    serde_json::to_value(params).expect("params are always serializable")
+}
+
+fn decode_hex(string: &str) -> Result<Vec<u8>, Error> {
+    hex::decode(string).map_err(|e| Error::InvalidParams(format!("invalid hex: {:?}", e)))
+}
+
+/// Derive the decryption key from `password`, using the key derivation function of `kdf`.
+///
+/// As per EIP-2335, the password is NFKD normalized and stripped of control codes.
+fn derive_key(kdf: &Module<serde_json::Value>, password: &str) -> Result<Vec<u8>, Error> {
+    let password: String = password.nfkd().filter(|c| !c.is_control()).collect();
+    let invalid = |e: serde_json::Error| Error::InvalidParams(format!("{:?}", e));
+    let mut key = vec![0; DKLEN];
+
+    match kdf.function.as_str() {
+        "scrypt" => {
+            let params: ScryptParams =
+                serde_json::from_value(kdf.params.clone()).map_err(invalid)?;
+            if params.dklen != DKLEN || !params.n.is_power_of_two() {
+                return Err(Error::InvalidParams("invalid scrypt params".to_string()));
+            }
+            let scrypt_params =
+                scrypt::ScryptParams::new(params.n.trailing_zeros() as u8, params.r, params.p)
+                    .map_err(|e| Error::InvalidParams(format!("{:?}", e)))?;
+            scrypt::scrypt(
+                password.as_bytes(),
+                &decode_hex(&params.salt)?,
+                &scrypt_params,
+                &mut key,
+            )
+            .map_err(|e| Error::InvalidParams(format!("{:?}", e)))?;
+        }
+        "pbkdf2" => {
+            let params: Pbkdf2Params =
+                serde_json::from_value(kdf.params.clone()).map_err(invalid)?;
+            if params.dklen != DKLEN || params.prf != "hmac-sha256" {
+                return Err(Error::InvalidParams("invalid pbkdf2 params".to_string()));
+            }
+            pbkdf2::pbkdf2::<Hmac<Sha256>>(
+                password.as_bytes(),
+                &decode_hex(&params.salt)?,
+                params.c as usize,
+                &mut key,
+            );
+        }
+        function => return Err(Error::UnsupportedFunction(function.to_string())),
+    }
+
+    Ok(key)
+}
+
+/// The checksum of the encrypted `secret`, which verifies the password.
+fn checksum(decryption_key: &[u8], secret: &[u8]) -> Vec<u8> {
+    let mut preimage = decryption_key[16..32].to_vec();
+    preimage.extend_from_slice(secret);
+    Sha256::digest(&preimage).to_vec()
+}
+
+/// Encrypt or decrypt `data` in-place.
+fn aes_128_ctr(decryption_key: &[u8], iv: &[u8], data: &mut [u8]) {
+    Aes128Ctr::new(
+        GenericArray::from_slice(&decryption_key[0..16]),
+        GenericArray::from_slice(iv),
+    )
+    .apply_keystream(data);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encrypt_decrypt() {
+        let keypair = Keypair::random();
+        let kdf = Kdf::Pbkdf2 { c: 16 };
+        let keystore = Keystore::encrypt(&keypair, "p\u{0007}assword", "m/12381/3600/0/0/0", kdf)
+            .expect("should encrypt");
+
+        let json = serde_json::to_string(&keystore).expect("should encode");
+        let keystore: Keystore = serde_json::from_str(&json).expect("should decode");
+
+        assert_eq!(keystore.public_key().unwrap(), keypair.pk);
+        // Control codes are stripped from the password.
+        assert_eq!(keystore.decrypt("password").unwrap().sk, keypair.sk);
+        match keystore.decrypt("wrong password") {
+            Err(Error::InvalidPassword) => {}
+            other => panic!("expected InvalidPassword, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn scrypt() {
+        let keypair = Keypair::random();
+        let kdf = Kdf::Scrypt { n: 16, r: 8, p: 1 };
+        let keystore = Keystore::encrypt(&keypair, "password", "", kdf).expect("should encrypt");
+
+        assert_eq!(keystore.decrypt("password").unwrap().pk, keypair.pk);
+    }
+}
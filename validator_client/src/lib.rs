@@ -1,3 +1,48 @@
+pub mod api_conformance;
+mod attestation_producer;
+pub mod audit_log;
+mod beacon_api;
+pub mod block_latency;
+pub mod block_producer;
+pub mod broadcast;
+mod builder_registration;
+pub mod chain_identity;
+pub mod chain_split;
 pub mod config;
+pub mod datadir;
+pub mod datadir_lock;
+mod debug_api;
+mod duties;
+pub mod endpoint_scores;
+pub mod error;
+pub mod fee_recipient;
+pub mod gas_limit;
+pub mod health;
+pub mod hsm;
+pub mod inclusion;
+pub mod keystore;
+pub mod metrics_push;
+pub mod notifier;
+pub mod password;
+pub mod proposal_timing;
+pub mod report;
+pub mod scheduler;
+mod service;
+pub mod shutdown;
+pub mod signature_cache;
+pub mod signer;
+pub mod signing_lease;
+pub mod signing_log;
+#[cfg(test)]
+mod signing_roots;
+pub mod slasher_submit;
+pub mod slashing_drill;
+pub mod slashing_protection;
+pub mod slashing_watcher;
+mod slot_tracker;
+pub mod snapshot;
+pub mod validator_state;
+pub mod wss_checkpoint;
 
-pub use crate::config::Config;
+pub use crate::config::{Config, ConfigProblem};
+pub use crate::service::{Connector, Service};
@@ -1,27 +1,60 @@
-mod attestation_producer;
-mod block_producer;
-mod config;
-mod duties;
-pub mod error;
-mod service;
-mod signer;
-
-use crate::config::Config as ValidatorClientConfig;
-use crate::service::Service as ValidatorService;
-use clap::{App, Arg};
+use clap::{App, AppSettings, Arg, ArgGroup, ArgMatches, SubCommand};
 use eth2_config::{read_from_file, write_to_file, Eth2Config};
-use protos::services_grpc::ValidatorServiceClient;
+use protos::auth::Authenticated;
+use protos::services_grpc::{
+    AttestationServiceClient, SlashingServiceClient, ValidatorServiceClient,
+};
 use slog::{crit, error, info, o, warn, Drain, Level};
+use ssz::Decode;
 use std::fs;
-use std::path::PathBuf;
-use types::{InteropEthSpec, Keypair, MainnetEthSpec, MinimalEthSpec};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicI32, Ordering};
+use types::{ChainSpec, EthSpec, InteropEthSpec, MainnetEthSpec, MinimalEthSpec, PublicKey};
+use validator_client::api_conformance::{self, Status};
+use validator_client::audit_log::{self, AuditLog, AuditedSigner};
+use validator_client::block_producer::replay::{self, Replay};
+use validator_client::block_producer::BeaconBlockGrpcClient;
+use validator_client::broadcast::Broadcast;
+use validator_client::config::DEFAULT_PRIVATE_KEY_FILENAME;
+use validator_client::datadir;
+use validator_client::keystore::KEYSTORE_FILENAME;
+use validator_client::report;
+use validator_client::signature_cache::CachingSigner;
+use validator_client::signer::ValidatorSigner;
+use validator_client::slasher_submit::Evidence;
+use validator_client::slashing_drill;
+use validator_client::slashing_protection::DEFAULT_RETENTION_EPOCHS;
+use validator_client::snapshot::Snapshot;
+use validator_client::validator_state::ValidatorState;
+use validator_client::Config as ValidatorClientConfig;
+use validator_client::Connector;
+use validator_client::Service as ValidatorService;
 
 pub const DEFAULT_SPEC: &str = "minimal";
 pub const DEFAULT_DATA_DIR: &str = ".lighthouse-validator";
+pub const DEFAULT_SLASHING_DRILL_DIR: &str = "slashing-drill";
+pub const DEFAULT_REPORT_EPOCHS: &str = "4";
 pub const CLIENT_CONFIG_FILENAME: &str = "validator-client.toml";
-pub const ETH2_CONFIG_FILENAME: &str = "eth2-spec.toml";
+pub const SERVICE_NAME: &str = "lighthouse-validator-client";
+
+/// The signer used for validators: a keypair stored on disk or a key held in an HSM, with all
+/// signing audited and recent signatures cached.
+type AuditedValidatorSigner = AuditedSigner<CachingSigner<ValidatorSigner>>;
+
+/// The exit code of the process, set once the validator client has shut down.
+static EXIT_CODE: AtomicI32 = AtomicI32::new(0);
 
 fn main() {
+    system_service::start(SERVICE_NAME, run_main);
+    // the logger has been dropped (flushing it) once `run_main` returns
+    let code = EXIT_CODE.load(Ordering::SeqCst);
+    if code != 0 {
+        std::process::exit(code);
+    }
+}
+
+fn run_main() {
     // Logging
     let decorator = slog_term::TermDecorator::new().build();
     let decorator = logging::AlignedTermDecorator::new(decorator, logging::MAX_MESSAGE_WIDTH);
@@ -29,18 +62,22 @@ fn main() {
     let drain = slog_async::Async::new(drain).build().fuse();
 
     // CLI
-    let matches = App::new("Lighthouse Validator Client")
-        .version("0.0.1")
-        .author("Sigma Prime <contact@sigmaprime.io>")
-        .about("Eth 2.0 Validator Client")
+    let run = SubCommand::with_name("run")
+        .about("Runs the validator client")
+        .args(&connection_args())
         .arg(
-            Arg::with_name("datadir")
-                .long("datadir")
-                .short("d")
-                .value_name("DIR")
-                .help("Data directory for keys and databases.")
+            Arg::with_name("interop-validators")
+                .long("interop-validators")
+                .value_name("COUNT")
+                .help("Run the first COUNT interop validators, whose keys are derived from their indices, instead of the validators in the data directory. These keys are public and only for testing.")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("windows-service")
+                .long("windows-service")
+                .help("Run as the lighthouse-validator-client Windows service. Only for the command line of the registered service.")
+                .takes_value(false),
+        )
         .arg(
             Arg::with_name("logfile")
                 .long("logfile")
@@ -49,28 +86,321 @@ fn main() {
                 .takes_value(true),
         )
         .arg(
-            Arg::with_name("eth2-spec")
-                .long("eth2-spec")
-                .short("e")
-                .value_name("TOML_FILE")
-                .help("Path to Ethereum 2.0 specifications file.")
+            Arg::with_name("suggested-fee-recipient")
+                .long("suggested-fee-recipient")
+                .value_name("ADDRESS")
+                .help("The address to which block fees are paid, for validators not listed in --fee-recipient-file.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("fee-recipient-file")
+                .long("fee-recipient-file")
+                .value_name("FILE")
+                .help("A JSON file mapping validator public keys to the address to which their block fees are paid.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("gas-limit")
+                .long("gas-limit")
+                .value_name("GAS")
+                .help("The gas limit registered with the block builder, for validators not listed in --gas-limit-file.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("gas-limit-file")
+                .long("gas-limit-file")
+                .value_name("FILE")
+                .help("A JSON file mapping validator public keys to the gas limit registered with the block builder.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("keystore-password-file")
+                .long("keystore-password-file")
+                .value_name("FILE")
+                .help("A file containing the password of the validator keystores (voting-keystore.json) which have no password of their own.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("secrets-dir")
+                .long("secrets-dir")
+                .value_name("DIR")
+                .help("A directory containing the password of each validator keystore, in a file named by the validator's public key (e.g., 0xa1b2...).")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("keyring")
+                .long("keyring")
+                .help("Look for keystore passwords in the OS keyring (service lighthouse-validator, account the validator's public key), using secret-tool on Linux or security on macOS.")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("unlock")
+                .long("unlock")
+                .help("Ask at the terminal for the password of each keystore whose password is not found elsewhere.")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("hsm-module")
+                .long("hsm-module")
+                .value_name("FILE")
+                .help("The PKCS#11 module (shared library) of an HSM holding the keys of further validators.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("hsm-mechanism")
+                .long("hsm-mechanism")
+                .value_name("MECHANISM")
+                .help("The vendor-defined PKCS#11 mechanism (e.g., 0x80000001) with which the HSM produces BLS signatures. Required with --hsm-module.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("hsm-slot")
+                .long("hsm-slot")
+                .value_name("SLOT")
+                .help("The slot of the HSM token holding the keys. Defaults to the first slot with a token.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("hsm-pin-file")
+                .long("hsm-pin-file")
+                .value_name("FILE")
+                .help("A file containing the PIN of the HSM token. If not set, the PIN is asked for at the terminal.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("hsm-sessions")
+                .long("hsm-sessions")
+                .value_name("SESSIONS")
+                .help("The number of sessions opened with the HSM, and so the number of signatures requested from it at once.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("duty-schedule-file")
+                .long("duty-schedule-file")
+                .value_name("FILE")
+                .help("A file to which the duties of all validators are written as JSON whenever they change, for external schedulers.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("snapshot-dir")
+                .long("snapshot-dir")
+                .value_name("DIR")
+                .help("A directory in which the duties, signing history and last processed slots are saved every slot, and restored from on startup.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("record-trace")
+                .long("record-trace")
+                .value_name("FILE")
+                .help("A file to which slot starts and BeaconNode block production responses are appended, for the debug replay subcommand.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("slashing-protection-retention")
+                .long("slashing-protection-retention")
+                .value_name("EPOCHS")
+                .help("The number of epochs of signing history retained when slashing protection is compacted at the start of each epoch. Messages older than the retained history are refused.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("inclusion-report")
+                .long("inclusion-report")
+                .value_name("FILE")
+                .help("A file to which the attestation inclusion delays, misses and effectiveness of all validators are written as JSON each slot.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("health-address")
+                .long("health-address")
+                .value_name("ADDRESS")
+                .help("The address (e.g., 127.0.0.1:5064) on which to serve the /health, /ready, /metrics and /proposal_timings endpoints.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("metrics-push")
+                .long("metrics-push")
+                .value_name("URL")
+                .help("Periodically push the metrics of /metrics to this endpoint, for validator clients which cannot be scraped (e.g., behind NAT). Prefix the URL with remote-write= to send them to a Prometheus remote-write endpoint, otherwise it is a pushgateway.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("metrics-push-job")
+                .long("metrics-push-job")
+                .value_name("JOB")
+                .help("The job label of the pushed metrics (default: lighthouse_validator).")
+                .requires("metrics-push")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("metrics-push-instance")
+                .long("metrics-push-instance")
+                .value_name("INSTANCE")
+                .help("The instance label of the pushed metrics, distinguishing validator clients which push to the same endpoint.")
+                .requires("metrics-push")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("metrics-push-interval")
+                .long("metrics-push-interval")
+                .value_name("SECONDS")
+                .help("The seconds between pushes of the metrics (default: 15).")
+                .requires("metrics-push")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("metrics-push-basic-auth")
+                .long("metrics-push-basic-auth")
+                .value_name("FILE")
+                .help("A file containing the user:password sent as basic auth with every push of the metrics.")
+                .requires("metrics-push")
+                .conflicts_with("metrics-push-bearer-token")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("metrics-push-bearer-token")
+                .long("metrics-push-bearer-token")
+                .value_name("FILE")
+                .help("A file containing the bearer token sent with every push of the metrics.")
+                .requires("metrics-push")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("signing-lease")
+                .long("signing-lease")
+                .value_name("DIR|URL")
+                .help("For validators shared with standby validator clients: claim each validator from this shared directory (e.g., on a network filesystem) or HTTP lock service before signing, so that only one validator client signs for it at each slot. A standby takes over a validator once its lease expires.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("signing-lease-owner")
+                .long("signing-lease-owner")
+                .value_name("NAME")
+                .help("The name of this validator client in the leases, unique among those which share validators (default: random at each start, so leases held before a restart must expire).")
+                .requires("signing-lease")
                 .takes_value(true),
         )
         .arg(
-            Arg::with_name("server")
-                .long("server")
-                .value_name("server")
-                .help("Address to connect to BeaconNode.")
+            Arg::with_name("signing-lease-secs")
+                .long("signing-lease-secs")
+                .value_name("SECONDS")
+                .help("The seconds for which a validator is held after each claim, after which a standby may take it over. At least two epochs and a slot (default: three epochs).")
+                .requires("signing-lease")
                 .takes_value(true),
         )
         .arg(
-            Arg::with_name("default-spec")
-                .long("default-spec")
-                .value_name("TITLE")
-                .short("default-spec")
-                .help("Specifies the default eth2 spec to be used. This will override any spec written to disk and will therefore be used by default in future instances.")
+            Arg::with_name("dry-run")
+                .long("dry-run")
+                .help("Perform all duties, but sign blocks and attestations with throwaway keys and log them rather than publishing them. For staging deployments against a live network.")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("api-conformance")
+                .long("api-conformance")
+                .help("Check that the BeaconNode HTTP API given by --beacon-api serves every endpoint the validator client uses as expected, report any which are missing or deviant, and exit. Nothing is signed.")
+                .requires("beacon-api")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("force-unlock")
+                .long("force-unlock")
+                .help("Remove the lock on the data directory, even if another validator client holds it. Only use this if no other validator client is running with the data directory (e.g., it is held by a hung process on a network filesystem), otherwise both will sign and the validators may be slashed.")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("webhooks")
+                .long("webhooks")
+                .value_name("URLS")
+                .help("Comma-separated webhooks notified of missed proposals, signer rejections, blocked slashable messages, beacon node outages and slashed validators. Prefix a URL with slack= or discord= to send messages in their format, otherwise events are posted as JSON.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("webhook-unreachable-slots")
+                .long("webhook-unreachable-slots")
+                .value_name("SLOTS")
+                .help("The number of slots the BeaconNode may be unreachable before the webhooks are notified.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("block-publish-target")
+                .long("block-publish-target")
+                .value_name("MILLISECONDS")
+                .help("The time after the start of a slot by which blocks should be published. Blocks are requested early enough to meet it, given the recent latency of the BeaconNode, and blocks published after it are logged as late. Defaults to 1000.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("late-block-cutoff")
+                .long("late-block-cutoff")
+                .value_name("MILLISECONDS")
+                .help("The time after the start of a slot after which blocks are unlikely to be included. Blocks which would be published after it are not signed.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("cross-check-servers")
+                .long("cross-check-servers")
+                .value_name("ADDRESSES")
+                .help("Comma-separated addresses of other BeaconNodes (gRPC), whose heads and finalized checkpoints are compared with those of the BeaconNode to detect chain splits.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("chain-split-threshold")
+                .long("chain-split-threshold")
+                .value_name("SLOTS")
+                .help("The number of consecutive slots for which the head of a cross-check BeaconNode may differ before it is considered to have split.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("pause-on-chain-split")
+                .long("pause-on-chain-split")
+                .help("Pause block and attestation signing whilst the BeaconNode disagrees with the majority of cross-check BeaconNodes.")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("broadcast")
+                .long("broadcast")
+                .help("Publish signed blocks and attestations to each of the cross-check BeaconNodes as well as the BeaconNode.")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("beacon-api")
+                .long("beacon-api")
+                .value_name("URL")
+                .help("Base URL of a BeaconNode HTTP API (e.g., http://localhost:5052). If supplied, the BeaconNode is contacted via its HTTP API instead of gRPC.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("wss-checkpoint")
+                .long("wss-checkpoint")
+                .value_name("ROOT:EPOCH")
+                .help("A weak subjectivity checkpoint: the 0x-prefixed root of the canonical block at the start of EPOCH. Beacon nodes whose finalized chain conflicts with it are refused.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("builder-endpoint")
+                .long("builder-endpoint")
+                .value_name("ADDRESS")
+                .help("Address of an external block builder. Blinded blocks are requested from the builder, falling back to the BeaconNode if it fails.")
+                .takes_value(true),
+        );
+    #[cfg(feature = "yubihsm")]
+    let run = run.arg(
+        Arg::with_name("yubihsm-auth-key")
+            .long("yubihsm-auth-key")
+            .value_name("ID")
+            .help("The id of the YubiHSM 2 authentication key with which to log in, if --hsm-module is the YubiHSM PKCS#11 module. --hsm-pin-file then contains the password of the key.")
+            .takes_value(true),
+    );
+    let app = App::new("Lighthouse Validator Client")
+        .version("0.0.1")
+        .author("Sigma Prime <contact@sigmaprime.io>")
+        .about("Eth 2.0 Validator Client")
+        .arg(
+            Arg::with_name("datadir")
+                .long("datadir")
+                .short("d")
+                .value_name("DIR")
+                .help("Data directory for keys and databases.")
                 .takes_value(true)
-                .possible_values(&["mainnet", "minimal", "interop"])
+                .global(true),
         )
         .arg(
             Arg::with_name("debug-level")
@@ -80,9 +410,211 @@ fn main() {
                 .help("The title of the spec constants for chain config.")
                 .takes_value(true)
                 .possible_values(&["info", "debug", "trace", "warn", "error", "crit"])
-                .default_value("info"),
+                .default_value("info")
+                .global(true),
+        )
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(run)
+        .subcommand(
+            SubCommand::with_name("account")
+                .about("Manages the validator keys in the data directory")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .args(&spec_args())
+                .subcommand(
+                    SubCommand::with_name("list")
+                        .about("Lists the validators in the data directory, with the source of their keys and whether signing is enabled"),
+                )
+                .subcommand(
+                    SubCommand::with_name("audit-log")
+                        .about("Verifies and prints the signing audit log of a validator")
+                        .arg(
+                            Arg::with_name("validator")
+                                .long("validator")
+                                .short("v")
+                                .value_name("VALIDATOR_ID")
+                                .help("The identifier (validator directory name) of the validator.")
+                                .takes_value(true)
+                                .required(true),
+                        ),
+                ),
         )
-        .get_matches();
+        .subcommand(
+            SubCommand::with_name("validator")
+                .about("Manages the validators of a stopped or running validator client")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("enable")
+                        .about("Enables signing for a validator, taking effect without a restart")
+                        .args(&spec_args())
+                        .arg(
+                            Arg::with_name("validator")
+                                .long("validator")
+                                .short("v")
+                                .value_name("VALIDATOR_ID")
+                                .help("The identifier (validator directory name) of the validator.")
+                                .takes_value(true)
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("disable")
+                        .about("Disables signing for a validator, taking effect without a restart")
+                        .args(&spec_args())
+                        .arg(
+                            Arg::with_name("validator")
+                                .long("validator")
+                                .short("v")
+                                .value_name("VALIDATOR_ID")
+                                .help("The identifier (validator directory name) of the validator.")
+                                .takes_value(true)
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("slasher-submit")
+                        .about("Submits evidence of slashable behaviour to the beacon node, for inclusion in a block")
+                        .args(&connection_args())
+                        .arg(
+                            Arg::with_name("proposer-slashing")
+                                .long("proposer-slashing")
+                                .value_name("FILE")
+                                .help("A JSON file holding a proposer slashing.")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("attester-slashing")
+                                .long("attester-slashing")
+                                .value_name("FILE")
+                                .help("A JSON file holding an attester slashing.")
+                                .takes_value(true),
+                        )
+                        .group(
+                            ArgGroup::with_name("evidence")
+                                .args(&["proposer-slashing", "attester-slashing"])
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("slashing-protection")
+                        .about("Manages the slashing protection history saved in snapshots")
+                        .setting(AppSettings::SubcommandRequiredElseHelp)
+                        .subcommand(
+                            SubCommand::with_name("prune")
+                                .about("Prunes old signing history from a snapshot, keeping the watermarks which refuse messages that may conflict with it. The validator client must be stopped.")
+                                .arg(
+                                    Arg::with_name("snapshot-dir")
+                                        .long("snapshot-dir")
+                                        .value_name("DIR")
+                                        .help("The directory containing the snapshot.")
+                                        .takes_value(true)
+                                        .required(true),
+                                )
+                                .arg(
+                                    Arg::with_name("retention")
+                                        .long("retention")
+                                        .value_name("EPOCHS")
+                                        .help("The number of epochs of signing history to keep, before the latest slot of the snapshot.")
+                                        .takes_value(true),
+                                )
+                                .arg(
+                                    Arg::with_name("spec-constants")
+                                        .long("spec-constants")
+                                        .value_name("TITLE")
+                                        .help("The spec constants of the chain on which the snapshot was saved.")
+                                        .takes_value(true)
+                                        .possible_values(&["mainnet", "minimal", "interop"])
+                                        .default_value(DEFAULT_SPEC),
+                                ),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("report")
+                .about("Reports the attestation inclusion, proposals and balance change of each validator over recent epochs, from the history of the beacon node")
+                .args(&connection_args())
+                .arg(
+                    Arg::with_name("epochs")
+                        .long("epochs")
+                        .value_name("N")
+                        .help("The number of epochs to report (at most 64), ending with the latest whose attestations can no longer be included.")
+                        .takes_value(true)
+                        .default_value(DEFAULT_REPORT_EPOCHS),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .help("Prints the report as a table or as JSON.")
+                        .takes_value(true)
+                        .possible_values(&["table", "json"])
+                        .default_value("table"),
+                )
+                .arg(
+                    Arg::with_name("pubkey")
+                        .long("pubkey")
+                        .value_name("PUBKEY")
+                        .help("The 0x-prefixed hex public key of a validator to report (e.g., held in an HSM). May be repeated. Defaults to the validators in the data directory.")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("debug")
+                .about("Tools for debugging the validator client")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("replay")
+                        .about("Replays the block proposals in a trace recorded with --record-trace, to debug missed proposals")
+                        .arg(
+                            Arg::with_name("trace")
+                                .long("trace")
+                                .value_name("FILE")
+                                .help("The recorded trace file.")
+                                .takes_value(true)
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("spec-constants")
+                                .long("spec-constants")
+                                .value_name("TITLE")
+                                .help("The spec constants of the chain on which the trace was recorded.")
+                                .takes_value(true)
+                                .possible_values(&["mainnet", "minimal", "interop"])
+                                .default_value(DEFAULT_SPEC),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("slashing-drill")
+                        .about("Verifies that slashing protection refuses conflicting messages, using a throwaway key")
+                        .arg(
+                            Arg::with_name("dir")
+                                .long("dir")
+                                .value_name("DIR")
+                                .help("The directory in which the drill saves its signing history. Defaults to <datadir>/slashing-drill.")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("force")
+                                .long("force")
+                                .help("Run the drill even if the given --dir is not empty, overwriting any snapshot within it."),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("dump-duties")
+                        .about("Prints the duties saved in a snapshot")
+                        .arg(
+                            Arg::with_name("snapshot-dir")
+                                .long("snapshot-dir")
+                                .value_name("DIR")
+                                .help("The directory containing the snapshot.")
+                                .takes_value(true)
+                                .required(true),
+                        ),
+                ),
+        );
+
+    let matches = app.get_matches();
 
     let drain = match matches.value_of("debug-level") {
         Some("info") => drain.filter_level(Level::Info),
@@ -114,13 +646,158 @@ fn main() {
         }
     };
 
-    // create the directory if needed
-    match fs::create_dir_all(&data_dir) {
-        Ok(_) => {}
-        Err(e) => {
-            crit!(log, "Failed to initialize data dir"; "error" => format!("{}", e));
-            return;
+    match matches.subcommand() {
+        ("run", Some(sub_matches)) => {
+            if let Some((client_config, eth2_config)) =
+                load_config(&data_dir, sub_matches, &mut log)
+            {
+                if sub_matches.is_present("api-conformance") {
+                    check_api_conformance(&client_config, &eth2_config, &log);
+                } else {
+                    run_validator_client(client_config, eth2_config, &log);
+                }
+            }
+        }
+        ("account", Some(sub_matches)) => {
+            let validators_dir = match validators_dir(&data_dir, sub_matches, &log) {
+                Some(validators_dir) => validators_dir,
+                None => return,
+            };
+            match sub_matches.subcommand() {
+                ("list", Some(_)) => list_accounts(&validators_dir, &log),
+                ("audit-log", Some(audit_matches)) => {
+                    let validator_id = audit_matches
+                        .value_of("validator")
+                        .expect("guarded by clap");
+                    print_audit_log(&validators_dir, validator_id, &log);
+                }
+                _ => unreachable!("guarded by clap"),
+            }
+        }
+        ("validator", Some(sub_matches)) => match sub_matches.subcommand() {
+            ("enable", Some(state_matches)) | ("disable", Some(state_matches)) => {
+                let validator_id = state_matches
+                    .value_of("validator")
+                    .expect("guarded by clap");
+                let enabled = sub_matches.subcommand_name() == Some("enable");
+                if let Some(validators_dir) = validators_dir(&data_dir, state_matches, &log) {
+                    set_validator_enabled(&validators_dir, validator_id, enabled, &log);
+                }
+            }
+            ("slasher-submit", Some(submit_matches)) => {
+                if let Some((client_config, eth2_config)) =
+                    load_config(&data_dir, submit_matches, &mut log)
+                {
+                    match eth2_config.spec_constants.as_str() {
+                        "mainnet" => {
+                            submit_slashing::<MainnetEthSpec>(&client_config, submit_matches, &log)
+                        }
+                        "minimal" => {
+                            submit_slashing::<MinimalEthSpec>(&client_config, submit_matches, &log)
+                        }
+                        "interop" => {
+                            submit_slashing::<InteropEthSpec>(&client_config, submit_matches, &log)
+                        }
+                        other => crit!(log, "Unknown spec constants"; "title" => other),
+                    }
+                }
+            }
+            ("slashing-protection", Some(protection_matches)) => {
+                if let ("prune", Some(prune_matches)) = protection_matches.subcommand() {
+                    let dir = prune_matches
+                        .value_of("snapshot-dir")
+                        .expect("guarded by clap");
+                    let retention = match prune_matches.value_of("retention").map(str::parse) {
+                        Some(Ok(retention)) => retention,
+                        Some(Err(_)) => {
+                            crit!(log, "retention is not a number of epochs");
+                            return;
+                        }
+                        None => DEFAULT_RETENTION_EPOCHS,
+                    };
+                    let slots_per_epoch = match prune_matches
+                        .value_of("spec-constants")
+                        .expect("has a default value")
+                    {
+                        "mainnet" => MainnetEthSpec::slots_per_epoch(),
+                        "minimal" => MinimalEthSpec::slots_per_epoch(),
+                        "interop" => InteropEthSpec::slots_per_epoch(),
+                        _ => unreachable!("guarded by clap"),
+                    };
+                    prune_slashing_protection(Path::new(dir), retention, slots_per_epoch, &log);
+                }
+            }
+            _ => unreachable!("guarded by clap"),
+        },
+        ("report", Some(report_matches)) => {
+            if let Some((client_config, eth2_config)) =
+                load_config(&data_dir, report_matches, &mut log)
+            {
+                let spec = &eth2_config.spec;
+                match eth2_config.spec_constants.as_str() {
+                    "mainnet" => {
+                        print_report::<MainnetEthSpec>(&client_config, spec, report_matches, &log)
+                    }
+                    "minimal" => {
+                        print_report::<MinimalEthSpec>(&client_config, spec, report_matches, &log)
+                    }
+                    "interop" => {
+                        print_report::<InteropEthSpec>(&client_config, spec, report_matches, &log)
+                    }
+                    other => crit!(log, "Unknown spec constants"; "title" => other),
+                }
+            }
         }
+        ("debug", Some(sub_matches)) => match sub_matches.subcommand() {
+            ("replay", Some(replay_matches)) => {
+                let trace = replay_matches.value_of("trace").expect("guarded by clap");
+                let spec_constants = replay_matches
+                    .value_of("spec-constants")
+                    .expect("has a default value");
+                replay_trace(Path::new(trace), spec_constants, &log);
+            }
+            ("slashing-drill", Some(drill_matches)) => {
+                // The default directory belongs to the drill, but a given one may hold a real
+                // snapshot, which the drill would overwrite.
+                match drill_matches.value_of("dir").map(PathBuf::from) {
+                    Some(dir) if !drill_matches.is_present("force") && !is_empty_dir(&dir) => {
+                        crit!(
+                            log,
+                            "Refusing to run the slashing drill in a non-empty directory";
+                            "dir" => format!("{:?}", dir),
+                            "help" => "use --force to overwrite it",
+                        );
+                        EXIT_CODE.store(1, Ordering::SeqCst);
+                    }
+                    dir => {
+                        let dir = dir.unwrap_or_else(|| data_dir.join(DEFAULT_SLASHING_DRILL_DIR));
+                        run_slashing_drill(&dir, &log);
+                    }
+                }
+            }
+            ("dump-duties", Some(dump_matches)) => {
+                let dir = dump_matches
+                    .value_of("snapshot-dir")
+                    .expect("guarded by clap");
+                dump_duties(Path::new(dir), &log);
+            }
+            _ => unreachable!("guarded by clap"),
+        },
+        _ => unreachable!("guarded by clap"),
+    }
+}
+
+/// Loads the configuration of the validator client from the data directory, updated with the
+/// arguments of the `run` (or another connecting) subcommand, logging any problems.
+fn load_config(
+    data_dir: &Path,
+    matches: &ArgMatches,
+    log: &mut slog::Logger,
+) -> Option<(ValidatorClientConfig, Eth2Config)> {
+    // create the directory if needed
+    if let Err(e) = fs::create_dir_all(data_dir) {
+        crit!(log, "Failed to initialize data dir"; "error" => format!("{}", e));
+        return None;
     }
 
     let client_config_path = data_dir.join(CLIENT_CONFIG_FILENAME);
@@ -136,94 +813,149 @@ fn main() {
             let default = ValidatorClientConfig::default();
             if let Err(e) = write_to_file(client_config_path.clone(), &default) {
                 crit!(log, "Failed to write default ClientConfig to file"; "error" => format!("{:?}", e));
-                return;
+                return None;
             }
             default
         }
         Err(e) => {
             crit!(log, "Failed to load a ChainConfig file"; "error" => format!("{:?}", e));
-            return;
+            return None;
         }
     };
 
     // Ensure the `data_dir` in the config matches that supplied to the CLI.
-    client_config.data_dir = data_dir.clone();
+    client_config.data_dir = data_dir.to_path_buf();
 
     // Update the client config with any CLI args.
-    match client_config.apply_cli_args(&matches, &mut log) {
+    match client_config.apply_cli_args(matches, log) {
         Ok(()) => (),
         Err(s) => {
             crit!(log, "Failed to parse ClientConfig CLI arguments"; "error" => s);
-            return;
+            return None;
         }
     };
 
-    let eth2_config_path: PathBuf = matches
-        .value_of("eth2-spec")
-        .and_then(|s| Some(PathBuf::from(s)))
-        .unwrap_or_else(|| data_dir.join(ETH2_CONFIG_FILENAME));
+    let eth2_config = select_eth2_config(data_dir, matches, true, log)?;
 
-    // Initialise the `Eth2Config`.
-    //
-    // If a CLI parameter is set, overwrite any config file present.
-    // If a parameter is not set, use either the config file present or default to minimal.
-    let cli_config = match matches.value_of("default-spec") {
-        Some("mainnet") => Some(Eth2Config::mainnet()),
-        Some("minimal") => Some(Eth2Config::minimal()),
-        Some("interop") => Some(Eth2Config::interop()),
-        _ => None,
-    };
-    // if a CLI flag is specified, write the new config if it doesn't exist,
-    // otherwise notify the user that the file will not be written.
-    let eth2_config_from_file = match read_from_file::<Eth2Config>(eth2_config_path.clone()) {
-        Ok(config) => config,
+    // Chain-specific data is kept in the directory of the network.
+    client_config.data_dir = open_network_dir(data_dir, &eth2_config, log)?;
+
+    // Cross-check the final configuration, reporting all problems at once.
+    let problems = client_config.validate(&eth2_config);
+    if !problems.is_empty() {
+        for problem in &problems {
+            crit!(
+                log,
+                "Invalid configuration";
+                "setting" => problem.setting,
+                "problem" => &problem.problem,
+                "suggestion" => &problem.suggestion
+            );
+        }
+        crit!(
+            log,
+            "Configuration has {} problem(s), exiting",
+            problems.len()
+        );
+        return None;
+    }
+
+    Some((client_config, eth2_config))
+}
+
+/// Selects the `Eth2Config` of the data directory, as overridden by `--eth2-spec` and
+/// `--default-spec` and updated with any other arguments, logging any problems.
+///
+/// If `save`, a config which is not yet in its file is written there.
+fn select_eth2_config(
+    data_dir: &Path,
+    matches: &ArgMatches,
+    save: bool,
+    log: &slog::Logger,
+) -> Option<Eth2Config> {
+    let selected = match datadir::select_eth2_config(
+        data_dir,
+        matches.value_of("eth2-spec").map(Path::new),
+        matches.value_of("default-spec"),
+    ) {
+        Ok(selected) => selected,
         Err(e) => {
-            crit!(log, "Failed to read the Eth2Config from file"; "error" => format!("{:?}", e));
-            return;
+            crit!(log, "Failed to read the Eth2Config from file"; "error" => e);
+            return None;
         }
     };
+    if selected.overridden {
+        warn!(
+            log,
+            "Eth2Config file exists. Configuration file is ignored, using default"
+        );
+    }
+    if save && selected.unsaved {
+        if let Err(e) = write_to_file(selected.path, &selected.eth2_config) {
+            crit!(log, "Failed to write default Eth2Config to file"; "error" => format!("{:?}", e));
+            return None;
+        }
+    }
 
-    let mut eth2_config = {
-        if let Some(cli_config) = cli_config {
-            if eth2_config_from_file.is_none() {
-                // write to file if one doesn't exist
-                if let Err(e) = write_to_file(eth2_config_path, &cli_config) {
-                    crit!(log, "Failed to write default Eth2Config to file"; "error" => format!("{:?}", e));
-                    return;
-                }
-            } else {
-                warn!(
+    let mut eth2_config = selected.eth2_config;
+    // Update the eth2 config with any CLI flags.
+    match eth2_config.apply_cli_args(matches) {
+        Ok(()) => Some(eth2_config),
+        Err(s) => {
+            crit!(log, "Failed to parse Eth2Config CLI arguments"; "error" => s);
+            None
+        }
+    }
+}
+
+/// Opens the directory of the network of `eth2_config` within the data directory, migrating any
+/// legacy layout into it.
+fn open_network_dir(
+    data_dir: &Path,
+    eth2_config: &Eth2Config,
+    log: &slog::Logger,
+) -> Option<PathBuf> {
+    match datadir::open_network_dir(data_dir, eth2_config) {
+        Ok(network_dir) => {
+            if !network_dir.migrated.is_empty() {
+                info!(
                     log,
-                    "Eth2Config file exists. Configuration file is ignored, using default"
+                    "Moved the data directory into the directory of its network";
+                    "entries" => network_dir.migrated.len(),
+                    "network_dir" => format!("{:?}", network_dir.path),
                 );
             }
-            cli_config
-        } else {
-            // CLI config not specified, read from disk
-            match eth2_config_from_file {
-                Some(config) => config,
-                None => {
-                    // set default to minimal
-                    let eth2_config = Eth2Config::minimal();
-                    if let Err(e) = write_to_file(eth2_config_path, &eth2_config) {
-                        crit!(log, "Failed to write default Eth2Config to file"; "error" => format!("{:?}", e));
-                        return;
-                    }
-                    eth2_config
-                }
-            }
+            Some(network_dir.path)
         }
-    };
+        Err(e) => {
+            crit!(log, "Unable to open the data directory of the network"; "error" => e.to_string());
+            None
+        }
+    }
+}
 
-    // Update the eth2 config with any CLI flags.
-    match eth2_config.apply_cli_args(&matches) {
-        Ok(()) => (),
-        Err(s) => {
-            crit!(log, "Failed to parse Eth2Config CLI arguments"; "error" => s);
-            return;
+/// Returns the directory of the validators of the network selected by `matches`, for the
+/// subcommands which do not load the full configuration.
+///
+/// Nothing is written to the data directory, nor is it migrated.
+fn validators_dir(data_dir: &Path, matches: &ArgMatches, log: &slog::Logger) -> Option<PathBuf> {
+    let eth2_config = select_eth2_config(data_dir, matches, false, log)?;
+    match datadir::find_validators_dir(data_dir, &eth2_config) {
+        Ok(validators_dir) => Some(validators_dir),
+        Err(e) => {
+            crit!(log, "Unable to find the validators of the network"; "error" => e.to_string());
+            None
         }
-    };
+    }
+}
 
+/// Runs the validator client until it shuts down, setting the exit code of the process: 1 if it
+/// exited with an error, or `shutdown::FORCED_EXIT_CODE` if productions in flight were abandoned.
+fn run_validator_client(
+    client_config: ValidatorClientConfig,
+    eth2_config: Eth2Config,
+    log: &slog::Logger,
+) {
     info!(
         log,
         "Starting validator client";
@@ -232,31 +964,504 @@ fn main() {
     );
 
     let result = match eth2_config.spec_constants.as_str() {
-        "mainnet" => ValidatorService::<ValidatorServiceClient, Keypair, MainnetEthSpec>::start(
-            client_config,
-            eth2_config,
-            log.clone(),
+        "mainnet" => ValidatorService::<
+            Authenticated<ValidatorServiceClient>,
+            Broadcast<BeaconBlockGrpcClient>,
+            Broadcast<Authenticated<AttestationServiceClient>>,
+            AuditedValidatorSigner,
+            MainnetEthSpec,
+        >::start(client_config, eth2_config, log.clone()),
+        "minimal" => ValidatorService::<
+            Authenticated<ValidatorServiceClient>,
+            Broadcast<BeaconBlockGrpcClient>,
+            Broadcast<Authenticated<AttestationServiceClient>>,
+            AuditedValidatorSigner,
+            MinimalEthSpec,
+        >::start(client_config, eth2_config, log.clone()),
+        "interop" => ValidatorService::<
+            Authenticated<ValidatorServiceClient>,
+            Broadcast<BeaconBlockGrpcClient>,
+            Broadcast<Authenticated<AttestationServiceClient>>,
+            AuditedValidatorSigner,
+            InteropEthSpec,
+        >::start(client_config, eth2_config, log.clone()),
+        other => {
+            crit!(log, "Unknown spec constants"; "title" => other);
+            EXIT_CODE.store(1, Ordering::SeqCst);
+            return;
+        }
+    };
+
+    // start the validator service.
+    // this specifies the GRPC and signer type to use as the duty manager beacon node.
+    let code = match result {
+        Ok(outcome) => {
+            info!(log, "Validator client shutdown successfully."; "outcome" => format!("{:?}", outcome));
+            outcome.exit_code()
+        }
+        Err(e) => {
+            crit!(log, "Validator client exited with error"; "error" => e.to_string());
+            1
+        }
+    };
+    EXIT_CODE.store(code, Ordering::SeqCst);
+}
+
+/// The arguments with which the beacon node is reached, and its chain configured, shared by
+/// the subcommands which connect to it.
+fn connection_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("server")
+            .long("server")
+            .value_name("server")
+            .help("Address to connect to BeaconNode.")
+            .takes_value(true),
+        Arg::with_name("tls-ca")
+            .long("tls-ca")
+            .value_name("FILE")
+            .help("PEM certificate authority which signs the certificates of BeaconNodes. Requires --tls-cert and --tls-key, and connects to BeaconNodes (gRPC) over mutual TLS.")
+            .takes_value(true),
+        Arg::with_name("tls-cert")
+            .long("tls-cert")
+            .value_name("FILE")
+            .help("PEM certificate presented to BeaconNodes.")
+            .takes_value(true),
+        Arg::with_name("tls-key")
+            .long("tls-key")
+            .value_name("FILE")
+            .help("PEM private key of --tls-cert.")
+            .takes_value(true),
+        Arg::with_name("auth-token")
+            .long("auth-token")
+            .value_name("FILE")
+            .help("File containing the token sent with every BeaconNode (gRPC) call, matching the --rpc-auth-token of the BeaconNode.")
+            .takes_value(true),
+    ]
+    .into_iter()
+    .chain(spec_args())
+    .collect()
+}
+
+/// The arguments which select the chain spec, and so the network whose data is used.
+fn spec_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("eth2-spec")
+            .long("eth2-spec")
+            .short("e")
+            .value_name("TOML_FILE")
+            .help("Path to Ethereum 2.0 specifications file.")
+            .takes_value(true),
+        Arg::with_name("default-spec")
+            .long("default-spec")
+            .value_name("TITLE")
+            .short("default-spec")
+            .help("Specifies the default eth2 spec to be used. This will override any spec written to disk and will therefore be used by default in future instances.")
+            .takes_value(true)
+            .possible_values(&["mainnet", "minimal", "interop"]),
+    ]
+}
+
+/// Enable or disable a validator, persisting its state in its directory.
+fn set_validator_enabled(
+    validators_dir: &Path,
+    validator_id: &str,
+    enabled: bool,
+    log: &slog::Logger,
+) {
+    match (ValidatorState { enabled }).save(validators_dir, validator_id) {
+        Ok(()) => info!(
+            log,
+            "Validator state updated";
+            "enabled" => enabled,
+            "validator" => validator_id,
         ),
-        "minimal" => ValidatorService::<ValidatorServiceClient, Keypair, MinimalEthSpec>::start(
-            client_config,
-            eth2_config,
-            log.clone(),
+        Err(e) => crit!(
+            log,
+            "Failed to update validator state";
+            "error" => format!("{:?}", e),
+            "validator" => validator_id,
         ),
-        "interop" => ValidatorService::<ValidatorServiceClient, Keypair, InteropEthSpec>::start(
-            client_config,
-            eth2_config,
-            log.clone(),
+    }
+}
+
+/// Replays the block proposals recorded in `trace`, printing the outcome of each.
+fn replay_trace(trace: &Path, spec_constants: &str, log: &slog::Logger) {
+    let trace_replay = match Replay::load(trace) {
+        Ok(trace_replay) => trace_replay,
+        Err(e) => {
+            crit!(log, "Failed to load trace"; "error" => e.to_string(), "path" => format!("{:?}", trace));
+            return;
+        }
+    };
+
+    let result = match spec_constants {
+        "mainnet" => replay::replay::<MainnetEthSpec>(trace_replay, MainnetEthSpec::default_spec()),
+        "minimal" => replay::replay::<MinimalEthSpec>(trace_replay, MinimalEthSpec::default_spec()),
+        "interop" => replay::replay::<InteropEthSpec>(trace_replay, InteropEthSpec::default_spec()),
+        _ => unreachable!("guarded by clap"),
+    };
+
+    match result {
+        Ok(proposals) => {
+            for proposal in &proposals {
+                let started = if proposal.slot_started {
+                    ""
+                } else {
+                    " (the start of the slot was not recorded)"
+                };
+                println!("slot {}: {:?}{}", proposal.slot, proposal.outcome, started);
+            }
+            info!(log, "Replay complete"; "proposals" => proposals.len());
+        }
+        Err(e) => crit!(log, "Replay failed"; "error" => e.to_string()),
+    }
+}
+
+/// Submit the slashing in the file given to the `slasher_submit` subcommand to the beacon node.
+fn submit_slashing<E: EthSpec>(
+    client_config: &ValidatorClientConfig,
+    matches: &clap::ArgMatches,
+    log: &slog::Logger,
+) {
+    let evidence = match (
+        matches.value_of("proposer-slashing"),
+        matches.value_of("attester-slashing"),
+    ) {
+        (Some(path), _) => Evidence::<E>::proposer_slashing(Path::new(path)),
+        (None, Some(path)) => Evidence::<E>::attester_slashing(Path::new(path)),
+        (None, None) => unreachable!("guarded by clap"),
+    };
+    let evidence = match evidence {
+        Ok(evidence) => evidence,
+        Err(e) => {
+            crit!(log, "Unable to load slashing evidence"; "error" => e.to_string());
+            return;
+        }
+    };
+
+    let client = match Connector::new(client_config, log) {
+        Ok(connector) => connector.connect(&client_config.server, SlashingServiceClient::new),
+        Err(e) => {
+            crit!(log, "Unable to connect to the beacon node"; "error" => e.to_string());
+            return;
+        }
+    };
+
+    match evidence.submit(&client) {
+        Ok(()) => info!(log, "Slashing evidence accepted"; "server" => &client_config.server),
+        Err(e) => crit!(
+            log,
+            "Slashing evidence not accepted";
+            "error" => e.to_string(),
+            "server" => &client_config.server,
         ),
+    }
+}
+
+/// Prints the performance of validators over the epochs given to the `report` subcommand.
+fn print_report<E: EthSpec>(
+    client_config: &ValidatorClientConfig,
+    spec: &ChainSpec,
+    matches: &clap::ArgMatches,
+    log: &slog::Logger,
+) {
+    let epochs = match matches
+        .value_of("epochs")
+        .expect("has a default value")
+        .parse::<u64>()
+    {
+        Ok(epochs) if epochs > 0 && epochs <= report::MAX_REPORT_EPOCHS => epochs,
+        _ => {
+            crit!(log, "epochs is not a number of epochs from 1 to the maximum"; "max" => report::MAX_REPORT_EPOCHS);
+            EXIT_CODE.store(1, Ordering::SeqCst);
+            return;
+        }
+    };
+
+    let pub_keys = match matches.values_of("pubkey") {
+        Some(values) => {
+            let pub_keys: Result<Vec<PublicKey>, _> = values
+                .map(|value| {
+                    hex::decode(value.trim_start_matches("0x"))
+                        .ok()
+                        .and_then(|bytes| PublicKey::from_ssz_bytes(&bytes).ok())
+                        .ok_or(value)
+                })
+                .collect();
+            match pub_keys {
+                Ok(pub_keys) => pub_keys,
+                Err(value) => {
+                    crit!(log, "Invalid public key"; "pubkey" => value);
+                    EXIT_CODE.store(1, Ordering::SeqCst);
+                    return;
+                }
+            }
+        }
+        None => client_config.fetch_public_keys(log),
+    };
+    if pub_keys.is_empty() {
+        crit!(log, "No validators to report"; "help" => "add validators to the data directory, or pass --pubkey");
+        EXIT_CODE.store(1, Ordering::SeqCst);
+        return;
+    }
+
+    let connector = match Connector::new(client_config, log) {
+        Ok(connector) => connector,
+        Err(e) => {
+            crit!(log, "Unable to connect to the beacon node"; "error" => e.to_string());
+            EXIT_CODE.store(1, Ordering::SeqCst);
+            return;
+        }
+    };
+
+    let performance = match report::fetch::<E>(
+        &connector,
+        &client_config.server,
+        epochs,
+        &pub_keys,
+        spec,
+    ) {
+        Ok(performance) => performance,
+        Err(e) => {
+            crit!(log, "Unable to report validator performance"; "error" => e.to_string(), "server" => &client_config.server);
+            EXIT_CODE.store(1, Ordering::SeqCst);
+            return;
+        }
+    };
+
+    if matches.value_of("format") == Some("json") {
+        match serde_json::to_string_pretty(&performance) {
+            Ok(json) => println!("{}", json),
+            Err(e) => {
+                crit!(log, "Unable to encode the report"; "error" => e.to_string());
+                EXIT_CODE.store(1, Ordering::SeqCst);
+            }
+        }
+    } else {
+        print!("{}", performance);
+    }
+}
+
+/// Check the conformance of the beacon node HTTP API, printing the result of each check and
+/// setting an exit code of 1 if any check is missing or deviant.
+fn check_api_conformance(
+    client_config: &ValidatorClientConfig,
+    eth2_config: &Eth2Config,
+    log: &slog::Logger,
+) {
+    let url = match &client_config.beacon_api {
+        Some(url) => url,
+        None => {
+            crit!(log, "--api-conformance requires --beacon-api");
+            EXIT_CODE.store(1, Ordering::SeqCst);
+            return;
+        }
+    };
+    let spec = &eth2_config.spec;
+    let result = match eth2_config.spec_constants.as_str() {
+        "mainnet" => api_conformance::run::<MainnetEthSpec>(url, spec),
+        "minimal" => api_conformance::run::<MinimalEthSpec>(url, spec),
+        "interop" => api_conformance::run::<InteropEthSpec>(url, spec),
         other => {
             crit!(log, "Unknown spec constants"; "title" => other);
+            EXIT_CODE.store(1, Ordering::SeqCst);
             return;
         }
     };
 
-    // start the validator service.
-    // this specifies the GRPC and signer type to use as the duty manager beacon node.
     match result {
-        Ok(_) => info!(log, "Validator client shutdown successfully."),
-        Err(e) => crit!(log, "Validator client exited with error"; "error" => e.to_string()),
+        Ok(checks) => {
+            for check in &checks {
+                println!("{}", check);
+            }
+            let missing = checks
+                .iter()
+                .filter(|check| check.status == Status::Missing)
+                .count();
+            let deviant = checks
+                .iter()
+                .filter(|check| check.status == Status::Deviant)
+                .count();
+            if missing == 0 && deviant == 0 {
+                info!(log, "Beacon node API is conformant"; "url" => url, "checks" => checks.len());
+            } else {
+                crit!(
+                    log,
+                    "Beacon node API is not conformant";
+                    "url" => url,
+                    "checks" => checks.len(),
+                    "missing" => missing,
+                    "deviant" => deviant,
+                );
+                EXIT_CODE.store(1, Ordering::SeqCst);
+            }
+        }
+        Err(e) => {
+            crit!(log, "Unable to check beacon node API"; "error" => e.to_string());
+            EXIT_CODE.store(1, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Returns `true` if `dir` does not exist or contains no entries.
+fn is_empty_dir(dir: &Path) -> bool {
+    match fs::read_dir(dir) {
+        Ok(mut entries) => entries.next().is_none(),
+        Err(e) => e.kind() == io::ErrorKind::NotFound,
+    }
+}
+
+/// Runs the slashing protection drill in `dir`, printing the result of each check and setting
+/// an exit code of 1 if any check fails or the drill is unable to run.
+fn run_slashing_drill(dir: &Path, log: &slog::Logger) {
+    match slashing_drill::run(dir) {
+        Ok(checks) => {
+            for check in &checks {
+                println!("{}", check);
+            }
+            let failed = checks.iter().filter(|check| !check.passed).count();
+            if failed == 0 {
+                info!(log, "Slashing drill passed"; "checks" => checks.len());
+            } else {
+                crit!(log, "Slashing drill failed"; "checks" => checks.len(), "failed" => failed);
+                EXIT_CODE.store(1, Ordering::SeqCst);
+            }
+        }
+        Err(e) => {
+            crit!(
+                log,
+                "Unable to run slashing drill";
+                "error" => e.to_string(),
+                "dir" => format!("{:?}", dir),
+            );
+            EXIT_CODE.store(1, Ordering::SeqCst);
+        }
+    }
+}
+
+fn prune_slashing_protection(dir: &Path, retention: u64, slots_per_epoch: u64, log: &slog::Logger) {
+    let mut snapshot = match Snapshot::load(dir) {
+        Ok(Some(snapshot)) => snapshot,
+        Ok(None) => {
+            crit!(log, "No snapshot to prune"; "dir" => format!("{:?}", dir));
+            return;
+        }
+        Err(e) => {
+            crit!(log, "Unable to load snapshot"; "error" => e.to_string(), "dir" => format!("{:?}", dir));
+            return;
+        }
+    };
+
+    match snapshot
+        .prune_slashing_protection(retention, slots_per_epoch)
+        .and_then(|pruned| snapshot.save(dir).map(|()| pruned))
+    {
+        Ok(pruned) => info!(
+            log,
+            "Pruned slashing protection";
+            "blocks" => pruned.blocks,
+            "attestations" => pruned.attestations,
+            "latest_slot" => snapshot.current_slot.as_u64(),
+        ),
+        Err(e) => crit!(log, "Unable to prune slashing protection"; "error" => e.to_string()),
+    }
+}
+
+/// Lists the validators in `validators_dir`, with the source of each key and whether signing is
+/// enabled.
+fn list_accounts(validators_dir: &Path, log: &slog::Logger) {
+    let entries = match fs::read_dir(validators_dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            crit!(log, "Unable to read the validators directory"; "error" => e.to_string(), "dir" => format!("{:?}", validators_dir));
+            return;
+        }
+    };
+    let mut validator_ids: Vec<String> = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            if !entry.file_type().ok()?.is_dir() {
+                return None;
+            }
+            entry.file_name().into_string().ok()
+        })
+        .collect();
+    validator_ids.sort();
+
+    let mut count = 0;
+    for validator_id in &validator_ids {
+        let validator_dir = validators_dir.join(validator_id);
+        let key = if validator_dir.join(KEYSTORE_FILENAME).is_file() {
+            "keystore"
+        } else if validator_dir.join(DEFAULT_PRIVATE_KEY_FILENAME).is_file() {
+            "unencrypted key"
+        } else {
+            // not a validator directory
+            continue;
+        };
+        let state = match ValidatorState::load(validators_dir, validator_id) {
+            Ok(state) if state.enabled => "enabled".to_string(),
+            Ok(_) => "disabled".to_string(),
+            Err(e) => format!("unknown state ({:?})", e),
+        };
+        println!("{}: {}, {}", validator_id, key, state);
+        count += 1;
+    }
+    info!(log, "Listed validators"; "validators" => count, "dir" => format!("{:?}", validators_dir));
+}
+
+/// Prints the duties of each epoch saved in the snapshot in `dir`.
+fn dump_duties(dir: &Path, log: &slog::Logger) {
+    let snapshot = match Snapshot::load(dir) {
+        Ok(Some(snapshot)) => snapshot,
+        Ok(None) => {
+            crit!(log, "No snapshot to dump"; "dir" => format!("{:?}", dir));
+            return;
+        }
+        Err(e) => {
+            crit!(log, "Unable to load snapshot"; "error" => e.to_string(), "dir" => format!("{:?}", dir));
+            return;
+        }
+    };
+
+    for epoch_duties in &snapshot.duties {
+        println!("epoch {}:", epoch_duties.epoch);
+        for (pubkey, duty) in &epoch_duties.duties {
+            match duty {
+                Some(duty) => println!("  {}: {}", pubkey.as_hex_string(), duty),
+                None => println!("  {}: inactive", pubkey.as_hex_string()),
+            }
+        }
+    }
+    info!(
+        log,
+        "Dumped duties";
+        "epochs" => snapshot.duties.len(),
+        "latest_slot" => snapshot.current_slot.as_u64(),
+    );
+}
+
+/// Verify the hash chain of a validator's audit log, printing each entry.
+fn print_audit_log(validators_dir: &Path, validator_id: &str, log: &slog::Logger) {
+    let path = AuditLog::path(validators_dir, validator_id);
+
+    match audit_log::verify(&path) {
+        Ok(entries) => {
+            for entry in &entries {
+                println!("{}", entry);
+            }
+            info!(
+                log,
+                "Audit log verified";
+                "entries" => entries.len(),
+                "path" => format!("{:?}", path),
+            );
+        }
+        Err(e) => crit!(
+            log,
+            "Audit log verification failed";
+            "error" => format!("{:?}", e),
+            "path" => format!("{:?}", path),
+        ),
     }
 }
@@ -0,0 +1,649 @@
+//! Pushes the metrics served on `/metrics` to a Prometheus pushgateway or remote-write endpoint,
+//! for validator clients which cannot be scraped (e.g., behind NAT).
+//!
+//! Every `interval_secs` the metric families of the registry are either:
+//!
+//! - `PUT` to `<url>/metrics/job/<job>[/instance/<instance>]` of a pushgateway, in the text
+//!   format, replacing those of the previous push, or
+//! - `POST`ed to a remote-write endpoint (e.g., of Cortex, Thanos or Grafana Cloud) as a
+//!   snappy-compressed protobuf `WriteRequest`, with `job` and `instance` labels added to every
+//!   series in place of those a scrape would add, unless the series has its own.
+//!
+//! Requests carry basic auth (a file of `user:password`) or a bearer token (a file of the token),
+//! if configured. Pushes are best-effort: a push which fails is logged and retried at the next
+//! interval.
+use futures::{Future, Stream};
+use prometheus::proto::{MetricFamily, MetricType};
+use prometheus::{Encoder, Registry, TextEncoder};
+use protos::auth::read_token;
+use reqwest::header::{CONTENT_ENCODING, CONTENT_TYPE};
+use reqwest::r#async::{Client, RequestBuilder};
+use serde_derive::{Deserialize, Serialize};
+use slog::{debug, info, warn};
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::timer::Interval;
+
+/// The maximum time to wait for an endpoint to accept a push.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The seconds between pushes, by default.
+pub const DEFAULT_INTERVAL_SECS: u64 = 15;
+
+/// The job of the pushed metrics, by default.
+pub const DEFAULT_JOB: &str = "lighthouse_validator";
+
+/// The version of the remote-write protocol spoken.
+const REMOTE_WRITE_VERSION: &str = "0.1.0";
+
+/// The protocol with which metrics are pushed.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PushKind {
+    /// A Prometheus pushgateway, which receives the text format.
+    Pushgateway,
+    /// A Prometheus remote-write endpoint, which receives snappy-compressed protobuf.
+    RemoteWrite,
+}
+
+/// An endpoint to which metrics are pushed.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct PushTarget {
+    pub kind: PushKind,
+    pub url: String,
+}
+
+impl FromStr for PushTarget {
+    type Err = String;
+
+    /// Parses `pushgateway=URL`, `remote-write=URL` or a bare `URL`, which is a pushgateway.
+    fn from_str(s: &str) -> Result<Self, String> {
+        let mut parts = s.splitn(2, '=');
+        let (kind, url) = match (parts.next(), parts.next()) {
+            (Some("pushgateway"), Some(url)) => (PushKind::Pushgateway, url),
+            (Some("remote-write"), Some(url)) => (PushKind::RemoteWrite, url),
+            _ => (PushKind::Pushgateway, s),
+        };
+
+        if url.starts_with("http://") || url.starts_with("https://") {
+            Ok(Self {
+                kind,
+                url: url.trim_end_matches('/').to_string(),
+            })
+        } else {
+            Err(format!("{} is not an HTTP(S) URL", url))
+        }
+    }
+}
+
+/// The credentials sent with every push, read from a file.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PushAuth {
+    /// A file of `user:password`, sent as basic auth.
+    Basic(PathBuf),
+    /// A file of a token, sent as a bearer token.
+    Bearer(PathBuf),
+}
+
+/// The credentials of a `PushAuth`, once read.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Credentials {
+    Basic { user: String, password: String },
+    Bearer(String),
+}
+
+impl PushAuth {
+    /// Reads the credentials from the file.
+    pub fn read(&self) -> Result<Credentials, io::Error> {
+        match self {
+            PushAuth::Basic(path) => {
+                let contents = read_token(path)?;
+                let mut parts = contents.splitn(2, ':');
+                match (parts.next(), parts.next()) {
+                    (Some(user), Some(password)) if !user.is_empty() => Ok(Credentials::Basic {
+                        user: user.to_string(),
+                        password: password.to_string(),
+                    }),
+                    _ => Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "the credentials must be `user:password`",
+                    )),
+                }
+            }
+            PushAuth::Bearer(path) => read_token(path).map(Credentials::Bearer),
+        }
+    }
+}
+
+/// How and where metrics are pushed.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct MetricsPushConfig {
+    pub target: PushTarget,
+    /// The `job` of the pushed metrics.
+    pub job: String,
+    /// The `instance` of the pushed metrics, if any.
+    pub instance: Option<String>,
+    /// The seconds between pushes.
+    pub interval_secs: u64,
+    /// The credentials sent with every push, if any.
+    pub auth: Option<PushAuth>,
+}
+
+impl MetricsPushConfig {
+    pub fn new(target: PushTarget) -> Self {
+        Self {
+            target,
+            job: DEFAULT_JOB.to_string(),
+            instance: None,
+            interval_secs: DEFAULT_INTERVAL_SECS,
+            auth: None,
+        }
+    }
+
+    /// Returns the URL to which metrics are pushed.
+    pub fn url(&self) -> String {
+        match self.target.kind {
+            PushKind::Pushgateway => {
+                let mut url = format!("{}/metrics/job/{}", self.target.url, self.job);
+                if let Some(instance) = &self.instance {
+                    url.push_str(&format!("/instance/{}", instance));
+                }
+                url
+            }
+            PushKind::RemoteWrite => self.target.url.clone(),
+        }
+    }
+
+    /// The labels added to every series pushed via remote-write.
+    fn labels(&self) -> Vec<(String, String)> {
+        let mut labels = vec![("job".to_string(), self.job.clone())];
+        if let Some(instance) = &self.instance {
+            labels.push(("instance".to_string(), instance.clone()));
+        }
+        labels
+    }
+}
+
+/// Returns `true` if `value` may be a `job` or `instance`, which are part of the pushgateway URL.
+pub fn is_valid_grouping_value(value: &str) -> bool {
+    !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-.:".contains(c))
+}
+
+/// Pushes the metrics of a registry at every interval.
+pub struct MetricsPusher {
+    config: MetricsPushConfig,
+    credentials: Option<Credentials>,
+    client: Client,
+    registry: Registry,
+    log: slog::Logger,
+}
+
+impl MetricsPusher {
+    pub fn new(
+        config: MetricsPushConfig,
+        registry: Registry,
+        log: slog::Logger,
+    ) -> Result<Self, String> {
+        let credentials = match &config.auth {
+            Some(auth) => Some(
+                auth.read()
+                    .map_err(|e| format!("Unable to read the metrics push credentials: {}", e))?,
+            ),
+            None => None,
+        };
+        let client = Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| format!("Unable to build HTTP client: {:?}", e))?;
+
+        Ok(Self {
+            config,
+            credentials,
+            client,
+            registry,
+            log,
+        })
+    }
+
+    /// Pushes the metrics at every interval, forever.
+    ///
+    /// Must be spawned on a runtime.
+    pub fn run(self) -> impl Future<Item = (), Error = ()> {
+        info!(
+            self.log,
+            "Pushing metrics";
+            "url" => self.config.url(),
+            "interval_secs" => self.config.interval_secs
+        );
+        let log = self.log.clone();
+
+        Interval::new_interval(Duration::from_secs(self.config.interval_secs))
+            .map_err(
+                move |e| warn!(log, "Metrics push timer failed"; "error" => format!("{:?}", e)),
+            )
+            .for_each(move |_| self.push())
+    }
+
+    /// Pushes the metrics once, logging any failure.
+    fn push(&self) -> impl Future<Item = (), Error = ()> {
+        let log = self.log.clone();
+        let url = self.config.url();
+        let request = self.request(&url);
+
+        futures::future::result(request)
+            .and_then(|request| {
+                request
+                    .send()
+                    .and_then(|response| response.error_for_status())
+                    .map_err(|e| e.to_string())
+            })
+            .then(move |result| {
+                match result {
+                    Ok(_) => debug!(log, "Pushed metrics"; "url" => url),
+                    Err(e) => warn!(log, "Unable to push metrics"; "url" => url, "error" => e),
+                }
+                Ok(())
+            })
+    }
+
+    /// Returns the request which pushes the current metrics to `url`.
+    fn request(&self, url: &str) -> Result<RequestBuilder, String> {
+        let families = self.registry.gather();
+        let request = match self.config.target.kind {
+            PushKind::Pushgateway => {
+                let encoder = TextEncoder::new();
+                let mut body = vec![];
+                encoder
+                    .encode(&families, &mut body)
+                    .map_err(|e| format!("unable to encode metrics: {:?}", e))?;
+                self.client
+                    .put(url)
+                    .header(CONTENT_TYPE, encoder.format_type())
+                    .body(body)
+            }
+            PushKind::RemoteWrite => {
+                let timestamp_ms = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_err(|e| format!("the system clock is before 1970: {:?}", e))?
+                    .as_millis() as i64;
+                let write_request =
+                    write_request(&series(&families, &self.config.labels()), timestamp_ms);
+                let body = snap::Encoder::new()
+                    .compress_vec(&write_request)
+                    .map_err(|e| format!("unable to compress metrics: {:?}", e))?;
+                self.client
+                    .post(url)
+                    .header(CONTENT_ENCODING, "snappy")
+                    .header(CONTENT_TYPE, "application/x-protobuf")
+                    .header("X-Prometheus-Remote-Write-Version", REMOTE_WRITE_VERSION)
+                    .body(body)
+            }
+        };
+
+        Ok(match &self.credentials {
+            Some(Credentials::Basic { user, password }) => request.basic_auth(user, Some(password)),
+            Some(Credentials::Bearer(token)) => request.bearer_auth(token),
+            None => request,
+        })
+    }
+}
+
+/// A single sample of a metric, with its labels sorted by name (including `__name__`).
+#[derive(Debug, PartialEq, Clone)]
+pub struct Series {
+    pub labels: Vec<(String, String)>,
+    pub value: f64,
+}
+
+/// Flattens `families` into the series a scrape would produce, adding `extra_labels` to each
+/// unless it already has a label of the same name (as a scrape would with `honor_labels`).
+///
+/// Histograms and summaries become their `_bucket` (or quantile), `_sum` and `_count` series.
+pub fn series(families: &[MetricFamily], extra_labels: &[(String, String)]) -> Vec<Series> {
+    let mut all = vec![];
+    for family in families {
+        let name = family.get_name();
+        for metric in family.get_metric() {
+            let mut push = |suffix: &str, label: Option<(&str, String)>, value: f64| {
+                let mut labels = vec![("__name__".to_string(), format!("{}{}", name, suffix))];
+                labels.extend(
+                    metric
+                        .get_label()
+                        .iter()
+                        .map(|pair| (pair.get_name().to_string(), pair.get_value().to_string())),
+                );
+                labels.extend(label.map(|(name, value)| (name.to_string(), value)));
+                for (name, value) in extra_labels {
+                    if labels.iter().all(|(existing, _)| existing != name) {
+                        labels.push((name.clone(), value.clone()));
+                    }
+                }
+                labels.sort();
+                all.push(Series { labels, value });
+            };
+
+            match family.get_field_type() {
+                MetricType::COUNTER => push("", None, metric.get_counter().get_value()),
+                MetricType::GAUGE => push("", None, metric.get_gauge().get_value()),
+                MetricType::UNTYPED => push("", None, metric.get_untyped().get_value()),
+                MetricType::HISTOGRAM => {
+                    let histogram = metric.get_histogram();
+                    for bucket in histogram.get_bucket() {
+                        push(
+                            "_bucket",
+                            Some(("le", bucket.get_upper_bound().to_string())),
+                            bucket.get_cumulative_count() as f64,
+                        );
+                    }
+                    let count = histogram.get_sample_count() as f64;
+                    push("_bucket", Some(("le", "+Inf".to_string())), count);
+                    push("_sum", None, histogram.get_sample_sum());
+                    push("_count", None, count);
+                }
+                MetricType::SUMMARY => {
+                    let summary = metric.get_summary();
+                    for quantile in summary.get_quantile() {
+                        push(
+                            "",
+                            Some(("quantile", quantile.get_quantile().to_string())),
+                            quantile.get_value(),
+                        );
+                    }
+                    push("_sum", None, summary.get_sample_sum());
+                    push("_count", None, summary.get_sample_count() as f64);
+                }
+            }
+        }
+    }
+    all
+}
+
+/// Encodes `series`, each sampled at `timestamp_ms`, as a remote-write `WriteRequest`:
+///
+/// ```protobuf
+/// message WriteRequest { repeated TimeSeries timeseries = 1; }
+/// message TimeSeries { repeated Label labels = 1; repeated Sample samples = 2; }
+/// message Label { string name = 1; string value = 2; }
+/// message Sample { double value = 1; int64 timestamp = 2; }
+/// ```
+pub fn write_request(series: &[Series], timestamp_ms: i64) -> Vec<u8> {
+    let mut request = vec![];
+    for series in series {
+        let mut time_series = vec![];
+        for (name, value) in &series.labels {
+            let mut label = vec![];
+            put_bytes(&mut label, 1, name.as_bytes());
+            put_bytes(&mut label, 2, value.as_bytes());
+            put_bytes(&mut time_series, 1, &label);
+        }
+
+        let mut sample = vec![];
+        put_key(&mut sample, 1, WIRE_FIXED64);
+        sample.extend_from_slice(&series.value.to_bits().to_le_bytes());
+        put_key(&mut sample, 2, WIRE_VARINT);
+        put_varint(&mut sample, timestamp_ms as u64);
+        put_bytes(&mut time_series, 2, &sample);
+
+        put_bytes(&mut request, 1, &time_series);
+    }
+    request
+}
+
+/// The protobuf wire types used by `write_request`.
+const WIRE_VARINT: u64 = 0;
+const WIRE_FIXED64: u64 = 1;
+const WIRE_LENGTH_DELIMITED: u64 = 2;
+
+fn put_key(buf: &mut Vec<u8>, field: u64, wire_type: u64) {
+    put_varint(buf, field << 3 | wire_type);
+}
+
+fn put_bytes(buf: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    put_key(buf, field, WIRE_LENGTH_DELIMITED);
+    put_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+fn put_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::AUTHORIZATION;
+    use hyper::service::service_fn_ok;
+    use hyper::{Body, Request, Response, Server};
+    use prometheus::{Gauge, Histogram, HistogramOpts, Opts};
+    use std::fs;
+    use std::sync::{Arc, Mutex};
+    use tempfile::TempDir;
+    use tokio::runtime::Runtime;
+
+    /// Pushes once to a local pushgateway with `auth`, returning the `Authorization` header it
+    /// received.
+    fn pushed_authorization(auth: Option<PushAuth>) -> Option<String> {
+        let received = Arc::new(Mutex::new(None));
+        let server_received = received.clone();
+        let server = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(move || {
+            let received = server_received.clone();
+            service_fn_ok(move |request: Request<Body>| {
+                *received.lock().unwrap() = request
+                    .headers()
+                    .get(AUTHORIZATION)
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::to_string);
+                Response::new(Body::empty())
+            })
+        });
+
+        let mut config =
+            MetricsPushConfig::new(format!("http://{}", server.local_addr()).parse().unwrap());
+        config.auth = auth;
+        let log = slog::Logger::root(slog::Discard, slog::o!());
+        let pusher =
+            MetricsPusher::new(config, Registry::new(), log).expect("should create pusher");
+        let request = pusher
+            .request(&pusher.config.url())
+            .expect("should build request");
+
+        let mut runtime = Runtime::new().expect("should create runtime");
+        runtime.spawn(server.map_err(|_| ()));
+        runtime
+            .block_on(
+                request
+                    .send()
+                    .and_then(|response| response.error_for_status()),
+            )
+            .expect("should push");
+
+        let authorization = received.lock().unwrap();
+        authorization.clone()
+    }
+
+    #[test]
+    fn parses_targets() {
+        assert_eq!(
+            "remote-write=https://prometheus.example/api/v1/write".parse(),
+            Ok(PushTarget {
+                kind: PushKind::RemoteWrite,
+                url: "https://prometheus.example/api/v1/write".to_string(),
+            })
+        );
+        assert_eq!(
+            "http://localhost:9091/".parse(),
+            Ok(PushTarget {
+                kind: PushKind::Pushgateway,
+                url: "http://localhost:9091".to_string(),
+            })
+        );
+        assert!("remote-write=localhost:9091".parse::<PushTarget>().is_err());
+    }
+
+    #[test]
+    fn pushgateway_urls_group_by_job_and_instance() {
+        let mut config =
+            MetricsPushConfig::new("pushgateway=http://localhost:9091".parse().unwrap());
+        assert_eq!(
+            config.url(),
+            "http://localhost:9091/metrics/job/lighthouse_validator"
+        );
+        config.instance = Some("vc-1".to_string());
+        assert_eq!(
+            config.url(),
+            "http://localhost:9091/metrics/job/lighthouse_validator/instance/vc-1"
+        );
+        assert!(is_valid_grouping_value("vc-1.example:5064"));
+        assert!(!is_valid_grouping_value("vc/1"));
+        assert!(!is_valid_grouping_value(""));
+    }
+
+    #[test]
+    fn reads_credentials() {
+        let dir = TempDir::new().expect("should create temp dir");
+        let path = dir.path().join("credentials");
+
+        fs::write(&path, "user:pass:word\n").expect("should write credentials");
+        assert_eq!(
+            PushAuth::Basic(path.clone()).read().ok(),
+            Some(Credentials::Basic {
+                user: "user".to_string(),
+                password: "pass:word".to_string(),
+            })
+        );
+        assert_eq!(
+            PushAuth::Bearer(path.clone()).read().ok(),
+            Some(Credentials::Bearer("user:pass:word".to_string()))
+        );
+
+        fs::write(&path, "token\n").expect("should write credentials");
+        assert!(PushAuth::Basic(path).read().is_err());
+    }
+
+    #[test]
+    fn sends_credentials() {
+        let dir = TempDir::new().expect("should create temp dir");
+        let path = dir.path().join("credentials");
+
+        assert_eq!(pushed_authorization(None), None);
+
+        fs::write(&path, "user:secret\n").expect("should write credentials");
+        assert_eq!(
+            pushed_authorization(Some(PushAuth::Basic(path.clone()))),
+            Some("Basic dXNlcjpzZWNyZXQ=".to_string())
+        );
+
+        fs::write(&path, "token\n").expect("should write credentials");
+        assert_eq!(
+            pushed_authorization(Some(PushAuth::Bearer(path))),
+            Some("Bearer token".to_string())
+        );
+    }
+
+    #[test]
+    fn series_labels_take_precedence() {
+        let registry = Registry::new();
+        let gauge = Gauge::with_opts(Opts::new("up", "help").const_label("job", "other")).unwrap();
+        registry.register(Box::new(gauge.clone())).unwrap();
+
+        let extra_labels = vec![
+            ("job".to_string(), "vc".to_string()),
+            ("instance".to_string(), "vc-1".to_string()),
+        ];
+        let label = |name: &str, value: &str| (name.to_string(), value.to_string());
+        assert_eq!(
+            series(&registry.gather(), &extra_labels),
+            vec![Series {
+                labels: vec![
+                    label("__name__", "up"),
+                    label("instance", "vc-1"),
+                    label("job", "other")
+                ],
+                value: 0.0,
+            }]
+        );
+    }
+
+    #[test]
+    fn flattens_histograms() {
+        let registry = Registry::new();
+        let histogram =
+            Histogram::with_opts(HistogramOpts::new("latency", "help").buckets(vec![0.5])).unwrap();
+        registry.register(Box::new(histogram.clone())).unwrap();
+        histogram.observe(0.25);
+        histogram.observe(2.0);
+
+        let job = vec![("job".to_string(), "vc".to_string())];
+        let label = |name: &str, value: &str| (name.to_string(), value.to_string());
+        let series = series(&registry.gather(), &job);
+        assert_eq!(
+            series,
+            vec![
+                Series {
+                    labels: vec![
+                        label("__name__", "latency_bucket"),
+                        label("job", "vc"),
+                        label("le", "0.5")
+                    ],
+                    value: 1.0,
+                },
+                Series {
+                    labels: vec![
+                        label("__name__", "latency_bucket"),
+                        label("job", "vc"),
+                        label("le", "+Inf")
+                    ],
+                    value: 2.0,
+                },
+                Series {
+                    labels: vec![label("__name__", "latency_sum"), label("job", "vc")],
+                    value: 2.25,
+                },
+                Series {
+                    labels: vec![label("__name__", "latency_count"), label("job", "vc")],
+                    value: 2.0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn encodes_write_requests() {
+        let registry = Registry::new();
+        let gauge = Gauge::new("up", "help").unwrap();
+        registry.register(Box::new(gauge.clone())).unwrap();
+        gauge.set(1.0);
+
+        let mut expected = vec![0x0a, 29, 0x0a, 14, 0x0a, 8];
+        expected.extend_from_slice(b"__name__");
+        expected.extend_from_slice(&[0x12, 2]);
+        expected.extend_from_slice(b"up");
+        expected.extend_from_slice(&[0x12, 11, 0x09]);
+        expected.extend_from_slice(&1.0_f64.to_bits().to_le_bytes());
+        expected.extend_from_slice(&[0x10, 5]);
+
+        assert_eq!(write_request(&series(&registry.gather(), &[]), 5), expected);
+    }
+
+    #[test]
+    fn encodes_varints() {
+        let varint = |value| {
+            let mut buf = vec![];
+            put_varint(&mut buf, value);
+            buf
+        };
+        assert_eq!(varint(0), vec![0]);
+        assert_eq!(varint(127), vec![127]);
+        assert_eq!(varint(300), vec![0xac, 0x02]);
+    }
+}
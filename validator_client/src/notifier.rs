@@ -0,0 +1,404 @@
+//! Sends notifications of significant events to webhooks, so that operators learn of problems
+//! without watching the logs.
+//!
+//! Slack and Discord webhooks receive the message of each event in their own format. Generic
+//! webhooks receive the `Event` as JSON, tagged with its `kind` and including a `message`:
+//!
+//! ```json
+//! {"kind": "missed_proposal", "validator": "0x..", "slot": 17, "reason": "..", "message": ".."}
+//! ```
+//!
+//! Notifications are best-effort: a webhook which fails is logged and the event is not resent.
+use futures::Future;
+use reqwest::r#async::Client;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::json;
+use slog::{debug, warn};
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+use types::{PublicKey, Slot};
+
+/// The maximum time to wait for a webhook to respond.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The number of slots for which the beacon node may be unreachable before a notification is
+/// sent.
+pub const DEFAULT_UNREACHABLE_SLOTS: u64 = 4;
+
+/// The format of the messages sent to a webhook.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookKind {
+    /// A Slack incoming webhook, which receives `{"text": ..}`.
+    Slack,
+    /// A Discord webhook, which receives `{"content": ..}`.
+    Discord,
+    /// Any HTTP endpoint, which receives the event as JSON.
+    Generic,
+}
+
+/// An endpoint to which every event is posted.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Webhook {
+    pub kind: WebhookKind,
+    pub url: String,
+}
+
+impl FromStr for Webhook {
+    type Err = String;
+
+    /// Parses `slack=URL`, `discord=URL`, `generic=URL` or a bare `URL`, which is generic.
+    fn from_str(s: &str) -> Result<Self, String> {
+        let mut parts = s.splitn(2, '=');
+        let (kind, url) = match (parts.next(), parts.next()) {
+            (Some("slack"), Some(url)) => (WebhookKind::Slack, url),
+            (Some("discord"), Some(url)) => (WebhookKind::Discord, url),
+            (Some("generic"), Some(url)) => (WebhookKind::Generic, url),
+            _ => (WebhookKind::Generic, s),
+        };
+
+        if url.starts_with("http://") || url.starts_with("https://") {
+            Ok(Self {
+                kind,
+                url: url.to_string(),
+            })
+        } else {
+            Err(format!("{} is not an HTTP(S) URL", url))
+        }
+    }
+}
+
+/// A significant event, of which operators are notified.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Event {
+    /// The block of `validator` at `slot` was not produced.
+    MissedProposal {
+        validator: String,
+        slot: Slot,
+        reason: String,
+    },
+    /// The signer of `validator` refused to sign a message at `slot`.
+    SignerRejection { validator: String, slot: Slot },
+    /// A message of `message_type` (block or attestation) of `validator` was not signed as it was
+    /// slashable.
+    SlashableMessageBlocked {
+        validator: String,
+        slot: Slot,
+        message_type: &'static str,
+    },
+    /// The beacon node has been unreachable for `slots` slots, since `since`.
+    BeaconNodeUnreachable { since: Slot, slots: u64 },
+    /// The beacon node reports `validator` as slashed.
+    ValidatorSlashed { validator: String },
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::MissedProposal {
+                validator,
+                slot,
+                reason,
+            } => write!(
+                f,
+                "Validator {} missed its proposal at slot {}: {}",
+                validator, slot, reason
+            ),
+            Event::SignerRejection { validator, slot } => write!(
+                f,
+                "The signer of validator {} refused to sign at slot {}",
+                validator, slot
+            ),
+            Event::SlashableMessageBlocked {
+                validator,
+                slot,
+                message_type,
+            } => write!(
+                f,
+                "Refused to sign a slashable {} of validator {} at slot {}",
+                message_type, validator, slot
+            ),
+            Event::BeaconNodeUnreachable { since, slots } => write!(
+                f,
+                "The beacon node has been unreachable for {} slots, since slot {}",
+                slots, since
+            ),
+            Event::ValidatorSlashed { validator } => {
+                write!(f, "Validator {} has been slashed", validator)
+            }
+        }
+    }
+}
+
+/// The body posted to a webhook of `kind` for `event`.
+fn payload(kind: WebhookKind, event: &Event) -> serde_json::Value {
+    let message = event.to_string();
+    match kind {
+        WebhookKind::Slack => json!({ "text": message }),
+        WebhookKind::Discord => json!({ "content": message }),
+        WebhookKind::Generic => {
+            let mut value = serde_json::to_value(event).unwrap_or_else(|_| json!({}));
+            if let Some(object) = value.as_object_mut() {
+                object.insert("message".to_string(), message.into());
+            }
+            value
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// The latest slot processed.
+    slot: Option<Slot>,
+    /// The slot in which the beacon node first failed, if it has failed since it was last reached.
+    unreachable_since: Option<Slot>,
+    /// `true` if the current outage has been notified.
+    unreachable_notified: bool,
+    /// The validators whose slashing has been notified.
+    slashed: HashSet<PublicKey>,
+}
+
+/// Notifies every webhook of significant events.
+///
+/// A notifier without webhooks does nothing, so it may always be passed to producers.
+pub struct Notifier {
+    webhooks: Vec<Webhook>,
+    client: Option<Client>,
+    /// The number of slots the beacon node may be unreachable before `BeaconNodeUnreachable`.
+    unreachable_slots: u64,
+    state: Mutex<State>,
+    log: slog::Logger,
+}
+
+impl Notifier {
+    pub fn new(
+        webhooks: Vec<Webhook>,
+        unreachable_slots: u64,
+        log: slog::Logger,
+    ) -> Result<Self, String> {
+        let client = if webhooks.is_empty() {
+            None
+        } else {
+            Some(
+                Client::builder()
+                    .timeout(REQUEST_TIMEOUT)
+                    .build()
+                    .map_err(|e| format!("Unable to build HTTP client: {:?}", e))?,
+            )
+        };
+
+        Ok(Self {
+            webhooks,
+            client,
+            unreachable_slots,
+            state: Mutex::new(State::default()),
+            log,
+        })
+    }
+
+    /// A notifier without webhooks.
+    pub fn disabled() -> Self {
+        Self {
+            webhooks: vec![],
+            client: None,
+            unreachable_slots: DEFAULT_UNREACHABLE_SLOTS,
+            state: Mutex::new(State::default()),
+            log: slog::Logger::root(slog::Discard, slog::o!()),
+        }
+    }
+
+    /// Posts `event` to every webhook.
+    ///
+    /// Must be called from within a runtime, on which the requests are spawned.
+    pub fn notify(&self, event: Event) {
+        let client = match &self.client {
+            Some(client) => client,
+            None => return,
+        };
+        debug!(self.log, "Notifying webhooks"; "event" => event.to_string());
+
+        for webhook in &self.webhooks {
+            let log = self.log.clone();
+            let url = webhook.url.clone();
+            tokio::spawn(
+                client
+                    .post(&webhook.url)
+                    .json(&payload(webhook.kind, &event))
+                    .send()
+                    .and_then(|response| response.error_for_status())
+                    .map(|_| ())
+                    .map_err(move |e| {
+                        warn!(log, "Unable to notify webhook"; "url" => url, "error" => e.to_string())
+                    }),
+            );
+        }
+    }
+
+    /// Records that `slot` has started, notifying if the beacon node has now been unreachable for
+    /// too long.
+    pub fn slot_processed(&self, slot: Slot) {
+        if let Some(event) = self.update(|state| {
+            state.slot = Some(slot);
+            match state.unreachable_since {
+                Some(since)
+                    if !state.unreachable_notified
+                        && slot.as_u64().saturating_sub(since.as_u64())
+                            >= self.unreachable_slots =>
+                {
+                    state.unreachable_notified = true;
+                    Some(Event::BeaconNodeUnreachable {
+                        since,
+                        slots: slot.as_u64() - since.as_u64(),
+                    })
+                }
+                _ => None,
+            }
+        }) {
+            self.notify(event)
+        }
+    }
+
+    /// Records a failed request to the beacon node.
+    pub fn beacon_node_failed(&self) {
+        self.update(|state| {
+            if state.unreachable_since.is_none() {
+                state.unreachable_since = state.slot;
+            }
+            Some(())
+        });
+    }
+
+    /// Records a successful request to the beacon node, ending any outage.
+    pub fn beacon_node_reached(&self) {
+        self.update(|state| {
+            state.unreachable_since = None;
+            state.unreachable_notified = false;
+            Some(())
+        });
+    }
+
+    /// Notifies of each of `validators` which the beacon node reports as slashed, unless their
+    /// slashing has already been notified.
+    pub fn validators_slashed(&self, validators: &[PublicKey]) {
+        let newly_slashed = self
+            .update(|state| {
+                Some(
+                    validators
+                        .iter()
+                        .filter(|validator| state.slashed.insert((*validator).clone()))
+                        .cloned()
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .unwrap_or_default();
+
+        for validator in newly_slashed {
+            self.notify(Event::ValidatorSlashed {
+                validator: validator.as_hex_string(),
+            })
+        }
+    }
+
+    fn update<T>(&self, f: impl FnOnce(&mut State) -> Option<T>) -> Option<T> {
+        self.state.lock().ok().and_then(|mut state| f(&mut state))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_webhooks() {
+        let webhook = |s: &str| s.parse::<Webhook>();
+        assert_eq!(
+            webhook("slack=https://hooks.slack.com/services/a"),
+            Ok(Webhook {
+                kind: WebhookKind::Slack,
+                url: "https://hooks.slack.com/services/a".to_string(),
+            })
+        );
+        assert_eq!(
+            webhook("discord=https://discord.com/api/webhooks/a").map(|w| w.kind),
+            Ok(WebhookKind::Discord)
+        );
+        assert_eq!(
+            webhook("http://localhost:8000/?a=b"),
+            Ok(Webhook {
+                kind: WebhookKind::Generic,
+                url: "http://localhost:8000/?a=b".to_string(),
+            })
+        );
+        assert!(webhook("slack=hooks.slack.com").is_err());
+    }
+
+    #[test]
+    fn formats_payloads() {
+        let event = Event::MissedProposal {
+            validator: "0xab".to_string(),
+            slot: Slot::new(17),
+            reason: "beacon node unable to produce a block".to_string(),
+        };
+        let message = event.to_string();
+
+        assert_eq!(
+            payload(WebhookKind::Slack, &event),
+            json!({ "text": message })
+        );
+        assert_eq!(
+            payload(WebhookKind::Discord, &event),
+            json!({ "content": message })
+        );
+        assert_eq!(
+            payload(WebhookKind::Generic, &event),
+            json!({
+                "kind": "missed_proposal",
+                "validator": "0xab",
+                "slot": 17,
+                "reason": "beacon node unable to produce a block",
+                "message": message,
+            })
+        );
+
+        // The fields of an event are not overwritten by the message.
+        let event = Event::SlashableMessageBlocked {
+            validator: "0xab".to_string(),
+            slot: Slot::new(17),
+            message_type: "block",
+        };
+        assert_eq!(
+            payload(WebhookKind::Generic, &event),
+            json!({
+                "kind": "slashable_message_blocked",
+                "validator": "0xab",
+                "slot": 17,
+                "message_type": "block",
+                "message": "Refused to sign a slashable block of validator 0xab at slot 17",
+            })
+        );
+    }
+
+    #[test]
+    fn notifies_outages_once() {
+        let notifier = Notifier::disabled();
+        let unreachable = |slot| {
+            notifier.slot_processed(Slot::new(slot));
+            let state = notifier.state.lock().unwrap();
+            (state.unreachable_since, state.unreachable_notified)
+        };
+
+        assert_eq!(unreachable(1), (None, false));
+        notifier.beacon_node_failed();
+        assert_eq!(unreachable(2), (Some(Slot::new(1)), false));
+        notifier.beacon_node_failed();
+        assert_eq!(unreachable(4), (Some(Slot::new(1)), false));
+        assert_eq!(unreachable(5), (Some(Slot::new(1)), true));
+
+        notifier.beacon_node_reached();
+        assert_eq!(unreachable(6), (None, false));
+    }
+}
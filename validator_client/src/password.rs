@@ -0,0 +1,177 @@
+//! Finds the password of each validator keystore, so that many keystores may be decrypted without
+//! a single shared plaintext password.
+//!
+//! The password of the keystore of a validator is the first found of:
+//!
+//! 1. The file in the secrets directory named by the validator's public key (e.g., `0xa1b2..`).
+//! 2. The OS keyring entry of the validator's public key, if enabled. Entries have the service
+//!    `lighthouse-validator` and the public key as the account, and are read with `secret-tool` on
+//!    Linux and `security` on macOS.
+//! 3. The shared keystore password file, if any.
+//! 4. A password typed at the terminal, in interactive mode.
+//!
+//! A trailing newline is not considered part of a password file.
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use thiserror::Error;
+use types::PublicKey;
+
+/// The service of the OS keyring entries holding keystore passwords.
+pub const KEYRING_SERVICE: &str = "lighthouse-validator";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    /// A password file exists but could not be read.
+    #[error("unable to read the password file {path:?}: {source}")]
+    File {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    /// The OS keyring could not be queried.
+    #[error("unable to query the OS keyring: {0}")]
+    Keyring(#[source] io::Error),
+    /// The password could not be read from the terminal.
+    #[error("unable to read the password from the terminal: {0}")]
+    Prompt(#[source] io::Error),
+}
+
+/// The places in which keystore passwords are looked for, in order.
+///
+/// Not `Debug`, so that the shared password is not logged.
+#[derive(Clone, Default)]
+pub struct PasswordSources {
+    /// A directory of files named by public key, each containing the password of that validator.
+    pub secrets_dir: Option<PathBuf>,
+    /// If `true`, passwords are looked for in the OS keyring.
+    pub keyring: bool,
+    /// The password of every keystore without a password of its own.
+    pub shared: Option<String>,
+    /// If `true`, the password of a keystore which is not found elsewhere is asked for at the
+    /// terminal.
+    pub interactive: bool,
+}
+
+impl PasswordSources {
+    /// Returns the password of the keystore of `validator`, or `None` if there is none.
+    pub fn password(&self, validator: &PublicKey) -> Result<Option<String>, Error> {
+        if let Some(path) = self.secrets_file(validator) {
+            if path.exists() {
+                return read_password_file(&path)
+                    .map(Some)
+                    .map_err(|source| Error::File { path, source });
+            }
+        }
+
+        if self.keyring {
+            if let Some(password) = keyring_password(&validator.as_hex_string())? {
+                return Ok(Some(password));
+            }
+        }
+
+        if let Some(password) = &self.shared {
+            return Ok(Some(password.clone()));
+        }
+
+        if self.interactive {
+            let prompt = format!("Password for validator {}: ", validator.as_hex_string());
+            return rpassword::read_password_from_tty(Some(&prompt))
+                .map(Some)
+                .map_err(Error::Prompt);
+        }
+
+        Ok(None)
+    }
+
+    /// Returns the file in the secrets directory which holds the password of `validator`.
+    pub fn secrets_file(&self, validator: &PublicKey) -> Option<PathBuf> {
+        self.secrets_dir
+            .as_ref()
+            .map(|dir| dir.join(validator.as_hex_string()))
+    }
+
+    /// Returns `true` if a password may be found for a keystore which has no file in the secrets
+    /// directory.
+    pub fn has_fallback(&self) -> bool {
+        self.keyring || self.shared.is_some() || self.interactive
+    }
+}
+
+/// Reads the password in the file at `path`, without any trailing newline.
+pub fn read_password_file(path: &Path) -> io::Result<String> {
+    let password = fs::read_to_string(path)?;
+    Ok(password
+        .trim_end_matches(|c| c == '\n' || c == '\r')
+        .to_string())
+}
+
+/// Returns the password stored in the OS keyring for `account`, if any.
+fn keyring_password(account: &str) -> Result<Option<String>, Error> {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = Command::new("security");
+        command.args(&[
+            "find-generic-password",
+            "-s",
+            KEYRING_SERVICE,
+            "-a",
+            account,
+            "-w",
+        ]);
+        command
+    } else {
+        let mut command = Command::new("secret-tool");
+        command.args(&["lookup", "service", KEYRING_SERVICE, "account", account]);
+        command
+    };
+
+    let output = command.output().map_err(Error::Keyring)?;
+    // Both tools exit with an error if there is no such entry.
+    if !output.status.success() {
+        return Ok(None);
+    }
+    Ok(Some(
+        String::from_utf8_lossy(&output.stdout)
+            .trim_end_matches(|c| c == '\n' || c == '\r')
+            .to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use types::Keypair;
+
+    #[test]
+    fn prefers_the_password_file_of_the_validator() {
+        let dir = TempDir::new().expect("should create temp dir");
+        let with_file = Keypair::random().pk;
+        let without_file = Keypair::random().pk;
+        fs::write(dir.path().join(with_file.as_hex_string()), "own\n")
+            .expect("should write password");
+
+        let mut sources = PasswordSources {
+            secrets_dir: Some(dir.path().to_path_buf()),
+            ..PasswordSources::default()
+        };
+        assert!(!sources.has_fallback());
+        assert_eq!(
+            sources.password(&with_file).ok(),
+            Some(Some("own".to_string()))
+        );
+        assert_eq!(sources.password(&without_file).ok(), Some(None));
+
+        sources.shared = Some("shared".to_string());
+        assert!(sources.has_fallback());
+        assert_eq!(
+            sources.password(&with_file).ok(),
+            Some(Some("own".to_string()))
+        );
+        assert_eq!(
+            sources.password(&without_file).ok(),
+            Some(Some("shared".to_string()))
+        );
+    }
+}
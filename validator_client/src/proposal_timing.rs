@@ -0,0 +1,280 @@
+//! The timing of each block proposal, measured from the start of its slot, so that operators can
+//! tell whether a slow proposal was due to the beacon node (or builder), the signer or publishing.
+//!
+//! Each stage is observed in a Prometheus histogram and the most recent proposals are kept in a
+//! ring buffer, served as JSON on `/proposal_timings`:
+//!
+//! ```json
+//! [{"slot": 17, "validator": "0x..", "received_ms": 210, "signed_ms": 240, "published_ms": 630}]
+//! ```
+//!
+//! A stage which was not reached (e.g., the block was never published) is `null`.
+//!
+//! The outcome of the latest proposal of each validator is also kept, for
+//! `/lighthouse/proposals` (see `debug_api`).
+use prometheus::{Histogram, HistogramOpts, Registry};
+use serde_derive::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use types::{PublicKey, Slot};
+
+pub use prometheus::Error;
+
+/// The number of proposals kept in the ring buffer.
+pub const DEFAULT_CAPACITY: usize = 64;
+
+/// The upper bounds of the histogram buckets, in seconds. A proposal is due within a slot, so the
+/// buckets are finest early in the slot.
+const BUCKETS: &[f64] = &[
+    0.05, 0.1, 0.25, 0.5, 0.75, 1.0, 1.5, 2.0, 3.0, 4.0, 6.0, 8.0, 12.0,
+];
+
+/// A stage of a proposal.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Stage {
+    /// The block (or blinded block header) was received from the beacon node (or builder).
+    Received,
+    /// The block was signed.
+    Signed,
+    /// The beacon node (or builder) acknowledged the published block.
+    Published,
+}
+
+/// The time from the start of the slot to each stage of a single proposal.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct ProposalTiming {
+    pub slot: Slot,
+    pub validator: String,
+    pub received_ms: Option<u64>,
+    pub signed_ms: Option<u64>,
+    pub published_ms: Option<u64>,
+}
+
+impl ProposalTiming {
+    fn stage_mut(&mut self, stage: Stage) -> &mut Option<u64> {
+        match stage {
+            Stage::Received => &mut self.received_ms,
+            Stage::Signed => &mut self.signed_ms,
+            Stage::Published => &mut self.published_ms,
+        }
+    }
+}
+
+/// The outcome of the latest proposal of a single validator.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct ProposalOutcome {
+    pub slot: Slot,
+    pub validator: String,
+    /// The `ValidatorEvent` of the proposal, or the error which prevented it.
+    pub outcome: String,
+}
+
+/// Records the timing of every proposal.
+pub struct ProposalTimings {
+    received: Histogram,
+    signed: Histogram,
+    published: Histogram,
+    capacity: usize,
+    recent: Mutex<VecDeque<ProposalTiming>>,
+    /// The latest outcome of each validator, keyed by its hex public key.
+    outcomes: Mutex<HashMap<String, ProposalOutcome>>,
+}
+
+impl ProposalTimings {
+    /// Create timings which keep the `capacity` most recent proposals.
+    pub fn new(capacity: usize) -> Result<Self, Error> {
+        let histogram = |name: &str, help: &str| {
+            Histogram::with_opts(HistogramOpts::new(name, help).buckets(BUCKETS.to_vec()))
+        };
+
+        Ok(Self {
+            received: histogram(
+                "proposal_block_received_seconds",
+                "time_from_slot_start_to_block_received",
+            )?,
+            signed: histogram(
+                "proposal_block_signed_seconds",
+                "time_from_slot_start_to_block_signed",
+            )?,
+            published: histogram(
+                "proposal_block_published_seconds",
+                "time_from_slot_start_to_block_publish_acknowledged",
+            )?,
+            capacity: capacity.max(1),
+            recent: Mutex::new(VecDeque::new()),
+            outcomes: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Registers the histograms with `registry`.
+    pub fn register(&self, registry: &Registry) -> Result<(), Error> {
+        registry.register(Box::new(self.received.clone()))?;
+        registry.register(Box::new(self.signed.clone()))?;
+        registry.register(Box::new(self.published.clone()))?;
+        Ok(())
+    }
+
+    /// Records that the proposal of `validator` at `slot`, which started at `slot_start`, has
+    /// reached `stage`.
+    pub fn record(&self, slot: Slot, validator: &PublicKey, slot_start: Instant, stage: Stage) {
+        let now = Instant::now();
+        let elapsed = if now > slot_start {
+            now - slot_start
+        } else {
+            Duration::from_secs(0)
+        };
+        self.record_elapsed(slot, validator.as_hex_string(), stage, elapsed)
+    }
+
+    fn record_elapsed(&self, slot: Slot, validator: String, stage: Stage, elapsed: Duration) {
+        let histogram = match stage {
+            Stage::Received => &self.received,
+            Stage::Signed => &self.signed,
+            Stage::Published => &self.published,
+        };
+        histogram.observe(elapsed.as_secs_f64());
+
+        if let Ok(mut recent) = self.recent.lock() {
+            let position = recent
+                .iter()
+                .rposition(|timing| timing.slot == slot && timing.validator == validator);
+            let timing = match position {
+                Some(position) => &mut recent[position],
+                None => {
+                    if recent.len() >= self.capacity {
+                        recent.pop_front();
+                    }
+                    recent.push_back(ProposalTiming {
+                        slot,
+                        validator,
+                        received_ms: None,
+                        signed_ms: None,
+                        published_ms: None,
+                    });
+                    recent.back_mut().expect("a timing was just pushed")
+                }
+            };
+            *timing.stage_mut(stage) = Some(elapsed.as_millis() as u64);
+        }
+    }
+
+    /// Records the `outcome` of the proposal of `validator` at `slot`, unless a later proposal of
+    /// the validator has already been recorded.
+    pub fn record_outcome(&self, slot: Slot, validator: &PublicKey, outcome: String) {
+        let validator = validator.as_hex_string();
+        if let Ok(mut outcomes) = self.outcomes.lock() {
+            if outcomes
+                .get(&validator)
+                .map_or(true, |latest| latest.slot <= slot)
+            {
+                outcomes.insert(
+                    validator.clone(),
+                    ProposalOutcome {
+                        slot,
+                        validator,
+                        outcome,
+                    },
+                );
+            }
+        }
+    }
+
+    /// Returns the outcome of the latest proposal of each validator, ordered by validator.
+    pub fn outcomes(&self) -> Vec<ProposalOutcome> {
+        let mut outcomes: Vec<ProposalOutcome> = self
+            .outcomes
+            .lock()
+            .map(|outcomes| outcomes.values().cloned().collect())
+            .unwrap_or_default();
+        outcomes.sort_by(|a, b| a.validator.cmp(&b.validator));
+        outcomes
+    }
+
+    /// Returns the most recent proposals, oldest first.
+    pub fn recent(&self) -> Vec<ProposalTiming> {
+        self.recent
+            .lock()
+            .map(|recent| recent.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+impl Default for ProposalTimings {
+    /// Timings which keep the `DEFAULT_CAPACITY` most recent proposals.
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY).expect("the histogram options are valid")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use types::Keypair;
+
+    #[test]
+    fn keeps_the_most_recent_proposals() {
+        let timings = ProposalTimings::new(2).expect("should create timings");
+        let record = |slot, stage, ms| {
+            timings.record_elapsed(
+                Slot::new(slot),
+                "0xab".to_string(),
+                stage,
+                Duration::from_millis(ms),
+            )
+        };
+
+        record(1, Stage::Received, 100);
+        record(1, Stage::Signed, 150);
+        record(2, Stage::Received, 200);
+        record(2, Stage::Signed, 250);
+        record(2, Stage::Published, 600);
+        record(3, Stage::Received, 300);
+
+        assert_eq!(
+            timings.recent(),
+            vec![
+                ProposalTiming {
+                    slot: Slot::new(2),
+                    validator: "0xab".to_string(),
+                    received_ms: Some(200),
+                    signed_ms: Some(250),
+                    published_ms: Some(600),
+                },
+                ProposalTiming {
+                    slot: Slot::new(3),
+                    validator: "0xab".to_string(),
+                    received_ms: Some(300),
+                    signed_ms: None,
+                    published_ms: None,
+                },
+            ]
+        );
+        assert_eq!(timings.received.get_sample_count(), 3);
+        assert_eq!(timings.published.get_sample_count(), 1);
+
+        let registry = Registry::new();
+        timings.register(&registry).expect("should register");
+        assert_eq!(registry.gather().len(), 3);
+    }
+
+    #[test]
+    fn keeps_the_latest_outcome_of_each_validator() {
+        let timings = ProposalTimings::new(2).expect("should create timings");
+        let validator = Keypair::random().pk;
+
+        timings.record_outcome(Slot::new(3), &validator, "BlockProduced(3)".to_string());
+        timings.record_outcome(Slot::new(9), &validator, "SignerRejection(9)".to_string());
+        // A proposal which completes after a later one does not replace it.
+        timings.record_outcome(Slot::new(5), &validator, "BlockProduced(5)".to_string());
+
+        assert_eq!(
+            timings.outcomes(),
+            vec![ProposalOutcome {
+                slot: Slot::new(9),
+                validator: validator.as_hex_string(),
+                outcome: "SignerRejection(9)".to_string(),
+            }]
+        );
+    }
+}
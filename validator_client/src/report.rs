@@ -0,0 +1,529 @@
+//! A report of the performance of validators over recent epochs, for the `report` subcommand.
+//!
+//! The duties of the validators during each epoch, and their balances at its start, are requested
+//! from the history of the beacon node. The canonical blocks from the start of the first epoch to
+//! the end of the inclusion windows of the last are then searched for the attestations of the
+//! validators, as by the inclusion `Tracker`. A proposal was made if the canonical chain has a
+//! block at its slot (which only its proposer could have produced), and missed otherwise.
+//!
+//! Only epochs whose inclusion windows have passed at the head of the beacon node are reported, so
+//! that no attestation is reported as missed whilst it may still be included.
+use crate::attestation_producer::{BeaconNodeAttestation, BlockAttestations};
+use crate::block_producer::BeaconNodeError;
+use crate::chain_split::BeaconNodeChainHead;
+use crate::inclusion::Tracker;
+use crate::service::Connector;
+use futures::Future;
+use protos::auth::Authenticated;
+use protos::services::{GetValidatorHistoryRequest, Validators};
+use protos::services_grpc::{
+    AttestationServiceClient, BeaconNodeServiceClient, ValidatorServiceClient,
+};
+use serde_derive::Serialize;
+use ssz::ssz_encode;
+use std::collections::HashMap;
+use std::fmt;
+use thiserror::Error;
+use types::{AttestationDuty, ChainSpec, Epoch, EthSpec, PublicKey, Slot};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("{0}")]
+    BeaconNode(#[from] BeaconNodeError),
+    #[error("no epoch has passed its inclusion window at the head of the beacon node (slot {0})")]
+    NoCompleteEpochs(Slot),
+    #[error("the beacon node has no state at the start of epoch {0} (it may have been pruned)")]
+    UnknownEpoch(Epoch),
+    #[error("the beacon node has no block at slot {0}")]
+    UnknownSlot(Slot),
+}
+
+/// The record of a validator at the start of an epoch.
+#[derive(Debug, PartialEq, Clone)]
+pub struct EpochRecord {
+    /// The balance of the validator at the start of the epoch, in Gwei.
+    pub balance: u64,
+    /// The attestation duty of the validator during the epoch, or `None` if it was inactive.
+    pub attestation_duty: Option<AttestationDuty>,
+    /// The slots at which the validator was to propose during the epoch.
+    pub proposal_slots: Vec<Slot>,
+}
+
+/// Defines the methods required to obtain the history of validators from a Beacon Node.
+pub trait BeaconNodeHistory: Send + Sync {
+    /// Returns the record at `epoch` of each of `pub_keys` in the registry at its start, or `None`
+    /// if the node has no state at its start.
+    fn validator_history(
+        &self,
+        epoch: Epoch,
+        pub_keys: &[PublicKey],
+    ) -> Result<Option<HashMap<PublicKey, EpochRecord>>, BeaconNodeError>;
+}
+
+impl BeaconNodeHistory for Authenticated<ValidatorServiceClient> {
+    fn validator_history(
+        &self,
+        epoch: Epoch,
+        pub_keys: &[PublicKey],
+    ) -> Result<Option<HashMap<PublicKey, EpochRecord>>, BeaconNodeError> {
+        let mut req = GetValidatorHistoryRequest::new();
+        req.set_epoch(epoch.as_u64());
+        let mut validators = Validators::new();
+        validators.set_public_keys(pub_keys.iter().map(|v| ssz_encode(v)).collect());
+        req.set_validators(validators);
+
+        let reply = self
+            .client()
+            .get_validator_history_opt(&req, self.call_option())
+            .map_err(|err| BeaconNodeError::RemoteFailure(format!("{:?}", err)))?;
+        if !reply.get_known() {
+            return Ok(None);
+        }
+
+        let mut records = HashMap::new();
+        for pub_key in pub_keys {
+            let history = match reply.get_validators().get(&pub_key.as_hex_string()) {
+                Some(history) => history,
+                // not in the registry
+                None => continue,
+            };
+            let duties = history.get_duties();
+            let (attestation_duty, proposal_slots) = if duties.has_duty() {
+                let duty = duties.get_duty();
+                let attestation_duty = AttestationDuty {
+                    slot: Slot::from(duty.get_attestation_slot()),
+                    shard: duty.get_attestation_shard(),
+                    committee_index: duty.get_committee_index() as usize,
+                    committee_len: duty.get_committee_len() as usize,
+                };
+                let proposal_slots = duty
+                    .get_block_production_slots()
+                    .iter()
+                    .map(|slot| Slot::from(*slot))
+                    .collect();
+                (Some(attestation_duty), proposal_slots)
+            } else {
+                (None, vec![])
+            };
+            records.insert(
+                pub_key.clone(),
+                EpochRecord {
+                    balance: history.get_balance(),
+                    attestation_duty,
+                    proposal_slots,
+                },
+            );
+        }
+        Ok(Some(records))
+    }
+}
+
+/// The performance of a single validator over the reported epochs.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct ValidatorPerformance {
+    pub pubkey: PublicKey,
+    /// The number of attestations due, whether included or not.
+    pub attestations: u64,
+    pub included: u64,
+    /// The fraction of due attestations which were included, or `None` if none were due.
+    pub inclusion_rate: Option<f64>,
+    /// The mean inclusion delay of included attestations, in slots.
+    pub mean_inclusion_delay: Option<f64>,
+    pub proposals: u64,
+    pub missed_proposals: u64,
+    /// The change in balance from the start of the first reported epoch to the end of the last, in
+    /// Gwei, or `None` if the validator was not in the registry throughout.
+    pub balance_delta: Option<i64>,
+}
+
+/// The performance of every validator over a range of epochs.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct PerformanceReport {
+    /// The first reported epoch.
+    pub start_epoch: Epoch,
+    /// The epoch after the last reported epoch.
+    pub end_epoch: Epoch,
+    pub validators: Vec<ValidatorPerformance>,
+}
+
+impl PerformanceReport {
+    /// Builds the report of the epochs from `start_epoch`, given the `records` at the start of each
+    /// of them and of the epoch after the last, and the canonical blocks in order from the start of
+    /// `start_epoch` to the end of the inclusion windows of the last epoch.
+    ///
+    /// The validators are reported in the order of `pub_keys`.
+    pub fn build<E: EthSpec>(
+        start_epoch: Epoch,
+        records: &[HashMap<PublicKey, EpochRecord>],
+        blocks: &[(Slot, BlockAttestations<E>)],
+        pub_keys: &[PublicKey],
+        spec: &ChainSpec,
+    ) -> Self {
+        let end_epoch = start_epoch + records.len().saturating_sub(1) as u64;
+        let reported = &records[..records.len().saturating_sub(1)];
+
+        let mut tracker = Tracker::new(E::slots_per_epoch(), spec.min_attestation_inclusion_delay);
+        let mut attestations: HashMap<&PublicKey, u64> = HashMap::new();
+        let mut proposals: HashMap<Slot, &PublicKey> = HashMap::new();
+        for epoch_records in reported {
+            for (pub_key, record) in epoch_records {
+                if let Some(duty) = record.attestation_duty {
+                    tracker.attested(pub_key.clone(), duty);
+                    *attestations.entry(pub_key).or_default() += 1;
+                }
+                for slot in &record.proposal_slots {
+                    proposals.insert(*slot, pub_key);
+                }
+            }
+        }
+
+        let mut made: HashMap<&PublicKey, u64> = HashMap::new();
+        let mut missed: HashMap<&PublicKey, u64> = HashMap::new();
+        for (slot, block) in blocks {
+            tracker.process_block(*slot, block);
+            if let Some(pub_key) = proposals.get(slot) {
+                let count = if block.skipped {
+                    missed.entry(*pub_key).or_default()
+                } else {
+                    made.entry(*pub_key).or_default()
+                };
+                *count += 1;
+            }
+        }
+
+        let inclusion = tracker.report();
+        let validators = pub_keys
+            .iter()
+            .map(|pub_key| {
+                let effectiveness = inclusion
+                    .validators
+                    .iter()
+                    .find(|validator| validator.pubkey == *pub_key)
+                    .map(|validator| &validator.effectiveness);
+                let due = attestations.get(pub_key).cloned().unwrap_or(0);
+                let included = effectiveness.map_or(0, |effectiveness| effectiveness.included);
+                let balance = |records: Option<&HashMap<PublicKey, EpochRecord>>| {
+                    records
+                        .and_then(|records| records.get(pub_key))
+                        .map(|record| record.balance as i64)
+                };
+
+                ValidatorPerformance {
+                    pubkey: pub_key.clone(),
+                    attestations: due,
+                    included,
+                    inclusion_rate: if due > 0 {
+                        Some(included as f64 / due as f64)
+                    } else {
+                        None
+                    },
+                    mean_inclusion_delay: effectiveness
+                        .filter(|effectiveness| effectiveness.included > 0)
+                        .map(|effectiveness| effectiveness.mean_inclusion_delay),
+                    proposals: made.get(pub_key).cloned().unwrap_or(0),
+                    missed_proposals: missed.get(pub_key).cloned().unwrap_or(0),
+                    balance_delta: match (balance(records.first()), balance(records.last())) {
+                        (Some(start), Some(end)) => Some(end - start),
+                        _ => None,
+                    },
+                }
+            })
+            .collect();
+
+        Self {
+            start_epoch,
+            end_epoch,
+            validators,
+        }
+    }
+}
+
+/// Prints the report as a table, with a row for each validator.
+impl fmt::Display for PerformanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Epochs {} to {}",
+            self.start_epoch,
+            self.end_epoch.as_u64().saturating_sub(1)
+        )?;
+        writeln!(
+            f,
+            "{:<12} {:>12} {:>9} {:>10} {:>10} {:>9} {:>15}",
+            "validator",
+            "attestations",
+            "inclusion",
+            "mean delay",
+            "proposals",
+            "missed",
+            "balance delta"
+        )?;
+        for validator in &self.validators {
+            let hex = validator.pubkey.as_hex_string();
+            let optional = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+            writeln!(
+                f,
+                "{:<12} {:>12} {:>9} {:>10} {:>10} {:>9} {:>15}",
+                &hex[..hex.len().min(12)],
+                format!("{}/{}", validator.included, validator.attestations),
+                optional(
+                    validator
+                        .inclusion_rate
+                        .map(|rate| format!("{:.1}%", rate * 100.0))
+                ),
+                optional(
+                    validator
+                        .mean_inclusion_delay
+                        .map(|delay| format!("{:.2}", delay))
+                ),
+                validator.proposals,
+                validator.missed_proposals,
+                optional(validator.balance_delta.map(|delta| format!("{:+}", delta))),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The largest number of epochs which may be reported, well within the history served by the
+/// beacon node.
+pub const MAX_REPORT_EPOCHS: u64 = 64;
+
+/// Returns the first and the after-last of the latest `epochs` epochs whose inclusion windows have
+/// passed at `head_slot`, or `None` if there are none.
+pub fn reported_epochs(
+    head_slot: Slot,
+    epochs: u64,
+    slots_per_epoch: u64,
+) -> Option<(Epoch, Epoch)> {
+    // The attestations of an epoch may be included until the end of the next epoch.
+    let passed_epochs = (head_slot.as_u64() + 1) / slots_per_epoch;
+    let end_epoch = passed_epochs.checked_sub(1).filter(|epoch| *epoch > 0)?;
+    let start_epoch = end_epoch.saturating_sub(epochs);
+    Some((Epoch::new(start_epoch), Epoch::new(end_epoch)))
+}
+
+/// Requests the history of `pub_keys` over the latest `epochs` complete epochs from the beacon node
+/// at `server`, and reports their performance.
+pub fn fetch<E: EthSpec>(
+    connector: &Connector,
+    server: &str,
+    epochs: u64,
+    pub_keys: &[PublicKey],
+    spec: &ChainSpec,
+) -> Result<PerformanceReport, Error> {
+    let beacon_node = connector.connect(server, BeaconNodeServiceClient::new);
+    let validator_client = connector.connect(server, ValidatorServiceClient::new);
+    let attestation_client = connector.connect(server, AttestationServiceClient::new);
+
+    let head_slot = beacon_node.chain_head()?.slot;
+    let (start_epoch, end_epoch) = reported_epochs(head_slot, epochs, E::slots_per_epoch())
+        .ok_or_else(|| Error::NoCompleteEpochs(head_slot))?;
+
+    let records = (start_epoch.as_u64()..=end_epoch.as_u64())
+        .map(Epoch::new)
+        .map(|epoch| {
+            validator_client
+                .validator_history(epoch, pub_keys)?
+                .ok_or_else(|| Error::UnknownEpoch(epoch))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let start_slot = start_epoch.start_slot(E::slots_per_epoch());
+    let end_slot = (end_epoch + 1).start_slot(E::slots_per_epoch());
+    let blocks = (start_slot.as_u64()..end_slot.as_u64())
+        .map(Slot::new)
+        .map(|slot| {
+            attestation_client
+                .block_attestations::<E>(slot)
+                .wait()?
+                .map(|block| (slot, block))
+                .ok_or_else(|| Error::UnknownSlot(slot))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(PerformanceReport::build(
+        start_epoch,
+        &records,
+        &blocks,
+        pub_keys,
+        spec,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use types::{
+        AggregateSignature, Attestation, AttestationData, BitList, Checkpoint, Crosslink, Hash256,
+        Keypair, MinimalEthSpec,
+    };
+
+    fn duty(slot: u64, committee_index: usize) -> AttestationDuty {
+        AttestationDuty {
+            slot: Slot::new(slot),
+            shard: 1,
+            committee_index,
+            committee_len: 4,
+        }
+    }
+
+    fn attestation(duty: &AttestationDuty) -> Attestation<MinimalEthSpec> {
+        let mut aggregation_bits =
+            BitList::with_capacity(duty.committee_len).expect("should create bits");
+        aggregation_bits
+            .set(duty.committee_index, true)
+            .expect("should set bit");
+
+        Attestation {
+            aggregation_bits,
+            data: AttestationData {
+                beacon_block_root: Hash256::zero(),
+                source: Checkpoint::default(),
+                target: Checkpoint {
+                    epoch: duty.slot.epoch(MinimalEthSpec::slots_per_epoch()),
+                    root: Hash256::zero(),
+                },
+                crosslink: Crosslink {
+                    shard: duty.shard,
+                    ..Crosslink::default()
+                },
+            },
+            custody_bits: BitList::with_capacity(duty.committee_len).expect("should create bits"),
+            signature: AggregateSignature::new(),
+        }
+    }
+
+    fn record(balance: u64, duty: Option<AttestationDuty>, proposals: &[u64]) -> EpochRecord {
+        EpochRecord {
+            balance,
+            attestation_duty: duty,
+            proposal_slots: proposals.iter().cloned().map(Slot::new).collect(),
+        }
+    }
+
+    #[test]
+    fn reports_only_epochs_past_their_inclusion_window() {
+        // With 8 slots per epoch, the attestations of epoch 1 may be included until slot 23.
+        assert_eq!(
+            reported_epochs(Slot::new(22), 4, 8),
+            Some((Epoch::new(0), Epoch::new(1)))
+        );
+        assert_eq!(
+            reported_epochs(Slot::new(23), 4, 8),
+            Some((Epoch::new(0), Epoch::new(2)))
+        );
+        assert_eq!(
+            reported_epochs(Slot::new(95), 4, 8),
+            Some((Epoch::new(7), Epoch::new(11)))
+        );
+        assert_eq!(reported_epochs(Slot::new(14), 4, 8), None);
+    }
+
+    #[test]
+    fn reports_inclusion_proposals_and_balances() {
+        let spec = MinimalEthSpec::default_spec();
+        let slots_per_epoch = MinimalEthSpec::slots_per_epoch();
+        let good = Keypair::random().pk;
+        let bad = Keypair::random().pk;
+        let pending = Keypair::random().pk;
+
+        // Two epochs, in which `good` attests on time and proposes, whilst `bad` misses one of its
+        // attestations and its proposal. `pending` is activated after the reported epochs.
+        let start = slots_per_epoch * 2;
+        let mut first = HashMap::new();
+        first.insert(
+            good.clone(),
+            record(32_000, Some(duty(start, 0)), &[start + 2]),
+        );
+        first.insert(
+            bad.clone(),
+            record(32_000, Some(duty(start, 1)), &[start + 3]),
+        );
+        let mut second = HashMap::new();
+        second.insert(
+            good.clone(),
+            record(32_010, Some(duty(start + slots_per_epoch, 0)), &[]),
+        );
+        second.insert(
+            bad.clone(),
+            record(31_995, Some(duty(start + slots_per_epoch, 1)), &[]),
+        );
+        let mut last = HashMap::new();
+        last.insert(good.clone(), record(32_020, None, &[]));
+        last.insert(bad.clone(), record(31_990, None, &[]));
+        last.insert(pending.clone(), record(32_000, None, &[]));
+
+        let blocks: Vec<_> = (start..start + 3 * slots_per_epoch)
+            .map(|slot| {
+                let mut attestations = vec![];
+                if slot == start + 1 {
+                    attestations.push(attestation(&duty(start, 0)));
+                    attestations.push(attestation(&duty(start, 1)));
+                }
+                if slot == start + slots_per_epoch + 2 {
+                    attestations.push(attestation(&duty(start + slots_per_epoch, 0)));
+                }
+                let block = BlockAttestations {
+                    block_root: Hash256::from_low_u64_be(slot),
+                    skipped: slot == start + 3,
+                    attestations,
+                };
+                (Slot::new(slot), block)
+            })
+            .collect();
+
+        let report = PerformanceReport::build(
+            Epoch::new(2),
+            &[first, second, last],
+            &blocks,
+            &[good.clone(), bad.clone(), pending.clone()],
+            &spec,
+        );
+        assert_eq!(
+            (report.start_epoch, report.end_epoch),
+            (Epoch::new(2), Epoch::new(4))
+        );
+        assert_eq!(
+            report.validators,
+            vec![
+                ValidatorPerformance {
+                    pubkey: good,
+                    attestations: 2,
+                    included: 2,
+                    inclusion_rate: Some(1.0),
+                    mean_inclusion_delay: Some(1.5),
+                    proposals: 1,
+                    missed_proposals: 0,
+                    balance_delta: Some(20),
+                },
+                ValidatorPerformance {
+                    pubkey: bad,
+                    attestations: 2,
+                    included: 1,
+                    inclusion_rate: Some(0.5),
+                    mean_inclusion_delay: Some(1.0),
+                    proposals: 0,
+                    missed_proposals: 1,
+                    balance_delta: Some(-10),
+                },
+                ValidatorPerformance {
+                    pubkey: pending,
+                    attestations: 0,
+                    included: 0,
+                    inclusion_rate: None,
+                    mean_inclusion_delay: None,
+                    proposals: 0,
+                    missed_proposals: 0,
+                    balance_delta: None,
+                },
+            ]
+        );
+
+        let table = report.to_string();
+        assert_eq!(table.lines().count(), 5);
+        assert!(table.lines().nth(2).unwrap().contains("2/2"));
+        assert!(table.lines().nth(3).unwrap().ends_with("-10"));
+        assert!(serde_json::to_string(&report).is_ok());
+    }
+}
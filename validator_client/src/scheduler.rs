@@ -0,0 +1,220 @@
+//! Runs duties with deadlines derived from the slot clock.
+//!
+//! A duty is worthless once its deadline passes (e.g., a block published after the end of its
+//! slot is orphaned), so a task which exceeds its deadline is cancelled rather than left to
+//! consume beacon node and signer resources. Each cancellation is logged and counted as a
+//! deadline miss against the name of the task.
+use futures::{future, Future};
+use slog::{debug, warn};
+use slot_clock::SlotClock;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::timer::{Delay, Timeout};
+
+/// A time within a slot, relative to the start of the slot.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SlotOffset {
+    /// A fixed duration after the start of the slot.
+    After(Duration),
+    /// The slot duration divided by the given value after the start of the slot (e.g., `3` for a
+    /// third of the way through the slot).
+    Fraction(u32),
+    /// The end of the slot, which is also the start of the next slot.
+    End,
+}
+
+impl SlotOffset {
+    /// Returns the duration from the start of the slot to the offset.
+    pub fn duration(self, slot_duration: Duration) -> Duration {
+        match self {
+            SlotOffset::After(duration) => duration,
+            SlotOffset::Fraction(divisor) => slot_duration / divisor.max(1),
+            SlotOffset::End => slot_duration,
+        }
+    }
+}
+
+/// The outcomes of all runs of a single task.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct TaskMetrics {
+    /// Runs which completed before their deadline.
+    pub completed: u64,
+    /// Runs which were cancelled at their deadline.
+    pub missed: u64,
+}
+
+/// Runs tasks which are cancelled once they exceed their deadline.
+#[derive(Clone)]
+pub struct Scheduler {
+    slot_duration: Duration,
+    metrics: Arc<RwLock<HashMap<&'static str, TaskMetrics>>>,
+    log: slog::Logger,
+}
+
+impl Scheduler {
+    pub fn new(slot_duration: Duration, log: slog::Logger) -> Self {
+        Self {
+            slot_duration,
+            metrics: Arc::new(RwLock::new(HashMap::new())),
+            log,
+        }
+    }
+
+    /// Returns the instant at which the present slot of `slot_clock` started, or `None` if the
+    /// slot clock cannot be read.
+    pub fn slot_start<C: SlotClock>(&self, slot_clock: &C) -> Option<Instant> {
+        let duration_to_next_slot = slot_clock.duration_to_next_slot().ok()??;
+        let now = Instant::now();
+        let elapsed = self.slot_duration.checked_sub(duration_to_next_slot)?;
+        Some(now - elapsed)
+    }
+
+    /// Returns the instant of `offset` in the slot which started at `slot_start`.
+    pub fn instant(&self, slot_start: Instant, offset: SlotOffset) -> Instant {
+        slot_start + offset.duration(self.slot_duration)
+    }
+
+    /// Returns a future which runs `task`, cancelling it at `deadline` in the slot which started
+    /// at `slot_start`.
+    pub fn run<F>(
+        &self,
+        name: &'static str,
+        slot_start: Instant,
+        deadline: SlotOffset,
+        task: F,
+    ) -> Box<dyn Future<Item = (), Error = ()> + Send>
+    where
+        F: Future<Item = (), Error = ()> + Send + 'static,
+    {
+        let scheduler = self.clone();
+        let deadline = self.instant(slot_start, deadline);
+
+        Box::new(Timeout::new_at(task, deadline).then(move |result| {
+            match result {
+                Ok(()) => scheduler.record(name, |metrics| metrics.completed += 1),
+                // The task has failed, and logged its own error.
+                Err(ref e) if e.is_inner() => {
+                    scheduler.record(name, |metrics| metrics.completed += 1)
+                }
+                Err(ref e) if e.is_elapsed() => {
+                    warn!(scheduler.log, "Task cancelled at its deadline"; "task" => name);
+                    scheduler.record(name, |metrics| metrics.missed += 1)
+                }
+                Err(e) => {
+                    warn!(scheduler.log, "Task timer failed"; "task" => name, "error" => format!("{:?}", e))
+                }
+            }
+            Ok(())
+        }))
+    }
+
+    /// Returns a future which waits until `start` in the slot which started at `slot_start` before
+    /// running `task` as `run()` does.
+    ///
+    /// `task` is only constructed once `start` is reached.
+    pub fn run_at<F, T>(
+        &self,
+        name: &'static str,
+        slot_start: Instant,
+        start: SlotOffset,
+        deadline: SlotOffset,
+        task: F,
+    ) -> Box<dyn Future<Item = (), Error = ()> + Send>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Future<Item = (), Error = ()> + Send + 'static,
+    {
+        let scheduler = self.clone();
+        let log = self.log.clone();
+
+        Box::new(
+            Delay::new(self.instant(slot_start, start))
+                .map_err(move |e| warn!(log, "Task timer failed"; "task" => name, "error" => format!("{:?}", e)))
+                .and_then(move |()| -> Box<dyn Future<Item = (), Error = ()> + Send> {
+                    if Instant::now() >= scheduler.instant(slot_start, deadline) {
+                        debug!(scheduler.log, "Task started after its deadline"; "task" => name);
+                        scheduler.record(name, |metrics| metrics.missed += 1);
+                        return Box::new(future::ok(()));
+                    }
+                    scheduler.run(name, slot_start, deadline, task())
+                }),
+        )
+    }
+
+    /// Returns the outcomes of all runs of each task.
+    pub fn metrics(&self) -> HashMap<&'static str, TaskMetrics> {
+        self.metrics
+            .read()
+            .map(|metrics| metrics.clone())
+            .unwrap_or_default()
+    }
+
+    fn record(&self, name: &'static str, f: impl FnOnce(&mut TaskMetrics)) {
+        if let Ok(mut metrics) = self.metrics.write() {
+            f(metrics.entry(name).or_insert_with(TaskMetrics::default))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use slog::o;
+    use tokio::runtime::Runtime;
+
+    #[test]
+    fn cancels_tasks_at_their_deadline() {
+        let log = slog::Logger::root(slog::Discard, o!());
+        let scheduler = Scheduler::new(Duration::from_millis(300), log);
+        let slot_start = Instant::now();
+
+        let mut runtime = Runtime::new().expect("should create runtime");
+        runtime
+            .block_on(
+                scheduler
+                    .run("complete", slot_start, SlotOffset::End, future::ok(()))
+                    .join(scheduler.run(
+                        "stall",
+                        slot_start,
+                        SlotOffset::Fraction(3),
+                        future::empty(),
+                    ))
+                    .join(scheduler.run_at(
+                        "late",
+                        slot_start,
+                        SlotOffset::End,
+                        SlotOffset::After(Duration::from_millis(100)),
+                        || future::ok(()),
+                    )),
+            )
+            .expect("should run tasks");
+
+        let metrics = scheduler.metrics();
+        let completed = TaskMetrics {
+            completed: 1,
+            missed: 0,
+        };
+        let missed = TaskMetrics {
+            completed: 0,
+            missed: 1,
+        };
+        assert_eq!(metrics.get("complete"), Some(&completed));
+        assert_eq!(metrics.get("stall"), Some(&missed));
+        assert_eq!(metrics.get("late"), Some(&missed));
+    }
+
+    #[test]
+    fn slot_offsets() {
+        let slot_duration = Duration::from_secs(6);
+        assert_eq!(
+            SlotOffset::After(Duration::from_secs(2)).duration(slot_duration),
+            Duration::from_secs(2)
+        );
+        assert_eq!(
+            SlotOffset::Fraction(3).duration(slot_duration),
+            Duration::from_secs(2)
+        );
+        assert_eq!(SlotOffset::End.duration(slot_duration), slot_duration);
+    }
+}
@@ -8,154 +8,679 @@
 /// When a validator needs to either produce a block or sign an attestation, it requests the
 /// data from the beacon node and performs the signing before publishing the block to the beacon
 /// node.
-use crate::attestation_producer::AttestationProducer;
-use crate::block_producer::{BeaconBlockGrpcClient, BlockProducer};
+use crate::attestation_producer::{AttestationProducer, BeaconNodeAttestation};
+use crate::audit_log::{AuditLog, AuditedSigner};
+use crate::beacon_api::{BeaconApiClient, NodeInfo};
+use crate::block_latency::{BlockLatencies, PublishTarget, Request, Timed};
+use crate::block_producer::pool::{self, ProductionPool};
+use crate::block_producer::replay::{Recorder, Recording, TraceEvent};
+use crate::block_producer::{
+    BeaconBlockGrpcClient, BeaconNodeBlock, BlockBuilderGrpcClient, BlockProducer,
+};
+use crate::broadcast::Broadcast;
+use crate::builder_registration::BuilderRegistrar;
+use crate::chain_identity::{ChainIdentity, Check};
+use crate::chain_split::{ChainSplit, ChainSplitDetector};
 use crate::config::Config as ValidatorConfig;
-use crate::duties::{BeaconNodeDuties, DutiesManager, EpochDutiesMap};
-use crate::error as error_chain;
-use crate::error::ErrorKind;
-use crate::signer::Signer;
-use bls::Keypair;
+use crate::datadir_lock::DatadirLock;
+use crate::duties::{BeaconNodeDuties, DutiesCache, DutiesManager, DutiesStore};
+use crate::endpoint_scores::EndpointScores;
+use crate::error::{self, Error};
+use crate::fee_recipient::FeeRecipients;
+use crate::health::{self, Health};
+use crate::hsm::{self, Hsm};
+use crate::inclusion::InclusionMonitor;
+use crate::metrics_push::{MetricsPushConfig, MetricsPusher};
+use crate::notifier::Notifier;
+use crate::proposal_timing::ProposalTimings;
+use crate::scheduler::{Scheduler, SlotOffset};
+use crate::shutdown::{self, InFlight, Outcome, Signals, DRAIN_TIMEOUT};
+use crate::signature_cache::{CachingSigner, SignatureCache, SIGNATURES_PER_VALIDATOR};
+use crate::signer::{Signer, ValidatorSigner};
+use crate::signing_lease::SigningLease;
+use crate::signing_log::SigningLog;
+use crate::slashing_protection::SlashingProtection;
+use crate::slashing_watcher::SlashingWatcher;
+use crate::slot_tracker::{Refused, SlotTracker};
+use crate::snapshot::Snapshot;
+use crate::validator_state::ValidatorState;
+use crate::wss_checkpoint::{self, CheckpointRoot};
 use eth2_config::Eth2Config;
-use grpcio::{ChannelBuilder, EnvBuilder};
-use protos::services::Empty;
+use grpcio::{Channel, ChannelBuilder, EnvBuilder, Environment};
+use prometheus::Registry;
+use protos::auth::{Authenticated, TlsCredentials};
+use protos::convert::{self, FromProto};
+use protos::services::{CheckpointRootRequest, Empty, HeadEvent};
 use protos::services_grpc::{
     AttestationServiceClient, BeaconBlockServiceClient, BeaconNodeServiceClient,
-    ValidatorServiceClient,
+    BlockBuilderServiceClient, ValidatorServiceClient,
 };
-use slog::{crit, error, info, warn};
-use slot_clock::{SlotClock, SystemTimeSlotClock};
+use rand::Rng;
+use slog::{crit, debug, error, info, warn};
+use slot_clock::{SlotClock, SystemTimeSlotClock, SystemTimeSlotClockError};
+use std::collections::HashSet;
 use std::marker::PhantomData;
+use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::RwLock;
+use std::thread::{self, JoinHandle};
 use std::time::{Duration, Instant, SystemTime};
+use system_service::{Probe, ServiceManager};
 use tokio::prelude::*;
-use tokio::runtime::Builder;
-use tokio::timer::Interval;
+use tokio::runtime::{Builder, Runtime};
+use tokio::timer::{Delay, Interval};
 use tokio_timer::clock::Clock;
-use types::{ChainSpec, Epoch, EthSpec, Fork, Slot};
+use types::{ChainSpec, Epoch, EthSpec, Fork, Hash256, PublicKey, Slot};
 
 /// A fixed amount of time after a slot to perform operations. This gives the node time to complete
 /// per-slot processes.
 const TIME_DELAY_FROM_SLOT: Duration = Duration::from_millis(100);
 
+/// Attestations are produced as soon as the beacon node notifies us of a head block for the
+/// current slot, or at this time in the slot if no such block arrives.
+const ATTESTATION_DEADLINE: SlotOffset = SlotOffset::Fraction(3);
+
+/// Block and attestation production is cancelled if it has not completed by this time in the
+/// slot, since neither is useful afterwards.
+const PRODUCTION_DEADLINE: SlotOffset = SlotOffset::End;
+
+/// Duties are refreshed after a random delay of up to this long, so that the validator clients of
+/// a beacon node do not all request their duties at the same instant of an epoch boundary.
+const DUTIES_JITTER: Duration = Duration::from_millis(500);
+
+/// Startup work which runs concurrently with the beacon node handshake.
+struct Startup {
+    /// The time at which the service started.
+    started: Instant,
+    /// Loads the validator keys, which may take a long time for many keystores, and connects to
+    /// the HSM, if any.
+    signers: JoinHandle<Result<Vec<ValidatorSigner>, hsm::Error>>,
+}
+
+impl Startup {
+    /// Starts loading the validator keys of `client_config` in the background.
+    fn new(client_config: &ValidatorConfig, log: &slog::Logger) -> Self {
+        let started = Instant::now();
+        let config = client_config.clone();
+        let log = log.clone();
+        let signers = thread::spawn(move || {
+            let mut signers: Vec<_> = config
+                .fetch_keys(&log)
+                .unwrap_or_default()
+                .into_iter()
+                .map(ValidatorSigner::Keypair)
+                .collect();
+            log_phase(&log, "load_keys", started, started);
+
+            if let Some(hsm_config) = &config.hsm {
+                let hsm_started = Instant::now();
+                let backend = hsm_config.backend(hsm_config.pin()?)?;
+                let hsm = Hsm::start(backend, hsm_config.sessions, log.clone())?;
+                signers.extend(Hsm::signers(&hsm).into_iter().map(ValidatorSigner::Hsm));
+                log_phase(&log, "connect_hsm", hsm_started, started);
+            }
+            Ok(signers)
+        });
+        Self { started, signers }
+    }
+
+    /// Waits for the validator keys to be loaded.
+    fn signers(self) -> Result<Vec<ValidatorSigner>, hsm::Error> {
+        self.signers.join().unwrap_or_else(|_| Ok(vec![]))
+    }
+}
+
+/// Logs the completion of the startup `phase`, which began at `phase_started`.
+fn log_phase(log: &slog::Logger, phase: &str, phase_started: Instant, started: Instant) {
+    info!(
+        log,
+        "Startup phase complete";
+        "phase" => phase,
+        "duration_ms" => phase_started.elapsed().as_millis() as u64,
+        "since_start_ms" => started.elapsed().as_millis() as u64,
+    );
+}
+
+/// Opens gRPC channels to beacon nodes, over mutual TLS and with an auth token if configured.
+#[derive(Clone)]
+pub struct Connector {
+    env: Arc<Environment>,
+    tls: Option<TlsCredentials>,
+    token: Option<String>,
+}
+
+impl Connector {
+    pub fn new(client_config: &ValidatorConfig, log: &slog::Logger) -> error::Result<Self> {
+        let tls = client_config
+            .tls_credentials()
+            .map_err(Error::GrpcCredentials)?;
+        let token = client_config.auth_token().map_err(Error::GrpcCredentials)?;
+        if token.is_some() && tls.is_none() {
+            warn!(
+                log,
+                "Auth token is sent unencrypted";
+                "advice" => "use --tls-ca, --tls-cert and --tls-key to contact the beacon node over TLS"
+            );
+        }
+
+        Ok(Self {
+            env: Arc::new(EnvBuilder::new().build()),
+            tls,
+            token,
+        })
+    }
+
+    /// Returns a client of the beacon node at `server`, built by `client` from a channel.
+    pub fn connect<C>(&self, server: &str, client: impl FnOnce(Channel) -> C) -> Authenticated<C> {
+        let builder = ChannelBuilder::new(self.env.clone());
+        let channel = match &self.tls {
+            Some(tls) => builder.secure_connect(server, tls.channel()),
+            None => builder.connect(server),
+        };
+        Authenticated::new(client(channel), self.token.clone())
+    }
+}
+
+/// A future which resolves to the service, once it has processed an event.
+type ServiceFuture<T> = Box<dyn Future<Item = T, Error = String> + Send>;
+
+/// An event which drives the service.
+enum ServiceEvent {
+    /// A new slot has started.
+    SlotStart,
+    /// The latest time at which attestations should be produced in the current slot.
+    AttestationDeadline,
+    /// The beacon node has a new head.
+    Head(HeadEvent),
+}
+
 /// The validator service. This is the main thread that executes and maintains validator
 /// duties.
 //TODO: Generalize the BeaconNode types to use testing
-pub struct Service<B: BeaconNodeDuties + 'static, S: Signer + 'static, E: EthSpec> {
+pub struct Service<
+    B: BeaconNodeDuties + 'static,
+    N: BeaconNodeBlock + 'static,
+    A: BeaconNodeAttestation + 'static,
+    S: Signer + 'static,
+    E: EthSpec,
+> {
     /// The node's current fork version we are processing on.
     fork: Fork,
     /// The slot clock for this service.
     slot_clock: SystemTimeSlotClock,
     /// The current slot we are processing.
     current_slot: Slot,
+    /// The slots which have been processed, from genesis.
+    slot_tracker: SlotTracker,
     slots_per_epoch: u64,
     /// The chain specification for this clients instance.
     spec: Arc<ChainSpec>,
     /// The duties manager which maintains the state of when to perform actions.
     duties_manager: Arc<DutiesManager<B, S>>,
-    // GRPC Clients
-    /// The beacon block GRPC client.
-    beacon_block_client: Arc<BeaconBlockGrpcClient>,
-    /// The attester GRPC client.
-    attestation_client: Arc<AttestationServiceClient>,
+    /// The latest slot in which attestations were produced.
+    attested_slot: Option<Slot>,
+    // Beacon node clients
+    /// The beacon node GRPC client, used to subscribe to head events. `None` if the beacon node is
+    /// contacted via its HTTP API.
+    beacon_node_client: Option<Authenticated<BeaconNodeServiceClient>>,
+    /// The beacon block client.
+    beacon_block_client: Arc<N>,
+    /// The endpoint of the beacon block client, by which its latencies are recorded.
+    beacon_block_endpoint: String,
+    /// The recent latencies of block requests to the beacon node.
+    block_latencies: Arc<BlockLatencies>,
+    /// When blocks should be published, relative to the start of their slot.
+    publish_target: PublishTarget,
+    /// The external block builder GRPC client, if a builder endpoint is configured.
+    builder_client: Option<Arc<BlockBuilderGrpcClient>>,
+    /// Registers the proposal preferences of all validators with the builder, if any.
+    builder_registrar: Option<Arc<BuilderRegistrar<BlockBuilderGrpcClient, S>>>,
+    /// The attester client.
+    attestation_client: Arc<A>,
+    /// The fee recipients of all validators.
+    fee_recipients: Arc<FeeRecipients>,
+    /// The signing history of all validators, shared by all block and attestation producers.
+    slashing_protection: Arc<SlashingProtection>,
+    /// The number of epochs of signing history retained when compacting, if compaction is enabled.
+    slashing_protection_retention: Option<u64>,
+    /// The directory of the validators, containing the state of each.
+    validators_dir: PathBuf,
+    /// The directory in which snapshots of the service are saved, if any.
+    snapshot_dir: Option<PathBuf>,
+    /// Persists the known duties, so that they are available immediately after a restart.
+    duties_cache: DutiesCache,
+    /// Records slot starts and block production responses for replay, if configured.
+    recorder: Option<Recorder>,
+    /// Cross-checks the beacon node with other beacon nodes, if any are configured.
+    chain_split_detector: Option<ChainSplitDetector<Authenticated<BeaconNodeServiceClient>>>,
+    /// If `true`, signing is paused whilst the beacon node has split from the majority.
+    pause_on_chain_split: bool,
+    /// `true` if block and attestation signing is paused due to a chain split.
+    signing_paused: bool,
+    /// The health of the service, served on `health_address` if configured.
+    health: Arc<Health>,
+    /// Tracks the inclusion of the attestations of all validators.
+    inclusion_monitor: Arc<InclusionMonitor>,
+    /// Notifies the configured webhooks of significant events.
+    notifier: Arc<Notifier>,
+    /// Stops the signing of validators which the beacon node reports as slashed.
+    slashing_watcher: Arc<SlashingWatcher>,
+    /// Claims each validator from the other validator clients which share it, before signing.
+    signing_lease: Arc<SigningLease>,
+    /// The timing of recent proposals, served on `health_address` if configured.
+    proposal_timings: Arc<ProposalTimings>,
+    /// The scores of every beacon node, served on `health_address` if configured.
+    endpoint_scores: Arc<EndpointScores>,
+    /// The metrics served on `health_address` and pushed by `metrics_push`, if configured.
+    metrics_registry: Registry,
+    metrics_push: Option<MetricsPushConfig>,
+    /// If `true`, blocks and attestations are signed with throwaway keys and not published.
+    dry_run: bool,
+    health_address: Option<SocketAddr>,
+    /// Runs block and attestation production, cancelling tasks which exceed their deadline.
+    scheduler: Scheduler,
+    /// Produces the blocks of all validators on a fixed number of workers, soonest deadline first.
+    block_production_pool: Arc<ProductionPool>,
+    /// The block and attestation productions in flight, awaited on shutdown.
+    in_flight: InFlight,
     /// The validator client logger.
     log: slog::Logger,
     _phantom: PhantomData<E>,
 }
 
-impl<B: BeaconNodeDuties + 'static, S: Signer + 'static, E: EthSpec> Service<B, S, E> {
-    ///  Initial connection to the beacon node to determine its properties.
+impl<
+        B: BeaconNodeDuties + 'static,
+        N: BeaconNodeBlock + 'static,
+        A: BeaconNodeAttestation + 'static,
+        S: Signer + 'static,
+        E: EthSpec,
+    > Service<B, N, A, S, E>
+{
+    ///  Initial connection to a beacon node via gRPC to determine its properties.
     ///
     ///  This tries to connect to a beacon node. Once connected, it initialised the gRPC clients
     ///  and returns an instance of the service.
-    fn initialize_service(
+    fn initialize_grpc_service(
+        startup: Startup,
         client_config: ValidatorConfig,
         eth2_config: Eth2Config,
         log: slog::Logger,
-    ) -> error_chain::Result<Service<ValidatorServiceClient, Keypair, E>> {
+    ) -> error::Result<
+        Service<
+            Authenticated<ValidatorServiceClient>,
+            Broadcast<BeaconBlockGrpcClient>,
+            Broadcast<Authenticated<AttestationServiceClient>>,
+            AuditedSigner<CachingSigner<ValidatorSigner>>,
+            E,
+        >,
+    > {
         // initialise the beacon node client to check for a connection
-
-        let env = Arc::new(EnvBuilder::new().build());
+        let handshake_started = Instant::now();
+        let connector = Connector::new(&client_config, &log)?;
         // Beacon node gRPC beacon node endpoints.
-        let beacon_node_client = {
-            let ch = ChannelBuilder::new(env.clone()).connect(&client_config.server);
-            BeaconNodeServiceClient::new(ch)
-        };
+        let beacon_node_client =
+            connector.connect(&client_config.server, BeaconNodeServiceClient::new);
 
-        // retrieve node information and validate the beacon node
+        // retrieve node information
         let node_info = loop {
-            match beacon_node_client.info(&Empty::new()) {
+            match beacon_node_client
+                .client()
+                .info_opt(&Empty::new(), beacon_node_client.call_option())
+            {
                 Err(e) => {
                     warn!(log, "Could not connect to node. Error: {}", e);
                     info!(log, "Retrying in 5 seconds...");
                     std::thread::sleep(Duration::from_secs(5));
                     continue;
                 }
-                Ok(info) => {
-                    // verify the node's genesis time
-                    if SystemTime::now()
-                        .duration_since(SystemTime::UNIX_EPOCH)
-                        .unwrap()
-                        .as_secs()
-                        < info.genesis_time
-                    {
-                        error!(
-                            log,
-                            "Beacon Node's genesis time is in the future. No work to do.\n Exiting"
-                        );
-                        return Err("Genesis time in the future".into());
-                    }
-                    // verify the node's network id
-                    if eth2_config.spec.network_id != info.network_id as u8 {
-                        error!(
-                            log,
-                            "Beacon Node's genesis time is in the future. No work to do.\n Exiting"
-                        );
-                        return Err(format!("Beacon node has the wrong chain id. Expected chain id: {}, node's chain id: {}", eth2_config.spec.network_id, info.network_id).into());
-                    }
-                    break info;
-                }
+                Ok(info) => break info,
             };
         };
+        log_phase(&log, "handshake", handshake_started, startup.started);
 
-        // build requisite objects to form Self
-        let genesis_time = node_info.get_genesis_time();
         let genesis_slot = Slot::from(node_info.get_genesis_slot());
 
-        info!(log,"Beacon node connected"; "Node Version" => node_info.version.clone(), "Chain ID" => node_info.network_id, "Genesis time" => genesis_time);
-
-        let proto_fork = node_info.get_fork();
-        let mut previous_version: [u8; 4] = [0; 4];
-        let mut current_version: [u8; 4] = [0; 4];
-        previous_version.copy_from_slice(&proto_fork.get_previous_version()[..4]);
-        current_version.copy_from_slice(&proto_fork.get_current_version()[..4]);
-        let fork = Fork {
-            previous_version,
-            current_version,
-            epoch: Epoch::from(proto_fork.get_epoch()),
+        let node_info = NodeInfo {
+            version: node_info.version.clone(),
+            genesis_time: node_info.get_genesis_time(),
+            chain_id: u64::from(node_info.network_id),
+            fork: Fork::from_proto(node_info.get_fork()).map_err(Error::NodeInfo)?,
+            // Beacon nodes which predate the field leave it empty.
+            genesis_root: match node_info.get_genesis_root() {
+                root if root.is_empty() => None,
+                root => {
+                    Some(convert::root_from_proto("genesis_root", root).map_err(Error::NodeInfo)?)
+                }
+            },
         };
 
+        // retrieve the canonical block root at the weak subjectivity checkpoint, if configured
+        let checkpoint_root = client_config.wss_checkpoint.as_ref().map(|checkpoint| {
+            let mut req = CheckpointRootRequest::new();
+            req.set_epoch(checkpoint.epoch.as_u64());
+            beacon_node_client
+                .client()
+                .checkpoint_root_opt(&req, beacon_node_client.call_option())
+                .map(|response| CheckpointRoot {
+                    root: Some(response.get_root())
+                        .filter(|root| root.len() == 32)
+                        .map(Hash256::from_slice),
+                    finalized_epoch: Epoch::new(response.get_finalized_epoch()),
+                })
+                .map_err(|e| e.to_string())
+        });
+
         // initialize the RPC clients
 
+        // Signed messages are also published to the cross-check servers, if configured.
+        let broadcast_servers = if client_config.broadcast {
+            client_config.cross_check_servers.clone()
+        } else {
+            vec![]
+        };
+        if !broadcast_servers.is_empty() {
+            info!(
+                log,
+                "Broadcasting signed messages";
+                "servers" => broadcast_servers.join(",")
+            );
+        }
+
+        // Requests to every beacon node are scored, so that data is requested from the best.
+        let endpoint_scores = Arc::new(EndpointScores::default());
+
         // Beacon node gRPC beacon block endpoints.
+        let spec = Arc::new(eth2_config.spec.clone());
         let beacon_block_client = {
-            let ch = ChannelBuilder::new(env.clone()).connect(&client_config.server);
-            let beacon_block_service_client = Arc::new(BeaconBlockServiceClient::new(ch));
-            // a wrapper around the service client to implement the beacon block node trait
-            Arc::new(BeaconBlockGrpcClient::new(beacon_block_service_client))
+            let connect = |server: &String| {
+                let beacon_block_service_client =
+                    Arc::new(connector.connect(server, BeaconBlockServiceClient::new));
+                // a wrapper around the service client to implement the beacon block node trait
+                let client = Arc::new(BeaconBlockGrpcClient::new(
+                    beacon_block_service_client,
+                    spec.clone(),
+                ));
+                (server.clone(), client)
+            };
+            Arc::new(
+                Broadcast::new(
+                    connect(&client_config.server),
+                    broadcast_servers.iter().map(connect).collect(),
+                    log.clone(),
+                )
+                .with_scores(endpoint_scores.clone()),
+            )
         };
 
         // Beacon node gRPC validator endpoints.
-        let validator_client = {
-            let ch = ChannelBuilder::new(env.clone()).connect(&client_config.server);
-            Arc::new(ValidatorServiceClient::new(ch))
-        };
+        let validator_client =
+            Arc::new(connector.connect(&client_config.server, ValidatorServiceClient::new));
 
         //Beacon node gRPC attester endpoints.
         let attestation_client = {
-            let ch = ChannelBuilder::new(env.clone()).connect(&client_config.server);
-            Arc::new(AttestationServiceClient::new(ch))
+            let connect = |server: &String| {
+                let client = connector.connect(server, AttestationServiceClient::new);
+                (server.clone(), Arc::new(client))
+            };
+            Arc::new(
+                Broadcast::new(
+                    connect(&client_config.server),
+                    broadcast_servers.iter().map(connect).collect(),
+                    log.clone(),
+                )
+                .with_scores(endpoint_scores.clone()),
+            )
+        };
+
+        Service::initialize_service(
+            startup,
+            node_info,
+            checkpoint_root,
+            genesis_slot,
+            Some(beacon_node_client),
+            validator_client,
+            beacon_block_client,
+            attestation_client,
+            endpoint_scores,
+            client_config,
+            eth2_config,
+            log,
+        )
+    }
+
+    ///  Initial connection to a beacon node via its HTTP API at `url` to determine its
+    ///  properties.
+    ///
+    ///  The beacon node HTTP API does not provide head events, so attestations are always
+    ///  produced at the attestation deadline.
+    fn initialize_rest_service(
+        startup: Startup,
+        url: &str,
+        runtime: &mut Runtime,
+        client_config: ValidatorConfig,
+        eth2_config: Eth2Config,
+        log: slog::Logger,
+    ) -> error::Result<
+        Service<
+            BeaconApiClient,
+            BeaconApiClient,
+            BeaconApiClient,
+            AuditedSigner<CachingSigner<ValidatorSigner>>,
+            E,
+        >,
+    > {
+        let spec = Arc::new(eth2_config.spec.clone());
+        let client = BeaconApiClient::new(url, spec).map_err(|reason| Error::BeaconApi {
+            url: url.to_string(),
+            reason,
+        })?;
+        let client = Arc::new(client);
+        info!(log, "Using beacon node HTTP API"; "url" => client.url());
+        let handshake_started = Instant::now();
+
+        // retrieve node information
+        let node_info = loop {
+            match runtime.block_on(client.node_info()) {
+                Err(e) => {
+                    warn!(log, "Could not connect to node. Error: {:?}", e);
+                    info!(log, "Retrying in 5 seconds...");
+                    std::thread::sleep(Duration::from_secs(5));
+                    continue;
+                }
+                Ok(info) => break info,
+            };
+        };
+        log_phase(&log, "handshake", handshake_started, startup.started);
+
+        let checkpoint_root = client_config.wss_checkpoint.as_ref().map(|checkpoint| {
+            runtime
+                .block_on(client.checkpoint_root(checkpoint.epoch))
+                .map_err(|e| format!("{:?}", e))
+        });
+
+        let genesis_slot = eth2_config.spec.genesis_slot;
+
+        Service::initialize_service(
+            startup,
+            node_info,
+            checkpoint_root,
+            genesis_slot,
+            None,
+            client.clone(),
+            client.clone(),
+            client,
+            Arc::new(EndpointScores::default()),
+            client_config,
+            eth2_config,
+            log,
+        )
+    }
+
+    /// Validates the beacon node described by `node_info` (and by `checkpoint_root`, its block
+    /// root at the weak subjectivity checkpoint) and builds an instance of the service which uses
+    /// the given clients.
+    #[allow(clippy::too_many_arguments)]
+    fn initialize_service(
+        startup: Startup,
+        node_info: NodeInfo,
+        checkpoint_root: Option<Result<CheckpointRoot, String>>,
+        genesis_slot: Slot,
+        beacon_node_client: Option<Authenticated<BeaconNodeServiceClient>>,
+        validator_client: Arc<B>,
+        beacon_block_client: Arc<N>,
+        attestation_client: Arc<A>,
+        endpoint_scores: Arc<EndpointScores>,
+        client_config: ValidatorConfig,
+        eth2_config: Eth2Config,
+        log: slog::Logger,
+    ) -> error::Result<Service<B, N, A, AuditedSigner<CachingSigner<ValidatorSigner>>, E>> {
+        // before genesis, the validator client waits for the genesis slot, so that its duties are
+        // performed along with those of every later slot
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        if now < node_info.genesis_time {
+            info!(
+                log,
+                "Waiting for genesis";
+                "genesis_time" => node_info.genesis_time,
+                "seconds" => node_info.genesis_time - now,
+            );
+        }
+        // verify the node's network id
+        if u64::from(eth2_config.spec.network_id) != node_info.chain_id {
+            error!(
+                log,
+                "Beacon Node's genesis time is in the future. No work to do.\n Exiting"
+            );
+            return Err(Error::WrongChainId {
+                expected: u64::from(eth2_config.spec.network_id),
+                found: node_info.chain_id,
+            });
+        }
+        // verify the node is on the chain the validators have signed on before
+        let chain_identity = ChainIdentity {
+            chain_id: node_info.chain_id,
+            genesis_time: node_info.genesis_time,
+            genesis_root: node_info.genesis_root,
+            fork: node_info.fork.clone(),
+        };
+        match chain_identity.check(&client_config.data_dir) {
+            Ok(Check::Pinned) => info!(
+                log,
+                "Pinned the chain of the beacon node";
+                "genesis_root" => format!("{:?}", node_info.genesis_root)
+            ),
+            Ok(Check::Updated) => info!(
+                log,
+                "Updated the pinned chain";
+                "fork" => format!("{:?}", node_info.fork)
+            ),
+            Ok(Check::Unverified) => warn!(
+                log,
+                "Unable to verify the genesis root of the beacon node";
+                "reason" => "the beacon node does not report it"
+            ),
+            Ok(Check::Matched) => {}
+            Err(e) => {
+                error!(
+                    log,
+                    "Beacon node is on another chain to the one pinned";
+                    "error" => e.to_string(),
+                    "pin" => format!("{:?}", ChainIdentity::path(&client_config.data_dir)),
+                    "suggestion" => "delete the pin if the change of chain is intended"
+                );
+                return Err(e.into());
+            }
+        }
+        // verify the node's finalized chain includes the weak subjectivity checkpoint
+        if let (Some(checkpoint), Some(checkpoint_root)) =
+            (&client_config.wss_checkpoint, checkpoint_root)
+        {
+            let check = checkpoint_root
+                .map_err(wss_checkpoint::Error::Query)
+                .and_then(|checkpoint_root| wss_checkpoint::check(checkpoint, &checkpoint_root));
+            match check {
+                Ok(wss_checkpoint::Check::Verified) => info!(
+                    log,
+                    "Verified the weak subjectivity checkpoint";
+                    "epoch" => checkpoint.epoch.as_u64(),
+                    "root" => format!("{:?}", checkpoint.root)
+                ),
+                Ok(wss_checkpoint::Check::Unfinalized {
+                    finalized_epoch,
+                    root,
+                }) => warn!(
+                    log,
+                    "Unable to verify the weak subjectivity checkpoint";
+                    "reason" => "the beacon node has not finalized its epoch",
+                    "finalized_epoch" => finalized_epoch.as_u64(),
+                    "checkpoint_epoch" => checkpoint.epoch.as_u64(),
+                    "canonical_root" => format!("{:?}", root)
+                ),
+                Err(e) => {
+                    error!(
+                        log,
+                        "Beacon node conflicts with the weak subjectivity checkpoint";
+                        "error" => e.to_string(),
+                        "suggestion" => "use a beacon node which has synced from the checkpoint"
+                    );
+                    return Err(e.into());
+                }
+            }
+        }
+
+        // build requisite objects to form Self
+        let genesis_time = node_info.genesis_time;
+
+        info!(log,"Beacon node connected"; "Node Version" => node_info.version.clone(), "Chain ID" => node_info.chain_id, "Genesis time" => genesis_time);
+
+        let fork = node_info.fork;
+
+        // External block builder gRPC endpoints.
+        let builder_client = client_config.builder_endpoint.as_ref().map(|endpoint| {
+            info!(log, "Requesting blocks from external builder"; "endpoint" => endpoint);
+            let env = Arc::new(EnvBuilder::new().build());
+            let ch = ChannelBuilder::new(env).connect(endpoint);
+            let builder_service_client = Arc::new(BlockBuilderServiceClient::new(ch));
+            Arc::new(BlockBuilderGrpcClient::new(builder_service_client))
+        });
+
+        // Other beacon nodes, which are cross-checked with the beacon node to detect chain splits.
+        let chain_split_detector = match &beacon_node_client {
+            Some(primary) if !client_config.cross_check_servers.is_empty() => {
+                let connector = Connector::new(&client_config, &log)?;
+                let nodes = client_config
+                    .cross_check_servers
+                    .iter()
+                    .map(|server| {
+                        let client = connector.connect(server, BeaconNodeServiceClient::new);
+                        (server.clone(), client)
+                    })
+                    .collect();
+                info!(
+                    log,
+                    "Cross-checking beacon nodes";
+                    "servers" => client_config.cross_check_servers.join(","),
+                    "pause_on_chain_split" => client_config.pause_on_chain_split,
+                );
+                Some(ChainSplitDetector::new(
+                    (client_config.server.clone(), primary.clone()),
+                    nodes,
+                    client_config.chain_split_threshold,
+                ))
+            }
+            None if !client_config.cross_check_servers.is_empty() => {
+                warn!(
+                    log,
+                    "Cross-check servers ignored";
+                    "reason" => "the beacon node is not contacted via gRPC"
+                );
+                None
+            }
+            _ => None,
         };
 
         // build the validator slot clock
@@ -167,20 +692,38 @@ impl<B: BeaconNodeDuties + 'static, S: Signer + 'static, E: EthSpec> Service<B,
 
         let current_slot = slot_clock
             .present_slot()
-            .map_err(ErrorKind::SlotClockError)?
-            .ok_or_else::<error_chain::Error, _>(|| {
-                "Genesis is not in the past. Exiting.".into()
-            })?;
+            .map_err(Error::SlotClock)?
+            .unwrap_or(genesis_slot);
 
         /* Generate the duties manager */
 
-        // Load generated keypairs
-        let keypairs = match client_config.fetch_keys(&log) {
-            Some(kps) => Arc::new(kps),
-            None => {
-                return Err("Unable to locate validator key pairs, nothing to do.".into());
-            }
-        };
+        // Load generated keypairs, which have been loading since startup
+        let started = startup.started;
+        let keypairs = startup.signers().map_err(Error::Hsm)?;
+        if keypairs.is_empty() {
+            return Err(Error::NoKeypairs);
+        }
+
+        // Record every signing request in a per-validator audit log, and sign each message at most
+        // once whilst its signature is cached.
+        let signature_cache = Arc::new(SignatureCache::new(
+            keypairs.len() * SIGNATURES_PER_VALIDATOR,
+        ));
+        let keypairs = keypairs
+            .into_iter()
+            .map(|keypair| {
+                let path = AuditLog::path(
+                    &client_config.validators_dir(),
+                    &keypair.to_public().concatenated_hex_id(),
+                );
+                let signer = CachingSigner::new(keypair, signature_cache.clone());
+                AuditedSigner::new(signer, &path).map_err(|source| Error::AuditLog {
+                    path: path.clone(),
+                    source,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let keypairs = Arc::new(keypairs);
 
         let slots_per_epoch = E::slots_per_epoch();
 
@@ -191,114 +734,763 @@ impl<B: BeaconNodeDuties + 'static, S: Signer + 'static, E: EthSpec> Service<B,
         // Builds a mapping of Epoch -> Map(PublicKey, EpochDuty)
         // where EpochDuty contains slot numbers and attestation data that each validator needs to
         // produce work on.
-        let duties_map = RwLock::new(EpochDutiesMap::new(slots_per_epoch));
+        let duties_store = DutiesStore::new(slots_per_epoch);
+
+        // Restore the duties, signing history and processed slots of the previous run, if any.
+        let snapshot = match &client_config.snapshot_dir {
+            Some(dir) => match Snapshot::load(dir) {
+                Ok(snapshot) => snapshot,
+                Err(e) => {
+                    warn!(log, "Unable to restore snapshot"; "dir" => format!("{:?}", dir), "error" => e.to_string());
+                    None
+                }
+            },
+            None => None,
+        };
+        let snapshot = snapshot.and_then(|snapshot| match snapshot.duties_store(slots_per_epoch) {
+            Ok(duties_store) => Some((snapshot, duties_store)),
+            Err(e) => {
+                warn!(log, "Unable to restore snapshot duties"; "error" => e.to_string());
+                None
+            }
+        });
+        let (duties_store, slashing_protection, attested_slot) = match snapshot {
+            Some((snapshot, restored_duties)) => {
+                info!(
+                    log,
+                    "Restored snapshot";
+                    "slot" => snapshot.current_slot.as_u64(),
+                    "epochs_of_duties" => snapshot.duties.len(),
+                );
+                (
+                    restored_duties,
+                    SlashingProtection::import(snapshot.slashing_protection),
+                    snapshot.attested_slot,
+                )
+            }
+            None => (duties_store, SlashingProtection::new(), None),
+        };
+        // Restore the duties obtained by the previous run, unless the snapshot restored them.
+        let duties_cache = DutiesCache::new(&client_config.data_dir);
+        if duties_store
+            .epochs()
+            .map_or(false, |epochs| epochs.is_empty())
+        {
+            match duties_cache.restore(&duties_store, current_slot.epoch(slots_per_epoch)) {
+                Ok(epochs) if epochs.is_empty() => {}
+                Ok(epochs) => info!(
+                    log,
+                    "Restored cached duties";
+                    "epochs" => format!("{:?}", epochs),
+                    "note" => "the duties are verified with the beacon node before they are trusted",
+                ),
+                Err(e) => warn!(log, "Unable to restore cached duties"; "error" => e.to_string()),
+            }
+        }
+        // every message approved from now on is durably recorded before it is signed
+        let signing_log = SigningLog::path(&client_config.data_dir);
+        let (slashing_protection, uncommitted) = slashing_protection.with_log(&signing_log)?;
+        if uncommitted > 0 {
+            warn!(
+                log,
+                "Signing was interrupted by the previous shutdown";
+                "messages" => uncommitted,
+                "note" => "the messages are treated as signed",
+            );
+        }
 
         // builds a manager which maintains the list of current duties for all known validators
         // and can check when a validator needs to perform a task.
+        let fee_recipients = Arc::new(
+            client_config
+                .fee_recipients()
+                .map_err(Error::FeeRecipients)?,
+        );
+
+        let gas_limits = client_config.gas_limits().map_err(Error::GasLimits)?;
+        let builder_registrar = builder_client.as_ref().map(|builder_client| {
+            Arc::new(BuilderRegistrar::new(
+                builder_client.clone(),
+                keypairs.clone(),
+                fee_recipients.clone(),
+                gas_limits,
+                &eth2_config.spec,
+            ))
+        });
+
+        let health = Arc::new(Health::new(
+            Duration::from_secs(eth2_config.spec.seconds_per_slot),
+            slots_per_epoch,
+            started,
+        ));
+        health.keystores_loaded(keypairs.len());
+
+        if client_config.dry_run {
+            warn!(
+                log,
+                "Dry run: blocks and attestations will be signed with throwaway keys and not published"
+            );
+        }
+
+        let notifier = Arc::new(
+            Notifier::new(
+                client_config.webhooks.clone(),
+                client_config.beacon_node_unreachable_slots,
+                log.clone(),
+            )
+            .map_err(Error::Notifier)?,
+        );
+        let slashing_watcher = Arc::new(SlashingWatcher::new(
+            client_config.validators_dir(),
+            notifier.clone(),
+            log.clone(),
+        ));
+        let signing_lease = match &client_config.signing_lease {
+            Some(config) => {
+                let signing_lease = SigningLease::new(
+                    config,
+                    eth2_config.spec.seconds_per_slot,
+                    client_config.slots_per_epoch,
+                )
+                .map_err(Error::SigningLease)?;
+                info!(
+                    log,
+                    "Claiming validators before signing";
+                    "backend" => format!("{:?}", config.backend),
+                    "owner" => signing_lease.owner(),
+                    "lease_secs" => signing_lease.lease_secs(),
+                );
+                signing_lease
+            }
+            None => SigningLease::disabled(),
+        };
+
+        let metrics_registry = Registry::new();
+        let proposal_timings = Arc::new(ProposalTimings::default());
+        proposal_timings
+            .register(&metrics_registry)
+            .map_err(Error::Metrics)?;
+        endpoint_scores
+            .register(&metrics_registry)
+            .map_err(Error::Metrics)?;
+        health.register(&metrics_registry).map_err(Error::Metrics)?;
+
+        let spec = Arc::new(eth2_config.spec);
         let duties_manager = Arc::new(DutiesManager {
-            duties_map,
+            store: Arc::new(duties_store),
             // these are abstract objects capable of signing
             signers: keypairs,
             beacon_node: validator_client,
+            fee_recipients: fee_recipients.clone(),
+            prepared_epoch: RwLock::new(None),
+            subscriptions: RwLock::new(HashSet::new()),
+            fork: fork.clone(),
+            spec: spec.clone(),
+            schedule_file: client_config.duty_schedule_file.clone(),
+            health: health.clone(),
+            notifier: notifier.clone(),
+            slashing_watcher: slashing_watcher.clone(),
         });
 
-        let spec = Arc::new(eth2_config.spec);
+        let recorder = match &client_config.trace_file {
+            Some(path) => {
+                Some(
+                    Recorder::open(path, log.clone()).map_err(|source| Error::Trace {
+                        path: path.clone(),
+                        source,
+                    })?,
+                )
+            }
+            None => None,
+        };
+
+        let scheduler = Scheduler::new(Duration::from_secs(spec.seconds_per_slot), log.clone());
+        let inclusion_monitor = Arc::new(InclusionMonitor::new(
+            slots_per_epoch,
+            spec.min_attestation_inclusion_delay,
+            client_config.inclusion_report_file.clone(),
+            log.clone(),
+        ));
 
         Ok(Service {
             fork,
             slot_clock,
             current_slot,
+            slot_tracker: SlotTracker::new(genesis_slot),
             slots_per_epoch,
             spec,
             duties_manager,
+            attested_slot,
+            beacon_node_client,
             beacon_block_client,
+            beacon_block_endpoint: client_config
+                .beacon_api
+                .clone()
+                .unwrap_or_else(|| client_config.server.clone()),
+            block_latencies: Arc::new(BlockLatencies::default()),
+            publish_target: PublishTarget {
+                target: Duration::from_millis(client_config.block_publish_target_ms),
+                cutoff: client_config
+                    .late_block_cutoff_ms
+                    .map(Duration::from_millis),
+            },
+            builder_client,
+            builder_registrar,
             attestation_client,
+            fee_recipients,
+            slashing_protection: Arc::new(slashing_protection),
+            slashing_protection_retention: client_config.slashing_protection_retention,
+            validators_dir: client_config.validators_dir(),
+            snapshot_dir: client_config.snapshot_dir.clone(),
+            duties_cache,
+            recorder,
+            chain_split_detector,
+            pause_on_chain_split: client_config.pause_on_chain_split,
+            signing_paused: false,
+            health,
+            inclusion_monitor,
+            notifier,
+            slashing_watcher,
+            signing_lease: Arc::new(signing_lease),
+            proposal_timings,
+            endpoint_scores,
+            metrics_registry,
+            metrics_push: client_config.metrics_push.clone(),
+            dry_run: client_config.dry_run,
+            health_address: client_config.health_address,
+            scheduler,
+            block_production_pool: Arc::new(ProductionPool::new(pool::DEFAULT_WORKERS)),
+            in_flight: InFlight::default(),
             log,
             _phantom: PhantomData,
         })
     }
 
-    /// Initialise the service then run the core thread.
+    /// Initialise the service then run the core thread until it is signalled to shut down,
+    /// returning how it shut down.
     // TODO: Improve handling of generic BeaconNode types, to stub grpcClient
     pub fn start(
         client_config: ValidatorConfig,
         eth2_config: Eth2Config,
         log: slog::Logger,
-    ) -> error_chain::Result<()> {
-        // connect to the node and retrieve its properties and initialize the gRPC clients
-        let mut service = Service::<ValidatorServiceClient, Keypair, E>::initialize_service(
-            client_config,
-            eth2_config,
-            log,
-        )?;
-
-        // we have connected to a node and established its parameters. Spin up the core service
+    ) -> error::Result<Outcome> {
+        // hold the data directory until the service stops, so no other validator client signs
+        // with the same keys and slashing protection history
+        if client_config.force_unlock {
+            match DatadirLock::force_unlock(&client_config.data_dir)? {
+                Some(holder) => warn!(
+                    log,
+                    "Forcibly removed the data directory lock";
+                    "holder" => holder.to_string(),
+                    "warning" => "if the holder is still running, its validators may be slashed"
+                ),
+                None => warn!(
+                    log,
+                    "The data directory was not locked, nothing to force unlock"
+                ),
+            }
+        }
+        let _datadir_lock = DatadirLock::acquire(&client_config.data_dir)?;
 
-        // set up the validator service runtime
+        // set up the validator service runtime, on which all duties are performed
         let mut runtime = Builder::new()
             .clock(Clock::system())
             .name_prefix("validator-client-")
             .build()
-            .map_err(|e| format!("Tokio runtime failed: {}", e))?;
+            .map_err(Error::Runtime)?;
+
+        // load the validator keys whilst connecting to the node
+        let startup = Startup::new(&client_config, &log);
 
-        let duration_to_next_slot = service
+        // connect to the node and retrieve its properties and initialize the clients
+        match client_config.beacon_api.clone() {
+            Some(url) => Self::initialize_rest_service(
+                startup,
+                &url,
+                &mut runtime,
+                client_config,
+                eth2_config,
+                log,
+            )?
+            .run(runtime),
+            None => Self::initialize_grpc_service(startup, client_config, eth2_config, log)?
+                .run(runtime),
+        }
+    }
+
+    /// Run the core thread of a connected service until a shutdown signal, then wait for the
+    /// productions in flight (see `shutdown`).
+    fn run(self, mut runtime: Runtime) -> error::Result<Outcome> {
+        let service = self;
+        let signals = Signals::install().map_err(Error::Signals)?;
+
+        // we have connected to a node and established its parameters. Spin up the core service
+
+        // before genesis, the first slot processed is the genesis slot
+        let duration_to_next_slot = match service
             .slot_clock
             .duration_to_next_slot()
-            .map_err(|e| format!("System clock error: {:?}", e))?
-            .ok_or_else::<error_chain::Error, _>(|| {
-                "Genesis is not in the past. Exiting.".into()
-            })?;
+            .map_err(Error::SlotClock)?
+        {
+            Some(duration) => duration,
+            None => service
+                .slot_clock
+                .duration_to_slot(service.slot_tracker.genesis_slot())
+                .map_err(Error::SlotClock)?
+                .unwrap_or_default(),
+        };
 
-        // set up the validator work interval - start at next slot and proceed every slot
-        let interval = {
+        // set up the validator work intervals - start at next slot and proceed every slot
+        let timers = {
             // Set the interval to start at the next slot, and every slot after
             let slot_duration = Duration::from_secs(service.spec.seconds_per_slot);
             //TODO: Handle checked add correctly
-            Interval::new(Instant::now() + duration_to_next_slot, slot_duration)
+            let slot_start = Instant::now() + duration_to_next_slot;
+            let attestation_deadline = slot_start + ATTESTATION_DEADLINE.duration(slot_duration);
+
+            Interval::new(slot_start, slot_duration)
+                .map(|_| ServiceEvent::SlotStart)
+                .select(
+                    Interval::new(attestation_deadline, slot_duration)
+                        .map(|_| ServiceEvent::AttestationDeadline),
+                )
+                .map_err(|e| format!("Service timer failed: {:?}", e))
         };
 
+        let head_events = service.subscribe_head();
+
+        // poll duties immediately rather than waiting for the next slot, so that duties are known
+        // as soon as possible after startup
+        runtime.spawn(service.check_for_duties());
+        runtime.spawn(service.block_production_pool.workers());
+
+        if let Some(address) = &service.health_address {
+            let sources = health::Sources {
+                timings: service.proposal_timings.clone(),
+                registry: service.metrics_registry.clone(),
+                duties: service.duties_manager.store.clone(),
+                slashing_protection: service.slashing_protection.clone(),
+                endpoint_scores: service.endpoint_scores.clone(),
+            };
+            let server = health::serve(
+                address,
+                service.health.clone(),
+                sources,
+                service.log.clone(),
+            )
+            .map_err(|e| Error::Health {
+                address: *address,
+                source: e,
+            })?;
+            runtime.spawn(server);
+        }
+
+        if let Some(config) = &service.metrics_push {
+            let pusher = MetricsPusher::new(
+                config.clone(),
+                service.metrics_registry.clone(),
+                service.log.clone(),
+            )
+            .map_err(Error::MetricsPush)?;
+            runtime.spawn(pusher.run());
+        }
+
+        service.spawn_readiness_monitor(&mut runtime);
+
+        // the parts of the service which outlive it during shutdown
+        let in_flight = service.in_flight.clone();
+        let block_production_pool = service.block_production_pool.clone();
+        let slashing_protection = service.slashing_protection.clone();
+        let inclusion_monitor = service.inclusion_monitor.clone();
+        let scheduler = service.scheduler.clone();
+        let log = service.log.clone();
+
         /* kick off the core service */
-        runtime.block_on(
-            interval
-                .for_each(move |_| {
-                    // wait for node to process
-                    std::thread::sleep(TIME_DELAY_FROM_SLOT);
-                    // if a non-fatal error occurs, proceed to the next slot.
-                    let _ignore_error = service.per_slot_execution();
-                    // completed a slot process
-                    Ok(())
-                })
-                .map_err(|e| format!("Service thread failed: {:?}", e)),
-        )?;
-        // validator client exited
-        Ok(())
+        // Events are processed in order, each resolving to the service once processed. Block and
+        // attestation production is spawned onto the runtime, so that it does not delay events.
+        let core = timers
+            .select(head_events)
+            .fold(service, |service, event| service.process_event(event))
+            .map_err(Error::Service);
+        // The service is dropped at the first signal, so that no further production is started.
+        let signals = match runtime.block_on(core.select2(signals.next())) {
+            Ok(future::Either::A(_)) => return Ok(Outcome::Clean),
+            Ok(future::Either::B((signals, _service))) => signals,
+            Err(future::Either::A((e, _))) => return Err(e),
+            Err(future::Either::B(((), _))) => {
+                return Err(Error::Signals("the signal handler stopped".to_string()))
+            }
+        };
+
+        let cancelled = block_production_pool.cancel_queued();
+        info!(
+            log,
+            "Shutting down";
+            "in_flight" => in_flight.count(),
+            "cancelled_productions" => cancelled,
+            "timeout_secs" => DRAIN_TIMEOUT.as_secs(),
+            "note" => "signal again to abandon the productions in flight",
+        );
+        let outcome = runtime
+            .block_on(shutdown::drain(in_flight, DRAIN_TIMEOUT, signals))
+            .map_err(|()| {
+                Error::Service("unable to await the productions in flight".to_string())
+            })?;
+
+        if let Err(e) = slashing_protection.flush() {
+            warn!(log, "Unable to flush slashing protection"; "error" => format!("{:?}", e));
+        }
+        inclusion_monitor.save_report();
+        for (task, metrics) in scheduler.metrics() {
+            info!(log, "Task deadlines"; "task" => task, "completed" => metrics.completed, "missed" => metrics.missed);
+        }
+        match outcome {
+            Outcome::Clean => info!(log, "Every production in flight completed"),
+            Outcome::Forced { in_flight } => warn!(
+                log,
+                "Abandoned the productions in flight";
+                "in_flight" => in_flight,
+                "note" => "their messages may have been signed without being published",
+            ),
+        }
+        // the runtime is dropped on return, which cancels any abandoned productions
+        Ok(outcome)
+    }
+
+    /// Reports readiness to the service manager which started the validator client (if any) once
+    /// its duties are loaded, and feeds its watchdog whilst the validator client is healthy.
+    fn spawn_readiness_monitor(&self, runtime: &mut Runtime) {
+        let manager = match ServiceManager::detect() {
+            Ok(Some(manager)) => manager,
+            Ok(None) => return,
+            Err(e) => {
+                warn!(self.log, "Unable to contact the service manager"; "error" => e.to_string());
+                return;
+            }
+        };
+        info!(self.log, "Reporting readiness to the service manager");
+
+        let health = self.health.clone();
+        let probe = move || {
+            let report = health.report();
+            Probe {
+                live: report.is_healthy(),
+                ready: report.is_ready(),
+                status: format!(
+                    "{}, {}, {}",
+                    report.slot_clock.detail, report.duties.detail, report.keystores.detail
+                ),
+            }
+        };
+        runtime.spawn(system_service::monitor(manager, probe, self.log.clone()));
+    }
+
+    /// Processes a single event, resolving to the service once processing is complete.
+    fn process_event(mut self, event: ServiceEvent) -> ServiceFuture<Self> {
+        match event {
+            ServiceEvent::SlotStart => {
+                // wait for node to process, unless blocks must be requested sooner to be
+                // published by the target.
+                let delay = self.publish_target.request_delay(
+                    TIME_DELAY_FROM_SLOT,
+                    self.block_latencies
+                        .expected(&self.beacon_block_endpoint, Request::Produce),
+                );
+                Box::new(
+                    Delay::new(Instant::now() + delay)
+                        .map_err(|e| format!("Service timer failed: {:?}", e))
+                        .and_then(move |()| self.per_slot_execution()),
+                )
+            }
+            ServiceEvent::AttestationDeadline => {
+                self.process_attestation_duties();
+                Box::new(future::ok(self))
+            }
+            ServiceEvent::Head(event) => self.process_head_event(event),
+        }
     }
 
     /// The execution logic that runs every slot.
     // Errors are logged to output, and core execution continues unless fatal errors occur.
-    fn per_slot_execution(&mut self) -> error_chain::Result<()> {
+    fn per_slot_execution(mut self) -> ServiceFuture<Self> {
         /* get the new current slot and epoch */
-        self.update_current_slot()?;
+        // if a non-fatal error occurs, proceed to the next slot.
+        if self.update_current_slot().is_err() {
+            return Box::new(future::ok(self));
+        }
 
-        /* check for new duties */
-        self.check_for_duties();
+        if let Some(recorder) = &self.recorder {
+            recorder.record(&TraceEvent::SlotStart {
+                slot: self.current_slot,
+            });
+        }
 
-        /* process any required duties for validators */
-        self.process_duties();
+        /* compare the beacon node with any cross-check nodes */
+        self.check_for_chain_split();
 
-        Ok(())
+        /* prune old signing history at the start of each epoch */
+        self.compact_slashing_protection();
+
+        /* check the inclusion of earlier attestations */
+        tokio::spawn(InclusionMonitor::check::<A, E>(
+            self.inclusion_monitor.clone(),
+            self.attestation_client.clone(),
+            self.current_slot,
+        ));
+
+        /* check for new duties at the start of each epoch, or if they are unknown or stale */
+        if !self.duties_due() {
+            /* duties report slashed validators, so poll for slashings half way through the epoch */
+            self.poll_for_slashings();
+            /* process any required block duties for validators, attestations are produced later */
+            self.process_block_duties();
+            self.save_snapshot();
+            return Box::new(future::ok(self));
+        }
+
+        let jitter = rand::thread_rng().gen_range(0, DUTIES_JITTER.as_millis() as u64);
+        Box::new(
+            Delay::new(Instant::now() + Duration::from_millis(jitter))
+                .map_err(|e| format!("Service timer failed: {:?}", e))
+                .and_then(move |()| {
+                    self.check_for_duties()
+                        .then(move |_| -> Result<Self, String> {
+                            self.save_duties_cache();
+                            self.process_block_duties();
+                            self.save_snapshot();
+                            Ok(self)
+                        })
+                }),
+        )
+    }
+
+    /// Returns `true` if the duties should be refreshed this slot: at the first slot of each
+    /// epoch, and in any slot whilst the duties of the current epoch are unknown (such as after
+    /// an update failed) or a re-org has left duties stale.
+    ///
+    /// Duties are otherwise left alone until the next epoch, since only a re-org can change them
+    /// and re-orgs are refreshed as soon as the beacon node reports them.
+    fn duties_due(&self) -> bool {
+        let current_epoch = self.current_slot.epoch(self.slots_per_epoch);
+        if self.current_slot == current_epoch.start_slot(self.slots_per_epoch) {
+            return true;
+        }
+
+        let store = &self.duties_manager.store;
+        let known = store
+            .epochs()
+            .map(|epochs| epochs.contains(&current_epoch))
+            .unwrap_or(false);
+        let stale = store
+            .stale_epochs()
+            .map(|epochs| !epochs.is_empty())
+            .unwrap_or(true);
+        !known || stale
+    }
+
+    /// Spawns a task which prunes signing history older than the retention period, if the current
+    /// slot is the first of its epoch.
+    fn compact_slashing_protection(&self) {
+        let retention = match self.slashing_protection_retention {
+            Some(retention) => retention,
+            None => return,
+        };
+        let current_epoch = self.current_slot.epoch(self.slots_per_epoch);
+        if self.current_slot != current_epoch.start_slot(self.slots_per_epoch)
+            || current_epoch.as_u64() <= retention
+        {
+            return;
+        }
+
+        let before = current_epoch - retention;
+        let slots_per_epoch = self.slots_per_epoch;
+        let slashing_protection = self.slashing_protection.clone();
+        let log = self.log.clone();
+        tokio::spawn(future::lazy(move || -> Result<(), ()> {
+            match slashing_protection.prune(before, slots_per_epoch) {
+                Ok(pruned) => debug!(
+                    log,
+                    "Compacted slashing protection";
+                    "before_epoch" => before.as_u64(),
+                    "blocks" => pruned.blocks,
+                    "attestations" => pruned.attestations,
+                ),
+                Err(e) => {
+                    warn!(log, "Unable to compact slashing protection"; "error" => format!("{:?}", e))
+                }
+            }
+            Ok(())
+        }));
+    }
+
+    /// Saves a snapshot of the service to the snapshot directory, if any.
+    fn save_snapshot(&self) {
+        let dir = match &self.snapshot_dir {
+            Some(dir) => dir,
+            None => return,
+        };
+
+        let result = Snapshot::new(
+            self.current_slot,
+            self.attested_slot,
+            &self.duties_manager.store,
+            &self.slashing_protection,
+        )
+        .and_then(|snapshot| snapshot.save(dir));
+
+        if let Err(e) = result {
+            warn!(self.log, "Unable to save snapshot"; "dir" => format!("{:?}", dir), "error" => e.to_string());
+        }
+    }
+
+    /// Writes the duties of the current and later epochs to the duties cache.
+    fn save_duties_cache(&self) {
+        let current_epoch = self.current_slot.epoch(self.slots_per_epoch);
+        if let Err(e) = self
+            .duties_cache
+            .save(&self.duties_manager.store, current_epoch)
+        {
+            warn!(self.log, "Unable to save the duties cache"; "error" => e.to_string());
+        }
+    }
+
+    /// Subscribe to changes of the beacon node's head.
+    ///
+    /// If the subscription fails, the returned stream is empty and attestations are produced at
+    /// the attestation deadline of each slot.
+    fn subscribe_head(&self) -> Box<dyn Stream<Item = ServiceEvent, Error = String> + Send> {
+        let beacon_node_client = match &self.beacon_node_client {
+            Some(client) => client,
+            None => return Box::new(stream::empty()),
+        };
+
+        match beacon_node_client
+            .client()
+            .subscribe_head_opt(&Empty::new(), beacon_node_client.call_option())
+        {
+            Ok(events) => {
+                let log = self.log.clone();
+                Box::new(
+                    events
+                        .then(move |result| {
+                            if let Err(e) = &result {
+                                warn!(log, "Head subscription failed"; "error" => format!("{:?}", e));
+                            }
+                            Ok::<_, String>(result.ok().map(ServiceEvent::Head))
+                        })
+                        .filter_map(|event| event),
+                )
+            }
+            Err(e) => {
+                warn!(self.log, "Unable to subscribe to head events"; "error" => format!("{:?}", e));
+                Box::new(stream::empty())
+            }
+        }
+    }
+
+    /// Re-evaluate duties after a re-org and produce attestations once the head of the current
+    /// slot is known.
+    fn process_head_event(self, event: HeadEvent) -> ServiceFuture<Self> {
+        let slot = Slot::from(event.get_slot());
+
+        let duties: Box<dyn Future<Item = (), Error = ()> + Send> = if event.get_reorg() {
+            self.process_reorg(&event)
+        } else {
+            Box::new(future::ok(()))
+        };
+
+        let mut service = self;
+        Box::new(duties.then(move |_| -> Result<Self, String> {
+            if slot == service.current_slot {
+                debug!(service.log, "Head block received for current slot"; "slot" => slot.as_u64());
+                service.process_attestation_duties();
+            }
+            Ok(service)
+        }))
+    }
+
+    /// Logs a re-org, marks the duties which it may have changed as stale and refreshes those of
+    /// the current and later epochs.
+    ///
+    /// The returned future completes once the duties have been updated (or the update failed).
+    fn process_reorg(&self, event: &HeadEvent) -> Box<dyn Future<Item = (), Error = ()> + Send> {
+        let common_ancestor_slot = Slot::from(event.get_common_ancestor_slot());
+        self.health.reorg_observed();
+
+        let current_epoch = self.current_slot.epoch(self.slots_per_epoch);
+        let epochs = match self
+            .duties_manager
+            .store
+            .mark_stale_after_fork(common_ancestor_slot)
+        {
+            Ok(epochs) => epochs
+                .into_iter()
+                .filter(|epoch| *epoch >= current_epoch)
+                .collect::<Vec<_>>(),
+            Err(e) => {
+                warn!(self.log, "Unable to read known duties after re-org"; "error" => e.to_string());
+                vec![current_epoch]
+            }
+        };
+
+        warn!(
+            self.log,
+            "Beacon node re-org";
+            "slot" => event.get_slot(),
+            "depth" => event.get_reorg_depth(),
+            "common_ancestor_slot" => common_ancestor_slot.as_u64(),
+            "refreshed_epochs" => format!("{:?}", epochs),
+        );
+
+        let updates = epochs
+            .into_iter()
+            .map(|epoch| {
+                self.duties_manager
+                    .clone()
+                    .run_update(epoch, self.log.clone())
+            })
+            .collect::<Vec<_>>();
+        Box::new(future::join_all(updates).map(|_| ()))
     }
 
     /// Updates the known current slot and epoch.
-    fn update_current_slot(&mut self) -> error_chain::Result<()> {
-        let current_slot = match self.slot_clock.present_slot() {
+    fn update_current_slot(&mut self) -> error::Result<()> {
+        let current_slot = match self.slot_clock.checked_present_slot() {
+            // the slot clock never goes backwards, so the slots it has already returned are not
+            // processed (and signed in) again
+            Err(SystemTimeSlotClockError::ClockWentBackwards {
+                present_slot,
+                max_slot,
+            }) => {
+                crit!(
+                    self.log,
+                    "System time went backwards, skipping slot";
+                    "present_slot" => present_slot.as_u64(),
+                    "max_slot" => max_slot.as_u64(),
+                );
+                self.health
+                    .slot_clock_failed(format!("system time went back to slot {}", present_slot));
+                return Err(Error::SlotClock(
+                    SystemTimeSlotClockError::ClockWentBackwards {
+                        present_slot,
+                        max_slot,
+                    },
+                ));
+            }
             Err(e) => {
                 error!(self.log, "SystemTimeError {:?}", e);
-                return Err("Could not read system time".into());
+                self.health.slot_clock_failed(format!("{:?}", e));
+                return Err(Error::SlotClock(e));
+            }
+            Ok(present_slot) => {
+                let to_genesis = self
+                    .slot_clock
+                    .duration_to_slot(self.slot_tracker.genesis_slot())
+                    .map_err(Error::SlotClock)?;
+                self.slot_tracker
+                    .fired_slot(present_slot, to_genesis)
+                    .ok_or_else(|| Error::GenesisNotInPast)?
             }
-            Ok(slot) => slot.ok_or_else::<error_chain::Error, _>(|| {
-                "Genesis is not in the past. Exiting.".into()
-            })?,
         };
 
         let current_epoch = current_slot.epoch(self.slots_per_epoch);
@@ -306,86 +1498,328 @@ impl<B: BeaconNodeDuties + 'static, S: Signer + 'static, E: EthSpec> Service<B,
         // this is a non-fatal error. If the slot clock repeats, the node could
         // have been slow to process the previous slot and is now duplicating tasks.
         // We ignore duplicated but raise a critical error.
-        if current_slot <= self.current_slot {
-            crit!(
-                self.log,
-                "The validator tried to duplicate a slot. Likely missed the previous slot"
-            );
-            return Err("Duplicate slot".into());
+        match self.slot_tracker.process(current_slot) {
+            Ok(()) => {}
+            Err(Refused::BeforeGenesis) => return Err(Error::GenesisNotInPast),
+            Err(Refused::Duplicate { last_processed }) => {
+                crit!(
+                    self.log,
+                    "The validator tried to duplicate a slot. Likely missed the previous slot";
+                    "last_processed" => last_processed.as_u64(),
+                );
+                return Err(Error::DuplicateSlot { slot: current_slot });
+            }
         }
         self.current_slot = current_slot;
+        self.health.slot_processed(current_slot);
+        self.notifier.slot_processed(current_slot);
         info!(self.log, "Processing"; "slot" => current_slot.as_u64(), "epoch" => current_epoch.as_u64());
+
+        // switch to any fork scheduled for this epoch, so that duties are signed with its domain.
+        let fork = self.spec.upgrade_fork(&self.fork, current_epoch);
+        if fork != self.fork {
+            info!(
+                self.log,
+                "Fork upgraded";
+                "epoch" => fork.epoch.as_u64(),
+                "version" => hex::encode(fork.current_version),
+            );
+            self.fork = fork;
+        }
         Ok(())
     }
 
-    /// For all known validator keypairs, update any known duties from the beacon node.
-    fn check_for_duties(&mut self) {
-        let cloned_manager = self.duties_manager.clone();
-        let cloned_log = self.log.clone();
+    /// Cross-checks the beacon node with other beacon nodes, alerting if any have split and
+    /// pausing signing (if configured) whilst the beacon node has split from the majority.
+    fn check_for_chain_split(&mut self) {
+        let detector = match &mut self.chain_split_detector {
+            Some(detector) => detector,
+            None => return,
+        };
+
+        let result = detector.check();
+        for (name, head_slot) in detector.head_slots() {
+            self.endpoint_scores
+                .record_head(name, *head_slot, self.current_slot);
+        }
+
+        let paused = match result {
+            Ok(ChainSplit::None) => false,
+            Ok(ChainSplit::Nodes(nodes)) => {
+                warn!(
+                    self.log,
+                    "Cross-check beacon nodes have split from the beacon node";
+                    "nodes" => nodes.join(","),
+                );
+                false
+            }
+            Ok(ChainSplit::Primary) => {
+                crit!(
+                    self.log,
+                    "Beacon node has split from the majority of cross-check beacon nodes";
+                    "help" => "check the beacon node is correctly configured and synced",
+                );
+                self.pause_on_chain_split
+            }
+            Err(e) => {
+                warn!(self.log, "Unable to cross-check the beacon node"; "error" => format!("{:?}", e));
+                self.signing_paused
+            }
+        };
+
+        if paused != self.signing_paused {
+            if paused {
+                crit!(self.log, "Signing paused due to chain split");
+            } else {
+                info!(self.log, "Signing resumed");
+            }
+            self.signing_paused = paused;
+        }
+    }
+
+    /// For all known validator keypairs, update the duties of the current epoch, and of any later
+    /// epoch whose duties are stale, from the beacon node, and register their proposal
+    /// preferences with the builder once per epoch.
+    ///
+    /// Stale duties are those which a re-org may have changed but which could not yet be
+    /// refreshed, so they are retried every slot until they are (see `duties_due`).
+    ///
+    /// Once updated, any attestation duties not yet sent to the beacon node are sent, so that it
+    /// subscribes to the subnets of aggregators ahead of their slots.
+    ///
+    /// The returned future completes once the duties have been updated (or the update failed).
+    fn check_for_duties(&self) -> Box<dyn Future<Item = (), Error = ()> + Send> {
         let current_epoch = self.current_slot.epoch(self.slots_per_epoch);
-        // spawn a new thread separate to the runtime
-        // TODO: Handle thread termination/timeout
-        // TODO: Add duties thread back in, with channel to process duties in duty change.
-        // leave sequential for now.
-        //std::thread::spawn(move || {
-        // the return value is a future which returns ready.
-        // built to be compatible with the tokio runtime.
-        let _empty = cloned_manager.run_update(current_epoch, cloned_log.clone());
-        //});
+        let stale_epochs = match self.duties_manager.store.stale_epochs() {
+            Ok(epochs) => epochs,
+            Err(e) => {
+                warn!(self.log, "Unable to read stale duties"; "error" => e.to_string());
+                vec![]
+            }
+        };
+        let epochs = std::iter::once(current_epoch).chain(
+            stale_epochs
+                .into_iter()
+                .filter(|epoch| *epoch > current_epoch),
+        );
+
+        // TODO: Handle update timeout
+        let updates = epochs
+            .map(|epoch| {
+                self.duties_manager
+                    .clone()
+                    .run_update(epoch, self.log.clone())
+            })
+            .collect::<Vec<_>>();
+        let manager = self.duties_manager.clone();
+        let current_slot = self.current_slot;
+        let log = self.log.clone();
+        let update: Box<dyn Future<Item = (), Error = ()> + Send> =
+            Box::new(future::join_all(updates).then(move |_| {
+                manager.prepare_subnets(current_slot).then(move |result| {
+                    match result {
+                        Err(e) => {
+                            warn!(log, "Unable to prepare attestation subnets"; "error" => e.to_string())
+                        }
+                        Ok(0) => {}
+                        Ok(sent) => debug!(log, "Prepared attestation subnets"; "duties" => sent),
+                    };
+                    Ok(())
+                })
+            }));
+
+        match &self.builder_registrar {
+            Some(registrar) => {
+                let registration = registrar
+                    .clone()
+                    .run_registration(current_epoch, self.log.clone());
+                Box::new(update.join(registration).map(|_| ()))
+            }
+            None => update,
+        }
+    }
+
+    /// Spawns a task which requests whether any validator has been slashed from the beacon node,
+    /// if the current slot is half way through its epoch.
+    fn poll_for_slashings(&self) {
+        let current_epoch = self.current_slot.epoch(self.slots_per_epoch);
+        if self.current_slot
+            != current_epoch.start_slot(self.slots_per_epoch) + self.slots_per_epoch / 2
+        {
+            return;
+        }
+
+        let pub_keys: Vec<PublicKey> = self
+            .duties_manager
+            .signers
+            .iter()
+            .map(Signer::to_public)
+            .collect();
+        tokio::spawn(self.slashing_watcher.clone().poll(
+            self.duties_manager.beacon_node.as_ref(),
+            current_epoch,
+            &pub_keys,
+        ));
+    }
+
+    /// Returns `true` if the operator has not disabled the validator of `signer`, and it has not
+    /// been slashed.
+    ///
+    /// The validator state is read from disk, so that it may be changed without a restart.
+    fn is_enabled(&self, signer: &S) -> bool {
+        let public_key = signer.to_public();
+        if self.slashing_watcher.is_slashed(&public_key) {
+            warn!(self.log, "Validator slashed, skipping duty"; "validator" => public_key.concatenated_hex_id());
+            return false;
+        }
+        let validator_id = public_key.concatenated_hex_id();
+
+        match ValidatorState::load(&self.validators_dir, &validator_id) {
+            Ok(state) => {
+                if !state.enabled {
+                    info!(self.log, "Validator disabled, skipping duty"; "validator" => validator_id);
+                }
+                state.enabled
+            }
+            Err(e) => {
+                // The operator may have been attempting to disable the validator.
+                error!(
+                    self.log,
+                    "Unable to read validator state, skipping duty";
+                    "validator" => validator_id,
+                    "error" => format!("{:?}", e),
+                );
+                false
+            }
+        }
     }
 
-    /// If there are any duties to process, spawn a separate thread and perform required actions.
-    fn process_duties(&mut self) {
+    /// Returns the instant at which the current slot started.
+    ///
+    /// If the slot clock cannot be read, the slot is assumed to have just started.
+    fn current_slot_start(&self) -> Instant {
+        self.scheduler
+            .slot_start(&self.slot_clock)
+            .unwrap_or_else(Instant::now)
+    }
+
+    /// If there are any block duties to process, queue the production of each block on the block
+    /// production pool.
+    fn process_block_duties(&mut self) {
+        if self.signing_paused {
+            return;
+        }
+
+        // blocks may be published until the end of the current slot.
+        let slot_start = self.current_slot_start();
+        let deadline = self.scheduler.instant(slot_start, PRODUCTION_DEADLINE);
+        let publish_window = self.publish_target.window(
+            slot_start,
+            self.block_latencies
+                .expected(&self.beacon_block_endpoint, Request::Publish),
+        );
+
         if let Some(work) = self.duties_manager.get_current_work(self.current_slot) {
             for (signer_index, work_type) in work {
-                if work_type.produce_block {
+                if work_type.produce_block
+                    && self.is_enabled(&self.duties_manager.signers[signer_index])
+                {
                     // we need to produce a block
-                    // spawns a thread to produce a beacon block
-                    let signers = self.duties_manager.signers.clone(); // this is an arc
-                    let fork = self.fork.clone();
-                    let slot = self.current_slot;
-                    let spec = self.spec.clone();
-                    let beacon_node = self.beacon_block_client.clone();
+                    // queues a task to produce a beacon block
+                    let signer = self.duties_manager.signers[signer_index].clone();
                     let log = self.log.clone();
-                    let slots_per_epoch = self.slots_per_epoch;
-                    std::thread::spawn(move || {
-                        info!(log, "Producing a block"; "Validator"=> format!("{}", signers[signer_index]));
-                        let signer = &signers[signer_index];
-                        let mut block_producer = BlockProducer {
-                            fork,
-                            slot,
-                            spec,
-                            beacon_node,
-                            signer,
-                            slots_per_epoch,
-                            _phantom: PhantomData::<E>,
+                    info!(log, "Producing a block"; "Validator"=> format!("{}", signer));
+                    let block_producer = BlockProducer {
+                        fork: self.fork.clone(),
+                        slot: self.current_slot,
+                        spec: self.spec.clone(),
+                        beacon_node: Arc::new(Recording::new(
+                            Arc::new(Timed::new(
+                                self.beacon_block_client.clone(),
+                                self.beacon_block_endpoint.clone(),
+                                self.block_latencies.clone(),
+                            )),
+                            self.recorder.clone(),
+                        )),
+                        fee_recipient: self.fee_recipients.get(&signer.to_public()),
+                        signer,
+                        slots_per_epoch: self.slots_per_epoch,
+                        slashing_protection: self.slashing_protection.clone(),
+                        signing_lease: self.signing_lease.clone(),
+                        slot_start,
+                        deadline,
+                        publish_window,
+                        timings: self.proposal_timings.clone(),
+                        notifier: self.notifier.clone(),
+                        dry_run: self.dry_run,
+                        _phantom: PhantomData::<E>,
+                    };
+                    let builder = self.builder_client.clone();
+                    let scheduler = self.scheduler.clone();
+                    let in_flight = self.in_flight.clone();
+                    self.block_production_pool.submit(deadline, move || {
+                        let task = match builder {
+                            Some(builder) => {
+                                block_producer.handle_produce_block_via_builder(builder, log)
+                            }
+                            None => block_producer.handle_produce_block(log),
                         };
-                        block_producer.handle_produce_block(log);
+                        in_flight.track(scheduler.run(
+                            "produce_block",
+                            slot_start,
+                            PRODUCTION_DEADLINE,
+                            task,
+                        ))
                     });
                 }
-                if work_type.attestation_duty.is_some() {
+            }
+        }
+    }
+
+    /// If there are any attestation duties to process that have not yet been processed this
+    /// slot, spawn a task on the runtime to produce each attestation.
+    fn process_attestation_duties(&mut self) {
+        if self.attested_slot == Some(self.current_slot) {
+            return;
+        }
+        self.attested_slot = Some(self.current_slot);
+        self.save_snapshot();
+
+        if self.signing_paused {
+            return;
+        }
+
+        let slot_start = self.current_slot_start();
+        if let Some(work) = self.duties_manager.get_current_work(self.current_slot) {
+            for (signer_index, work_type) in work {
+                if work_type.attestation_duty.is_some()
+                    && self.is_enabled(&self.duties_manager.signers[signer_index])
+                {
                     // we need to produce an attestation
-                    // spawns a thread to produce and sign an attestation
-                    let signers = self.duties_manager.signers.clone(); // this is an arc
-                    let fork = self.fork.clone();
-                    let spec = self.spec.clone();
-                    let beacon_node = self.attestation_client.clone();
+                    // spawns a task to produce and sign an attestation
+                    let signer = self.duties_manager.signers[signer_index].clone();
                     let log = self.log.clone();
-                    let slots_per_epoch = self.slots_per_epoch;
-                    std::thread::spawn(move || {
-                        info!(log, "Producing an attestation"; "Validator"=> format!("{}", signers[signer_index]));
-                        let signer = &signers[signer_index];
-                        let mut attestation_producer = AttestationProducer {
-                            fork,
-                            duty: work_type.attestation_duty.expect("Should never be none"),
-                            spec,
-                            beacon_node,
-                            signer,
-                            slots_per_epoch,
-                            _phantom: PhantomData::<E>,
-                        };
-                        attestation_producer.handle_produce_attestation(log);
-                    });
+                    info!(log, "Producing an attestation"; "Validator"=> format!("{}", signer));
+                    let attestation_producer = AttestationProducer {
+                        fork: self.fork.clone(),
+                        duty: work_type.attestation_duty.expect("Should never be none"),
+                        spec: self.spec.clone(),
+                        beacon_node: self.attestation_client.clone(),
+                        signer,
+                        slots_per_epoch: self.slots_per_epoch,
+                        slashing_protection: self.slashing_protection.clone(),
+                        signing_lease: self.signing_lease.clone(),
+                        inclusion_monitor: self.inclusion_monitor.clone(),
+                        notifier: self.notifier.clone(),
+                        dry_run: self.dry_run,
+                        _phantom: PhantomData::<E>,
+                    };
+                    tokio::spawn(self.in_flight.track(self.scheduler.run(
+                        "produce_attestation",
+                        slot_start,
+                        PRODUCTION_DEADLINE,
+                        attestation_producer.handle_produce_attestation(log),
+                    )));
                 }
             }
         }
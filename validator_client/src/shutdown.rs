@@ -0,0 +1,208 @@
+//! Soft shutdown of the validator client on `SIGINT` or `SIGTERM` (or a stop request from the
+//! Windows service control manager).
+//!
+//! The first signal stops the service from starting any further productions, then waits up to
+//! `DRAIN_TIMEOUT` for the productions in flight to complete, so that a message which has been
+//! signed is not abandoned before it is published. A second signal, or the timeout, abandons the
+//! productions still in flight, and the shutdown is reported as forced.
+use futures::future::{self, Loop};
+use futures::sync::mpsc;
+use futures::{Future, Stream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::timer::Delay;
+
+/// The longest time for which in-flight productions are awaited, which is the longest a
+/// production may run (until the end of its slot).
+pub const DRAIN_TIMEOUT: Duration = Duration::from_secs(12);
+
+/// The interval at which the in-flight productions are counted whilst draining.
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The exit code of a forced shutdown.
+pub const FORCED_EXIT_CODE: i32 = 2;
+
+/// How the validator client shut down.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Outcome {
+    /// Every production in flight completed.
+    Clean,
+    /// `in_flight` productions were abandoned.
+    Forced { in_flight: usize },
+}
+
+impl Outcome {
+    /// Returns the exit code of the process after shutting down with this outcome.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Outcome::Clean => 0,
+            Outcome::Forced { .. } => FORCED_EXIT_CODE,
+        }
+    }
+}
+
+/// Counts the productions (each of which may be signing or publishing) in flight.
+#[derive(Debug, Clone, Default)]
+pub struct InFlight {
+    count: Arc<AtomicUsize>,
+}
+
+/// Counts a production as in flight until it is dropped.
+struct Guard(Arc<AtomicUsize>);
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl InFlight {
+    /// Returns `task`, counted as in flight from now until it completes or is dropped.
+    pub fn track<F>(&self, task: F) -> Box<dyn Future<Item = (), Error = ()> + Send>
+    where
+        F: Future<Item = (), Error = ()> + Send + 'static,
+    {
+        self.count.fetch_add(1, Ordering::SeqCst);
+        let guard = Guard(self.count.clone());
+        Box::new(task.then(move |result| {
+            drop(guard);
+            result
+        }))
+    }
+
+    /// Returns the number of productions in flight.
+    pub fn count(&self) -> usize {
+        self.count.load(Ordering::SeqCst)
+    }
+}
+
+/// The signals which request a shutdown.
+pub struct Signals {
+    receiver: mpsc::UnboundedReceiver<()>,
+}
+
+impl Signals {
+    /// Returns `Signals` which receive a signal for each message sent to the returned sender.
+    pub fn channel() -> (mpsc::UnboundedSender<()>, Self) {
+        let (sender, receiver) = mpsc::unbounded();
+        (sender, Self { receiver })
+    }
+
+    /// Handles `SIGINT` and `SIGTERM` (and stop requests from the Windows service control
+    /// manager) as shutdown signals, rather than exiting immediately.
+    pub fn install() -> Result<Self, String> {
+        let (sender, signals) = Self::channel();
+        let stop_sender = sender.clone();
+        ctrlc::set_handler(move || {
+            let _ = sender.unbounded_send(());
+        })
+        .map_err(|e| format!("{:?}", e))?;
+        system_service::on_stop(move || {
+            let _ = stop_sender.unbounded_send(());
+        });
+        Ok(signals)
+    }
+
+    /// Returns a future which resolves with the remaining signals at the next signal.
+    ///
+    /// If no further signal can be received, the future never resolves.
+    pub fn next(self) -> Box<dyn Future<Item = Self, Error = ()> + Send> {
+        Box::new(
+            self.receiver.into_future().map_err(|_| ()).and_then(
+                |(signal, receiver)| match signal {
+                    Some(()) => future::Either::A(future::ok(Self { receiver })),
+                    None => future::Either::B(future::empty()),
+                },
+            ),
+        )
+    }
+}
+
+/// Returns a future which resolves once no production is in flight, or at `timeout` or the next
+/// of `signals`, whichever is first.
+pub fn drain(
+    in_flight: InFlight,
+    timeout: Duration,
+    signals: Signals,
+) -> Box<dyn Future<Item = Outcome, Error = ()> + Send> {
+    let counter = in_flight.clone();
+    let idle = future::loop_fn((), move |()| {
+        if counter.count() == 0 {
+            return future::Either::A(future::ok(Loop::Break(())));
+        }
+        future::Either::B(
+            Delay::new(Instant::now() + DRAIN_POLL_INTERVAL)
+                .map_err(|_| ())
+                .map(|()| Loop::Continue(())),
+        )
+    });
+    let deadline = Delay::new(Instant::now() + timeout).then(|_| -> Result<(), ()> { Ok(()) });
+    let forced = signals.next().map(|_| ());
+
+    Box::new(
+        idle.select(deadline)
+            .map(|_| ())
+            .map_err(|_| ())
+            .select(forced)
+            .then(move |_| -> Result<Outcome, ()> {
+                match in_flight.count() {
+                    0 => Ok(Outcome::Clean),
+                    in_flight => Ok(Outcome::Forced { in_flight }),
+                }
+            }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::runtime::Runtime;
+
+    /// A production which completes after `millis`.
+    fn production(
+        in_flight: &InFlight,
+        millis: u64,
+    ) -> Box<dyn Future<Item = (), Error = ()> + Send> {
+        in_flight.track(Delay::new(Instant::now() + Duration::from_millis(millis)).map_err(|_| ()))
+    }
+
+    #[test]
+    fn waits_for_productions_in_flight() {
+        let mut runtime = Runtime::new().expect("should create runtime");
+        let in_flight = InFlight::default();
+        runtime.spawn(production(&in_flight, 100));
+        runtime.spawn(production(&in_flight, 200));
+        assert_eq!(in_flight.count(), 2);
+
+        let (_sender, signals) = Signals::channel();
+        let outcome = runtime.block_on(drain(in_flight.clone(), Duration::from_secs(5), signals));
+        assert_eq!(outcome, Ok(Outcome::Clean));
+        assert_eq!(in_flight.count(), 0);
+    }
+
+    #[test]
+    fn abandons_productions_at_the_timeout_or_a_second_signal() {
+        let mut runtime = Runtime::new().expect("should create runtime");
+        let in_flight = InFlight::default();
+        runtime.spawn(production(&in_flight, 10_000));
+
+        let (_sender, signals) = Signals::channel();
+        let outcome = runtime.block_on(drain(
+            in_flight.clone(),
+            Duration::from_millis(100),
+            signals,
+        ));
+        assert_eq!(outcome, Ok(Outcome::Forced { in_flight: 1 }));
+
+        let (sender, signals) = Signals::channel();
+        sender.unbounded_send(()).expect("should send signal");
+        let outcome = runtime.block_on(drain(in_flight.clone(), Duration::from_secs(5), signals));
+        assert_eq!(outcome, Ok(Outcome::Forced { in_flight: 1 }));
+        assert_eq!(outcome.map(Outcome::exit_code), Ok(FORCED_EXIT_CODE));
+
+        // A dropped production is no longer in flight.
+        drop(production(&in_flight, 10_000));
+        assert_eq!(in_flight.count(), 1);
+    }
+}
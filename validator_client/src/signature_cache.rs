@@ -0,0 +1,229 @@
+//! A cache of recently produced signatures, so that signing the same message again (e.g., when a
+//! block is produced again after a failed publish) returns the same signature without asking the
+//! signer.
+//!
+//! Remote signers (e.g., HSMs) are slow, so each cache hit saves a round-trip. A repeated signature
+//! is never slashable, so returning a cached one is always safe.
+use crate::signer::{SignatureFuture, Signer};
+use futures::{future, Future};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use types::{PublicKey, Signature};
+
+/// The number of signatures cached for each validator, which covers the proposals, randao reveals
+/// and attestations of the last few epochs.
+pub const SIGNATURES_PER_VALIDATOR: usize = 16;
+
+/// A signature is cached by the validator which produced it and the message and domain it signs.
+///
+/// The slot of a signed message is committed to by its root, so it is not part of the key.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+struct Key {
+    validator: String,
+    domain: u64,
+    message: Vec<u8>,
+}
+
+#[derive(Default)]
+struct Entries {
+    signatures: HashMap<Key, Signature>,
+    /// All keys in `signatures`, least recently used first.
+    order: VecDeque<Key>,
+}
+
+/// A least-recently-used cache of signatures, shared by the signers of many validators.
+pub struct SignatureCache {
+    capacity: usize,
+    entries: Mutex<Entries>,
+}
+
+impl SignatureCache {
+    /// Create a cache which holds at most `capacity` signatures.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(Entries::default()),
+        }
+    }
+
+    /// Returns the number of cached signatures.
+    pub fn len(&self) -> usize {
+        self.entries
+            .lock()
+            .map(|entries| entries.signatures.len())
+            .unwrap_or(0)
+    }
+
+    /// Returns `true` if no signatures are cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn get(&self, key: &Key) -> Option<Signature> {
+        let mut entries = self.entries.lock().ok()?;
+        let signature = entries.signatures.get(key).cloned()?;
+
+        if let Some(i) = entries.order.iter().position(|k| k == key) {
+            entries.order.remove(i);
+        }
+        entries.order.push_back(key.clone());
+
+        Some(signature)
+    }
+
+    fn insert(&self, key: Key, signature: Signature) {
+        let mut entries = match self.entries.lock() {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+
+        if entries.signatures.insert(key.clone(), signature).is_none() {
+            entries.order.push_back(key);
+        }
+
+        while entries.signatures.len() > self.capacity {
+            match entries.order.pop_front() {
+                Some(oldest) => {
+                    entries.signatures.remove(&oldest);
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+/// A `Signer` which returns the cached signature of a message it has already signed, rather than
+/// asking `signer` to sign it again.
+///
+/// Refusals are not cached, so a message which could not be signed may be retried.
+#[derive(Clone)]
+pub struct CachingSigner<S: Signer> {
+    signer: S,
+    validator: String,
+    cache: Arc<SignatureCache>,
+}
+
+impl<S: Signer> CachingSigner<S> {
+    /// Wrap `signer`, caching its signatures in `cache`.
+    pub fn new(signer: S, cache: Arc<SignatureCache>) -> Self {
+        Self {
+            validator: signer.to_public().concatenated_hex_id(),
+            signer,
+            cache,
+        }
+    }
+}
+
+impl<S: Signer> Signer for CachingSigner<S> {
+    fn sign_message(&self, message: &[u8], domain: u64) -> SignatureFuture {
+        let key = Key {
+            validator: self.validator.clone(),
+            domain,
+            message: message.to_vec(),
+        };
+
+        if let Some(signature) = self.cache.get(&key) {
+            return Box::new(future::ok(signature));
+        }
+
+        let cache = self.cache.clone();
+        Box::new(
+            self.signer
+                .sign_message(message, domain)
+                .map(move |signature| {
+                    cache.insert(key, signature.clone());
+                    signature
+                }),
+        )
+    }
+
+    fn to_public(&self) -> PublicKey {
+        self.signer.to_public()
+    }
+}
+
+impl<S: Signer> fmt::Display for CachingSigner<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.signer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use types::Keypair;
+
+    /// Counts the messages it signs.
+    #[derive(Clone)]
+    struct CountingSigner {
+        keypair: Keypair,
+        count: Arc<AtomicUsize>,
+    }
+
+    impl fmt::Display for CountingSigner {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "CountingSigner")
+        }
+    }
+
+    impl Signer for CountingSigner {
+        fn sign_message(&self, message: &[u8], domain: u64) -> SignatureFuture {
+            self.count.fetch_add(1, Ordering::SeqCst);
+            self.keypair.sign_message(message, domain)
+        }
+
+        fn to_public(&self) -> PublicKey {
+            self.keypair.pk.clone()
+        }
+    }
+
+    fn counting_signer() -> (CountingSigner, Arc<AtomicUsize>) {
+        let count = Arc::new(AtomicUsize::new(0));
+        let signer = CountingSigner {
+            keypair: Keypair::random(),
+            count: count.clone(),
+        };
+        (signer, count)
+    }
+
+    #[test]
+    fn repeated_messages_are_signed_once() {
+        let cache = Arc::new(SignatureCache::new(SIGNATURES_PER_VALIDATOR));
+        let (a, a_count) = counting_signer();
+        let (b, b_count) = counting_signer();
+        let a = CachingSigner::new(a, cache.clone());
+        let b = CachingSigner::new(b, cache.clone());
+
+        let first = a.sign_message(&[1; 32], 0).wait();
+        assert_eq!(a.sign_message(&[1; 32], 0).wait(), first);
+        assert_eq!(a_count.load(Ordering::SeqCst), 1);
+
+        // A different domain, or a different validator, is a different message.
+        assert!(a.sign_message(&[1; 32], 1).wait().is_ok());
+        assert!(b.sign_message(&[1; 32], 0).wait().is_ok());
+        assert_eq!(a_count.load(Ordering::SeqCst), 2);
+        assert_eq!(b_count.load(Ordering::SeqCst), 1);
+        assert_eq!(cache.len(), 3);
+    }
+
+    #[test]
+    fn least_recently_used_signatures_are_evicted() {
+        let cache = Arc::new(SignatureCache::new(2));
+        let (signer, count) = counting_signer();
+        let signer = CachingSigner::new(signer, cache.clone());
+
+        for message in &[[1; 32], [2; 32], [1; 32], [3; 32]] {
+            assert!(signer.sign_message(message, 0).wait().is_ok());
+        }
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+        assert_eq!(cache.len(), 2);
+
+        // `[2; 32]` was the least recently used, so it was evicted.
+        assert!(signer.sign_message(&[1; 32], 0).wait().is_ok());
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+        assert!(signer.sign_message(&[2; 32], 0).wait().is_ok());
+        assert_eq!(count.load(Ordering::SeqCst), 4);
+    }
+}
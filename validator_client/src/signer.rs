@@ -1,9 +1,17 @@
-use std::fmt::Display;
+use crate::hsm::HsmSigner;
+use futures::{future, Future};
+use std::fmt::{self, Display};
 use types::{Keypair, PublicKey, Signature};
 
+/// A future which resolves to a signature, or to an error if the signer refuses to sign.
+pub type SignatureFuture = Box<dyn Future<Item = Signature, Error = ()> + Send>;
+
 /// Signs message using an internally-maintained private key.
+///
+/// Signing is asynchronous, so that signers which contact a remote service do not block the
+/// validator client.
 pub trait Signer: Display + Send + Sync + Clone {
-    fn sign_message(&self, message: &[u8], domain: u64) -> Option<Signature>;
+    fn sign_message(&self, message: &[u8], domain: u64) -> SignatureFuture;
     /// Returns a public key for the signer object.
     fn to_public(&self) -> PublicKey;
 }
@@ -15,7 +23,39 @@ impl Signer for Keypair {
         self.pk.clone()
     }
 
-    fn sign_message(&self, message: &[u8], domain: u64) -> Option<Signature> {
-        Some(Signature::new(message, domain, &self.sk))
+    fn sign_message(&self, message: &[u8], domain: u64) -> SignatureFuture {
+        Box::new(future::ok(Signature::new(message, domain, &self.sk)))
+    }
+}
+
+/// The signer of a validator: a keypair held in memory, or a key held in an HSM.
+#[derive(Clone)]
+pub enum ValidatorSigner {
+    Keypair(Keypair),
+    Hsm(HsmSigner),
+}
+
+impl Signer for ValidatorSigner {
+    fn to_public(&self) -> PublicKey {
+        match self {
+            ValidatorSigner::Keypair(keypair) => keypair.to_public(),
+            ValidatorSigner::Hsm(signer) => signer.to_public(),
+        }
+    }
+
+    fn sign_message(&self, message: &[u8], domain: u64) -> SignatureFuture {
+        match self {
+            ValidatorSigner::Keypair(keypair) => keypair.sign_message(message, domain),
+            ValidatorSigner::Hsm(signer) => signer.sign_message(message, domain),
+        }
+    }
+}
+
+impl Display for ValidatorSigner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidatorSigner::Keypair(keypair) => write!(f, "{}", keypair),
+            ValidatorSigner::Hsm(signer) => write!(f, "{}", signer),
+        }
     }
 }
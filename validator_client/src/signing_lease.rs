@@ -0,0 +1,543 @@
+//! Coordinates the validator clients of an active/standby deployment, which share the same
+//! validators, so that at most one of them signs for a validator at each slot.
+//!
+//! Before signing a block or attestation, a producer claims the (validator, slot) of the message
+//! from a backend shared by every instance, which holds a lease on each validator:
+//!
+//! - A directory (e.g., on a shared filesystem), with a lease file per validator which is locked
+//!   (with `flock`) whilst it is read and updated. The locks must be reliable on the filesystem,
+//!   which they may not be on NFS.
+//! - An HTTP lock service, to which each claim is `POST`ed as JSON, e.g.
+//!   `{"validator": "0x…", "slot": 42, "owner": "…", "lease_secs": 1152}`. The service answers
+//!   `200 OK` if it grants the claim, or `409 Conflict` with `{"holder": "…"}` if it does not.
+//!
+//! Either way, a claim is granted if the lease of the validator is held by the claiming instance,
+//! or has expired and the slot is after the last slot claimed for the validator. Each grant renews
+//! the lease for `lease_secs` and records the slot (the last processed slot of the validator,
+//! shared by every instance). The active instance therefore keeps its validators for as long as it
+//! claims them at least once per lease, and a standby takes them over at the first slot it claims
+//! after the active instance has stopped claiming them for a whole lease.
+//!
+//! A claim which cannot be made (e.g., as the backend is unreachable) is refused, so that an
+//! unavailable backend stops signing rather than risk a double proposal. The expiry of leases is
+//! measured by the clock of each instance, so the clocks of the instances must agree to well within
+//! a lease.
+//!
+//! A lease file is created holding its first lease, and later leases are written over it without
+//! truncating it first, so that an interrupted write cannot leave a file which reads as no lease.
+use fs2::FileExt;
+use futures::{future, Async, Future};
+use reqwest::r#async::Client;
+use reqwest::StatusCode;
+use serde_derive::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use types::{PublicKey, Slot};
+use uuid::Uuid;
+
+/// The epochs for which a lease is held after each claim, by default.
+pub const DEFAULT_LEASE_EPOCHS: u64 = 3;
+
+/// The shortest lease which cannot expire between the claims of a healthy validator client.
+///
+/// A validator attests once per epoch, at any slot of it, so two attestations may be up to two
+/// epochs less a slot apart; a lease must outlast that by at least a slot.
+pub fn min_lease_secs(seconds_per_slot: u64, slots_per_epoch: u64) -> u64 {
+    seconds_per_slot * (2 * slots_per_epoch + 1)
+}
+
+/// The maximum time to wait for the lock service to answer a claim, which must leave time to sign
+/// within the slot.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The lease file could not be read or written.
+    #[error("unable to access the lease file {path:?}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    /// The lease file is empty, and so may have lost the lease of another validator client.
+    #[error("the lease file {path:?} is empty")]
+    Empty { path: PathBuf },
+    /// The lease file does not hold a valid lease.
+    #[error("the lease file {path:?} is invalid: {source}")]
+    Invalid {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+    /// The lock service could not be reached, or answered unexpectedly.
+    #[error("the lock service refused the claim: {0}")]
+    Http(String),
+}
+
+/// Where the leases are held.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaseBackend {
+    /// A directory holding a lease file per validator.
+    File(PathBuf),
+    /// The URL of an HTTP lock service.
+    Http(String),
+}
+
+impl FromStr for LeaseBackend {
+    type Err = String;
+
+    /// Parses an HTTP(S) URL, or the path of a directory otherwise.
+    fn from_str(s: &str) -> Result<Self, String> {
+        if s.starts_with("http://") || s.starts_with("https://") {
+            Ok(LeaseBackend::Http(s.to_string()))
+        } else if s.is_empty() {
+            Err("the lease directory is empty".to_string())
+        } else {
+            Ok(LeaseBackend::File(PathBuf::from(s)))
+        }
+    }
+}
+
+/// How and where this validator client claims its validators.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct SigningLeaseConfig {
+    pub backend: LeaseBackend,
+    /// Identifies this validator client to the others, which must each have a different owner.
+    ///
+    /// If `None`, a random owner is chosen at startup, so leases held before a restart must
+    /// expire before they are claimed again.
+    pub owner: Option<String>,
+    /// The seconds for which a lease is held after each claim, or `DEFAULT_LEASE_EPOCHS` epochs
+    /// if `None`.
+    pub lease_secs: Option<u64>,
+}
+
+impl SigningLeaseConfig {
+    pub fn new(backend: LeaseBackend) -> Self {
+        Self {
+            backend,
+            owner: None,
+            lease_secs: None,
+        }
+    }
+
+    /// The seconds for which a lease is held after each claim, with slots and epochs of the given
+    /// length.
+    pub fn lease_secs(&self, seconds_per_slot: u64, slots_per_epoch: u64) -> u64 {
+        self.lease_secs
+            .unwrap_or(seconds_per_slot * slots_per_epoch * DEFAULT_LEASE_EPOCHS)
+    }
+}
+
+/// The outcome of a claim.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Claim {
+    /// This validator client may sign for the validator at the slot.
+    Granted,
+    /// Another validator client holds the lease of the validator.
+    Held { holder: String },
+}
+
+/// A future which resolves to the outcome of a claim.
+pub type ClaimFuture = Box<dyn Future<Item = Claim, Error = Error> + Send>;
+
+/// The lease of a validator, as stored in its lease file.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Lease {
+    pub owner: String,
+    /// The last slot claimed for the validator.
+    pub slot: Slot,
+    /// The time at which the lease expires, in seconds since the UNIX epoch.
+    pub expires: u64,
+}
+
+/// Returns the lease after `owner` claims `slot` at `now` (in seconds since the UNIX epoch), or
+/// the holder of the lease if the claim is refused.
+pub fn claim_lease(
+    lease: Option<&Lease>,
+    owner: &str,
+    slot: Slot,
+    now: u64,
+    lease_secs: u64,
+) -> Result<Lease, String> {
+    let slot = match lease {
+        None => slot,
+        Some(lease) if lease.owner == owner => std::cmp::max(lease.slot, slot),
+        Some(lease) if now >= lease.expires && slot > lease.slot => slot,
+        Some(lease) => return Err(lease.owner.clone()),
+    };
+    Ok(Lease {
+        owner: owner.to_string(),
+        slot,
+        expires: now + lease_secs,
+    })
+}
+
+/// The body of a claim sent to an HTTP lock service.
+#[derive(Debug, Serialize)]
+struct ClaimRequest<'a> {
+    validator: String,
+    slot: Slot,
+    owner: &'a str,
+    lease_secs: u64,
+}
+
+/// The body of a `409 Conflict` answer of an HTTP lock service.
+#[derive(Debug, Deserialize)]
+struct Conflict {
+    holder: String,
+}
+
+enum Backend {
+    File(PathBuf),
+    Http { client: Client, url: String },
+}
+
+/// Claims validators from the backend shared with other validator clients, if any.
+pub struct SigningLease {
+    backend: Option<Backend>,
+    owner: String,
+    lease_secs: u64,
+}
+
+impl SigningLease {
+    pub fn new(
+        config: &SigningLeaseConfig,
+        seconds_per_slot: u64,
+        slots_per_epoch: u64,
+    ) -> Result<Self, String> {
+        let backend = match &config.backend {
+            LeaseBackend::File(dir) => {
+                fs::create_dir_all(dir).map_err(|e| {
+                    format!("Unable to create the lease directory {:?}: {}", dir, e)
+                })?;
+                Backend::File(dir.clone())
+            }
+            LeaseBackend::Http(url) => Backend::Http {
+                client: Client::builder()
+                    .timeout(REQUEST_TIMEOUT)
+                    .build()
+                    .map_err(|e| format!("Unable to build HTTP client: {:?}", e))?,
+                url: url.clone(),
+            },
+        };
+
+        Ok(Self {
+            backend: Some(backend),
+            owner: config
+                .owner
+                .clone()
+                .unwrap_or_else(|| Uuid::new_v4().to_string()),
+            lease_secs: config.lease_secs(seconds_per_slot, slots_per_epoch),
+        })
+    }
+
+    /// A lease which grants every claim, for validator clients which do not share validators.
+    pub fn disabled() -> Self {
+        Self {
+            backend: None,
+            owner: String::new(),
+            lease_secs: 0,
+        }
+    }
+
+    /// Identifies this validator client to the others.
+    pub fn owner(&self) -> &str {
+        &self.owner
+    }
+
+    /// The seconds for which a lease is held after each claim.
+    pub fn lease_secs(&self) -> u64 {
+        self.lease_secs
+    }
+
+    /// Claims `validator` at `slot` for this validator client, which may only sign for it at
+    /// `slot` if the claim is granted.
+    pub fn claim(&self, validator: &PublicKey, slot: Slot) -> ClaimFuture {
+        match &self.backend {
+            None => Box::new(future::ok(Claim::Granted)),
+            Some(Backend::File(dir)) => {
+                let path = dir.join(format!("{}.json", validator.as_hex_string()));
+                let (owner, lease_secs) = (self.owner.clone(), self.lease_secs);
+                let mut claim = move || claim_file(&path, &owner, slot, lease_secs);
+                // Locking and syncing the file blocks, so it must not hold up the executor.
+                Box::new(future::poll_fn(move || {
+                    match tokio_threadpool::blocking(&mut claim) {
+                        Ok(Async::Ready(claimed)) => claimed.map(Async::Ready),
+                        Ok(Async::NotReady) => Ok(Async::NotReady),
+                        // Outside of a thread pool (e.g., in tests) there is no executor to hold
+                        // up.
+                        Err(_) => claim().map(Async::Ready),
+                    }
+                }))
+            }
+            Some(Backend::Http { client, url }) => self.claim_http(client, url, validator, slot),
+        }
+    }
+
+    /// Posts the claim to the lock service at `url`.
+    fn claim_http(
+        &self,
+        client: &Client,
+        url: &str,
+        validator: &PublicKey,
+        slot: Slot,
+    ) -> ClaimFuture {
+        let request = ClaimRequest {
+            validator: validator.as_hex_string(),
+            slot,
+            owner: &self.owner,
+            lease_secs: self.lease_secs,
+        };
+        Box::new(
+            client
+                .post(url)
+                .json(&request)
+                .send()
+                .map_err(|e| Error::Http(e.to_string()))
+                .and_then(|mut response| -> ClaimFuture {
+                    match response.status() {
+                        StatusCode::OK => Box::new(future::ok(Claim::Granted)),
+                        StatusCode::CONFLICT => {
+                            Box::new(response.json().then(|conflict: Result<Conflict, _>| {
+                                Ok::<_, Error>(Claim::Held {
+                                    holder: conflict
+                                        .map(|c| c.holder)
+                                        .unwrap_or_else(|_| "another validator client".to_string()),
+                                })
+                            }))
+                        }
+                        status => Box::new(future::err(Error::Http(format!(
+                            "the lock service responded with {}",
+                            status
+                        )))),
+                    }
+                }),
+        )
+    }
+}
+
+/// Claims the lease in the file at `path` for `owner`, locking the file whilst it is read and
+/// updated.
+fn claim_file(path: &Path, owner: &str, slot: Slot, lease_secs: u64) -> Result<Claim, Error> {
+    let io_error = |source| Error::Io {
+        path: path.to_path_buf(),
+        source,
+    };
+    let open = || OpenOptions::new().read(true).write(true).open(path);
+    let mut file = match open() {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            match claim_lease(None, owner, slot, now(), lease_secs) {
+                Ok(lease) => match create_lease_file(path, &lease) {
+                    Ok(()) => return Ok(Claim::Granted),
+                    // Another validator client has created the file since.
+                    Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                        open().map_err(io_error)?
+                    }
+                    Err(e) => return Err(io_error(e)),
+                },
+                Err(holder) => return Ok(Claim::Held { holder }),
+            }
+        }
+        Err(e) => return Err(io_error(e)),
+    };
+    // The lock is released when the file is closed.
+    file.lock_exclusive().map_err(io_error)?;
+
+    let mut contents = vec![];
+    file.read_to_end(&mut contents).map_err(io_error)?;
+    // Every lease file is created holding a lease, so an empty one has lost it.
+    if contents.is_empty() {
+        return Err(Error::Empty {
+            path: path.to_path_buf(),
+        });
+    }
+    let lease: Lease = serde_json::from_slice(&contents).map_err(|source| Error::Invalid {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    match claim_lease(Some(&lease), owner, slot, now(), lease_secs) {
+        Ok(lease) => {
+            let contents = serde_json::to_vec(&lease).map_err(|e| io_error(e.into()))?;
+            // An interrupted write leaves an invalid lease, rather than none.
+            file.seek(SeekFrom::Start(0))
+                .and_then(|_| file.write_all(&contents))
+                .and_then(|()| file.set_len(contents.len() as u64))
+                .and_then(|()| file.sync_all())
+                .map_err(io_error)?;
+            Ok(Claim::Granted)
+        }
+        Err(holder) => Ok(Claim::Held { holder }),
+    }
+}
+
+/// Creates the lease file at `path` holding `lease`, failing with `AlreadyExists` if there is one.
+///
+/// The lease is written to a temporary file which is then linked at `path`, so that the lease file
+/// is never empty.
+fn create_lease_file(path: &Path, lease: &Lease) -> io::Result<()> {
+    let contents = serde_json::to_vec(lease)?;
+    let temp_path = path.with_extension(format!("{}.tmp", Uuid::new_v4()));
+    let linked = File::create(&temp_path)
+        .and_then(|mut file| file.write_all(&contents).and_then(|()| file.sync_all()))
+        .and_then(|()| fs::hard_link(&temp_path, path));
+    let _ = fs::remove_file(&temp_path);
+    linked?;
+
+    // Sync the directory, so that the lease file is not lost in a crash.
+    match path.parent() {
+        Some(dir) => File::open(dir)?.sync_all(),
+        None => Ok(()),
+    }
+}
+
+/// The seconds since the UNIX epoch.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use types::Keypair;
+
+    #[test]
+    fn leases_are_taken_over_once_expired() {
+        let slot = Slot::new(10);
+        let active = claim_lease(None, "active", slot, 100, 50).unwrap();
+        assert_eq!(
+            active,
+            Lease {
+                owner: "active".to_string(),
+                slot,
+                expires: 150,
+            }
+        );
+
+        // The holder may claim any slot, without moving the last slot back.
+        assert_eq!(
+            claim_lease(Some(&active), "active", Slot::new(9), 120, 50)
+                .unwrap()
+                .slot,
+            slot
+        );
+
+        // No other instance may claim the validator until the lease expires, nor a slot which has
+        // been claimed.
+        assert_eq!(
+            claim_lease(Some(&active), "standby", Slot::new(11), 149, 50),
+            Err("active".to_string())
+        );
+        assert_eq!(
+            claim_lease(Some(&active), "standby", slot, 150, 50),
+            Err("active".to_string())
+        );
+        assert_eq!(
+            claim_lease(Some(&active), "standby", Slot::new(11), 150, 50),
+            Ok(Lease {
+                owner: "standby".to_string(),
+                slot: Slot::new(11),
+                expires: 200,
+            })
+        );
+    }
+
+    #[test]
+    fn file_leases_are_shared_between_instances() {
+        let dir = tempfile::tempdir().unwrap();
+        let lease = |owner: &str| {
+            let mut config = SigningLeaseConfig::new(LeaseBackend::File(dir.path().join("leases")));
+            config.owner = Some(owner.to_string());
+            SigningLease::new(&config, 6, 8).unwrap()
+        };
+        let (active, standby) = (lease("active"), lease("standby"));
+        let validator = Keypair::random().pk;
+
+        assert_eq!(
+            active.claim(&validator, Slot::new(1)).wait().unwrap(),
+            Claim::Granted
+        );
+        assert_eq!(
+            active.claim(&validator, Slot::new(2)).wait().unwrap(),
+            Claim::Granted
+        );
+        assert_eq!(
+            standby.claim(&validator, Slot::new(3)).wait().unwrap(),
+            Claim::Held {
+                holder: "active".to_string()
+            }
+        );
+
+        // A lease is written over a longer one without leaving any of it behind.
+        let path = dir
+            .path()
+            .join("leases")
+            .join(format!("{}.json", validator.as_hex_string()));
+        let longer = serde_json::to_vec(&Lease {
+            owner: "active".to_string(),
+            slot: Slot::new(2),
+            expires: u64::max_value(),
+        })
+        .unwrap();
+        fs::write(&path, &longer).unwrap();
+        assert_eq!(
+            active.claim(&validator, Slot::new(4)).wait().unwrap(),
+            Claim::Granted
+        );
+        let lease: Lease = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(lease.slot, Slot::new(4));
+        assert!(fs::read(&path).unwrap().len() < longer.len());
+
+        // An emptied lease file may have lost the lease of another instance, so is not claimed.
+        fs::write(&path, b"").unwrap();
+        match standby.claim(&validator, Slot::new(5)).wait() {
+            Err(Error::Empty { .. }) => {}
+            other => panic!("should refuse an empty lease file, not {:?}", other),
+        }
+
+        // The leases of other validators are independent.
+        assert_eq!(
+            standby
+                .claim(&Keypair::random().pk, Slot::new(3))
+                .wait()
+                .unwrap(),
+            Claim::Granted
+        );
+
+        assert_eq!(
+            SigningLease::disabled()
+                .claim(&validator, Slot::new(3))
+                .wait()
+                .unwrap(),
+            Claim::Granted
+        );
+
+        // No temporary files are left behind.
+        assert_eq!(fs::read_dir(dir.path().join("leases")).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn backends_are_parsed() {
+        assert_eq!(
+            "https://lock.example.com/claims".parse(),
+            Ok(LeaseBackend::Http(
+                "https://lock.example.com/claims".to_string()
+            ))
+        );
+        assert_eq!(
+            "/mnt/shared/leases".parse(),
+            Ok(LeaseBackend::File(PathBuf::from("/mnt/shared/leases")))
+        );
+        assert!("".parse::<LeaseBackend>().is_err());
+    }
+}
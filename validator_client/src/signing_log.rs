@@ -0,0 +1,299 @@
+//! A write-ahead log of the messages approved by slashing protection, so that a message is
+//! durably recorded before its signature may leave the signer.
+//!
+//! The log is written to `<datadir>/<network>/signing_log.jsonl`, one JSON entry per line. Each
+//! message approved by `SlashingProtection::begin_sign_block` (or `begin_sign_attestation`) is
+//! appended and fsynced before the signer is asked to sign it, and `SlashingProtection::commit`
+//! appends a `Committed` marker once the signature has been produced. On startup the log is
+//! replayed into slashing protection, so a crash between the approval and the snapshot of a signed
+//! message cannot allow a conflicting message to be signed.
+//!
+//! A message without a `Committed` marker may or may not have been signed, so it is treated as
+//! signed. A final line which is incomplete was never fsynced, so its message was never signed,
+//! and it is discarded. Any other invalid line may have hidden a signed message, so the log is
+//! refused.
+//!
+//! The log is compacted to the history of every validator (including its watermark) when it is
+//! opened and whenever the history is pruned, so it grows only with the messages signed since.
+use crate::slashing_protection::Watermark;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use types::{Epoch, Hash256, PublicKey, Slot};
+
+/// The name of the signing log within the data directory.
+pub const SIGNING_LOG_FILENAME: &str = "signing_log.jsonl";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The log could not be read or written.
+    #[error("unable to access the signing log {path:?}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    /// An entry other than the last is invalid, so the log cannot be trusted.
+    #[error("line {line} of the signing log {path:?} is invalid: {source}")]
+    Corrupt {
+        path: PathBuf,
+        line: usize,
+        #[source]
+        source: serde_json::Error,
+    },
+}
+
+/// A single entry of the log.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum Entry {
+    /// `validator` may have signed the block at `slot` with `signed_root`.
+    Block {
+        validator: PublicKey,
+        slot: Slot,
+        signed_root: Hash256,
+    },
+    /// `validator` may have signed the attestation from `source` to `target` with `signed_root`.
+    Attestation {
+        validator: PublicKey,
+        source: Epoch,
+        target: Epoch,
+        signed_root: Hash256,
+    },
+    /// The history of `validator` was pruned up to `watermark`.
+    Watermark {
+        validator: PublicKey,
+        watermark: Watermark,
+    },
+    /// The message of the entry with `sequence` was signed.
+    Committed { sequence: u64 },
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+struct Line {
+    /// `None` if the entry was written by compaction, and so is committed.
+    #[serde(default)]
+    sequence: Option<u64>,
+    entry: Entry,
+}
+
+/// The entries read from a log when it was opened.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Replay {
+    /// Every entry other than the `Committed` markers, oldest first.
+    pub entries: Vec<Entry>,
+    /// The number of messages without a `Committed` marker, whose signing may have been
+    /// interrupted.
+    pub uncommitted: usize,
+}
+
+/// An open log, to which entries are appended.
+#[derive(Debug)]
+pub struct SigningLog {
+    path: PathBuf,
+    file: File,
+    next_sequence: u64,
+}
+
+impl SigningLog {
+    /// Returns the path of the signing log in `data_dir`.
+    pub fn path(data_dir: &Path) -> PathBuf {
+        data_dir.join(SIGNING_LOG_FILENAME)
+    }
+
+    /// Reads the log at `path`, if there is one, and opens it for appending.
+    ///
+    /// An incomplete final line is removed, so that it does not corrupt the next entry.
+    pub fn open(path: &Path) -> Result<(Self, Replay), Error> {
+        let (replay, next_sequence, complete_len) = Self::read(path)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(io_error(path))?;
+        file.set_len(complete_len).map_err(io_error(path))?;
+
+        let log = Self {
+            path: path.to_path_buf(),
+            file,
+            next_sequence,
+        };
+        Ok((log, replay))
+    }
+
+    /// Reads the log at `path`, returning its entries, the next sequence number and the length of
+    /// its complete lines.
+    fn read(path: &Path) -> Result<(Replay, u64, u64), Error> {
+        let contents = match fs::read(path) {
+            Ok(contents) => contents,
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok((Replay::default(), 0, 0)),
+            Err(e) => return Err(io_error(path)(e)),
+        };
+        // Each entry is fsynced with its newline, so a final line without one was never fsynced.
+        let complete_len = contents
+            .iter()
+            .rposition(|byte| *byte == b'\n')
+            .map_or(0, |newline| newline + 1);
+
+        let mut signed = vec![];
+        let mut committed = HashSet::new();
+        let mut next_sequence = 0;
+        let lines = contents[..complete_len].split(|byte| *byte == b'\n');
+        for (index, line) in lines.enumerate().filter(|(_, line)| !line.is_empty()) {
+            let line: Line = match serde_json::from_slice(line) {
+                Ok(line) => line,
+                Err(source) => {
+                    return Err(Error::Corrupt {
+                        path: path.to_path_buf(),
+                        line: index + 1,
+                        source,
+                    })
+                }
+            };
+            if let Some(sequence) = line.sequence {
+                next_sequence = next_sequence.max(sequence + 1);
+            }
+            match line.entry {
+                Entry::Committed { sequence } => {
+                    committed.insert(sequence);
+                }
+                entry => signed.push((line.sequence, entry)),
+            }
+        }
+
+        let uncommitted = signed
+            .iter()
+            .filter(|(sequence, _)| sequence.map_or(false, |s| !committed.contains(&s)))
+            .count();
+        let replay = Replay {
+            entries: signed.into_iter().map(|(_, entry)| entry).collect(),
+            uncommitted,
+        };
+        Ok((replay, next_sequence, complete_len as u64))
+    }
+
+    /// Replaces the log with `entries`, all of which are committed.
+    ///
+    /// The entries are written to a temporary file which is fsynced and renamed over the log, so
+    /// the log is never partially replaced.
+    pub fn compact(&mut self, entries: Vec<Entry>) -> Result<(), Error> {
+        let temp_path = self.path.with_extension("jsonl.tmp");
+        let mut temp = File::create(&temp_path).map_err(io_error(&temp_path))?;
+        for entry in entries {
+            write_line(&mut temp, None, entry).map_err(io_error(&temp_path))?;
+        }
+        temp.sync_all().map_err(io_error(&temp_path))?;
+        fs::rename(&temp_path, &self.path).map_err(io_error(&self.path))?;
+
+        // Sync the directory, so that a crash cannot revert the log to its uncompacted version
+        // after entries have been appended to the compacted one.
+        let dir = match self.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        File::open(dir)
+            .and_then(|dir| dir.sync_all())
+            .map_err(io_error(dir))?;
+
+        self.file = OpenOptions::new()
+            .append(true)
+            .open(&self.path)
+            .map_err(io_error(&self.path))?;
+        Ok(())
+    }
+
+    /// Appends `entry` and waits for it to reach the disk, returning its sequence number.
+    pub fn append(&mut self, entry: Entry) -> Result<u64, Error> {
+        let sequence = self.next_sequence;
+        write_line(&mut self.file, Some(sequence), entry)
+            .and_then(|()| self.file.sync_data())
+            .map_err(io_error(&self.path))?;
+        self.next_sequence += 1;
+        Ok(sequence)
+    }
+
+    /// Waits for every entry, including the `Committed` markers, to reach the disk.
+    pub fn sync(&self) -> Result<(), Error> {
+        self.file.sync_all().map_err(io_error(&self.path))
+    }
+
+    /// Appends a `Committed` marker for the entry with `sequence`.
+    ///
+    /// The marker is not fsynced: if it is lost, the message is treated as signed regardless.
+    pub fn commit(&mut self, sequence: u64) -> Result<(), Error> {
+        write_line(
+            &mut self.file,
+            Some(sequence),
+            Entry::Committed { sequence },
+        )
+        .map_err(io_error(&self.path))
+    }
+}
+
+fn write_line(file: &mut File, sequence: Option<u64>, entry: Entry) -> io::Result<()> {
+    let mut line = serde_json::to_vec(&Line { sequence, entry })?;
+    line.push(b'\n');
+    file.write_all(&line)
+}
+
+fn io_error(path: &Path) -> impl FnOnce(io::Error) -> Error + '_ {
+    move |source| Error::Io {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use types::Keypair;
+
+    #[test]
+    fn replays_entries_and_ignores_an_incomplete_last_line() {
+        let dir = TempDir::new().expect("should create temp dir");
+        let path = SigningLog::path(dir.path());
+        let validator = Keypair::random().pk;
+        let block = |slot| Entry::Block {
+            validator: validator.clone(),
+            slot: Slot::new(slot),
+            signed_root: Hash256::from_low_u64_be(slot),
+        };
+
+        let (mut log, replay) = SigningLog::open(&path).expect("should open log");
+        assert_eq!(replay, Replay::default());
+        let first = log.append(block(1)).expect("should append");
+        log.commit(first).expect("should commit");
+        log.append(block(2)).expect("should append");
+        drop(log);
+
+        // A crash whilst appending leaves an incomplete line.
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all(b"{\"sequence\":2,\"ent"))
+            .expect("should write partial line");
+
+        let (mut log, replay) = SigningLog::open(&path).expect("should reopen log");
+        assert_eq!(replay.entries, vec![block(1), block(2)]);
+        assert_eq!(replay.uncommitted, 1);
+        assert_eq!(log.append(block(3)).expect("should append"), 2);
+
+        log.compact(vec![block(3)]).expect("should compact");
+        let (_, replay) = SigningLog::open(&path).expect("should reopen log");
+        assert_eq!(replay.entries, vec![block(3)]);
+        assert_eq!(replay.uncommitted, 0);
+
+        // A complete line which is invalid may have hidden a signed message.
+        fs::write(&path, "garbage\n{}").expect("should write log");
+        match SigningLog::open(&path) {
+            Err(Error::Corrupt { line: 1, .. }) => {}
+            other => panic!(
+                "expected the log to be corrupt, found {:?}",
+                other.map(|_| ())
+            ),
+        }
+    }
+}
@@ -0,0 +1,292 @@
+//! Golden tests which pin the exact messages and domains signed by the validator client.
+//!
+//! Each message is signed via the same code path as the validator client (e.g., a proposal via
+//! `BlockProducer::produce_block`) by a `RecordingSigner`, which records what it is asked to sign.
+//! The recorded signing roots and domains are compared against the golden files in
+//! `tests/signing_roots`, so that a refactor cannot silently change what gets signed.
+//!
+//! After an intentional change, the golden files may be regenerated with:
+//!
+//! ```ignore
+//! UPDATE_GOLDEN=1 cargo test -p validator_client signing_roots
+//! ```
+use crate::attestation_producer::{AttestationProducer, BeaconNodeAttestation, BlockAttestations};
+use crate::block_latency::PublishWindow;
+use crate::block_producer::test_utils::TestBeaconNode;
+use crate::block_producer::{BeaconNodeFuture, BlockProducer, PublishOutcome, ValidatorEvent};
+use crate::inclusion::InclusionMonitor;
+use crate::notifier::Notifier;
+use crate::proposal_timing::ProposalTimings;
+use crate::signer::{SignatureFuture, Signer};
+use crate::signing_lease::SigningLease;
+use crate::slashing_protection::SlashingProtection;
+use futures::{future, Future};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::marker::PhantomData;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tree_hash::SignedRoot;
+use types::test_utils::generate_deterministic_keypair;
+use types::{
+    Attestation, AttestationData, AttestationDuty, Checkpoint, Crosslink, Domain, Epoch, EthSpec,
+    Fork, Hash256, MainnetEthSpec, MinimalEthSpec, PublicKey, Signature, Slot, VoluntaryExit,
+};
+
+/// The epoch of all proposals and attestations.
+const EPOCH: u64 = 3;
+
+/// The epoch of the voluntary exit, which precedes the fork.
+const EXIT_EPOCH: u64 = 1;
+
+/// A signing root and domain, both hex-encoded.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct SignedMessage {
+    signing_root: String,
+    /// The little-endian bytes of the domain.
+    domain: String,
+}
+
+impl SignedMessage {
+    fn new(message: &[u8], domain: u64) -> Self {
+        Self {
+            signing_root: format!("0x{}", hex::encode(message)),
+            domain: format!("0x{}", hex::encode(domain.to_le_bytes())),
+        }
+    }
+}
+
+/// Signs with an empty signature, recording every message and domain it is asked to sign.
+#[derive(Clone)]
+struct RecordingSigner {
+    public_key: PublicKey,
+    signed: Arc<Mutex<Vec<SignedMessage>>>,
+}
+
+impl RecordingSigner {
+    fn new() -> Self {
+        Self {
+            public_key: generate_deterministic_keypair(0).pk,
+            signed: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    /// Returns all messages signed so far, in order, leaving none recorded.
+    fn take(&self) -> Vec<SignedMessage> {
+        std::mem::replace(&mut *self.signed.lock().expect("should lock"), vec![])
+    }
+}
+
+impl fmt::Display for RecordingSigner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "RecordingSigner")
+    }
+}
+
+impl Signer for RecordingSigner {
+    fn sign_message(&self, message: &[u8], domain: u64) -> SignatureFuture {
+        self.signed
+            .lock()
+            .expect("should lock")
+            .push(SignedMessage::new(message, domain));
+        Box::new(future::ok(Signature::empty_signature()))
+    }
+
+    fn to_public(&self) -> PublicKey {
+        self.public_key.clone()
+    }
+}
+
+/// Responds to every request with the same attestation data.
+struct TestAttestationNode {
+    data: AttestationData,
+}
+
+impl BeaconNodeAttestation for TestAttestationNode {
+    fn produce_attestation_data(
+        &self,
+        _slot: Slot,
+        _shard: u64,
+    ) -> BeaconNodeFuture<AttestationData> {
+        Box::new(future::ok(self.data.clone()))
+    }
+
+    fn publish_attestation<T: EthSpec>(
+        &self,
+        _attestation: Attestation<T>,
+    ) -> BeaconNodeFuture<PublishOutcome> {
+        Box::new(future::ok(PublishOutcome::Valid))
+    }
+
+    fn attestation_known(
+        &self,
+        _slot: Slot,
+        _shard: u64,
+        _committee_index: usize,
+    ) -> BeaconNodeFuture<bool> {
+        Box::new(future::ok(false))
+    }
+
+    fn block_attestations<T: EthSpec>(
+        &self,
+        _slot: Slot,
+    ) -> BeaconNodeFuture<Option<BlockAttestations<T>>> {
+        Box::new(future::ok(None))
+    }
+}
+
+/// A fork which is scheduled before `EPOCH`, so that both fork versions are signed.
+fn fork() -> Fork {
+    Fork {
+        previous_version: [1, 2, 3, 4],
+        current_version: [5, 6, 7, 8],
+        epoch: Epoch::new(2),
+    }
+}
+
+/// Signs every message type for `E`, returning the signed messages by name.
+fn sign_all<E: EthSpec>() -> BTreeMap<String, SignedMessage> {
+    let spec = Arc::new(E::default_spec());
+    let signer = RecordingSigner::new();
+    let slot = Epoch::new(EPOCH).start_slot(E::slots_per_epoch()) + 1;
+    let mut signed = BTreeMap::new();
+
+    let block_producer = BlockProducer {
+        fork: fork(),
+        slot,
+        spec: spec.clone(),
+        beacon_node: Arc::new(TestBeaconNode::default()),
+        signer: signer.clone(),
+        slots_per_epoch: E::slots_per_epoch(),
+        slashing_protection: Arc::new(SlashingProtection::new()),
+        signing_lease: Arc::new(SigningLease::disabled()),
+        fee_recipient: None,
+        slot_start: Instant::now(),
+        deadline: Instant::now(),
+        timings: Arc::new(ProposalTimings::default()),
+        publish_window: PublishWindow::default(),
+        notifier: Arc::new(Notifier::disabled()),
+        dry_run: false,
+        _phantom: PhantomData::<E>,
+    };
+    assert_eq!(
+        block_producer.produce_block().wait(),
+        Ok(ValidatorEvent::BlockProduced(slot))
+    );
+    let mut messages = signer.take().into_iter();
+    signed.insert("randao".to_string(), messages.next().expect("randao"));
+    signed.insert("proposal".to_string(), messages.next().expect("proposal"));
+
+    let attestation_producer = AttestationProducer {
+        fork: fork(),
+        duty: AttestationDuty {
+            slot,
+            shard: 4,
+            committee_index: 0,
+            committee_len: 1,
+        },
+        spec: spec.clone(),
+        beacon_node: Arc::new(TestAttestationNode {
+            data: AttestationData {
+                beacon_block_root: Hash256::repeat_byte(1),
+                source: Checkpoint {
+                    epoch: Epoch::new(1),
+                    root: Hash256::repeat_byte(2),
+                },
+                target: Checkpoint {
+                    epoch: Epoch::new(2),
+                    root: Hash256::repeat_byte(3),
+                },
+                crosslink: Crosslink {
+                    shard: 4,
+                    parent_root: Hash256::repeat_byte(5),
+                    start_epoch: Epoch::new(1),
+                    end_epoch: Epoch::new(2),
+                    data_root: Hash256::repeat_byte(6),
+                },
+            },
+        }),
+        signer: signer.clone(),
+        slots_per_epoch: E::slots_per_epoch(),
+        slashing_protection: Arc::new(SlashingProtection::new()),
+        signing_lease: Arc::new(SigningLease::disabled()),
+        inclusion_monitor: Arc::new(InclusionMonitor::new(
+            E::slots_per_epoch(),
+            spec.min_attestation_inclusion_delay,
+            None,
+            slog::Logger::root(slog::Discard, slog::o!()),
+        )),
+        notifier: Arc::new(Notifier::disabled()),
+        dry_run: false,
+        _phantom: PhantomData::<E>,
+    };
+    assert_eq!(
+        attestation_producer.produce_attestation().wait(),
+        Ok(ValidatorEvent::AttestationProduced(slot))
+    );
+    signed.insert(
+        "attestation".to_string(),
+        signer.take().pop().expect("attestation"),
+    );
+
+    // The validator client does not yet sign exits, so the exit is signed directly.
+    let exit = VoluntaryExit {
+        epoch: Epoch::new(EXIT_EPOCH),
+        validator_index: 7,
+        signature: Signature::empty_signature(),
+    };
+    let domain = spec.get_domain(exit.epoch, Domain::VoluntaryExit, &fork());
+    signer
+        .sign_message(&exit.signed_root(), domain)
+        .wait()
+        .expect("should sign");
+    signed.insert(
+        "voluntary_exit".to_string(),
+        signer.take().pop().expect("voluntary exit"),
+    );
+
+    signed
+}
+
+/// Compares the messages signed for `E` with the golden file for `spec_name`, or overwrites the
+/// golden file if `UPDATE_GOLDEN` is set.
+fn check_golden<E: EthSpec>(spec_name: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("signing_roots")
+        .join(format!("{}.json", spec_name));
+    let signed = sign_all::<E>();
+
+    if std::env::var("UPDATE_GOLDEN").is_ok() {
+        let json = serde_json::to_string_pretty(&signed).expect("should encode");
+        fs::write(&path, json + "\n").expect("should write golden file");
+        return;
+    }
+
+    let golden: BTreeMap<String, SignedMessage> =
+        serde_json::from_slice(&fs::read(&path).expect("should read golden file"))
+            .expect("should decode golden file");
+    for (name, message) in &golden {
+        assert_eq!(
+            signed.get(name),
+            Some(message),
+            "{} signs a different message for {}",
+            name,
+            spec_name
+        );
+    }
+    assert_eq!(signed.len(), golden.len(), "not all messages are pinned");
+}
+
+#[test]
+fn minimal_signing_roots() {
+    check_golden::<MinimalEthSpec>("minimal");
+}
+
+#[test]
+fn mainnet_signing_roots() {
+    check_golden::<MainnetEthSpec>("mainnet");
+}
@@ -0,0 +1,131 @@
+//! Submits evidence of slashable behaviour discovered locally (e.g., a double vote seen by an
+//! operator) to a beacon node, which validates it and queues it for inclusion in a block.
+//!
+//! Evidence is read from a JSON file holding a single `ProposerSlashing` or `AttesterSlashing`.
+use protos::auth::Authenticated;
+use protos::convert::ToProto;
+use protos::services::{PublishAttesterSlashingRequest, PublishProposerSlashingRequest};
+use protos::services_grpc::SlashingServiceClient;
+use serde::de::DeserializeOwned;
+use std::fs::File;
+use std::path::Path;
+use thiserror::Error;
+use types::{AttesterSlashing, EthSpec, ProposerSlashing};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The evidence file could not be opened.
+    #[error("unable to open the evidence file: {0}")]
+    UnableToOpenFile(#[source] std::io::Error),
+    /// The evidence file does not hold a slashing of the expected kind.
+    #[error("invalid evidence file: {0}")]
+    InvalidFile(#[source] serde_json::Error),
+    /// The beacon node could not be reached.
+    #[error("unable to submit the evidence: {0:?}")]
+    Grpc(grpcio::Error),
+    /// The beacon node found the evidence to be invalid.
+    #[error("the beacon node refused the evidence: {0}")]
+    Refused(String),
+}
+
+/// Evidence that a validator is slashable.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Evidence<E: EthSpec> {
+    ProposerSlashing(ProposerSlashing),
+    AttesterSlashing(AttesterSlashing<E>),
+}
+
+impl<E: EthSpec> Evidence<E> {
+    /// Reads a proposer slashing from the JSON file at `path`.
+    pub fn proposer_slashing(path: &Path) -> Result<Self, Error> {
+        read_json(path).map(Evidence::ProposerSlashing)
+    }
+
+    /// Reads an attester slashing from the JSON file at `path`.
+    pub fn attester_slashing(path: &Path) -> Result<Self, Error> {
+        read_json(path).map(Evidence::AttesterSlashing)
+    }
+
+    /// Submits the evidence to the beacon node of `client`, blocking until it replies.
+    pub fn submit(&self, client: &Authenticated<SlashingServiceClient>) -> Result<(), Error> {
+        let reply = match self {
+            Evidence::ProposerSlashing(slashing) => {
+                let mut req = PublishProposerSlashingRequest::new();
+                req.set_proposer_slashing(slashing.to_proto());
+
+                client
+                    .client()
+                    .publish_proposer_slashing_opt(&req, client.call_option())
+            }
+            Evidence::AttesterSlashing(slashing) => {
+                let mut req = PublishAttesterSlashingRequest::new();
+                req.set_attester_slashing(slashing.to_proto());
+
+                client
+                    .client()
+                    .publish_attester_slashing_opt(&req, client.call_option())
+            }
+        }
+        .map_err(Error::Grpc)?;
+
+        if reply.get_success() {
+            Ok(())
+        } else {
+            Err(Error::Refused(
+                String::from_utf8_lossy(reply.get_msg()).into_owned(),
+            ))
+        }
+    }
+}
+
+fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T, Error> {
+    let file = File::open(path).map_err(Error::UnableToOpenFile)?;
+    serde_json::from_reader(file).map_err(Error::InvalidFile)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+    use types::{BeaconBlockHeader, Hash256, MinimalEthSpec, Signature, Slot};
+
+    #[test]
+    fn reads_evidence_of_the_expected_kind() {
+        let dir = TempDir::new().expect("should create temp dir");
+        let path = dir.path().join("evidence.json");
+        let header = BeaconBlockHeader {
+            slot: Slot::new(3),
+            parent_root: Hash256::zero(),
+            state_root: Hash256::zero(),
+            body_root: Hash256::zero(),
+            signature: Signature::empty_signature(),
+        };
+        let slashing = ProposerSlashing {
+            proposer_index: 1,
+            header_1: header.clone(),
+            header_2: BeaconBlockHeader {
+                body_root: Hash256::repeat_byte(1),
+                ..header
+            },
+        };
+        fs::write(
+            &path,
+            serde_json::to_string(&slashing).expect("should encode"),
+        )
+        .expect("should write");
+
+        assert_eq!(
+            Evidence::<MinimalEthSpec>::proposer_slashing(&path).ok(),
+            Some(Evidence::ProposerSlashing(slashing))
+        );
+        match Evidence::<MinimalEthSpec>::attester_slashing(&path) {
+            Err(Error::InvalidFile(_)) => (),
+            other => panic!("should not read an attester slashing, got {:?}", other),
+        }
+        match Evidence::<MinimalEthSpec>::proposer_slashing(&dir.path().join("missing.json")) {
+            Err(Error::UnableToOpenFile(_)) => (),
+            other => panic!("should not open a missing file, got {:?}", other),
+        }
+    }
+}
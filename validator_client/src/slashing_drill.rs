@@ -0,0 +1,263 @@
+//! A sandboxed drill which verifies that slashing protection refuses conflicting messages, so that
+//! an operator may validate their setup before staking real funds.
+//!
+//! The drill signs synthetic conflicting duty sequences (a double proposal, and double and
+//! surround votes) with a throwaway key, using the same block producer and slashing protection
+//! as the validator service. The history is then saved to, and restored from, a snapshot on disk
+//! and the conflicting messages are attempted again, verifying that protection survives a
+//! restart.
+use crate::block_latency::PublishWindow;
+use crate::block_producer::{
+    BeaconNodeBlock, BeaconNodeFuture, BlockProducer, PublishOutcome, ValidatorEvent,
+};
+use crate::duties::DutiesStore;
+use crate::notifier::Notifier;
+use crate::proposal_timing::ProposalTimings;
+use crate::signing_lease::SigningLease;
+use crate::slashing_protection::{NotSafe, SlashingProtection};
+use crate::snapshot::{self, Snapshot};
+use futures::future;
+use std::fmt;
+use std::marker::PhantomData;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::runtime::current_thread::Runtime;
+use types::{
+    Address, AttestationData, BeaconBlock, BeaconBlockAny, Checkpoint, Crosslink, Epoch, EthSpec,
+    Hash256, Keypair, MinimalEthSpec, Signature, Slot,
+};
+
+/// The slot of the double proposal.
+const PROPOSAL_SLOT: u64 = 3;
+
+/// The result of a single check of the drill.
+#[derive(Debug, PartialEq)]
+pub struct Check {
+    pub name: &'static str,
+    pub passed: bool,
+    /// What happened, in either case.
+    pub detail: String,
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let status = if self.passed { "PASS" } else { "FAIL" };
+        write!(f, "[{}] {}: {}", status, self.name, self.detail)
+    }
+}
+
+/// An in-process stand-in for the beacon node, which returns a distinct block each time one is
+/// requested and accepts every block published to it.
+#[derive(Default)]
+struct DrillBeaconNode {
+    /// The number of blocks produced, which distinguishes each block from the last.
+    produced: AtomicU64,
+}
+
+impl BeaconNodeBlock for DrillBeaconNode {
+    fn produce_beacon_block<T: EthSpec>(
+        &self,
+        slot: Slot,
+        randao_reveal: &Signature,
+        _fee_recipient: Option<Address>,
+    ) -> BeaconNodeFuture<Option<BeaconBlockAny<T>>> {
+        let count = self.produced.fetch_add(1, Ordering::SeqCst);
+
+        let mut block = BeaconBlock::empty(&T::default_spec());
+        block.slot = slot;
+        block.parent_root = Hash256::repeat_byte(1);
+        block.state_root = Hash256::repeat_byte(2);
+        block.body.randao_reveal = randao_reveal.clone();
+        block.body.graffiti[0..8].copy_from_slice(&count.to_le_bytes());
+
+        Box::new(future::ok(Some(block.into())))
+    }
+
+    fn publish_beacon_block<T: EthSpec>(
+        &self,
+        _block: BeaconBlockAny<T>,
+    ) -> BeaconNodeFuture<PublishOutcome> {
+        Box::new(future::ok(PublishOutcome::Valid))
+    }
+}
+
+/// Runs the drill, storing its snapshot in `dir`, and returns the result of each check.
+///
+/// Returns an `Err` only if the drill was unable to run.
+pub fn run(dir: &Path) -> Result<Vec<Check>, snapshot::Error> {
+    let mut runtime = Runtime::new()?;
+    let keypair = Keypair::random();
+    let pubkey = keypair.pk.clone();
+    let beacon_node = Arc::new(DrillBeaconNode::default());
+
+    let propose = |runtime: &mut Runtime, protection: Arc<SlashingProtection>| {
+        let producer = BlockProducer {
+            fork: Default::default(),
+            slot: Slot::new(PROPOSAL_SLOT),
+            spec: Arc::new(MinimalEthSpec::default_spec()),
+            beacon_node: beacon_node.clone(),
+            signer: keypair.clone(),
+            slots_per_epoch: MinimalEthSpec::slots_per_epoch(),
+            slashing_protection: protection,
+            signing_lease: Arc::new(SigningLease::disabled()),
+            fee_recipient: None,
+            slot_start: Instant::now(),
+            deadline: Instant::now() + Duration::from_secs(1),
+            timings: Arc::new(ProposalTimings::default()),
+            publish_window: PublishWindow::default(),
+            notifier: Arc::new(Notifier::disabled()),
+            dry_run: false,
+            _phantom: PhantomData::<MinimalEthSpec>,
+        };
+        runtime.block_on(producer.produce_block())
+    };
+
+    let mut checks = vec![];
+    let protection = Arc::new(SlashingProtection::new());
+
+    // The beacon node returns a different block each time one is requested for the same slot.
+    let first = propose(&mut runtime, protection.clone());
+    checks.push(Check {
+        name: "first proposal signed",
+        passed: first == Ok(ValidatorEvent::BlockProduced(Slot::new(PROPOSAL_SLOT))),
+        detail: format!("{:?}", first),
+    });
+    checks.push(double_proposal_check(
+        "double proposal refused",
+        propose(&mut runtime, protection.clone()),
+    ));
+
+    let vote = |protection: &SlashingProtection, source, target, root| {
+        protection.check_and_insert_attestation(&pubkey, &attestation(source, target, root))
+    };
+
+    let first = vote(&protection, 2, 3, 1);
+    checks.push(Check {
+        name: "first vote signed",
+        passed: first.is_ok(),
+        detail: format!("{:?}", first),
+    });
+    checks.push(vote_check(
+        "double vote refused",
+        vote(&protection, 2, 3, 2),
+        is_double_vote,
+    ));
+    checks.push(vote_check(
+        "surrounding vote refused",
+        vote(&protection, 1, 4, 1),
+        is_surround_vote,
+    ));
+    let outer = vote(&protection, 5, 10, 1);
+    checks.push(vote_check(
+        "surrounded vote refused",
+        outer.and_then(|()| vote(&protection, 6, 9, 1)),
+        is_surround_vote,
+    ));
+
+    // Restart, restoring the signing history from disk.
+    let slots_per_epoch = MinimalEthSpec::slots_per_epoch();
+    Snapshot::new(
+        Slot::new(PROPOSAL_SLOT),
+        None,
+        &DutiesStore::new(slots_per_epoch),
+        &protection,
+    )?
+    .save(dir)?;
+    let restored = match Snapshot::load(dir)? {
+        Some(snapshot) => Arc::new(SlashingProtection::import(snapshot.slashing_protection)),
+        None => {
+            return Err(snapshot::Error::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "the saved snapshot was not found",
+            )))
+        }
+    };
+
+    checks.push(double_proposal_check(
+        "double proposal refused after restart",
+        propose(&mut runtime, restored.clone()),
+    ));
+    checks.push(vote_check(
+        "double vote refused after restart",
+        vote(&restored, 2, 3, 2),
+        is_double_vote,
+    ));
+
+    Ok(checks)
+}
+
+fn double_proposal_check(
+    name: &'static str,
+    outcome: Result<ValidatorEvent, crate::block_producer::Error>,
+) -> Check {
+    Check {
+        name,
+        passed: outcome
+            == Ok(ValidatorEvent::SlashableBlockNotProduced(Slot::new(
+                PROPOSAL_SLOT,
+            ))),
+        detail: format!("{:?}", outcome),
+    }
+}
+
+fn vote_check(
+    name: &'static str,
+    outcome: Result<(), NotSafe>,
+    expected: impl Fn(&NotSafe) -> bool,
+) -> Check {
+    Check {
+        name,
+        passed: outcome.as_ref().err().map_or(false, expected),
+        detail: format!("{:?}", outcome),
+    }
+}
+
+fn is_double_vote(e: &NotSafe) -> bool {
+    match e {
+        NotSafe::DoubleVote { .. } => true,
+        _ => false,
+    }
+}
+
+fn is_surround_vote(e: &NotSafe) -> bool {
+    match e {
+        NotSafe::SurroundVote { .. } => true,
+        _ => false,
+    }
+}
+
+/// Returns attestation data with the given source and target epochs, whose root is
+/// distinguished by `root`.
+fn attestation(source: u64, target: u64, root: u8) -> AttestationData {
+    AttestationData {
+        beacon_block_root: Hash256::repeat_byte(root),
+        source: Checkpoint {
+            epoch: Epoch::new(source),
+            root: Hash256::zero(),
+        },
+        target: Checkpoint {
+            epoch: Epoch::new(target),
+            root: Hash256::repeat_byte(root),
+        },
+        crosslink: Crosslink::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn drill_passes() {
+        let dir = TempDir::new().expect("should create temp dir");
+        let checks = run(dir.path()).expect("should run drill");
+
+        assert_eq!(checks.len(), 8);
+        for check in &checks {
+            assert!(check.passed, "{}", check);
+        }
+    }
+}
@@ -0,0 +1,749 @@
+//! In-memory protection against signing slashable blocks and attestations.
+//!
+//! Every block and attestation is checked against (and then recorded in) the history of its
+//! validator before it is signed. Re-signing an identical message is permitted, conflicting
+//! messages are refused.
+//!
+//! Signing is two-phase: `begin_sign_block` (or `begin_sign_attestation`) checks and records a
+//! message, returning a `SigningPermit`, and `commit` consumes the permit once the message has been
+//! signed. If a signing log is attached (see `signing_log`), the message is durably written to it
+//! before the permit is returned, so a message is never signed without being recorded. Otherwise
+//! the history is only persisted in snapshots (see `snapshot`), so protection does not survive a
+//! restart of the validator client unless a snapshot is restored.
+//!
+//! Old records may be pruned to bound the size of the history. Pruning raises the low watermarks
+//! of a validator past every pruned record, and any message at or below a watermark is refused,
+//! so no message which conflicts with a pruned record may be signed.
+use crate::signing_log::{self, Entry, SigningLog};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::Mutex;
+use tree_hash::TreeHash;
+use types::{AttestationData, Epoch, Hash256, PublicKey, Slot};
+
+/// The number of epochs of signing history retained by default when compacting.
+pub const DEFAULT_RETENTION_EPOCHS: u64 = 256;
+
+/// The reason a message was deemed unsafe to sign.
+#[derive(Debug, PartialEq, Clone)]
+pub enum NotSafe {
+    /// A different block has already been signed at this slot.
+    DoubleBlockProposal { slot: Slot },
+    /// A different attestation has already been signed with this target epoch.
+    DoubleVote { target: Epoch },
+    /// The attestation surrounds, or is surrounded by, a previously signed attestation.
+    SurroundVote {
+        previous_source: Epoch,
+        previous_target: Epoch,
+    },
+    /// The block is at or before a slot whose history has been pruned.
+    BlockBelowWatermark { watermark: Slot },
+    /// The attestation has a source before, or a target at or before, an attestation whose
+    /// history has been pruned.
+    AttestationBelowWatermark { source: Epoch, target: Epoch },
+    /// The message could not be written to the signing log, so it may not be signed.
+    LogFailed(String),
+    /// The history lock was poisoned.
+    LockPoisoned,
+}
+
+/// A record of a signed attestation.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+struct SignedAttestation {
+    source: Epoch,
+    target: Epoch,
+    root: Hash256,
+}
+
+/// The bounds at or below which messages are refused, as their conflicts may have been pruned.
+#[derive(Debug, PartialEq, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Watermark {
+    /// The latest slot of a pruned block.
+    block_slot: Option<Slot>,
+    /// The latest source epoch of a pruned attestation.
+    attestation_source: Option<Epoch>,
+    /// The latest target epoch of a pruned attestation.
+    attestation_target: Option<Epoch>,
+}
+
+impl Watermark {
+    /// Returns the greater of each bound of `self` and `other`.
+    fn merge(self, other: Self) -> Self {
+        Self {
+            block_slot: std::cmp::max(self.block_slot, other.block_slot),
+            attestation_source: std::cmp::max(self.attestation_source, other.attestation_source),
+            attestation_target: std::cmp::max(self.attestation_target, other.attestation_target),
+        }
+    }
+
+    fn check_block(&self, slot: Slot) -> Result<(), NotSafe> {
+        match self.block_slot {
+            Some(watermark) if slot <= watermark => Err(NotSafe::BlockBelowWatermark { watermark }),
+            _ => Ok(()),
+        }
+    }
+
+    /// An attestation with an earlier source may surround a pruned attestation, and one with an
+    /// earlier or equal target may be surrounded by, or double vote with, a pruned attestation.
+    fn check_attestation(&self, attestation: &SignedAttestation) -> Result<(), NotSafe> {
+        match (self.attestation_source, self.attestation_target) {
+            (Some(source), Some(target))
+                if attestation.source < source || attestation.target <= target =>
+            {
+                Err(NotSafe::AttestationBelowWatermark { source, target })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The number of records removed by pruning.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct PruneSummary {
+    pub blocks: usize,
+    pub attestations: usize,
+}
+
+/// The signing history of a single validator.
+#[derive(Debug, Default)]
+struct ValidatorHistory {
+    /// Slot -> signed root of the block signed at that slot.
+    blocks: BTreeMap<Slot, Hash256>,
+    /// All signed attestations.
+    attestations: Vec<SignedAttestation>,
+    watermark: Watermark,
+}
+
+impl ValidatorHistory {
+    /// Removes the blocks before `slot` and the attestations which target epochs before `epoch`,
+    /// raising the watermark past each of them.
+    fn prune(&mut self, slot: Slot, epoch: Epoch) -> PruneSummary {
+        let retained = self.blocks.split_off(&slot);
+        let pruned_blocks = std::mem::replace(&mut self.blocks, retained);
+        if let Some(latest) = pruned_blocks.keys().next_back() {
+            self.watermark.block_slot = std::cmp::max(self.watermark.block_slot, Some(*latest));
+        }
+
+        let (pruned_attestations, retained): (Vec<_>, Vec<_>) = self
+            .attestations
+            .drain(..)
+            .partition(|attestation| attestation.target < epoch);
+        self.attestations = retained;
+        for attestation in &pruned_attestations {
+            let watermark = &mut self.watermark;
+            watermark.attestation_source =
+                std::cmp::max(watermark.attestation_source, Some(attestation.source));
+            watermark.attestation_target =
+                std::cmp::max(watermark.attestation_target, Some(attestation.target));
+        }
+
+        PruneSummary {
+            blocks: pruned_blocks.len(),
+            attestations: pruned_attestations.len(),
+        }
+    }
+}
+
+/// The signing history of a single validator, in a form which may be persisted.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct HistoryRecord {
+    pub validator: PublicKey,
+    blocks: Vec<(Slot, Hash256)>,
+    attestations: Vec<SignedAttestation>,
+    #[serde(default)]
+    watermark: Watermark,
+}
+
+/// The latest messages signed by a single validator, and its watermark.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct Watermarks {
+    pub validator: PublicKey,
+    /// The slot of the latest block signed.
+    pub latest_block_slot: Option<Slot>,
+    /// The target epoch of the latest attestation signed.
+    pub latest_attestation_target: Option<Epoch>,
+    /// The bounds at or below which messages are refused.
+    pub pruned: Watermark,
+}
+
+/// Permission to sign a message approved by `SlashingProtection`, to be passed to
+/// `SlashingProtection::commit` once the message has been signed.
+#[must_use]
+#[derive(Debug, PartialEq)]
+pub struct SigningPermit {
+    /// The sequence number of the message in the signing log, or `None` if it was not written to
+    /// the log (e.g., it had already been recorded).
+    sequence: Option<u64>,
+}
+
+/// Stores the signing history of all validators and refuses to approve slashable messages.
+#[derive(Debug, Default)]
+pub struct SlashingProtection {
+    histories: Mutex<HashMap<PublicKey, ValidatorHistory>>,
+    /// The write-ahead log of approved messages, if any.
+    log: Option<Mutex<SigningLog>>,
+}
+
+impl SlashingProtection {
+    /// Create an instance with an empty history.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an instance with the signing history in `records`.
+    pub fn import(records: Vec<HistoryRecord>) -> Self {
+        let histories = records
+            .into_iter()
+            .map(|record| {
+                let history = ValidatorHistory {
+                    blocks: record.blocks.into_iter().collect(),
+                    attestations: record.attestations,
+                    watermark: record.watermark,
+                };
+                (record.validator, history)
+            })
+            .collect();
+
+        Self {
+            histories: Mutex::new(histories),
+            log: None,
+        }
+    }
+
+    /// Replays the signing log at `path` into the history, compacts the log and attaches it, so
+    /// that every message approved from now on is written to it before it may be signed.
+    ///
+    /// Returns the number of messages in the log whose signing may have been interrupted, all of
+    /// which are treated as signed.
+    pub fn with_log(mut self, path: &Path) -> Result<(Self, usize), signing_log::Error> {
+        let (mut log, replay) = SigningLog::open(path)?;
+
+        // The history is owned, so the lock can only have been poisoned by a previous owner.
+        let histories = self
+            .histories
+            .get_mut()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        for entry in replay.entries {
+            match entry {
+                Entry::Block {
+                    validator,
+                    slot,
+                    signed_root,
+                } => {
+                    let history = histories.entry(validator).or_default();
+                    history.blocks.entry(slot).or_insert(signed_root);
+                }
+                Entry::Attestation {
+                    validator,
+                    source,
+                    target,
+                    signed_root,
+                } => {
+                    let attestation = SignedAttestation {
+                        source,
+                        target,
+                        root: signed_root,
+                    };
+                    let history = histories.entry(validator).or_default();
+                    if !history.attestations.contains(&attestation) {
+                        history.attestations.push(attestation);
+                    }
+                }
+                Entry::Watermark {
+                    validator,
+                    watermark,
+                } => {
+                    let history = histories.entry(validator).or_default();
+                    history.watermark = history.watermark.merge(watermark);
+                }
+                Entry::Committed { .. } => {}
+            }
+        }
+
+        log.compact(log_entries(histories))?;
+        self.log = Some(Mutex::new(log));
+        Ok((self, replay.uncommitted))
+    }
+
+    /// Returns the signing history of every validator.
+    pub fn export(&self) -> Result<Vec<HistoryRecord>, NotSafe> {
+        let histories = self.histories.lock().map_err(|_| NotSafe::LockPoisoned)?;
+
+        Ok(histories
+            .iter()
+            .map(|(validator, history)| HistoryRecord {
+                validator: validator.clone(),
+                blocks: history
+                    .blocks
+                    .iter()
+                    .map(|(slot, root)| (*slot, *root))
+                    .collect(),
+                attestations: history.attestations.clone(),
+                watermark: history.watermark,
+            })
+            .collect())
+    }
+
+    /// Removes the records of blocks in epochs before `epoch` and of attestations which target
+    /// epochs before `epoch`, returning the number removed.
+    ///
+    /// Messages conflicting with removed records remain refused, as do all messages at or before
+    /// the removed records. The signing log, if any, is compacted to the remaining records.
+    pub fn prune(&self, epoch: Epoch, slots_per_epoch: u64) -> Result<PruneSummary, NotSafe> {
+        let mut histories = self.histories.lock().map_err(|_| NotSafe::LockPoisoned)?;
+        let slot = epoch.start_slot(slots_per_epoch);
+
+        let summary = histories
+            .values_mut()
+            .map(|history| history.prune(slot, epoch))
+            .fold(PruneSummary::default(), |total, pruned| PruneSummary {
+                blocks: total.blocks + pruned.blocks,
+                attestations: total.attestations + pruned.attestations,
+            });
+
+        if let Some(log) = &self.log {
+            log.lock()
+                .map_err(|_| NotSafe::LockPoisoned)?
+                .compact(log_entries(&histories))
+                .map_err(|e| NotSafe::LogFailed(e.to_string()))?;
+        }
+        Ok(summary)
+    }
+
+    /// Check that `validator` may sign a block at `slot` with the given `signed_root`, recording
+    /// it if so.
+    ///
+    /// Equivalent to `begin_sign_block` followed by `commit`, for a block which is signed
+    /// elsewhere (or not at all).
+    pub fn check_and_insert_block(
+        &self,
+        validator: &PublicKey,
+        slot: Slot,
+        signed_root: Hash256,
+    ) -> Result<(), NotSafe> {
+        self.begin_sign_block(validator, slot, signed_root)
+            .map(|permit| self.commit(permit))
+    }
+
+    /// Check that `validator` may sign `attestation`, recording it if so.
+    ///
+    /// Equivalent to `begin_sign_attestation` followed by `commit`.
+    pub fn check_and_insert_attestation(
+        &self,
+        validator: &PublicKey,
+        attestation: &AttestationData,
+    ) -> Result<(), NotSafe> {
+        self.begin_sign_attestation(validator, attestation)
+            .map(|permit| self.commit(permit))
+    }
+
+    /// Check that `validator` may sign a block at `slot` with the given `signed_root`, recording
+    /// it (durably, if a signing log is attached) and returning permission to sign it if so.
+    pub fn begin_sign_block(
+        &self,
+        validator: &PublicKey,
+        slot: Slot,
+        signed_root: Hash256,
+    ) -> Result<SigningPermit, NotSafe> {
+        let mut histories = self.histories.lock().map_err(|_| NotSafe::LockPoisoned)?;
+        let history = histories.entry(validator.clone()).or_default();
+
+        match history.blocks.get(&slot) {
+            Some(root) if *root != signed_root => Err(NotSafe::DoubleBlockProposal { slot }),
+            Some(_) => Ok(SigningPermit { sequence: None }),
+            None => {
+                history.watermark.check_block(slot)?;
+                let permit = self.write_ahead(Entry::Block {
+                    validator: validator.clone(),
+                    slot,
+                    signed_root,
+                })?;
+                history.blocks.insert(slot, signed_root);
+                Ok(permit)
+            }
+        }
+    }
+
+    /// Check that `validator` may sign `attestation`, recording it (durably, if a signing log is
+    /// attached) and returning permission to sign it if so.
+    pub fn begin_sign_attestation(
+        &self,
+        validator: &PublicKey,
+        attestation: &AttestationData,
+    ) -> Result<SigningPermit, NotSafe> {
+        let new = SignedAttestation {
+            source: attestation.source.epoch,
+            target: attestation.target.epoch,
+            root: Hash256::from_slice(&attestation.tree_hash_root()),
+        };
+
+        let mut histories = self.histories.lock().map_err(|_| NotSafe::LockPoisoned)?;
+        let history = histories.entry(validator.clone()).or_default();
+
+        for previous in &history.attestations {
+            if previous.target == new.target {
+                if previous.root == new.root {
+                    return Ok(SigningPermit { sequence: None });
+                } else {
+                    return Err(NotSafe::DoubleVote { target: new.target });
+                }
+            }
+
+            let surrounds = new.source < previous.source && previous.target < new.target;
+            let surrounded = previous.source < new.source && new.target < previous.target;
+            if surrounds || surrounded {
+                return Err(NotSafe::SurroundVote {
+                    previous_source: previous.source,
+                    previous_target: previous.target,
+                });
+            }
+        }
+
+        history.watermark.check_attestation(&new)?;
+        let permit = self.write_ahead(Entry::Attestation {
+            validator: validator.clone(),
+            source: new.source,
+            target: new.target,
+            signed_root: new.root,
+        })?;
+        history.attestations.push(new);
+        Ok(permit)
+    }
+
+    /// Records in the signing log, if any, that the message of `permit` has been signed.
+    ///
+    /// A failure to record it is ignored, since a message without a record of its signature is
+    /// treated as signed regardless.
+    pub fn commit(&self, permit: SigningPermit) {
+        if let (Some(log), Some(sequence)) = (&self.log, permit.sequence) {
+            if let Ok(mut log) = log.lock() {
+                let _ = log.commit(sequence);
+            }
+        }
+    }
+
+    /// Waits for the signing log, if any, to reach the disk, so that the signatures recorded by
+    /// `commit` are not replayed as interrupted after a restart.
+    pub fn flush(&self) -> Result<(), NotSafe> {
+        match &self.log {
+            Some(log) => log
+                .lock()
+                .map_err(|_| NotSafe::LockPoisoned)?
+                .sync()
+                .map_err(|e| NotSafe::LogFailed(e.to_string())),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes `entry` to the signing log, if any, returning permission to sign its message once
+    /// it has reached the disk.
+    fn write_ahead(&self, entry: Entry) -> Result<SigningPermit, NotSafe> {
+        let log = match &self.log {
+            Some(log) => log,
+            None => return Ok(SigningPermit { sequence: None }),
+        };
+        let sequence = log
+            .lock()
+            .map_err(|_| NotSafe::LockPoisoned)?
+            .append(entry)
+            .map_err(|e| NotSafe::LogFailed(e.to_string()))?;
+        Ok(SigningPermit {
+            sequence: Some(sequence),
+        })
+    }
+
+    /// Returns the latest messages signed by each validator, and its watermark.
+    pub fn watermarks(&self) -> Result<Vec<Watermarks>, NotSafe> {
+        let histories = self.histories.lock().map_err(|_| NotSafe::LockPoisoned)?;
+        let mut watermarks: Vec<Watermarks> = histories
+            .iter()
+            .map(|(validator, history)| Watermarks {
+                validator: validator.clone(),
+                latest_block_slot: history
+                    .blocks
+                    .keys()
+                    .next_back()
+                    .cloned()
+                    .or(history.watermark.block_slot),
+                latest_attestation_target: history
+                    .attestations
+                    .iter()
+                    .map(|attestation| attestation.target)
+                    .max()
+                    .or(history.watermark.attestation_target),
+                pruned: history.watermark,
+            })
+            .collect();
+        watermarks.sort_by_key(|watermarks| watermarks.validator.as_hex_string());
+        Ok(watermarks)
+    }
+
+    /// Returns the number of blocks recorded for `validator`.
+    pub fn num_blocks(&self, validator: &PublicKey) -> usize {
+        self.histories
+            .lock()
+            .ok()
+            .and_then(|histories| histories.get(validator).map(|h| h.blocks.len()))
+            .unwrap_or(0)
+    }
+}
+
+/// Returns the signing log entries which record `histories`.
+fn log_entries(histories: &HashMap<PublicKey, ValidatorHistory>) -> Vec<Entry> {
+    let mut entries = vec![];
+    for (validator, history) in histories {
+        if history.watermark != Watermark::default() {
+            entries.push(Entry::Watermark {
+                validator: validator.clone(),
+                watermark: history.watermark,
+            });
+        }
+        entries.extend(history.blocks.iter().map(|(slot, root)| Entry::Block {
+            validator: validator.clone(),
+            slot: *slot,
+            signed_root: *root,
+        }));
+        entries.extend(
+            history
+                .attestations
+                .iter()
+                .map(|attestation| Entry::Attestation {
+                    validator: validator.clone(),
+                    source: attestation.source,
+                    target: attestation.target,
+                    signed_root: attestation.root,
+                }),
+        );
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use types::{Checkpoint, Crosslink, Keypair};
+
+    fn attestation(source: u64, target: u64, shard: u64) -> AttestationData {
+        AttestationData {
+            beacon_block_root: Hash256::zero(),
+            source: Checkpoint {
+                epoch: Epoch::new(source),
+                root: Hash256::zero(),
+            },
+            target: Checkpoint {
+                epoch: Epoch::new(target),
+                root: Hash256::zero(),
+            },
+            crosslink: Crosslink {
+                shard,
+                ..Crosslink::default()
+            },
+        }
+    }
+
+    #[test]
+    fn double_block_proposal() {
+        let protection = SlashingProtection::new();
+        let pk = Keypair::random().pk;
+        let slot = Slot::new(3);
+
+        assert_eq!(
+            protection.check_and_insert_block(&pk, slot, Hash256::from_low_u64_be(1)),
+            Ok(())
+        );
+        assert_eq!(
+            protection.check_and_insert_block(&pk, slot, Hash256::from_low_u64_be(1)),
+            Ok(())
+        );
+        assert_eq!(
+            protection.check_and_insert_block(&pk, slot, Hash256::from_low_u64_be(2)),
+            Err(NotSafe::DoubleBlockProposal { slot })
+        );
+        assert_eq!(
+            protection.check_and_insert_block(
+                &Keypair::random().pk,
+                slot,
+                Hash256::from_low_u64_be(2)
+            ),
+            Ok(())
+        );
+        assert_eq!(protection.num_blocks(&pk), 1);
+    }
+
+    #[test]
+    fn export_and_import() {
+        let protection = SlashingProtection::new();
+        let pk = Keypair::random().pk;
+        let slot = Slot::new(3);
+
+        protection
+            .check_and_insert_block(&pk, slot, Hash256::from_low_u64_be(1))
+            .expect("should insert block");
+        protection
+            .check_and_insert_attestation(&pk, &attestation(2, 3, 0))
+            .expect("should insert attestation");
+
+        let imported = SlashingProtection::import(protection.export().expect("should export"));
+        assert_eq!(
+            imported.check_and_insert_block(&pk, slot, Hash256::from_low_u64_be(2)),
+            Err(NotSafe::DoubleBlockProposal { slot })
+        );
+        assert_eq!(
+            imported.check_and_insert_attestation(&pk, &attestation(2, 3, 1)),
+            Err(NotSafe::DoubleVote {
+                target: Epoch::new(3)
+            })
+        );
+    }
+
+    #[test]
+    fn pruning_preserves_watermarks() {
+        let protection = SlashingProtection::new();
+        let pk = Keypair::random().pk;
+        let slots_per_epoch = 8;
+
+        for (slot, root) in vec![(3, 1), (30, 2)] {
+            protection
+                .check_and_insert_block(&pk, Slot::new(slot), Hash256::from_low_u64_be(root))
+                .expect("should insert block");
+        }
+        for (source, target) in vec![(0, 1), (1, 2), (2, 3)] {
+            protection
+                .check_and_insert_attestation(&pk, &attestation(source, target, 0))
+                .expect("should insert attestation");
+        }
+
+        assert_eq!(
+            protection.prune(Epoch::new(3), slots_per_epoch),
+            Ok(PruneSummary {
+                blocks: 1,
+                attestations: 2,
+            })
+        );
+        let protection = SlashingProtection::import(protection.export().expect("should export"));
+
+        // A pruned block may not be signed again, nor may any earlier block.
+        for slot in vec![3, 2] {
+            assert_eq!(
+                protection.check_and_insert_block(
+                    &pk,
+                    Slot::new(slot),
+                    Hash256::from_low_u64_be(1)
+                ),
+                Err(NotSafe::BlockBelowWatermark {
+                    watermark: Slot::new(3)
+                })
+            );
+        }
+        assert_eq!(
+            protection.check_and_insert_block(&pk, Slot::new(4), Hash256::from_low_u64_be(1)),
+            Ok(())
+        );
+        assert_eq!(protection.num_blocks(&pk), 2);
+
+        // Votes which could conflict with the pruned votes are refused.
+        let below_watermark = Err(NotSafe::AttestationBelowWatermark {
+            source: Epoch::new(1),
+            target: Epoch::new(2),
+        });
+        assert_eq!(
+            protection.check_and_insert_attestation(&pk, &attestation(1, 2, 1)),
+            below_watermark
+        );
+        assert_eq!(
+            protection.check_and_insert_attestation(&pk, &attestation(0, 4, 0)),
+            below_watermark
+        );
+        assert_eq!(
+            protection.check_and_insert_attestation(&pk, &attestation(2, 3, 0)),
+            Ok(())
+        );
+        assert_eq!(
+            protection.check_and_insert_attestation(&pk, &attestation(3, 4, 0)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn double_and_surround_votes() {
+        let protection = SlashingProtection::new();
+        let pk = Keypair::random().pk;
+
+        assert_eq!(
+            protection.check_and_insert_attestation(&pk, &attestation(2, 3, 0)),
+            Ok(())
+        );
+        assert_eq!(
+            protection.check_and_insert_attestation(&pk, &attestation(2, 3, 0)),
+            Ok(())
+        );
+        assert_eq!(
+            protection.check_and_insert_attestation(&pk, &attestation(2, 3, 1)),
+            Err(NotSafe::DoubleVote {
+                target: Epoch::new(3)
+            })
+        );
+        assert_eq!(
+            protection.check_and_insert_attestation(&pk, &attestation(1, 4, 0)),
+            Err(NotSafe::SurroundVote {
+                previous_source: Epoch::new(2),
+                previous_target: Epoch::new(3),
+            })
+        );
+        assert_eq!(
+            protection.check_and_insert_attestation(&pk, &attestation(3, 4, 0)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn approved_messages_survive_a_crash_before_they_are_signed() {
+        let dir = TempDir::new().expect("should create temp dir");
+        let path = SigningLog::path(dir.path());
+        let pk = Keypair::random().pk;
+        let slot = Slot::new(3);
+
+        let (protection, uncommitted) = SlashingProtection::new()
+            .with_log(&path)
+            .expect("should open log");
+        assert_eq!(uncommitted, 0);
+        let permit = protection
+            .begin_sign_block(&pk, slot, Hash256::from_low_u64_be(1))
+            .expect("should approve block");
+        protection.commit(permit);
+        // The validator client stops after approving the attestation, before committing it.
+        let _permit = protection
+            .begin_sign_attestation(&pk, &attestation(2, 3, 0))
+            .expect("should approve attestation");
+        drop(protection);
+
+        // Neither message was in a snapshot, but both are refused after a restart.
+        let (protection, uncommitted) = SlashingProtection::new()
+            .with_log(&path)
+            .expect("should reopen log");
+        assert_eq!(uncommitted, 1);
+        assert_eq!(
+            protection.check_and_insert_block(&pk, slot, Hash256::from_low_u64_be(2)),
+            Err(NotSafe::DoubleBlockProposal { slot })
+        );
+        assert_eq!(
+            protection.check_and_insert_attestation(&pk, &attestation(2, 3, 1)),
+            Err(NotSafe::DoubleVote {
+                target: Epoch::new(3)
+            })
+        );
+
+        // Pruning compacts the log, which keeps the watermarks.
+        protection.prune(Epoch::new(1), 8).expect("should prune");
+        drop(protection);
+        let (protection, _) = SlashingProtection::new()
+            .with_log(&path)
+            .expect("should reopen log");
+        assert_eq!(
+            protection.check_and_insert_block(&pk, Slot::new(2), Hash256::from_low_u64_be(2)),
+            Err(NotSafe::BlockBelowWatermark { watermark: slot })
+        );
+    }
+}
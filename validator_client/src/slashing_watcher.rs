@@ -0,0 +1,144 @@
+//! Watches for the slashing of the validators of this client, so that a slashed validator stops
+//! signing immediately rather than accruing further penalties (e.g., whilst a misconfigured
+//! duplicate instance runs elsewhere).
+//!
+//! The beacon node reports which of the requested validators are slashed with their duties. Since
+//! duties are only requested once per epoch, the watcher also polls the beacon node half way
+//! through each epoch, so that a slashing is noticed within half an epoch without requesting the
+//! duties of every validator in every slot. Once a validator is reported as slashed:
+//!
+//! - It is no longer signed for, regardless of its validator state.
+//! - Its validator state is saved as disabled (see `validator_state`), so that it remains disabled
+//!   after a restart until the operator enables it again.
+//! - Operators are notified (see `notifier`).
+use crate::duties::BeaconNodeDuties;
+use crate::notifier::Notifier;
+use crate::validator_state::ValidatorState;
+use futures::Future;
+use slog::{crit, error, warn};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use types::{Epoch, PublicKey};
+
+/// Records the slashing of validators, disabling them and notifying operators.
+pub struct SlashingWatcher {
+    /// The directory of the validators, containing the state of each.
+    validators_dir: PathBuf,
+    notifier: Arc<Notifier>,
+    /// The validators which the beacon node has reported as slashed.
+    slashed: RwLock<HashSet<PublicKey>>,
+    log: slog::Logger,
+}
+
+impl SlashingWatcher {
+    pub fn new(validators_dir: PathBuf, notifier: Arc<Notifier>, log: slog::Logger) -> Self {
+        Self {
+            validators_dir,
+            notifier,
+            slashed: RwLock::new(HashSet::new()),
+            log,
+        }
+    }
+
+    /// Returns `true` if `validator` has been reported as slashed, and so must not sign.
+    ///
+    /// A poisoned lock is treated as slashed, since the validator may have been.
+    pub fn is_slashed(&self, validator: &PublicKey) -> bool {
+        self.slashed
+            .read()
+            .map_or(true, |slashed| slashed.contains(validator))
+    }
+
+    /// Records that the beacon node reports each of `validators` as slashed, disabling those not
+    /// already known to have been slashed and notifying operators of them.
+    pub fn validators_slashed(&self, validators: &[PublicKey]) {
+        let newly_slashed = match self.slashed.write() {
+            Ok(mut slashed) => validators
+                .iter()
+                .filter(|validator| slashed.insert((*validator).clone()))
+                .cloned()
+                .collect::<Vec<_>>(),
+            Err(_) => {
+                error!(self.log, "Unable to record slashed validators"; "error" => "lock poisoned");
+                validators.to_vec()
+            }
+        };
+        if newly_slashed.is_empty() {
+            return;
+        }
+
+        for validator in &newly_slashed {
+            let validator_id = validator.concatenated_hex_id();
+            crit!(self.log, "Validator slashed, signing stopped"; "validator" => &validator_id);
+
+            // Signing has already stopped, so failing to save the state only affects restarts.
+            if let Err(e) =
+                (ValidatorState { enabled: false }).save(&self.validators_dir, &validator_id)
+            {
+                error!(
+                    self.log,
+                    "Unable to disable slashed validator";
+                    "validator" => validator_id,
+                    "error" => format!("{:?}", e),
+                );
+            }
+        }
+        self.notifier.validators_slashed(&newly_slashed);
+    }
+
+    /// Requests which of `pub_keys` are slashed in `epoch` from `beacon_node`, and records them.
+    ///
+    /// The returned future completes once the request has completed (or failed).
+    pub fn poll<U: BeaconNodeDuties>(
+        self: Arc<Self>,
+        beacon_node: &U,
+        epoch: Epoch,
+        pub_keys: &[PublicKey],
+    ) -> Box<dyn Future<Item = (), Error = ()> + Send> {
+        let log = self.log.clone();
+        Box::new(
+            beacon_node
+                .request_duties(epoch, pub_keys)
+                .map(move |response| self.validators_slashed(&response.slashed))
+                .map_err(move |e| {
+                    warn!(log, "Unable to poll for slashed validators"; "error" => e.to_string())
+                }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+    use types::Keypair;
+
+    #[test]
+    fn disables_slashed_validators() {
+        let dir = TempDir::new().expect("should create temp dir");
+        let slashed = Keypair::random().pk;
+        let unknown = Keypair::random().pk;
+        let other = Keypair::random().pk;
+        fs::create_dir(dir.path().join(slashed.concatenated_hex_id()))
+            .expect("should create validator dir");
+
+        let watcher = SlashingWatcher::new(
+            dir.path().to_path_buf(),
+            Arc::new(Notifier::disabled()),
+            slog::Logger::root(slog::Discard, slog::o!()),
+        );
+        watcher.validators_slashed(&[slashed.clone(), unknown.clone()]);
+
+        assert!(watcher.is_slashed(&slashed));
+        assert!(!watcher.is_slashed(&other));
+        assert_eq!(
+            ValidatorState::load(dir.path(), &slashed.concatenated_hex_id())
+                .expect("should load state"),
+            ValidatorState { enabled: false }
+        );
+        // A validator whose state cannot be saved still stops signing.
+        assert!(watcher.is_slashed(&unknown));
+    }
+}
@@ -0,0 +1,137 @@
+//! Tracks the slots processed by the service, so that every slot from the genesis slot onwards
+//! is processed at most once.
+//!
+//! No slot has been processed until the first slot timer fires, so the genesis slot (slot 0) is
+//! processed like any other if the validator client is started before genesis, rather than being
+//! mistaken for an already processed slot.
+use std::time::Duration;
+use types::Slot;
+
+/// How long before genesis (by the system clock) the timer of the genesis slot may fire, and still
+/// process the genesis slot. Timers may fire a moment early if the system clock has drifted.
+pub const GENESIS_TOLERANCE: Duration = Duration::from_millis(500);
+
+/// Why a slot was not processed.
+#[derive(Debug, PartialEq)]
+pub enum Refused {
+    /// The slot is before the genesis slot.
+    BeforeGenesis,
+    /// The slot is not after the last processed slot.
+    Duplicate { last_processed: Slot },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SlotTracker {
+    genesis_slot: Slot,
+    /// The last slot processed, or `None` if none has been.
+    last_processed: Option<Slot>,
+}
+
+impl SlotTracker {
+    pub fn new(genesis_slot: Slot) -> Self {
+        Self {
+            genesis_slot,
+            last_processed: None,
+        }
+    }
+
+    pub fn genesis_slot(&self) -> Slot {
+        self.genesis_slot
+    }
+
+    /// Returns the last slot processed, or `None` if none has been.
+    pub fn last_processed(&self) -> Option<Slot> {
+        self.last_processed
+    }
+
+    /// Returns the slot whose timer has fired, given the `present_slot` of the slot clock and the
+    /// duration `to_genesis`, if genesis has yet to start.
+    ///
+    /// This is the present slot or, if the timer fired at most `GENESIS_TOLERANCE` before
+    /// genesis, the genesis slot. Returns `None` before genesis.
+    pub fn fired_slot(
+        &self,
+        present_slot: Option<Slot>,
+        to_genesis: Option<Duration>,
+    ) -> Option<Slot> {
+        match (present_slot, to_genesis) {
+            (Some(slot), _) => Some(slot),
+            (None, Some(to_genesis)) if to_genesis <= GENESIS_TOLERANCE => Some(self.genesis_slot),
+            (None, _) => None,
+        }
+    }
+
+    /// Records `slot` as processed, unless it is before genesis or has already been processed.
+    pub fn process(&mut self, slot: Slot) -> Result<(), Refused> {
+        if slot < self.genesis_slot {
+            return Err(Refused::BeforeGenesis);
+        }
+        match self.last_processed {
+            Some(last_processed) if slot <= last_processed => {
+                Err(Refused::Duplicate { last_processed })
+            }
+            _ => {
+                self.last_processed = Some(slot);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn processes_the_genesis_slot() {
+        let mut tracker = SlotTracker::new(Slot::new(0));
+        assert_eq!(tracker.last_processed(), None);
+        assert_eq!(tracker.process(Slot::new(0)), Ok(()));
+        assert_eq!(tracker.last_processed(), Some(Slot::new(0)));
+        assert_eq!(
+            tracker.process(Slot::new(0)),
+            Err(Refused::Duplicate {
+                last_processed: Slot::new(0)
+            })
+        );
+
+        // the last slot of epoch 0 and the first of epoch 1 (of the minimal spec) follow in turn
+        assert_eq!(tracker.process(Slot::new(7)), Ok(()));
+        assert_eq!(tracker.process(Slot::new(8)), Ok(()));
+        assert_eq!(
+            tracker.process(Slot::new(7)),
+            Err(Refused::Duplicate {
+                last_processed: Slot::new(8)
+            })
+        );
+    }
+
+    #[test]
+    fn refuses_slots_before_a_later_genesis() {
+        let mut tracker = SlotTracker::new(Slot::new(64));
+        assert_eq!(tracker.process(Slot::new(0)), Err(Refused::BeforeGenesis));
+        assert_eq!(tracker.process(Slot::new(63)), Err(Refused::BeforeGenesis));
+        assert_eq!(tracker.last_processed(), None);
+        assert_eq!(tracker.process(Slot::new(64)), Ok(()));
+    }
+
+    #[test]
+    fn fires_the_genesis_slot_just_before_genesis() {
+        let tracker = SlotTracker::new(Slot::new(0));
+        let millis = Duration::from_millis;
+        assert_eq!(
+            tracker.fired_slot(Some(Slot::new(3)), None),
+            Some(Slot::new(3))
+        );
+        assert_eq!(
+            tracker.fired_slot(None, Some(millis(10))),
+            Some(Slot::new(0))
+        );
+        assert_eq!(
+            tracker.fired_slot(None, Some(GENESIS_TOLERANCE)),
+            Some(Slot::new(0))
+        );
+        assert_eq!(tracker.fired_slot(None, Some(millis(10_000))), None);
+        assert_eq!(tracker.fired_slot(None, None), None);
+    }
+}
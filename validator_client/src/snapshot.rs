@@ -0,0 +1,200 @@
+//! Snapshots of the runtime state of the validator client, which may be restored on startup to
+//! recover quickly and safely from a crash.
+//!
+//! A snapshot contains the known duties, the signing history of every validator and the last
+//! slots processed. It is written to `<snapshot-dir>/snapshot.json` at every slot, so it may lag
+//! behind messages signed during the slot in which the validator client stopped.
+use crate::duties::{DutiesStore, DutiesStoreError, EpochDuties, EpochDuty};
+use crate::slashing_protection::{HistoryRecord, PruneSummary, SlashingProtection};
+use serde_derive::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use types::{Epoch, PublicKey, Slot};
+
+/// The name of the snapshot file within the snapshot directory.
+pub const SNAPSHOT_FILENAME: &str = "snapshot.json";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("unable to read or write the snapshot: {0}")]
+    Io(#[from] io::Error),
+    #[error("invalid snapshot: {0}")]
+    Invalid(#[from] serde_json::Error),
+    #[error("a lock was poisoned whilst taking the snapshot")]
+    LockPoisoned,
+    #[error("unable to read the duties: {0}")]
+    Duties(#[from] DutiesStoreError),
+}
+
+/// The duties of every validator during `epoch`.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct EpochDutiesSnapshot {
+    pub epoch: Epoch,
+    pub duties: Vec<(PublicKey, Option<EpochDuty>)>,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// The latest slot processed.
+    pub current_slot: Slot,
+    /// The latest slot in which attestations were produced.
+    pub attested_slot: Option<Slot>,
+    /// The duties of the current and any later epochs.
+    pub duties: Vec<EpochDutiesSnapshot>,
+    pub slashing_protection: Vec<HistoryRecord>,
+}
+
+impl Snapshot {
+    /// Takes a snapshot of the given state, omitting the duties of epochs prior to that of
+    /// `current_slot`.
+    pub fn new(
+        current_slot: Slot,
+        attested_slot: Option<Slot>,
+        duties_store: &DutiesStore,
+        slashing_protection: &SlashingProtection,
+    ) -> Result<Self, Error> {
+        let current_epoch = current_slot.epoch(duties_store.slots_per_epoch());
+        let mut duties = vec![];
+        for epoch in duties_store.epochs()? {
+            if epoch < current_epoch {
+                continue;
+            }
+            if let Some(epoch_duties) = duties_store.epoch_duties(epoch)? {
+                duties.push(EpochDutiesSnapshot {
+                    epoch,
+                    duties: epoch_duties.into_iter().collect(),
+                });
+            }
+        }
+
+        Ok(Self {
+            current_slot,
+            attested_slot,
+            duties,
+            slashing_protection: slashing_protection
+                .export()
+                .map_err(|_| Error::LockPoisoned)?,
+        })
+    }
+
+    /// Returns the path of the snapshot file in `dir`.
+    pub fn path(dir: &Path) -> PathBuf {
+        dir.join(SNAPSHOT_FILENAME)
+    }
+
+    /// Reads the snapshot in `dir`, if there is one.
+    pub fn load(dir: &Path) -> Result<Option<Self>, Error> {
+        match File::open(Self::path(dir)) {
+            Ok(file) => Ok(Some(serde_json::from_reader(file)?)),
+            Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Writes the snapshot to `dir`, creating it if required.
+    pub fn save(&self, dir: &Path) -> Result<(), Error> {
+        fs::create_dir_all(dir)?;
+
+        // Write to a temporary file and rename, so the snapshot is never partially written.
+        let path = Self::path(dir);
+        let temp_path = path.with_extension("json.tmp");
+        serde_json::to_writer(File::create(&temp_path)?, self)?;
+        fs::rename(&temp_path, &path)?;
+        Ok(())
+    }
+
+    /// Prunes the signing history of epochs more than `retention` epochs before that of
+    /// `current_slot`, returning the number of records removed.
+    pub fn prune_slashing_protection(
+        &mut self,
+        retention: u64,
+        slots_per_epoch: u64,
+    ) -> Result<PruneSummary, Error> {
+        let current_epoch = self.current_slot.epoch(slots_per_epoch);
+        if current_epoch.as_u64() <= retention {
+            return Ok(PruneSummary::default());
+        }
+
+        let records = std::mem::replace(&mut self.slashing_protection, vec![]);
+        let slashing_protection = SlashingProtection::import(records);
+        let pruned = slashing_protection
+            .prune(current_epoch - retention, slots_per_epoch)
+            .map_err(|_| Error::LockPoisoned)?;
+        self.slashing_protection = slashing_protection
+            .export()
+            .map_err(|_| Error::LockPoisoned)?;
+        Ok(pruned)
+    }
+
+    /// Returns the duties in the snapshot.
+    pub fn duties_store(&self, slots_per_epoch: u64) -> Result<DutiesStore, Error> {
+        let duties_store = DutiesStore::new(slots_per_epoch);
+        for snapshot in &self.duties {
+            let duties: EpochDuties = snapshot.duties.iter().cloned().collect();
+            // Snapshots do not record dependent roots, so the restored duties are compared with
+            // those next obtained from the beacon node.
+            duties_store.insert(snapshot.epoch, duties, None)?;
+        }
+        Ok(duties_store)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use types::{AttestationDuty, Hash256, Keypair};
+
+    #[test]
+    fn save_and_restore() {
+        let dir = TempDir::new().expect("should create temp dir");
+        let slots_per_epoch = 8;
+        let pubkey = Keypair::random().pk;
+
+        let duty = EpochDuty {
+            block_production_slots: vec![Slot::new(17)],
+            attestation_duty: AttestationDuty::default(),
+        };
+        let duties_store = DutiesStore::new(slots_per_epoch);
+        for epoch in 1..4 {
+            let mut duties = EpochDuties::new();
+            duties.insert(pubkey.clone(), Some(duty.clone()));
+            duties_store
+                .insert(Epoch::new(epoch), duties, None)
+                .expect("should insert duties");
+        }
+
+        let slashing_protection = SlashingProtection::new();
+        slashing_protection
+            .check_and_insert_block(&pubkey, Slot::new(17), Hash256::from_low_u64_be(1))
+            .expect("should insert block");
+
+        assert_eq!(Snapshot::load(dir.path()).expect("should load"), None);
+
+        let snapshot = Snapshot::new(
+            Slot::new(17),
+            Some(Slot::new(16)),
+            &duties_store,
+            &slashing_protection,
+        )
+        .expect("should take snapshot");
+        snapshot.save(dir.path()).expect("should save");
+
+        let restored = Snapshot::load(dir.path())
+            .expect("should load")
+            .expect("should have a snapshot");
+        assert_eq!(restored, snapshot);
+
+        // Duties prior to the current epoch are not restored.
+        let epochs = restored
+            .duties_store(slots_per_epoch)
+            .expect("should restore duties")
+            .epochs();
+        assert_eq!(epochs, Ok(vec![Epoch::new(2), Epoch::new(3)]));
+
+        let restored_protection = SlashingProtection::import(restored.slashing_protection);
+        assert_eq!(restored_protection.num_blocks(&pubkey), 1);
+    }
+}
@@ -0,0 +1,102 @@
+//! The operator-controlled state of each validator, which may be changed whilst the validator
+//! client is running.
+//!
+//! Each validator may have a state file at
+//! `<datadir>/<network>/validators/<validator_id>/validator_state.json`. The file is read before
+//! every duty, so a validator may be disabled (e.g., whilst migrating it to another machine)
+//! without restarting the validator client or affecting other validators. Validators without a
+//! state file are enabled.
+use serde_derive::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// The name of the state file within each validator directory.
+pub const VALIDATOR_STATE_FILENAME: &str = "validator_state.json";
+
+#[derive(Debug)]
+pub enum Error {
+    /// There is no directory for the validator.
+    UnknownValidator(PathBuf),
+    /// The state file could not be read or written.
+    Io(std::io::Error),
+    /// The state file is invalid.
+    InvalidFile(serde_json::Error),
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct ValidatorState {
+    /// If `false`, the validator does not sign any blocks or attestations.
+    pub enabled: bool,
+}
+
+impl Default for ValidatorState {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+impl ValidatorState {
+    /// Returns the path of the state file for the validator with `validator_id` in
+    /// `validators_dir`.
+    pub fn path(validators_dir: &Path, validator_id: &str) -> PathBuf {
+        validators_dir
+            .join(validator_id)
+            .join(VALIDATOR_STATE_FILENAME)
+    }
+
+    /// Reads the state of the validator with `validator_id`, which is the default state if it has
+    /// no state file.
+    pub fn load(validators_dir: &Path, validator_id: &str) -> Result<Self, Error> {
+        match File::open(Self::path(validators_dir, validator_id)) {
+            Ok(file) => serde_json::from_reader(file).map_err(Error::InvalidFile),
+            Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(Error::Io(e)),
+        }
+    }
+
+    /// Writes the state of the validator with `validator_id`, which must have a validator
+    /// directory.
+    pub fn save(&self, validators_dir: &Path, validator_id: &str) -> Result<(), Error> {
+        let validator_dir = validators_dir.join(validator_id);
+        if !validator_dir.is_dir() {
+            return Err(Error::UnknownValidator(validator_dir));
+        }
+
+        // Write to a temporary file and rename, so a running validator client never reads a
+        // partially-written file.
+        let path = Self::path(validators_dir, validator_id);
+        let temp_path = path.with_extension("json.tmp");
+        let file = File::create(&temp_path).map_err(Error::Io)?;
+        serde_json::to_writer(file, self).map_err(Error::InvalidFile)?;
+        fs::rename(&temp_path, &path).map_err(Error::Io)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn save_and_load() {
+        let dir = TempDir::new().expect("should create temp dir");
+        let data_dir = dir.path();
+
+        assert!(ValidatorState::default().save(data_dir, "0x1234").is_err());
+
+        fs::create_dir(data_dir.join("0x1234")).expect("should create validator dir");
+        assert_eq!(
+            ValidatorState::load(data_dir, "0x1234").unwrap(),
+            ValidatorState { enabled: true }
+        );
+
+        ValidatorState { enabled: false }
+            .save(data_dir, "0x1234")
+            .expect("should save state");
+        assert_eq!(
+            ValidatorState::load(data_dir, "0x1234").unwrap(),
+            ValidatorState { enabled: false }
+        );
+    }
+}
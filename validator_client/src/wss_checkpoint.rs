@@ -0,0 +1,120 @@
+//! Checks the beacon node against a weak subjectivity checkpoint, a block root known (out of band)
+//! to be in the canonical chain at the start of an epoch.
+//!
+//! A beacon node which has finalized the epoch of the checkpoint with another block root is on a
+//! chain the validators must never sign on (such as that of a long-range attack), and is refused.
+//! A beacon node which has not yet finalized the epoch cannot conflict with the checkpoint, but no
+//! more is known of its chain.
+use serde_derive::Deserialize;
+use thiserror::Error;
+use types::{Checkpoint, Epoch, Hash256};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The beacon node was unable to report its block root at the checkpoint.
+    #[error("unable to query the beacon node for its checkpoint root: {0}")]
+    Query(String),
+    /// The finalized chain of the beacon node has another block root at the checkpoint.
+    #[error(
+        "the finalized chain of the beacon node has block {found:?} at epoch {epoch}, \
+         but the checkpoint is {expected:?}"
+    )]
+    Conflict {
+        epoch: Epoch,
+        expected: Hash256,
+        found: Option<Hash256>,
+    },
+}
+
+/// The outcome of a successful `check`.
+#[derive(Debug, PartialEq)]
+pub enum Check {
+    /// The finalized chain of the beacon node includes the checkpoint.
+    Verified,
+    /// The beacon node has not finalized the epoch of the checkpoint, and has `root` at it (if
+    /// any).
+    Unfinalized {
+        finalized_epoch: Epoch,
+        root: Option<Hash256>,
+    },
+}
+
+/// The canonical chain of a beacon node at the epoch of a checkpoint.
+#[derive(Debug, PartialEq, Clone, Deserialize)]
+pub struct CheckpointRoot {
+    /// The root of the canonical block at the start of the epoch, or `None` if the node has none.
+    pub root: Option<Hash256>,
+    /// The finalized epoch of the node.
+    pub finalized_epoch: Epoch,
+}
+
+/// Parses a checkpoint of the form `0x<root>:<epoch>`.
+pub fn parse(checkpoint: &str) -> Result<Checkpoint, &'static str> {
+    let mut parts = checkpoint.splitn(2, ':');
+    let root = parts.next().unwrap_or_default();
+    let epoch = parts
+        .next()
+        .ok_or("wss-checkpoint must be of the form ROOT:EPOCH")?;
+
+    let root = hex::decode(root.trim_start_matches("0x"))
+        .map_err(|_| "wss-checkpoint root is not valid hex")?;
+    if root.len() != 32 {
+        return Err("wss-checkpoint root must be 32 bytes");
+    }
+    let epoch = epoch
+        .parse::<u64>()
+        .map_err(|_| "wss-checkpoint epoch is not a valid integer")?;
+
+    Ok(Checkpoint {
+        epoch: Epoch::new(epoch),
+        root: Hash256::from_slice(&root),
+    })
+}
+
+/// Compares `checkpoint` with the chain of a beacon node.
+pub fn check(checkpoint: &Checkpoint, node: &CheckpointRoot) -> Result<Check, Error> {
+    if node.finalized_epoch < checkpoint.epoch {
+        return Ok(Check::Unfinalized {
+            finalized_epoch: node.finalized_epoch,
+            root: node.root,
+        });
+    }
+
+    if node.root == Some(checkpoint.root) {
+        Ok(Check::Verified)
+    } else {
+        Err(Error::Conflict {
+            epoch: checkpoint.epoch,
+            expected: checkpoint.root,
+            found: node.root,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refuses_finalized_chains_without_the_checkpoint() {
+        let checkpoint = parse(&format!("{:?}:10", Hash256::from_low_u64_be(1))).unwrap();
+        assert_eq!(checkpoint.epoch, Epoch::new(10));
+        assert_eq!(checkpoint.root, Hash256::from_low_u64_be(1));
+        assert!(parse("0x01:10").is_err());
+        assert!(parse(&format!("{:?}", Hash256::zero())).is_err());
+
+        let node = |root: u64, finalized_epoch: u64| CheckpointRoot {
+            root: Some(Hash256::from_low_u64_be(root)),
+            finalized_epoch: Epoch::new(finalized_epoch),
+        };
+        assert_eq!(check(&checkpoint, &node(1, 12)).ok(), Some(Check::Verified));
+        assert!(check(&checkpoint, &node(2, 10)).is_err());
+        assert_eq!(
+            check(&checkpoint, &node(2, 9)).ok(),
+            Some(Check::Unfinalized {
+                finalized_epoch: Epoch::new(9),
+                root: Some(Hash256::from_low_u64_be(2)),
+            })
+        );
+    }
+}